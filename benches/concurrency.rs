@@ -0,0 +1,80 @@
+//! Throughput of concurrent clients hammering the key space.
+//!
+//! Runs a server in process and measures the number of GET/SET per second served to a growing
+//! number of connections, each client working on its own keys, for runtimes with a growing
+//! number of worker threads. Run with `cargo bench --bench concurrency`.
+
+use redust::{client, server};
+
+use bytes::Bytes;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio::runtime;
+
+/// How long each configuration is measured
+const RUN_TIME: Duration = Duration::from_secs(3);
+
+/// Keys written by each client
+const KEYS_PER_CLIENT: usize = 1000;
+
+/// Share of the operations which are reads, in percent
+const READ_RATIO: usize = 80;
+
+fn main() {
+    println!("{:>8} {:>8} {:>12}", "threads", "clients", "ops/s");
+    for &threads in &[1, 2, 4, 8] {
+        // the clients share the runtime of the server, as they would the CPUs of the host
+        let runtime = runtime::Builder::new_multi_thread()
+            .worker_threads(threads)
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut server = server::Builder::new()
+                .worker_threads(threads)
+                .start(listener)
+                .unwrap();
+
+            for &clients in &[1, 4, 16, 64] {
+                let ops_per_sec = measure(server.local_addr(), clients).await;
+                println!("{:>8} {:>8} {:>12.0}", threads, clients, ops_per_sec);
+            }
+
+            server.shutdown();
+            server.join().await.unwrap();
+        });
+    }
+}
+/// Run `clients` concurrent clients for `RUN_TIME`, returns the total number of operations per
+/// second
+async fn measure(addr: std::net::SocketAddr, clients: usize) -> f64 {
+    let start = Instant::now();
+
+    let tasks: Vec<_> = (0..clients)
+        .map(|id| {
+            tokio::spawn(async move {
+                let mut client = client::connect(addr).await.unwrap();
+                let value = Bytes::from_static(b"value");
+                let mut ops = 0;
+
+                while start.elapsed() < RUN_TIME {
+                    let key = format!("client-{}:key-{}", id, ops % KEYS_PER_CLIENT);
+                    if ops % 100 < READ_RATIO {
+                        client.get::<Option<Bytes>>(&key).await.unwrap();
+                    } else {
+                        client.set(&key, value.clone()).await.unwrap();
+                    }
+                    ops += 1;
+                }
+                ops
+            })
+        })
+        .collect();
+
+    let mut total = 0;
+    for task in tasks {
+        total += task.await.unwrap();
+    }
+    total as f64 / start.elapsed().as_secs_f64()
+}
@@ -0,0 +1,19 @@
+//! Feed arbitrary bytes to `Frame::parse_partial`, run with `cargo fuzz run parse_partial`.
+//!
+//! Besides not panicking, a parsed frame must encode to bytes which parse back whole, and to the
+//! same encoding.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use redust::Frame;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((frame, len)) = Frame::parse_partial(data) {
+        assert!(len <= data.len());
+
+        let encoded = frame.to_bytes();
+        let (reparsed, reparsed_len) = Frame::parse_partial(&encoded).expect("encoded frame parses");
+        assert_eq!(reparsed_len, encoded.len());
+        assert_eq!(reparsed.to_bytes(), encoded);
+    }
+});
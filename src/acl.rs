@@ -0,0 +1,303 @@
+//! Users and the commands and keys they are allowed to access, see `ACL` and `AUTH`.
+//!
+//! Commands are grouped in categories: `read`, `write`, `admin` and `pubsub`. A user is granted
+//! categories and glob patterns of the keys it may access. Connection commands such as `PING` or
+//! `ACL WHOAMI` belong to no category and are allowed to every authenticated user.
+//!
+//! The `default` user always exists, connections start authenticated as it unless the server
+//! requires a password. Its password is the one set by `requirepass`, the other users get theirs
+//! from `ACL SETUSER`. Passwords are only kept as their SHA-256 digest and are never shown back.
+
+use crate::glob;
+
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+/// User new connections are authenticated as, and `AUTH password` authenticates
+pub(crate) const DEFAULT_USER: &str = "default";
+
+/// Group of commands a user is granted at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Category {
+    /// Reads the key space
+    Read,
+    /// Changes the key space
+    Write,
+    /// Inspects or changes the server itself
+    Admin,
+    /// Publishes or subscribes to channels
+    Pubsub,
+}
+
+const CATEGORIES: [Category; 4] = [Category::Read, Category::Write, Category::Admin, Category::Pubsub];
+
+impl Category {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Category::Read => "read",
+            Category::Write => "write",
+            Category::Admin => "admin",
+            Category::Pubsub => "pubsub",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    fn from_name(name: &str) -> Option<Category> {
+        CATEGORIES.iter().copied().find(|category| category.name().eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct User {
+    /// A disabled user can't authenticate, the connections already authenticated as it can no
+    /// longer run commands
+    pub(crate) enabled: bool,
+    /// Whether any password is accepted
+    pub(crate) nopass: bool,
+    /// SHA-256 digests of the passwords
+    passwords: Vec<PasswordHash>,
+    /// Categories granted, one bit per `Category`
+    categories: u8,
+    /// Glob patterns of the keys the user may access
+    pub(crate) key_patterns: Vec<String>,
+}
+
+type PasswordHash = [u8; 32];
+
+#[derive(Debug)]
+pub(crate) struct Acl {
+    users: RwLock<BTreeMap<String, User>>,
+}
+
+impl Default for Acl {
+    fn default() -> Acl {
+        let default_user = User {
+            enabled: true,
+            nopass: true,
+            passwords: vec![],
+            categories: CATEGORIES.iter().fold(0, |bits, category| bits | category.bit()),
+            key_patterns: vec!["*".to_string()],
+        };
+        let mut users = BTreeMap::new();
+        users.insert(DEFAULT_USER.to_string(), default_user);
+        Acl {
+            users: RwLock::new(users),
+        }
+    }
+}
+
+impl User {
+    /// A user created by `ACL SETUSER`, disabled and without any permission until rules grant them
+    fn new() -> User {
+        User {
+            enabled: false,
+            nopass: false,
+            passwords: vec![],
+            categories: 0,
+            key_patterns: vec![],
+        }
+    }
+
+    /// Number of passwords the user can authenticate with
+    pub(crate) fn password_count(&self) -> usize {
+        self.passwords.len()
+    }
+
+    pub(crate) fn categories(&self) -> impl Iterator<Item = Category> + '_ {
+        CATEGORIES.iter().copied().filter(move |category| self.categories & category.bit() != 0)
+    }
+
+    /// The rules recreating the user, passwords left out, as `ACL LIST` shows them
+    pub(crate) fn describe(&self, name: &str) -> String {
+        let mut rules = vec![format!("user {}", name)];
+        rules.push(if self.enabled { "on" } else { "off" }.to_string());
+        if self.nopass {
+            rules.push("nopass".to_string());
+        }
+        rules.extend(self.key_patterns.iter().map(|pattern| format!("~{}", pattern)));
+        rules.extend(self.categories().map(|category| format!("+@{}", category.name())));
+        rules.join(" ")
+    }
+
+    fn apply_rule(&mut self, rule: &str) -> Result<(), String> {
+        match rule {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "allkeys" => self.key_patterns = vec!["*".to_string()],
+            "resetkeys" => self.key_patterns.clear(),
+            "allcommands" | "+@all" => {
+                self.categories = CATEGORIES.iter().fold(0, |bits, category| bits | category.bit())
+            }
+            "nocommands" | "-@all" => self.categories = 0,
+            "reset" => *self = User::new(),
+            _ => {
+                if let Some(password) = rule.strip_prefix('>') {
+                    self.nopass = false;
+                    let hash = hash_password(password);
+                    if !self.passwords.contains(&hash) {
+                        self.passwords.push(hash);
+                    }
+                } else if let Some(password) = rule.strip_prefix('<') {
+                    let hash = hash_password(password);
+                    let before = self.passwords.len();
+                    self.passwords.retain(|known| *known != hash);
+                    if self.passwords.len() == before {
+                        return Err("no such password".to_string());
+                    }
+                } else if let Some(pattern) = rule.strip_prefix('~') {
+                    self.key_patterns.push(pattern.to_string());
+                } else if let Some(name) = rule.strip_prefix("+@") {
+                    self.categories |= category(name)?.bit();
+                } else if let Some(name) = rule.strip_prefix("-@") {
+                    self.categories &= !category(name)?.bit();
+                } else {
+                    return Err("Syntax error".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn category(name: &str) -> Result<Category, String> {
+    Category::from_name(name).ok_or_else(|| "Unknown command category".to_string())
+}
+
+impl Acl {
+    /// Whether `username` can authenticate with `password`. The password of the default user is
+    /// `requirepass`, any is accepted when it isn't set.
+    pub(crate) fn authenticate(&self, username: &str, password: &str, requirepass: Option<&str>) -> bool {
+        let users = self.users.read().unwrap();
+        match users.get(username) {
+            Some(user) if !user.enabled => false,
+            Some(_) if username == DEFAULT_USER => {
+                requirepass.is_none_or(|expected| same_hash(&hash_password(expected), &hash_password(password)))
+            }
+            Some(user) => {
+                let hash = hash_password(password);
+                user.nopass || user.passwords.iter().any(|known| same_hash(known, &hash))
+            }
+            None => false,
+        }
+    }
+
+    /// Whether `username` may run a command of `category` on `keys`, the error to reply otherwise.
+    /// `keys` is `None` when they aren't known, only the users allowed every key may run the
+    /// command then.
+    pub(crate) fn check(
+        &self,
+        username: &str,
+        command: &str,
+        category: Category,
+        keys: Option<&[&str]>,
+    ) -> Result<(), String> {
+        let users = self.users.read().unwrap();
+        let user = match users.get(username) {
+            Some(user) if user.enabled => user,
+            _ => return Err(format!("NOPERM User {} is disabled or was deleted", username)),
+        };
+
+        if user.categories & category.bit() == 0 {
+            return Err(format!(
+                "NOPERM User {} has no permissions to run the '{}' command",
+                username, command
+            ));
+        }
+        let allowed = match keys {
+            Some(keys) => keys.iter().all(|key| {
+                user.key_patterns
+                    .iter()
+                    .any(|pattern| glob::matches(pattern.as_bytes(), key.as_bytes()))
+            }),
+            None => user.key_patterns.iter().any(|pattern| pattern == "*"),
+        };
+        if !allowed {
+            return Err("NOPERM No permissions to access a key".to_string());
+        }
+        Ok(())
+    }
+
+    /// Create `username` if it doesn't exist, then apply `rules` in order. Either every rule is
+    /// applied or none.
+    pub(crate) fn set_user(&self, username: &str, rules: &[String]) -> Result<(), String> {
+        let mut users = self.users.write().unwrap();
+        let mut user = users.get(username).cloned().unwrap_or_else(User::new);
+
+        for rule in rules {
+            let is_password_rule = rule.starts_with('>')
+                || rule.starts_with('<')
+                || rule.eq_ignore_ascii_case("nopass")
+                || rule.eq_ignore_ascii_case("resetpass")
+                || rule.eq_ignore_ascii_case("reset");
+            if username == DEFAULT_USER && is_password_rule {
+                return Err(format!(
+                    "ERR Error in ACL SETUSER modifier '{}': the password of the default user is set with CONFIG SET requirepass",
+                    rule
+                ));
+            }
+
+            // passwords are case sensitive, the other rules aren't
+            let normalized = match rule.chars().next() {
+                Some('>') | Some('<') | Some('~') => rule.clone(),
+                _ => rule.to_lowercase(),
+            };
+            user.apply_rule(&normalized)
+                .map_err(|reason| format!("ERR Error in ACL SETUSER modifier '{}': {}", rule, reason))?;
+        }
+
+        users.insert(username.to_string(), user);
+        Ok(())
+    }
+
+    /// `username` as it currently is, the default user has a password if `requirepass` is set
+    pub(crate) fn get_user(&self, username: &str, requirepass: Option<&str>) -> Option<User> {
+        let users = self.users.read().unwrap();
+        users.get(username).map(|user| effective(username, user, requirepass))
+    }
+
+    /// Every user along with its rules, sorted by name
+    pub(crate) fn list(&self, requirepass: Option<&str>) -> Vec<String> {
+        let users = self.users.read().unwrap();
+        users
+            .iter()
+            .map(|(name, user)| effective(name, user, requirepass).describe(name))
+            .collect()
+    }
+
+    /// Whether a new connection starts authenticated as the default user
+    pub(crate) fn default_login(&self, requirepass: Option<&str>) -> bool {
+        requirepass.is_none() && self.users.read().unwrap()[DEFAULT_USER].enabled
+    }
+}
+
+fn effective(username: &str, user: &User, requirepass: Option<&str>) -> User {
+    let mut user = user.clone();
+    if username == DEFAULT_USER {
+        user.nopass = requirepass.is_none();
+        user.passwords = requirepass.map(hash_password).into_iter().collect();
+    }
+    user
+}
+
+fn hash_password(password: &str) -> PasswordHash {
+    Sha256::digest(password.as_bytes()).into()
+}
+
+/// Compare every byte whatever the outcome, so the time taken doesn't reveal how much of the
+/// digest was guessed right
+fn same_hash(expected: &PasswordHash, hash: &PasswordHash) -> bool {
+    let diff = expected.iter().zip(hash).fold(0, |diff, (a, b)| diff | (a ^ b));
+    diff == 0
+}
@@ -0,0 +1,94 @@
+//! Retry with exponential backoff.
+//!
+//! The delay before retry `n` is `base * 2^n`, capped to `cap`. With jitter, each delay is
+//! shortened by a random share of up to `jitter` of itself so that many clients retrying at once
+//! don't stay in lock step.
+
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use tokio::time;
+
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    base: Duration,
+    cap: Duration,
+    /// Share of each delay which is randomized, between `0.0` and `1.0`
+    jitter: f64,
+    /// Number of retries before giving up, `None` retries forever
+    max_retries: Option<u32>,
+}
+
+impl Default for Backoff {
+    /// Retries after 1s, 2s, 4s... up to 64s then gives up
+    fn default() -> Backoff {
+        Backoff::new(Duration::from_secs(1), Duration::from_secs(64)).max_retries(7)
+    }
+}
+
+impl Backoff {
+    /// Retry forever, waiting `base` then twice as long each time, never more than `cap`
+    pub fn new(base: Duration, cap: Duration) -> Backoff {
+        Backoff {
+            base,
+            cap,
+            jitter: 0.0,
+            max_retries: None,
+        }
+    }
+
+    /// Randomize up to `ratio` of each delay, the ratio is clamped between `0.0` and `1.0`
+    pub fn jitter(mut self, ratio: f64) -> Backoff {
+        self.jitter = ratio.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Give up after `retries` failed retries
+    pub fn max_retries(mut self, retries: u32) -> Backoff {
+        self.max_retries = Some(retries);
+        self
+    }
+
+    /// Delay before retry number `attempt`, starting at `0`
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt).unwrap_or(u32::MAX);
+        let delay = self.base.checked_mul(factor).unwrap_or(self.cap).min(self.cap);
+
+        if self.jitter == 0.0 {
+            return delay;
+        }
+        delay.mul_f64(1.0 - self.jitter * random_unit())
+    }
+
+    /// Run `op` until it succeeds, sleeping between attempts. Returns the last error once the
+    /// retries are exhausted.
+    pub async fn retry<T, E, F, Fut>(&self, mut op: F) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match op().await {
+                Ok(value) => return Ok(value),
+                Err(err) if Some(attempt) == self.max_retries => return Err(err),
+                Err(_) => {}
+            }
+
+            time::sleep(self.delay(attempt)).await;
+            attempt = attempt.saturating_add(1);
+        }
+    }
+}
+
+/// Random number in `[0, 1)`, good enough to spread retries
+pub(crate) fn random_unit() -> f64 {
+    (random_bits() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// 64 random bits, not suitable for secrets
+pub(crate) fn random_bits() -> u64 {
+    // every `RandomState` is seeded with fresh random keys
+    RandomState::new().build_hasher().finish()
+}
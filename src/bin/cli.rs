@@ -1,8 +1,15 @@
-use std::{num::ParseIntError, time::Duration};
+use std::{num::ParseIntError, time::{Duration, Instant}};
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use hdrhistogram::Histogram;
 use structopt::StructOpt;
-use redust::DEFAULT_PORT;
+use redust::{Error, Frame, RespCodec, DEFAULT_PORT};
+use tokio::io::{self, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
+use tokio::net::TcpStream;
+use tokio::signal;
+use tokio::sync::oneshot;
+use tokio_stream::StreamExt;
+use tokio_util::codec::FramedRead;
 
 #[derive(StructOpt, Debug)]
 enum Command {
@@ -17,6 +24,63 @@ enum Command {
         #[structopt(parse(try_from_str = duration_from_ms_str))]
         expires: Option<Duration>,
     },
+    /// Remove keys
+    Del {
+        #[structopt(required = true)]
+        keys: Vec<String>,
+    },
+    /// Count the keys which exist
+    Exists {
+        #[structopt(required = true)]
+        keys: Vec<String>,
+    },
+    /// Set the time to live of a key, in seconds
+    Expire {
+        key: String,
+        #[structopt(parse(try_from_str = duration_from_secs_str))]
+        seconds: Duration,
+    },
+    /// Increment the counter held by a key
+    Incr {
+        key: String,
+    },
+    /// List the keys matching a glob-style pattern
+    Keys {
+        pattern: String,
+    },
+    /// Post a message on a channel
+    Publish {
+        channel: String,
+        #[structopt(parse(from_str=bytes_from_str))]
+        message: Bytes,
+    },
+    /// Print the messages posted on channels until interrupted
+    Subscribe {
+        #[structopt(required = true)]
+        channels: Vec<String>,
+    },
+    /// Measure the throughput and latency of GET/SET issued by concurrent clients
+    Bench {
+        /// Number of concurrent connections
+        #[structopt(short = "c", long = "--clients", default_value = "50")]
+        clients: usize,
+
+        /// How long to run, in seconds
+        #[structopt(short = "d", long = "--duration", default_value = "10", parse(try_from_str = duration_from_secs_str))]
+        duration: Duration,
+
+        /// Share of the operations which are GET, in percent
+        #[structopt(long = "--read-ratio", default_value = "80")]
+        read_ratio: u64,
+
+        /// Size of the values written, in bytes
+        #[structopt(long = "--size", default_value = "16")]
+        size: usize,
+
+        /// Number of distinct keys used
+        #[structopt(long = "--keyspace", default_value = "10000")]
+        keyspace: u64,
+    },
 }
 
 #[derive(StructOpt, Debug)]
@@ -28,7 +92,12 @@ enum Command {
 )]
 struct Cli {
     #[structopt(subcommand)]
-    command: Command,
+    command: Option<Command>,
+
+    /// Pipeline the commands read from stdin, as RESP or one command per line, then report how
+    /// many replies were errors
+    #[structopt(long = "--pipe")]
+    pipe: bool,
 
     #[structopt(name="hostname", long="--host", default_value="127.0.0.1")]
     host: String,
@@ -38,24 +107,27 @@ struct Cli {
 
 }
 
-#[tokio::main(flavor="current_thread")]
+#[tokio::main]
 async fn main() -> redust::Result<()> {
     tracing_subscriber::fmt::try_init()?;
     let cli = Cli::from_args();
     let addr= format!("{}:{}", cli.host, cli.port);
 
+    if cli.pipe {
+        return pipe(&addr).await;
+    }
+    let command = cli.command.ok_or("a command is required unless --pipe is given")?;
+    if let Command::Bench { clients, duration, read_ratio, size, keyspace } = command {
+        return bench(&addr, clients, duration, read_ratio, size, keyspace).await;
+    }
+
     let mut client = redust::client::connect(&addr).await?;
 
-    match cli.command {
+    match command {
         Command::Get { key } => {
-            if let Some(value) = client.get(&key).await? {
-                if let Ok(string) = std::str::from_utf8(&value) {
-                    println!("\"{}\"", string);
-                } else {
-                    println!("{:?}", value);
-                }
-            } else {
-                println!("(nil)");
+            match client.get(&key).await? {
+                Some(value) => println!("{}", Frame::Bulk(value)),
+                None => println!("{}", Frame::Null),
             }
         }
 
@@ -72,10 +144,243 @@ async fn main() -> redust::Result<()> {
             client.set_expires(&key, value, expires).await?;
             println!("OK");
         }
+
+        Command::Del { keys } => {
+            println!("{}", Frame::Integer(client.del(&keys).await? as i64));
+        }
+
+        Command::Exists { keys } => {
+            println!("{}", Frame::Integer(client.exists(&keys).await? as i64));
+        }
+
+        Command::Expire { key, seconds } => {
+            let set = client.expire(&key, seconds).await?;
+            println!("{}", Frame::Integer(set as i64));
+        }
+
+        Command::Incr { key } => {
+            println!("{}", Frame::Integer(client.incr(&key).await?));
+        }
+
+        Command::Keys { pattern } => {
+            let keys = client.keys(&pattern).await?;
+            let frame = Frame::Array(keys.into_iter().map(|key| Frame::Bulk(key.into())).collect());
+            println!("{}", frame);
+        }
+
+        Command::Publish { channel, message } => {
+            println!("{}", Frame::Integer(client.publish(&channel, message).await? as i64));
+        }
+
+        Command::Subscribe { channels } => {
+            let subscriber = client.subscribe(channels).await?;
+            for channel in subscriber.get_subscribed() {
+                println!("subscribed to {}", channel);
+            }
+
+            let messages = subscriber.into_stream();
+            tokio::pin!(messages);
+            loop {
+                let message = tokio::select! {
+                    message = messages.next() => message,
+                    _ = signal::ctrl_c() => break,
+                };
+                match message {
+                    Some(Ok(message)) => {
+                        let frame = Frame::Array(vec![
+                            Frame::Bulk(Bytes::from_static(b"message")),
+                            Frame::Bulk(message.channel.into()),
+                            Frame::Bulk(message.content),
+                        ]);
+                        println!("{}", frame);
+                    }
+                    // the subscription goes on, the missed messages are only reported
+                    Some(Err(err @ Error::Lagged { .. })) => eprintln!("{}", err),
+                    Some(Err(err)) => return Err(err),
+                    None => break,
+                }
+            }
+        }
+
+        Command::Bench { .. } => unreachable!("handled before connecting"),
     }
     Ok(())
 }
 
+/// Run `clients` connections issuing GET and SET on random keys for `duration`, then print the
+/// throughput and the latency percentiles
+async fn bench(
+    addr: &str,
+    clients: usize,
+    duration: Duration,
+    read_ratio: u64,
+    size: usize,
+    keyspace: u64,
+) -> redust::Result<()> {
+    let value = Bytes::from(vec![b'x'; size]);
+    let start = Instant::now();
+
+    let mut tasks = vec![];
+    for id in 0..clients {
+        let mut client = redust::client::connect(addr).await?;
+        let value = value.clone();
+
+        tasks.push(tokio::spawn(async move {
+            let mut latencies = Histogram::<u64>::new_with_bounds(1, 60_000_000, 3).unwrap();
+            let mut rng = id as u64 + 1;
+            let mut errors = 0u64;
+
+            while start.elapsed() < duration {
+                let n = xorshift(&mut rng);
+                let key = format!("bench:key:{}", n % keyspace.max(1));
+                let sent = Instant::now();
+                let res = if (n >> 32) % 100 < read_ratio {
+                    client.get::<Option<Bytes>>(&key).await.map(drop)
+                } else {
+                    client.set(&key, value.clone()).await
+                };
+                match res {
+                    Ok(()) => latencies.saturating_record(sent.elapsed().as_micros() as u64),
+                    Err(_) => errors += 1,
+                }
+            }
+            (latencies, errors)
+        }));
+    }
+
+    let mut latencies = Histogram::<u64>::new_with_bounds(1, 60_000_000, 3).unwrap();
+    let mut errors = 0;
+    for task in tasks {
+        let (task_latencies, task_errors) = task.await.map_err(|err| err.to_string())?;
+        latencies.add(task_latencies).map_err(|err| err.to_string())?;
+        errors += task_errors;
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    println!("clients: {}, read ratio: {}%, value size: {} bytes, keyspace: {}", clients, read_ratio, size, keyspace);
+    println!("requests: {}, errors: {}, elapsed: {:.2}s", latencies.len(), errors, elapsed);
+    println!("throughput: {:.0} requests/s", latencies.len() as f64 / elapsed);
+    for percentile in &[50.0, 90.0, 99.0, 99.9] {
+        let usec = latencies.value_at_quantile(percentile / 100.0);
+        println!("p{:<5} {:>10.3} ms", percentile, usec as f64 / 1000.0);
+    }
+    println!("max    {:>10.3} ms", latencies.max() as f64 / 1000.0);
+    Ok(())
+}
+
+/// Cheap pseudo random numbers to pick keys and operations, `state` must not be `0`
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+/// Send the commands read from stdin without waiting for the replies, which are counted as they
+/// arrive. Stdin holds either RESP arrays, as produced by other tools, or one command per line
+/// with its arguments separated by spaces: `SET key "some value"`.
+async fn pipe(addr: &str) -> redust::Result<()> {
+    let (rd, wr) = TcpStream::connect(addr).await?.into_split();
+    let (sent_tx, mut sent_rx) = oneshot::channel();
+
+    let writer = tokio::spawn(async move {
+        let res = send_commands(wr).await;
+        let _ = sent_tx.send(*res.as_ref().unwrap_or(&0));
+        res
+    });
+
+    let mut replies = FramedRead::new(rd, RespCodec::new());
+    let (mut received, mut errors) = (0, 0);
+    let mut sent = None;
+    while sent.is_none_or(|sent| received < sent) {
+        tokio::select! {
+            res = &mut sent_rx, if sent.is_none() => sent = Some(res.unwrap_or(0)),
+            reply = replies.next() => match reply.transpose()? {
+                Some(Frame::Error(msg)) => {
+                    errors += 1;
+                    eprintln!("{}", msg);
+                    received += 1;
+                }
+                Some(_) => received += 1,
+                None => return Err(Error::ConnectionReset),
+            },
+        }
+    }
+    writer.await.map_err(|err| err.to_string())??;
+
+    println!("All data transferred. errors: {}, replies: {}", errors, received);
+    if errors > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Write the commands read from stdin to `dst`, returns the number of commands sent
+async fn send_commands(dst: impl AsyncWrite + Unpin) -> redust::Result<u64> {
+    let mut dst = BufWriter::new(dst);
+    let mut stdin = BufReader::new(io::stdin());
+    let mut buf = BytesMut::new();
+    let mut sent = 0;
+
+    // RESP input starts with an array, anything else is read as text
+    let resp = stdin.fill_buf().await?.first() == Some(&b'*');
+    if resp {
+        let mut commands = FramedRead::new(stdin, RespCodec::new());
+        while let Some(frame) = commands.next().await.transpose()? {
+            frame.encode(&mut buf);
+            dst.write_all(&buf.split()).await?;
+            sent += 1;
+        }
+    } else {
+        let mut lines = stdin.lines();
+        while let Some(line) = lines.next_line().await? {
+            let args = split_args(&line)?;
+            if args.is_empty() {
+                continue;
+            }
+            Frame::Array(args.into_iter().map(Frame::Bulk).collect()).encode(&mut buf);
+            dst.write_all(&buf.split()).await?;
+            sent += 1;
+        }
+    }
+    dst.flush().await?;
+    Ok(sent)
+}
+
+/// Split a line of text into arguments. Arguments are separated by spaces, double quotes
+/// delimit an argument which may contain spaces and `\` escapes the next character within them.
+fn split_args(line: &str) -> redust::Result<Vec<Bytes>> {
+    let mut args = vec![];
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let mut arg = String::new();
+        match chars.peek() {
+            None => return Ok(args),
+            Some('"') => {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => arg.extend(chars.next()),
+                        Some(c) => arg.push(c),
+                        None => return Err(format!("unbalanced quotes in `{}`", line).into()),
+                    }
+                }
+            }
+            Some(_) => {
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    arg.push(c);
+                }
+            }
+        }
+        args.push(Bytes::from(arg));
+    }
+}
+
 fn bytes_from_str(src: &str) -> Bytes {
     Bytes::from(src.to_string())
 }
@@ -84,3 +389,8 @@ fn duration_from_ms_str(src: &str) -> Result<Duration, ParseIntError> {
     let ms= src.parse::<u64>()?;
     Ok(Duration::from_millis(ms))
 }
+
+fn duration_from_secs_str(src: &str) -> Result<Duration, ParseIntError> {
+    let secs = src.parse::<u64>()?;
+    Ok(Duration::from_secs(secs))
+}
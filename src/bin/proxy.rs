@@ -0,0 +1,508 @@
+//! RESP proxy multiplexing the commands of many clients over a small pool of upstream
+//! connections.
+//!
+//! Each upstream connection is driven by a task which pipelines the requests queued by the
+//! clients and hands the replies back in order. Commands which tie state to the connection can't
+//! be shared this way:
+//!
+//! * `WATCH` and `MULTI` pin the client to a dedicated upstream connection until `EXEC`,
+//!   `DISCARD`, or `UNWATCH` outside of a transaction.
+//! * Blocking commands run on a dedicated upstream connection, so they don't stall the pool.
+//! * Subscriptions and streams (`SUBSCRIBE`, `SYNCFROM`, `MONITOR`) turn the client connection
+//!   into a plain relay to a dedicated upstream connection.
+//! * `SELECT`, `AUTH`, `HELLO` and `CLIENT SETNAME` are refused: the state they set would only
+//!   apply to whichever pooled connection ran them.
+//!
+//! With `--shadow`, the write commands served by the pool are also mirrored to a secondary server,
+//! for instance redust shadowing an existing Redis before the cut over. Clients only ever get the
+//! replies of the upstream server: the mirrored commands are queued and sent in the background,
+//! and dropped if the secondary server falls behind. One command out of `--shadow-compare-every`,
+//! reads included, is also run on the secondary server and both replies are compared. Divergences
+//! are logged as they are found and a summary is logged periodically.
+
+use redust::{Backoff, Connection, Frame, DEFAULT_PORT};
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use structopt::StructOpt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Duration;
+use tracing::{debug, error, info, warn};
+
+/// Maximum number of requests written to an upstream connection before reading the replies
+const MAX_PIPELINE: usize = 64;
+
+/// Commands blocking the connection until they complete
+const BLOCKING: &[&str] = &[
+    "blpop", "brpop", "brpoplpush", "blmove", "blmpop", "bzpopmin", "bzpopmax", "bzmpop", "wait",
+];
+
+/// Commands after which the connection only streams replies
+const STREAMING: &[&str] = &["subscribe", "psubscribe", "ssubscribe", "syncfrom", "monitor"];
+
+/// Commands setting state on the connection they run on, `CLIENT SETNAME` aside
+const CONNECTION_STATE: &[&str] = &["select", "auth", "hello"];
+
+/// Commands modifying the key space, mirrored to the shadow server
+const WRITES: &[&str] = &[
+    "set", "setex", "psetex", "setnx", "getset", "getdel", "getex", "mset", "msetnx", "append",
+    "setrange", "setbit", "incr", "incrby", "incrbyfloat", "decr", "decrby", "del", "unlink",
+    "expire", "pexpire", "expireat", "pexpireat", "persist", "rename", "renamenx", "hset",
+    "hsetnx", "hmset", "hdel", "hincrby", "hincrbyfloat", "lpush", "rpush", "lpop", "rpop", "lset",
+    "linsert", "lrem", "ltrim", "sadd", "srem", "zadd", "zrem", "zincrby", "geoadd", "flushdb",
+    "flushall", "cas", "reserve", "confirm", "lock", "unlock", "blog.append",
+];
+
+/// Maximum number of mirrored commands waiting to be sent to the shadow server
+const SHADOW_QUEUE: usize = 16 * 1024;
+
+/// How often the shadowing summary is logged
+const SHADOW_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "redust-proxy")]
+struct Cli {
+    /// Port clients connect to
+    #[structopt(long = "--port", default_value = "6380")]
+    port: String,
+
+    /// Address of the upstream redust or Redis server
+    #[structopt(long = "--upstream")]
+    upstream: Option<String>,
+
+    /// Number of upstream connections shared by the clients
+    #[structopt(long = "--pool-size", default_value = "4")]
+    pool_size: usize,
+
+    /// Address of a secondary server the write commands are mirrored to
+    #[structopt(long = "--shadow")]
+    shadow: Option<String>,
+
+    /// Compare the replies of the upstream and shadow servers for one command out of this many.
+    /// `0` disables the comparisons.
+    #[structopt(long = "--shadow-compare-every", default_value = "100")]
+    shadow_compare_every: u64,
+}
+
+type Reply = redust::Result<Frame>;
+
+/// A command queued on a pooled upstream connection
+struct Request {
+    frame: Frame,
+    reply: oneshot::Sender<Reply>,
+}
+
+/// Upstream connections shared by every client, requests are spread round robin
+struct Pool {
+    upstream: String,
+    workers: Vec<mpsc::Sender<Request>>,
+    next: AtomicUsize,
+    shadow: Option<Shadow>,
+}
+
+/// Mirrors commands to the shadow server
+struct Shadow {
+    queue: mpsc::Sender<Mirrored>,
+    compare_every: u64,
+    /// Number of commands seen, drives the sampling of comparisons
+    seen: AtomicU64,
+    stats: Arc<ShadowStats>,
+}
+
+/// A command queued for the shadow server
+struct Mirrored {
+    frame: Frame,
+    /// Reply of the upstream server, only kept if the replies are compared
+    expected: Option<Frame>,
+}
+
+#[derive(Default)]
+struct ShadowStats {
+    mirrored: AtomicU64,
+    dropped: AtomicU64,
+    compared: AtomicU64,
+    diverged: AtomicU64,
+}
+
+#[tokio::main]
+pub async fn main() -> redust::Result<()> {
+    tracing_subscriber::fmt::try_init()?;
+    let cli = Cli::from_args();
+    let upstream = cli
+        .upstream
+        .unwrap_or_else(|| format!("127.0.0.1:{}", DEFAULT_PORT));
+
+    let mut pool = Pool::new(upstream, cli.pool_size.max(1));
+    if let Some(shadow) = cli.shadow {
+        info!(%shadow, compare_every = cli.shadow_compare_every, "shadowing writes");
+        pool.shadow = Some(Shadow::new(shadow, cli.shadow_compare_every));
+    }
+    let pool = Arc::new(pool);
+
+    let addr = format!("127.0.0.1:{}", cli.port);
+    let listener = TcpListener::bind(&addr).await?;
+    info!(%addr, upstream = %pool.upstream, "proxy listening");
+
+    loop {
+        let (socket, _) = listener.accept().await?;
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            if let Err(err) = serve(Connection::new(socket), &pool).await {
+                debug!(cause = %err, "client connection error");
+            }
+        });
+    }
+}
+
+impl Pool {
+    fn new(upstream: String, size: usize) -> Pool {
+        let workers = (0..size)
+            .map(|_| {
+                let (tx, rx) = mpsc::channel(1024);
+                tokio::spawn(drive_upstream(upstream.clone(), rx));
+                tx
+            })
+            .collect();
+
+        Pool {
+            upstream,
+            workers,
+            next: AtomicUsize::new(0),
+            shadow: None,
+        }
+    }
+
+    /// Run `frame` on one of the pooled connections
+    async fn call(&self, frame: Frame) -> Reply {
+        let i = self.next.fetch_add(1, Ordering::Relaxed) % self.workers.len();
+        let (reply, rx) = oneshot::channel();
+
+        self.workers[i]
+            .send(Request { frame, reply })
+            .await
+            .map_err(|_| "upstream connection closed")?;
+        rx.await.map_err(|_| "upstream connection closed")?
+    }
+
+    /// Open a connection to the upstream server for a single client
+    async fn dedicated(&self) -> redust::Result<Connection> {
+        Ok(Connection::new(TcpStream::connect(&self.upstream).await?))
+    }
+}
+
+/// Serve the commands of a client
+async fn serve(mut client: Connection, pool: &Pool) -> redust::Result<()> {
+    while let Some(frame) = client.read_frame().await? {
+        let name = command_name(&frame);
+
+        match name.as_deref() {
+            Some(name) if changes_connection_state(name, &frame) => {
+                client.write_frame(&connection_state_error(name)).await?;
+            }
+            Some(name @ ("watch" | "multi")) => {
+                let mut upstream = pool.dedicated().await?;
+                upstream.write_frame(&frame).await?;
+                relay_reply(&mut upstream, &mut client).await?;
+                pinned(&mut client, &mut upstream, name == "multi").await?;
+            }
+            Some(name) if BLOCKING.contains(&name) => {
+                let mut upstream = pool.dedicated().await?;
+                upstream.write_frame(&frame).await?;
+                relay_reply(&mut upstream, &mut client).await?;
+            }
+            Some(name) if STREAMING.contains(&name) => {
+                let mut upstream = pool.dedicated().await?;
+                upstream.write_frame(&frame).await?;
+                return relay(&mut client, &mut upstream).await;
+            }
+            _ => {
+                let mirrored = pool.shadow.as_ref().map(|shadow| (shadow, frame.clone()));
+                let reply = match pool.call(frame).await {
+                    Ok(reply) => reply,
+                    Err(err) => Frame::error(format!("ERR proxy: {}", err)),
+                };
+                client.write_frame(&reply).await?;
+
+                if let Some((shadow, frame)) = mirrored {
+                    shadow.mirror(name.as_deref(), frame, &reply);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Forward the commands of a client which watched keys or started a transaction, until the
+/// transaction is executed or discarded, or the keys are unwatched
+async fn pinned(client: &mut Connection, upstream: &mut Connection, mut multi: bool) -> redust::Result<()> {
+    while let Some(frame) = client.read_frame().await? {
+        let name = command_name(&frame);
+        let done = match name.as_deref() {
+            Some(name) if changes_connection_state(name, &frame) => {
+                client.write_frame(&connection_state_error(name)).await?;
+                continue;
+            }
+            Some("multi") => {
+                multi = true;
+                false
+            }
+            Some("exec") | Some("discard") => true,
+            // queued as part of the transaction
+            Some("unwatch") => !multi,
+            _ => false,
+        };
+
+        upstream.write_frame(&frame).await?;
+        relay_reply(upstream, client).await?;
+
+        if done {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+/// Forward one reply from `upstream` to `client`
+async fn relay_reply(upstream: &mut Connection, client: &mut Connection) -> redust::Result<()> {
+    match upstream.read_frame().await? {
+        Some(reply) => Ok(client.write_frame(&reply).await?),
+        None => Err(redust::Error::ConnectionReset),
+    }
+}
+
+/// Forward frames both ways until either side closes its connection
+async fn relay(client: &mut Connection, upstream: &mut Connection) -> redust::Result<()> {
+    loop {
+        tokio::select! {
+            res = client.read_frame() => match res? {
+                Some(frame) => upstream.write_frame(&frame).await?,
+                None => return Ok(()),
+            },
+            res = upstream.read_frame() => match res? {
+                Some(frame) => client.write_frame(&frame).await?,
+                None => return Ok(()),
+            },
+        }
+    }
+}
+
+/// Lowercased name of the command held by `frame`
+fn command_name(frame: &Frame) -> Option<String> {
+    lowercase_arg(frame, 0)
+}
+
+/// Lowercased argument `i` of the command held by `frame`
+fn lowercase_arg(frame: &Frame, i: usize) -> Option<String> {
+    match frame {
+        Frame::Array(parts) => match parts.get(i) {
+            Some(Frame::Bulk(arg)) => Some(String::from_utf8_lossy(arg).to_lowercase()),
+            Some(Frame::Simple(arg)) => Some(arg.to_lowercase()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether the command `name` held by `frame` sets state on the upstream connection it runs on
+fn changes_connection_state(name: &str, frame: &Frame) -> bool {
+    CONNECTION_STATE.contains(&name)
+        || (name == "client" && lowercase_arg(frame, 1).as_deref() == Some("setname"))
+}
+
+fn connection_state_error(name: &str) -> Frame {
+    Frame::error(format!(
+        "ERR proxy: {} is not supported, the upstream connections are shared",
+        name.to_uppercase()
+    ))
+}
+
+/// Drive a pooled upstream connection, reconnecting whenever it breaks
+async fn drive_upstream(upstream: String, mut requests: mpsc::Receiver<Request>) {
+    let backoff = Backoff::new(Duration::from_millis(100), Duration::from_secs(5)).jitter(0.5);
+
+    loop {
+        let connect = || async {
+            TcpStream::connect(&upstream).await.map_err(|err| {
+                warn!(cause = %err, %upstream, "failed to connect upstream");
+                err
+            })
+        };
+        let mut conn = match backoff.retry(connect).await {
+            Ok(socket) => Connection::new(socket),
+            // retries forever
+            Err(_) => unreachable!(),
+        };
+
+        match pipeline(&mut conn, &mut requests).await {
+            Ok(()) => return,
+            Err(err) => error!(cause = %err, "upstream connection error"),
+        }
+    }
+}
+
+/// Pipeline the queued requests on `conn`. Returns once every client handle is gone.
+async fn pipeline(conn: &mut Connection, requests: &mut mpsc::Receiver<Request>) -> redust::Result<()> {
+    let mut batch = VecDeque::with_capacity(MAX_PIPELINE);
+
+    while let Some(request) = requests.recv().await {
+        batch.push_back(request);
+        while batch.len() < MAX_PIPELINE {
+            match requests.try_recv() {
+                Ok(request) => batch.push_back(request),
+                Err(_) => break,
+            }
+        }
+
+        let res = exchange(conn, &mut batch).await;
+        // the requests left didn't get a reply, the connection can't be trusted anymore
+        for request in batch.drain(..) {
+            let _ = request.reply.send(Err(redust::Error::ConnectionReset));
+        }
+        res?;
+    }
+    Ok(())
+}
+
+/// Write out `batch` then hand each reply to its client, in order
+async fn exchange(conn: &mut Connection, batch: &mut VecDeque<Request>) -> redust::Result<()> {
+    for request in batch.iter() {
+        conn.write_frame_unflushed(&request.frame).await?;
+    }
+    conn.flush().await?;
+
+    while !batch.is_empty() {
+        let reply = match conn.read_frame().await? {
+            Some(reply) => reply,
+            None => return Err(redust::Error::ConnectionReset),
+        };
+        let request = batch.pop_front().unwrap();
+        let _ = request.reply.send(Ok(reply));
+    }
+    Ok(())
+}
+
+impl Shadow {
+    fn new(addr: String, compare_every: u64) -> Shadow {
+        let (queue, rx) = mpsc::channel(SHADOW_QUEUE);
+        let stats = Arc::new(ShadowStats::default());
+        tokio::spawn(drive_shadow(addr, rx, stats.clone()));
+        tokio::spawn(report_shadow(stats.clone()));
+
+        Shadow {
+            queue,
+            compare_every,
+            seen: AtomicU64::new(0),
+            stats,
+        }
+    }
+
+    /// Queue `frame` for the shadow server if it is a write or sampled for comparison. `reply` is
+    /// what the upstream server answered.
+    fn mirror(&self, name: Option<&str>, frame: Frame, reply: &Frame) {
+        let seen = self.seen.fetch_add(1, Ordering::Relaxed);
+        let compare = self.compare_every != 0 && seen.is_multiple_of(self.compare_every);
+        let write = name.is_some_and(|name| WRITES.contains(&name));
+
+        // a failed command didn't change the upstream server
+        if !compare && (!write || matches!(reply, Frame::Error(_))) {
+            return;
+        }
+        let mirrored = Mirrored {
+            frame,
+            expected: compare.then(|| reply.clone()),
+        };
+        match self.queue.try_send(mirrored) {
+            Ok(()) => self.stats.mirrored.fetch_add(1, Ordering::Relaxed),
+            Err(_) => self.stats.dropped.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+}
+
+/// Send the mirrored commands to the shadow server, reconnecting whenever the connection breaks
+async fn drive_shadow(addr: String, mut queue: mpsc::Receiver<Mirrored>, stats: Arc<ShadowStats>) {
+    let backoff = Backoff::new(Duration::from_millis(100), Duration::from_secs(5)).jitter(0.5);
+
+    loop {
+        let connect = || async {
+            TcpStream::connect(&addr).await.map_err(|err| {
+                warn!(cause = %err, shadow = %addr, "failed to connect to the shadow server");
+                err
+            })
+        };
+        let mut conn = match backoff.retry(connect).await {
+            Ok(socket) => Connection::new(socket),
+            // retries forever
+            Err(_) => unreachable!(),
+        };
+
+        match shadow(&mut conn, &mut queue, &stats).await {
+            Ok(()) => return,
+            Err(err) => error!(cause = %err, "shadow connection error"),
+        }
+    }
+}
+
+/// Run the mirrored commands on `conn` one at a time. Returns once the proxy is gone.
+async fn shadow(
+    conn: &mut Connection,
+    queue: &mut mpsc::Receiver<Mirrored>,
+    stats: &ShadowStats,
+) -> redust::Result<()> {
+    while let Some(mirrored) = queue.recv().await {
+        conn.write_frame(&mirrored.frame).await?;
+        let reply = match conn.read_frame().await? {
+            Some(reply) => reply,
+            None => return Err(redust::Error::ConnectionReset),
+        };
+
+        if let Some(expected) = mirrored.expected {
+            stats.compared.fetch_add(1, Ordering::Relaxed);
+            if !same_reply(&expected, &reply) {
+                stats.diverged.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    command = ?mirrored.frame,
+                    upstream = ?expected,
+                    shadow = ?reply,
+                    "shadow server diverged"
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether two servers replied the same. Error messages differ across implementations, only the
+/// error codes are compared.
+fn same_reply(a: &Frame, b: &Frame) -> bool {
+    match (a, b) {
+        (Frame::Simple(a), Frame::Simple(b)) => a == b,
+        (Frame::Error(a), Frame::Error(b)) => a.split(' ').next() == b.split(' ').next(),
+        (Frame::Integer(a), Frame::Integer(b)) => a == b,
+        (Frame::Bulk(a), Frame::Bulk(b)) => a == b,
+        (Frame::Null, Frame::Null) => true,
+        (Frame::Array(a), Frame::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_reply(a, b))
+        }
+        _ => false,
+    }
+}
+
+/// Log the shadowing summary periodically
+async fn report_shadow(stats: Arc<ShadowStats>) {
+    let mut interval = tokio::time::interval(SHADOW_REPORT_INTERVAL);
+    // the first tick completes immediately
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        info!(
+            mirrored = stats.mirrored.load(Ordering::Relaxed),
+            dropped = stats.dropped.load(Ordering::Relaxed),
+            compared = stats.compared.load(Ordering::Relaxed),
+            diverged = stats.diverged.load(Ordering::Relaxed),
+            "shadow report"
+        );
+    }
+}
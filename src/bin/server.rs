@@ -1,24 +1,450 @@
-use redust::{server, DEFAULT_PORT};
+use redust::rate_limit::ClientKey;
+use redust::sentinel::Monitor;
+use redust::server::{self, PubSubOverflow};
+use redust::DEFAULT_PORT;
 
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+#[cfg(feature = "serde")]
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
 use structopt::StructOpt;
 use tokio::net::TcpListener;
 use tokio::signal;
 
-#[tokio::main]
-pub async fn main() -> redust::Result<()> {
-    tracing_subscriber::fmt::try_init()?;
-    let cli = Cli::from_args();
+pub fn main() -> redust::Result<()> {
+    let mut cli = Cli::from_args();
+    if let Some(path) = cli.config.take() {
+        cli = cli.or(FileConfig::load(&path)?);
+    }
+
+    let threads = match cli.threads {
+        Some(0) => return Err("--threads must be at least 1".into()),
+        Some(threads) => threads,
+        None => thread::available_parallelism().map_or(1, |threads| threads.get()),
+    };
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(threads)
+        .enable_all()
+        .build()?
+        .block_on(serve(cli, threads))
+}
+
+async fn serve(cli: Cli, threads: usize) -> redust::Result<()> {
+    match cli.log_format.unwrap_or(LogFormat::Text) {
+        LogFormat::Text => tracing_subscriber::fmt::try_init()?,
+        // the fields of each span are kept in its own object of `spans`, outermost first, so the
+        // fields shared by the connection and request spans can't clash
+        LogFormat::Json => tracing_subscriber::fmt()
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .try_init()?,
+    }
+    let bind = cli.bind.as_deref().unwrap_or("127.0.0.1");
     let port = cli.port.as_deref().unwrap_or(DEFAULT_PORT);
 
-    let addr = format!("127.0.0.1:{}", port);
+    let addr = format!("{}:{}", bind, port);
     log::info!("Listening {}", &addr);
     let listener = TcpListener::bind(&addr).await?;
-    server::run(listener, signal::ctrl_c()).await
+    let mut builder = server::Builder::new()
+        .active_defrag(cli.active_defrag)
+        .reject_excess_clients(cli.reject_excess_clients)
+        .proxy_protocol(cli.proxy_protocol)
+        .protocol_dump(cli.protocol_dump)
+        .enable_debug_command(cli.enable_debug_command)
+        .worker_threads(threads);
+    if let Some(max) = cli.maxclients {
+        builder = builder.max_clients(max);
+    }
+    if let Some(bytes) = cli.maxmemory {
+        builder = builder.maxmemory(bytes);
+    }
+    if let Some(shards) = cli.shards {
+        builder = builder.shards(shards);
+    }
+    if let Some(password) = cli.requirepass {
+        builder = builder.requirepass(password);
+    }
+    if let Some(path) = cli.warm_restart {
+        builder = builder.warm_restart(path);
+    }
+    if let Some(path) = cli.rdb_file {
+        builder = builder.rdb_file(path);
+    }
+    if let Some(path) = cli.import_rdb {
+        builder = builder.import_rdb(path);
+    }
+    if let Some(port) = cli.health_port {
+        let health_addr = format!("{}:{}", bind, port);
+        log::info!("Health checks on http://{}/healthz, metrics on /metrics", &health_addr);
+        builder = builder.health_check(TcpListener::bind(&health_addr).await?);
+    }
+    if let Some(secs) = cli.tcp_keepalive {
+        builder = builder.tcp_keepalive(Some(Duration::from_secs(secs)).filter(|_| secs > 0));
+    }
+    if let Some(millis) = cli.command_timeout {
+        builder = builder.command_timeout(Duration::from_millis(millis));
+    }
+    if let Some(rate) = cli.ratelimit_global {
+        builder = builder.rate_limit(rate);
+    }
+    if let Some(rate) = cli.ratelimit_client {
+        let key = match &cli.ratelimit_client_by {
+            Some(key) => key.parse::<ClientKey>()?,
+            None => ClientKey::Addr,
+        };
+        builder = builder.client_rate_limit(rate, key);
+    }
+    if let Some(capacity) = cli.pubsub_channel_capacity {
+        builder = builder.pubsub_channel_capacity(capacity);
+    }
+    if let Some(policy) = &cli.pubsub_overflow {
+        builder = builder.pubsub_overflow(policy.parse::<PubSubOverflow>()?);
+    }
+    #[cfg(feature = "websocket")]
+    if let Some(port) = cli.websocket_port {
+        let websocket_addr = format!("{}:{}", bind, port);
+        log::info!("WebSocket connections on ws://{}", &websocket_addr);
+        builder = builder.websocket(TcpListener::bind(&websocket_addr).await?);
+    }
+    #[cfg(feature = "http-gateway")]
+    if let Some(port) = cli.http_port {
+        let http_addr = format!("{}:{}", bind, port);
+        log::info!("HTTP API on http://{}", &http_addr);
+        builder = builder.http_gateway(TcpListener::bind(&http_addr).await?);
+    }
+    if let Some(monitor) = &cli.sentinel_monitor {
+        let mut monitor = monitor
+            .parse::<Monitor>()?
+            .replicas(cli.sentinel_replica)
+            .peers(cli.sentinel_peer);
+        if let Some(millis) = cli.sentinel_down_after {
+            monitor = monitor.down_after(Duration::from_millis(millis));
+        }
+        if let Some(password) = &cli.sentinel_auth_pass {
+            monitor = monitor.auth(cli.sentinel_auth_user.as_deref(), password);
+        }
+        if let Some(password) = &cli.sentinel_peer_pass {
+            monitor = monitor.peer_auth(cli.sentinel_peer_user.as_deref(), password);
+        }
+        builder = builder.sentinel(monitor);
+    }
+    if let Some(secs) = cli.drain_timeout {
+        builder = builder.drain_timeout(Duration::from_secs(secs));
+    }
+    builder.run(listener, signal::ctrl_c()).await
 }
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "redust-server")]
 struct Cli {
+    /// TOML file with the server settings, named as the flags without the leading dashes. The
+    /// flags given on the command line take precedence. Requires the `serde` feature.
+    #[structopt(long = "--config", parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Address to listen on, 127.0.0.1 by default
+    #[structopt(long = "--bind")]
+    bind: Option<String>,
+
     #[structopt(name = "port", long = "--port")]
     port: Option<String>,
+
+    /// Periodically shrink the key space after mass deletions
+    #[structopt(long = "--active-defrag")]
+    active_defrag: bool,
+
+    /// Number of partitions of the key space, each with its own lock. Defaults to 4 per worker
+    /// thread, and at least 16.
+    #[structopt(long = "--shards")]
+    shards: Option<usize>,
+
+    /// Worker threads serving the connections, one per CPU the process may run on by default. To
+    /// pin the server to some CPUs, start it under `taskset`, the default follows.
+    #[structopt(long = "--threads")]
+    threads: Option<usize>,
+
+    /// Seconds given to in-flight connections to finish on shutdown before they are closed
+    #[structopt(long = "--drain-timeout")]
+    drain_timeout: Option<u64>,
+
+    /// Snapshot file keeping the key space across restarts. SIGUSR2 saves it and stops the
+    /// server, the next start loads it.
+    #[structopt(long = "--warm-restart", parse(from_os_str))]
+    warm_restart: Option<PathBuf>,
+
+    /// RDB file written by `SAVE`, `dump.rdb` in the working directory by default
+    #[structopt(long = "--rdb-file", parse(from_os_str))]
+    rdb_file: Option<PathBuf>,
+
+    /// RDB file written by Redis to load on startup, only its string keys are imported
+    #[structopt(long = "--import-rdb", parse(from_os_str))]
+    import_rdb: Option<PathBuf>,
+
+    /// Run as a sentinel watching a primary, given as `<name> <ip:port> <quorum>`. The primary and
+    /// its replicas must support `REPLICAOF`, as Redis servers do.
+    #[structopt(long = "--sentinel-monitor")]
+    sentinel_monitor: Option<String>,
+
+    /// Replica promoted when the watched primary is down, in order of preference. Repeat the flag
+    /// for each replica.
+    #[structopt(long = "--sentinel-replica", number_of_values = 1)]
+    sentinel_replica: Vec<SocketAddr>,
+
+    /// Other sentinel watching the same primary. Repeat the flag for each sentinel.
+    #[structopt(long = "--sentinel-peer", number_of_values = 1)]
+    sentinel_peer: Vec<SocketAddr>,
+
+    /// Milliseconds the primary must stay unreachable to be considered down
+    #[structopt(long = "--sentinel-down-after")]
+    sentinel_down_after: Option<u64>,
+
+    /// User the sentinel authenticates as to the primary and its replicas, the default user if
+    /// not given
+    #[structopt(long = "--sentinel-auth-user")]
+    sentinel_auth_user: Option<String>,
+
+    /// Password the sentinel authenticates with to the primary and its replicas
+    #[structopt(long = "--sentinel-auth-pass")]
+    sentinel_auth_pass: Option<String>,
+
+    /// User the sentinel authenticates as to the other sentinels, the default user if not given
+    #[structopt(long = "--sentinel-peer-user")]
+    sentinel_peer_user: Option<String>,
+
+    /// Password the sentinel authenticates with to the other sentinels
+    #[structopt(long = "--sentinel-peer-pass")]
+    sentinel_peer_pass: Option<String>,
+
+    /// Maximum number of clients connected at once
+    #[structopt(long = "--maxclients")]
+    maxclients: Option<usize>,
+
+    /// Bytes the keys and values may use before the commands adding data are refused
+    #[structopt(long = "--maxmemory")]
+    maxmemory: Option<usize>,
+
+    /// Reply an error to clients over `--maxclients` instead of keeping them waiting
+    #[structopt(long = "--reject-excess-clients")]
+    reject_excess_clients: bool,
+
+    /// Password clients must send with `AUTH` before running commands
+    #[structopt(long = "--requirepass")]
+    requirepass: Option<String>,
+
+    /// Expect connections to start with a PROXY protocol header, when behind HAProxy or a
+    /// network load balancer, so the address of the clients is known
+    #[structopt(long = "--proxy-protocol")]
+    proxy_protocol: bool,
+
+    /// Port answering HTTP readiness probes on `/healthz` and serving metrics on `/metrics`
+    #[structopt(long = "--health-port")]
+    health_port: Option<u16>,
+
+    /// Port accepting connections over WebSocket, RESP being carried in binary messages
+    #[cfg(feature = "websocket")]
+    #[structopt(long = "--websocket-port")]
+    websocket_port: Option<u16>,
+
+    /// Port serving the HTTP API: `GET`, `PUT` and `DELETE` on `/keys/{key}`, `POST` on
+    /// `/publish/{channel}`
+    #[cfg(feature = "http-gateway")]
+    #[structopt(long = "--http-port")]
+    http_port: Option<u16>,
+
+    /// Seconds a connection stays idle before keepalive probes are sent, 300 by default, 0
+    /// disables them
+    #[structopt(long = "--tcp-keepalive")]
+    tcp_keepalive: Option<u64>,
+
+    /// Milliseconds a command may run before being answered `-TIMEOUT`
+    #[structopt(long = "--command-timeout")]
+    command_timeout: Option<u64>,
+
+    /// Commands per second accepted from all the clients together, the others are answered
+    /// `-BUSY rate limit exceeded`
+    #[structopt(long = "--ratelimit-global")]
+    ratelimit_global: Option<u64>,
+
+    /// Commands per second accepted from each client
+    #[structopt(long = "--ratelimit-client")]
+    ratelimit_client: Option<u64>,
+
+    /// How `--ratelimit-client` tells clients apart: `addr`, the default, or `user` for the ACL
+    /// user
+    #[structopt(long = "--ratelimit-client-by")]
+    ratelimit_client_by: Option<String>,
+
+    /// Messages a pub/sub channel holds for its slowest subscriber, 1024 by default
+    #[structopt(long = "--pubsub-channel-capacity")]
+    pubsub_channel_capacity: Option<usize>,
+
+    /// What happens to a subscriber whose channel is full: `drop`, the default, skips the oldest
+    /// messages, `disconnect` closes its connection
+    #[structopt(long = "--pubsub-overflow")]
+    pubsub_overflow: Option<String>,
+
+    /// Log every frame received and sent, as `redis-cli` renders them. Can be toggled at runtime
+    /// with `CONFIG SET protocol-dump yes|no`.
+    #[structopt(long = "--protocol-dump")]
+    protocol_dump: bool,
+
+    /// Allow the DEBUG subcommands altering the server behavior, such as `DEBUG SLEEP`. Can't be
+    /// changed at runtime.
+    #[structopt(long = "--enable-debug-command")]
+    enable_debug_command: bool,
+
+    /// `text` for humans, the default, or `json` for one object per line, the fields of the
+    /// connection and request spans listed under `spans`
+    #[structopt(long = "--log-format")]
+    log_format: Option<LogFormat>,
+}
+
+/// Settings read from the `--config` file, every key is optional.
+///
+/// ```toml
+/// bind = "0.0.0.0"
+/// port = 6379
+/// maxclients = 1000
+/// requirepass = "secret"
+/// warm-restart = "/var/lib/redust/snapshot"
+/// ```
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields, rename_all = "kebab-case"))]
+struct FileConfig {
+    bind: Option<String>,
+    port: Option<u16>,
+    active_defrag: bool,
+    shards: Option<usize>,
+    threads: Option<usize>,
+    drain_timeout: Option<u64>,
+    warm_restart: Option<PathBuf>,
+    rdb_file: Option<PathBuf>,
+    import_rdb: Option<PathBuf>,
+    sentinel_monitor: Option<String>,
+    sentinel_replica: Vec<SocketAddr>,
+    sentinel_peer: Vec<SocketAddr>,
+    sentinel_down_after: Option<u64>,
+    sentinel_auth_user: Option<String>,
+    sentinel_auth_pass: Option<String>,
+    sentinel_peer_user: Option<String>,
+    sentinel_peer_pass: Option<String>,
+    maxclients: Option<usize>,
+    maxmemory: Option<usize>,
+    reject_excess_clients: bool,
+    requirepass: Option<String>,
+    proxy_protocol: bool,
+    health_port: Option<u16>,
+    #[cfg(feature = "websocket")]
+    websocket_port: Option<u16>,
+    #[cfg(feature = "http-gateway")]
+    http_port: Option<u16>,
+    tcp_keepalive: Option<u64>,
+    command_timeout: Option<u64>,
+    ratelimit_global: Option<u64>,
+    ratelimit_client: Option<u64>,
+    ratelimit_client_by: Option<String>,
+    pubsub_channel_capacity: Option<usize>,
+    pubsub_overflow: Option<String>,
+    protocol_dump: bool,
+    enable_debug_command: bool,
+    log_format: Option<LogFormat>,
+}
+
+impl FileConfig {
+    #[cfg(feature = "serde")]
+    fn load(path: &Path) -> redust::Result<FileConfig> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        let config = toml::from_str(&text)
+            .map_err(|err| format!("invalid config file {}: {}", path.display(), err))?;
+        Ok(config)
+    }
+
+    #[cfg(not(feature = "serde"))]
+    fn load(path: &Path) -> redust::Result<FileConfig> {
+        let msg = format!("can't read {}, config files require the `serde` feature", path.display());
+        Err(msg.into())
+    }
+}
+
+impl Cli {
+    /// Fill the settings not given on the command line from `file`
+    fn or(self, file: FileConfig) -> Cli {
+        Cli {
+            config: None,
+            bind: self.bind.or(file.bind),
+            port: self.port.or(file.port.map(|port| port.to_string())),
+            active_defrag: self.active_defrag || file.active_defrag,
+            shards: self.shards.or(file.shards),
+            threads: self.threads.or(file.threads),
+            drain_timeout: self.drain_timeout.or(file.drain_timeout),
+            warm_restart: self.warm_restart.or(file.warm_restart),
+            rdb_file: self.rdb_file.or(file.rdb_file),
+            import_rdb: self.import_rdb.or(file.import_rdb),
+            sentinel_monitor: self.sentinel_monitor.or(file.sentinel_monitor),
+            sentinel_replica: or_list(self.sentinel_replica, file.sentinel_replica),
+            sentinel_peer: or_list(self.sentinel_peer, file.sentinel_peer),
+            sentinel_down_after: self.sentinel_down_after.or(file.sentinel_down_after),
+            sentinel_auth_user: self.sentinel_auth_user.or(file.sentinel_auth_user),
+            sentinel_auth_pass: self.sentinel_auth_pass.or(file.sentinel_auth_pass),
+            sentinel_peer_user: self.sentinel_peer_user.or(file.sentinel_peer_user),
+            sentinel_peer_pass: self.sentinel_peer_pass.or(file.sentinel_peer_pass),
+            maxclients: self.maxclients.or(file.maxclients),
+            maxmemory: self.maxmemory.or(file.maxmemory),
+            reject_excess_clients: self.reject_excess_clients || file.reject_excess_clients,
+            requirepass: self.requirepass.or(file.requirepass),
+            proxy_protocol: self.proxy_protocol || file.proxy_protocol,
+            health_port: self.health_port.or(file.health_port),
+            #[cfg(feature = "websocket")]
+            websocket_port: self.websocket_port.or(file.websocket_port),
+            #[cfg(feature = "http-gateway")]
+            http_port: self.http_port.or(file.http_port),
+            tcp_keepalive: self.tcp_keepalive.or(file.tcp_keepalive),
+            command_timeout: self.command_timeout.or(file.command_timeout),
+            ratelimit_global: self.ratelimit_global.or(file.ratelimit_global),
+            ratelimit_client: self.ratelimit_client.or(file.ratelimit_client),
+            ratelimit_client_by: self.ratelimit_client_by.or(file.ratelimit_client_by),
+            pubsub_channel_capacity: self.pubsub_channel_capacity.or(file.pubsub_channel_capacity),
+            pubsub_overflow: self.pubsub_overflow.or(file.pubsub_overflow),
+            protocol_dump: self.protocol_dump || file.protocol_dump,
+            enable_debug_command: self.enable_debug_command || file.enable_debug_command,
+            log_format: self.log_format.or(file.log_format),
+        }
+    }
+}
+
+/// The flags repeated on the command line, or the list of the file if there are none
+fn or_list<T>(cli: Vec<T>, file: Vec<T>) -> Vec<T> {
+    if cli.is_empty() {
+        file
+    } else {
+        cli
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LogFormat, String> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("unknown log format `{}`, expected `text` or `json`", other)),
+        }
+    }
 }
@@ -0,0 +1,95 @@
+//! Bit operations on string values, with the same layout as Redis `SETBIT` and `GETBIT`: bit `0`
+//! is the most significant bit of the first byte.
+
+use bytes::BytesMut;
+use std::convert::TryInto;
+
+/// Largest bit offset accepted by `SETBIT`, the value then holds 512MB
+pub(crate) const MAX_OFFSET: u64 = (1 << 32) - 1;
+
+/// Unit of the range given to `BITCOUNT`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Unit {
+    Byte,
+    Bit,
+}
+
+/// Value of the bit at `offset`, bits past the end are `0`
+pub(crate) fn get(data: &[u8], offset: u64) -> bool {
+    match data.get((offset / 8) as usize) {
+        Some(byte) => byte & mask(offset) != 0,
+        None => false,
+    }
+}
+
+/// Set the bit at `offset`, growing `data` with zeros as needed. Returns the previous value.
+pub(crate) fn set(data: &mut BytesMut, offset: u64, bit: bool) -> bool {
+    let index = (offset / 8) as usize;
+    if index >= data.len() {
+        data.resize(index + 1, 0);
+    }
+
+    let prev = data[index] & mask(offset) != 0;
+    if bit {
+        data[index] |= mask(offset);
+    } else {
+        data[index] &= !mask(offset);
+    }
+    prev
+}
+
+/// Number of bits set in `data`, within the inclusive `start..=end` range if given. Negative
+/// positions count from the end, `-1` being the last byte or bit.
+pub(crate) fn count(data: &[u8], range: Option<(i64, i64, Unit)>) -> u64 {
+    let (start, end, unit) = match range {
+        Some(range) => range,
+        None => return popcount(data),
+    };
+
+    let len = match unit {
+        Unit::Byte => data.len() as i64,
+        Unit::Bit => data.len() as i64 * 8,
+    };
+    let (start, end) = match clamp(start, end, len) {
+        Some(bounds) => bounds,
+        None => return 0,
+    };
+
+    match unit {
+        Unit::Byte => popcount(&data[start as usize..=end as usize]),
+        Unit::Bit => {
+            let (first, last) = ((start / 8) as usize, (end / 8) as usize);
+            // bits of the first and last bytes outside of the range
+            let head = data[first] & !(0xff >> (start % 8));
+            let tail = data[last] & (0x7f >> (end % 8));
+            popcount(&data[first..=last]) - head.count_ones() as u64 - tail.count_ones() as u64
+        }
+    }
+}
+
+/// Resolve negative positions and clamp the range to `0..len`. Returns `None` if it is empty.
+fn clamp(start: i64, end: i64, len: i64) -> Option<(i64, i64)> {
+    let resolve = |pos: i64| if pos < 0 { (pos + len).max(0) } else { pos };
+    let (start, end) = (resolve(start), resolve(end).min(len - 1));
+
+    if len == 0 || start > end {
+        return None;
+    }
+    Some((start, end))
+}
+
+/// Number of bits set in `data`, a word at a time
+fn popcount(data: &[u8]) -> u64 {
+    let mut chunks = data.chunks_exact(8);
+    let mut count: u64 = chunks
+        .by_ref()
+        .map(|chunk| u64::from_ne_bytes(chunk.try_into().unwrap()).count_ones() as u64)
+        .sum();
+    count += chunks.remainder().iter().map(|b| b.count_ones() as u64).sum::<u64>();
+    count
+}
+
+/// Mask of the bit at `offset` within its byte
+fn mask(offset: u64) -> u8 {
+    0x80 >> (offset % 8)
+}
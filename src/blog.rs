@@ -0,0 +1,73 @@
+//! Append-only byte log value, see `BLOG.APPEND` and `BLOG.READ`.
+//!
+//! Bytes are addressed by their absolute offset since the log was created. Once the log grows
+//! past its maximum size the oldest bytes are dropped, readers then have to resume from the
+//! oldest offset still available.
+
+use bytes::{Buf, Bytes, BytesMut};
+
+#[derive(Debug, Default)]
+pub(crate) struct Blog {
+    data: BytesMut,
+    /// Offset of the first byte of `data`
+    start: u64,
+}
+
+/// The requested offset was dropped by a rotation
+#[derive(Debug)]
+pub(crate) struct Rotated {
+    /// Oldest offset still available
+    pub(crate) oldest: u64,
+}
+
+impl Blog {
+    /// Rebuild a log holding `data` from offset `start`
+    pub(crate) fn from_parts(start: u64, data: &[u8]) -> Blog {
+        Blog {
+            data: BytesMut::from(data),
+            start,
+        }
+    }
+
+    /// Oldest offset still available
+    pub(crate) fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// The bytes held, from `start`
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Append `chunk`, dropping the oldest bytes past `max_size`. Returns the offset following
+    /// the appended bytes.
+    pub(crate) fn append(&mut self, chunk: &[u8], max_size: usize) -> u64 {
+        self.data.extend_from_slice(chunk);
+        if self.data.len() > max_size {
+            let rotated = self.data.len() - max_size;
+            self.data.advance(rotated);
+            self.start += rotated as u64;
+        }
+        self.end()
+    }
+
+    /// Read up to `len` bytes starting at `offset`. Nothing is returned past the end of the log.
+    pub(crate) fn read(&self, offset: u64, len: usize) -> Result<Bytes, Rotated> {
+        if offset < self.start {
+            return Err(Rotated { oldest: self.start });
+        }
+        let from = ((offset - self.start) as usize).min(self.data.len());
+        let to = from.saturating_add(len).min(self.data.len());
+        Ok(Bytes::copy_from_slice(&self.data[from..to]))
+    }
+
+    /// Offset following the last byte appended
+    pub(crate) fn end(&self) -> u64 {
+        self.start + self.data.len() as u64
+    }
+
+    /// Number of bytes held
+    pub(crate) fn len(&self) -> usize {
+        self.data.len()
+    }
+}
@@ -1,15 +1,45 @@
-use std::{io::ErrorKind, time::Duration};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use bytes::Bytes;
-use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::net::ToSocketAddrs;
+use tokio::time;
+use tokio_stream::Stream;
 use tracing::{debug, instrument};
 
-use crate::{Connection, Frame, Result, cmd::{Get, Set}};
+use crate::{Connection, Error, Frame, Result, cmd::{Acl, CommandSpec, Auth, BitCount, BlogAppend, BlogRead, Cas, Config, Confirm, Debug, Del, Dump, Exists, Expire, GeoAdd, GeoDist, GeoSearch, Get, GetBit, GetEntry, GetSet, HDel, HGet, HGetAll, HIncrBy, HIncrByFloat, HLen, HRandField, HScan, HSet, Incr, Info, BLPop, BRPop, Keys, LInsert, LLen, LPop, LPos, LPush, LRange, LRem, LSet, Latency, Lock, Lolwut, Memory, Ping, Publish, Pubsub, Quit, RPop, RPush, Reserve, Reset, Restore, Save, Sentinel, Seq, Set, SetBit, SlowLog, Subscribe, TtlStats, Unlock, Unsubscribe, ZAdd, ZCard, ZRange, ZRem, ZScore}};
+
+mod near_cache;
+use near_cache::NearCache;
+pub use near_cache::NearCacheStats;
+
+mod multiplexed;
+pub use multiplexed::{connect_multiplexed, MultiplexedClient};
+
+mod replicated;
+pub use replicated::{connect_replicated, ReadPolicy, ReplicatedClient};
+
+mod builder;
+pub use builder::ClientBuilder;
+
+mod convert;
+pub use convert::{FromFrame, ToArgs};
 
 pub struct Client {
     connection: Connection,
+
+    /// Values read recently, only used once enabled with `enable_near_cache`
+    near_cache: Option<NearCache>,
+
+    /// How long a reply is waited for, see `set_timeout`
+    timeout: Option<Duration>,
+
+    /// Number of requests which timed out, their replies are skipped when they arrive
+    stale_replies: usize,
 }
 
+/// A client in subscribe mode, see `Client::subscribe`. Only the subscriptions can be managed,
+/// other commands are rejected by the server.
 pub struct Subscriber {
     client: Client,
 
@@ -22,19 +52,145 @@ pub struct Message {
     pub content: Bytes,
 }
 
+/// A value along with the metadata of the write which produced it, see `Client::get_entry`
+#[derive(Debug)]
+pub struct Entry {
+    pub value: Bytes,
+    /// Time left before the key expires, `None` if it has no expiration
+    pub ttl: Option<Duration>,
+    /// Version of the value, a later write always has a greater version
+    pub version: u64,
+    /// Time of the write
+    pub mtime: SystemTime,
+}
+
+/// Latest latency spike of a command, see `Client::latency_latest`
+#[derive(Debug)]
+pub struct LatencyEvent {
+    pub command: String,
+    /// Time of the latest spike
+    pub time: SystemTime,
+    /// Latency of the latest spike
+    pub latency: Duration,
+    /// Highest latency of the command since it was last reset
+    pub max: Duration,
+}
+
+/// A command kept by the slow log of the server, see `Client::slowlog_get`
+#[derive(Debug)]
+pub struct SlowLogEntry {
+    pub id: u64,
+    /// Time the command was logged at
+    pub time: SystemTime,
+    /// Execution time of the command
+    pub duration: Duration,
+    /// Name of the command followed by its keys
+    pub args: Vec<String>,
+    /// Address of the client which sent the command, empty if unknown
+    pub client: String,
+    /// User the client was authenticated as
+    pub user: String,
+}
+
+/// Distribution of the key expirations, see `Client::ttl_stats`
+#[derive(Debug)]
+pub struct ExpiryStats {
+    pub keys_with_ttl: u64,
+    pub keys_without_ttl: u64,
+    /// Number of keys by time left before they expire, labelled by the exclusive upper bound of
+    /// the bucket, the last one is `inf`
+    pub histogram: Vec<(String, u64)>,
+    /// Number of keys expiring during each upcoming minute
+    pub forecast: Vec<u64>,
+}
+
+/// Connect to the server at `addr` with the default options, see `ClientBuilder`
 pub async fn connect<T: ToSocketAddrs>(addr: T) -> Result<Client> {
-    let socket = TcpStream::connect(addr).await?;
-    let conn = Connection::new(socket);
-    Ok(Client{ connection: conn })
+    ClientBuilder::new().connect(addr).await
+}
+
+/// Like `connect`, failing with `Error::Timeout` if the connection isn't established within
+/// `timeout`
+pub async fn connect_timeout<T: ToSocketAddrs>(addr: T, timeout: Duration) -> Result<Client> {
+    ClientBuilder::new().connect_timeout(timeout).connect(addr).await
 }
 
 
 impl Client {
+    /// A client over an established `connection`, with the default options
+    #[cfg(feature = "simulation")]
+    pub(crate) fn from_connection(connection: Connection) -> Client {
+        Client {
+            connection,
+            near_cache: None,
+            timeout: None,
+            stale_replies: 0,
+        }
+    }
+
+    /// Cache up to `capacity` values read by `get`, least recently used values are evicted first.
+    ///
+    /// A value is served from the cache until the key would expire on the server, and at most for
+    /// `max_age`. Writes made by other clients are only seen once the cached value is dropped, so
+    /// `max_age` bounds how stale a read can be.
+    pub fn enable_near_cache(&mut self, capacity: usize, max_age: Duration) {
+        self.near_cache = Some(NearCache::new(capacity, max_age));
+    }
+
+    /// Fail the requests whose reply takes longer than `timeout` with `Error::Timeout`, `None`
+    /// waits forever, the default.
+    ///
+    /// The client stays usable after a timeout: the late reply is skipped once it arrives.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Hit and miss counters of the near cache, `None` if it isn't enabled
+    pub fn near_cache_stats(&self) -> Option<NearCacheStats> {
+        self.near_cache.as_ref().map(NearCache::stats)
+    }
+
+    /// Get the value of `key`, read as a `T`. Nil reads as `None` when `T` is an `Option`, and
+    /// is an error otherwise.
+    ///
+    /// ```no_run
+    /// # async fn run(client: &mut redust::client::Client) -> redust::Result<()> {
+    /// let visits: Option<i64> = client.get("visits").await?;
+    /// let name = client.get::<String>("name").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
     #[instrument(skip(self))]
-    pub async fn get(&mut self, key: &str) -> Result<Option<Bytes>> {
+    pub async fn get<T: FromFrame>(&mut self, key: &str) -> Result<T> {
+        T::from_frame(match self.get_bytes(key).await? {
+            Some(value) => Frame::Bulk(value),
+            None => Frame::Null,
+        })
+    }
+
+    async fn get_bytes(&mut self, key: &str) -> Result<Option<Bytes>> {
+        if let Some(cache) = self.near_cache.as_mut() {
+            if let Some(value) = cache.get(key) {
+                return Ok(Some(value));
+            }
+
+            // the ttl tells how long the value can be cached
+            let entry = self.get_entry(key).await?;
+            return Ok(entry.map(|entry| {
+                if let Some(cache) = self.near_cache.as_mut() {
+                    cache.insert(key, entry.value.clone(), entry.ttl);
+                }
+                entry.value
+            }));
+        }
+
         let frame = Get::new(key).into_frame();
 
-        debug!(request = ?frame);
+        debug!(request = %frame);
 
         self.connection.write_frame(&frame).await?;
 
@@ -46,19 +202,213 @@ impl Client {
         }
     }
 
+    /// Get the value of `key` along with its ttl, version and modification time in one round
+    /// trip, the metadata is guaranteed to match the value.
     #[instrument(skip(self))]
-    pub async fn set(&mut self, key: &str, value: Bytes) -> crate::Result<()> {
+    pub async fn get_entry(&mut self, key: &str) -> Result<Option<Entry>> {
+        let frame = GetEntry::new(key).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Null => Ok(None),
+            Frame::Array(parts) => match &parts[..] {
+                [Frame::Bulk(value), ttl, Frame::Integer(version), Frame::Integer(mtime)] => {
+                    let ttl = match ttl {
+                        Frame::Integer(ms) => Some(Duration::from_millis(*ms as u64)),
+                        _ => None,
+                    };
+                    Ok(Some(Entry {
+                        value: value.clone(),
+                        ttl,
+                        version: *version as u64,
+                        mtime: UNIX_EPOCH + Duration::from_millis(*mtime as u64),
+                    }))
+                }
+                _ => Err(Frame::Array(parts).to_error()),
+            },
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Set `key` to `value`, which can be any single argument: bytes, a string or a number
+    #[instrument(skip(self, value))]
+    pub async fn set(&mut self, key: &str, value: impl ToArgs) -> crate::Result<()> {
+        let value = single_arg(value)?;
+        self.invalidate(key);
         self.set_cmd(Set::new(key, value, None)).await
     }
 
-    #[instrument(skip(self))]
-    pub async fn set_expires(&mut self, key: &str, value: Bytes, expire: Duration) -> crate::Result<()> {
+    #[instrument(skip(self, value))]
+    pub async fn set_expires(
+        &mut self,
+        key: &str,
+        value: impl ToArgs,
+        expire: Duration,
+    ) -> crate::Result<()> {
+        let value = single_arg(value)?;
+        self.invalidate(key);
         self.set_cmd(Set::new(key, value, Some(expire))).await
     }
 
+    /// Set `key` to `value` and return the value it replaced, `None` if the key didn't exist. The
+    /// expiration of the key is dropped.
+    #[instrument(skip(self))]
+    pub async fn get_set(&mut self, key: &str, value: Bytes) -> crate::Result<Option<Bytes>> {
+        self.invalidate(key);
+        self.swap_cmd(GetSet::new(key, value).into_frame()).await
+    }
+
+    /// Set `key` to `new` only if its current value is `expected`. Returns the current value, the
+    /// swap happened if it is equal to `expected`.
+    #[instrument(skip(self))]
+    pub async fn compare_and_swap(
+        &mut self,
+        key: &str,
+        expected: Bytes,
+        new: Bytes,
+    ) -> crate::Result<Option<Bytes>> {
+        self.invalidate(key);
+        self.swap_cmd(Cas::new(key, expected, new).into_frame()).await
+    }
+
+    async fn swap_cmd(&mut self, frame: Frame) -> crate::Result<Option<Bytes>> {
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Bulk(value) => Ok(Some(value)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Remove `keys`, returns the number of keys which existed
+    #[instrument(skip(self))]
+    pub async fn del(&mut self, keys: &[String]) -> crate::Result<u64> {
+        for key in keys {
+            self.invalidate(key);
+        }
+        self.integer_cmd(Del::new(keys.to_vec()).into_frame()).await
+    }
+
+    /// Number of `keys` which exist, a key given several times is counted as many times
+    #[instrument(skip(self))]
+    pub async fn exists(&mut self, keys: &[String]) -> crate::Result<u64> {
+        self.integer_cmd(Exists::new(keys.to_vec()).into_frame()).await
+    }
+
+    /// Expire `key` after `ttl`, rounded down to the second. Returns `false` if the key doesn't
+    /// exist.
+    #[instrument(skip(self))]
+    pub async fn expire(&mut self, key: &str, ttl: Duration) -> crate::Result<bool> {
+        self.invalidate(key);
+        self.integer_cmd(Expire::new(key, ttl).into_frame()).await.map(|set| set == 1)
+    }
+
+    /// Increment the counter held by `key` and return its new value
+    #[instrument(skip(self))]
+    pub async fn incr(&mut self, key: &str) -> crate::Result<i64> {
+        self.invalidate(key);
+        let frame = Incr::new(key).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(n) => Ok(n),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Keys matching the glob-style `pattern`. The server walks the whole key space.
+    #[instrument(skip(self))]
+    pub async fn keys(&mut self, pattern: &str) -> crate::Result<Vec<String>> {
+        let frame = Keys::new(pattern).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        Vec::from_frame(response)
+    }
+
+    /// Serialize the value of `key` in the RDB format of Redis, `None` if the key doesn't exist
+    #[instrument(skip(self))]
+    pub async fn dump(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
+        self.swap_cmd(Dump::new(key).into_frame()).await
+    }
+
+    /// Create `key` from the payload of `dump`, expiring after `ttl` if given. Fails with a
+    /// `BUSYKEY` error if the key exists, unless `replace` is set.
+    #[instrument(skip(self, payload))]
+    pub async fn restore(
+        &mut self,
+        key: &str,
+        payload: Bytes,
+        ttl: Option<Duration>,
+        replace: bool,
+    ) -> crate::Result<()> {
+        self.invalidate(key);
+        let mut cmd = Restore::new(key, payload, ttl);
+        if replace {
+            cmd = cmd.replace();
+        }
+        self.ok_cmd(cmd.into_frame()).await
+    }
+
+    /// Write the key space of the server to its RDB file, see `SAVE`
+    #[instrument(skip(self))]
+    pub async fn save(&mut self) -> crate::Result<()> {
+        self.ok_cmd(Save::new().into_frame()).await
+    }
+
+    async fn ok_cmd(&mut self, frame: Frame) -> crate::Result<()> {
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(resp) if resp == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Set or clear the bit at `offset` of the value of `key`. Returns the previous value of the bit.
+    #[instrument(skip(self))]
+    pub async fn set_bit(&mut self, key: &str, offset: u64, bit: bool) -> crate::Result<bool> {
+        self.invalidate(key);
+        self.integer_cmd(SetBit::new(key, offset, bit).into_frame()).await.map(|prev| prev == 1)
+    }
+
+    /// Value of the bit at `offset` of the value of `key`
+    #[instrument(skip(self))]
+    pub async fn get_bit(&mut self, key: &str, offset: u64) -> crate::Result<bool> {
+        self.integer_cmd(GetBit::new(key, offset).into_frame()).await.map(|bit| bit == 1)
+    }
+
+    /// Number of bits set in the value of `key`
+    #[instrument(skip(self))]
+    pub async fn bit_count(&mut self, key: &str) -> crate::Result<u64> {
+        self.integer_cmd(BitCount::new(key).into_frame()).await
+    }
+
+    async fn integer_cmd(&mut self, frame: Frame) -> crate::Result<u64> {
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(n) => Ok(n as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
     async fn set_cmd(&mut self, cmd: Set) -> crate::Result<()> {
         let frame = cmd.into_frame();
-        debug!(request = ?frame);
+        debug!(request = %frame);
 
         self.connection.write_frame(&frame).await?;
 
@@ -68,17 +418,1280 @@ impl Client {
         }
     }
 
-    async fn read_response(&mut self) -> Result<Frame> {
-        let response = self.connection.read_frame().await?;
-        debug!(?response);
+    /// Reserve `key` for `ttl`. Unless `confirm` is called before it expires, the payload is
+    /// pushed to the default dead-letter list, see `lpop`.
+    #[instrument(skip(self))]
+    pub async fn reserve(&mut self, key: &str, payload: Bytes, ttl: Duration) -> crate::Result<()> {
+        self.invalidate(key);
+        let frame = Reserve::new(key, ttl, payload).into_frame();
+        debug!(request = %frame);
 
-        match response {
-            Some(Frame::Error(msg)) => Err(msg.into()),
-            Some(frame) => Ok(frame),
-            None => {
-                let err = std::io::Error::new(ErrorKind::ConnectionReset, "connection reset by server");
-                Err(err.into())
-            }
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(resp) if resp == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Confirm the reservation on `key`. Returns `false` if there was no pending reservation.
+    #[instrument(skip(self))]
+    pub async fn confirm(&mut self, key: &str) -> crate::Result<bool> {
+        let frame = Confirm::new(key).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(n) => Ok(n == 1),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Insert `elements` at the head of the list `key`, the last one ending up first. Returns
+    /// the length of the list.
+    #[instrument(skip(self, elements))]
+    pub async fn lpush(&mut self, key: &str, elements: Vec<Bytes>) -> crate::Result<u64> {
+        self.invalidate(key);
+        let frame = LPush::new(key, elements).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(len) => Ok(len as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Insert `elements` at the tail of the list `key`. Returns the length of the list.
+    #[instrument(skip(self, elements))]
+    pub async fn rpush(&mut self, key: &str, elements: Vec<Bytes>) -> crate::Result<u64> {
+        self.invalidate(key);
+        let frame = RPush::new(key, elements).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(len) => Ok(len as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Remove and return the first element of the list `key`, `None` if the key doesn't exist
+    #[instrument(skip(self))]
+    pub async fn lpop(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
+        self.invalidate(key);
+        let frame = LPop::new(key).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        Option::from_frame(response)
+    }
+
+    /// Remove and return the last element of the list `key`, `None` if the key doesn't exist
+    #[instrument(skip(self))]
+    pub async fn rpop(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
+        self.invalidate(key);
+        let frame = RPop::new(key).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        Option::from_frame(response)
+    }
+
+    /// Remove and return the first element of the first non-empty list among `keys`, blocking
+    /// until an element is pushed if they are all empty. Returns the key along with the element,
+    /// `None` once `timeout` elapses. A zero `timeout` blocks forever.
+    #[instrument(skip(self))]
+    pub async fn blpop(
+        &mut self,
+        keys: &[String],
+        timeout: Duration,
+    ) -> crate::Result<Option<(String, Bytes)>> {
+        let frame = BLPop::new(keys.to_vec(), timeout).into_frame();
+        self.blocking_pop_cmd(keys, frame, timeout).await
+    }
+
+    /// Remove and return the last element of the first non-empty list among `keys`, see `blpop`
+    #[instrument(skip(self))]
+    pub async fn brpop(
+        &mut self,
+        keys: &[String],
+        timeout: Duration,
+    ) -> crate::Result<Option<(String, Bytes)>> {
+        let frame = BRPop::new(keys.to_vec(), timeout).into_frame();
+        self.blocking_pop_cmd(keys, frame, timeout).await
+    }
+
+    async fn blocking_pop_cmd(
+        &mut self,
+        keys: &[String],
+        frame: Frame,
+        timeout: Duration,
+    ) -> crate::Result<Option<(String, Bytes)>> {
+        for key in keys {
+            self.invalidate(key);
+        }
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        // the server may hold the reply for `timeout`, on top of the request timeout
+        let request_timeout = self.timeout;
+        self.timeout = request_timeout
+            .filter(|_| !timeout.is_zero())
+            .map(|request_timeout| request_timeout + timeout);
+        let response = self.read_response().await;
+        self.timeout = request_timeout;
+        Option::from_frame(response?)
+    }
+
+    /// Number of elements of the list `key`
+    #[instrument(skip(self))]
+    pub async fn llen(&mut self, key: &str) -> crate::Result<u64> {
+        let frame = LLen::new(key).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(len) => Ok(len as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Elements of the list `key` from `start` to `stop` included, negative indexes counting
+    /// from the tail
+    #[instrument(skip(self))]
+    pub async fn lrange(&mut self, key: &str, start: i64, stop: i64) -> crate::Result<Vec<Bytes>> {
+        let frame = LRange::new(key, start, stop).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        Vec::from_frame(response)
+    }
+
+    /// Index of the first element of the list `key` equal to `element`, `None` if there is none
+    #[instrument(skip(self, element))]
+    pub async fn lpos(&mut self, key: &str, element: Bytes) -> crate::Result<Option<u64>> {
+        let frame = LPos::new(key, element).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        Option::from_frame(response)
+    }
+
+    /// Insert `element` before the first element of the list `key` equal to `pivot`. Returns the
+    /// length of the list, `0` if the key doesn't exist, or `None` if `pivot` isn't found.
+    #[instrument(skip(self, pivot, element))]
+    pub async fn linsert_before(
+        &mut self,
+        key: &str,
+        pivot: Bytes,
+        element: Bytes,
+    ) -> crate::Result<Option<u64>> {
+        self.invalidate(key);
+        self.linsert_cmd(LInsert::before(key, pivot, element).into_frame())
+            .await
+    }
+
+    /// Insert `element` after the first element of the list `key` equal to `pivot`, see
+    /// `linsert_before`
+    #[instrument(skip(self, pivot, element))]
+    pub async fn linsert_after(
+        &mut self,
+        key: &str,
+        pivot: Bytes,
+        element: Bytes,
+    ) -> crate::Result<Option<u64>> {
+        self.invalidate(key);
+        self.linsert_cmd(LInsert::after(key, pivot, element).into_frame())
+            .await
+    }
+
+    async fn linsert_cmd(&mut self, frame: Frame) -> crate::Result<Option<u64>> {
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(-1) => Ok(None),
+            Frame::Integer(len) => Ok(Some(len as u64)),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Remove the elements of the list `key` equal to `element`: the first `count` ones if
+    /// positive, the last ones if negative, all of them if `0`. Returns the number of elements
+    /// removed.
+    #[instrument(skip(self, element))]
+    pub async fn lrem(&mut self, key: &str, count: i64, element: Bytes) -> crate::Result<u64> {
+        self.invalidate(key);
+        let frame = LRem::new(key, count, element).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(removed) => Ok(removed as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Replace the element at `index` of the list `key`, negative indexes counting from the
+    /// tail. Fails if the key doesn't exist or the index is out of range.
+    #[instrument(skip(self, element))]
+    pub async fn lset(&mut self, key: &str, index: i64, element: Bytes) -> crate::Result<()> {
+        self.invalidate(key);
+        let frame = LSet::new(key, index, element).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(resp) if resp == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Set the `fields` of the hash `key` to their values. Returns the number of fields added.
+    #[instrument(skip(self, fields))]
+    pub async fn hset(&mut self, key: &str, fields: Vec<(Bytes, Bytes)>) -> crate::Result<u64> {
+        self.invalidate(key);
+        let frame = HSet::new(key, fields).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(added) => Ok(added as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Value of `field` in the hash `key`, `None` if either doesn't exist
+    #[instrument(skip(self, field))]
+    pub async fn hget(&mut self, key: &str, field: Bytes) -> crate::Result<Option<Bytes>> {
+        let frame = HGet::new(key, field).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        Option::from_frame(response)
+    }
+
+    /// Remove the `fields` of the hash `key`. Returns the number of fields removed.
+    #[instrument(skip(self, fields))]
+    pub async fn hdel(&mut self, key: &str, fields: Vec<Bytes>) -> crate::Result<u64> {
+        self.invalidate(key);
+        let frame = HDel::new(key, fields).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(removed) => Ok(removed as u64),
+            frame => Err(frame.to_error()),
         }
     }
+
+    /// Number of fields of the hash `key`, `0` if the key doesn't exist
+    #[instrument(skip(self))]
+    pub async fn hlen(&mut self, key: &str) -> crate::Result<u64> {
+        let frame = HLen::new(key).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(len) => Ok(len as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Every field of the hash `key` along with its value
+    #[instrument(skip(self))]
+    pub async fn hgetall(&mut self, key: &str) -> crate::Result<Vec<(Bytes, Bytes)>> {
+        let frame = HGetAll::new(key).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        Ok(field_pairs(Vec::from_frame(response)?))
+    }
+
+    /// Add `delta` to the integer held by `field` of the hash `key`. Returns the new value.
+    #[instrument(skip(self, field))]
+    pub async fn hincrby(&mut self, key: &str, field: Bytes, delta: i64) -> crate::Result<i64> {
+        self.invalidate(key);
+        let frame = HIncrBy::new(key, field, delta).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(value) => Ok(value),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Add `delta` to the number held by `field` of the hash `key`. Returns the new value.
+    #[instrument(skip(self, field))]
+    pub async fn hincrbyfloat(
+        &mut self,
+        key: &str,
+        field: Bytes,
+        delta: f64,
+    ) -> crate::Result<f64> {
+        self.invalidate(key);
+        let frame = HIncrByFloat::new(key, field, delta).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        f64::from_frame(response)
+    }
+
+    /// Fields of the hash `key` picked at random: `count` distinct ones at most if it is positive,
+    /// `-count` possibly repeated ones otherwise
+    #[instrument(skip(self))]
+    pub async fn hrandfield(&mut self, key: &str, count: i64) -> crate::Result<Vec<Bytes>> {
+        let frame = HRandField::new(key).count(count).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        Vec::from_frame(response)
+    }
+
+    /// Up to `count` fields of the hash `key` from `cursor` on, only those matching the glob
+    /// `pattern` being returned along with their values. Returns the cursor of the next call, `0`
+    /// once every field was walked.
+    #[instrument(skip(self))]
+    pub async fn hscan(
+        &mut self,
+        key: &str,
+        cursor: u64,
+        pattern: Option<&str>,
+        count: u64,
+    ) -> crate::Result<(u64, Vec<(Bytes, Bytes)>)> {
+        let mut cmd = HScan::new(key, cursor).count(count);
+        if let Some(pattern) = pattern {
+            cmd = cmd.pattern(Bytes::copy_from_slice(pattern.as_bytes()));
+        }
+        let frame = cmd.into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        let (next, fields) = FromFrame::from_frame(response)?;
+        Ok((next, field_pairs(fields)))
+    }
+
+    /// Set the scores of the `members` of the sorted set `key`. Returns the number of members
+    /// added.
+    #[instrument(skip(self, members))]
+    pub async fn zadd(&mut self, key: &str, members: Vec<(f64, Bytes)>) -> crate::Result<u64> {
+        self.invalidate(key);
+        let frame = ZAdd::new(key, members).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(added) => Ok(added as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Score of `member` in the sorted set `key`, `None` if either doesn't exist
+    #[instrument(skip(self, member))]
+    pub async fn zscore(&mut self, key: &str, member: Bytes) -> crate::Result<Option<f64>> {
+        let frame = ZScore::new(key, member).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        Option::from_frame(response)
+    }
+
+    /// Remove the `members` of the sorted set `key`. Returns the number of members removed.
+    #[instrument(skip(self, members))]
+    pub async fn zrem(&mut self, key: &str, members: Vec<Bytes>) -> crate::Result<u64> {
+        self.invalidate(key);
+        let frame = ZRem::new(key, members).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(removed) => Ok(removed as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Number of members of the sorted set `key`, `0` if the key doesn't exist
+    #[instrument(skip(self))]
+    pub async fn zcard(&mut self, key: &str) -> crate::Result<u64> {
+        let frame = ZCard::new(key).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(len) => Ok(len as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Members of the sorted set `key` from rank `start` to `stop` included, lowest score first.
+    /// Negative ranks count from the highest score.
+    #[instrument(skip(self))]
+    pub async fn zrange(&mut self, key: &str, start: i64, stop: i64) -> crate::Result<Vec<Bytes>> {
+        let frame = ZRange::new(key, start, stop).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        Vec::from_frame(response)
+    }
+
+    /// Add the `positions` of members, as longitude, latitude and member, to the sorted set `key`.
+    /// Returns the number of members added.
+    #[instrument(skip(self, positions))]
+    pub async fn geoadd(
+        &mut self,
+        key: &str,
+        positions: Vec<(f64, f64, Bytes)>,
+    ) -> crate::Result<u64> {
+        self.invalidate(key);
+        let frame = GeoAdd::new(key, positions).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(added) => Ok(added as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Distance in meters between the positions of `from` and `to` in the sorted set `key`, `None`
+    /// if any of them doesn't exist
+    #[instrument(skip(self, from, to))]
+    pub async fn geodist(
+        &mut self,
+        key: &str,
+        from: Bytes,
+        to: Bytes,
+    ) -> crate::Result<Option<f64>> {
+        let frame = GeoDist::new(key, from, to).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        Option::from_frame(response)
+    }
+
+    /// Members of the sorted set `key` positioned within `radius` meters of `longitude` and
+    /// `latitude`, nearest first
+    #[instrument(skip(self))]
+    pub async fn geosearch_radius(
+        &mut self,
+        key: &str,
+        longitude: f64,
+        latitude: f64,
+        radius: f64,
+    ) -> crate::Result<Vec<Bytes>> {
+        let frame = GeoSearch::radius(key, longitude, latitude, radius).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        Vec::from_frame(response)
+    }
+
+    /// Take the lock `key` for `ttl` on behalf of `token`, or extend it if `token` already holds
+    /// it. Returns `false` if another token holds the lock.
+    #[instrument(skip(self))]
+    pub async fn lock(&mut self, key: &str, token: Bytes, ttl: Duration) -> crate::Result<bool> {
+        self.invalidate(key);
+        self.lock_cmd(Lock::new(key, token, ttl).into_frame()).await
+    }
+
+    /// Take the lock `key` for `ttl` on behalf of `token` only if it is free
+    #[instrument(skip(self))]
+    pub async fn lock_nx(&mut self, key: &str, token: Bytes, ttl: Duration) -> crate::Result<bool> {
+        self.invalidate(key);
+        self.lock_cmd(Lock::new(key, token, ttl).nx().into_frame()).await
+    }
+
+    /// Release the lock `key` held by `token`. Returns `false` if `token` doesn't hold the lock.
+    #[instrument(skip(self))]
+    pub async fn unlock(&mut self, key: &str, token: Bytes) -> crate::Result<bool> {
+        self.invalidate(key);
+        self.lock_cmd(Unlock::new(key, token).into_frame()).await
+    }
+
+    async fn lock_cmd(&mut self, frame: Frame) -> crate::Result<bool> {
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(n) => Ok(n == 1),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Sequence number of the latest write applied by the server
+    #[instrument(skip(self))]
+    pub async fn seq(&mut self) -> crate::Result<u64> {
+        let frame = Seq::new().into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(seq) => Ok(seq as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Address of the current primary of `name`, asked to a sentinel. `None` if the sentinel
+    /// doesn't watch `name`.
+    #[instrument(skip(self))]
+    pub async fn sentinel_primary(&mut self, name: &str) -> crate::Result<Option<SocketAddr>> {
+        let frame = Sentinel::get_master_addr_by_name(name).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        match Option::<(String, u16)>::from_frame(response)? {
+            Some((ip, port)) => {
+                let ip: IpAddr = ip.parse().map_err(|_| format!("invalid primary address {}", ip))?;
+                Ok(Some(SocketAddr::new(ip, port)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Fetch the server `INFO` report, optionally restricted to a single section
+    #[instrument(skip(self))]
+    pub async fn info(&mut self, section: Option<&str>) -> crate::Result<String> {
+        let frame = Info::new(section.map(|s| s.to_string())).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Bulk(value) => Ok(String::from_utf8(value.to_vec())?),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Delivery counters of a pub/sub channel as `(name, value)` pairs, `None` if the channel
+    /// doesn't exist.
+    #[instrument(skip(self))]
+    pub async fn channel_stats(&mut self, channel: &str) -> crate::Result<Option<Vec<(String, u64)>>> {
+        let frame = Pubsub::stats(channel).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Null => Ok(None),
+            Frame::Array(parts) => {
+                let mut stats = Vec::with_capacity(parts.len() / 2);
+                for pair in parts.chunks(2) {
+                    match pair {
+                        [Frame::Bulk(name), Frame::Integer(value)] => {
+                            stats.push((String::from_utf8(name.to_vec())?, *value as u64));
+                        }
+                        _ => return Err(Error::Protocol("protocol error; invalid channel stats".into())),
+                    }
+                }
+                Ok(Some(stats))
+            }
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Histogram of the time left before the keys expire, and the number of keys expiring in each
+    /// of the next `minutes`.
+    #[instrument(skip(self))]
+    pub async fn ttl_stats(&mut self, minutes: u64) -> crate::Result<ExpiryStats> {
+        let frame = TtlStats::new(minutes).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let invalid = || Error::Protocol("protocol error; invalid ttl stats".into());
+        let parts = match self.read_response().await? {
+            Frame::Array(parts) => parts,
+            frame => return Err(frame.to_error()),
+        };
+        match parts.as_slice() {
+            [_, Frame::Integer(keys_with_ttl), _, Frame::Integer(keys_without_ttl), _, Frame::Array(histogram), _, Frame::Array(forecast)] =>
+            {
+                let mut buckets = Vec::with_capacity(histogram.len() / 2);
+                for pair in histogram.chunks(2) {
+                    match pair {
+                        [Frame::Bulk(label), Frame::Integer(count)] => {
+                            buckets.push((String::from_utf8(label.to_vec())?, *count as u64));
+                        }
+                        _ => return Err(invalid()),
+                    }
+                }
+                let forecast = forecast
+                    .iter()
+                    .map(|count| match count {
+                        Frame::Integer(count) => Ok(*count as u64),
+                        _ => Err(invalid()),
+                    })
+                    .collect::<Result<_>>()?;
+
+                Ok(ExpiryStats {
+                    keys_with_ttl: *keys_with_ttl as u64,
+                    keys_without_ttl: *keys_without_ttl as u64,
+                    histogram: buckets,
+                    forecast,
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Authenticate the connection, as `username` if given
+    #[instrument(skip(self, password))]
+    pub async fn auth(&mut self, username: Option<&str>, password: &str) -> crate::Result<()> {
+        let frame = Auth::new(username.map(str::to_string), password).into_frame();
+        debug!(username);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(resp) if resp == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Create or change `username` with `rules`, see `ACL SETUSER`
+    #[instrument(skip(self, rules))]
+    pub async fn acl_set_user(&mut self, username: &str, rules: &[&str]) -> crate::Result<()> {
+        let rules = rules.iter().map(|rule| rule.to_string()).collect();
+        let frame = Acl::set_user(username, rules).into_frame();
+        debug!(username);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(resp) if resp == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Rules of every user, one string per user
+    #[instrument(skip(self))]
+    pub async fn acl_list(&mut self) -> crate::Result<Vec<String>> {
+        let frame = Acl::list().into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        Vec::from_frame(response)
+    }
+
+    /// User the connection is authenticated as
+    #[instrument(skip(self))]
+    pub async fn acl_whoami(&mut self) -> crate::Result<String> {
+        let frame = Acl::whoami().into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        String::from_frame(response)
+    }
+
+    /// Bring the connection back to its initial state
+    #[instrument(skip(self))]
+    pub async fn reset(&mut self) -> crate::Result<()> {
+        let frame = Reset::new().into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(resp) if resp == "RESET" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// End the session, the server closes the connection once it replied
+    #[instrument(skip(self))]
+    pub async fn quit(mut self) -> crate::Result<()> {
+        let frame = Quit::new().into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(resp) if resp == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Art along with the version of the server, a liveness check
+    #[instrument(skip(self))]
+    pub async fn lolwut(&mut self) -> crate::Result<String> {
+        let frame = Lolwut::new().into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        String::from_frame(response)
+    }
+
+    /// Check the server is responsive, `msg` is echoed back if given
+    #[instrument(skip(self))]
+    pub async fn ping(&mut self, msg: Option<Bytes>) -> crate::Result<Bytes> {
+        let frame = Ping::new(msg).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        let response = self.read_response().await?;
+        Bytes::from_frame(response)
+    }
+
+    /// Post `message` on `channel`, returns the number of subscribers it was sent to
+    #[instrument(skip(self))]
+    pub async fn publish(&mut self, channel: &str, message: Bytes) -> crate::Result<u64> {
+        self.integer_cmd(Publish::new(channel, message).into_frame()).await
+    }
+
+    /// Subscribe to `channels`, the client enters subscribe mode and becomes a `Subscriber`
+    #[instrument(skip(self))]
+    pub async fn subscribe(mut self, channels: Vec<String>) -> crate::Result<Subscriber> {
+        self.subscribe_cmd(&channels).await?;
+
+        Ok(Subscriber {
+            client: self,
+            subscribed_channels: channels,
+        })
+    }
+
+    async fn subscribe_cmd(&mut self, channels: &[String]) -> crate::Result<()> {
+        let frame = Subscribe::new(channels.to_vec()).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        for channel in channels {
+            match self.read_response().await? {
+                Frame::Array(parts) => match &parts[..] {
+                    [kind, name, ..] if *kind == "subscribe" && *name == channel.as_str() => {}
+                    _ => return Err(Frame::Array(parts).to_error()),
+                },
+                frame => return Err(frame.to_error()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a command the typed API doesn't cover yet and read its reply as a `T`. Each value of
+    /// `args` adds its arguments, tuples and slices add theirs in order.
+    ///
+    /// The keys written by the command aren't known, so the whole near cache is dropped.
+    ///
+    /// ```no_run
+    /// # async fn run(client: &mut redust::client::Client) -> redust::Result<()> {
+    /// let flipped: i64 = client.command(("setbit", "flags", 7, 1)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self, args))]
+    pub async fn command<T: FromFrame>(&mut self, args: impl ToArgs) -> crate::Result<T> {
+        T::from_frame(self.raw_command(command_frame(args)?).await?)
+    }
+
+    /// Send `frame` as is and return the reply. Error replies are returned as `Err`, like for the
+    /// typed commands.
+    ///
+    /// Only the name of the command is logged, the arguments may hold secrets such as the password
+    /// of `AUTH`.
+    #[instrument(skip(self, frame), fields(command = %command_name(&frame)))]
+    pub async fn raw_command(&mut self, frame: Frame) -> crate::Result<Frame> {
+        if let Some(cache) = self.near_cache.as_mut() {
+            cache.clear();
+        }
+
+        self.connection.write_frame(&frame).await?;
+        self.read_response().await
+    }
+
+    /// Drop the cached value of a key written through this client
+    fn invalidate(&mut self, key: &str) {
+        if let Some(cache) = self.near_cache.as_mut() {
+            cache.invalidate(key);
+        }
+    }
+
+    /// Name and value of the server settings matching the glob `pattern`
+    #[instrument(skip(self))]
+    pub async fn config_get(&mut self, pattern: &str) -> crate::Result<Vec<(String, String)>> {
+        let frame = Config::get(pattern).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Array(parts) => {
+                let mut params = Vec::with_capacity(parts.len() / 2);
+                for pair in parts.chunks(2) {
+                    match pair {
+                        [Frame::Bulk(name), Frame::Bulk(value)] => params.push((
+                            String::from_utf8(name.to_vec())?,
+                            String::from_utf8(value.to_vec())?,
+                        )),
+                        _ => return Err(Error::Protocol("protocol error; invalid config reply".into())),
+                    }
+                }
+                Ok(params)
+            }
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Change a server setting at runtime
+    #[instrument(skip(self))]
+    pub async fn config_set(&mut self, name: &str, value: &str) -> crate::Result<()> {
+        let frame = Config::set(name, value).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(resp) if resp == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Reset the statistics reported by `INFO`, such as the command statistics and latencies
+    #[instrument(skip(self))]
+    pub async fn config_resetstat(&mut self) -> crate::Result<()> {
+        let frame = Config::resetstat().into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(resp) if resp == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Append `chunk` to the byte log of `key`. Returns the offset following the appended bytes.
+    #[instrument(skip(self, chunk))]
+    pub async fn blog_append(&mut self, key: &str, chunk: Bytes) -> crate::Result<u64> {
+        let frame = BlogAppend::new(key, chunk).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(end) => Ok(end as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Read up to `len` bytes of the byte log of `key` starting at `offset`. Returns `None` if the
+    /// log doesn't exist.
+    #[instrument(skip(self))]
+    pub async fn blog_read(&mut self, key: &str, offset: u64, len: u64) -> crate::Result<Option<Bytes>> {
+        let frame = BlogRead::new(key, offset, len).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Bulk(data) => Ok(Some(data)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Latency of `command` at `percentile` as recorded by the server, `None` if the command
+    /// never ran
+    #[instrument(skip(self))]
+    pub async fn latency_percentile(&mut self, command: &str, percentile: f64) -> crate::Result<Option<Duration>> {
+        let frame = Latency::percentile(command, percentile).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(usec) => Ok(Some(Duration::from_micros(usec as u64))),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Latest spike of every command which reached the latency monitor threshold of the server
+    #[instrument(skip(self))]
+    pub async fn latency_latest(&mut self) -> crate::Result<Vec<LatencyEvent>> {
+        let frame = Latency::latest().into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Array(events) => events
+                .into_iter()
+                .map(|event| match event.into_array()?.as_slice() {
+                    [Frame::Bulk(command), Frame::Integer(time), Frame::Integer(latency), Frame::Integer(max)] => {
+                        Ok(LatencyEvent {
+                            command: String::from_utf8(command.to_vec())?,
+                            time: UNIX_EPOCH + Duration::from_secs(*time as u64),
+                            latency: Duration::from_millis(*latency as u64),
+                            max: Duration::from_millis(*max as u64),
+                        })
+                    }
+                    parts => Err(Frame::Array(parts.to_vec()).to_error()),
+                })
+                .collect(),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Latency spikes of `command` along with their time, oldest first
+    #[instrument(skip(self))]
+    pub async fn latency_history(&mut self, command: &str) -> crate::Result<Vec<(SystemTime, Duration)>> {
+        let frame = Latency::history(command).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Array(samples) => samples
+                .into_iter()
+                .map(|sample| match sample.into_array()?.as_slice() {
+                    [Frame::Integer(time), Frame::Integer(latency)] => {
+                        let time = UNIX_EPOCH + Duration::from_secs(*time as u64);
+                        Ok((time, Duration::from_millis(*latency as u64)))
+                    }
+                    parts => Err(Frame::Array(parts.to_vec()).to_error()),
+                })
+                .collect(),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// The latest `count` commands of the slow log of the server, 10 by default or all of them if
+    /// negative, latest first
+    #[instrument(skip(self))]
+    pub async fn slowlog_get(&mut self, count: Option<i64>) -> crate::Result<Vec<SlowLogEntry>> {
+        let frame = SlowLog::get(count).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Array(entries) => entries
+                .into_iter()
+                .map(|entry| match entry.into_array()?.as_slice() {
+                    [Frame::Integer(id), Frame::Integer(time), Frame::Integer(duration), Frame::Array(args), Frame::Bulk(client), Frame::Bulk(user)] => {
+                        Ok(SlowLogEntry {
+                            id: *id as u64,
+                            time: UNIX_EPOCH + Duration::from_secs(*time as u64),
+                            duration: Duration::from_micros(*duration as u64),
+                            args: args
+                                .iter()
+                                .map(|arg| match arg {
+                                    Frame::Bulk(arg) => Ok(String::from_utf8(arg.to_vec())?),
+                                    frame => Err(frame.to_error()),
+                                })
+                                .collect::<crate::Result<_>>()?,
+                            client: String::from_utf8(client.to_vec())?,
+                            user: String::from_utf8(user.to_vec())?,
+                        })
+                    }
+                    parts => Err(Frame::Array(parts.to_vec()).to_error()),
+                })
+                .collect(),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Number of commands in the slow log of the server
+    #[instrument(skip(self))]
+    pub async fn slowlog_len(&mut self) -> crate::Result<u64> {
+        let frame = SlowLog::len().into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(len) => Ok(len as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Drop the commands of the slow log of the server
+    #[instrument(skip(self))]
+    pub async fn slowlog_reset(&mut self) -> crate::Result<()> {
+        let frame = SlowLog::reset().into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(resp) if resp == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Estimated number of bytes held by `key` and its value, `None` if the key doesn't exist
+    #[instrument(skip(self))]
+    pub async fn memory_usage(&mut self, key: &str) -> crate::Result<Option<u64>> {
+        let frame = Memory::usage(key).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(bytes) => Ok(Some(bytes as u64)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Have the server wait `duration` before replying, to simulate a slow command. The server
+    /// must run with `enable-debug-command` on.
+    #[instrument(skip(self))]
+    pub async fn debug_sleep(&mut self, duration: Duration) -> crate::Result<()> {
+        let frame = Debug::sleep(duration).into_frame();
+        debug!(request = %frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(resp) if resp == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    async fn read_response(&mut self) -> Result<Frame> {
+        // the replies to the requests which timed out come first
+        while self.stale_replies > 0 {
+            let stale = self.read_frame().await?;
+            debug!(?stale, "skipped the reply of a request which timed out");
+            self.stale_replies -= 1;
+        }
+
+        let response = self.read_frame().await?;
+        debug!(?response);
+
+        match response {
+            Some(Frame::Error(msg)) => Err(Error::from_reply(msg)),
+            Some(frame) => Ok(frame),
+            None => Err(Error::ConnectionReset),
+        }
+    }
+
+    /// Read the next frame, within the timeout if any. On timeout, the reply is counted as stale.
+    async fn read_frame(&mut self) -> Result<Option<Frame>> {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return self.connection.read_frame().await,
+        };
+
+        // reading a frame is cancel safe, the bytes already received stay buffered
+        match time::timeout(timeout, self.connection.read_frame()).await {
+            Ok(res) => res,
+            Err(_) => {
+                self.stale_replies += 1;
+                Err(Error::Timeout)
+            }
+        }
+    }
+}
+
+impl Subscriber {
+    pub fn get_subscribed(&self) -> &[String] {
+        &self.subscribed_channels
+    }
+
+    /// Wait for the next message published on the subscribed channels. Returns `None` once the
+    /// server closes the connection.
+    ///
+    /// Messages the subscriber was too slow to receive are dropped by the server, which is
+    /// reported as `Error::Lagged`. The subscription is still usable after that error.
+    pub async fn next_message(&mut self) -> crate::Result<Option<Message>> {
+        // a subscriber may legitimately wait forever, the request timeout doesn't apply
+        let frame = match self.client.connection.read_frame().await? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+        debug!(%frame);
+
+        match frame {
+            Frame::Array(parts) => match &parts[..] {
+                [kind, Frame::Bulk(channel), Frame::Bulk(content)] if *kind == "message" => {
+                    Ok(Some(Message {
+                        channel: String::from_utf8(channel.to_vec())?,
+                        content: content.clone(),
+                    }))
+                }
+                [kind, Frame::Bulk(channel), Frame::Integer(missed)] if *kind == "lagged" => {
+                    Err(Error::Lagged {
+                        channel: String::from_utf8(channel.to_vec())?,
+                        missed: *missed as u64,
+                    })
+                }
+                _ => Err(Frame::Array(parts).to_error()),
+            },
+            Frame::Error(msg) => Err(Error::from_reply(msg)),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Convert the subscriber into a `Stream` of the messages. A lag is yielded as
+    /// `Err(Error::Lagged)` and the stream goes on, any other error ends it.
+    pub fn into_stream(mut self) -> impl Stream<Item = crate::Result<Message>> {
+        async_stream::stream! {
+            loop {
+                match self.next_message().await {
+                    Ok(Some(message)) => yield Ok(message),
+                    Ok(None) => break,
+                    Err(err @ Error::Lagged { .. }) => yield Err(err),
+                    Err(err) => {
+                        yield Err(err);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Subscribe to more channels
+    #[instrument(skip(self))]
+    pub async fn subscribe(&mut self, channels: &[String]) -> crate::Result<()> {
+        self.client.subscribe_cmd(channels).await?;
+        self.subscribed_channels.extend(channels.iter().cloned());
+        Ok(())
+    }
+
+    /// Leave every channel and return to a regular client. The messages published in the meantime
+    /// are discarded.
+    #[instrument(skip(self))]
+    pub async fn reset(mut self) -> crate::Result<Client> {
+        let frame = Reset::new().into_frame();
+        debug!(request = %frame);
+
+        self.client.connection.write_frame(&frame).await?;
+
+        loop {
+            match self.client.read_response().await? {
+                Frame::Simple(resp) if resp == "RESET" => return Ok(self.client),
+                // published before the server got the request
+                Frame::Array(parts) if parts.len() == 3 => {}
+                frame => return Err(frame.to_error()),
+            }
+        }
+    }
+
+    /// Unsubscribe from `channels`, or from every channel if it is empty
+    #[instrument(skip(self))]
+    pub async fn unsubscribe(&mut self, channels: &[String]) -> crate::Result<()> {
+        let frame = Unsubscribe::new(channels).into_frame();
+        debug!(request = %frame);
+
+        self.client.connection.write_frame(&frame).await?;
+
+        let replies = match channels.len() {
+            0 => self.subscribed_channels.len(),
+            n => n,
+        };
+        for _ in 0..replies {
+            match self.client.read_response().await? {
+                Frame::Array(parts) => match &parts[..] {
+                    [kind, Frame::Bulk(channel), ..] if *kind == "unsubscribe" => {
+                        self.subscribed_channels.retain(|name| name.as_bytes() != &channel[..]);
+                    }
+                    _ => return Err(Frame::Array(parts).to_error()),
+                },
+                frame => return Err(frame.to_error()),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Build the request of a command from its name and arguments
+fn command_frame(args: impl ToArgs) -> Result<Frame> {
+    let args = args.to_args();
+    if args.is_empty() {
+        return Err("a command needs at least a name".into());
+    }
+
+    let mut frame = Vec::new();
+    for arg in args {
+        frame.push(Frame::Bulk(arg));
+    }
+    Ok(Frame::Array(frame))
+}
+
+/// Name of the command requested by `frame`, empty if it isn't a request
+fn command_name(frame: &Frame) -> String {
+    match frame {
+        Frame::Array(parts) => match parts.first() {
+            Some(Frame::Bulk(name)) => String::from_utf8_lossy(name).to_lowercase(),
+            Some(Frame::Simple(name)) => name.to_lowercase(),
+            _ => String::new(),
+        },
+        _ => String::new(),
+    }
+}
+
+/// The argument for a value sent as is, such as the value of `SET`
+fn single_arg(value: impl ToArgs) -> Result<Bytes> {
+    let mut args = value.to_args();
+    match args.len() {
+        1 => Ok(args.pop().unwrap()),
+        n => Err(format!("a value must convert to a single argument, got {}", n).into()),
+    }
+}
+
+/// Fields and their values from a reply alternating them, as `HGETALL` replies
+fn field_pairs(flat: Vec<Bytes>) -> Vec<(Bytes, Bytes)> {
+    let mut flat = flat.into_iter();
+    let mut fields = vec![];
+    while let (Some(field), Some(value)) = (flat.next(), flat.next()) {
+        fields.push((field, value));
+    }
+    fields
 }
@@ -0,0 +1,173 @@
+//! Options applied when connecting a `Client`.
+
+use super::Client;
+use crate::{socket, Connection, Error, Frame, Result};
+
+use std::io;
+use std::time::Duration;
+use tokio::net::{self, TcpSocket, TcpStream, ToSocketAddrs};
+use tokio::time;
+use tracing::debug;
+
+/// Connects a `Client` with the given options, `client::connect` uses the defaults.
+///
+/// The credentials, name and database are set up as soon as the connection is established, with
+/// `AUTH`, `CLIENT SETNAME` and `SELECT`. Connecting fails if the server refuses any of them.
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    username: Option<String>,
+    password: Option<String>,
+    name: Option<String>,
+    database: Option<u64>,
+    nodelay: bool,
+    keepalive: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> ClientBuilder {
+        ClientBuilder {
+            username: None,
+            password: None,
+            name: None,
+            database: None,
+            nodelay: true,
+            keepalive: None,
+            connect_timeout: None,
+            timeout: None,
+        }
+    }
+}
+
+impl ClientBuilder {
+    pub fn new() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// Authenticate with `password`, as `username` if one is set
+    pub fn password(mut self, password: impl ToString) -> ClientBuilder {
+        self.password = Some(password.to_string());
+        self
+    }
+
+    /// User to authenticate as, only used along with `password`
+    pub fn username(mut self, username: impl ToString) -> ClientBuilder {
+        self.username = Some(username.to_string());
+        self
+    }
+
+    /// Name of the connection, as reported by `CLIENT LIST`
+    pub fn name(mut self, name: impl ToString) -> ClientBuilder {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Database selected once connected
+    pub fn database(mut self, database: u64) -> ClientBuilder {
+        self.database = Some(database);
+        self
+    }
+
+    /// Disable Nagle's algorithm, so small requests are sent right away. On by default.
+    pub fn nodelay(mut self, enabled: bool) -> ClientBuilder {
+        self.nodelay = enabled;
+        self
+    }
+
+    /// Enable TCP keepalive probes, so a dead server is eventually noticed on an idle connection.
+    /// Probes start after 300 seconds of inactivity, see `keepalive_idle`.
+    pub fn keepalive(mut self, enabled: bool) -> ClientBuilder {
+        self.keepalive = Some(socket::DEFAULT_KEEPALIVE).filter(|_| enabled);
+        self
+    }
+
+    /// Enable TCP keepalive probes once the connection is idle for `idle`
+    pub fn keepalive_idle(mut self, idle: Duration) -> ClientBuilder {
+        self.keepalive = Some(idle);
+        self
+    }
+
+    /// Fail with `Error::Timeout` if the connection isn't established and set up within `timeout`
+    pub fn connect_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Timeout of the requests, see `Client::set_timeout`
+    pub fn timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Connect to the server at `addr`
+    pub async fn connect<T: ToSocketAddrs>(self, addr: T) -> Result<Client> {
+        match self.connect_timeout {
+            Some(timeout) => match time::timeout(timeout, self.establish(addr)).await {
+                Ok(res) => res,
+                Err(_) => Err(Error::Timeout),
+            },
+            None => self.establish(addr).await,
+        }
+    }
+
+    async fn establish<T: ToSocketAddrs>(&self, addr: T) -> Result<Client> {
+        let socket = self.open(addr).await?;
+        let mut client = Client {
+            connection: Connection::new(socket),
+            near_cache: None,
+            timeout: self.timeout,
+            stale_replies: 0,
+        };
+
+        if let Some(password) = &self.password {
+            let mut args = vec!["auth"];
+            args.extend(self.username.as_deref());
+            args.push(password);
+            handshake(&mut client, &args).await?;
+        }
+        if let Some(name) = &self.name {
+            handshake(&mut client, &["client", "setname", name]).await?;
+        }
+        if let Some(database) = self.database {
+            handshake(&mut client, &["select", &database.to_string()]).await?;
+        }
+        Ok(client)
+    }
+
+    /// Open the TCP connection, trying each address `addr` resolves to
+    async fn open<T: ToSocketAddrs>(&self, addr: T) -> io::Result<TcpStream> {
+        let mut last_err = None;
+
+        for addr in net::lookup_host(addr).await? {
+            let socket = if addr.is_ipv4() {
+                TcpSocket::new_v4()?
+            } else {
+                TcpSocket::new_v6()?
+            };
+
+            match socket.connect(addr).await {
+                Ok(stream) => {
+                    socket::configure(&stream, self.nodelay, self.keepalive)?;
+                    return Ok(stream);
+                }
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "could not resolve to any address")
+        }))
+    }
+}
+
+/// Send a setup command, the server must reply `OK`
+async fn handshake(client: &mut Client, args: &[&str]) -> Result<()> {
+    let frame = Frame::Array(args.iter().map(|arg| Frame::from(*arg)).collect());
+    debug!(command = args[0], "connection setup");
+
+    client.connection.write_frame(&frame).await?;
+    match client.read_response().await? {
+        Frame::Simple(resp) if resp == "OK" => Ok(()),
+        frame => Err(frame.to_error()),
+    }
+}
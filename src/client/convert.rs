@@ -0,0 +1,239 @@
+//! Conversions between Rust values and the arguments and replies of commands.
+//!
+//! `ToArgs` turns a value into command arguments: strings and bytes as is, numbers in their
+//! decimal form. `FromFrame` reads a reply into a value: `Option<T>` maps nil to `None`, `Vec<T>`
+//! and tuples read arrays. Numbers are read from integer replies as well as from bulk strings, so
+//! `client.get::<i64>("counter")` works on a value written with `client.set("counter", 42)`.
+
+use crate::{Error, Frame, Result};
+
+use bytes::Bytes;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// A value which can be read from a reply
+pub trait FromFrame: Sized {
+    fn from_frame(frame: Frame) -> Result<Self>;
+}
+
+/// A value which can be sent as command arguments
+pub trait ToArgs {
+    /// Append the arguments for the value to `out`
+    fn write_args(&self, out: &mut Vec<Bytes>);
+
+    fn to_args(&self) -> Vec<Bytes> {
+        let mut out = vec![];
+        self.write_args(&mut out);
+        out
+    }
+}
+
+fn mismatch<T>(frame: &Frame) -> Error {
+    format!("cannot convert {} to {}", frame, std::any::type_name::<T>()).into()
+}
+
+impl FromFrame for Frame {
+    fn from_frame(frame: Frame) -> Result<Frame> {
+        Ok(frame)
+    }
+}
+
+impl FromFrame for Bytes {
+    fn from_frame(frame: Frame) -> Result<Bytes> {
+        match frame {
+            Frame::Bulk(data) => Ok(data),
+            Frame::Simple(data) => Ok(data.into()),
+            Frame::Integer(n) => Ok(n.to_string().into()),
+            frame => Err(mismatch::<Bytes>(&frame)),
+        }
+    }
+}
+
+impl FromFrame for Vec<u8> {
+    fn from_frame(frame: Frame) -> Result<Vec<u8>> {
+        Bytes::from_frame(frame).map(|data| data.to_vec())
+    }
+}
+
+impl FromFrame for String {
+    fn from_frame(frame: Frame) -> Result<String> {
+        Ok(String::from_utf8(Vec::from_frame(frame)?)?)
+    }
+}
+
+impl FromFrame for bool {
+    fn from_frame(frame: Frame) -> Result<bool> {
+        match frame {
+            Frame::Integer(n) => Ok(n != 0),
+            Frame::Simple(resp) if resp == "OK" => Ok(true),
+            frame => Ok(i64::from_frame(frame)? != 0),
+        }
+    }
+}
+
+/// Parse the text of a bulk or simple string reply
+fn parse<T: FromStr>(frame: Frame) -> Result<T> {
+    let text = match &frame {
+        Frame::Bulk(data) => std::str::from_utf8(data).ok(),
+        Frame::Simple(data) => Some(data.as_str()),
+        _ => None,
+    };
+    text.and_then(|text| text.parse().ok())
+        .ok_or_else(|| mismatch::<T>(&frame))
+}
+
+macro_rules! integer {
+    ($($ty:ty),*) => {$(
+        impl FromFrame for $ty {
+            fn from_frame(frame: Frame) -> Result<$ty> {
+                match frame {
+                    Frame::Integer(n) => <$ty>::try_from(n).map_err(|_| mismatch::<$ty>(&frame)),
+                    frame => parse(frame),
+                }
+            }
+        }
+
+        impl ToArgs for $ty {
+            fn write_args(&self, out: &mut Vec<Bytes>) {
+                out.push(Bytes::copy_from_slice(itoa::Buffer::new().format(*self).as_bytes()));
+            }
+        }
+    )*};
+}
+
+// `u8` is left out, so `Vec<u8>` and `&[u8]` are bytes rather than arrays of numbers
+integer!(i8, i16, u16, i32, u32, i64, u64, isize, usize);
+
+macro_rules! float {
+    ($($ty:ty),*) => {$(
+        impl FromFrame for $ty {
+            fn from_frame(frame: Frame) -> Result<$ty> {
+                match frame {
+                    Frame::Integer(n) => Ok(n as $ty),
+                    frame => parse(frame),
+                }
+            }
+        }
+
+        impl ToArgs for $ty {
+            fn write_args(&self, out: &mut Vec<Bytes>) {
+                out.push(self.to_string().into());
+            }
+        }
+    )*};
+}
+
+float!(f32, f64);
+
+impl<T: FromFrame> FromFrame for Option<T> {
+    fn from_frame(frame: Frame) -> Result<Option<T>> {
+        match frame {
+            Frame::Null => Ok(None),
+            frame => T::from_frame(frame).map(Some),
+        }
+    }
+}
+
+impl<T: FromFrame> FromFrame for Vec<T> {
+    fn from_frame(frame: Frame) -> Result<Vec<T>> {
+        match frame {
+            Frame::Array(items) => items.into_iter().map(T::from_frame).collect(),
+            Frame::Null => Ok(vec![]),
+            frame => Err(mismatch::<Vec<T>>(&frame)),
+        }
+    }
+}
+
+macro_rules! tuple {
+    ($len:expr; $($name:ident),+) => {
+        impl<$($name: FromFrame),+> FromFrame for ($($name,)+) {
+            fn from_frame(frame: Frame) -> Result<($($name,)+)> {
+                match frame {
+                    Frame::Array(items) if items.len() == $len => {
+                        let mut items = items.into_iter();
+                        Ok(($($name::from_frame(items.next().unwrap())?,)+))
+                    }
+                    frame => Err(mismatch::<($($name,)+)>(&frame)),
+                }
+            }
+        }
+
+        impl<$($name: ToArgs),+> ToArgs for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn write_args(&self, out: &mut Vec<Bytes>) {
+                let ($($name,)+) = self;
+                $($name.write_args(out);)+
+            }
+        }
+    };
+}
+
+tuple!(1; A);
+tuple!(2; A, B);
+tuple!(3; A, B, C);
+tuple!(4; A, B, C, D);
+
+impl ToArgs for Bytes {
+    fn write_args(&self, out: &mut Vec<Bytes>) {
+        out.push(self.clone());
+    }
+}
+
+impl ToArgs for [u8] {
+    fn write_args(&self, out: &mut Vec<Bytes>) {
+        out.push(Bytes::copy_from_slice(self));
+    }
+}
+
+impl ToArgs for Vec<u8> {
+    fn write_args(&self, out: &mut Vec<Bytes>) {
+        self[..].write_args(out);
+    }
+}
+
+impl ToArgs for str {
+    fn write_args(&self, out: &mut Vec<Bytes>) {
+        self.as_bytes().write_args(out);
+    }
+}
+
+impl ToArgs for String {
+    fn write_args(&self, out: &mut Vec<Bytes>) {
+        self.as_bytes().write_args(out);
+    }
+}
+
+impl ToArgs for bool {
+    fn write_args(&self, out: &mut Vec<Bytes>) {
+        out.push(Bytes::from_static(if *self { b"1" } else { b"0" }));
+    }
+}
+
+/// `None` adds no argument
+impl<T: ToArgs> ToArgs for Option<T> {
+    fn write_args(&self, out: &mut Vec<Bytes>) {
+        if let Some(value) = self {
+            value.write_args(out);
+        }
+    }
+}
+
+impl<T: ToArgs> ToArgs for [T] {
+    fn write_args(&self, out: &mut Vec<Bytes>) {
+        for value in self {
+            value.write_args(out);
+        }
+    }
+}
+
+impl<T: ToArgs> ToArgs for Vec<T> {
+    fn write_args(&self, out: &mut Vec<Bytes>) {
+        self[..].write_args(out);
+    }
+}
+
+impl<T: ToArgs + ?Sized> ToArgs for &T {
+    fn write_args(&self, out: &mut Vec<Bytes>) {
+        (**self).write_args(out);
+    }
+}
@@ -0,0 +1,130 @@
+//! Client sharing one tagged connection between concurrent commands, see `HELLO`.
+
+use crate::cmd::{CommandSpec, Get, Hello, Set};
+use crate::{Connection, Error, Frame, Result};
+
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, instrument};
+
+/// Handle to a multiplexed connection, cloned to issue commands concurrently.
+///
+/// Each request is tagged and the server replies as soon as it completes, so a slow command
+/// doesn't hold back the replies of the others. The connection is closed once every handle is
+/// dropped.
+#[derive(Debug, Clone)]
+pub struct MultiplexedClient {
+    requests: mpsc::Sender<Request>,
+}
+
+#[derive(Debug)]
+struct Request {
+    frame: Frame,
+    reply: oneshot::Sender<Frame>,
+}
+
+/// Connect to the server at `addr` and switch the connection to tagged framing
+pub async fn connect_multiplexed<T: ToSocketAddrs>(addr: T) -> Result<MultiplexedClient> {
+    let socket = TcpStream::connect(addr).await?;
+    let mut connection = Connection::new(socket);
+
+    connection.write_frame(&Hello::tagged().into_frame()).await?;
+    match connection.read_frame().await? {
+        Some(Frame::Array(_)) => {}
+        Some(Frame::Error(msg)) => return Err(Error::from_reply(msg)),
+        Some(frame) => return Err(frame.to_error()),
+        None => return Err(Error::ConnectionReset),
+    }
+
+    let (requests, rx) = mpsc::channel(1024);
+    tokio::spawn(async move {
+        if let Err(err) = drive(connection, rx).await {
+            debug!(cause = %err, "multiplexed connection closed");
+        }
+    });
+    Ok(MultiplexedClient { requests })
+}
+
+impl MultiplexedClient {
+    #[instrument(skip(self))]
+    pub async fn get(&self, key: &str) -> Result<Option<Bytes>> {
+        match self.call(Get::new(key).into_frame()).await? {
+            Frame::Simple(value) => Ok(Some(value.into())),
+            Frame::Bulk(value) => Ok(Some(value)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn set(&self, key: &str, value: Bytes) -> Result<()> {
+        self.set_cmd(Set::new(key, value, None)).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn set_expires(&self, key: &str, value: Bytes, expire: Duration) -> Result<()> {
+        self.set_cmd(Set::new(key, value, Some(expire))).await
+    }
+
+    async fn set_cmd(&self, cmd: Set) -> Result<()> {
+        match self.call(cmd.into_frame()).await? {
+            Frame::Simple(response) if response == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Send `frame` and wait for its reply
+    async fn call(&self, frame: Frame) -> Result<Frame> {
+        debug!(request = %frame);
+        let (reply, rx) = oneshot::channel();
+
+        self.requests
+            .send(Request { frame, reply })
+            .await
+            .map_err(|_| Error::ConnectionReset)?;
+
+        let response = rx.await.map_err(|_| Error::ConnectionReset)?;
+        debug!(%response);
+        match response {
+            Frame::Error(msg) => Err(Error::from_reply(msg)),
+            frame => Ok(frame),
+        }
+    }
+}
+
+/// Write the requests as they come and route each reply to its request by tag. Returns once
+/// every handle is dropped or the connection fails, the pending requests then fail.
+async fn drive(mut connection: Connection, mut requests: mpsc::Receiver<Request>) -> Result<()> {
+    let mut pending: HashMap<u64, oneshot::Sender<Frame>> = HashMap::new();
+    let mut next_tag = 0;
+
+    loop {
+        tokio::select! {
+            request = requests.recv() => {
+                let request = match request {
+                    Some(request) => request,
+                    None => return Ok(()),
+                };
+                let tag = next_tag;
+                next_tag += 1;
+
+                connection.write_frame(&Frame::tagged(tag, request.frame)).await?;
+                pending.insert(tag, request.reply);
+            }
+            res = connection.read_frame() => {
+                let frame = res?.ok_or(Error::ConnectionReset)?;
+                let (tag, reply) = frame
+                    .untag()
+                    .map_err(|frame| Error::Protocol(format!("protocol error; untagged reply {}", frame)))?;
+
+                // the caller may have given up on the reply
+                if let Some(tx) = pending.remove(&tag) {
+                    let _ = tx.send(reply);
+                }
+            }
+        }
+    }
+}
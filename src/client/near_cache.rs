@@ -0,0 +1,94 @@
+//! In-process cache of the values read by a `Client`.
+//!
+//! The server doesn't notify clients about writes made by others, so cached values are only
+//! trusted until they would expire on the server or until they reach the configured maximum age,
+//! whichever comes first. Writes made through the same client invalidate the cached value right
+//! away.
+
+use bytes::Bytes;
+use lru::LruCache;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub(crate) struct NearCache {
+    entries: LruCache<String, Cached>,
+
+    /// Longest time a value is served from the cache
+    max_age: Duration,
+
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Debug)]
+struct Cached {
+    value: Bytes,
+    expires_at: Instant,
+}
+
+/// Hit and miss counters of a near cache, see `Client::near_cache_stats`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NearCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Number of values currently cached
+    pub len: usize,
+}
+
+impl NearCache {
+    pub(crate) fn new(capacity: usize, max_age: Duration) -> NearCache {
+        NearCache {
+            entries: LruCache::new(capacity),
+            max_age,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns the cached value of `key` if it is still fresh
+    pub(crate) fn get(&mut self, key: &str) -> Option<Bytes> {
+        let now = Instant::now();
+        let value = match self.entries.get(key) {
+            Some(cached) if cached.expires_at > now => Some(cached.value.clone()),
+            Some(_) => {
+                self.entries.pop(key);
+                None
+            }
+            None => None,
+        };
+
+        match value {
+            Some(_) => self.hits += 1,
+            None => self.misses += 1,
+        }
+        value
+    }
+
+    /// Cache `value`, `ttl` is the time left before the key expires on the server
+    pub(crate) fn insert(&mut self, key: &str, value: Bytes, ttl: Option<Duration>) {
+        let age = ttl.map_or(self.max_age, |ttl| ttl.min(self.max_age));
+        if age == Duration::from_secs(0) {
+            return;
+        }
+        let expires_at = Instant::now() + age;
+        self.entries.put(key.to_string(), Cached { value, expires_at });
+    }
+
+    /// Forget the cached value of `key`
+    pub(crate) fn invalidate(&mut self, key: &str) {
+        self.entries.pop(key);
+    }
+
+    /// Forget every cached value
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub(crate) fn stats(&self) -> NearCacheStats {
+        NearCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            len: self.entries.len(),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! Client spreading reads over replicas while writes go to the primary.
+//!
+//! The replicas must be servers replicating the primary and reporting `role:slave` in
+//! `INFO replication`, such as Redis replicas. redust has no replica role, every redust server
+//! reports `role:master`, so a redust server listed as a replica is left out and the reads go to
+//! the primary.
+
+use crate::client::{self, Client, FromFrame, ToArgs};
+use crate::cmd::{CommandSpec, Exists, Get, Keys};
+use crate::{Error, Frame, Result};
+
+use bytes::Bytes;
+use std::net::SocketAddr;
+use tokio::time::{Duration, Instant};
+use tracing::{debug, instrument, warn};
+
+/// Time given to a replica to accept a connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Default time an unreachable replica is left out before being tried again
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Weight of the latest sample in the latency average of a replica
+const LATENCY_WEIGHT: f64 = 0.2;
+
+/// How reads pick the replica they are sent to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadPolicy {
+    /// Each replica in turn
+    RoundRobin,
+    /// The replica which answered the fastest lately
+    LowestLatency,
+}
+
+/// Connections to a primary and its replicas.
+///
+/// Writes are sent to the primary, reads to one of the replicas as chosen by the `ReadPolicy`.
+/// A replica which can't be reached or whose connection fails is left out for a while, the read
+/// is retried on another one, falling back to the primary once no replica is left. Error replies
+/// don't count as failures. A node which doesn't report the replica role is left out alike.
+///
+/// Replicas may lag behind the primary: a read following a write may not see it yet.
+pub struct ReplicatedClient {
+    primary: Client,
+    replicas: Vec<Replica>,
+    policy: ReadPolicy,
+    retry_after: Duration,
+    /// Replica the next round robin read starts from
+    next: usize,
+}
+
+struct Replica {
+    addr: SocketAddr,
+    /// `None` while demoted
+    client: Option<Client>,
+    /// When a demoted replica may be tried again
+    retry_at: Instant,
+    /// Moving average of the time taken by the reads, `None` until the first one
+    latency: Option<Duration>,
+}
+
+/// Connect to the primary at `primary` and to the replicas at `replicas`. Only the primary must be
+/// reachable, the replicas which aren't, or don't report the replica role yet, are tried again
+/// later.
+pub async fn connect_replicated(
+    primary: SocketAddr,
+    replicas: Vec<SocketAddr>,
+) -> Result<ReplicatedClient> {
+    let primary = client::connect(primary).await?;
+
+    let mut client = ReplicatedClient {
+        primary,
+        replicas: replicas
+            .into_iter()
+            .map(|addr| Replica {
+                addr,
+                client: None,
+                retry_at: Instant::now(),
+                latency: None,
+            })
+            .collect(),
+        policy: ReadPolicy::RoundRobin,
+        retry_after: DEFAULT_RETRY_AFTER,
+        next: 0,
+    };
+    for index in 0..client.replicas.len() {
+        client.reconnect(index).await;
+    }
+    Ok(client)
+}
+
+impl ReplicatedClient {
+    /// Choose how reads pick their replica, round robin by default
+    pub fn set_read_policy(&mut self, policy: ReadPolicy) {
+        self.policy = policy;
+    }
+
+    /// Time an unreachable replica is left out before being tried again, 5 seconds by default
+    pub fn set_retry_after(&mut self, retry_after: Duration) {
+        self.retry_after = retry_after;
+    }
+
+    /// Addresses of the replicas currently serving reads
+    pub fn available_replicas(&self) -> Vec<SocketAddr> {
+        self.replicas
+            .iter()
+            .filter(|replica| replica.client.is_some())
+            .map(|replica| replica.addr)
+            .collect()
+    }
+
+    /// The connection to the primary, for the commands not covered here
+    pub fn primary(&mut self) -> &mut Client {
+        &mut self.primary
+    }
+
+    /// Get the value of `key` from a replica
+    #[instrument(skip(self))]
+    pub async fn get<T: FromFrame>(&mut self, key: &str) -> Result<T> {
+        T::from_frame(self.read(Get::new(key).into_frame()).await?)
+    }
+
+    /// Number of `keys` which exist, read from a replica
+    #[instrument(skip(self))]
+    pub async fn exists(&mut self, keys: &[String]) -> Result<u64> {
+        u64::from_frame(self.read(Exists::new(keys.to_vec()).into_frame()).await?)
+    }
+
+    /// Keys matching the glob-style `pattern`, read from a replica
+    #[instrument(skip(self))]
+    pub async fn keys(&mut self, pattern: &str) -> Result<Vec<String>> {
+        Vec::from_frame(self.read(Keys::new(pattern).into_frame()).await?)
+    }
+
+    /// Send a read-only command to a replica, see `Client::command`
+    #[instrument(skip(self, args))]
+    pub async fn read_command<T: FromFrame>(&mut self, args: impl ToArgs) -> Result<T> {
+        T::from_frame(self.read(client::command_frame(args)?).await?)
+    }
+
+    /// Set `key` to `value` on the primary, see `Client::set`
+    #[instrument(skip(self, value))]
+    pub async fn set(&mut self, key: &str, value: impl ToArgs) -> Result<()> {
+        self.primary.set(key, value).await
+    }
+
+    /// Set `key` to `value` on the primary, expiring after `expire`
+    #[instrument(skip(self, value))]
+    pub async fn set_expires(
+        &mut self,
+        key: &str,
+        value: impl ToArgs,
+        expire: Duration,
+    ) -> Result<()> {
+        self.primary.set_expires(key, value, expire).await
+    }
+
+    /// Remove `keys` on the primary, returns the number of keys which existed
+    #[instrument(skip(self))]
+    pub async fn del(&mut self, keys: &[String]) -> Result<u64> {
+        self.primary.del(keys).await
+    }
+
+    /// Increment the counter held by `key` on the primary and return its new value
+    #[instrument(skip(self))]
+    pub async fn incr(&mut self, key: &str) -> Result<i64> {
+        self.primary.incr(key).await
+    }
+
+    /// Post `message` on `channel` through the primary
+    #[instrument(skip(self))]
+    pub async fn publish(&mut self, channel: &str, message: Bytes) -> Result<u64> {
+        self.primary.publish(channel, message).await
+    }
+
+    /// Send a command to the primary, see `Client::command`
+    #[instrument(skip(self, args))]
+    pub async fn command<T: FromFrame>(&mut self, args: impl ToArgs) -> Result<T> {
+        self.primary.command(args).await
+    }
+
+    /// Send `frame` to a replica, to the primary if none is available
+    async fn read(&mut self, frame: Frame) -> Result<Frame> {
+        while let Some(index) = self.pick().await {
+            let replica = &mut self.replicas[index];
+            let client = replica.client.as_mut().expect("picked replicas are connected");
+
+            let start = Instant::now();
+            match client.raw_command(frame.clone()).await {
+                Err(err) if is_unreachable(&err) => {
+                    warn!(replica = %replica.addr, cause = %err, "replica demoted");
+                    replica.client = None;
+                    replica.retry_at = Instant::now() + self.retry_after;
+                }
+                reply => {
+                    let elapsed = start.elapsed();
+                    replica.latency = Some(match replica.latency {
+                        Some(average) => {
+                            average.mul_f64(1.0 - LATENCY_WEIGHT) + elapsed.mul_f64(LATENCY_WEIGHT)
+                        }
+                        None => elapsed,
+                    });
+                    return reply;
+                }
+            }
+        }
+
+        debug!("no replica available, reading from the primary");
+        self.primary.raw_command(frame).await
+    }
+
+    /// Index of the replica the next read goes to, reconnecting the demoted replicas due for a
+    /// retry first
+    async fn pick(&mut self) -> Option<usize> {
+        let now = Instant::now();
+        for index in 0..self.replicas.len() {
+            let replica = &self.replicas[index];
+            if replica.client.is_none() && replica.retry_at <= now {
+                self.reconnect(index).await;
+            }
+        }
+
+        match self.policy {
+            ReadPolicy::RoundRobin => {
+                let len = self.replicas.len();
+                let index = (0..len)
+                    .map(|offset| (self.next + offset) % len)
+                    .find(|index| self.replicas[*index].client.is_some())?;
+                self.next = index + 1;
+                Some(index)
+            }
+            // the replicas not measured yet go first, so each gets a sample
+            ReadPolicy::LowestLatency => self
+                .replicas
+                .iter()
+                .enumerate()
+                .filter(|(_, replica)| replica.client.is_some())
+                .min_by_key(|(_, replica)| replica.latency.unwrap_or_default())
+                .map(|(index, _)| index),
+        }
+    }
+
+    async fn reconnect(&mut self, index: usize) {
+        let replica = &mut self.replicas[index];
+        let connected = async {
+            let mut client = client::connect_timeout(replica.addr, CONNECT_TIMEOUT).await?;
+            client.set_timeout(Some(CONNECT_TIMEOUT));
+            let info = client.info(Some("replication")).await?;
+            if !is_replica(&info) {
+                return Err(Error::from("the node doesn't report the replica role"));
+            }
+            client.set_timeout(None);
+            Ok(client)
+        };
+        match connected.await {
+            Ok(client) => {
+                debug!(replica = %replica.addr, "replica connected");
+                replica.client = Some(client);
+                replica.latency = None;
+            }
+            Err(err) => {
+                debug!(replica = %replica.addr, cause = %err, "replica unavailable");
+                replica.retry_at = Instant::now() + self.retry_after;
+            }
+        }
+    }
+}
+
+/// Whether the `INFO replication` report `info` is the one of a replica
+fn is_replica(info: &str) -> bool {
+    info.lines().any(|line| line.trim_end() == "role:slave")
+}
+
+/// Whether `err` means the node can't be talked to, rather than an error reply
+fn is_unreachable(err: &Error) -> bool {
+    matches!(err, Error::Io(_) | Error::ConnectionReset | Error::Timeout)
+}
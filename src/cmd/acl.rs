@@ -0,0 +1,191 @@
+use crate::acl::Category;
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+
+use bytes::Bytes;
+use std::fmt;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Manage the users and their permissions, see the `acl` module for the rules.
+///
+/// * `ACL SETUSER username [rule ...]` creates the user if needed then applies the rules: `on`,
+///   `off`, `>password`, `<password`, `nopass`, `resetpass`, `~pattern`, `allkeys`, `resetkeys`,
+///   `+@category`, `-@category`, `allcommands`, `nocommands` and `reset`.
+/// * `ACL GETUSER username` replies with a flat array of `flags`, `passwords` (only their
+///   number), `commands` and `keys`, or nil if the user doesn't exist.
+/// * `ACL LIST` replies with the rules of every user, one string per user.
+/// * `ACL WHOAMI` replies with the user the connection is authenticated as.
+#[derive(Debug)]
+pub struct Acl {
+    subcommand: Subcommand,
+}
+
+enum Subcommand {
+    SetUser(String, Vec<String>),
+    GetUser(String),
+    List,
+    WhoAmI,
+}
+
+impl Acl {
+    /// Create an `ACL SETUSER username rule...` command
+    pub fn set_user(username: impl ToString, rules: Vec<String>) -> Acl {
+        Acl {
+            subcommand: Subcommand::SetUser(username.to_string(), rules),
+        }
+    }
+
+    /// Create an `ACL GETUSER username` command
+    pub fn get_user(username: impl ToString) -> Acl {
+        Acl {
+            subcommand: Subcommand::GetUser(username.to_string()),
+        }
+    }
+
+    /// Create an `ACL LIST` command
+    pub fn list() -> Acl {
+        Acl {
+            subcommand: Subcommand::List,
+        }
+    }
+
+    /// Create an `ACL WHOAMI` command
+    pub fn whoami() -> Acl {
+        Acl {
+            subcommand: Subcommand::WhoAmI,
+        }
+    }
+}
+
+impl CommandSpec for Acl {
+    const NAME: &'static str = "acl";
+    const ARITY: i32 = -2;
+    /// `WHOAMI` aside, see `category`
+    const CATEGORY: Option<Category> = Some(Category::Admin);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Acl> {
+        let subcommand = match &parse.next_string()?.to_lowercase()[..] {
+            "setuser" => {
+                let username = parse.next_string()?;
+                let mut rules = vec![];
+                loop {
+                    match parse.next_string() {
+                        Ok(rule) => rules.push(rule),
+                        Err(ParseError::EndOfStream) => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                Subcommand::SetUser(username, rules)
+            }
+            "getuser" => Subcommand::GetUser(parse.next_string()?),
+            "list" => Subcommand::List,
+            "whoami" => Subcommand::WhoAmI,
+            other => return Err(format!("ERR unknown subcommand '{}' for 'acl'", other).into()),
+        };
+        Ok(Acl { subcommand })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match self.subcommand {
+            Subcommand::SetUser(username, rules) => match db.acl_set_user(&username, &rules) {
+                Ok(()) => Frame::ok(),
+                Err(err) => Frame::Error(err),
+            },
+            Subcommand::GetUser(username) => match db.acl_user(&username) {
+                Some(user) => {
+                    let mut flags = vec![Frame::Bulk(Bytes::from_static(if user.enabled {
+                        b"on"
+                    } else {
+                        b"off"
+                    }))];
+                    if user.nopass {
+                        flags.push(Frame::Bulk(Bytes::from_static(b"nopass")));
+                    }
+                    let commands: Vec<String> = user
+                        .categories()
+                        .map(|category| format!("+@{}", category.name()))
+                        .collect();
+                    let keys: Vec<String> = user
+                        .key_patterns
+                        .iter()
+                        .map(|pattern| format!("~{}", pattern))
+                        .collect();
+
+                    Frame::Array(vec![
+                        Frame::Bulk(Bytes::from_static(b"flags")),
+                        Frame::Array(flags),
+                        Frame::Bulk(Bytes::from_static(b"passwords")),
+                        Frame::Integer(user.password_count() as i64),
+                        Frame::Bulk(Bytes::from_static(b"commands")),
+                        Frame::Bulk(Bytes::from(commands.join(" "))),
+                        Frame::Bulk(Bytes::from_static(b"keys")),
+                        Frame::Bulk(Bytes::from(keys.join(" "))),
+                    ])
+                }
+                None => Frame::Null,
+            },
+            Subcommand::List => Frame::Array(
+                db.acl_list()
+                    .into_iter()
+                    .map(|rules| Frame::Bulk(Bytes::from(rules)))
+                    .collect(),
+            ),
+            Subcommand::WhoAmI => match dst.user() {
+                Some(user) => Frame::Bulk(Bytes::copy_from_slice(user.as_bytes())),
+                None => Frame::Null,
+            },
+        };
+
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = Vec::new();
+        frame.push(Frame::Bulk(Bytes::from("acl".as_bytes())));
+        match self.subcommand {
+            Subcommand::SetUser(username, rules) => {
+                frame.push(Frame::Bulk(Bytes::from("setuser".as_bytes())));
+                frame.push(Frame::Bulk(Bytes::from(username.into_bytes())));
+                for rule in rules {
+                    frame.push(Frame::Bulk(Bytes::from(rule.into_bytes())));
+                }
+            }
+            Subcommand::GetUser(username) => {
+                frame.push(Frame::Bulk(Bytes::from("getuser".as_bytes())));
+                frame.push(Frame::Bulk(Bytes::from(username.into_bytes())));
+            }
+            Subcommand::List => frame.push(Frame::Bulk(Bytes::from("list".as_bytes()))),
+            Subcommand::WhoAmI => frame.push(Frame::Bulk(Bytes::from("whoami".as_bytes()))),
+        }
+        Frame::Array(frame)
+    }
+
+    /// `ACL WHOAMI` is allowed to every user, the other subcommands are administrative
+    fn category(&self) -> Option<Category> {
+        match self.subcommand {
+            Subcommand::WhoAmI => None,
+            _ => Some(Category::Admin),
+        }
+    }
+}
+
+/// The rules are left out of `SETUSER`, they may hold passwords
+impl fmt::Debug for Subcommand {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Subcommand::SetUser(username, _) => fmt.debug_tuple("SetUser").field(username).finish_non_exhaustive(),
+            Subcommand::GetUser(username) => fmt.debug_tuple("GetUser").field(username).finish(),
+            Subcommand::List => fmt.write_str("List"),
+            Subcommand::WhoAmI => fmt.write_str("WhoAmI"),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::acl::DEFAULT_USER;
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use std::fmt;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Authenticate the connection as a user, see `ACL SETUSER`.
+///
+/// `AUTH [username] password`, the default user is the one whose password is set by `requirepass`.
+/// Until it authenticates, a connection to a server requiring a password can only run `AUTH`,
+/// `HELLO` and `RESET`, every other command is answered `NOAUTH`.
+pub struct Auth {
+    username: Option<String>,
+    password: String,
+}
+
+impl Auth {
+    pub fn new(username: Option<String>, password: impl ToString) -> Auth {
+        Auth {
+            username,
+            password: password.to_string(),
+        }
+    }
+}
+
+impl CommandSpec for Auth {
+    const NAME: &'static str = "auth";
+    const ARITY: i32 = -2;
+    const CATEGORY: Option<Category> = None;
+
+    fn parse(parse: &mut Parse) -> crate::Result<Auth> {
+        let first = parse.next_string()?;
+        match parse.next_string() {
+            Ok(password) => Ok(Auth::new(Some(first), password)),
+            Err(ParseError::EndOfStream) => Ok(Auth::new(None, first)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let username = self.username.as_deref().unwrap_or(DEFAULT_USER);
+
+        let response = if self.username.is_none() && db.config().requirepass.is_none() {
+            Frame::Error(
+                "ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?"
+                    .to_string(),
+            )
+        } else if db.authenticate(username, &self.password) {
+            dst.set_user(Some(username.to_string()));
+            Frame::ok()
+        } else {
+            Frame::error("WRONGPASS invalid username-password pair or user is disabled.")
+        };
+
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = Vec::new();
+        frame.push(Frame::Bulk(Bytes::from("auth".as_bytes())));
+        if let Some(username) = self.username {
+            frame.push(Frame::Bulk(Bytes::from(username.into_bytes())));
+        }
+        frame.push(Frame::Bulk(Bytes::from(self.password.into_bytes())));
+        Frame::Array(frame)
+    }
+}
+
+/// The password is left out, so it doesn't end up in the logs
+impl fmt::Debug for Auth {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Auth")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,265 @@
+use crate::bitmap::{self, Unit};
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Set or clear the bit at `offset` of a string value, growing the value with zeros as needed.
+///
+/// `SETBIT key offset 0|1`
+///
+/// Replies with the previous value of the bit.
+#[derive(Debug)]
+pub struct SetBit {
+    key: String,
+    offset: u64,
+    bit: bool,
+}
+
+/// Value of the bit at `offset` of a string value. Bits past the end of the value are `0`.
+///
+/// `GETBIT key offset`
+#[derive(Debug)]
+pub struct GetBit {
+    key: String,
+    offset: u64,
+}
+
+/// Number of bits set in a string value.
+///
+/// `BITCOUNT key [start end [BYTE|BIT]]`
+///
+/// The inclusive range is in bytes unless `BIT` is given. Negative positions count from the end of
+/// the value.
+#[derive(Debug)]
+pub struct BitCount {
+    key: String,
+    range: Option<(i64, i64, Unit)>,
+}
+
+/// Parse a bit offset, limited like Redis to values of 512MB
+fn parse_offset(parse: &mut Parse) -> crate::Result<u64> {
+    match parse.next_string()?.parse::<u64>() {
+        Ok(offset) if offset <= bitmap::MAX_OFFSET => Ok(offset),
+        _ => Err("ERR bit offset is not an integer or out of range".into()),
+    }
+}
+
+/// Parse a position of a `BITCOUNT` range
+fn parse_position(parse: &mut Parse) -> crate::Result<i64> {
+    parse
+        .next_string()?
+        .parse::<i64>()
+        .map_err(|_| "ERR value is not an integer or out of range".into())
+}
+
+impl SetBit {
+    pub fn new(key: impl ToString, offset: u64, bit: bool) -> SetBit {
+        SetBit {
+            key: key.to_string(),
+            offset,
+            bit,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for SetBit {
+    const NAME: &'static str = "setbit";
+    const ARITY: i32 = 4;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<SetBit> {
+        let key = parse.next_string()?;
+        let offset = parse_offset(parse)?;
+        let bit = match &parse.next_string()?[..] {
+            "0" => false,
+            "1" => true,
+            _ => return Err("ERR bit is not an integer or out of range".into()),
+        };
+        Ok(SetBit { key, offset, bit })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.set_bit(self.key, self.offset, self.bit) {
+            Ok(prev) => Frame::Integer(prev as i64),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("setbit".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+            Frame::Bulk(Bytes::from(self.offset.to_string())),
+            Frame::Bulk(Bytes::from_static(if self.bit { b"1" } else { b"0" })),
+        ])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl GetBit {
+    pub fn new(key: impl ToString, offset: u64) -> GetBit {
+        GetBit {
+            key: key.to_string(),
+            offset,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for GetBit {
+    const NAME: &'static str = "getbit";
+    const ARITY: i32 = 3;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<GetBit> {
+        let key = parse.next_string()?;
+        let offset = parse_offset(parse)?;
+        Ok(GetBit { key, offset })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.get_bit(&self.key, self.offset) {
+            Ok(bit) => Frame::Integer(bit as i64),
+            Err(err) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "getbit", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("getbit".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+            Frame::Bulk(Bytes::from(self.offset.to_string())),
+        ])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl BitCount {
+    pub fn new(key: impl ToString) -> BitCount {
+        BitCount {
+            key: key.to_string(),
+            range: None,
+        }
+    }
+
+    /// Only count the bits within the inclusive `start..=end` range of bytes
+    pub fn bytes(mut self, start: i64, end: i64) -> BitCount {
+        self.range = Some((start, end, Unit::Byte));
+        self
+    }
+
+    /// Only count the bits within the inclusive `start..=end` range of bits
+    pub fn bits(mut self, start: i64, end: i64) -> BitCount {
+        self.range = Some((start, end, Unit::Bit));
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for BitCount {
+    const NAME: &'static str = "bitcount";
+    const ARITY: i32 = -2;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<BitCount> {
+        use ParseError::EndOfStream;
+
+        let mut cmd = BitCount::new(parse.next_string()?);
+
+        let start = match parse.next_string() {
+            Ok(start) => start
+                .parse::<i64>()
+                .map_err(|_| "ERR value is not an integer or out of range")?,
+            Err(EndOfStream) => return Ok(cmd),
+            Err(err) => return Err(err.into()),
+        };
+        let end = parse_position(parse)?;
+
+        let unit = match parse.next_string() {
+            Ok(s) if s.to_uppercase() == "BYTE" => Unit::Byte,
+            Ok(s) if s.to_uppercase() == "BIT" => Unit::Bit,
+            Ok(_) => return Err("ERR syntax error".into()),
+            Err(EndOfStream) => Unit::Byte,
+            Err(err) => return Err(err.into()),
+        };
+        cmd.range = Some((start, end, unit));
+        Ok(cmd)
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.bit_count(&self.key, self.range) {
+            Ok(count) => Frame::Integer(count as i64),
+            Err(err) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "bitcount", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = vec![
+            Frame::Bulk(Bytes::from("bitcount".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+        ];
+        if let Some((start, end, unit)) = self.range {
+            frame.push(Frame::Bulk(Bytes::from(start.to_string())));
+            frame.push(Frame::Bulk(Bytes::from(end.to_string())));
+            frame.push(Frame::Bulk(Bytes::from_static(match unit {
+                Unit::Byte => b"byte",
+                Unit::Bit => b"bit",
+            })));
+        }
+        Frame::Array(frame)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
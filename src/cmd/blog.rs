@@ -0,0 +1,148 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use std::convert::TryFrom;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Append bytes to the byte log of a key, the log is created if the key doesn't exist.
+///
+/// `BLOG.APPEND key bytes` replies with the offset following the appended bytes, which is the
+/// total number of bytes ever appended to the log. Once a log grows past `blog-max-bytes`, its
+/// oldest bytes are dropped. An expired log is replaced by a new one.
+///
+/// The byte logs are refused when the server stores its values through a value transform.
+#[derive(Debug)]
+pub struct BlogAppend {
+    key: String,
+    chunk: Bytes,
+}
+
+/// Read from the byte log of a key.
+///
+/// `BLOG.READ key offset len` replies with up to `len` bytes starting at the absolute `offset`,
+/// an empty string past the end of the log and `(nil)` if the key doesn't exist. Reading an
+/// offset dropped by a rotation is an error which tells the oldest offset still available.
+#[derive(Debug)]
+pub struct BlogRead {
+    key: String,
+    offset: u64,
+    len: u64,
+}
+
+impl BlogAppend {
+    pub fn new(key: impl ToString, chunk: Bytes) -> BlogAppend {
+        BlogAppend {
+            key: key.to_string(),
+            chunk,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for BlogAppend {
+    const NAME: &'static str = "blog.append";
+    const ARITY: i32 = 3;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<BlogAppend> {
+        let key = parse.next_string()?;
+        let chunk = parse.next_bytes()?;
+        Ok(BlogAppend { key, chunk })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.blog_append(&self.key, self.chunk) {
+            Ok(end) => Frame::Integer(end as i64),
+            Err(err) => super::error_reply(&err),
+        };
+
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("blog.append".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+            Frame::Bulk(self.chunk),
+        ])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl BlogRead {
+    pub fn new(key: impl ToString, offset: u64, len: u64) -> BlogRead {
+        BlogRead {
+            key: key.to_string(),
+            offset,
+            len,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for BlogRead {
+    const NAME: &'static str = "blog.read";
+    const ARITY: i32 = 4;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<BlogRead> {
+        let key = parse.next_string()?;
+        let offset = parse.next_int()?;
+        let len = parse.next_int()?;
+        Ok(BlogRead { key, offset, len })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let len = usize::try_from(self.len).unwrap_or(usize::MAX);
+        let response = match db.blog_read(&self.key, self.offset, len) {
+            Ok(Some(data)) => Frame::Bulk(data),
+            Ok(None) => Frame::Null,
+            Err(err) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "blog.read", &self.key);
+
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("blog.read".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+            Frame::Integer(self.offset as i64),
+            Frame::Integer(self.len as i64),
+        ])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
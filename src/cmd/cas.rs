@@ -0,0 +1,77 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// `CAS key expected new` sets `key` to `new` only if its current value is `expected`.
+///
+/// The current value is returned either way, or nil if the key doesn't exist: the swap happened
+/// if it is equal to `expected`. A missing key never matches. Like `SET` without options, a swap
+/// drops the expiration of the key.
+#[derive(Debug)]
+pub struct Cas {
+    key: String,
+    expected: Bytes,
+    new: Bytes,
+}
+
+impl Cas {
+    pub fn new(key: impl ToString, expected: Bytes, new: Bytes) -> Cas {
+        Cas {
+            key: key.to_string(),
+            expected,
+            new,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for Cas {
+    const NAME: &'static str = "cas";
+    const ARITY: i32 = 4;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<Cas> {
+        let key = parse.next_string()?;
+        let expected = parse.next_bytes()?;
+        let new = parse.next_bytes()?;
+        Ok(Cas { key, expected, new })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.compare_and_swap(self.key, &self.expected, self.new) {
+            Ok(Some(value)) => Frame::Bulk(value),
+            Ok(None) => Frame::Null,
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("cas".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+            Frame::Bulk(self.expected),
+            Frame::Bulk(self.new),
+        ])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
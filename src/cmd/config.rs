@@ -0,0 +1,148 @@
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Reads and changes the server settings at runtime.
+///
+/// `CONFIG GET pattern [pattern ...]` replies with a flat array of the name and value of every
+/// setting matching one of the glob patterns. `CONFIG SET name value [name value ...]` changes
+/// settings, either all of them are applied or none when one is invalid. `CONFIG RESETSTAT`
+/// resets the statistics reported by `INFO`: the command statistics, the latencies and the
+/// counts of rejected connections and commands.
+#[derive(Debug)]
+pub struct Config {
+    subcommand: Subcommand,
+}
+
+#[derive(Debug)]
+enum Subcommand {
+    Get(Vec<String>),
+    Set(Vec<(String, String)>),
+    ResetStat,
+}
+
+impl Config {
+    /// Create a `CONFIG GET pattern` command
+    pub fn get(pattern: impl ToString) -> Config {
+        Config {
+            subcommand: Subcommand::Get(vec![pattern.to_string()]),
+        }
+    }
+
+    /// Create a `CONFIG RESETSTAT` command
+    pub fn resetstat() -> Config {
+        Config {
+            subcommand: Subcommand::ResetStat,
+        }
+    }
+
+    /// Create a `CONFIG SET name value` command
+    pub fn set(name: impl ToString, value: impl ToString) -> Config {
+        Config {
+            subcommand: Subcommand::Set(vec![(name.to_string(), value.to_string())]),
+        }
+    }
+}
+
+impl CommandSpec for Config {
+    const NAME: &'static str = "config";
+    const ARITY: i32 = -2;
+    const CATEGORY: Option<Category> = Some(Category::Admin);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Config> {
+        let subcommand = match &parse.next_string()?.to_lowercase()[..] {
+            "get" => {
+                let mut patterns = vec![parse.next_string()?];
+                loop {
+                    match parse.next_string() {
+                        Ok(pattern) => patterns.push(pattern),
+                        Err(ParseError::EndOfStream) => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                Subcommand::Get(patterns)
+            }
+            "set" => {
+                let mut changes = vec![(parse.next_string()?, parse.next_string()?)];
+                loop {
+                    match parse.next_string() {
+                        Ok(name) => changes.push((name, parse.next_string()?)),
+                        Err(ParseError::EndOfStream) => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                Subcommand::Set(changes)
+            }
+            "resetstat" => Subcommand::ResetStat,
+            other => return Err(format!("ERR unknown subcommand '{}' for 'config'", other).into()),
+        };
+        Ok(Config { subcommand })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match self.subcommand {
+            Subcommand::Get(patterns) => {
+                let mut frame = Vec::new();
+                let mut seen = Vec::new();
+                for pattern in &patterns {
+                    for (name, value) in db.config_params(pattern) {
+                        // a setting matching several patterns is only listed once
+                        if seen.contains(&name) {
+                            continue;
+                        }
+                        seen.push(name);
+                        frame.push(Frame::Bulk(Bytes::from_static(name.as_bytes())));
+                        frame.push(Frame::Bulk(Bytes::from(value)));
+                    }
+                }
+                Frame::Array(frame)
+            }
+            Subcommand::Set(changes) => match db.set_config_params(&changes) {
+                Ok(()) => Frame::ok(),
+                Err(msg) => Frame::Error(msg),
+            },
+            Subcommand::ResetStat => {
+                db.reset_stats();
+                Frame::ok()
+            }
+        };
+
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = Vec::new();
+        frame.push(Frame::Bulk(Bytes::from("config".as_bytes())));
+        match self.subcommand {
+            Subcommand::Get(patterns) => {
+                frame.push(Frame::Bulk(Bytes::from("get".as_bytes())));
+                for pattern in patterns {
+                    frame.push(Frame::Bulk(Bytes::from(pattern.into_bytes())));
+                }
+            }
+            Subcommand::Set(changes) => {
+                frame.push(Frame::Bulk(Bytes::from("set".as_bytes())));
+                for (name, value) in changes {
+                    frame.push(Frame::Bulk(Bytes::from(name.into_bytes())));
+                    frame.push(Frame::Bulk(Bytes::from(value.into_bytes())));
+                }
+            }
+            Subcommand::ResetStat => {
+                frame.push(Frame::Bulk(Bytes::from("resetstat".as_bytes())));
+            }
+        }
+        Frame::Array(frame)
+    }
+}
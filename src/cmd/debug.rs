@@ -0,0 +1,133 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tokio::time::{self, Duration};
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Debugging helpers for tests and operators.
+///
+/// * `DEBUG OBJECT key` describes an entry: type, stored size, ttl and version.
+/// * `DEBUG SLEEP seconds` waits before replying, to simulate a slow command. Only the calling
+///   connection is held, unlike Redis which blocks the whole server.
+/// * `DEBUG SET-ACTIVE-EXPIRE 0|1` stops or resumes the background removal of expired keys.
+///
+/// `SLEEP` and `SET-ACTIVE-EXPIRE` change how the server behaves, they are refused unless the
+/// server was started with `enable-debug-command` on, which `CONFIG SET` can't change.
+#[derive(Debug)]
+pub struct Debug {
+    subcommand: Subcommand,
+}
+
+#[derive(Debug)]
+enum Subcommand {
+    Object(String),
+    Sleep(Duration),
+    SetActiveExpire(bool),
+}
+
+impl Debug {
+    /// Create a `DEBUG OBJECT key` command
+    pub fn object(key: impl ToString) -> Debug {
+        Debug {
+            subcommand: Subcommand::Object(key.to_string()),
+        }
+    }
+
+    /// Create a `DEBUG SLEEP seconds` command
+    pub fn sleep(duration: Duration) -> Debug {
+        Debug {
+            subcommand: Subcommand::Sleep(duration),
+        }
+    }
+}
+
+impl CommandSpec for Debug {
+    const NAME: &'static str = "debug";
+    const ARITY: i32 = -2;
+    const CATEGORY: Option<Category> = Some(Category::Admin);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Debug> {
+        let subcommand = match &parse.next_string()?.to_lowercase()[..] {
+            "object" => Subcommand::Object(parse.next_string()?),
+            "sleep" => {
+                let secs = parse.next_string()?;
+                // rejects the negative, NaN and too large durations
+                let duration = secs
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+                match duration {
+                    Some(duration) => Subcommand::Sleep(duration),
+                    None => return Err("ERR value is not a valid float".into()),
+                }
+            }
+            "set-active-expire" => match parse.next_int()? {
+                0 => Subcommand::SetActiveExpire(false),
+                1 => Subcommand::SetActiveExpire(true),
+                _ => return Err("ERR value must be 0 or 1".into()),
+            },
+            other => return Err(format!("ERR unknown subcommand '{}' for 'debug'", other).into()),
+        };
+        Ok(Debug { subcommand })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match self.subcommand {
+            Subcommand::Object(key) => match db.object_info(&key) {
+                Some(info) => {
+                    let ttl = info.ttl.map_or(-1, |ttl| ttl.as_millis() as i64);
+                    Frame::Simple(format!(
+                        "type:{} serializedlength:{} ttl:{} version:{}",
+                        info.kind, info.size, ttl, info.version
+                    ))
+                }
+                None => Frame::error("ERR no such key"),
+            },
+            _ if !db.config().enable_debug_command => Frame::Error(
+                "ERR DEBUG command not allowed. Start the server with enable-debug-command on"
+                    .to_string(),
+            ),
+            Subcommand::Sleep(duration) => {
+                time::sleep(duration).await;
+                Frame::ok()
+            }
+            Subcommand::SetActiveExpire(enabled) => {
+                db.set_active_expire(enabled);
+                Frame::ok()
+            }
+        };
+
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = Vec::new();
+        frame.push(Frame::Bulk(Bytes::from("debug".as_bytes())));
+        match self.subcommand {
+            Subcommand::Object(key) => {
+                frame.push(Frame::Bulk(Bytes::from("object".as_bytes())));
+                frame.push(Frame::Bulk(Bytes::from(key.into_bytes())));
+            }
+            Subcommand::Sleep(duration) => {
+                frame.push(Frame::Bulk(Bytes::from("sleep".as_bytes())));
+                frame.push(Frame::Bulk(Bytes::from(duration.as_secs_f64().to_string())));
+            }
+            Subcommand::SetActiveExpire(enabled) => {
+                frame.push(Frame::Bulk(Bytes::from("set-active-expire".as_bytes())));
+                frame.push(Frame::Integer(enabled as i64));
+            }
+        }
+        Frame::Array(frame)
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// `DEL key [key ...]` removes the keys, whatever the type of their value.
+///
+/// Replies the number of keys removed, the keys which didn't exist are ignored.
+#[derive(Debug)]
+pub struct Del {
+    keys: Vec<String>,
+}
+
+impl Del {
+    pub fn new(keys: Vec<String>) -> Del {
+        Del { keys }
+    }
+
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+}
+
+impl CommandSpec for Del {
+    const NAME: &'static str = "del";
+    const ARITY: i32 = -2;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Del> {
+        Ok(Del::new(parse_keys(parse)?))
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = Frame::Integer(db.delete(&self.keys) as i64);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = Vec::new();
+        frame.push(Frame::Bulk(Bytes::from("del".as_bytes())));
+        for key in self.keys {
+            frame.push(Frame::Bulk(Bytes::from(key.into_bytes())));
+        }
+        Frame::Array(frame)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.keys.iter().map(String::as_str).collect()
+    }
+}
+
+/// At least one key, then every remaining argument
+pub(super) fn parse_keys(parse: &mut Parse) -> crate::Result<Vec<String>> {
+    let mut keys = vec![parse.next_string()?];
+    loop {
+        match parse.next_string() {
+            Ok(key) => keys.push(key),
+            Err(ParseError::EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(keys)
+}
@@ -0,0 +1,202 @@
+use crate::{rdb, Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Serialize the value of a key in the RDB format of Redis.
+///
+/// `DUMP key`
+///
+/// Replies the payload, or nil if the key doesn't exist. The payload is restored with `RESTORE`,
+/// by redust or by Redis. Only string values can be dumped.
+#[derive(Debug)]
+pub struct Dump {
+    key: String,
+}
+
+/// Create a key from the payload of `DUMP`.
+///
+/// `RESTORE key ttl payload [REPLACE] [ABSTTL] [IDLETIME seconds] [FREQ frequency]`
+///
+/// `ttl` is in milliseconds, `0` for no expiration. With `ABSTTL` it is the unix time in
+/// milliseconds the key expires at instead. The key must not exist unless `REPLACE` is given,
+/// otherwise the reply is `-BUSYKEY`. `IDLETIME` and `FREQ` are accepted for compatibility but
+/// ignored, keys aren't evicted.
+#[derive(Debug)]
+pub struct Restore {
+    key: String,
+    ttl: u64,
+    payload: Bytes,
+    replace: bool,
+    absttl: bool,
+}
+
+impl Dump {
+    pub fn new(key: impl ToString) -> Dump {
+        Dump { key: key.to_string() }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for Dump {
+    const NAME: &'static str = "dump";
+    const ARITY: i32 = 2;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Dump> {
+        let key = parse.next_string()?;
+        Ok(Dump { key })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.get(&self.key) {
+            Ok(Some(value)) => Frame::Bulk(rdb::dump(&value)),
+            Ok(None) => Frame::Null,
+            Err(err) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "dump", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("dump".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+        ])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl Restore {
+    /// Restore `payload` as `key`, expiring after `ttl` if given
+    pub fn new(key: impl ToString, payload: Bytes, ttl: Option<Duration>) -> Restore {
+        Restore {
+            key: key.to_string(),
+            ttl: ttl.map_or(0, |ttl| ttl.as_millis() as u64),
+            payload,
+            replace: false,
+            absttl: false,
+        }
+    }
+
+    /// Replace the key if it exists
+    pub fn replace(mut self) -> Restore {
+        self.replace = true;
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn restore(self, db: &Db) -> crate::Result<bool> {
+        let value = rdb::restore(&self.payload)?;
+        let expire = match (self.ttl, self.absttl) {
+            (0, _) => None,
+            (ttl, false) => Some(Duration::from_millis(ttl)),
+            (at, true) => match (UNIX_EPOCH + Duration::from_millis(at)).duration_since(SystemTime::now()) {
+                Ok(ttl) => Some(ttl),
+                // already expired, the key is only removed, as Redis does
+                Err(_) => {
+                    if !self.replace && db.exists(std::slice::from_ref(&self.key)) > 0 {
+                        return Ok(false);
+                    }
+                    db.delete(&[self.key]);
+                    return Ok(true);
+                }
+            },
+        };
+        db.restore(self.key, value, expire, self.replace)
+    }
+}
+
+impl CommandSpec for Restore {
+    const NAME: &'static str = "restore";
+    const ARITY: i32 = -4;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<Restore> {
+        let key = parse.next_string()?;
+        let ttl = parse.next_int()?;
+        let payload = parse.next_bytes()?;
+        let mut restore = Restore {
+            key,
+            ttl,
+            payload,
+            replace: false,
+            absttl: false,
+        };
+
+        loop {
+            match parse.next_string() {
+                Ok(s) => match &s.to_uppercase()[..] {
+                    "REPLACE" => restore.replace = true,
+                    "ABSTTL" => restore.absttl = true,
+                    "IDLETIME" | "FREQ" => {
+                        parse.next_int()?;
+                    }
+                    _ => return Err("ERR syntax error".into()),
+                },
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(restore)
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match self.restore(db) {
+            Ok(true) => Frame::ok(),
+            Ok(false) => Frame::error("BUSYKEY Target key name already exists."),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = vec![
+            Frame::Bulk(Bytes::from("restore".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+            Frame::Integer(self.ttl as i64),
+            Frame::Bulk(self.payload),
+        ];
+        if self.replace {
+            frame.push(Frame::Bulk(Bytes::from("replace".as_bytes())));
+        }
+        if self.absttl {
+            frame.push(Frame::Bulk(Bytes::from("absttl".as_bytes())));
+        }
+        Frame::Array(frame)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
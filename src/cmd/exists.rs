@@ -0,0 +1,60 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// `EXISTS key [key ...]` replies the number of keys which exist. A key given several times is
+/// counted as many times.
+#[derive(Debug)]
+pub struct Exists {
+    keys: Vec<String>,
+}
+
+impl Exists {
+    pub fn new(keys: Vec<String>) -> Exists {
+        Exists { keys }
+    }
+
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+}
+
+impl CommandSpec for Exists {
+    const NAME: &'static str = "exists";
+    const ARITY: i32 = -2;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Exists> {
+        Ok(Exists::new(super::del::parse_keys(parse)?))
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = Frame::Integer(db.exists(&self.keys) as i64);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = Vec::new();
+        frame.push(Frame::Bulk(Bytes::from("exists".as_bytes())));
+        for key in self.keys {
+            frame.push(Frame::Bulk(Bytes::from(key.into_bytes())));
+        }
+        Frame::Array(frame)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.keys.iter().map(String::as_str).collect()
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use std::time::Duration;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// `EXPIRE key seconds` sets the time to live of `key`, replacing its current expiration.
+///
+/// Replies `1` if the expiration was set, `0` if the key doesn't exist. A time to live of `0`
+/// removes the key right away.
+#[derive(Debug)]
+pub struct Expire {
+    key: String,
+    ttl: Duration,
+}
+
+impl Expire {
+    /// Create a new `Expire` command, `ttl` is sent with a resolution of one second
+    pub fn new(key: impl ToString, ttl: Duration) -> Expire {
+        Expire {
+            key: key.to_string(),
+            ttl,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+}
+
+impl CommandSpec for Expire {
+    const NAME: &'static str = "expire";
+    const ARITY: i32 = 3;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Expire> {
+        let key = parse.next_string()?;
+        let ttl = super::check_ttl(Duration::from_secs(parse.next_int()?), "expire")?;
+        Ok(Expire { key, ttl })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = Frame::Integer(db.expire(&self.key, self.ttl) as i64);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("expire".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+            Frame::Integer(self.ttl.as_secs() as i64),
+        ])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
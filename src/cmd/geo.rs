@@ -0,0 +1,514 @@
+use crate::acl::Category;
+use crate::db::MemberUpdate;
+use crate::geo::{self, Center, Found, Shape};
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::sorted_set::{add_options, parse_add_options};
+use super::CommandSpec;
+
+/// Add positions to a sorted set, the set being created if missing.
+///
+/// `GEOADD key [NX|XX] [CH] longitude latitude member [longitude latitude member ...]`
+///
+/// Each member is stored with the geohash of its position as score, see `ZADD` for the options.
+/// Replies with the number of members added.
+#[derive(Debug)]
+pub struct GeoAdd {
+    key: String,
+    positions: Vec<(f64, f64, Bytes)>,
+    update: MemberUpdate,
+    changed: bool,
+}
+
+/// Distance between the positions of two members of a sorted set.
+///
+/// `GEODIST key member1 member2 [M|KM|FT|MI]`
+///
+/// Replies with the distance in the unit given, meters by default, or nil if either member or the
+/// key doesn't exist.
+#[derive(Debug)]
+pub struct GeoDist {
+    key: String,
+    from: Bytes,
+    to: Bytes,
+    unit: Unit,
+}
+
+/// Members of a sorted set positioned within an area.
+///
+/// `GEOSEARCH key FROMMEMBER member|FROMLONLAT longitude latitude
+/// BYRADIUS radius unit|BYBOX width height unit [ASC|DESC] [COUNT count [ANY]] [WITHCOORD]
+/// [WITHDIST] [WITHHASH]`
+///
+/// The area is centered on a member or a position. Members are returned nearest first with `ASC`,
+/// farthest first with `DESC`, in no particular order otherwise. `COUNT` returns the nearest ones
+/// only, unless `ANY` which returns the first found. Each member is followed by its distance in
+/// the unit of the area with `WITHDIST`, its geohash with `WITHHASH` and its position with
+/// `WITHCOORD`.
+#[derive(Debug)]
+pub struct GeoSearch {
+    key: String,
+    center: Center,
+    /// In `unit`
+    shape: Shape,
+    unit: Unit,
+    order: Option<Order>,
+    count: Option<u64>,
+    any: bool,
+    with_coord: bool,
+    with_dist: bool,
+    with_hash: bool,
+}
+
+/// Unit of a distance
+#[derive(Debug, Clone, Copy)]
+enum Unit {
+    Meters,
+    Kilometers,
+    Feet,
+    Miles,
+}
+
+/// Order of the members returned by `GEOSEARCH`, by distance
+#[derive(Debug, Clone, Copy)]
+enum Order {
+    Asc,
+    Desc,
+}
+
+const FROM_ERROR: &str =
+    "ERR exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH";
+const BY_ERROR: &str = "ERR exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH";
+
+impl Unit {
+    fn parse(parse: &mut Parse) -> crate::Result<Unit> {
+        match &parse.next_string()?.to_lowercase()[..] {
+            "m" => Ok(Unit::Meters),
+            "km" => Ok(Unit::Kilometers),
+            "ft" => Ok(Unit::Feet),
+            "mi" => Ok(Unit::Miles),
+            _ => Err("ERR unsupported unit provided. please use M, KM, FT, MI".into()),
+        }
+    }
+
+    /// Meters in one unit
+    fn meters(self) -> f64 {
+        match self {
+            Unit::Meters => 1.0,
+            Unit::Kilometers => 1000.0,
+            Unit::Feet => 0.3048,
+            Unit::Miles => 1609.34,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Unit::Meters => "m",
+            Unit::Kilometers => "km",
+            Unit::Feet => "ft",
+            Unit::Miles => "mi",
+        }
+    }
+}
+
+fn command_frame(name: &'static str, key: String, args: impl IntoIterator<Item = Bytes>) -> Frame {
+    let mut frame = Vec::new();
+    frame.push(Frame::Bulk(Bytes::from(name.as_bytes())));
+    frame.push(Frame::Bulk(Bytes::from(key.into_bytes())));
+    for arg in args {
+        frame.push(Frame::Bulk(arg));
+    }
+    Frame::Array(frame)
+}
+
+fn parse_number(text: &str) -> crate::Result<f64> {
+    match text.parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok(number),
+        _ => Err("ERR value is not a valid float".into()),
+    }
+}
+
+/// A longitude and a latitude, which must be valid
+fn parse_position(longitude: &str, latitude: &str) -> crate::Result<(f64, f64)> {
+    let (longitude, latitude) = (parse_number(longitude)?, parse_number(latitude)?);
+    if !geo::is_valid(longitude, latitude) {
+        let message = format!(
+            "ERR invalid longitude,latitude pair {:.6},{:.6}",
+            longitude, latitude
+        );
+        return Err(message.into());
+    }
+    Ok((longitude, latitude))
+}
+
+/// A distance as replied, in `unit`
+fn distance_frame(meters: f64, unit: Unit) -> Frame {
+    Frame::Bulk(Bytes::from(format!("{:.4}", meters / unit.meters())))
+}
+
+impl GeoAdd {
+    pub fn new(key: impl ToString, positions: Vec<(f64, f64, Bytes)>) -> GeoAdd {
+        GeoAdd {
+            key: key.to_string(),
+            positions,
+            update: MemberUpdate::All,
+            changed: false,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for GeoAdd {
+    const NAME: &'static str = "geoadd";
+    const ARITY: i32 = -5;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<GeoAdd> {
+        let mut cmd = GeoAdd::new(parse.next_string()?, vec![]);
+        let (update, changed, mut longitude) = parse_add_options(parse)?;
+        cmd.update = update;
+        cmd.changed = changed;
+
+        loop {
+            // a position without its member is a wrong number of arguments
+            let latitude = parse.next_string()?;
+            let member = parse.next_bytes()?;
+            let position = parse_position(&longitude, &latitude)?;
+            cmd.positions.push((position.0, position.1, member));
+            longitude = match parse.next_string() {
+                Ok(longitude) => longitude,
+                Err(ParseError::EndOfStream) => return Ok(cmd),
+                Err(err) => return Err(err.into()),
+            };
+        }
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let members = self
+            .positions
+            .into_iter()
+            .map(|(longitude, latitude, member)| (geo::encode(longitude, latitude) as f64, member))
+            .collect();
+        let response = match db.sorted_set_add(&self.key, members, self.update, self.changed) {
+            Ok(count) => Frame::Integer(count as i64),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut args = add_options(self.update, self.changed);
+        for (longitude, latitude, member) in self.positions {
+            args.push(Bytes::from(longitude.to_string()));
+            args.push(Bytes::from(latitude.to_string()));
+            args.push(member);
+        }
+        command_frame("geoadd", self.key, args)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl GeoDist {
+    /// The distance in meters
+    pub fn new(key: impl ToString, from: Bytes, to: Bytes) -> GeoDist {
+        GeoDist {
+            key: key.to_string(),
+            from,
+            to,
+            unit: Unit::Meters,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for GeoDist {
+    const NAME: &'static str = "geodist";
+    const ARITY: i32 = -4;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<GeoDist> {
+        let mut cmd = GeoDist::new(
+            parse.next_string()?,
+            parse.next_bytes()?,
+            parse.next_bytes()?,
+        );
+        match parse.remaining() {
+            0 => {}
+            1 => cmd.unit = Unit::parse(parse)?,
+            _ => return Err("ERR syntax error".into()),
+        }
+        Ok(cmd)
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.geo_distance(&self.key, &self.from, &self.to) {
+            Ok(Some(distance)) => distance_frame(distance, self.unit),
+            Ok(None) => Frame::Null,
+            Err(err) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "geodist", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let unit = Bytes::from_static(self.unit.name().as_bytes());
+        command_frame("geodist", self.key, [self.from, self.to, unit])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl GeoSearch {
+    /// Members within `radius` meters of the position at `longitude` and `latitude`, nearest first
+    pub fn radius(key: impl ToString, longitude: f64, latitude: f64, radius: f64) -> GeoSearch {
+        GeoSearch {
+            key: key.to_string(),
+            center: Center::Position(longitude, latitude),
+            shape: Shape::Radius(radius),
+            unit: Unit::Meters,
+            order: Some(Order::Asc),
+            count: None,
+            any: false,
+            with_coord: false,
+            with_dist: false,
+            with_hash: false,
+        }
+    }
+
+    /// The `count` nearest members only
+    pub fn count(mut self, count: u64) -> GeoSearch {
+        self.count = Some(count);
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The reply for a member found
+    fn found_frame(&self, found: Found) -> Frame {
+        if !(self.with_dist || self.with_hash || self.with_coord) {
+            return Frame::Bulk(found.member);
+        }
+
+        let mut item = vec![Frame::Bulk(found.member)];
+        if self.with_dist {
+            item.push(distance_frame(found.distance, self.unit));
+        }
+        if self.with_hash {
+            item.push(Frame::Integer(found.hash as i64));
+        }
+        if self.with_coord {
+            let (longitude, latitude) = geo::decode(found.hash);
+            item.push(Frame::Array(vec![
+                Frame::Bulk(Bytes::from(longitude.to_string())),
+                Frame::Bulk(Bytes::from(latitude.to_string())),
+            ]));
+        }
+        Frame::Array(item)
+    }
+}
+
+impl CommandSpec for GeoSearch {
+    const NAME: &'static str = "geosearch";
+    const ARITY: i32 = -7;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<GeoSearch> {
+        let key = parse.next_string()?;
+        let mut center = None;
+        let mut shape = None;
+        let mut cmd = GeoSearch::radius(key, 0.0, 0.0, 0.0);
+        cmd.order = None;
+
+        loop {
+            let option = match parse.next_string() {
+                Ok(option) => option.to_uppercase(),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            };
+            match &option[..] {
+                "FROMMEMBER" if center.is_none() => {
+                    center = Some(Center::Member(parse.next_bytes()?));
+                }
+                "FROMLONLAT" if center.is_none() => {
+                    let longitude = parse.next_string()?;
+                    let latitude = parse.next_string()?;
+                    let (longitude, latitude) = parse_position(&longitude, &latitude)?;
+                    center = Some(Center::Position(longitude, latitude));
+                }
+                "FROMMEMBER" | "FROMLONLAT" => return Err(FROM_ERROR.into()),
+                "BYRADIUS" if shape.is_none() => {
+                    let radius = parse_number(&parse.next_string()?)?;
+                    if radius < 0.0 {
+                        return Err("ERR radius cannot be negative".into());
+                    }
+                    cmd.unit = Unit::parse(parse)?;
+                    shape = Some(Shape::Radius(radius));
+                }
+                "BYBOX" if shape.is_none() => {
+                    let width = parse_number(&parse.next_string()?)?;
+                    let height = parse_number(&parse.next_string()?)?;
+                    if width < 0.0 || height < 0.0 {
+                        return Err("ERR height or width cannot be negative".into());
+                    }
+                    cmd.unit = Unit::parse(parse)?;
+                    shape = Some(Shape::Box { width, height });
+                }
+                "BYRADIUS" | "BYBOX" => return Err(BY_ERROR.into()),
+                "ASC" => cmd.order = Some(Order::Asc),
+                "DESC" => cmd.order = Some(Order::Desc),
+                "COUNT" => match parse.next_string()?.parse::<i64>() {
+                    Ok(count) if count > 0 => cmd.count = Some(count as u64),
+                    Ok(_) => return Err("ERR COUNT must be > 0".into()),
+                    Err(_) => return Err("ERR value is not an integer or out of range".into()),
+                },
+                "ANY" => cmd.any = true,
+                "WITHCOORD" => cmd.with_coord = true,
+                "WITHDIST" => cmd.with_dist = true,
+                "WITHHASH" => cmd.with_hash = true,
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+
+        cmd.center = center.ok_or(FROM_ERROR)?;
+        cmd.shape = shape.ok_or(BY_ERROR)?;
+        if cmd.any && cmd.count.is_none() {
+            return Err("ERR the ANY argument requires COUNT argument".into());
+        }
+        Ok(cmd)
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let meters = self.unit.meters();
+        let shape = match self.shape {
+            Shape::Radius(radius) => Shape::Radius(radius * meters),
+            Shape::Box { width, height } => Shape::Box {
+                width: width * meters,
+                height: height * meters,
+            },
+        };
+
+        let response = match db.geo_search(&self.key, &self.center, shape) {
+            Ok(mut found) => {
+                let limit = self.count.unwrap_or(u64::MAX).min(usize::MAX as u64) as usize;
+                // the first found, rather than the nearest
+                if self.any {
+                    found.truncate(limit);
+                }
+                // a count without `ANY` returns the nearest members
+                let order = match (self.order, self.count) {
+                    (None, Some(_)) if !self.any => Some(Order::Asc),
+                    (order, _) => order,
+                };
+                match order {
+                    Some(Order::Asc) => found.sort_by(|a, b| a.distance.total_cmp(&b.distance)),
+                    Some(Order::Desc) => found.sort_by(|a, b| b.distance.total_cmp(&a.distance)),
+                    None => {}
+                }
+                found.truncate(limit);
+                Frame::Array(
+                    found
+                        .into_iter()
+                        .map(|found| self.found_frame(found))
+                        .collect(),
+                )
+            }
+            Err(err) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "geosearch", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut args = vec![];
+        match &self.center {
+            Center::Member(member) => {
+                args.push(Bytes::from_static(b"frommember"));
+                args.push(member.clone());
+            }
+            Center::Position(longitude, latitude) => {
+                args.push(Bytes::from_static(b"fromlonlat"));
+                args.push(Bytes::from(longitude.to_string()));
+                args.push(Bytes::from(latitude.to_string()));
+            }
+        }
+        match self.shape {
+            Shape::Radius(radius) => {
+                args.push(Bytes::from_static(b"byradius"));
+                args.push(Bytes::from(radius.to_string()));
+            }
+            Shape::Box { width, height } => {
+                args.push(Bytes::from_static(b"bybox"));
+                args.push(Bytes::from(width.to_string()));
+                args.push(Bytes::from(height.to_string()));
+            }
+        }
+        args.push(Bytes::from_static(self.unit.name().as_bytes()));
+        match self.order {
+            Some(Order::Asc) => args.push(Bytes::from_static(b"asc")),
+            Some(Order::Desc) => args.push(Bytes::from_static(b"desc")),
+            None => {}
+        }
+        if let Some(count) = self.count {
+            args.push(Bytes::from_static(b"count"));
+            args.push(Bytes::from(count.to_string()));
+            if self.any {
+                args.push(Bytes::from_static(b"any"));
+            }
+        }
+        for (set, option) in [
+            (self.with_coord, &b"withcoord"[..]),
+            (self.with_dist, &b"withdist"[..]),
+            (self.with_hash, &b"withhash"[..]),
+        ] {
+            if set {
+                args.push(Bytes::from_static(option));
+            }
+        }
+        command_frame("geosearch", self.key, args)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
@@ -1,8 +1,11 @@
-use crate::{Connection, Db, Frame, Parse};
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
 
 use bytes::Bytes;
 use tracing::{debug, instrument};
 
+use super::CommandSpec;
+
 #[derive(Debug)]
 
 pub struct Get {
@@ -19,8 +22,14 @@ impl Get {
     pub fn key(&self) -> &str {
         &self.key
     }
+}
+
+impl CommandSpec for Get {
+    const NAME: &'static str = "get";
+    const ARITY: i32 = 2;
+    const CATEGORY: Option<Category> = Some(Category::Read);
 
-    pub(crate) fn parse_frame(parse: &mut Parse) -> crate::Result<Get> {
+    fn parse(parse: &mut Parse) -> crate::Result<Get> {
         // The `GET` string has already been consumed. The next value is the name of the key to
         // get. If the next value is not a string or the input is fully consumed, the an error is
         // returned!
@@ -29,29 +38,38 @@ impl Get {
         Ok(Get { key })
     }
 
-
     /// Apply the Get command into the db instance
     ///
     /// The response is written into `dst`. This is called by the server in otrder to execute a
     /// received command
-    #[instrument(skip(self, db, dst))]
-    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
-        let response = if let Some(value) = db.get(&self.key) {
-            Frame::Bulk(value)
-        } else {
-            Frame::Null
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.get(&self.key) {
+            Ok(Some(value)) => Frame::Bulk(value),
+            Ok(None) => Frame::Null,
+            Err(err) => super::error_reply(&err),
         };
 
-        debug!(?response);
+        super::audit_read(db, dst, "get", &self.key);
+
+        debug!(%response);
         dst.write_frame(&response).await?;
         Ok(())
     }
 
-    pub(crate) fn into_frame(self) -> Frame {
-        let mut frame = Frame::array();
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("get".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+        ])
+    }
 
-        frame.push_bulk(Bytes::from("get".as_bytes()));
-        frame.push_bulk(Bytes::from(self.key.into_bytes()));
-        frame
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
     }
 }
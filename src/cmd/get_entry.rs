@@ -0,0 +1,81 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use std::time::UNIX_EPOCH;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Get the value of a key together with its metadata in a single round trip.
+///
+/// `GETENTRY key` replies with `(nil)` when the key doesn't exist, otherwise with an array of
+/// `[value, ttl in milliseconds or (nil), version, modification time in unix milliseconds]`.
+#[derive(Debug)]
+pub struct GetEntry {
+    key: String,
+}
+
+impl GetEntry {
+    pub fn new(key: impl ToString) -> GetEntry {
+        GetEntry {
+            key: key.to_string(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for GetEntry {
+    const NAME: &'static str = "getentry";
+    const ARITY: i32 = 2;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<GetEntry> {
+        let key = parse.next_string()?;
+        Ok(GetEntry { key })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.get_entry(&self.key) {
+            Ok(Some(entry)) => {
+                let mut frame = vec![Frame::Bulk(entry.data)];
+                match entry.ttl {
+                    Some(ttl) => frame.push(Frame::Integer(ttl.as_millis() as i64)),
+                    None => frame.push(Frame::Null),
+                }
+                frame.push(Frame::Integer(entry.version as i64));
+                let mtime = entry.modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+                frame.push(Frame::Integer(mtime.as_millis() as i64));
+                Frame::Array(frame)
+            }
+            Ok(None) => Frame::Null,
+            Err(err) => super::error_reply(&err),
+        };
+
+        super::audit_read(db, dst, "getentry", &self.key);
+
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("getentry".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+        ])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
@@ -0,0 +1,705 @@
+use crate::acl::Category;
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Number of fields `HSCAN` walks by default
+const SCAN_COUNT: u64 = 10;
+
+/// Set fields of a hash to values, the hash being created if missing.
+///
+/// `HSET key field value [field value ...]`
+///
+/// Replies with the number of fields added, the fields which already existed are updated.
+#[derive(Debug)]
+pub struct HSet {
+    key: String,
+    fields: Vec<(Bytes, Bytes)>,
+}
+
+/// Value of a field of a hash.
+///
+/// `HGET key field`
+///
+/// Replies with the value, or nil if the field or the key doesn't exist.
+#[derive(Debug)]
+pub struct HGet {
+    key: String,
+    field: Bytes,
+}
+
+/// Remove fields from a hash.
+///
+/// `HDEL key field [field ...]`
+///
+/// Replies with the number of fields removed. The hash is removed once empty.
+#[derive(Debug)]
+pub struct HDel {
+    key: String,
+    fields: Vec<Bytes>,
+}
+
+/// Number of fields of a hash, `0` if the key doesn't exist.
+///
+/// `HLEN key`
+#[derive(Debug)]
+pub struct HLen {
+    key: String,
+}
+
+/// Every field of a hash along with its value.
+///
+/// `HGETALL key`
+///
+/// Replies with an array of each field followed by its value, empty if the key doesn't exist.
+#[derive(Debug)]
+pub struct HGetAll {
+    key: String,
+}
+
+/// Add an increment to the integer held by a field of a hash.
+///
+/// `HINCRBY key field increment`
+///
+/// A missing field, or hash, is created holding `0` first. Replies with the new value.
+#[derive(Debug)]
+pub struct HIncrBy {
+    key: String,
+    field: Bytes,
+    delta: i64,
+}
+
+/// Add an increment to the number held by a field of a hash.
+///
+/// `HINCRBYFLOAT key field increment`
+///
+/// A missing field, or hash, is created holding `0` first. Replies with the new value in its
+/// decimal form.
+#[derive(Debug)]
+pub struct HIncrByFloat {
+    key: String,
+    field: Bytes,
+    delta: f64,
+}
+
+/// Fields of a hash picked at random.
+///
+/// `HRANDFIELD key [count [WITHVALUES]]`
+///
+/// Without a count, replies with a single field or nil if the key doesn't exist. With a positive
+/// count, replies with that many distinct fields at most. With a negative one, the same field may
+/// be returned more than once. `WITHVALUES` returns each field followed by its value.
+#[derive(Debug)]
+pub struct HRandField {
+    key: String,
+    count: Option<i64>,
+    with_values: bool,
+}
+
+/// Iterate over the fields of a hash.
+///
+/// `HSCAN key cursor [MATCH pattern] [COUNT count]`
+///
+/// Replies with the cursor to pass to the next call, `0` once done, along with the fields walked,
+/// each followed by its value. Up to `count` fields are walked per call, 10 by default, only those
+/// matching the glob `pattern` being returned. Every field present for the whole iteration is
+/// returned.
+#[derive(Debug)]
+pub struct HScan {
+    key: String,
+    cursor: u64,
+    pattern: Option<Bytes>,
+    count: u64,
+}
+
+fn command_frame(name: &'static str, key: String, args: impl IntoIterator<Item = Bytes>) -> Frame {
+    let mut frame = Vec::new();
+    frame.push(Frame::Bulk(Bytes::from(name.as_bytes())));
+    frame.push(Frame::Bulk(Bytes::from(key.into_bytes())));
+    for arg in args {
+        frame.push(Frame::Bulk(arg));
+    }
+    Frame::Array(frame)
+}
+
+fn parse_integer(parse: &mut Parse) -> crate::Result<i64> {
+    parse
+        .next_string()?
+        .parse::<i64>()
+        .map_err(|_| "ERR value is not an integer or out of range".into())
+}
+
+/// Each field followed by its value, as `HGETALL` replies
+fn fields_frame(fields: Vec<(Bytes, Bytes)>) -> Frame {
+    Frame::Array(
+        fields
+            .into_iter()
+            .flat_map(|(field, value)| [Frame::Bulk(field), Frame::Bulk(value)])
+            .collect(),
+    )
+}
+
+impl HSet {
+    pub fn new(key: impl ToString, fields: Vec<(Bytes, Bytes)>) -> HSet {
+        HSet {
+            key: key.to_string(),
+            fields,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for HSet {
+    const NAME: &'static str = "hset";
+    const ARITY: i32 = -4;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<HSet> {
+        let key = parse.next_string()?;
+        let mut fields = vec![(parse.next_bytes()?, parse.next_bytes()?)];
+        loop {
+            match parse.next_bytes() {
+                // a field without its value is a wrong number of arguments
+                Ok(field) => fields.push((field, parse.next_bytes()?)),
+                Err(ParseError::EndOfStream) => return Ok(HSet { key, fields }),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.hash_set(&self.key, self.fields) {
+            Ok(added) => Frame::Integer(added as i64),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let args = self
+            .fields
+            .into_iter()
+            .flat_map(|(field, value)| [field, value]);
+        command_frame("hset", self.key, args)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl HGet {
+    pub fn new(key: impl ToString, field: Bytes) -> HGet {
+        HGet {
+            key: key.to_string(),
+            field,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for HGet {
+    const NAME: &'static str = "hget";
+    const ARITY: i32 = 3;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<HGet> {
+        let key = parse.next_string()?;
+        let field = parse.next_bytes()?;
+        Ok(HGet { key, field })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.hash_get(&self.key, &self.field) {
+            Ok(Some(value)) => Frame::Bulk(value),
+            Ok(None) => Frame::Null,
+            Err(err) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "hget", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        command_frame("hget", self.key, Some(self.field))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl HDel {
+    pub fn new(key: impl ToString, fields: Vec<Bytes>) -> HDel {
+        HDel {
+            key: key.to_string(),
+            fields,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for HDel {
+    const NAME: &'static str = "hdel";
+    const ARITY: i32 = -3;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+
+    fn parse(parse: &mut Parse) -> crate::Result<HDel> {
+        let key = parse.next_string()?;
+        let mut fields = vec![parse.next_bytes()?];
+        loop {
+            match parse.next_bytes() {
+                Ok(field) => fields.push(field),
+                Err(ParseError::EndOfStream) => return Ok(HDel { key, fields }),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.hash_delete(&self.key, self.fields) {
+            Ok(removed) => Frame::Integer(removed as i64),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        command_frame("hdel", self.key, self.fields)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl HLen {
+    pub fn new(key: impl ToString) -> HLen {
+        HLen {
+            key: key.to_string(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for HLen {
+    const NAME: &'static str = "hlen";
+    const ARITY: i32 = 2;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<HLen> {
+        Ok(HLen::new(parse.next_string()?))
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.hash_len(&self.key) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "hlen", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        command_frame("hlen", self.key, None)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl HGetAll {
+    pub fn new(key: impl ToString) -> HGetAll {
+        HGetAll {
+            key: key.to_string(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for HGetAll {
+    const NAME: &'static str = "hgetall";
+    const ARITY: i32 = 2;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<HGetAll> {
+        Ok(HGetAll::new(parse.next_string()?))
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.hash_get_all(&self.key) {
+            Ok(fields) => fields_frame(fields),
+            Err(err) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "hgetall", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        command_frame("hgetall", self.key, None)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl HIncrBy {
+    pub fn new(key: impl ToString, field: Bytes, delta: i64) -> HIncrBy {
+        HIncrBy {
+            key: key.to_string(),
+            field,
+            delta,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for HIncrBy {
+    const NAME: &'static str = "hincrby";
+    const ARITY: i32 = 4;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<HIncrBy> {
+        let key = parse.next_string()?;
+        let field = parse.next_bytes()?;
+        let delta = parse_integer(parse)?;
+        Ok(HIncrBy { key, field, delta })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.hash_incr_by(&self.key, self.field, self.delta) {
+            Ok(value) => Frame::Integer(value),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let delta = Bytes::from(self.delta.to_string());
+        command_frame("hincrby", self.key, [self.field, delta])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl HIncrByFloat {
+    pub fn new(key: impl ToString, field: Bytes, delta: f64) -> HIncrByFloat {
+        HIncrByFloat {
+            key: key.to_string(),
+            field,
+            delta,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for HIncrByFloat {
+    const NAME: &'static str = "hincrbyfloat";
+    const ARITY: i32 = 4;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<HIncrByFloat> {
+        let key = parse.next_string()?;
+        let field = parse.next_bytes()?;
+        let delta = parse
+            .next_string()?
+            .parse::<f64>()
+            .map_err(|_| "ERR value is not a valid float")?;
+        Ok(HIncrByFloat { key, field, delta })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.hash_incr_by_float(&self.key, self.field, self.delta) {
+            Ok(value) => Frame::Bulk(value),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let delta = Bytes::from(self.delta.to_string());
+        command_frame("hincrbyfloat", self.key, [self.field, delta])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl HRandField {
+    /// A single field, without its value
+    pub fn new(key: impl ToString) -> HRandField {
+        HRandField {
+            key: key.to_string(),
+            count: None,
+            with_values: false,
+        }
+    }
+
+    /// `count` fields, distinct ones if it is positive
+    pub fn count(mut self, count: i64) -> HRandField {
+        self.count = Some(count);
+        self
+    }
+
+    /// Return each field followed by its value, along with a count only
+    pub fn with_values(mut self) -> HRandField {
+        self.with_values = true;
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for HRandField {
+    const NAME: &'static str = "hrandfield";
+    const ARITY: i32 = -2;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<HRandField> {
+        let mut cmd = HRandField::new(parse.next_string()?);
+        if parse.remaining() == 0 {
+            return Ok(cmd);
+        }
+        cmd.count = Some(parse_integer(parse)?);
+
+        match parse.next_string() {
+            Ok(option) if option.eq_ignore_ascii_case("withvalues") => cmd.with_values = true,
+            Ok(_) => return Err("ERR syntax error".into()),
+            Err(ParseError::EndOfStream) => return Ok(cmd),
+            Err(err) => return Err(err.into()),
+        }
+        if parse.remaining() > 0 {
+            return Err("ERR syntax error".into());
+        }
+        Ok(cmd)
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let fields = db.hash_random_fields(&self.key, self.count.unwrap_or(1));
+        let response = match (fields, self.count) {
+            (Ok(fields), None) => match fields.into_iter().next() {
+                Some((field, _)) => Frame::Bulk(field),
+                None => Frame::Null,
+            },
+            (Ok(fields), Some(_)) if self.with_values => fields_frame(fields),
+            (Ok(fields), Some(_)) => Frame::Array(
+                fields
+                    .into_iter()
+                    .map(|(field, _)| Frame::Bulk(field))
+                    .collect(),
+            ),
+            (Err(err), _) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "hrandfield", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut args = vec![];
+        if let Some(count) = self.count {
+            args.push(Bytes::from(count.to_string()));
+            if self.with_values {
+                args.push(Bytes::from_static(b"withvalues"));
+            }
+        }
+        command_frame("hrandfield", self.key, args)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl HScan {
+    /// Walk the fields from `cursor`, `0` starting the iteration
+    pub fn new(key: impl ToString, cursor: u64) -> HScan {
+        HScan {
+            key: key.to_string(),
+            cursor,
+            pattern: None,
+            count: SCAN_COUNT,
+        }
+    }
+
+    /// Only return the fields matching the glob `pattern`
+    pub fn pattern(mut self, pattern: Bytes) -> HScan {
+        self.pattern = Some(pattern);
+        self
+    }
+
+    /// Walk up to `count` fields
+    pub fn count(mut self, count: u64) -> HScan {
+        self.count = count;
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for HScan {
+    const NAME: &'static str = "hscan";
+    const ARITY: i32 = -3;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<HScan> {
+        let key = parse.next_string()?;
+        let cursor = parse
+            .next_string()?
+            .parse::<u64>()
+            .map_err(|_| "ERR invalid cursor")?;
+        let mut cmd = HScan::new(key, cursor);
+
+        loop {
+            let option = match parse.next_string() {
+                Ok(option) => option.to_uppercase(),
+                Err(ParseError::EndOfStream) => return Ok(cmd),
+                Err(err) => return Err(err.into()),
+            };
+            match &option[..] {
+                "MATCH" => cmd.pattern = Some(parse.next_bytes()?),
+                "COUNT" => match parse_integer(parse)? {
+                    count if count < 1 => return Err("ERR syntax error".into()),
+                    count => cmd.count = count as u64,
+                },
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let count = self.count.min(usize::MAX as u64) as usize;
+        let response = match db.hash_scan(&self.key, self.cursor, self.pattern.as_deref(), count) {
+            Ok((next, fields)) => Frame::Array(vec![
+                Frame::Bulk(Bytes::from(next.to_string())),
+                fields_frame(fields),
+            ]),
+            Err(err) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "hscan", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut args = vec![Bytes::from(self.cursor.to_string())];
+        if let Some(pattern) = self.pattern {
+            args.push(Bytes::from_static(b"match"));
+            args.push(pattern);
+        }
+        if self.count != SCAN_COUNT {
+            args.push(Bytes::from_static(b"count"));
+            args.push(Bytes::from(self.count.to_string()));
+        }
+        command_frame("hscan", self.key, args)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
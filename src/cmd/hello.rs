@@ -0,0 +1,114 @@
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Only RESP2 is spoken
+const PROTOCOL_VERSION: u64 = 2;
+
+/// Handshake describing the server, and optionally switching the connection to tagged framing.
+///
+/// `HELLO [protover [TAGGED]]`. The reply is a flat array of field/value pairs: `server`,
+/// `version`, `proto` and `tagged`.
+///
+/// Once `TAGGED` is negotiated, every request must be sent as `*2 :tag request` and every reply
+/// comes back as `*2 :tag reply`, with the tag of its request. The server runs the requests of
+/// the connection concurrently and replies as soon as each one completes, in any order: a request
+/// depending on the effect of another must wait for its reply. Streaming commands such as
+/// `SUBSCRIBE` are refused on a tagged connection, and so is `HELLO` itself. The reply to the
+/// `HELLO` switching the connection is not tagged.
+#[derive(Debug, Default)]
+pub struct Hello {
+    protover: Option<u64>,
+    tagged: bool,
+}
+
+impl Hello {
+    pub fn new() -> Hello {
+        Hello::default()
+    }
+
+    /// Handshake switching the connection to tagged framing
+    pub fn tagged() -> Hello {
+        Hello {
+            protover: Some(PROTOCOL_VERSION),
+            tagged: true,
+        }
+    }
+}
+
+impl CommandSpec for Hello {
+    const NAME: &'static str = "hello";
+    const ARITY: i32 = -1;
+    const CATEGORY: Option<Category> = None;
+
+    fn parse(parse: &mut Parse) -> crate::Result<Hello> {
+        let protover = match parse.next_int() {
+            Ok(protover) => protover,
+            Err(ParseError::EndOfStream) => return Ok(Hello::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let tagged = match parse.next_string() {
+            Ok(option) if option.to_uppercase() == "TAGGED" => true,
+            Ok(option) => return Err(format!("ERR Syntax error in HELLO option '{}'", option).into()),
+            Err(ParseError::EndOfStream) => false,
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Hello {
+            protover: Some(protover),
+            tagged,
+        })
+    }
+
+    #[instrument(skip(self, _db, dst, _shutdown))]
+    async fn apply(
+        self,
+        _db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        if let Some(protover) = self.protover {
+            if protover != PROTOCOL_VERSION {
+                let response = Frame::error("NOPROTO unsupported protocol version");
+                dst.write_frame(&response).await?;
+                return Ok(());
+            }
+        }
+
+        let tagged = self.tagged || dst.is_tagged();
+        let response = Frame::Array(vec![
+            Frame::Bulk(Bytes::from_static(b"server")),
+            Frame::Bulk(Bytes::from_static(b"redust")),
+            Frame::Bulk(Bytes::from_static(b"version")),
+            Frame::Bulk(Bytes::from_static(env!("CARGO_PKG_VERSION").as_bytes())),
+            Frame::Bulk(Bytes::from_static(b"proto")),
+            Frame::Integer(PROTOCOL_VERSION as i64),
+            Frame::Bulk(Bytes::from_static(b"tagged")),
+            Frame::Integer(tagged as i64),
+        ]);
+
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        if self.tagged {
+            dst.set_tagged();
+        }
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = Vec::new();
+        frame.push(Frame::Bulk(Bytes::from("hello".as_bytes())));
+        if let Some(protover) = self.protover {
+            frame.push(Frame::Integer(protover as i64));
+            if self.tagged {
+                frame.push(Frame::Bulk(Bytes::from("TAGGED".as_bytes())));
+            }
+        }
+        Frame::Array(frame)
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// `INCR key` adds one to the integer held by `key` and replies the new value. A missing key is
+/// set to `1`, the expiration of an existing key is kept.
+///
+/// Counters are signed 64 bit integers: the command fails if the value isn't one, or if the
+/// increment would go past `i64::MAX`.
+#[derive(Debug)]
+pub struct Incr {
+    key: String,
+}
+
+impl Incr {
+    pub fn new(key: impl ToString) -> Incr {
+        Incr {
+            key: key.to_string(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for Incr {
+    const NAME: &'static str = "incr";
+    const ARITY: i32 = 2;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<Incr> {
+        let key = parse.next_string()?;
+        Ok(Incr { key })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.incr_by(self.key, 1) {
+            Ok(value) => Frame::Integer(value),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("incr".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+        ])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use std::fmt::Write;
+use std::sync::atomic::Ordering;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Returns information and statistics about the server, in the Redis `INFO` format.
+///
+/// `INFO [section]`, every section is returned when none is given.
+#[derive(Debug)]
+pub struct Info {
+    section: Option<String>,
+}
+
+/// Renders one section of the report
+type Section = fn(&Db, &mut String);
+
+/// Known sections, in the order they are rendered
+const SECTIONS: &[(&str, Section)] = &[
+    ("clients", clients),
+    ("memory", memory),
+    ("stats", stats),
+    ("replication", replication),
+    ("commandstats", commandstats),
+    ("latencystats", latencystats),
+];
+
+impl Info {
+    pub fn new(section: Option<String>) -> Info {
+        Info { section }
+    }
+
+    pub fn section(&self) -> Option<&str> {
+        self.section.as_deref()
+    }
+}
+
+impl CommandSpec for Info {
+    const NAME: &'static str = "info";
+    const ARITY: i32 = -1;
+    const CATEGORY: Option<Category> = Some(Category::Admin);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Info> {
+        match parse.next_string() {
+            Ok(section) => Ok(Info::new(Some(section.to_lowercase()))),
+            Err(ParseError::EndOfStream) => Ok(Info::new(None)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let mut out = String::new();
+
+        for (name, render) in SECTIONS {
+            let wanted = match self.section.as_deref() {
+                None | Some("all") | Some("default") | Some("everything") => true,
+                Some(section) => section == *name,
+            };
+            if wanted {
+                if !out.is_empty() {
+                    out.push_str("\r\n");
+                }
+                render(db, &mut out);
+            }
+        }
+
+        let response = Frame::Bulk(Bytes::from(out));
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = Vec::new();
+        frame.push(Frame::Bulk(Bytes::from("info".as_bytes())));
+        if let Some(section) = self.section {
+            frame.push(Frame::Bulk(Bytes::from(section.into_bytes())));
+        }
+        Frame::Array(frame)
+    }
+}
+
+fn clients(db: &Db, out: &mut String) {
+    let clients = db.clients();
+
+    out.push_str("# Clients\r\n");
+    let _ = write!(out, "connected_clients:{}\r\n", clients.connected.load(Ordering::Relaxed));
+    let _ = write!(out, "maxclients:{}\r\n", db.config().max_clients);
+}
+
+fn memory(db: &Db, out: &mut String) {
+    let stats = db.memory_stats();
+
+    out.push_str("# Memory\r\n");
+    let _ = write!(out, "keyspace_shards:{}\r\n", stats.shards);
+    let _ = write!(out, "keys:{}\r\n", stats.keys);
+    let _ = write!(out, "keys_capacity:{}\r\n", stats.keys_capacity);
+    let _ = write!(out, "expires:{}\r\n", stats.expires);
+    let _ = write!(out, "used_memory_dataset:{}\r\n", stats.dataset_bytes);
+    let _ = write!(out, "maxmemory:{}\r\n", stats.maxmemory);
+    out.push_str("maxmemory_policy:noeviction\r\n");
+    let _ = write!(out, "pubsub_channels:{}\r\n", stats.pubsub_channels);
+    let _ = write!(out, "pubsub_channels_capacity:{}\r\n", stats.pubsub_channels_capacity);
+    let _ = write!(out, "keyspace_overhead_bytes:{}\r\n", stats.overhead_bytes());
+    let _ = write!(out, "mem_fragmentation_ratio:{:.2}\r\n", stats.fragmentation_ratio());
+    let _ = write!(out, "active_defrag_enabled:{}\r\n", stats.active_defrag as u8);
+    let _ = write!(out, "active_defrag_runs:{}\r\n", stats.defrag_runs);
+}
+
+fn stats(db: &Db, out: &mut String) {
+    let pubsub = db.pubsub_totals();
+
+    let clients = db.clients();
+
+    out.push_str("# Stats\r\n");
+    let _ = write!(
+        out,
+        "total_connections_received:{}\r\n",
+        clients.total_connections.load(Ordering::Relaxed)
+    );
+    let _ = write!(
+        out,
+        "rejected_connections:{}\r\n",
+        clients.rejected_connections.load(Ordering::Relaxed)
+    );
+    let _ = write!(
+        out,
+        "rate_limited_commands:{}\r\n",
+        clients.rate_limited_commands.load(Ordering::Relaxed)
+    );
+    let _ = write!(
+        out,
+        "timed_out_commands:{}\r\n",
+        clients.timed_out_commands.load(Ordering::Relaxed)
+    );
+    let _ = write!(out, "pubsub_subscribers:{}\r\n", pubsub.subscribers);
+    let _ = write!(out, "pubsub_published:{}\r\n", pubsub.published);
+    let _ = write!(out, "pubsub_delivered:{}\r\n", pubsub.delivered);
+    let _ = write!(out, "pubsub_dropped:{}\r\n", pubsub.dropped);
+    let _ = write!(out, "pubsub_disconnected:{}\r\n", pubsub.disconnected);
+}
+
+fn replication(db: &Db, out: &mut String) {
+    out.push_str("# Replication\r\n");
+    out.push_str("role:master\r\n");
+    let _ = write!(out, "last_write_seq:{}\r\n", db.last_seq());
+}
+
+fn commandstats(db: &Db, out: &mut String) {
+    out.push_str("# Commandstats\r\n");
+    for (command, stats) in db.command_stats().snapshot() {
+        let _ = write!(
+            out,
+            "cmdstat_{}:calls={},usec={},usec_per_call={:.2},failed_calls={}\r\n",
+            command,
+            stats.calls,
+            stats.usec,
+            stats.usec as f64 / stats.calls as f64,
+            stats.failed
+        );
+    }
+}
+
+fn latencystats(db: &Db, out: &mut String) {
+    let percentiles = db.config().latency_tracking_info_percentiles;
+
+    out.push_str("# Latencystats\r\n");
+    for (command, latencies) in db.latency().percentiles(&percentiles) {
+        let values: Vec<String> = percentiles
+            .iter()
+            .zip(latencies)
+            .map(|(percentile, usec)| format!("p{}={}", percentile, usec))
+            .collect();
+        let _ = write!(out, "latency_percentiles_usec_{}:{}\r\n", command, values.join(","));
+    }
+}
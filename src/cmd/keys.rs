@@ -0,0 +1,60 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// `KEYS pattern` replies the keys matching the glob-style `pattern`, in no particular order.
+///
+/// The whole key space is walked, which blocks the writes of each shard while it is scanned. Meant
+/// for debugging, not for the regular traffic.
+#[derive(Debug)]
+pub struct Keys {
+    pattern: String,
+}
+
+impl Keys {
+    pub fn new(pattern: impl ToString) -> Keys {
+        Keys {
+            pattern: pattern.to_string(),
+        }
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+}
+
+impl CommandSpec for Keys {
+    const NAME: &'static str = "keys";
+    const ARITY: i32 = 2;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Keys> {
+        let pattern = parse.next_string()?;
+        Ok(Keys { pattern })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let keys = db.keys(&self.pattern).into_iter().map(|key| Frame::Bulk(Bytes::from(key)));
+        let response = Frame::Array(keys.collect());
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("keys".as_bytes())),
+            Frame::Bulk(Bytes::from(self.pattern.into_bytes())),
+        ])
+    }
+}
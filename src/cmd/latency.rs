@@ -0,0 +1,175 @@
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Command latency tracking.
+///
+/// * `LATENCY PERCENTILE command percentile` returns the latency of `command` at `percentile`, in
+///   microseconds, or nil if the command never ran.
+/// * `LATENCY LATEST` returns, for every command which reached `latency-monitor-threshold`, its
+///   name, the unix time of its latest spike, the latency of that spike and the highest latency
+///   seen, both in milliseconds.
+/// * `LATENCY HISTORY command` returns the spikes of `command`, as pairs of unix time and latency
+///   in milliseconds, oldest first.
+/// * `LATENCY RESET [command ...]` drops the latencies recorded for the given commands, or for
+///   every command. Returns the number of commands reset.
+///
+/// Percentiles of every command are also reported by `INFO latencystats`.
+#[derive(Debug)]
+pub struct Latency {
+    subcommand: Subcommand,
+}
+
+#[derive(Debug)]
+enum Subcommand {
+    Percentile { command: String, percentile: f64 },
+    Latest,
+    History(String),
+    Reset(Vec<String>),
+}
+
+impl Latency {
+    /// Create a `LATENCY PERCENTILE command percentile` command
+    pub fn percentile(command: impl ToString, percentile: f64) -> Latency {
+        Latency {
+            subcommand: Subcommand::Percentile {
+                command: command.to_string(),
+                percentile,
+            },
+        }
+    }
+
+    /// Create a `LATENCY LATEST` command
+    pub fn latest() -> Latency {
+        Latency {
+            subcommand: Subcommand::Latest,
+        }
+    }
+
+    /// Create a `LATENCY HISTORY command` command
+    pub fn history(command: impl ToString) -> Latency {
+        Latency {
+            subcommand: Subcommand::History(command.to_string()),
+        }
+    }
+
+    /// Create a `LATENCY RESET [command ...]` command
+    pub fn reset(commands: Vec<String>) -> Latency {
+        Latency {
+            subcommand: Subcommand::Reset(commands),
+        }
+    }
+}
+
+impl CommandSpec for Latency {
+    const NAME: &'static str = "latency";
+    const ARITY: i32 = -2;
+    const CATEGORY: Option<Category> = Some(Category::Admin);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Latency> {
+        let subcommand = match &parse.next_string()?.to_lowercase()[..] {
+            "percentile" => {
+                let command = parse.next_string()?.to_lowercase();
+                let percentile = match parse.next_string()?.parse::<f64>() {
+                    Ok(percentile) if (0.0..=100.0).contains(&percentile) => percentile,
+                    _ => return Err("ERR percentile must be a number between 0 and 100".into()),
+                };
+                Subcommand::Percentile { command, percentile }
+            }
+            "latest" => Subcommand::Latest,
+            "history" => Subcommand::History(parse.next_string()?.to_lowercase()),
+            "reset" => {
+                let mut commands = vec![];
+                loop {
+                    match parse.next_string() {
+                        Ok(command) => commands.push(command),
+                        Err(ParseError::EndOfStream) => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                Subcommand::Reset(commands)
+            }
+            other => return Err(format!("ERR unknown subcommand '{}' for 'latency'", other).into()),
+        };
+        Ok(Latency { subcommand })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match self.subcommand {
+            Subcommand::Percentile { command, percentile } => {
+                match db.latency().percentile(&command, percentile) {
+                    Some(usec) => Frame::Integer(usec as i64),
+                    None => Frame::Null,
+                }
+            }
+            Subcommand::Latest => {
+                let events = db
+                    .latency()
+                    .latest()
+                    .into_iter()
+                    .map(|latest| {
+                        Frame::Array(vec![
+                            Frame::from(latest.command),
+                            Frame::Integer(latest.time as i64),
+                            Frame::Integer(latest.latency as i64),
+                            Frame::Integer(latest.max as i64),
+                        ])
+                    })
+                    .collect();
+                Frame::Array(events)
+            }
+            Subcommand::History(command) => {
+                let samples = db
+                    .latency()
+                    .history(&command)
+                    .into_iter()
+                    .map(|(time, latency)| {
+                        Frame::Array(vec![Frame::Integer(time as i64), Frame::Integer(latency as i64)])
+                    })
+                    .collect();
+                Frame::Array(samples)
+            }
+            Subcommand::Reset(commands) => Frame::Integer(db.latency().reset(&commands) as i64),
+        };
+
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = Vec::new();
+        frame.push(Frame::Bulk(Bytes::from("latency".as_bytes())));
+        match self.subcommand {
+            Subcommand::Percentile { command, percentile } => {
+                frame.push(Frame::Bulk(Bytes::from("percentile".as_bytes())));
+                frame.push(Frame::Bulk(Bytes::from(command.into_bytes())));
+                frame.push(Frame::Bulk(Bytes::from(percentile.to_string().into_bytes())));
+            }
+            Subcommand::Latest => {
+                frame.push(Frame::Bulk(Bytes::from("latest".as_bytes())));
+            }
+            Subcommand::History(command) => {
+                frame.push(Frame::Bulk(Bytes::from("history".as_bytes())));
+                frame.push(Frame::Bulk(Bytes::from(command.into_bytes())));
+            }
+            Subcommand::Reset(commands) => {
+                frame.push(Frame::Bulk(Bytes::from("reset".as_bytes())));
+                for command in commands {
+                    frame.push(Frame::Bulk(Bytes::from(command.into_bytes())));
+                }
+            }
+        }
+        Frame::Array(frame)
+    }
+}
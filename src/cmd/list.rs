@@ -0,0 +1,962 @@
+use crate::acl::Category;
+use crate::db::{ListEnd, Side};
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Insert elements at the head of a list, created if missing.
+///
+/// `LPUSH key element [element ...]`
+///
+/// The elements are inserted one after the other, so the last one ends up first. Replies with the
+/// length of the list.
+#[derive(Debug)]
+pub struct LPush {
+    key: String,
+    elements: Vec<Bytes>,
+}
+
+/// Insert elements at the tail of a list, created if missing.
+///
+/// `RPUSH key element [element ...]`
+///
+/// Replies with the length of the list.
+#[derive(Debug)]
+pub struct RPush {
+    key: String,
+    elements: Vec<Bytes>,
+}
+
+/// Remove and return the first elements of a list.
+///
+/// `LPOP key [count]`
+///
+/// Replies with the first element, or nil if the key doesn't exist. With a count, replies with an
+/// array of up to `count` elements, or nil if the key doesn't exist. The list is removed once
+/// empty.
+#[derive(Debug)]
+pub struct LPop {
+    key: String,
+    count: Option<u64>,
+}
+
+/// Remove and return the last elements of a list.
+///
+/// `RPOP key [count]`
+///
+/// Replies as `LPOP` does, the elements being taken from the tail, last first.
+#[derive(Debug)]
+pub struct RPop {
+    key: String,
+    count: Option<u64>,
+}
+
+/// Number of elements of a list, `0` if the key doesn't exist.
+///
+/// `LLEN key`
+#[derive(Debug)]
+pub struct LLen {
+    key: String,
+}
+
+/// Elements of a list from `start` to `stop` included.
+///
+/// `LRANGE key start stop`
+///
+/// Negative indexes count from the tail of the list, `-1` being the last element.
+#[derive(Debug)]
+pub struct LRange {
+    key: String,
+    start: i64,
+    stop: i64,
+}
+
+/// Index of the first element equal to `element` in a list.
+///
+/// `LPOS key element [RANK rank] [COUNT num-matches] [MAXLEN len]`
+///
+/// Replies with the index of the match, or nil if there is none. `RANK` skips the first matches,
+/// `2` returning the second one, and searches from the tail if negative, `-1` returning the last
+/// match. With `COUNT`, replies with an array of the indexes of up to `num-matches` matches, `0`
+/// meaning all of them. `MAXLEN` limits the search to the first `len` elements, or the last ones
+/// with a negative rank.
+#[derive(Debug)]
+pub struct LPos {
+    key: String,
+    element: Bytes,
+    rank: i64,
+    count: Option<u64>,
+    max_len: u64,
+}
+
+/// Insert an element next to another one of a list.
+///
+/// `LINSERT key BEFORE|AFTER pivot element`
+///
+/// `element` is inserted before or after the first element equal to `pivot`. Replies with the
+/// length of the list, `0` if the key doesn't exist, or `-1` if `pivot` isn't found.
+#[derive(Debug)]
+pub struct LInsert {
+    key: String,
+    side: Side,
+    pivot: Bytes,
+    element: Bytes,
+}
+
+/// Remove the elements of a list equal to a value.
+///
+/// `LREM key count element`
+///
+/// Removes the first `count` matches if `count` is positive, the last ones if negative, or all of
+/// them if `0`. Replies with the number of elements removed. The list is removed once empty.
+#[derive(Debug)]
+pub struct LRem {
+    key: String,
+    count: i64,
+    element: Bytes,
+}
+
+/// Replace the element of a list at an index.
+///
+/// `LSET key index element`
+///
+/// Negative indexes count from the tail of the list. Replies `OK`, or an error if the key doesn't
+/// exist or the index is out of range.
+#[derive(Debug)]
+pub struct LSet {
+    key: String,
+    index: i64,
+    element: Bytes,
+}
+
+/// Pop the first element of the first non-empty list among several, blocking until there is one.
+///
+/// `BLPOP key [key ...] timeout`
+///
+/// Replies with the key and the element popped. If every list is empty, the client blocks until
+/// an element is pushed to one of them, and replies nil once `timeout` seconds elapse. A timeout
+/// of `0` blocks forever. The clients blocked on a list are each handed one element, in the order
+/// they blocked.
+#[derive(Debug)]
+pub struct BLPop {
+    keys: Vec<String>,
+    timeout: Duration,
+}
+
+/// Pop the last element of the first non-empty list among several, blocking until there is one.
+///
+/// `BRPOP key [key ...] timeout`
+///
+/// Blocks and replies as `BLPOP` does, the element being taken from the tail.
+#[derive(Debug)]
+pub struct BRPop {
+    keys: Vec<String>,
+    timeout: Duration,
+}
+
+fn parse_index(parse: &mut Parse) -> crate::Result<i64> {
+    parse
+        .next_string()?
+        .parse::<i64>()
+        .map_err(|_| "ERR value is not an integer or out of range".into())
+}
+
+fn parse_elements(parse: &mut Parse) -> crate::Result<Vec<Bytes>> {
+    let mut elements = vec![parse.next_bytes()?];
+    loop {
+        match parse.next_bytes() {
+            Ok(element) => elements.push(element),
+            Err(ParseError::EndOfStream) => return Ok(elements),
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+fn parse_count(parse: &mut Parse) -> crate::Result<Option<u64>> {
+    match parse.next_string() {
+        Ok(count) => match count.parse::<u64>() {
+            Ok(count) => Ok(Some(count)),
+            Err(_) => Err("ERR value is out of range, must be positive".into()),
+        },
+        Err(ParseError::EndOfStream) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn push(
+    db: &Db,
+    dst: &mut Connection,
+    key: &str,
+    elements: Vec<Bytes>,
+    end: ListEnd,
+) -> crate::Result<()> {
+    let response = match db.list_push(key, elements, end) {
+        Ok(len) => Frame::Integer(len as i64),
+        Err(err) => super::error_reply(&err),
+    };
+    debug!(%response);
+    dst.write_frame(&response).await?;
+    Ok(())
+}
+
+async fn pop(
+    db: &Db,
+    dst: &mut Connection,
+    key: &str,
+    count: Option<u64>,
+    end: ListEnd,
+) -> crate::Result<()> {
+    let limit = count.unwrap_or(1).min(usize::MAX as u64) as usize;
+    let response = match (db.list_pop(key, limit, end), count) {
+        (Ok(Some(elements)), Some(_)) => {
+            Frame::Array(elements.into_iter().map(Frame::Bulk).collect())
+        }
+        (Ok(Some(elements)), None) => match elements.into_iter().next() {
+            Some(element) => Frame::Bulk(element),
+            None => Frame::Null,
+        },
+        (Ok(None), _) => Frame::Null,
+        (Err(err), _) => super::error_reply(&err),
+    };
+    debug!(%response);
+    dst.write_frame(&response).await?;
+    Ok(())
+}
+
+/// Parse the keys and the timeout in seconds of a blocking pop
+fn parse_blocking(parse: &mut Parse) -> crate::Result<(Vec<String>, Duration)> {
+    let mut keys = vec![parse.next_string()?];
+    loop {
+        match parse.next_string() {
+            Ok(arg) => keys.push(arg),
+            Err(ParseError::EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    // the arity leaves a key and the timeout at least
+    let timeout = keys.pop().unwrap();
+    match timeout.parse::<f64>() {
+        Ok(secs) if secs < 0.0 => Err("ERR timeout is negative".into()),
+        Ok(secs) => Duration::try_from_secs_f64(secs)
+            .map(|timeout| (keys, timeout))
+            .map_err(|_| "ERR timeout is not a float or out of range".into()),
+        Err(_) => Err("ERR timeout is not a float or out of range".into()),
+    }
+}
+
+async fn blocking_pop(
+    db: &Db,
+    dst: &mut Connection,
+    shutdown: &mut Shutdown,
+    keys: &[String],
+    timeout: Duration,
+    end: ListEnd,
+) -> crate::Result<()> {
+    let timeout = Some(timeout).filter(|timeout| !timeout.is_zero());
+    let popped = tokio::select! {
+        popped = db.blocking_list_pop(keys, end, timeout) => popped,
+        // the client is gone or the server is shutting down, nobody waits for the reply
+        _ = dst.closed() => return Ok(()),
+        _ = shutdown.recv() => return Ok(()),
+    };
+    let response = match popped {
+        Ok(Some((key, element))) => {
+            Frame::Array(vec![Frame::Bulk(Bytes::from(key)), Frame::Bulk(element)])
+        }
+        Ok(None) => Frame::Null,
+        Err(err) => super::error_reply(&err),
+    };
+    debug!(%response);
+    dst.write_frame(&response).await?;
+    Ok(())
+}
+
+fn blocking_frame(name: &'static str, keys: Vec<String>, timeout: Duration) -> Frame {
+    let mut frame = vec![Frame::Bulk(Bytes::from(name.as_bytes()))];
+    frame.extend(keys.into_iter().map(|key| Frame::Bulk(Bytes::from(key))));
+    frame.push(Frame::Bulk(Bytes::from(timeout.as_secs_f64().to_string())));
+    Frame::Array(frame)
+}
+
+fn command_frame(name: &'static str, key: String, args: impl IntoIterator<Item = Bytes>) -> Frame {
+    let mut frame = Vec::new();
+    frame.push(Frame::Bulk(Bytes::from(name.as_bytes())));
+    frame.push(Frame::Bulk(Bytes::from(key.into_bytes())));
+    for arg in args {
+        frame.push(Frame::Bulk(arg));
+    }
+    Frame::Array(frame)
+}
+
+impl LPush {
+    pub fn new(key: impl ToString, elements: Vec<Bytes>) -> LPush {
+        LPush {
+            key: key.to_string(),
+            elements,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for LPush {
+    const NAME: &'static str = "lpush";
+    const ARITY: i32 = -3;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<LPush> {
+        let key = parse.next_string()?;
+        Ok(LPush::new(key, parse_elements(parse)?))
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        push(db, dst, &self.key, self.elements, ListEnd::Head).await
+    }
+
+    fn into_frame(self) -> Frame {
+        command_frame("lpush", self.key, self.elements)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl RPush {
+    pub fn new(key: impl ToString, elements: Vec<Bytes>) -> RPush {
+        RPush {
+            key: key.to_string(),
+            elements,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for RPush {
+    const NAME: &'static str = "rpush";
+    const ARITY: i32 = -3;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<RPush> {
+        let key = parse.next_string()?;
+        Ok(RPush::new(key, parse_elements(parse)?))
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        push(db, dst, &self.key, self.elements, ListEnd::Tail).await
+    }
+
+    fn into_frame(self) -> Frame {
+        command_frame("rpush", self.key, self.elements)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl LPop {
+    pub fn new(key: impl ToString) -> LPop {
+        LPop {
+            key: key.to_string(),
+            count: None,
+        }
+    }
+
+    /// Pop up to `count` elements, replied as an array
+    pub fn count(mut self, count: u64) -> LPop {
+        self.count = Some(count);
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for LPop {
+    const NAME: &'static str = "lpop";
+    const ARITY: i32 = -2;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+
+    fn parse(parse: &mut Parse) -> crate::Result<LPop> {
+        let mut cmd = LPop::new(parse.next_string()?);
+        cmd.count = parse_count(parse)?;
+        Ok(cmd)
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        pop(db, dst, &self.key, self.count, ListEnd::Head).await
+    }
+
+    fn into_frame(self) -> Frame {
+        let count = self.count.map(|count| Bytes::from(count.to_string()));
+        command_frame("lpop", self.key, count)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl RPop {
+    pub fn new(key: impl ToString) -> RPop {
+        RPop {
+            key: key.to_string(),
+            count: None,
+        }
+    }
+
+    /// Pop up to `count` elements, replied as an array
+    pub fn count(mut self, count: u64) -> RPop {
+        self.count = Some(count);
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for RPop {
+    const NAME: &'static str = "rpop";
+    const ARITY: i32 = -2;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+
+    fn parse(parse: &mut Parse) -> crate::Result<RPop> {
+        let mut cmd = RPop::new(parse.next_string()?);
+        cmd.count = parse_count(parse)?;
+        Ok(cmd)
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        pop(db, dst, &self.key, self.count, ListEnd::Tail).await
+    }
+
+    fn into_frame(self) -> Frame {
+        let count = self.count.map(|count| Bytes::from(count.to_string()));
+        command_frame("rpop", self.key, count)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl LLen {
+    pub fn new(key: impl ToString) -> LLen {
+        LLen {
+            key: key.to_string(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for LLen {
+    const NAME: &'static str = "llen";
+    const ARITY: i32 = 2;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<LLen> {
+        Ok(LLen::new(parse.next_string()?))
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.list_len(&self.key) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "llen", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        command_frame("llen", self.key, None)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl LRange {
+    pub fn new(key: impl ToString, start: i64, stop: i64) -> LRange {
+        LRange {
+            key: key.to_string(),
+            start,
+            stop,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for LRange {
+    const NAME: &'static str = "lrange";
+    const ARITY: i32 = 4;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<LRange> {
+        let key = parse.next_string()?;
+        let start = parse_index(parse)?;
+        let stop = parse_index(parse)?;
+        Ok(LRange { key, start, stop })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.list_range(&self.key, self.start, self.stop) {
+            Ok(elements) => Frame::Array(elements.into_iter().map(Frame::Bulk).collect()),
+            Err(err) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "lrange", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let range = [self.start, self.stop].map(|index| Bytes::from(index.to_string()));
+        command_frame("lrange", self.key, range)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl LPos {
+    pub fn new(key: impl ToString, element: Bytes) -> LPos {
+        LPos {
+            key: key.to_string(),
+            element,
+            rank: 1,
+            count: None,
+            max_len: 0,
+        }
+    }
+
+    /// Skip the matches before the `rank`-th one, searching from the tail if negative. Must not
+    /// be `0`.
+    pub fn rank(mut self, rank: i64) -> LPos {
+        self.rank = rank;
+        self
+    }
+
+    /// Reply with the indexes of up to `count` matches, `0` meaning all of them
+    pub fn count(mut self, count: u64) -> LPos {
+        self.count = Some(count);
+        self
+    }
+
+    /// Compare at most `max_len` elements, `0` meaning no limit
+    pub fn max_len(mut self, max_len: u64) -> LPos {
+        self.max_len = max_len;
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for LPos {
+    const NAME: &'static str = "lpos";
+    const ARITY: i32 = -3;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<LPos> {
+        let mut cmd = LPos::new(parse.next_string()?, parse.next_bytes()?);
+
+        loop {
+            let option = match parse.next_string() {
+                Ok(option) => option.to_uppercase(),
+                Err(ParseError::EndOfStream) => return Ok(cmd),
+                Err(err) => return Err(err.into()),
+            };
+            let value = parse_index(parse)?;
+            match &option[..] {
+                "RANK" if value == 0 || value == i64::MIN => {
+                    return Err("ERR RANK can't be zero: use 1 to start from the first match, 2 from the second ... or use negative to start from the end of the list".into());
+                }
+                "RANK" => cmd.rank = value,
+                "COUNT" if value < 0 => return Err("ERR COUNT can't be negative".into()),
+                "COUNT" => cmd.count = Some(value as u64),
+                "MAXLEN" if value < 0 => return Err("ERR MAXLEN can't be negative".into()),
+                "MAXLEN" => cmd.max_len = value as u64,
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let count = self.count.unwrap_or(1).min(usize::MAX as u64) as usize;
+        let max_len = self.max_len.min(usize::MAX as u64) as usize;
+        let positions = db.list_positions(&self.key, &self.element, self.rank, count, max_len);
+        let response = match (positions, self.count) {
+            (Ok(positions), Some(_)) => Frame::Array(
+                positions
+                    .into_iter()
+                    .map(|index| Frame::Integer(index as i64))
+                    .collect(),
+            ),
+            (Ok(positions), None) => match positions.first() {
+                Some(&index) => Frame::Integer(index as i64),
+                None => Frame::Null,
+            },
+            (Err(err), _) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "lpos", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut args = vec![self.element];
+        if self.rank != 1 {
+            args.push(Bytes::from_static(b"rank"));
+            args.push(Bytes::from(self.rank.to_string()));
+        }
+        if let Some(count) = self.count {
+            args.push(Bytes::from_static(b"count"));
+            args.push(Bytes::from(count.to_string()));
+        }
+        if self.max_len != 0 {
+            args.push(Bytes::from_static(b"maxlen"));
+            args.push(Bytes::from(self.max_len.to_string()));
+        }
+        command_frame("lpos", self.key, args)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl LInsert {
+    /// Insert `element` before the first element equal to `pivot`
+    pub fn before(key: impl ToString, pivot: Bytes, element: Bytes) -> LInsert {
+        LInsert {
+            key: key.to_string(),
+            side: Side::Before,
+            pivot,
+            element,
+        }
+    }
+
+    /// Insert `element` after the first element equal to `pivot`
+    pub fn after(key: impl ToString, pivot: Bytes, element: Bytes) -> LInsert {
+        LInsert {
+            side: Side::After,
+            ..LInsert::before(key, pivot, element)
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for LInsert {
+    const NAME: &'static str = "linsert";
+    const ARITY: i32 = 5;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<LInsert> {
+        let key = parse.next_string()?;
+        let side = match &parse.next_string()?.to_uppercase()[..] {
+            "BEFORE" => Side::Before,
+            "AFTER" => Side::After,
+            _ => return Err("ERR syntax error".into()),
+        };
+        let pivot = parse.next_bytes()?;
+        let element = parse.next_bytes()?;
+        Ok(LInsert {
+            key,
+            side,
+            pivot,
+            element,
+        })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.list_insert(&self.key, self.side, &self.pivot, self.element) {
+            Ok(Some(len)) => Frame::Integer(len as i64),
+            Ok(None) => Frame::Integer(-1),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let side = match self.side {
+            Side::Before => Bytes::from_static(b"before"),
+            Side::After => Bytes::from_static(b"after"),
+        };
+        command_frame("linsert", self.key, [side, self.pivot, self.element])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl LRem {
+    pub fn new(key: impl ToString, count: i64, element: Bytes) -> LRem {
+        LRem {
+            key: key.to_string(),
+            count,
+            element,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for LRem {
+    const NAME: &'static str = "lrem";
+    const ARITY: i32 = 4;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+
+    fn parse(parse: &mut Parse) -> crate::Result<LRem> {
+        let key = parse.next_string()?;
+        let count = parse_index(parse)?;
+        let element = parse.next_bytes()?;
+        Ok(LRem {
+            key,
+            count,
+            element,
+        })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.list_remove(&self.key, self.count, &self.element) {
+            Ok(removed) => Frame::Integer(removed as i64),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let count = Bytes::from(self.count.to_string());
+        command_frame("lrem", self.key, [count, self.element])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl LSet {
+    pub fn new(key: impl ToString, index: i64, element: Bytes) -> LSet {
+        LSet {
+            key: key.to_string(),
+            index,
+            element,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for LSet {
+    const NAME: &'static str = "lset";
+    const ARITY: i32 = 4;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<LSet> {
+        let key = parse.next_string()?;
+        let index = parse_index(parse)?;
+        let element = parse.next_bytes()?;
+        Ok(LSet {
+            key,
+            index,
+            element,
+        })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.list_set(&self.key, self.index, self.element) {
+            Ok(()) => Frame::ok(),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let index = Bytes::from(self.index.to_string());
+        command_frame("lset", self.key, [index, self.element])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl BLPop {
+    pub fn new(keys: Vec<String>, timeout: Duration) -> BLPop {
+        BLPop { keys, timeout }
+    }
+
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+}
+
+impl CommandSpec for BLPop {
+    const NAME: &'static str = "blpop";
+    const ARITY: i32 = -3;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+
+    fn parse(parse: &mut Parse) -> crate::Result<BLPop> {
+        let (keys, timeout) = parse_blocking(parse)?;
+        Ok(BLPop { keys, timeout })
+    }
+
+    #[instrument(skip(self, db, dst, shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        blocking_pop(db, dst, shutdown, &self.keys, self.timeout, ListEnd::Head).await
+    }
+
+    fn into_frame(self) -> Frame {
+        blocking_frame("blpop", self.keys, self.timeout)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.keys.iter().map(String::as_str).collect()
+    }
+}
+
+impl BRPop {
+    pub fn new(keys: Vec<String>, timeout: Duration) -> BRPop {
+        BRPop { keys, timeout }
+    }
+
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+}
+
+impl CommandSpec for BRPop {
+    const NAME: &'static str = "brpop";
+    const ARITY: i32 = -3;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+
+    fn parse(parse: &mut Parse) -> crate::Result<BRPop> {
+        let (keys, timeout) = parse_blocking(parse)?;
+        Ok(BRPop { keys, timeout })
+    }
+
+    #[instrument(skip(self, db, dst, shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        blocking_pop(db, dst, shutdown, &self.keys, self.timeout, ListEnd::Tail).await
+    }
+
+    fn into_frame(self) -> Frame {
+        blocking_frame("brpop", self.keys, self.timeout)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.keys.iter().map(String::as_str).collect()
+    }
+}
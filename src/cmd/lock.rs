@@ -0,0 +1,174 @@
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use std::time::Duration;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Take an advisory lock on a key for a limited time.
+///
+/// `LOCK key token milliseconds [NX]`
+///
+/// Replies `1` if the lock was taken, `0` if another token holds it. The holder extends the lock
+/// by taking it again with the same token, unless `NX` is given in which case the lock is only
+/// taken if it is free.
+///
+/// A lock which expires before being released is forcibly released: its token is published on the
+/// `__lock__:expired:<key>` channel, so the holder or a supervisor can notice it lost the lock.
+#[derive(Debug)]
+pub struct Lock {
+    key: String,
+    token: Bytes,
+    ttl: Duration,
+    nx: bool,
+}
+
+/// Release a lock taken with `LOCK`.
+///
+/// `UNLOCK key token`
+///
+/// Replies `1` if the lock was released, `0` if `token` doesn't hold it, for instance because the
+/// lock expired and was taken by someone else in the meantime.
+#[derive(Debug)]
+pub struct Unlock {
+    key: String,
+    token: Bytes,
+}
+
+impl Lock {
+    pub fn new(key: impl ToString, token: Bytes, ttl: Duration) -> Lock {
+        Lock {
+            key: key.to_string(),
+            token,
+            ttl,
+            nx: false,
+        }
+    }
+
+    /// Only take the lock if it is free, the holder can't extend it
+    pub fn nx(mut self) -> Lock {
+        self.nx = true;
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for Lock {
+    const NAME: &'static str = "lock";
+    const ARITY: i32 = -4;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<Lock> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        let token = parse.next_bytes()?;
+        let ttl = match parse.next_int()? {
+            0 => return Err("ERR invalid expire time in 'lock' command".into()),
+            millis => super::check_ttl(Duration::from_millis(millis), "lock")?,
+        };
+
+        let mut lock = Lock::new(key, token, ttl);
+
+        match parse.next_string() {
+            Ok(s) if s.to_uppercase() == "NX" => lock.nx = true,
+            Ok(_) => return Err("`LOCK` only supports the `NX` option".into()),
+            Err(EndOfStream) => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        Ok(lock)
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.lock(self.key, self.token, self.ttl, self.nx) {
+            Ok(locked) => Frame::Integer(locked as i64),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = vec![
+            Frame::Bulk(Bytes::from("lock".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+            Frame::Bulk(self.token),
+            Frame::Integer(self.ttl.as_millis() as i64),
+        ];
+        if self.nx {
+            frame.push(Frame::Bulk(Bytes::from("nx".as_bytes())));
+        }
+        Frame::Array(frame)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl Unlock {
+    pub fn new(key: impl ToString, token: Bytes) -> Unlock {
+        Unlock {
+            key: key.to_string(),
+            token,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for Unlock {
+    const NAME: &'static str = "unlock";
+    const ARITY: i32 = 3;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Unlock> {
+        let key = parse.next_string()?;
+        let token = parse.next_bytes()?;
+        Ok(Unlock { key, token })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.unlock(&self.key, &self.token) {
+            Ok(released) => Frame::Integer(released as i64),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("unlock".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+            Frame::Bulk(self.token),
+        ])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+const ART: &str = r"
+ ____  ____  ____  _  _  ____  ____
+(  _ \( ___)(  _ \( )( )/ ___)(_  _)
+ )   / )__)  )(_) ))()( \___ \  )(
+(_)\_)(____)(____/ \__/ (____/ (__)
+";
+
+/// `LOLWUT [VERSION version] [...]` replies a piece of art followed by the version of the server.
+///
+/// Tooling sends it as a liveness check which also tells the server apart, the arguments are
+/// accepted and ignored.
+#[derive(Debug, Default)]
+pub struct Lolwut;
+
+impl Lolwut {
+    pub fn new() -> Lolwut {
+        Lolwut
+    }
+}
+
+impl CommandSpec for Lolwut {
+    const NAME: &'static str = "lolwut";
+    const ARITY: i32 = -1;
+    const CATEGORY: Option<Category> = None;
+
+    fn parse(parse: &mut Parse) -> crate::Result<Lolwut> {
+        loop {
+            match parse.next_bytes() {
+                Ok(_) => {}
+                Err(ParseError::EndOfStream) => return Ok(Lolwut),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    #[instrument(skip(self, _db, dst, _shutdown))]
+    async fn apply(
+        self,
+        _db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let text = format!("{}\nredust ver. {}\n", ART, env!("CARGO_PKG_VERSION"));
+        let response = Frame::Bulk(Bytes::from(text));
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![Frame::Bulk(Bytes::from("lolwut".as_bytes()))])
+    }
+}
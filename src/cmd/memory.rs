@@ -0,0 +1,117 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Memory accounting of the key space.
+///
+/// * `MEMORY USAGE key` returns the estimated number of bytes held by `key` and its value, or nil
+///   if the key doesn't exist.
+/// * `MEMORY STATS` returns a summary of the key space as a flat array of field names and values.
+///
+/// Sizes are estimates: the key, the value and the bookkeeping of the entry are counted, the
+/// allocator overhead isn't.
+#[derive(Debug)]
+pub struct Memory {
+    subcommand: Subcommand,
+}
+
+#[derive(Debug)]
+enum Subcommand {
+    Usage(String),
+    Stats,
+}
+
+impl Memory {
+    /// Create a `MEMORY USAGE key` command
+    pub fn usage(key: impl ToString) -> Memory {
+        Memory {
+            subcommand: Subcommand::Usage(key.to_string()),
+        }
+    }
+
+    /// Create a `MEMORY STATS` command
+    pub fn stats() -> Memory {
+        Memory {
+            subcommand: Subcommand::Stats,
+        }
+    }
+}
+
+impl CommandSpec for Memory {
+    const NAME: &'static str = "memory";
+    const ARITY: i32 = -2;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Memory> {
+        let subcommand = match &parse.next_string()?.to_lowercase()[..] {
+            "usage" => Subcommand::Usage(parse.next_string()?),
+            "stats" => Subcommand::Stats,
+            other => return Err(format!("ERR unknown subcommand '{}' for 'memory'", other).into()),
+        };
+        Ok(Memory { subcommand })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match self.subcommand {
+            Subcommand::Usage(key) => match db.memory_usage(&key) {
+                Some(bytes) => Frame::Integer(bytes as i64),
+                None => Frame::Null,
+            },
+            Subcommand::Stats => {
+                let stats = db.memory_stats();
+                let bytes_per_key = match stats.keys {
+                    0 => 0,
+                    keys => stats.dataset_bytes / keys,
+                };
+
+                let mut frame = Vec::new();
+                for (field, value) in [
+                    ("keys.count", stats.keys),
+                    ("keys.bytes-per-key", bytes_per_key),
+                    ("expires.count", stats.expires),
+                    ("dataset.bytes", stats.dataset_bytes),
+                    ("overhead.total", stats.overhead_bytes()),
+                    ("pubsub.channels", stats.pubsub_channels),
+                ] {
+                    frame.push(Frame::Bulk(Bytes::from_static(field.as_bytes())));
+                    frame.push(Frame::Integer(value as i64));
+                }
+                Frame::Array(frame)
+            }
+        };
+
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = Vec::new();
+        frame.push(Frame::Bulk(Bytes::from("memory".as_bytes())));
+        match self.subcommand {
+            Subcommand::Usage(key) => {
+                frame.push(Frame::Bulk(Bytes::from("usage".as_bytes())));
+                frame.push(Frame::Bulk(Bytes::from(key.into_bytes())));
+            }
+            Subcommand::Stats => frame.push(Frame::Bulk(Bytes::from("stats".as_bytes()))),
+        }
+        Frame::Array(frame)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        match &self.subcommand {
+            Subcommand::Usage(key) => vec![key],
+            Subcommand::Stats => vec![],
+        }
+    }
+}
@@ -1,28 +1,333 @@
 mod get;
 pub use get::Get;
 
+mod get_entry;
+pub use get_entry::GetEntry;
+
 mod set;
 pub use set::Set;
 
+mod get_set;
+pub use get_set::GetSet;
+
+mod del;
+pub use del::Del;
+
+mod exists;
+pub use exists::Exists;
+
+mod expire;
+pub use expire::Expire;
+
+mod incr;
+pub use incr::Incr;
+
+mod keys;
+pub use keys::Keys;
+
+mod dump;
+pub use dump::{Dump, Restore};
+
+mod save;
+pub use save::Save;
+
+mod cas;
+pub use cas::Cas;
+
+mod bit;
+pub use bit::{BitCount, GetBit, SetBit};
+
 mod publish;
 pub use publish::Publish;
 
+mod pubsub;
+pub use pubsub::Pubsub;
+
+mod seq;
+pub use seq::Seq;
+
 mod subscribe;
 pub use subscribe::Subscribe;
 
+mod info;
+pub use info::Info;
+
+mod reserve;
+pub use reserve::{Confirm, Reserve};
+
+mod lock;
+pub use lock::{Lock, Unlock};
+
+mod list;
+pub use list::{BLPop, BRPop, LInsert, LLen, LPop, LPos, LPush, LRange, LRem, LSet, RPop, RPush};
+
+mod hash;
+pub use hash::{HDel, HGet, HGetAll, HIncrBy, HIncrByFloat, HLen, HRandField, HScan, HSet};
+
+mod sorted_set;
+pub use sorted_set::{ZAdd, ZCard, ZRange, ZRem, ZScore};
+
+mod geo;
+pub use geo::{GeoAdd, GeoDist, GeoSearch};
+
+mod sync_from;
+pub use sync_from::SyncFrom;
+
+mod config;
+pub use config::Config;
+
+mod debug;
+pub use debug::Debug;
+
+mod sentinel;
+pub use sentinel::Sentinel;
+
+mod blog;
+pub use blog::{BlogAppend, BlogRead};
+
+mod ttl_stats;
+pub use ttl_stats::TtlStats;
+
+mod hello;
+pub use hello::Hello;
+
+mod latency;
+pub use latency::Latency;
+
+mod slowlog;
+pub use slowlog::SlowLog;
+
+mod memory;
+pub use memory::Memory;
+
+mod reset;
+pub use reset::Reset;
+
+mod quit;
+pub use quit::Quit;
+
+mod lolwut;
+pub use lolwut::Lolwut;
+
+mod ping;
+pub use ping::Ping;
+
+mod auth;
+pub use auth::Auth;
+
+mod acl;
+pub use acl::Acl;
+
 mod unknown;
 pub use unknown::Unknown;
 
+pub(crate) mod registry;
+pub(crate) use registry::CommandSpec;
+
 pub use self::subscribe::Unsubscribe;
 
-#[derive(Debug)]
-pub enum Command {
-    Get(Get),
-    Set(Set),
-    Publish(Publish),
-    Subscribe(Subscribe),
-    Unsubscribe(Unsubscribe),
-    Unknown(Unknown),
+use crate::acl::Category;
+
+/// Audit the read of `key` by `command` for the client of `dst`, see `Db::audit_read`. Reads of
+/// missing keys are audited too, they may be probing for secrets.
+pub(crate) fn audit_read(
+    db: &crate::Db,
+    dst: &crate::Connection,
+    command: &'static str,
+    key: &str,
+) {
+    if let Ok(addr) = dst.peer_addr() {
+        db.audit_read(command, key, addr, dst.user());
+    }
+}
+
+/// Build the error reply sent to a client for `err`.
+///
+/// Messages which already carry an error code (`ERR`, `WRONGTYPE`...) are sent as is, anything else
+/// is reported as a generic `ERR`.
+pub(crate) fn error_reply(err: &impl std::fmt::Display) -> crate::Frame {
+    let msg = err.to_string();
+    let has_code = msg
+        .split(' ')
+        .next()
+        .map(|code| code.len() > 1 && code.bytes().all(|b| b.is_ascii_uppercase()))
+        .unwrap_or(false);
+
+    if has_code {
+        crate::Frame::Error(msg)
+    } else {
+        crate::Frame::error(format!("ERR {}", msg))
+    }
+}
+
+/// Check the time to live given to `command`, rejecting the ones past `MAX_TTL` with the reply of
+/// Redis
+pub(crate) fn check_ttl(
+    ttl: std::time::Duration,
+    command: &str,
+) -> crate::Result<std::time::Duration> {
+    if ttl > crate::db::MAX_TTL {
+        return Err(format!("ERR invalid expire time in '{}' command", command).into());
+    }
+    Ok(ttl)
+}
+
+/// Declare the `Command` enum over the types implementing `CommandSpec`, along with the dispatch
+/// to them and the table of `registry`
+macro_rules! commands {
+    ($($name:ident,)*) => {
+        #[derive(Debug)]
+        pub enum Command {
+            $($name($name),)*
+            Unknown(Unknown),
+        }
+
+        $(
+            impl From<$name> for Command {
+                fn from(cmd: $name) -> Command {
+                    Command::$name(cmd)
+                }
+            }
+        )*
+
+        /// Every command known to the server, see `registry`
+        pub(crate) static COMMANDS: &[registry::Spec] = &[$(registry::Spec::of::<$name>(),)*];
+
+        /// Position of each command in `COMMANDS`
+        enum Id {
+            $($name,)*
+        }
+
+        impl Command {
+            async fn dispatch(
+                self,
+                db: &crate::Db,
+                dst: &mut crate::Connection,
+                shutdown: &mut crate::Shutdown,
+            ) -> crate::Result<()> {
+                match self {
+                    $(Command::$name(cmd) => cmd.apply(db, dst, shutdown).await,)*
+                    Command::Unknown(cmd) => cmd.apply(db, dst).await,
+                }
+            }
+
+            /// Category of the command, `None` for the connection commands allowed to every user
+            pub(crate) fn category(&self) -> Option<Category> {
+                match self {
+                    $(Command::$name(cmd) => CommandSpec::category(cmd),)*
+                    Command::Unknown(_) => None,
+                }
+            }
+
+            /// Whether the command may add data, see `CommandSpec::DENY_OOM`
+            pub(crate) fn deny_oom(&self) -> bool {
+                match self {
+                    $(Command::$name(_) => $name::DENY_OOM,)*
+                    Command::Unknown(_) => false,
+                }
+            }
+
+            /// Every key the command works on
+            pub(crate) fn keys(&self) -> Vec<&str> {
+                match self {
+                    $(Command::$name(cmd) => CommandSpec::keys(cmd),)*
+                    Command::Unknown(_) => vec![],
+                }
+            }
+
+            /// Position of the command in `COMMANDS`, `None` for the unknown ones. It indexes the
+            /// statistics kept by command.
+            pub(crate) fn id(&self) -> Option<usize> {
+                match self {
+                    $(Command::$name(_) => Some(Id::$name as usize),)*
+                    Command::Unknown(_) => None,
+                }
+            }
+
+            /// Lowercased name of the command
+            pub fn get_name(&self) -> &str {
+                match self {
+                    $(Command::$name(_) => $name::NAME,)*
+                    Command::Unknown(cmd) => cmd.get_name(),
+                }
+            }
+        }
+    };
+}
+
+commands! {
+    Get,
+    GetEntry,
+    Set,
+    GetSet,
+    Del,
+    Exists,
+    Expire,
+    Incr,
+    Keys,
+    Dump,
+    Restore,
+    Save,
+    Cas,
+    SetBit,
+    GetBit,
+    BitCount,
+    Publish,
+    Subscribe,
+    Unsubscribe,
+    Pubsub,
+    Reserve,
+    Confirm,
+    Lock,
+    Unlock,
+    LPush,
+    RPush,
+    LPop,
+    RPop,
+    LLen,
+    LRange,
+    LPos,
+    LInsert,
+    LRem,
+    LSet,
+    BLPop,
+    BRPop,
+    HSet,
+    HGet,
+    HDel,
+    HLen,
+    HGetAll,
+    HIncrBy,
+    HIncrByFloat,
+    HRandField,
+    HScan,
+    ZAdd,
+    ZScore,
+    ZRem,
+    ZCard,
+    ZRange,
+    GeoAdd,
+    GeoDist,
+    GeoSearch,
+    Info,
+    Seq,
+    SyncFrom,
+    Config,
+    Debug,
+    Sentinel,
+    BlogAppend,
+    BlogRead,
+    TtlStats,
+    Hello,
+    Latency,
+    SlowLog,
+    Memory,
+    Reset,
+    Quit,
+    Lolwut,
+    Ping,
+    Auth,
+    Acl,
 }
 
 impl Command {
@@ -30,47 +335,114 @@ impl Command {
         // parse the frame
         let mut parse = crate::Parse::new(frame)?;
 
-        let command_name = parse.next_string()?.to_lowercase();
-
-        let command = match &command_name[..] {
-            "get" => Command::Get(Get::parse_frame(&mut parse)?),
-            "set" => Command::Set(Set::parse_frame(&mut parse)?),
-            "publish" => Command::Publish(Publish::parse_frames(&mut parse)?),
-            "subscribe" => Command::Subscribe(Subscribe::parse_frames(&mut parse)?),
-            "unsubscribe" => Command::Unsubscribe(Unsubscribe::parse_frames(&mut parse)?),
-            _ => {
-                return Ok(Command::Unknown(Unknown::new(command_name)));
+        let command_name = parse.next_string()?;
+        let spec = match registry::lookup(&command_name) {
+            Some(spec) => spec,
+            None => {
+                let name = command_name.trim().to_lowercase();
+                return Ok(Command::Unknown(Unknown::new(name, parse)));
             }
         };
+        // the name was taken already
+        if !spec.accepts(parse.remaining() + 1) {
+            return Err(registry::wrong_arity(spec.name));
+        }
 
-        parse.finish()?;
+        // commands taking a variable number of arguments may still lack some or get too many,
+        // any other parse error is replied as is
+        let command = match (spec.parse)(&mut parse) {
+            Err(crate::Error::EndOfStream) => return Err(registry::wrong_arity(spec.name)),
+            res => res?,
+        };
+        if parse.finish().is_err() {
+            return Err(registry::wrong_arity(spec.name));
+        }
         Ok(command)
     }
 
+    /// Whether the command runs for as long as its client wants: the streaming commands, and the
+    /// blocking ones which wait for the timeout they are given. They have no execution budget and
+    /// are left out of the slow log.
+    pub(crate) fn is_unbounded(&self) -> bool {
+        matches!(
+            self,
+            Command::Subscribe(_) | Command::SyncFrom(_) | Command::BLPop(_) | Command::BRPop(_)
+        )
+    }
+
+    /// Whether the command can only run on the connection of its client: the unbounded ones,
+    /// which stop when the client goes away, and the ones changing the state of the connection.
+    /// They are refused on tagged connections, whose requests run apart from it.
+    pub(crate) fn is_connection_bound(&self) -> bool {
+        self.is_unbounded() || self.allowed_before_auth()
+    }
+
+    /// Whether the command changes the state of the connection, the only commands a client can
+    /// send before it authenticates.
+    fn allowed_before_auth(&self) -> bool {
+        matches!(
+            self,
+            Command::Hello(_) | Command::Auth(_) | Command::Reset(_) | Command::Quit(_)
+        )
+    }
+
     pub(crate) async fn apply(
         self,
         db: &crate::Db,
         dst: &mut crate::Connection,
         shutdown: &mut crate::Shutdown,
     ) -> crate::Result<()> {
-        match self {
-            Command::Get(cmd) => cmd.apply(db, dst).await,
-            Command::Set(cmd) => cmd.apply(db, dst).await,
-            Command::Publish(cmd) => cmd.apply(db, dst).await,
-            Command::Subscribe(cmd) => cmd.apply(db, dst, shutdown).await,
-            Command::Unknown(cmd) => cmd.apply(dst).await,
-            Command::Unsubscribe(_) => Err("`Unsubscribe` is unsupported in this context".into()),
+        let handler = match &self {
+            Command::Unknown(cmd) => db.command_handler(cmd.get_name()),
+            _ => None,
+        };
+        // the commands registered by the application all write
+        let category = match handler {
+            Some(_) => Some(Category::Write),
+            None => self.category(),
+        };
+        let denied = match (dst.user(), category) {
+            (None, _) if !self.allowed_before_auth() => {
+                Some(crate::Error::NotAuthenticated.to_string())
+            }
+            (Some(user), Some(category)) => {
+                // the keys of the commands registered by the application are told by their handler
+                let declared = match (&self, &handler) {
+                    (Command::Unknown(cmd), Some(handler)) => handler.keys(&mut cmd.args().clone()),
+                    _ => None,
+                };
+                let keys = match handler {
+                    Some(_) => declared
+                        .as_ref()
+                        .map(|keys| keys.iter().map(String::as_str).collect()),
+                    None => Some(self.keys()),
+                };
+                db.check_permission(user, self.get_name(), category, keys.as_deref())
+                    .err()
+            }
+            _ => None,
+        };
+        if let Some(err) = denied {
+            dst.write_frame(&crate::Frame::Error(err)).await?;
+            return Ok(());
         }
+
+        // the commands registered by the application may add data as well
+        if handler.is_some() || self.deny_oom() {
+            if let Err(err) = db.check_maxmemory() {
+                dst.write_frame(&crate::Frame::Error(err)).await?;
+                return Ok(());
+            }
+        }
+
+        self.dispatch(db, dst, shutdown).await
     }
 
-    pub(crate) fn get_name(&self) -> &str {
-        match self {
-            Command::Get(_) => "get",
-            Command::Set(_) => "set",
-            Command::Publish(_) => "publish",
-            Command::Subscribe(_) => "subscribe",
-            Command::Unsubscribe(_) => "unsubcribe",
-            Command::Unknown(cmd) => cmd.get_name(),
+    /// Key the command works on, `None` for commands working on several keys or none
+    pub fn key(&self) -> Option<&str> {
+        match self.keys()[..] {
+            [key] => Some(key),
+            _ => None,
         }
     }
 }
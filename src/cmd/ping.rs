@@ -0,0 +1,62 @@
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// `PING [message]` replies `PONG`, or `message` as a bulk string when one is given.
+#[derive(Debug, Default)]
+pub struct Ping {
+    msg: Option<Bytes>,
+}
+
+impl Ping {
+    pub fn new(msg: Option<Bytes>) -> Ping {
+        Ping { msg }
+    }
+
+    pub fn msg(&self) -> Option<&Bytes> {
+        self.msg.as_ref()
+    }
+}
+
+impl CommandSpec for Ping {
+    const NAME: &'static str = "ping";
+    const ARITY: i32 = -1;
+    const CATEGORY: Option<Category> = None;
+
+    fn parse(parse: &mut Parse) -> crate::Result<Ping> {
+        match parse.next_bytes() {
+            Ok(msg) => Ok(Ping::new(Some(msg))),
+            Err(ParseError::EndOfStream) => Ok(Ping::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    #[instrument(skip(self, _db, dst, _shutdown))]
+    async fn apply(
+        self,
+        _db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match self.msg {
+            Some(msg) => Frame::Bulk(msg),
+            None => Frame::Simple("PONG".to_string()),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = Vec::new();
+        frame.push(Frame::Bulk(Bytes::from("ping".as_bytes())));
+        if let Some(msg) = self.msg {
+            frame.push(Frame::Bulk(msg));
+        }
+        Frame::Array(frame)
+    }
+}
@@ -1,7 +1,10 @@
-use crate::{Connection, Db, Frame, Parse};
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
 
 use bytes::Bytes;
 
+use super::CommandSpec;
+
 /// Posts a mesasge to a given channel.
 ///
 /// Send a message into a channel without any knowledge of individual consumers
@@ -22,26 +25,37 @@ impl Publish {
             message: mesasge,
         }
     }
+}
+
+impl CommandSpec for Publish {
+    const NAME: &'static str = "publish";
+    const ARITY: i32 = 3;
+    const CATEGORY: Option<Category> = Some(Category::Pubsub);
 
-    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Publish> {
+    fn parse(parse: &mut Parse) -> crate::Result<Publish> {
         let channel = parse.next_string()?;
         let mesasge = parse.next_bytes()?;
         Ok(Publish::new(channel, mesasge))
     }
 
-    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
         let num_subs = db.publish(&self.channel, self.message);
 
-        let resp = Frame::Integer(num_subs as u64);
+        let resp = Frame::Integer(num_subs as i64);
         dst.write_frame(&resp).await?;
         Ok(())
     }
 
-    pub(crate) fn into_frame(self) -> Frame {
-        let mut frame = Frame::array();
-        frame.push_bulk(Bytes::from("publish".as_bytes()));
-        frame.push_bulk(Bytes::from(self.channel.into_bytes()));
-        frame.push_bulk(self.message);
-        frame
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("publish".as_bytes())),
+            Frame::Bulk(Bytes::from(self.channel.into_bytes())),
+            Frame::Bulk(self.message),
+        ])
     }
 }
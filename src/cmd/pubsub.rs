@@ -0,0 +1,95 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use std::sync::atomic::Ordering;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Introspection of the pub/sub subsystem.
+///
+/// `PUBSUB STATS channel` replies with the delivery counters of a channel as a flat array of
+/// field/value pairs: current subscribers, messages published, delivered (counted once per
+/// receiving subscriber), dropped for lagging subscribers, lagging subscribers disconnected, plus
+/// the subscribe and unsubscribe totals to measure churn. Unknown channels reply `(nil)`.
+#[derive(Debug)]
+pub struct Pubsub {
+    subcommand: Subcommand,
+}
+
+#[derive(Debug)]
+enum Subcommand {
+    Stats(String),
+}
+
+impl Pubsub {
+    /// Create a `PUBSUB STATS channel` command
+    pub fn stats(channel: impl ToString) -> Pubsub {
+        Pubsub {
+            subcommand: Subcommand::Stats(channel.to_string()),
+        }
+    }
+}
+
+impl CommandSpec for Pubsub {
+    const NAME: &'static str = "pubsub";
+    const ARITY: i32 = -2;
+    const CATEGORY: Option<Category> = Some(Category::Pubsub);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Pubsub> {
+        let subcommand = match &parse.next_string()?.to_lowercase()[..] {
+            "stats" => Subcommand::Stats(parse.next_string()?),
+            other => return Err(format!("ERR unknown subcommand '{}' for 'pubsub'", other).into()),
+        };
+        Ok(Pubsub { subcommand })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match self.subcommand {
+            Subcommand::Stats(channel) => match db.channel_stats(&channel) {
+                Some((subscribers, stats)) => {
+                    let fields = [
+                        ("subscribers", subscribers as u64),
+                        ("published", stats.published.load(Ordering::Relaxed)),
+                        ("delivered", stats.delivered.load(Ordering::Relaxed)),
+                        ("dropped", stats.dropped.load(Ordering::Relaxed)),
+                        ("disconnected", stats.disconnected.load(Ordering::Relaxed)),
+                        ("subscribes", stats.subscribes.load(Ordering::Relaxed)),
+                        ("unsubscribes", stats.unsubscribes.load(Ordering::Relaxed)),
+                    ];
+
+                    let mut frame = Vec::new();
+                    for (name, value) in fields.iter() {
+                        frame.push(Frame::Bulk(Bytes::from_static(name.as_bytes())));
+                        frame.push(Frame::Integer(*value as i64));
+                    }
+                    Frame::Array(frame)
+                }
+                None => Frame::Null,
+            },
+        };
+
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = Vec::new();
+        frame.push(Frame::Bulk(Bytes::from("pubsub".as_bytes())));
+        match self.subcommand {
+            Subcommand::Stats(channel) => {
+                frame.push(Frame::Bulk(Bytes::from("stats".as_bytes())));
+                frame.push(Frame::Bulk(Bytes::from(channel.into_bytes())));
+            }
+        }
+        Frame::Array(frame)
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// `QUIT` replies `OK`, then the server closes the connection.
+#[derive(Debug, Default)]
+pub struct Quit;
+
+impl Quit {
+    pub fn new() -> Quit {
+        Quit
+    }
+}
+
+impl CommandSpec for Quit {
+    const NAME: &'static str = "quit";
+    const ARITY: i32 = -1;
+    const CATEGORY: Option<Category> = None;
+
+    // arguments are ignored, as Redis does
+    fn parse(parse: &mut Parse) -> crate::Result<Quit> {
+        while parse.next_bytes().is_ok() {}
+        Ok(Quit)
+    }
+
+    #[instrument(skip(self, _db, dst, _shutdown))]
+    async fn apply(
+        self,
+        _db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = Frame::ok();
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        dst.close();
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![Frame::Bulk(Bytes::from("quit".as_bytes()))])
+    }
+}
@@ -0,0 +1,121 @@
+//! Table of the commands known to the server: how each is parsed and how many arguments it takes.
+//!
+//! A command is a type implementing `CommandSpec`, in its own file. Listing the type in the
+//! `commands!` invocation of `cmd/mod.rs` adds it to the `Command` enum and registers it here.
+
+use crate::acl::Category;
+use crate::cmd::{Command, COMMANDS};
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+
+/// A command: how it is parsed from a request, applied and sent by the client
+pub(crate) trait CommandSpec: Sized + Into<Command> {
+    /// Lowercased name
+    const NAME: &'static str;
+    /// Number of arguments, the name included. A negative arity `-n` is a minimum of `n`, as in
+    /// the `COMMAND` reply of Redis.
+    const ARITY: i32;
+    /// `None` for the connection commands allowed to every user
+    const CATEGORY: Option<Category>;
+    /// Whether the command may add data, it is then refused once `maxmemory` is reached
+    const DENY_OOM: bool = false;
+
+    /// Parse the arguments following the name
+    fn parse(parse: &mut Parse) -> crate::Result<Self>;
+
+    /// Run the command and write its reply to `dst`
+    async fn apply(self, db: &Db, dst: &mut Connection, shutdown: &mut Shutdown) -> crate::Result<()>;
+
+    /// The request sending the command
+    fn into_frame(self) -> Frame;
+
+    /// Category of this invocation, for commands whose subcommands differ
+    fn category(&self) -> Option<Category> {
+        Self::CATEGORY
+    }
+
+    /// Every key the command works on
+    fn keys(&self) -> Vec<&str> {
+        vec![]
+    }
+}
+
+/// A command known to the server
+#[derive(Debug)]
+pub(crate) struct Spec {
+    /// Lowercased name
+    pub(crate) name: &'static str,
+    /// Number of arguments, the name included. A negative arity `-n` is a minimum of `n`, as in
+    /// the `COMMAND` reply of Redis.
+    pub(crate) arity: i32,
+    /// Parse the arguments following the name
+    pub(crate) parse: fn(&mut Parse) -> crate::Result<Command>,
+}
+
+impl Spec {
+    pub(crate) const fn of<T: CommandSpec>() -> Spec {
+        Spec {
+            name: T::NAME,
+            arity: T::ARITY,
+            parse: parse_as::<T>,
+        }
+    }
+
+    /// Whether `args` arguments, the name included, fit the arity of the command
+    pub(crate) fn accepts(&self, args: usize) -> bool {
+        if self.arity < 0 {
+            args >= self.arity.unsigned_abs() as usize
+        } else {
+            args == self.arity as usize
+        }
+    }
+}
+
+/// The command called `name`, ignoring the case and the surrounding whitespace
+pub(crate) fn lookup(name: &str) -> Option<&'static Spec> {
+    id(name).map(|id| &COMMANDS[id])
+}
+
+/// Position in `COMMANDS` of the command called `name`, see `Command::id`
+pub(crate) fn id(name: &str) -> Option<usize> {
+    let name = name.trim();
+    COMMANDS.iter().position(|spec| spec.name.eq_ignore_ascii_case(name))
+}
+
+/// Error replied to a command called with too few or too many arguments
+pub(crate) fn wrong_arity(name: &str) -> crate::Error {
+    format!("wrong number of arguments for '{}' command", name).into()
+}
+
+/// The command `name` is most likely a typo of, if any is close enough
+pub(crate) fn suggest(name: &str) -> Option<&'static str> {
+    let name = name.trim().to_ascii_lowercase();
+    // one typo per three letters, so short names don't match everything
+    let max_distance = (name.len() / 3).clamp(1, 3);
+    COMMANDS
+        .iter()
+        .map(|spec| (distance(&name, spec.name), spec.name))
+        .filter(|(distance, _)| (1..=max_distance).contains(distance))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// Levenshtein distance between `a` and `b`, counting bytes
+fn distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, x) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = previous[j] + (x != y) as usize;
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+fn parse_as<T: CommandSpec>(parse: &mut Parse) -> crate::Result<Command> {
+    Ok(T::parse(parse)?.into())
+}
@@ -0,0 +1,165 @@
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use std::time::Duration;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// List receiving the payload of reservations that expired without being confirmed, unless
+/// `DEADLETTER` is given.
+pub const DEFAULT_DEAD_LETTER: &str = "__reservations__:expired";
+
+/// Set a key that expires after `ttl` and hands its payload to a dead-letter list unless the
+/// reservation is confirmed first.
+///
+/// `RESERVE key seconds payload [DEADLETTER list]`
+///
+/// This covers the inventory-hold and saga timeout patterns: the holder calls `CONFIRM key` once
+/// the work completes, otherwise the payload is pushed at the tail of the dead-letter list when
+/// the hold times out. The list keeps the payloads until they are taken with `LPOP`, so none is
+/// lost while no consumer is connected.
+#[derive(Debug)]
+pub struct Reserve {
+    key: String,
+    ttl: Duration,
+    payload: Bytes,
+    dead_letter: String,
+}
+
+/// Confirm a pending reservation so the key expires without reaching the dead-letter list.
+///
+/// `CONFIRM key`
+#[derive(Debug)]
+pub struct Confirm {
+    key: String,
+}
+
+impl Reserve {
+    pub fn new(key: impl ToString, ttl: Duration, payload: Bytes) -> Reserve {
+        Reserve {
+            key: key.to_string(),
+            ttl,
+            payload,
+            dead_letter: DEFAULT_DEAD_LETTER.to_string(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn dead_letter(&self) -> &str {
+        &self.dead_letter
+    }
+}
+
+impl CommandSpec for Reserve {
+    const NAME: &'static str = "reserve";
+    const ARITY: i32 = -4;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<Reserve> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        let ttl = super::check_ttl(Duration::from_secs(parse.next_int()?), "reserve")?;
+        let payload = parse.next_bytes()?;
+
+        let mut reserve = Reserve::new(key, ttl, payload);
+
+        match parse.next_string() {
+            Ok(s) if s.to_uppercase() == "DEADLETTER" => {
+                reserve.dead_letter = parse.next_string()?;
+            }
+            Ok(_) => return Err("`RESERVE` only supports the `DEADLETTER` option".into()),
+            Err(EndOfStream) => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        Ok(reserve)
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.reserve(self.key, self.payload, self.ttl, self.dead_letter) {
+            Ok(()) => Frame::ok(),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("reserve".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+            Frame::Integer(self.ttl.as_secs() as i64),
+            Frame::Bulk(self.payload),
+            Frame::Bulk(Bytes::from("deadletter".as_bytes())),
+            Frame::Bulk(Bytes::from(self.dead_letter.into_bytes())),
+        ])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key(), self.dead_letter()]
+    }
+}
+
+impl Confirm {
+    pub fn new(key: impl ToString) -> Confirm {
+        Confirm {
+            key: key.to_string(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for Confirm {
+    const NAME: &'static str = "confirm";
+    const ARITY: i32 = 2;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Confirm> {
+        let key = parse.next_string()?;
+        Ok(Confirm { key })
+    }
+
+    /// Replies `1` if a pending reservation was confirmed, `0` otherwise
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let confirmed = db.confirm(&self.key);
+
+        let response = Frame::Integer(confirmed as i64);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("confirm".as_bytes())),
+            Frame::Bulk(Bytes::from(self.key.into_bytes())),
+        ])
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
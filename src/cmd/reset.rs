@@ -0,0 +1,50 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// `RESET` brings the connection back to its initial state and replies `RESET`.
+///
+/// A subscribed client leaves every channel and returns to the regular mode. The connection is
+/// authenticated as the default user again, or has to authenticate if the server requires a
+/// password. Tagged framing negotiated with `HELLO` is kept, the requests already in flight can't
+/// be taken back, and `RESET` itself is refused as a tagged request.
+#[derive(Debug, Default)]
+pub struct Reset;
+
+impl Reset {
+    pub fn new() -> Reset {
+        Reset
+    }
+}
+
+impl CommandSpec for Reset {
+    const NAME: &'static str = "reset";
+    const ARITY: i32 = 1;
+    const CATEGORY: Option<Category> = None;
+
+    fn parse(_parse: &mut Parse) -> crate::Result<Reset> {
+        Ok(Reset)
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        dst.set_user(db.default_login());
+        let response = Frame::Simple("RESET".to_string());
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![Frame::Bulk(Bytes::from("reset".as_bytes()))])
+    }
+}
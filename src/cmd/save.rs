@@ -0,0 +1,57 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, info, instrument};
+
+use super::CommandSpec;
+
+/// Write the key space to an RDB file Redis can load, at the path set by the `dir` and
+/// `dbfilename` parameters.
+///
+/// `SAVE`
+///
+/// The file is written before replying, blocking the shard being saved. Keys holding values with
+/// no Redis equivalent are left out, see `rdb`.
+#[derive(Debug, Default)]
+pub struct Save {}
+
+impl Save {
+    pub fn new() -> Save {
+        Save {}
+    }
+}
+
+impl CommandSpec for Save {
+    const NAME: &'static str = "save";
+    const ARITY: i32 = 1;
+    const CATEGORY: Option<Category> = Some(Category::Admin);
+
+    fn parse(_parse: &mut Parse) -> crate::Result<Save> {
+        Ok(Save::new())
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let path = db.rdb_path();
+        let response = match db.save_rdb(&path) {
+            Ok((keys, skipped)) => {
+                info!(keys, skipped, ?path, "key space saved");
+                Frame::ok()
+            }
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![Frame::Bulk(Bytes::from("save".as_bytes()))])
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use std::net::{IpAddr, SocketAddr};
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Queries answered by a server running as a sentinel, see the `sentinel` module.
+///
+/// * `SENTINEL get-master-addr-by-name name` returns the IP and port of the current primary of
+///   `name`, or nil if it isn't the one watched.
+/// * `SENTINEL master name` returns the primary of `name` as field/value pairs: `name`, `ip`,
+///   `port`, `flags` and `config-epoch`, the epoch of the election which made it the primary. Nil
+///   if it isn't the one watched.
+/// * `SENTINEL is-master-down-by-addr ip port epoch runid` returns whether this sentinel sees the
+///   primary at `ip:port` down, as `[down, leader, leader_epoch]`. Unless `runid` is `*`, the
+///   sentinel also votes for `runid` in `epoch`, if it didn't vote in that epoch yet, and replies
+///   the run id it voted for in its latest epoch.
+#[derive(Debug)]
+pub struct Sentinel {
+    subcommand: Subcommand,
+}
+
+#[derive(Debug)]
+enum Subcommand {
+    GetMasterAddrByName(String),
+    Master(String),
+    /// The address, and the epoch and run id of the candidate asking for a vote if any
+    IsMasterDownByAddr(SocketAddr, Option<(u64, String)>),
+}
+
+impl Sentinel {
+    /// Create a `SENTINEL get-master-addr-by-name name` command
+    pub fn get_master_addr_by_name(name: impl ToString) -> Sentinel {
+        Sentinel {
+            subcommand: Subcommand::GetMasterAddrByName(name.to_string()),
+        }
+    }
+
+    /// Create a `SENTINEL master name` command
+    pub fn master(name: impl ToString) -> Sentinel {
+        Sentinel {
+            subcommand: Subcommand::Master(name.to_string()),
+        }
+    }
+
+    /// Create a `SENTINEL is-master-down-by-addr ip port 0 *` command, which asks for no vote
+    pub fn is_master_down_by_addr(addr: SocketAddr) -> Sentinel {
+        Sentinel {
+            subcommand: Subcommand::IsMasterDownByAddr(addr, None),
+        }
+    }
+
+    /// Create a `SENTINEL is-master-down-by-addr ip port epoch runid` command, asking for a vote
+    /// for `run_id` in `epoch`
+    pub fn request_vote(addr: SocketAddr, epoch: u64, run_id: impl ToString) -> Sentinel {
+        Sentinel {
+            subcommand: Subcommand::IsMasterDownByAddr(addr, Some((epoch, run_id.to_string()))),
+        }
+    }
+}
+
+impl CommandSpec for Sentinel {
+    const NAME: &'static str = "sentinel";
+    const ARITY: i32 = -2;
+    const CATEGORY: Option<Category> = Some(Category::Admin);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Sentinel> {
+        let subcommand = match &parse.next_string()?.to_lowercase()[..] {
+            "get-master-addr-by-name" => Subcommand::GetMasterAddrByName(parse.next_string()?),
+            "master" => Subcommand::Master(parse.next_string()?),
+            "is-master-down-by-addr" => {
+                let ip: IpAddr = parse
+                    .next_string()?
+                    .parse()
+                    .map_err(|_| "ERR invalid IP address")?;
+                let port = parse.next_int()?;
+                if port > u16::MAX as u64 {
+                    return Err("ERR invalid port".into());
+                }
+                let epoch = match parse.next_int() {
+                    Ok(epoch) => Some(epoch),
+                    Err(ParseError::EndOfStream) => None,
+                    Err(err) => return Err(err.into()),
+                };
+                // a run id of `*` asks for no vote
+                let candidate = match epoch {
+                    Some(epoch) => match parse.next_string() {
+                        Ok(run_id) if run_id == "*" => None,
+                        Ok(run_id) => Some((epoch, run_id)),
+                        Err(ParseError::EndOfStream) => None,
+                        Err(err) => return Err(err.into()),
+                    },
+                    None => None,
+                };
+                Subcommand::IsMasterDownByAddr(SocketAddr::new(ip, port as u16), candidate)
+            }
+            other => return Err(format!("ERR unknown subcommand '{}' for 'sentinel'", other).into()),
+        };
+        Ok(Sentinel { subcommand })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.sentinel() {
+            None => Frame::error("ERR this server isn't running as a sentinel"),
+            Some(sentinel) => match self.subcommand {
+                Subcommand::GetMasterAddrByName(name) => match sentinel.primary_addr(&name) {
+                    Some(addr) => Frame::Array(vec![
+                        Frame::Bulk(Bytes::from(addr.ip().to_string())),
+                        Frame::Bulk(Bytes::from(addr.port().to_string())),
+                    ]),
+                    None => Frame::Null,
+                },
+                Subcommand::Master(name) => match sentinel.primary_config(&name) {
+                    Some((addr, config_epoch, down)) => {
+                        let flags = if down { "master,s_down" } else { "master" };
+                        let fields = [
+                            ("name", name),
+                            ("ip", addr.ip().to_string()),
+                            ("port", addr.port().to_string()),
+                            ("flags", flags.to_string()),
+                            ("config-epoch", config_epoch.to_string()),
+                        ];
+                        let mut frame = Vec::new();
+                        for (field, value) in fields {
+                            frame.push(Frame::Bulk(Bytes::from_static(field.as_bytes())));
+                            frame.push(Frame::Bulk(Bytes::from(value)));
+                        }
+                        Frame::Array(frame)
+                    }
+                    None => Frame::Null,
+                },
+                Subcommand::IsMasterDownByAddr(addr, candidate) => {
+                    let (leader, leader_epoch) = match candidate {
+                        Some((epoch, run_id)) => sentinel.vote(epoch, &run_id),
+                        None => ("*".to_string(), 0),
+                    };
+                    Frame::Array(vec![
+                        Frame::Integer(sentinel.is_down(addr) as i64),
+                        Frame::Bulk(Bytes::from(leader)),
+                        Frame::Integer(leader_epoch as i64),
+                    ])
+                }
+            },
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = vec![Frame::Bulk(Bytes::from("sentinel".as_bytes()))];
+        match self.subcommand {
+            Subcommand::GetMasterAddrByName(name) => {
+                frame.push(Frame::Bulk(Bytes::from("get-master-addr-by-name".as_bytes())));
+                frame.push(Frame::Bulk(Bytes::from(name.into_bytes())));
+            }
+            Subcommand::Master(name) => {
+                frame.push(Frame::Bulk(Bytes::from("master".as_bytes())));
+                frame.push(Frame::Bulk(Bytes::from(name.into_bytes())));
+            }
+            Subcommand::IsMasterDownByAddr(addr, candidate) => {
+                frame.push(Frame::Bulk(Bytes::from("is-master-down-by-addr".as_bytes())));
+                frame.push(Frame::Bulk(Bytes::from(addr.ip().to_string())));
+                frame.push(Frame::Integer(addr.port() as i64));
+                let (epoch, run_id) = candidate.unwrap_or_else(|| (0, "*".to_string()));
+                frame.push(Frame::Integer(epoch as i64));
+                frame.push(Frame::Bulk(Bytes::from(run_id.into_bytes())));
+            }
+        }
+        Frame::Array(frame)
+    }
+}
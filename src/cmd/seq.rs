@@ -0,0 +1,48 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Returns the sequence number of the latest write applied to the key space.
+///
+/// Every write, expirations included, is assigned a strictly increasing sequence number by the
+/// commit pipeline. Change data capture consumers use it as the position to resume from. `0` is
+/// returned when nothing was written yet.
+#[derive(Debug, Default)]
+pub struct Seq {}
+
+impl Seq {
+    pub fn new() -> Seq {
+        Seq {}
+    }
+}
+
+impl CommandSpec for Seq {
+    const NAME: &'static str = "seq";
+    const ARITY: i32 = 1;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(_parse: &mut Parse) -> crate::Result<Seq> {
+        Ok(Seq::new())
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = Frame::Integer(db.last_seq() as i64);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![Frame::Bulk(Bytes::from("seq".as_bytes()))])
+    }
+}
@@ -1,9 +1,12 @@
-use crate::{Connection, Db, Frame, Parse, ParseError};
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
 
 use bytes::Bytes;
 use std::time::Duration;
 use tracing::{debug, instrument};
 
+use super::CommandSpec;
+
 
 #[derive(Debug)]
 pub struct Set {
@@ -32,8 +35,15 @@ impl Set {
     pub fn expire(&self) -> Option<Duration> {
         self.expire
     }
+}
 
-    pub(crate) fn parse_frame(parse: &mut Parse) -> crate::Result<Set> {
+impl CommandSpec for Set {
+    const NAME: &'static str = "set";
+    const ARITY: i32 = -3;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<Set> {
         use ParseError::EndOfStream;
 
         // Read the set key
@@ -47,12 +57,12 @@ impl Set {
             Ok(s) if s.to_uppercase() == "EX" => {
                 // an expiration is specified in seconds. the next value is an integer
                 let secs = parse.next_int()?;
-                expire = Some(Duration::from_secs(secs));
+                expire = Some(super::check_ttl(Duration::from_secs(secs), "set")?);
             }
             Ok(s) if s.to_uppercase() == "PX" => {
                 // millis
                 let millis = parse.next_int()?;
-                expire = Some(Duration::from_millis(millis));
+                expire = Some(super::check_ttl(Duration::from_millis(millis), "set")?);
             }
 
             Ok(_) => return Err("currently `SET` only uspport the expiration option".into()),
@@ -64,28 +74,37 @@ impl Set {
         Ok(Set {key, value, expire})
     }
 
-
-    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
-        db.set(self.key, self.value, self.expire);
-
-        let response = Frame::Simple("OK".to_string());
-        debug!(?response);
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.set(self.key, self.value, self.expire) {
+            Ok(()) => Frame::ok(),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
         dst.write_frame(&response).await?;
         Ok(())
     }
 
-    pub(crate) fn into_frame(self) -> Frame {
-        let mut frame = Frame::array();
+    fn into_frame(self) -> Frame {
+        let mut frame = Vec::new();
 
-        frame.push_bulk(Bytes::from("set".as_bytes()));
-        frame.push_bulk(Bytes::from(self.key.into_bytes()));
-        frame.push_bulk(self.value);
+        frame.push(Frame::Bulk(Bytes::from("set".as_bytes())));
+        frame.push(Frame::Bulk(Bytes::from(self.key.into_bytes())));
+        frame.push(Frame::Bulk(self.value));
 
         if let Some(ms) = self.expire {
-            frame.push_bulk(Bytes::from("px".as_bytes()));
-            frame.push_int(ms.as_millis() as u64);
+            frame.push(Frame::Bulk(Bytes::from("px".as_bytes())));
+            frame.push(Frame::Integer(ms.as_millis() as i64));
         }
-        frame
+        Frame::Array(frame)
+
+    }
 
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
     }
 }
@@ -0,0 +1,135 @@
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Number of entries `SLOWLOG GET` returns by default, as in Redis
+const DEFAULT_COUNT: i64 = 10;
+
+/// The commands which reached `slowlog-log-slower-than`, see `slowlog`.
+///
+/// * `SLOWLOG GET [count]` returns the latest `count` entries, 10 by default or all of them if
+///   negative, latest first. An entry is an array of its id, the unix time it was logged at, the
+///   execution time in microseconds, the name and the keys of the command, the address of the
+///   client and its user.
+/// * `SLOWLOG LEN` returns the number of entries.
+/// * `SLOWLOG RESET` drops every entry.
+#[derive(Debug)]
+pub struct SlowLog {
+    subcommand: Subcommand,
+}
+
+#[derive(Debug)]
+enum Subcommand {
+    Get(i64),
+    Len,
+    Reset,
+}
+
+impl SlowLog {
+    /// Create a `SLOWLOG GET [count]` command
+    pub fn get(count: Option<i64>) -> SlowLog {
+        SlowLog {
+            subcommand: Subcommand::Get(count.unwrap_or(DEFAULT_COUNT)),
+        }
+    }
+
+    /// Create a `SLOWLOG LEN` command
+    pub fn len() -> SlowLog {
+        SlowLog {
+            subcommand: Subcommand::Len,
+        }
+    }
+
+    /// Create a `SLOWLOG RESET` command
+    pub fn reset() -> SlowLog {
+        SlowLog {
+            subcommand: Subcommand::Reset,
+        }
+    }
+}
+
+impl CommandSpec for SlowLog {
+    const NAME: &'static str = "slowlog";
+    const ARITY: i32 = -2;
+    const CATEGORY: Option<Category> = Some(Category::Admin);
+
+    fn parse(parse: &mut Parse) -> crate::Result<SlowLog> {
+        let subcommand = match &parse.next_string()?.to_lowercase()[..] {
+            "get" => match parse.next_string() {
+                Ok(count) => match count.parse::<i64>() {
+                    Ok(count) => Subcommand::Get(count),
+                    Err(_) => return Err("ERR value is not an integer or out of range".into()),
+                },
+                Err(ParseError::EndOfStream) => Subcommand::Get(DEFAULT_COUNT),
+                Err(err) => return Err(err.into()),
+            },
+            "len" => Subcommand::Len,
+            "reset" => Subcommand::Reset,
+            other => return Err(format!("ERR unknown subcommand '{}' for 'slowlog'", other).into()),
+        };
+        Ok(SlowLog { subcommand })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match self.subcommand {
+            Subcommand::Get(count) => {
+                let count = if count < 0 { usize::MAX } else { count as usize };
+                let entries = db
+                    .slowlog()
+                    .get(count)
+                    .into_iter()
+                    .map(|entry| {
+                        let args = entry.args.into_iter().map(Frame::from).collect();
+                        let client = entry.client.map(|addr| addr.to_string()).unwrap_or_default();
+                        Frame::Array(vec![
+                            Frame::Integer(entry.id as i64),
+                            Frame::Integer(entry.time as i64),
+                            Frame::Integer(entry.duration as i64),
+                            Frame::Array(args),
+                            Frame::from(client),
+                            Frame::from(entry.user.unwrap_or_default()),
+                        ])
+                    })
+                    .collect();
+                Frame::Array(entries)
+            }
+            Subcommand::Len => Frame::Integer(db.slowlog().len() as i64),
+            Subcommand::Reset => {
+                db.slowlog().reset();
+                Frame::ok()
+            }
+        };
+
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = Vec::new();
+        frame.push(Frame::Bulk(Bytes::from("slowlog".as_bytes())));
+        match self.subcommand {
+            Subcommand::Get(count) => {
+                frame.push(Frame::Bulk(Bytes::from("get".as_bytes())));
+                frame.push(Frame::Bulk(Bytes::from(count.to_string().into_bytes())));
+            }
+            Subcommand::Len => {
+                frame.push(Frame::Bulk(Bytes::from("len".as_bytes())));
+            }
+            Subcommand::Reset => {
+                frame.push(Frame::Bulk(Bytes::from("reset".as_bytes())));
+            }
+        }
+        Frame::Array(frame)
+    }
+}
@@ -0,0 +1,468 @@
+use crate::acl::Category;
+use crate::db::MemberUpdate;
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Set the scores of members of a sorted set, the set being created if missing.
+///
+/// `ZADD key [NX|XX] [CH] score member [score member ...]`
+///
+/// `NX` only adds new members, `XX` only updates existing ones. Replies with the number of
+/// members added, along with those whose score changed with `CH`.
+#[derive(Debug)]
+pub struct ZAdd {
+    key: String,
+    members: Vec<(f64, Bytes)>,
+    update: MemberUpdate,
+    changed: bool,
+}
+
+/// Score of a member of a sorted set.
+///
+/// `ZSCORE key member`
+///
+/// Replies with the score, or nil if the member or the key doesn't exist.
+#[derive(Debug)]
+pub struct ZScore {
+    key: String,
+    member: Bytes,
+}
+
+/// Remove members from a sorted set.
+///
+/// `ZREM key member [member ...]`
+///
+/// Replies with the number of members removed. The set is removed once empty.
+#[derive(Debug)]
+pub struct ZRem {
+    key: String,
+    members: Vec<Bytes>,
+}
+
+/// Number of members of a sorted set, `0` if the key doesn't exist.
+///
+/// `ZCARD key`
+#[derive(Debug)]
+pub struct ZCard {
+    key: String,
+}
+
+/// Members of a sorted set by rank, lowest score first.
+///
+/// `ZRANGE key start stop [WITHSCORES]`
+///
+/// Both ranks are included, negative ones counting from the highest score. `WITHSCORES` returns
+/// each member followed by its score.
+#[derive(Debug)]
+pub struct ZRange {
+    key: String,
+    start: i64,
+    stop: i64,
+    with_scores: bool,
+}
+
+fn command_frame(name: &'static str, key: String, args: impl IntoIterator<Item = Bytes>) -> Frame {
+    let mut frame = Vec::new();
+    frame.push(Frame::Bulk(Bytes::from(name.as_bytes())));
+    frame.push(Frame::Bulk(Bytes::from(key.into_bytes())));
+    for arg in args {
+        frame.push(Frame::Bulk(arg));
+    }
+    Frame::Array(frame)
+}
+
+/// A score, `inf` and `-inf` included
+fn parse_score(text: &str) -> crate::Result<f64> {
+    match text.parse::<f64>() {
+        Ok(score) if !score.is_nan() => Ok(score),
+        _ => Err("ERR value is not a valid float".into()),
+    }
+}
+
+/// A score as replied, in its shortest decimal form
+fn score_frame(score: f64) -> Frame {
+    Frame::Bulk(Bytes::from(score.to_string()))
+}
+
+/// The `NX`, `XX` and `CH` options of `ZADD` and `GEOADD`, which come first. Returns them along
+/// with the argument following them.
+pub(super) fn parse_add_options(parse: &mut Parse) -> crate::Result<(MemberUpdate, bool, String)> {
+    let (mut nx, mut xx, mut changed) = (false, false, false);
+    let mut next = parse.next_string()?;
+    loop {
+        match &next.to_uppercase()[..] {
+            "NX" => nx = true,
+            "XX" => xx = true,
+            "CH" => changed = true,
+            _ => break,
+        }
+        next = parse.next_string()?;
+    }
+    let update = match (nx, xx) {
+        (true, true) => {
+            return Err("ERR XX and NX options at the same time are not compatible".into())
+        }
+        (true, false) => MemberUpdate::Add,
+        (false, true) => MemberUpdate::Change,
+        (false, false) => MemberUpdate::All,
+    };
+    Ok((update, changed, next))
+}
+
+/// The `NX`, `XX` and `CH` arguments of `ZADD` and `GEOADD`
+pub(super) fn add_options(update: MemberUpdate, changed: bool) -> Vec<Bytes> {
+    let mut args = vec![];
+    match update {
+        MemberUpdate::All => {}
+        MemberUpdate::Add => args.push(Bytes::from_static(b"nx")),
+        MemberUpdate::Change => args.push(Bytes::from_static(b"xx")),
+    }
+    if changed {
+        args.push(Bytes::from_static(b"ch"));
+    }
+    args
+}
+
+fn parse_index(parse: &mut Parse) -> crate::Result<i64> {
+    parse
+        .next_string()?
+        .parse::<i64>()
+        .map_err(|_| "ERR value is not an integer or out of range".into())
+}
+
+fn parse_members(parse: &mut Parse) -> crate::Result<Vec<Bytes>> {
+    let mut members = vec![parse.next_bytes()?];
+    loop {
+        match parse.next_bytes() {
+            Ok(member) => members.push(member),
+            Err(ParseError::EndOfStream) => return Ok(members),
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+impl ZAdd {
+    pub fn new(key: impl ToString, members: Vec<(f64, Bytes)>) -> ZAdd {
+        ZAdd {
+            key: key.to_string(),
+            members,
+            update: MemberUpdate::All,
+            changed: false,
+        }
+    }
+
+    /// Only add new members
+    pub fn nx(mut self) -> ZAdd {
+        self.update = MemberUpdate::Add;
+        self
+    }
+
+    /// Only update existing members
+    pub fn xx(mut self) -> ZAdd {
+        self.update = MemberUpdate::Change;
+        self
+    }
+
+    /// Also count the members whose score changed
+    pub fn ch(mut self) -> ZAdd {
+        self.changed = true;
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for ZAdd {
+    const NAME: &'static str = "zadd";
+    const ARITY: i32 = -4;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+    const DENY_OOM: bool = true;
+
+    fn parse(parse: &mut Parse) -> crate::Result<ZAdd> {
+        let mut cmd = ZAdd::new(parse.next_string()?, vec![]);
+        let (update, changed, mut score) = parse_add_options(parse)?;
+        cmd.update = update;
+        cmd.changed = changed;
+
+        loop {
+            // a score without its member is a wrong number of arguments
+            cmd.members
+                .push((parse_score(&score)?, parse.next_bytes()?));
+            score = match parse.next_string() {
+                Ok(score) => score,
+                Err(ParseError::EndOfStream) => return Ok(cmd),
+                Err(err) => return Err(err.into()),
+            };
+        }
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.sorted_set_add(&self.key, self.members, self.update, self.changed) {
+            Ok(count) => Frame::Integer(count as i64),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut args = add_options(self.update, self.changed);
+        for (score, member) in self.members {
+            args.push(Bytes::from(score.to_string()));
+            args.push(member);
+        }
+        command_frame("zadd", self.key, args)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl ZScore {
+    pub fn new(key: impl ToString, member: Bytes) -> ZScore {
+        ZScore {
+            key: key.to_string(),
+            member,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for ZScore {
+    const NAME: &'static str = "zscore";
+    const ARITY: i32 = 3;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<ZScore> {
+        let key = parse.next_string()?;
+        let member = parse.next_bytes()?;
+        Ok(ZScore { key, member })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.sorted_set_score(&self.key, &self.member) {
+            Ok(Some(score)) => score_frame(score),
+            Ok(None) => Frame::Null,
+            Err(err) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "zscore", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        command_frame("zscore", self.key, Some(self.member))
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl ZRem {
+    pub fn new(key: impl ToString, members: Vec<Bytes>) -> ZRem {
+        ZRem {
+            key: key.to_string(),
+            members,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for ZRem {
+    const NAME: &'static str = "zrem";
+    const ARITY: i32 = -3;
+    const CATEGORY: Option<Category> = Some(Category::Write);
+
+    fn parse(parse: &mut Parse) -> crate::Result<ZRem> {
+        let key = parse.next_string()?;
+        let members = parse_members(parse)?;
+        Ok(ZRem { key, members })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.sorted_set_remove(&self.key, self.members) {
+            Ok(removed) => Frame::Integer(removed as i64),
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        command_frame("zrem", self.key, self.members)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl ZCard {
+    pub fn new(key: impl ToString) -> ZCard {
+        ZCard {
+            key: key.to_string(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for ZCard {
+    const NAME: &'static str = "zcard";
+    const ARITY: i32 = 2;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<ZCard> {
+        Ok(ZCard::new(parse.next_string()?))
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.sorted_set_len(&self.key) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "zcard", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        command_frame("zcard", self.key, None)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
+
+impl ZRange {
+    pub fn new(key: impl ToString, start: i64, stop: i64) -> ZRange {
+        ZRange {
+            key: key.to_string(),
+            start,
+            stop,
+            with_scores: false,
+        }
+    }
+
+    /// Return each member followed by its score
+    pub fn with_scores(mut self) -> ZRange {
+        self.with_scores = true;
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl CommandSpec for ZRange {
+    const NAME: &'static str = "zrange";
+    const ARITY: i32 = -4;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<ZRange> {
+        let key = parse.next_string()?;
+        let start = parse_index(parse)?;
+        let stop = parse_index(parse)?;
+        let mut cmd = ZRange::new(key, start, stop);
+
+        match parse.next_string() {
+            Ok(option) if option.eq_ignore_ascii_case("withscores") => cmd.with_scores = true,
+            Ok(_) => return Err("ERR syntax error".into()),
+            Err(ParseError::EndOfStream) => return Ok(cmd),
+            Err(err) => return Err(err.into()),
+        }
+        if parse.remaining() > 0 {
+            return Err("ERR syntax error".into());
+        }
+        Ok(cmd)
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match db.sorted_set_range(&self.key, self.start, self.stop) {
+            Ok(members) if self.with_scores => Frame::Array(
+                members
+                    .into_iter()
+                    .flat_map(|(member, score)| [Frame::Bulk(member), score_frame(score)])
+                    .collect(),
+            ),
+            Ok(members) => Frame::Array(
+                members
+                    .into_iter()
+                    .map(|(member, _)| Frame::Bulk(member))
+                    .collect(),
+            ),
+            Err(err) => super::error_reply(&err),
+        };
+        super::audit_read(db, dst, "zrange", &self.key);
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut args = vec![
+            Bytes::from(self.start.to_string()),
+            Bytes::from(self.stop.to_string()),
+        ];
+        if self.with_scores {
+            args.push(Bytes::from_static(b"withscores"));
+        }
+        command_frame("zrange", self.key, args)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![self.key()]
+    }
+}
@@ -1,12 +1,16 @@
-use std::{pin::Pin, vec};
+use std::sync::Arc;
+use std::{mem, pin::Pin, vec};
 
-use crate::{Command, Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::db::{ChannelStats, Published};
+use crate::server::PubSubOverflow;
+use crate::{Command, Connection, Db, Error, Frame, OwnedReadHalf, OwnedWriteHalf, Parse, ParseError, Shutdown};
+use crate::acl::Category;
 use bytes::Bytes;
 use tokio::select;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
 use tokio_stream::{Stream, StreamExt, StreamMap};
 
-use super::Unknown;
+use super::{CommandSpec, Quit, Reset};
 
 #[derive(Debug)]
 pub struct Subscribe {
@@ -18,14 +22,125 @@ pub struct Unsubscribe {
     channels: Vec<String>,
 }
 
-type Message = Pin<Box<dyn Stream<Item = Bytes> + Send>>;
+type Message = Pin<Box<dyn Stream<Item = Delivery> + Send>>;
+
+/// What a subscription stream hands to the connection
+enum Delivery {
+    Message(Published),
+    /// The subscriber fell behind and this many messages were dropped
+    Lagged(u64),
+    /// The subscriber fell behind, this many messages were dropped and it must be disconnected
+    Overflowed(u64),
+}
+
+/// Maximum number of queued frames written to a subscriber before flushing
+const MAX_MESSAGE_BATCH: usize = 64;
+
+/// Number of frames queued for a subscriber. Once full, its subscriptions aren't read until the
+/// client catches up, their channels fill up and `pubsub-overflow` applies.
+const MAX_QUEUED_FRAMES: usize = 1024;
+
+/// A frame queued for a subscriber
+enum Outbound {
+    Frame(Frame),
+    /// A `message` frame, encoded once by the publisher
+    Encoded(Bytes),
+}
+
+/// How a client left subscribe mode
+enum Exit {
+    /// The client went away or the server is shutting down
+    Closed,
+    Reset(Reset),
+    Quit(Quit),
+}
 
 impl Subscribe {
     pub(crate) fn new(channels: Vec<String>) -> Subscribe {
         Subscribe { channels }
     }
 
-    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Subscribe> {
+    /// Serve the subscriber until it leaves subscribe mode.
+    ///
+    /// The frames sent to the client go through a queue written to the connection on its own, so
+    /// a client slow to read doesn't hold up its commands nor the reading of its channels.
+    async fn serve(
+        &mut self,
+        subscriptions: &mut StreamMap<String, Message>,
+        db: &Db,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<Exit> {
+        // the halves are put back together once the client leaves subscribe mode
+        let connection = mem::replace(dst, Connection::capture(None));
+        let (mut reader, writer) = connection.into_split();
+        let (queue, queued) = mpsc::channel(MAX_QUEUED_FRAMES);
+
+        let (writer, res) = {
+            let write = write_queued(writer, queued);
+            tokio::pin!(write);
+            let read = self.read(subscriptions, db, &mut reader, queue, shutdown);
+            tokio::pin!(read);
+
+            select! {
+                // the queue is only closed once `read` completes, the writer stopped on an error
+                (writer, res) = &mut write => (writer, res.map(|_| Exit::Closed)),
+                exit = &mut read => {
+                    // `read` dropped the queue, write out what is left in it
+                    let (writer, res) = write.await;
+                    (writer, res.and(exit))
+                }
+            }
+        };
+
+        *dst = reader.reunite(writer);
+        res
+    }
+
+    /// Handle the commands of the client and queue the messages of its channels
+    async fn read(
+        &mut self,
+        subscriptions: &mut StreamMap<String, Message>,
+        db: &Db,
+        reader: &mut OwnedReadHalf,
+        queue: mpsc::Sender<Outbound>,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<Exit> {
+        loop {
+            for channel_name in self.channels.drain(..) {
+                subscribe_to_channel(channel_name, subscriptions, db, &queue).await?;
+            }
+            // wait for the one of the following to happend
+            select! {
+                Some((channel_name, msg)) = next_delivery(subscriptions, &queue) => {
+                    queue_delivery(channel_name, msg, &queue).await?;
+                }
+
+                res = reader.read_frame() => {
+                    let frame = match res? {
+                        Some(frame) => frame,
+                        None => return Ok(Exit::Closed),
+                    };
+                    let channels = &mut self.channels;
+                    if let Some(exit) = handle_command(frame, channels, subscriptions, db, &queue).await? {
+                        return Ok(exit);
+                    }
+                }
+
+                _ = shutdown.recv() => {
+                    return Ok(Exit::Closed);
+                }
+            };
+        }
+    }
+}
+
+impl CommandSpec for Subscribe {
+    const NAME: &'static str = "subscribe";
+    const ARITY: i32 = -2;
+    const CATEGORY: Option<Category> = Some(Category::Pubsub);
+
+    fn parse(parse: &mut Parse) -> crate::Result<Subscribe> {
         use ParseError::EndOfStream;
 
         let mut channels = vec![parse.next_string()?];
@@ -43,45 +158,37 @@ impl Subscribe {
         Ok(Subscribe::new(channels))
     }
 
-    pub(crate) async fn apply(
+    async fn apply(
         mut self,
         db: &Db,
         dst: &mut Connection,
         shutdown: &mut Shutdown,
     ) -> crate::Result<()> {
         let mut subscriptions = StreamMap::new();
-        loop {
-            for channel_name in self.channels.drain(..) {
-                subscribe_to_channel(channel_name, &mut subscriptions, db, dst).await?;
-            }
-            // wait for the one of the following to happend
-            select! {
-                Some((channel_name, msg)) = subscriptions.next() => {
-                    dst.write_frame(&make_message_frame(channel_name, msg)).await?;
-                }
+        let res = self.serve(&mut subscriptions, db, dst, shutdown).await;
 
-                res = dst.read_frame() => {
-                    let frame = match res? {
-                        Some(frame) => frame,
-                        None => return Ok(()),
-                    };
-                    handle_command(frame, &mut self.channels, &mut subscriptions, dst).await?;
-                }
+        // the client is gone, drop the channels it was the last subscriber of
+        let channels: Vec<String> = subscriptions.keys().cloned().collect();
+        drop(subscriptions);
+        for channel_name in channels {
+            db.release_channel(&channel_name);
+        }
 
-                _ = shutdown.recv() => {
-                    return Ok(());
-                }
-            };
+        // the command leaving subscribe mode is answered after the frames queued before it
+        match res? {
+            Exit::Closed => Ok(()),
+            Exit::Reset(cmd) => cmd.apply(db, dst, shutdown).await,
+            Exit::Quit(cmd) => cmd.apply(db, dst, shutdown).await,
         }
     }
-    pub(crate) fn into_frame(self) -> Frame {
-        let mut f = Frame::array();
-        f.push_bulk(Bytes::from("subscribe".as_bytes()));
+
+    fn into_frame(self) -> Frame {
+        let mut f = vec![Frame::Bulk(Bytes::from("subscribe".as_bytes()))];
 
         for channel in self.channels {
-            f.push_bulk(Bytes::from(channel.into_bytes()));
+            f.push(Frame::Bulk(Bytes::from(channel.into_bytes())));
         }
-        f
+        Frame::Array(f)
     }
 }
 
@@ -89,15 +196,29 @@ async fn subscribe_to_channel(
     channel_name: String,
     subscriptions: &mut StreamMap<String, Message>,
     db: &Db,
-    dst: &mut Connection,
+    queue: &mpsc::Sender<Outbound>,
 ) -> crate::Result<()> {
-    let mut rx = db.subscribe(channel_name.clone());
+    let (mut rx, stats) = db.subscribe(channel_name.clone());
+    let db = db.clone();
 
     let rx = Box::pin(async_stream::stream! {
+        // counts the unsubscription once the stream is dropped
+        let _guard = UnsubscribeGuard(stats.clone());
         loop {
             match rx.recv().await {
-                Ok(msg) => yield msg,
-                Err(broadcast::error::RecvError::Lagged(_)) => {},
+                Ok(msg) => {
+                    stats.record_delivered();
+                    yield Delivery::Message(msg);
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    stats.record_dropped(n);
+                    if db.pubsub_overflow() == PubSubOverflow::Disconnect {
+                        stats.record_disconnected();
+                        yield Delivery::Overflowed(n);
+                        break;
+                    }
+                    yield Delivery::Lagged(n);
+                }
                 Err(_) => break,
             }
         }
@@ -105,27 +226,119 @@ async fn subscribe_to_channel(
 
     subscriptions.insert(channel_name.clone(), rx);
     let resp = make_subscribe_frame(channel_name, subscriptions.len());
-    dst.write_frame(&resp).await?;
+    push(queue, Outbound::Frame(resp)).await
+}
+/// Records the channel unsubscription when the subscription stream is dropped, whether the client
+/// unsubscribed or went away.
+struct UnsubscribeGuard(Arc<ChannelStats>);
+
+impl Drop for UnsubscribeGuard {
+    fn drop(&mut self) {
+        self.0.record_unsubscribe();
+    }
+}
+
+/// Write out the queued frames until the queue is closed.
+///
+/// Frames are written unflushed while more are queued, so a burst of publishes reaches the client
+/// in a single flush. The batch is capped so the client gets its replies in a timely manner.
+async fn write_queued(
+    mut writer: OwnedWriteHalf,
+    mut queued: mpsc::Receiver<Outbound>,
+) -> (OwnedWriteHalf, crate::Result<()>) {
+    let res = async {
+        while let Some(outbound) = queued.recv().await {
+            write_outbound(&mut writer, outbound).await?;
+            for _ in 1..MAX_MESSAGE_BATCH {
+                match queued.try_recv() {
+                    Ok(outbound) => write_outbound(&mut writer, outbound).await?,
+                    Err(_) => break,
+                }
+            }
+            writer.flush().await?;
+        }
+        Ok(())
+    }
+    .await;
+    (writer, res)
+}
 
+async fn write_outbound(writer: &mut OwnedWriteHalf, outbound: Outbound) -> crate::Result<()> {
+    match outbound {
+        Outbound::Frame(frame) => writer.write_frame_unflushed(&frame).await?,
+        Outbound::Encoded(encoded) => writer.write_encoded_unflushed(&encoded).await?,
+    }
     Ok(())
 }
-fn make_message_frame(channel_name: String, msg: Bytes) -> Frame {
-    let mut f = Frame::array();
-    f.push_bulk(Bytes::from_static(b"message"));
-    f.push_bulk(Bytes::from(channel_name));
-    f.push_bulk(msg);
-    f
+
+/// The next message of the subscriptions, once the queue has room for it. The messages wait in
+/// their channels while the queue is full.
+async fn next_delivery(
+    subscriptions: &mut StreamMap<String, Message>,
+    queue: &mpsc::Sender<Outbound>,
+) -> Option<(String, Delivery)> {
+    // the reader is the only sender, the room is still there once the message is queued
+    queue.reserve().await.ok()?;
+    subscriptions.next().await
+}
+
+/// Queue `outbound` for the client
+async fn push(queue: &mpsc::Sender<Outbound>, outbound: Outbound) -> crate::Result<()> {
+    // the writer only stops early on a connection error
+    queue.send(outbound).await.map_err(|_| Error::ConnectionReset)
+}
+
+/// Queue `delivery` for the client. A subscriber which overflowed its channel is answered an
+/// error, which is returned so the connection gets closed.
+async fn queue_delivery(
+    channel_name: String,
+    delivery: Delivery,
+    queue: &mpsc::Sender<Outbound>,
+) -> crate::Result<()> {
+    match delivery {
+        Delivery::Overflowed(missed) => {
+            let err = Error::Lagged {
+                channel: channel_name,
+                missed,
+            };
+            push(queue, Outbound::Frame(super::error_reply(&err))).await?;
+            Err(err)
+        }
+        Delivery::Message(msg) => push(queue, Outbound::Encoded(msg.frame)).await,
+        Delivery::Lagged(n) => push(queue, Outbound::Frame(make_lagged_frame(channel_name, n))).await,
+    }
+}
+
+/// `lagged channel n` when `n` messages were dropped because the subscriber couldn't keep up
+fn make_lagged_frame(channel_name: String, n: u64) -> Frame {
+    Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"lagged")),
+        Frame::Bulk(Bytes::from(channel_name)),
+        Frame::Integer(n as i64),
+    ])
 }
 
+/// Handle a command received while subscribed. Returns how the client leaves subscribe mode
+/// once it does.
 async fn handle_command(
     frame: Frame,
     subscribe_to: &mut Vec<String>,
     subscriptions: &mut StreamMap<String, Message>,
-    dst: &mut Connection,
-) -> crate::Result<()> {
-    match Command::from_frame(frame)? {
+    db: &Db,
+    queue: &mpsc::Sender<Outbound>,
+) -> crate::Result<Option<Exit>> {
+    let command = match Command::from_frame(frame) {
+        Ok(command) => command,
+        Err(err) => {
+            push(queue, Outbound::Frame(super::error_reply(&err))).await?;
+            return Ok(None);
+        }
+    };
+
+    let response = match command {
         Command::Subscribe(sub) => {
             subscribe_to.extend(sub.channels.into_iter());
+            return Ok(None);
         }
 
         Command::Unsubscribe(mut unsubscribe) => {
@@ -136,34 +349,55 @@ async fn handle_command(
                     .collect();
             }
             for channel_name in unsubscribe.channels {
-                subscriptions.remove(&channel_name);
+                // dropping the stream drops its receiver, the channel can then be released
+                if subscriptions.remove(&channel_name).is_some() {
+                    db.release_channel(&channel_name);
+                }
 
                 let resp = make_unsubscribe_frame(channel_name, subscriptions.len());
-                dst.write_frame(&resp).await?;
+                push(queue, Outbound::Frame(resp)).await?;
             }
+
+            // `StreamMap` keeps the capacity of its peak number of subscriptions
+            if subscriptions.is_empty() {
+                *subscriptions = StreamMap::new();
+            }
+            return Ok(None);
         }
-        command => {
-            let cmd = Unknown::new(command.get_name());
-            cmd.apply(dst).await?;
+        // a subscriber of a RESP2 connection only reads arrays, the pong is one
+        Command::Ping(ping) => {
+            Frame::Array(vec![
+                Frame::Bulk(Bytes::from_static(b"pong")),
+                Frame::Bulk(ping.msg().cloned().unwrap_or_default()),
+            ])
         }
-    }
-    Ok(())
+        // the subscriptions are dropped along with subscribe mode
+        Command::Reset(cmd) => return Ok(Some(Exit::Reset(cmd))),
+        Command::Quit(cmd) => return Ok(Some(Exit::Quit(cmd))),
+        Command::Unknown(cmd) => cmd.into_error(),
+        command => Frame::error(format!(
+            "ERR Can't execute '{}': only SUBSCRIBE / UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+            command.get_name()
+        )),
+    };
+    push(queue, Outbound::Frame(response)).await?;
+    Ok(None)
 }
 
 fn make_subscribe_frame(channel_name: String, num_subs: usize) -> Frame {
-    let mut f = Frame::array();
-    f.push_bulk(Bytes::from_static(b"subscribe"));
-    f.push_bulk(Bytes::from(channel_name));
-    f.push_int(num_subs as u64);
-    f
+    Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"subscribe")),
+        Frame::Bulk(Bytes::from(channel_name)),
+        Frame::Integer(num_subs as i64),
+    ])
 }
 
 fn make_unsubscribe_frame(channel_name: String, num_subts: usize) -> Frame {
-    let mut f = Frame::array();
-    f.push_bulk(Bytes::from_static(b"unsubscribe"));
-    f.push_bulk(Bytes::from(channel_name));
-    f.push_int(num_subts as u64);
-    f
+    Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"unsubscribe")),
+        Frame::Bulk(Bytes::from(channel_name)),
+        Frame::Integer(num_subts as i64),
+    ])
 }
 
 impl Unsubscribe {
@@ -172,8 +406,14 @@ impl Unsubscribe {
             channels: channels.to_vec(),
         }
     }
+}
+
+impl CommandSpec for Unsubscribe {
+    const NAME: &'static str = "unsubscribe";
+    const ARITY: i32 = -1;
+    const CATEGORY: Option<Category> = Some(Category::Pubsub);
 
-    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Unsubscribe> {
+    fn parse(parse: &mut Parse) -> crate::Result<Unsubscribe> {
         use ParseError::EndOfStream;
         let mut channels = vec![];
 
@@ -188,13 +428,22 @@ impl Unsubscribe {
         Ok(Unsubscribe::new(&channels))
     }
 
-    pub(crate) fn into_frame(self) -> Frame {
-        let mut frame = Frame::array();
-        frame.push_bulk(Bytes::from("unsubscribe".as_bytes()));
+    /// Only valid while subscribed, where `handle_command` takes care of it
+    async fn apply(
+        self,
+        _db: &Db,
+        _dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        Err("`Unsubscribe` is unsupported in this context".into())
+    }
+
+    fn into_frame(self) -> Frame {
+        let mut frame = vec![Frame::Bulk(Bytes::from("unsubscribe".as_bytes()))];
         for channel in self.channels {
-            frame.push_bulk(Bytes::from(channel.into_bytes()));
+            frame.push(Frame::Bulk(Bytes::from(channel.into_bytes())));
         }
 
-        frame
+        Frame::Array(frame)
     }
 }
@@ -0,0 +1,137 @@
+use crate::commit::WriteRecord;
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tokio::select;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Maximum number of write records sent to the consumer before flushing
+const MAX_RECORD_BATCH: usize = 64;
+
+/// Stream every write applied to the key space, starting at a sequence number.
+///
+/// `SYNCFROM seq` turns the connection into a change data capture stream: each write with a
+/// sequence number of at least `seq` is sent as an array, see `WriteRecord::to_frame` for the
+/// layout. `SYNCFROM 0` starts from the oldest write of the backlog. Records older than the server
+/// backlog can't be replayed, the command then fails and the consumer has to resynchronize from a
+/// fresh copy of the data.
+///
+/// The stream lasts until the client disconnects or the server shuts down. A consumer reading
+/// slower than the writes are applied is sent an error and disconnected, it can resume with
+/// `SYNCFROM` from the next sequence number.
+#[derive(Debug)]
+pub struct SyncFrom {
+    seq: u64,
+}
+
+impl SyncFrom {
+    pub fn new(seq: u64) -> SyncFrom {
+        SyncFrom { seq }
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+}
+
+impl CommandSpec for SyncFrom {
+    const NAME: &'static str = "syncfrom";
+    const ARITY: i32 = 2;
+    const CATEGORY: Option<Category> = Some(Category::Admin);
+
+    fn parse(parse: &mut Parse) -> crate::Result<SyncFrom> {
+        let seq = parse.next_int()?;
+        Ok(SyncFrom { seq })
+    }
+
+    #[instrument(skip(self, db, dst, shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let (backlog, mut rx) = match db.subscribe_writes_from(self.seq) {
+            Ok(subscription) => subscription,
+            Err(evicted) => {
+                let response = Frame::error(format!(
+                    "ERR sequence number {} is no longer in the backlog, oldest available is {}",
+                    self.seq, evicted.oldest
+                ));
+                debug!(%response);
+                dst.write_frame(&response).await?;
+                return Ok(());
+            }
+        };
+
+        // Records following the backlog may also have been dispatched before the subscription, so
+        // track the next expected sequence number to skip duplicates.
+        let mut next = self.seq;
+
+        for record in &backlog {
+            dst.write_frame_unflushed(&record.to_frame()).await?;
+            next = record.seq + 1;
+        }
+        dst.flush().await?;
+
+        loop {
+            select! {
+                res = rx.recv() => {
+                    let record = match res {
+                        Some(record) => record,
+                        // the dispatcher dropped the consumer, it fell behind
+                        None => {
+                            let response = Frame::error(format!(
+                                "ERR consumer fell behind the writes, resume with SYNCFROM {}",
+                                next
+                            ));
+                            debug!(%response);
+                            dst.write_frame(&response).await?;
+                            dst.close();
+                            return Ok(());
+                        }
+                    };
+                    write_record(&record, &mut next, dst).await?;
+
+                    // write out the records which are already queued before flushing
+                    for _ in 0..MAX_RECORD_BATCH {
+                        match rx.try_recv() {
+                            Ok(record) => write_record(&record, &mut next, dst).await?,
+                            Err(_) => break,
+                        }
+                    }
+                    dst.flush().await?;
+                }
+
+                res = dst.read_frame() => {
+                    // The stream is one way, commands sent by the consumer are ignored
+                    if res?.is_none() {
+                        return Ok(());
+                    }
+                }
+
+                _ = shutdown.recv() => {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("syncfrom".as_bytes())),
+            Frame::Integer(self.seq as i64),
+        ])
+    }
+}
+
+async fn write_record(record: &WriteRecord, next: &mut u64, dst: &mut Connection) -> crate::Result<()> {
+    if record.seq >= *next {
+        dst.write_frame_unflushed(&record.to_frame()).await?;
+        *next = record.seq + 1;
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::acl::Category;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use super::CommandSpec;
+
+/// Number of minutes forecast when none is given
+const DEFAULT_FORECAST_MINUTES: u64 = 10;
+
+/// Longest forecast, one day
+const MAX_FORECAST_MINUTES: u64 = 24 * 60;
+
+/// Distribution of the key expirations, to anticipate load spikes from mass expiry.
+///
+/// `TTLSTATS [minutes]` replies with a flat array of field/value pairs: the number of keys with
+/// and without a ttl, a histogram of the time left before the keys expire as label/count pairs,
+/// where each label is the exclusive upper bound of its bucket, and the number of keys expiring
+/// during each of the next `minutes` (10 by default).
+///
+/// The whole expiration index is walked, which blocks the key space on large data sets.
+#[derive(Debug)]
+pub struct TtlStats {
+    minutes: u64,
+}
+
+impl TtlStats {
+    pub fn new(minutes: u64) -> TtlStats {
+        TtlStats { minutes }
+    }
+}
+
+impl CommandSpec for TtlStats {
+    const NAME: &'static str = "ttlstats";
+    const ARITY: i32 = -1;
+    const CATEGORY: Option<Category> = Some(Category::Read);
+
+    fn parse(parse: &mut Parse) -> crate::Result<TtlStats> {
+        let minutes = match parse.next_int() {
+            Ok(minutes) if minutes <= MAX_FORECAST_MINUTES => minutes,
+            Ok(_) => {
+                return Err(format!("ERR forecast is limited to {} minutes", MAX_FORECAST_MINUTES).into())
+            }
+            Err(ParseError::EndOfStream) => DEFAULT_FORECAST_MINUTES,
+            Err(err) => return Err(err.into()),
+        };
+        Ok(TtlStats { minutes })
+    }
+
+    #[instrument(skip(self, db, dst, _shutdown))]
+    async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        _shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let stats = db.ttl_stats(self.minutes as usize);
+
+        let mut histogram = Vec::new();
+        for (label, count) in stats.histogram {
+            histogram.push(Frame::Bulk(Bytes::from_static(label.as_bytes())));
+            histogram.push(Frame::Integer(count as i64));
+        }
+
+        let mut forecast = Vec::new();
+        for count in stats.forecast {
+            forecast.push(Frame::Integer(count as i64));
+        }
+
+        let response = Frame::Array(vec![
+            Frame::Bulk(Bytes::from_static(b"keys_with_ttl")),
+            Frame::Integer(stats.keys_with_ttl as i64),
+            Frame::Bulk(Bytes::from_static(b"keys_without_ttl")),
+            Frame::Integer(stats.keys_without_ttl as i64),
+            Frame::Bulk(Bytes::from_static(b"histogram")),
+            Frame::Array(histogram),
+            Frame::Bulk(Bytes::from_static(b"forecast")),
+            Frame::Array(forecast),
+        ]);
+
+        debug!(%response);
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    fn into_frame(self) -> Frame {
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("ttlstats".as_bytes())),
+            Frame::Integer(self.minutes as i64),
+        ])
+    }
+}
@@ -1,27 +1,128 @@
-use crate::{Connection, Frame};
+use crate::{Connection, Db, Frame, Parse, Store};
 
 use tracing::{debug, instrument};
 
+use super::registry;
+
+/// Length of the arguments quoted by the unknown command error
+const MAX_QUOTED_ARGS: usize = 128;
+
 #[derive(Debug)]
 pub struct Unknown {
     command_name: String,
+    /// Arguments following the name, for the commands registered by the application
+    args: Parse,
 }
 
 impl Unknown {
-    pub (crate) fn new(key: impl ToString) -> Unknown {
-        Unknown {command_name: key.to_string() }
+    pub (crate) fn new(key: impl ToString, args: Parse) -> Unknown {
+        Unknown { command_name: key.to_string(), args }
     }
 
     pub (crate) fn get_name(&self) -> &str{
         &self.command_name
     }
 
+    /// Arguments following the name
+    pub(crate) fn args(&self) -> &Parse {
+        &self.args
+    }
+
+    /// Run the handler registered under the name of the command, or reply with an error if
+    /// there is none
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(mut self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let handler = match db.command_handler(&self.command_name) {
+            Some(handler) => handler,
+            None => return self.reject(dst).await,
+        };
+
+        let response = match handler.call(&mut self.args, &Store::from_db(db.clone())) {
+            Ok(frame) => frame,
+            Err(err) => super::error_reply(&err),
+        };
+        debug!(%response);
+
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+
+    /// Reply that the command isn't known
     #[instrument(skip(self, dst))]
-    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
-        let response = Frame::Error(format!("ERR unknown command '{}'", self.command_name));
-        debug!(?response);
+    pub(crate) async fn reject(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.into_error();
+        debug!(%response);
 
         dst.write_frame(&response).await?;
         Ok(())
     }
+
+    /// The error telling the command isn't known, quoting its first arguments and the command it
+    /// may be a typo of
+    pub(crate) fn into_error(mut self) -> Frame {
+        // as Redis does, the quoted arguments stop once 128 bytes were written
+        let mut args = String::new();
+        while args.len() < MAX_QUOTED_ARGS {
+            let arg = match self.args.next_bytes() {
+                Ok(arg) => arg,
+                Err(_) => break,
+            };
+            let arg = String::from_utf8_lossy(&arg);
+            let arg: String = arg.chars().take(MAX_QUOTED_ARGS - args.len()).collect();
+            args.push_str(&format!("'{}' ", arg));
+        }
+
+        let mut msg = format!(
+            "ERR unknown command '{}', with args beginning with: {}",
+            self.command_name, args
+        );
+        if let Some(name) = registry::suggest(&self.command_name) {
+            msg.push_str(&format!("(did you mean '{}'?)", name));
+        }
+        Frame::Error(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+
+    fn unknown(args: &[&'static str]) -> Unknown {
+        let frame = Frame::Array(args.iter().map(|arg| Frame::Bulk(Bytes::from_static(arg.as_bytes()))).collect());
+        let mut parse = Parse::new(frame).unwrap();
+        let name = parse.next_string().unwrap();
+        Unknown::new(name, parse)
+    }
+
+    #[test]
+    fn suggests_close_command() {
+        assert_eq!(registry::suggest("sett"), Some("set"));
+        assert_eq!(registry::suggest("SETT"), Some("set"));
+        assert_eq!(registry::suggest("publsh"), Some("publish"));
+    }
+
+    #[test]
+    fn suggestion_cutoff() {
+        // one typo allowed in a three letter name, this one has two
+        assert_eq!(registry::suggest("gte"), None);
+        assert_eq!(registry::suggest("xyzzy"), None);
+        // a known command isn't a typo
+        assert_eq!(registry::suggest("get"), None);
+    }
+
+    #[test]
+    fn error_with_suggestion() {
+        let err = unknown(&["sett", "k", "v"]).into_error();
+        let msg = "ERR unknown command 'sett', with args beginning with: 'k' 'v' (did you mean 'set'?)";
+        assert_eq!(err, Frame::Error(msg.to_string()));
+    }
+
+    #[test]
+    fn error_without_suggestion() {
+        let err = unknown(&["xyzzy"]).into_error();
+        let msg = "ERR unknown command 'xyzzy', with args beginning with: ";
+        assert_eq!(err, Frame::Error(msg.to_string()));
+    }
 }
@@ -0,0 +1,74 @@
+//! `tokio_util::codec` support for the RESP protocol.
+//!
+//! `RespCodec` lets the frame machinery be plugged into `Framed` streams or any other transport
+//! without going through `Connection`.
+
+use crate::frame::{self, Frame, Limits};
+
+use bytes::{Buf, BytesMut};
+use std::io::Cursor;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Encodes and decodes `Frame` values using the redis protocol
+#[derive(Debug, Default, Clone)]
+pub struct RespCodec {
+    limits: Limits,
+}
+
+impl RespCodec {
+    pub fn new() -> RespCodec {
+        RespCodec::default()
+    }
+
+    /// Create a codec which rejects decoded frames exceeding `limits`
+    pub fn with_limits(limits: Limits) -> RespCodec {
+        RespCodec { limits }
+    }
+}
+
+impl Decoder for RespCodec {
+    type Item = Frame;
+    type Error = crate::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> crate::Result<Option<Frame>> {
+        use frame::Error::Incomplete;
+
+        let mut buf = Cursor::new(&src[..]);
+
+        // Same two step process as `Connection::parse_frame`: a cheap `check` to find out whether a
+        // full frame has been buffered, then the actual parse.
+        match Frame::check_with_limits(&mut buf, &self.limits) {
+            Ok(_) => {
+                let len = buf.position() as usize;
+                buf.set_position(0);
+
+                let frame = Frame::parse(&mut buf)?;
+                src.advance(len);
+                Ok(Some(frame))
+            }
+            Err(Incomplete) if src.len() > self.limits.max_frame_size => {
+                Err(crate::Error::Protocol("protocol error; frame too large".into()))
+            }
+            Err(Incomplete) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Encoder<Frame> for RespCodec {
+    type Error = crate::Error;
+
+    fn encode(&mut self, item: Frame, dst: &mut BytesMut) -> crate::Result<()> {
+        item.encode(dst);
+        Ok(())
+    }
+}
+
+impl Encoder<&Frame> for RespCodec {
+    type Error = crate::Error;
+
+    fn encode(&mut self, item: &Frame, dst: &mut BytesMut) -> crate::Result<()> {
+        item.encode(dst);
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+//! Per command call statistics, see `INFO commandstats` and `CONFIG RESETSTAT`.
+//!
+//! Every command executed is counted by the `RecordCalls` layer, along with the time it took and
+//! whether it failed: the command returned an error, or replied one to the client. The counters
+//! are atomics indexed by the command id in the registry, no lock is taken on the hot path.
+
+use crate::cmd::COMMANDS;
+use crate::middleware::{BoxFuture, Context, Layer, Next};
+use crate::Command;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub(crate) struct CommandStats {
+    /// Statistics of the commands of the registry, by command id, see `Command::id`
+    by_id: Box<[Counters]>,
+    /// Statistics of the commands registered by the application, by name. Only the first call of
+    /// each takes the write lock.
+    extensions: RwLock<HashMap<String, Counters>>,
+}
+
+/// Statistics of a command
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Calls {
+    /// Number of times the command ran
+    pub(crate) calls: u64,
+    /// Number of those which failed
+    pub(crate) failed: u64,
+    /// Time spent running the command, in microseconds
+    pub(crate) usec: u64,
+}
+
+/// `Calls` updated concurrently
+#[derive(Debug, Default)]
+struct Counters {
+    calls: AtomicU64,
+    failed: AtomicU64,
+    usec: AtomicU64,
+}
+
+/// A command counted by `RecordCalls`
+enum Counted {
+    Id(usize),
+    Extension(String),
+}
+
+/// Counts the calls of each command
+#[derive(Debug)]
+pub(crate) struct RecordCalls;
+
+impl Default for CommandStats {
+    fn default() -> CommandStats {
+        CommandStats {
+            by_id: COMMANDS.iter().map(|_| Counters::default()).collect(),
+            extensions: RwLock::default(),
+        }
+    }
+}
+
+impl CommandStats {
+    /// Record that the command `id` ran for `elapsed`, and whether it failed
+    pub(crate) fn record(&self, id: usize, elapsed: Duration, failed: bool) {
+        self.by_id[id].record(elapsed, failed);
+    }
+
+    /// Record that the command `name` registered by the application ran for `elapsed`, and
+    /// whether it failed
+    pub(crate) fn record_extension(&self, name: &str, elapsed: Duration, failed: bool) {
+        if let Some(counters) = self.extensions.read().unwrap().get(name) {
+            counters.record(elapsed, failed);
+            return;
+        }
+        let mut extensions = self.extensions.write().unwrap();
+        extensions.entry(name.to_string()).or_default().record(elapsed, failed);
+    }
+
+    /// Statistics of every command which ran, sorted by command name
+    pub(crate) fn snapshot(&self) -> Vec<(String, Calls)> {
+        let extensions = self.extensions.read().unwrap();
+        let mut stats: Vec<_> = COMMANDS
+            .iter()
+            .map(|spec| spec.name)
+            .zip(self.by_id.iter())
+            .chain(extensions.iter().map(|(name, counters)| (name.as_str(), counters)))
+            .map(|(name, counters)| (name.to_string(), counters.load()))
+            .filter(|(_, calls)| calls.calls > 0)
+            .collect();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        stats
+    }
+
+    pub(crate) fn reset(&self) {
+        for counters in self.by_id.iter() {
+            counters.reset();
+        }
+        self.extensions.write().unwrap().clear();
+    }
+}
+
+impl Counters {
+    fn record(&self, elapsed: Duration, failed: bool) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.failed.fetch_add(failed as u64, Ordering::Relaxed);
+        self.usec.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn load(&self) -> Calls {
+        Calls {
+            calls: self.calls.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            usec: self.usec.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        self.calls.store(0, Ordering::Relaxed);
+        self.failed.store(0, Ordering::Relaxed);
+        self.usec.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Layer for RecordCalls {
+    fn call<'a>(
+        &'a self,
+        cmd: Command,
+        cx: &'a mut Context<'_>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, crate::Result<()>> {
+        Box::pin(async move {
+            // unbounded commands last as long as their client wants, unknown ones are only counted
+            // when registered by the application
+            let counted = match &cmd {
+                cmd if cmd.is_unbounded() => None,
+                Command::Unknown(cmd) if cx.db.command_handler(cmd.get_name()).is_none() => None,
+                Command::Unknown(cmd) => Some(Counted::Extension(cmd.get_name().to_string())),
+                _ => cmd.id().map(Counted::Id),
+            };
+
+            let errors = cx.connection.errors_written();
+            let start = Instant::now();
+            let res = next.run(cmd, cx).await;
+            if let Some(counted) = counted {
+                let failed = res.is_err() || cx.connection.errors_written() != errors;
+                let stats = cx.db.command_stats();
+                match counted {
+                    Counted::Id(id) => stats.record(id, start.elapsed(), failed),
+                    Counted::Extension(name) => stats.record_extension(&name, start.elapsed(), failed),
+                }
+            }
+            res
+        })
+    }
+}
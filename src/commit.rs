@@ -0,0 +1,415 @@
+//! The commit pipeline: a single ordered stream of every write applied to the key space.
+//!
+//! Each write is recorded as a compact `WriteOp` tagged with a sequence number. Records are
+//! produced while the lock of the written key's shard is held, and the pipeline allocates the
+//! sequence numbers, so they follow the order in which writes were applied. A dispatcher task forwards them to every registered consumer (AOF, replication,
+//! change data capture...), instead of each subsystem instrumenting `Db::set` on its own.
+
+use crate::db::ListEnd;
+use crate::Frame;
+
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+/// A write applied to the key space
+#[derive(Debug, Clone)]
+pub(crate) enum WriteOp {
+    /// `key` was set to `value`, expiring at `expires_at` if given
+    Set {
+        key: String,
+        value: Bytes,
+        expires_at: Option<SystemTime>,
+    },
+    /// `key` was reserved, see `RESERVE`
+    Reserve {
+        key: String,
+        value: Bytes,
+        expires_at: SystemTime,
+        dead_letter: String,
+    },
+    /// The reservation on `key` was confirmed
+    Confirm { key: String },
+    /// `key` expired and was removed by the background task
+    Expire { key: String },
+    /// `chunk` was appended to the byte log of `key`
+    BlogAppend { key: String, chunk: Bytes },
+    /// The bit at `offset` of the value of `key` was set to `bit`, see `SETBIT`
+    SetBit { key: String, offset: u64, bit: bool },
+    /// `key` was removed, see `UNLOCK` and `DEL`
+    Delete { key: String },
+    /// The expiration of `key` was changed, see `EXPIRE`
+    SetExpiration { key: String, expires_at: SystemTime },
+    /// `values` were pushed one after the other at `end` of the list `key`, see `LPUSH`
+    ListPush { key: String, values: Vec<Bytes>, end: ListEnd },
+    /// `count` elements were removed from `end` of the list `key`, see `LPOP`
+    ListPop { key: String, count: u64, end: ListEnd },
+    /// The element at `index` of the list `key` was replaced by `value`, see `LSET`
+    ListSet { key: String, index: u64, value: Bytes },
+    /// `value` was inserted at `index` of the list `key`, see `LINSERT`
+    ListInsert { key: String, index: u64, value: Bytes },
+    /// The elements at `indexes` of the list `key` were removed, see `LREM`
+    ListRemove { key: String, indexes: Vec<u64> },
+    /// The `fields` of the hash `key` were set to their values, see `HSET`
+    HashSet { key: String, fields: Vec<(Bytes, Bytes)> },
+    /// The `fields` of the hash `key` were removed, see `HDEL`
+    HashDelete { key: String, fields: Vec<Bytes> },
+    /// The `members` of the sorted set `key` were set to their scores, see `ZADD`
+    SortedSetAdd { key: String, members: Vec<(f64, Bytes)> },
+    /// The `members` of the sorted set `key` were removed, see `ZREM`
+    SortedSetRemove { key: String, members: Vec<Bytes> },
+    /// `key` was read by `command`, sampled among the keys matching `audit-read-patterns`.
+    /// Nothing changed, consumers replaying the writes skip it.
+    AuditedRead {
+        key: String,
+        command: &'static str,
+        client: SocketAddr,
+        user: Option<String>,
+        at: SystemTime,
+    },
+}
+
+/// A `WriteOp` along with its position in the stream of writes
+#[derive(Debug, Clone)]
+pub(crate) struct WriteRecord {
+    /// Strictly increasing across writes
+    pub(crate) seq: u64,
+    pub(crate) op: WriteOp,
+}
+
+impl WriteRecord {
+    /// Encode the record as a RESP array: the sequence number followed by the operation and its
+    /// arguments.
+    ///
+    /// ```text
+    /// seq "set" key value expires_at_ms|nil
+    /// seq "reserve" key value expires_at_ms dead_letter
+    /// seq "confirm" key
+    /// seq "expire" key
+    /// seq "blog.append" key chunk
+    /// seq "setbit" key offset 0|1
+    /// seq "del" key
+    /// seq "pexpireat" key expires_at_ms
+    /// seq "lpush"|"rpush" key value...
+    /// seq "lpop"|"rpop" key count
+    /// seq "lset" key index value
+    /// seq "linsert" key index value
+    /// seq "lrem" key index...
+    /// seq "hset" key field value...
+    /// seq "hdel" key field...
+    /// seq "zadd" key score member...
+    /// seq "zrem" key member...
+    /// seq "read" key command client_addr user|nil read_at_ms
+    /// ```
+    ///
+    /// Expiration times are unix timestamps in milliseconds, scores are in their shortest decimal
+    /// form.
+    pub(crate) fn to_frame(&self) -> Frame {
+        let mut frame = Vec::new();
+        frame.push(Frame::Integer(self.seq as i64));
+
+        match &self.op {
+            WriteOp::Set {
+                key,
+                value,
+                expires_at,
+            } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"set")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+                frame.push(Frame::Bulk(value.clone()));
+                match expires_at {
+                    Some(when) => frame.push(Frame::Integer(unix_millis(*when) as i64)),
+                    None => frame.push(Frame::Null),
+                }
+            }
+            WriteOp::Reserve {
+                key,
+                value,
+                expires_at,
+                dead_letter,
+            } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"reserve")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+                frame.push(Frame::Bulk(value.clone()));
+                frame.push(Frame::Integer(unix_millis(*expires_at) as i64));
+                frame.push(Frame::Bulk(Bytes::from(dead_letter.clone())));
+            }
+            WriteOp::Confirm { key } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"confirm")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+            }
+            WriteOp::Expire { key } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"expire")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+            }
+            WriteOp::BlogAppend { key, chunk } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"blog.append")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+                frame.push(Frame::Bulk(chunk.clone()));
+            }
+            WriteOp::SetBit { key, offset, bit } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"setbit")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+                frame.push(Frame::Integer(*offset as i64));
+                frame.push(Frame::Integer(*bit as i64));
+            }
+            WriteOp::Delete { key } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"del")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+            }
+            WriteOp::SetExpiration { key, expires_at } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"pexpireat")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+                frame.push(Frame::Integer(unix_millis(*expires_at) as i64));
+            }
+            WriteOp::ListPush { key, values, end } => {
+                frame.push(Frame::Bulk(Bytes::from_static(match end {
+                    ListEnd::Head => b"lpush",
+                    ListEnd::Tail => b"rpush",
+                })));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+                for value in values {
+                    frame.push(Frame::Bulk(value.clone()));
+                }
+            }
+            WriteOp::ListPop { key, count, end } => {
+                frame.push(Frame::Bulk(Bytes::from_static(match end {
+                    ListEnd::Head => b"lpop",
+                    ListEnd::Tail => b"rpop",
+                })));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+                frame.push(Frame::Integer(*count as i64));
+            }
+            WriteOp::ListSet { key, index, value } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"lset")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+                frame.push(Frame::Integer(*index as i64));
+                frame.push(Frame::Bulk(value.clone()));
+            }
+            WriteOp::ListInsert { key, index, value } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"linsert")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+                frame.push(Frame::Integer(*index as i64));
+                frame.push(Frame::Bulk(value.clone()));
+            }
+            WriteOp::ListRemove { key, indexes } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"lrem")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+                for index in indexes {
+                    frame.push(Frame::Integer(*index as i64));
+                }
+            }
+            WriteOp::HashSet { key, fields } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"hset")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+                for (field, value) in fields {
+                    frame.push(Frame::Bulk(field.clone()));
+                    frame.push(Frame::Bulk(value.clone()));
+                }
+            }
+            WriteOp::HashDelete { key, fields } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"hdel")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+                for field in fields {
+                    frame.push(Frame::Bulk(field.clone()));
+                }
+            }
+            WriteOp::SortedSetAdd { key, members } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"zadd")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+                for (score, member) in members {
+                    frame.push(Frame::Bulk(Bytes::from(score.to_string())));
+                    frame.push(Frame::Bulk(member.clone()));
+                }
+            }
+            WriteOp::SortedSetRemove { key, members } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"zrem")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+                for member in members {
+                    frame.push(Frame::Bulk(member.clone()));
+                }
+            }
+            WriteOp::AuditedRead {
+                key,
+                command,
+                client,
+                user,
+                at,
+            } => {
+                frame.push(Frame::Bulk(Bytes::from_static(b"read")));
+                frame.push(Frame::Bulk(Bytes::from(key.clone())));
+                frame.push(Frame::Bulk(Bytes::from_static(command.as_bytes())));
+                frame.push(Frame::Bulk(Bytes::from(client.to_string())));
+                match user {
+                    Some(user) => frame.push(Frame::Bulk(Bytes::from(user.clone()))),
+                    None => frame.push(Frame::Null),
+                }
+                frame.push(Frame::Integer(unix_millis(*at) as i64));
+            }
+        }
+        Frame::Array(frame)
+    }
+}
+
+pub(crate) fn unix_millis(when: SystemTime) -> u64 {
+    when.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+/// Number of records kept in the backlog by default
+pub(crate) const DEFAULT_BACKLOG: usize = 16 * 1024;
+
+/// Records queued for a consumer. A consumer falling further behind is disconnected, it resumes
+/// from the backlog.
+const CONSUMER_CAPACITY: usize = 16 * 1024;
+
+#[derive(Debug)]
+pub(crate) struct Pipeline {
+    tx: mpsc::UnboundedSender<WriteRecord>,
+
+    /// Sequence number of the next write. Every write, including expirations, is associated with
+    /// a unique and increasing identifier.
+    next_seq: Mutex<u64>,
+
+    dispatched: Arc<Mutex<Dispatched>>,
+}
+
+/// State of the dispatcher, consumers are registered and the backlog is read under the same lock
+/// the dispatcher holds while forwarding a record, so a consumer starting from the backlog never
+/// misses or sees twice a record.
+#[derive(Debug)]
+struct Dispatched {
+    consumers: Vec<mpsc::Sender<WriteRecord>>,
+
+    /// Latest dispatched records, oldest first
+    backlog: VecDeque<WriteRecord>,
+
+    /// Maximum number of records in `backlog`
+    backlog_capacity: usize,
+
+    /// Sequence number of the latest record evicted from the backlog, `0` if none
+    evicted: u64,
+}
+
+/// Error returned when a consumer asks for records which were evicted from the backlog
+#[derive(Debug)]
+pub(crate) struct Evicted {
+    /// Oldest sequence number which can still be requested
+    pub(crate) oldest: u64,
+}
+
+impl Pipeline {
+    /// Create the pipeline and spawn its dispatcher task. The task stops once the pipeline is
+    /// dropped.
+    pub(crate) fn new() -> Pipeline {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let dispatched = Arc::new(Mutex::new(Dispatched {
+            consumers: vec![],
+            backlog: VecDeque::new(),
+            backlog_capacity: DEFAULT_BACKLOG,
+            evicted: 0,
+        }));
+
+        tokio::spawn(dispatch(rx, dispatched.clone()));
+        Pipeline {
+            tx,
+            // sequence numbers start at 1, `0` means nothing was written yet
+            next_seq: Mutex::new(1),
+            dispatched,
+        }
+    }
+
+    /// Record a write and return its sequence number. Must be called while holding the lock of
+    /// the written key's shard, which is what keeps the writes to a key in order.
+    pub(crate) fn commit(&self, op: WriteOp) -> u64 {
+        // the record is sent under the same lock the sequence number is allocated with, so
+        // records of different shards are still sent in sequence order
+        let mut next_seq = self.next_seq.lock().unwrap();
+        let seq = *next_seq;
+        *next_seq += 1;
+
+        // Sending only fails once the dispatcher is gone, which happens during shutdown
+        let _ = self.tx.send(WriteRecord { seq, op });
+        seq
+    }
+
+    /// Continue the sequence of a previous process whose latest write was `seq`. Its records are
+    /// gone, consumers asking for them are told they were evicted.
+    pub(crate) fn resume_after(&self, seq: u64) {
+        let mut next_seq = self.next_seq.lock().unwrap();
+        *next_seq = seq + 1;
+        self.dispatched.lock().unwrap().evicted = seq;
+    }
+
+    /// Sequence number of the latest write, `0` if nothing was written yet
+    pub(crate) fn last_seq(&self) -> u64 {
+        *self.next_seq.lock().unwrap() - 1
+    }
+
+    /// Set how many of the latest records are kept for consumers starting in the past
+    pub(crate) fn set_backlog_capacity(&self, capacity: usize) {
+        let mut dispatched = self.dispatched.lock().unwrap();
+        dispatched.backlog_capacity = capacity;
+        dispatched.truncate_backlog();
+    }
+
+    /// Register a consumer starting at sequence number `from`, `0` starts from the oldest record
+    /// of the backlog.
+    ///
+    /// A write never waits on a consumer: one with `CONSUMER_CAPACITY` records queued is dropped,
+    /// its receiver then yields `None`. Dropping the receiver unregisters it.
+    ///
+    /// Returns the records of the backlog starting at `from`, the receiver then yields the
+    /// following ones. Records already dispatched but older than `from` may still be received
+    /// and must be skipped by the caller. Fails if records from `from` on were evicted.
+    pub(crate) fn subscribe_from(
+        &self,
+        from: u64,
+    ) -> Result<(Vec<WriteRecord>, mpsc::Receiver<WriteRecord>), Evicted> {
+        let mut dispatched = self.dispatched.lock().unwrap();
+        let from = if from == 0 { dispatched.evicted + 1 } else { from };
+
+        if from <= dispatched.evicted {
+            return Err(Evicted {
+                oldest: dispatched.evicted + 1,
+            });
+        }
+
+        let backlog = dispatched
+            .backlog
+            .iter()
+            .filter(|record| record.seq >= from)
+            .cloned()
+            .collect();
+
+        let (tx, rx) = mpsc::channel(CONSUMER_CAPACITY);
+        dispatched.consumers.push(tx);
+        Ok((backlog, rx))
+    }
+}
+
+impl Dispatched {
+    fn truncate_backlog(&mut self) {
+        while self.backlog.len() > self.backlog_capacity {
+            if let Some(record) = self.backlog.pop_front() {
+                self.evicted = record.seq;
+            }
+        }
+    }
+}
+
+/// Routine executed by the dispatcher task
+async fn dispatch(mut rx: mpsc::UnboundedReceiver<WriteRecord>, dispatched: Arc<Mutex<Dispatched>>) {
+    while let Some(record) = rx.recv().await {
+        let mut dispatched = dispatched.lock().unwrap();
+        // forward the record, dropping the consumers which went away or fell behind
+        dispatched
+            .consumers
+            .retain(|consumer| consumer.try_send(record.clone()).is_ok());
+
+        dispatched.backlog.push_back(record);
+        dispatched.truncate_backlog();
+    }
+}
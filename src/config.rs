@@ -0,0 +1,512 @@
+//! Settings which can be read and changed at runtime with `CONFIG GET` and `CONFIG SET`.
+//!
+//! The settings are shared by the listener, the connection handlers and the key space through
+//! `Db`. Each component reads the current value when it needs it, so a change applies from the
+//! next accepted connection, background task run, and so on.
+
+use crate::commit::DEFAULT_BACKLOG;
+use crate::glob;
+use crate::keyspace_events::KeyspaceEvents;
+use crate::rate_limit::ClientKey;
+use crate::server::PubSubOverflow;
+use crate::slowlog;
+use crate::socket::DEFAULT_KEEPALIVE;
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// Default maximum number of connections served at once
+pub(crate) const DEFAULT_MAX_CLIENTS: usize = 250;
+
+/// Default number of messages a pub/sub channel holds for its slowest subscriber
+pub(crate) const DEFAULT_PUBSUB_CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Settings {
+    /// Maximum number of connections served at once
+    pub(crate) max_clients: usize,
+
+    /// Memory usage of the key space past which the commands adding data are refused, no limit
+    /// if 0
+    pub(crate) maxmemory: usize,
+
+    /// Keyspace notifications sent, see `keyspace_events`
+    pub(crate) notify_keyspace_events: KeyspaceEvents,
+
+    /// Whether the background task periodically shrinks over-allocated maps
+    pub(crate) active_defrag: bool,
+
+    /// Number of the latest writes kept for `SYNCFROM` consumers
+    pub(crate) write_backlog: usize,
+
+    /// Glob patterns of the keys whose reads are audited, no read is audited when empty
+    pub(crate) audit_read_patterns: Vec<String>,
+
+    /// Only one audited read out of this many is reported
+    pub(crate) audit_read_sample: u64,
+
+    /// Whether the `DEBUG` subcommands altering the server behavior are allowed
+    pub(crate) enable_debug_command: bool,
+
+    /// Size past which the oldest bytes of a byte log are dropped
+    pub(crate) blog_max_bytes: usize,
+
+    /// Whether the latency of each command is recorded
+    pub(crate) latency_tracking: bool,
+
+    /// Significant digits of the recorded latencies, from 1 to 5
+    pub(crate) latency_tracking_precision: u8,
+
+    /// Percentiles reported by `INFO latencystats`
+    pub(crate) latency_tracking_info_percentiles: Vec<f64>,
+
+    /// Commands taking at least this many milliseconds are kept for `LATENCY HISTORY`, 0 turns
+    /// the latency monitor off
+    pub(crate) latency_monitor_threshold: u64,
+
+    /// Commands taking at least this many microseconds are kept in the slow log, none if negative
+    pub(crate) slowlog_log_slower_than: i64,
+
+    /// Number of commands kept in the slow log
+    pub(crate) slowlog_max_len: usize,
+
+    /// Whether every frame received and sent by the connections is logged
+    pub(crate) protocol_dump: bool,
+
+    /// Number of messages a pub/sub channel created from now on holds for its slowest subscriber
+    pub(crate) pubsub_channel_capacity: usize,
+
+    /// What happens to the subscribers whose channel is full
+    pub(crate) pubsub_overflow: PubSubOverflow,
+
+    /// Password new connections must send with `AUTH` before running commands, none if `None`
+    pub(crate) requirepass: Option<String>,
+
+    /// Commands per second accepted from all the clients together, unlimited if 0
+    pub(crate) ratelimit_global: u64,
+
+    /// Commands per second accepted from each client, unlimited if 0
+    pub(crate) ratelimit_client: u64,
+
+    /// How clients are told apart by `ratelimit_client`
+    pub(crate) ratelimit_client_by: ClientKey,
+
+    /// Whether `TCP_NODELAY` is set on the accepted connections
+    pub(crate) tcp_nodelay: bool,
+
+    /// Idle time before keepalive probes are sent on the accepted connections, none if `None`
+    pub(crate) tcp_keepalive: Option<Duration>,
+
+    /// Time a command may run before being answered `-TIMEOUT`, no limit if `None`
+    pub(crate) command_timeout: Option<Duration>,
+
+    /// Directory `SAVE` writes the RDB file to
+    pub(crate) dir: PathBuf,
+
+    /// Name of the RDB file written by `SAVE`
+    pub(crate) dbfilename: String,
+
+    /// Address the server accepts connections on, set once it starts
+    pub(crate) listen_addr: Option<SocketAddr>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            max_clients: DEFAULT_MAX_CLIENTS,
+            maxmemory: 0,
+            notify_keyspace_events: KeyspaceEvents::default(),
+            active_defrag: false,
+            write_backlog: DEFAULT_BACKLOG,
+            audit_read_patterns: Vec::new(),
+            audit_read_sample: 1,
+            enable_debug_command: false,
+            blog_max_bytes: 1024 * 1024,
+            latency_tracking: true,
+            latency_tracking_precision: 2,
+            latency_tracking_info_percentiles: vec![50.0, 99.0, 99.9],
+            latency_monitor_threshold: 0,
+            slowlog_log_slower_than: slowlog::DEFAULT_SLOWER_THAN,
+            slowlog_max_len: slowlog::DEFAULT_MAX_LEN,
+            protocol_dump: false,
+            pubsub_channel_capacity: DEFAULT_PUBSUB_CHANNEL_CAPACITY,
+            pubsub_overflow: PubSubOverflow::Drop,
+            requirepass: None,
+            ratelimit_global: 0,
+            ratelimit_client: 0,
+            ratelimit_client_by: ClientKey::Addr,
+            tcp_nodelay: true,
+            tcp_keepalive: Some(DEFAULT_KEEPALIVE),
+            command_timeout: None,
+            dir: PathBuf::from("."),
+            dbfilename: "dump.rdb".to_string(),
+            listen_addr: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Config {
+    settings: RwLock<Settings>,
+}
+
+/// A setting exposed to `CONFIG`
+struct Param {
+    name: &'static str,
+    get: fn(&Settings) -> String,
+    set: fn(&mut Settings, &str) -> Result<(), String>,
+}
+
+/// Reason given when setting a parameter fixed at startup
+const IMMUTABLE: &str = "can't be changed at runtime";
+
+/// Settings exposed to `CONFIG`, in the order `CONFIG GET` lists them
+const PARAMS: &[Param] = &[
+    Param {
+        name: "bind",
+        get: |settings| settings.listen_addr.map(|addr| addr.ip().to_string()).unwrap_or_default(),
+        set: |_, _| Err(IMMUTABLE.to_string()),
+    },
+    Param {
+        name: "port",
+        get: |settings| settings.listen_addr.map(|addr| addr.port().to_string()).unwrap_or_default(),
+        set: |_, _| Err(IMMUTABLE.to_string()),
+    },
+    Param {
+        name: "requirepass",
+        get: |settings| settings.requirepass.clone().unwrap_or_default(),
+        set: |settings, value| {
+            settings.requirepass = Some(value.to_string()).filter(|password| !password.is_empty());
+            Ok(())
+        },
+    },
+    Param {
+        name: "maxclients",
+        get: |settings| settings.max_clients.to_string(),
+        set: |settings, value| {
+            settings.max_clients = parse_number(value)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "maxmemory",
+        get: |settings| settings.maxmemory.to_string(),
+        set: |settings, value| {
+            settings.maxmemory = parse_memory(value)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "notify-keyspace-events",
+        get: |settings| settings.notify_keyspace_events.flags(),
+        set: |settings, value| {
+            settings.notify_keyspace_events = KeyspaceEvents::parse(value)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "activedefrag",
+        get: |settings| yes_no(settings.active_defrag),
+        set: |settings, value| {
+            settings.active_defrag = parse_bool(value)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "write-backlog",
+        get: |settings| settings.write_backlog.to_string(),
+        set: |settings, value| {
+            settings.write_backlog = parse_number(value)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "audit-read-patterns",
+        get: |settings| settings.audit_read_patterns.join(" "),
+        set: |settings, value| {
+            settings.audit_read_patterns = value.split_whitespace().map(str::to_string).collect();
+            Ok(())
+        },
+    },
+    Param {
+        name: "audit-read-sample",
+        get: |settings| settings.audit_read_sample.to_string(),
+        set: |settings, value| match parse_number(value)? {
+            0 => Err("argument must be greater than 0".to_string()),
+            sample => {
+                settings.audit_read_sample = sample as u64;
+                Ok(())
+            }
+        },
+    },
+    Param {
+        name: "enable-debug-command",
+        get: |settings| yes_no(settings.enable_debug_command),
+        // a client allowed to run CONFIG SET could otherwise turn on the commands altering the
+        // server behavior for itself
+        set: |_, _| Err(IMMUTABLE.to_string()),
+    },
+    Param {
+        name: "blog-max-bytes",
+        get: |settings| settings.blog_max_bytes.to_string(),
+        set: |settings, value| {
+            settings.blog_max_bytes = parse_number(value)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "latency-tracking",
+        get: |settings| yes_no(settings.latency_tracking),
+        set: |settings, value| {
+            settings.latency_tracking = parse_bool(value)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "latency-tracking-precision",
+        get: |settings| settings.latency_tracking_precision.to_string(),
+        set: |settings, value| match parse_number(value)? {
+            precision @ 1..=5 => {
+                settings.latency_tracking_precision = precision as u8;
+                Ok(())
+            }
+            _ => Err("argument must be between 1 and 5".to_string()),
+        },
+    },
+    Param {
+        name: "latency-tracking-info-percentiles",
+        get: |settings| {
+            let percentiles: Vec<String> = settings
+                .latency_tracking_info_percentiles
+                .iter()
+                .map(f64::to_string)
+                .collect();
+            percentiles.join(" ")
+        },
+        set: |settings, value| {
+            settings.latency_tracking_info_percentiles = value
+                .split_whitespace()
+                .map(|percentile| match percentile.parse::<f64>() {
+                    Ok(percentile) if (0.0..=100.0).contains(&percentile) => Ok(percentile),
+                    _ => Err("percentiles must be numbers between 0 and 100".to_string()),
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "latency-monitor-threshold",
+        get: |settings| settings.latency_monitor_threshold.to_string(),
+        set: |settings, value| {
+            settings.latency_monitor_threshold = parse_number(value)? as u64;
+            Ok(())
+        },
+    },
+    Param {
+        name: "slowlog-log-slower-than",
+        get: |settings| settings.slowlog_log_slower_than.to_string(),
+        set: |settings, value| {
+            settings.slowlog_log_slower_than = value
+                .parse()
+                .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "slowlog-max-len",
+        get: |settings| settings.slowlog_max_len.to_string(),
+        set: |settings, value| {
+            settings.slowlog_max_len = parse_number(value)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "tcp-nodelay",
+        get: |settings| yes_no(settings.tcp_nodelay),
+        set: |settings, value| {
+            settings.tcp_nodelay = parse_bool(value)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "tcp-keepalive",
+        get: |settings| settings.tcp_keepalive.map_or(0, |idle| idle.as_secs()).to_string(),
+        set: |settings, value| {
+            let secs = parse_number(value)? as u64;
+            settings.tcp_keepalive = Some(Duration::from_secs(secs)).filter(|_| secs > 0);
+            Ok(())
+        },
+    },
+    Param {
+        name: "command-timeout",
+        get: |settings| settings.command_timeout.map_or(0, |timeout| timeout.as_millis()).to_string(),
+        set: |settings, value| {
+            let millis = parse_number(value)? as u64;
+            settings.command_timeout = Some(Duration::from_millis(millis)).filter(|_| millis > 0);
+            Ok(())
+        },
+    },
+    Param {
+        name: "ratelimit-global",
+        get: |settings| settings.ratelimit_global.to_string(),
+        set: |settings, value| {
+            settings.ratelimit_global = parse_number(value)? as u64;
+            Ok(())
+        },
+    },
+    Param {
+        name: "ratelimit-client",
+        get: |settings| settings.ratelimit_client.to_string(),
+        set: |settings, value| {
+            settings.ratelimit_client = parse_number(value)? as u64;
+            Ok(())
+        },
+    },
+    Param {
+        name: "ratelimit-client-by",
+        get: |settings| settings.ratelimit_client_by.name().to_string(),
+        set: |settings, value| {
+            settings.ratelimit_client_by =
+                ClientKey::from_name(value).ok_or_else(|| "argument must be 'addr' or 'user'".to_string())?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "dir",
+        get: |settings| settings.dir.display().to_string(),
+        set: |_, _| Err(IMMUTABLE.to_string()),
+    },
+    Param {
+        name: "dbfilename",
+        get: |settings| settings.dbfilename.clone(),
+        // a path would let clients write files anywhere the server can
+        set: |settings, value| match value {
+            "" => Err("dbfilename can't be empty".to_string()),
+            name if name.contains(std::path::is_separator) => {
+                Err("dbfilename can't be a path, just a filename".to_string())
+            }
+            name => {
+                settings.dbfilename = name.to_string();
+                Ok(())
+            }
+        },
+    },
+    Param {
+        name: "protocol-dump",
+        get: |settings| yes_no(settings.protocol_dump),
+        set: |settings, value| {
+            settings.protocol_dump = parse_bool(value)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "pubsub-channel-capacity",
+        get: |settings| settings.pubsub_channel_capacity.to_string(),
+        set: |settings, value| match parse_number(value)? {
+            0 => Err("argument must be greater than 0".to_string()),
+            capacity => {
+                settings.pubsub_channel_capacity = capacity;
+                Ok(())
+            }
+        },
+    },
+    Param {
+        name: "pubsub-overflow",
+        get: |settings| settings.pubsub_overflow.name().to_string(),
+        set: |settings, value| {
+            settings.pubsub_overflow = PubSubOverflow::from_name(value)
+                .ok_or_else(|| "argument must be 'drop' or 'disconnect'".to_string())?;
+            Ok(())
+        },
+    },
+];
+
+impl Config {
+    /// Snapshot of the current settings
+    pub(crate) fn get(&self) -> Settings {
+        self.settings.read().unwrap().clone()
+    }
+
+    /// Read the current settings without copying them
+    pub(crate) fn read<R>(&self, f: impl FnOnce(&Settings) -> R) -> R {
+        f(&self.settings.read().unwrap())
+    }
+
+    pub(crate) fn update(&self, f: impl FnOnce(&mut Settings)) {
+        f(&mut self.settings.write().unwrap());
+    }
+
+    /// Name and value of the settings matching the glob `pattern`
+    pub(crate) fn params(&self, pattern: &str) -> Vec<(&'static str, String)> {
+        let pattern = pattern.to_lowercase();
+        let settings = self.settings.read().unwrap();
+        PARAMS
+            .iter()
+            .filter(|param| glob::matches(pattern.as_bytes(), param.name.as_bytes()))
+            .map(|param| (param.name, (param.get)(&settings)))
+            .collect()
+    }
+
+    /// Change settings from their textual values. Either every change is applied or none.
+    pub(crate) fn set_params(&self, changes: &[(String, String)]) -> Result<(), String> {
+        let mut settings = self.settings.write().unwrap();
+        let mut updated = settings.clone();
+
+        for (name, value) in changes {
+            let param = PARAMS
+                .iter()
+                .find(|param| param.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| format!("ERR Unknown option or number of arguments for CONFIG SET - '{}'", name))?;
+
+            (param.set)(&mut updated, value).map_err(|reason| {
+                format!(
+                    "ERR Invalid argument '{}' for CONFIG SET '{}' - {}",
+                    value, param.name, reason
+                )
+            })?;
+        }
+
+        *settings = updated;
+        Ok(())
+    }
+}
+
+fn parse_number(value: &str) -> Result<usize, String> {
+    value.parse().map_err(|_| "argument couldn't be parsed into an integer".to_string())
+}
+
+/// Parse a number of bytes, optionally followed by a unit as Redis accepts them: `k`, `m` and `g`
+/// are powers of 1000, `kb`, `mb` and `gb` powers of 1024
+pub(crate) fn parse_memory(value: &str) -> Result<usize, String> {
+    let lowercase = value.to_lowercase();
+    let digits = lowercase.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit = match &lowercase[digits.len()..] {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return Err("argument must be a memory value".to_string()),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|amount| amount.checked_mul(unit))
+        .ok_or_else(|| "argument must be a memory value".to_string())
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match &value.to_lowercase()[..] {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err("argument must be 'yes' or 'no'".to_string()),
+    }
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
@@ -1,22 +1,186 @@
-use crate::frame::{self, Frame};
+use crate::frame::{self, Frame, Limits};
+use crate::proxy_protocol;
 
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
+use std::fmt;
 use std::io::{self, Cursor};
-use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter, ReadBuf, ReadHalf, WriteHalf};
 use tokio::net::TcpStream;
+use tracing::info;
 
 #[derive(Debug)]
 pub struct Connection {
-    stream: BufWriter<TcpStream>,
+    stream: Stream,
     buffer: BytesMut,
+    // scratch buffer frames are encoded into before being written
+    encoded: BytesMut,
+    // limits applied to received frames
+    limits: Limits,
+    // whether requests and replies carry a correlation tag, see `HELLO`
+    tagged: bool,
+    // whether the frames received and sent are logged
+    protocol_dump: bool,
+    // user the client is authenticated as, it can only run `AUTH` until it is set
+    user: Option<String>,
+    // number of frames whose writing started
+    frames_written: u64,
+    // number of error replies among them
+    errors_written: u64,
+    // address of the client behind a load balancer, see `read_proxy_header`
+    proxied_addr: Option<SocketAddr>,
+    // whether the client asked for the connection to be closed, see `QUIT`
+    closing: bool,
 }
 
+enum Stream {
+    Socket(BufWriter<TcpStream>),
+    /// Any other transport, see `Connection::from_stream`
+    Boxed {
+        stream: BufWriter<Box<dyn Io>>,
+        peer_addr: Option<SocketAddr>,
+    },
+    /// The frames written are kept instead of being sent, and nothing is ever read. Used to run
+    /// a command apart from the connection of its client.
+    Capture {
+        peer_addr: Option<SocketAddr>,
+        frames: Vec<Frame>,
+    },
+}
+
+/// The byte streams a connection can be served over
+trait Io: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Io for T {}
+
 impl Connection {
     pub fn new(socket: TcpStream) -> Connection {
+        Connection::with_stream(Stream::Socket(BufWriter::new(socket)))
+    }
+
+    /// A connection over any byte stream, such as a TLS session or a bridged WebSocket, with the
+    /// client at `peer_addr`
+    pub fn from_stream<S>(stream: S, peer_addr: Option<SocketAddr>) -> Connection
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        Connection::with_stream(Stream::Boxed {
+            stream: BufWriter::new(Box::new(stream)),
+            peer_addr,
+        })
+    }
+
+    /// A connection collecting the frames written to it, on behalf of the client at `peer_addr`.
+    /// Reading from it always returns `None`.
+    pub(crate) fn capture(peer_addr: Option<SocketAddr>) -> Connection {
+        Connection::with_stream(Stream::Capture {
+            peer_addr,
+            frames: vec![],
+        })
+    }
+
+    fn with_stream(stream: Stream) -> Connection {
+        // use 4KB read to read, nothing is ever read from a capture
+        let capacity = match stream {
+            Stream::Capture { .. } => 0,
+            _ => 4 * 1024,
+        };
         Connection {
-            stream: BufWriter::new(socket),
-            // use 4KB read to read
-            buffer: BytesMut::with_capacity(4 * 1024),
+            stream,
+            buffer: BytesMut::with_capacity(capacity),
+            encoded: BytesMut::new(),
+            limits: Limits::default(),
+            tagged: false,
+            protocol_dump: false,
+            user: None,
+            frames_written: 0,
+            errors_written: 0,
+            proxied_addr: None,
+            closing: false,
+        }
+    }
+
+    /// The frames written to a connection created with `capture`
+    pub(crate) fn into_captured(self) -> Vec<Frame> {
+        match self.stream {
+            Stream::Capture { frames, .. } => frames,
+            Stream::Socket(_) | Stream::Boxed { .. } => vec![],
+        }
+    }
+
+    pub(crate) fn is_tagged(&self) -> bool {
+        self.tagged
+    }
+
+    /// Switch the connection to tagged requests and replies
+    pub(crate) fn set_tagged(&mut self) {
+        self.tagged = true;
+    }
+
+    /// User the client is authenticated as, `None` until it authenticates
+    pub(crate) fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    pub(crate) fn set_user(&mut self, user: Option<String>) {
+        self.user = user;
+    }
+
+    /// Whether the connection is closed once the current command is answered
+    pub(crate) fn is_closing(&self) -> bool {
+        self.closing
+    }
+
+    pub(crate) fn close(&mut self) {
+        self.closing = true;
+    }
+
+    /// Number of frames whose writing started, including the one being written if any
+    pub(crate) fn frames_written(&self) -> u64 {
+        self.frames_written
+    }
+
+    /// Number of error replies whose writing started
+    pub(crate) fn errors_written(&self) -> u64 {
+        self.errors_written
+    }
+
+    /// Set the limits received frames are checked against. A frame exceeding them makes
+    /// `read_frame` return an error.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Log every frame received and sent from now on, rendered as `redis-cli` does. Inbound frames
+    /// are prefixed with `<<`, outbound ones with `>>`. The events are at the `INFO` level under
+    /// the `redust::protocol` target.
+    pub fn set_protocol_dump(&mut self, enabled: bool) {
+        self.protocol_dump = enabled;
+    }
+
+    /// Read the PROXY protocol header starting the connection, see the `proxy_protocol` module.
+    /// `peer_addr` then returns the address of the client it carries.
+    pub(crate) async fn read_proxy_header(&mut self) -> crate::Result<()> {
+        if let Stream::Socket(stream) = &mut self.stream {
+            // nothing was read yet, the header can be read from the socket itself
+            self.proxied_addr = proxy_protocol::read_header(stream.get_mut()).await?;
+        }
+        Ok(())
+    }
+
+    /// Address of the remote end of the connection, or of the client behind the load balancer
+    /// if the connection started with a PROXY protocol header
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        if let Some(addr) = self.proxied_addr {
+            return Ok(addr);
+        }
+        match &self.stream {
+            Stream::Socket(stream) => stream.get_ref().peer_addr(),
+            Stream::Boxed { peer_addr, .. } | Stream::Capture { peer_addr, .. } => {
+                peer_addr.ok_or_else(|| io::ErrorKind::NotConnected.into())
+            }
         }
     }
 
@@ -34,119 +198,355 @@ impl Connection {
         loop {
             // attempt to parse a frame from the buffered data. If enough data
             // has been buffeded, the frame is returned
-            if let Some(frame) = self.parse_frame()? {
+            if let Some(frame) = parse_frame(&mut self.buffer, &self.limits)? {
+                if self.protocol_dump {
+                    info!(target: "redust::protocol", "<< {}", frame);
+                }
                 return Ok(Some(frame));
             }
 
-            if 0 == self.stream.read_buf(&mut self.buffer).await? {
+            let stream = match self.stream.io() {
+                Some(stream) => stream,
+                None => return Ok(None),
+            };
+            if 0 == stream.read_buf(&mut self.buffer).await? {
                 if self.buffer.is_empty() {
                     return Ok(None);
                 }
-                return Err("connection reset by pear".into());
+                return Err(crate::Error::ConnectionReset);
             }
         }
     }
 
-    /// tries to parse a frame from the buffer. If the buffer contains enough
-    /// data, the frame is returned and the data removed the buffer.
-    /// If not enough data has been bufferded yet, `Ok(None)` is returned. It the
-    /// buffered data does not represent a valid frame, `Err` is returned
-    fn parse_frame(&mut self) -> crate::Result<Option<Frame>> {
-        use frame::Error::Incomplete;
-        // Cursor used to track the current location in the buffer.
-        // Currsor also implements `Buf` from the bytes crate
-        // which provides a number of helpful utilities for working
-        // with bytes
-        let mut buf = Cursor::new(&self.buffer[..]);
-
-        // The first step is to check if enough data has been buffered to parse a single frame.
-        // This tstep is usually must faster than doing a full parse of the frame
-        // and allow us to skip allocating data structures
-        // to hold the frame data unless we know the full frame has been received
-        match Frame::check(&mut buf) {
-            Ok(_) => {
-                // The check function will have advanced the cursor until the end of frame
-                //Since the cursor had position set to zero before Frame::check was called,
-                //we obtain the length of the frame by checking the cursor position
-                let len = buf.position() as usize;
-                buf.set_position(0);
-
-                let frame = Frame::parse(&mut buf)?;
-
-                self.buffer.advance(len);
-                Ok(Some(frame))
+    /// Wait until the client closes the connection, while a command blocks. The requests it
+    /// pipelines in the meantime are buffered for `read_frame`, up to the size of a frame.
+    pub(crate) async fn closed(&mut self) {
+        loop {
+            let stream = match self.stream.io() {
+                Some(stream) if self.buffer.len() < self.limits.max_frame_size => stream,
+                _ => return std::future::pending().await,
+            };
+            // reading is cancel safe, the bytes received stay buffered
+            match stream.read_buf(&mut self.buffer).await {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
             }
-            Err(Incomplete) => Ok(None),
-            Err(e) => Err(e.into()),
         }
     }
 
+    /// Write a single `Frame` to the underlying stream and flush it.
     pub async fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
-        match frame {
-            Frame::Array(val) => {
-                // array type
-                self.stream.write_u8(b'*').await?;
-                // size of array
-                self.write_decimal(val.len() as u64).await?;
-
-                // Iterate and encode each entry in the array
-                for entry in &**val {
-                    self.write_value(entry).await?;
-                }
-            },
-            _ => self.write_value(frame).await?,
+        self.write_frame_unflushed(frame).await?;
+        self.flush().await
+    }
 
+    /// Encode `frame` into the write buffer without flushing it to the socket.
+    ///
+    /// Use this when writing several frames in a row (pipelined replies, pub/sub fan-out) and
+    /// call `flush` once the batch is complete. The frame may still reach the socket early if the
+    /// write buffer fills up.
+    pub async fn write_frame_unflushed(&mut self, frame: &Frame) -> io::Result<()> {
+        if self.protocol_dump {
+            info!(target: "redust::protocol", ">> {}", frame);
+        }
+        self.frames_written += 1;
+        self.errors_written += matches!(frame, Frame::Error(_)) as u64;
+        if let Stream::Capture { frames, .. } = &mut self.stream {
+            frames.push(frame.clone());
+            return Ok(());
+        }
+        // encoding is shared with `RespCodec` and `Frame::to_bytes`, the scratch buffer is reused
+        // across calls to avoid an allocation per frame
+        self.encoded.clear();
+        frame.encode(&mut self.encoded);
+        match self.stream.io() {
+            Some(stream) => stream.write_all(&self.encoded).await,
+            None => Ok(()),
         }
-        // Ensure the encoded frame is written to the socket. The calls above
-        // are to the buffered stream and writes. Calling `flush` writes the remaining content of
-        // the buffer to the scoket
-        self.stream.flush().await
     }
 
-    /// Write a frame literal to the stream
-    async fn write_value(&mut self, frame: &Frame) -> io::Result<()> {
-        match frame {
-            Frame::Simple(val) => {
-                self.stream.write_u8(b'+').await?;
-                self.stream.write_all(val.as_bytes()).await?;
-                self.stream.write_all(b"\r\n").await?;
-            }
-            Frame::Error(val) => {
-                self.stream.write_u8(b'-').await?;
-                self.stream.write_all(val.as_bytes()).await?;
-                self.stream.write_all(b"\r\n").await?;
-            }
-            Frame::Integer(val) => {
-                self.stream.write_u8(b':').await?;
-                self.write_decimal(*val).await?;
-            }
-            Frame::Null => {
-                self.stream.write_all(b"$-1\r\n").await?;
+    /// Flush any buffered frames to the socket.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        // The calls to `write_frame_unflushed` are to the buffered stream and writes. Calling
+        // `flush` writes the remaining content of the buffer to the scoket
+        match self.stream.io() {
+            Some(stream) => stream.flush().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Split the connection into a half reading frames and a half writing them, which can be
+    /// moved to different tasks. `OwnedReadHalf::reunite` puts them back together.
+    ///
+    /// Frames are still buffered by the writing half until it is flushed.
+    pub fn into_split(mut self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        let peer_addr = self.peer_addr().ok();
+        // a capture keeps the frames it was written, in the writing half while split
+        let captured = match &mut self.stream {
+            Stream::Capture { frames, .. } => Some(std::mem::take(frames)),
+            Stream::Socket(_) | Stream::Boxed { .. } => None,
+        };
+        let (read, write) = tokio::io::split(self.stream);
+
+        let reader = OwnedReadHalf {
+            stream: read,
+            buffer: self.buffer,
+            limits: self.limits,
+            protocol_dump: self.protocol_dump,
+            peer_addr,
+            state: SessionState {
+                tagged: self.tagged,
+                user: self.user,
+                proxied_addr: self.proxied_addr,
+                closing: self.closing,
+            },
+        };
+        let writer = OwnedWriteHalf {
+            stream: write,
+            captured,
+            encoded: self.encoded,
+            protocol_dump: self.protocol_dump,
+            frames_written: self.frames_written,
+            errors_written: self.errors_written,
+            peer_addr,
+        };
+        (reader, writer)
+    }
+}
+
+/// The half of a `Connection` reading frames, see `Connection::into_split`
+#[derive(Debug)]
+pub struct OwnedReadHalf {
+    stream: ReadHalf<Stream>,
+    buffer: BytesMut,
+    limits: Limits,
+    protocol_dump: bool,
+    peer_addr: Option<SocketAddr>,
+    /// The rest of the connection, restored by `reunite`
+    state: SessionState,
+}
+
+/// The half of a `Connection` writing frames, see `Connection::into_split`
+#[derive(Debug)]
+pub struct OwnedWriteHalf {
+    stream: WriteHalf<Stream>,
+    /// The frames written to a capture, `None` for any other connection
+    captured: Option<Vec<Frame>>,
+    encoded: BytesMut,
+    protocol_dump: bool,
+    frames_written: u64,
+    errors_written: u64,
+    peer_addr: Option<SocketAddr>,
+}
+
+/// What a connection knows of its client, kept aside while it is split
+#[derive(Debug)]
+struct SessionState {
+    tagged: bool,
+    user: Option<String>,
+    proxied_addr: Option<SocketAddr>,
+    closing: bool,
+}
+
+impl OwnedReadHalf {
+    /// Read a single frame, see `Connection::read_frame`
+    pub async fn read_frame(&mut self) -> crate::Result<Option<Frame>> {
+        loop {
+            if let Some(frame) = parse_frame(&mut self.buffer, &self.limits)? {
+                if self.protocol_dump {
+                    info!(target: "redust::protocol", "<< {}", frame);
+                }
+                return Ok(Some(frame));
             }
-            Frame::Bulk(val) => {
-                let len = val.len();
-                self.stream.write_u8(b'$').await?;
-                self.write_decimal(len as u64).await?;
-                self.stream.write_all(val).await?;
-                self.stream.write_all(b"\r\n").await?;
+
+            if 0 == self.stream.read_buf(&mut self.buffer).await? {
+                if self.buffer.is_empty() {
+                    return Ok(None);
+                }
+                return Err(crate::Error::ConnectionReset);
             }
-            Frame::Array(_val) => unreachable!(),
         }
+    }
 
-        Ok(())
+    /// Address of the client, see `Connection::peer_addr`
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.peer_addr.ok_or_else(|| io::ErrorKind::NotConnected.into())
     }
 
-    async fn write_decimal(&mut self, val: u64) -> io::Result<()> {
-        use std::io::Write;
+    /// Put the connection back together.
+    ///
+    /// # Panics
+    ///
+    /// If `writer` doesn't come from the same connection.
+    pub fn reunite(self, writer: OwnedWriteHalf) -> Connection {
+        let mut stream = self.stream.unsplit(writer.stream);
+        if let (Stream::Capture { frames, .. }, Some(captured)) = (&mut stream, writer.captured) {
+            *frames = captured;
+        }
 
-        // convert the vlaue into a string
-        let mut buf = [0u8, 20];
-        let mut buf = Cursor::new(&mut buf[..]);
-        write!(&mut buf, "{}", val)?;
+        Connection {
+            stream,
+            buffer: self.buffer,
+            encoded: writer.encoded,
+            limits: self.limits,
+            tagged: self.state.tagged,
+            protocol_dump: writer.protocol_dump,
+            user: self.state.user,
+            frames_written: writer.frames_written,
+            errors_written: writer.errors_written,
+            proxied_addr: self.state.proxied_addr,
+            closing: self.state.closing,
+        }
+    }
+}
 
-        let pos = buf.position() as usize;
-        self.stream.write_all(&buf.get_ref()[..pos]).await?;
-        self.stream.write_all(b"\r\n").await?;
+impl OwnedWriteHalf {
+    /// Write a single frame and flush it, see `Connection::write_frame`
+    pub async fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        self.write_frame_unflushed(frame).await?;
+        self.flush().await
+    }
+
+    /// Encode `frame` into the write buffer without flushing it, see
+    /// `Connection::write_frame_unflushed`
+    pub async fn write_frame_unflushed(&mut self, frame: &Frame) -> io::Result<()> {
+        if self.protocol_dump {
+            info!(target: "redust::protocol", ">> {}", frame);
+        }
+        self.frames_written += 1;
+        self.errors_written += matches!(frame, Frame::Error(_)) as u64;
+        if let Some(frames) = &mut self.captured {
+            frames.push(frame.clone());
+            return Ok(());
+        }
+        self.encoded.clear();
+        frame.encode(&mut self.encoded);
+        self.stream.write_all(&self.encoded).await
+    }
+
+    /// Write a frame already encoded with `Frame::encode`, without flushing it. Lets a frame sent to
+    /// many connections be encoded once.
+    pub(crate) async fn write_encoded_unflushed(&mut self, encoded: &Bytes) -> crate::Result<()> {
+        // decoded again only when it has to be seen
+        if self.protocol_dump || self.captured.is_some() {
+            let frame = Frame::parse(&mut Cursor::new(&encoded[..]))?;
+            return Ok(self.write_frame_unflushed(&frame).await?);
+        }
+        self.frames_written += 1;
+        self.stream.write_all(encoded).await?;
         Ok(())
     }
+
+    /// Flush any buffered frames to the socket
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush().await
+    }
+
+    /// Address of the client, see `Connection::peer_addr`
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.peer_addr.ok_or_else(|| io::ErrorKind::NotConnected.into())
+    }
+}
+
+impl Stream {
+    /// The buffered stream to read from and write to, `None` for a capture
+    fn io(&mut self) -> Option<&mut (dyn Io + '_)> {
+        match self {
+            Stream::Socket(stream) => Some(stream),
+            Stream::Boxed { stream, .. } => Some(stream),
+            Stream::Capture { .. } => None,
+        }
+    }
+}
+
+/// Reading a capture always reaches the end of the stream, writing to it discards the bytes. The
+/// frames are captured before they are encoded.
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut().io() {
+            Some(stream) => Pin::new(stream).poll_read(cx, buf),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut().io() {
+            Some(stream) => Pin::new(stream).poll_write(cx, buf),
+            None => Poll::Ready(Ok(buf.len())),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut().io() {
+            Some(stream) => Pin::new(stream).poll_flush(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut().io() {
+            Some(stream) => Pin::new(stream).poll_shutdown(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+impl fmt::Debug for Stream {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stream::Socket(stream) => fmt.debug_tuple("Socket").field(stream).finish(),
+            Stream::Boxed { peer_addr, .. } => {
+                fmt.debug_struct("Boxed").field("peer_addr", peer_addr).finish_non_exhaustive()
+            }
+            Stream::Capture { peer_addr, frames } => fmt
+                .debug_struct("Capture")
+                .field("peer_addr", peer_addr)
+                .field("frames", frames)
+                .finish(),
+        }
+    }
+}
+
+/// tries to parse a frame from the buffer. If the buffer contains enough
+/// data, the frame is returned and the data removed the buffer.
+/// If not enough data has been bufferded yet, `Ok(None)` is returned. It the
+/// buffered data does not represent a valid frame, `Err` is returned
+fn parse_frame(buffer: &mut BytesMut, limits: &Limits) -> crate::Result<Option<Frame>> {
+    use frame::Error::Incomplete;
+    // Cursor used to track the current location in the buffer.
+    // Currsor also implements `Buf` from the bytes crate
+    // which provides a number of helpful utilities for working
+    // with bytes
+    let mut buf = Cursor::new(&buffer[..]);
+
+    // The first step is to check if enough data has been buffered to parse a single frame.
+    // This tstep is usually must faster than doing a full parse of the frame
+    // and allow us to skip allocating data structures
+    // to hold the frame data unless we know the full frame has been received
+    match Frame::check_with_limits(&mut buf, limits) {
+        Ok(_) => {
+            // The check function will have advanced the cursor until the end of frame
+            //Since the cursor had position set to zero before Frame::check was called,
+            //we obtain the length of the frame by checking the cursor position
+            let len = buf.position() as usize;
+            buf.set_position(0);
+
+            let frame = Frame::parse(&mut buf)?;
+
+            buffer.advance(len);
+            Ok(Some(frame))
+        }
+        // a frame can't grow past the size limit, even while incomplete
+        Err(Incomplete) if buffer.len() > limits.max_frame_size => {
+            Err(crate::Error::Protocol("protocol error; frame too large".into()))
+        }
+        Err(Incomplete) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
 }
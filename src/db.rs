@@ -1,9 +1,60 @@
-use tokio::sync::{broadcast, Notify};
+use tokio::sync::{broadcast, mpsc, oneshot, Notify};
 use tokio::time::{self, Duration, Instant};
+use tracing::{debug, warn};
 
-use bytes::Bytes;
-use std::collections::{BTreeMap, HashMap};
+use bytes::{BufMut, Bytes, BytesMut};
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::BuildHasher;
+use std::mem;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::acl::{self, Acl, Category, User};
+use crate::backoff::random_unit;
+use crate::bitmap;
+use crate::blog::{Blog, Rotated};
+use crate::command_stats::CommandStats;
+use crate::commit::{Evicted, Pipeline, WriteOp, WriteRecord};
+use crate::config::{Config, Settings};
+use crate::geo;
+use crate::glob;
+use crate::hash::Hash;
+use crate::keyspace_events::{self, Class};
+use crate::latency::LatencyStats;
+use crate::rate_limit::{ClientKey, RateLimits};
+use crate::rdb;
+use crate::sentinel::Sentinel;
+use crate::server::PubSubOverflow;
+use crate::shard_lock::ShardLock;
+use crate::slowlog::SlowLog;
+use crate::snapshot::{self, Record, Stored};
+use crate::sorted_set::SortedSet;
+use crate::{CommandHandler, Frame, ValueTransform};
+
+/// How often the background task checks whether the key space needs to be defragmented
+const DEFRAG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Maps are only rebuilt once they hold at least this many unused slots, shrinking small maps
+/// isn't worth the rehash.
+const DEFRAG_MIN_WASTED_SLOTS: usize = 1024;
+
+/// Prefix of the channel the token of a lock is published on when the lock expires before being
+/// released, followed by the key of the lock
+pub(crate) const LOCK_EXPIRED_PREFIX: &str = "__lock__:expired:";
+
+/// How often the background task drops the pub/sub channels nobody is subscribed to anymore
+const CHANNEL_SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Longest time to live a key can be given, about a century. Commands reject longer ones, the
+/// embedded API clamps them: added to the clock under the lock of a shard, they could overflow.
+pub(crate) const MAX_TTL: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+/// Number of partitions of the key space by default
+pub(crate) const DEFAULT_SHARDS: usize = 16;
 
 /// Server state shared across all connections
 ///
@@ -14,19 +65,68 @@ pub(crate) struct Db {
 
 #[derive(Debug)]
 struct Shared {
+    /// The key space, partitioned by the hash of the keys so commands on different keys don't
+    /// contend on the same lock
+    shards: Box<[ShardLock<Shard>]>,
+
+    /// Hashes the keys to pick their shard
+    hasher: RandomState,
+
     state: Mutex<State>,
     background_task: Notify,
+
+    /// Settings changed at runtime with `CONFIG SET`
+    config: Config,
+
+    /// Users and their permissions, see `ACL`
+    acl: Acl,
+
+    /// Token buckets of the rate limits
+    rate_limits: RateLimits,
+
+    /// Number of defragmentation passes which rebuilt at least one map
+    defrag_runs: AtomicU64,
+
+    /// Ordered stream of the writes applied to the key space
+    commits: Pipeline,
+
+    /// Client connection counters, maintained by the server
+    clients: ClientStats,
+
+    /// Applied to the values before they are stored, and reversed when they are read
+    transform: Option<Arc<dyn ValueTransform>>,
+
+    /// Number of reads which matched an audited pattern, used for sampling
+    audited_reads: AtomicU64,
+
+    /// Whether the background task removes the expired keys, see `DEBUG SET-ACTIVE-EXPIRE`
+    active_expire: AtomicBool,
+
+    /// Latency histograms of the commands executed
+    latency: LatencyStats,
+
+    /// Number of calls of the commands executed
+    command_stats: CommandStats,
+
+    /// The commands which reached `slowlog-log-slower-than`
+    slowlog: SlowLog,
+
+    /// Memory usage of the key space, the sum of the `used_bytes` of the shards
+    used_bytes: Arc<AtomicUsize>,
+
+    /// Failover state when running as a sentinel
+    sentinel: Mutex<Option<Arc<Sentinel>>>,
+
+    /// Commands registered by the application, by lowercased name
+    command_handlers: Mutex<HashMap<String, Arc<dyn CommandHandler>>>,
 }
 
-#[derive(Debug)]
-struct State {
+/// A partition of the key space, along with the expirations of its keys
+#[derive(Debug, Default)]
+struct Shard {
     /// key - value data
     entries: HashMap<String, Entry>,
 
-    /// The pub/sub key-space. Redis use a **separate** key space for key-value and pub/sub.
-    /// `mini-redis` handles this by using a separate `HashMap`
-    pub_sub: HashMap<String, broadcast::Sender<Bytes>>,
-
     /// Tracks key ttls
     ///
     /// A BTreemaps is used to maintain expiratyions sorted by when they expire. This allow the
@@ -37,51 +137,2209 @@ struct State {
     /// identifier (`u64`) is used to break these ties.
     expirations: BTreeMap<(Instant, u64), String>,
 
-    // Identifier to use for the next expiration. Each expiration is associated with a unique
-    // identifier
-    next_id: u64,
+    /// Sum of the estimated memory usage of the entries, see `Entry::usage`
+    used_bytes: usize,
+
+    /// Memory usage of the whole key space, shared by the shards, see `Shared::used_bytes`
+    total_bytes: Arc<AtomicUsize>,
+
+    /// Clients blocked on the lists of the shard, by key, in the order they blocked
+    waiters: HashMap<String, VecDeque<Waiter>>,
+}
+
+/// A client blocked by `BLPOP` or `BRPOP`, queued on each of its keys. It is handed a single
+/// element through `slot`, emptied by whichever key serves it first.
+#[derive(Debug, Clone)]
+struct Waiter {
+    end: ListEnd,
+    slot: Arc<Mutex<Option<Handoff>>>,
+}
+
+/// Sends a blocked client the key and the element, as stored, it was served
+type Handoff = oneshot::Sender<(String, Bytes)>;
+
+/// Unqueues a blocked client from its keys once it stops waiting, see `Db::blocking_list_pop`
+struct Blocked<'a> {
+    shared: &'a Shared,
+    waiter: Waiter,
+    /// The keys the client is queued on
+    keys: Vec<String>,
+    /// Receives the key and the element as stored
+    rx: oneshot::Receiver<(String, Bytes)>,
+}
+
+#[derive(Debug)]
+struct State {
+    /// The pub/sub key-space. Redis use a **separate** key space for key-value and pub/sub.
+    /// `mini-redis` handles this by using a separate `HashMap`
+    pub_sub: HashMap<String, Channel>,
+
+    /// Delivery statistics of the channels dropped from `pub_sub`, so the server totals never go
+    /// backward
+    retired_channels: PubSubTotals,
 
     shutdown: bool,
 }
 
 #[derive(Debug)]
 struct Entry {
-    // Uniquely identifier this entry
+    // Uniquely identifies this version of the entry, the sequence number of its latest write
     id: u64,
 
-    data: Bytes,
+    value: Value,
 
     expires_at: Option<Instant>,
+
+    /// Dead-letter list of a pending reservation. When the entry expires before the reservation
+    /// is confirmed, its data is pushed to this list. The token of an expired lock is published
+    /// on this channel instead, see `LOCK_EXPIRED_PREFIX`.
+    reservation: Option<String>,
+
+    /// Wall clock time of the latest write to this entry
+    modified: SystemTime,
+}
+
+/// The value held by a key
+#[derive(Debug)]
+enum Value {
+    /// As stored, after the value transform if any
+    String(Bytes),
+    Blog(Blog),
+    /// Elements as stored, head first
+    List(VecDeque<Bytes>),
+    /// Values as stored, by field
+    Hash(Hash),
+    /// Members by score
+    SortedSet(SortedSet),
+}
+
+/// An expired reservation: its key, its dead-letter list and its data
+type DeadLetter = (String, String, Bytes);
+
+/// End of a list pushed to or popped from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ListEnd {
+    Head,
+    Tail,
+}
+
+/// Side of the pivot an element is inserted on, see `LINSERT`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Side {
+    Before,
+    After,
+}
+
+/// Members a `ZADD` writes, see its `NX` and `XX` options
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MemberUpdate {
+    /// New and existing members
+    All,
+    /// New members only
+    Add,
+    /// Existing members only
+    Change,
+}
+
+/// A pub/sub channel
+#[derive(Debug)]
+struct Channel {
+    tx: broadcast::Sender<Published>,
+    /// Start of the `message` frames of the channel, encoded once: the array header, `message`
+    /// and the channel name
+    frame_prefix: Bytes,
+    stats: Arc<ChannelStats>,
+}
+
+/// A message published on a channel, shared by all its subscribers
+#[derive(Debug, Clone)]
+pub(crate) struct Published {
+    pub(crate) payload: Bytes,
+    /// The `message` frame sent to the subscribed connections, encoded once for all of them
+    pub(crate) frame: Bytes,
+}
+
+/// Delivery counters of a pub/sub channel.
+///
+/// Shared with the subscriber streams, which count what they actually received.
+#[derive(Debug, Default)]
+pub(crate) struct ChannelStats {
+    /// Messages published on the channel
+    pub(crate) published: AtomicU64,
+    /// Messages received by subscribers, a message counts once per subscriber
+    pub(crate) delivered: AtomicU64,
+    /// Messages lagging subscribers missed because the channel was full
+    pub(crate) dropped: AtomicU64,
+    /// Subscribers disconnected because the channel was full, see `PubSubOverflow::Disconnect`
+    pub(crate) disconnected: AtomicU64,
+    /// Number of times a client subscribed to the channel
+    pub(crate) subscribes: AtomicU64,
+    /// Number of times a client left the channel
+    pub(crate) unsubscribes: AtomicU64,
+}
+
+/// Upper bounds of the time-to-expiry histogram buckets of `TTLSTATS`, the last bucket holds
+/// everything above
+const TTL_BUCKETS: &[(&str, Duration)] = &[
+    ("1s", Duration::from_secs(1)),
+    ("10s", Duration::from_secs(10)),
+    ("1m", Duration::from_secs(60)),
+    ("10m", Duration::from_secs(10 * 60)),
+    ("1h", Duration::from_secs(60 * 60)),
+    ("1d", Duration::from_secs(24 * 60 * 60)),
+];
+
+/// Distribution of the expirations across the key space, reported by `TTLSTATS`
+#[derive(Debug)]
+pub(crate) struct TtlStats {
+    pub(crate) keys_with_ttl: usize,
+    pub(crate) keys_without_ttl: usize,
+    /// Number of keys expiring within each bound, the last bucket is unbounded
+    pub(crate) histogram: Vec<(&'static str, u64)>,
+    /// Number of keys expiring during each upcoming minute
+    pub(crate) forecast: Vec<u64>,
+}
+
+/// Internals of an entry, reported by `DEBUG OBJECT`
+#[derive(Debug)]
+pub(crate) struct ObjectInfo {
+    pub(crate) kind: &'static str,
+    /// Size of the value as stored
+    pub(crate) size: usize,
+    pub(crate) ttl: Option<Duration>,
+    pub(crate) version: u64,
+}
+
+/// Client connection counters, reported by `INFO clients` and `INFO stats`
+#[derive(Debug, Default)]
+pub(crate) struct ClientStats {
+    /// Connections currently being served
+    pub(crate) connected: AtomicUsize,
+    /// Notified when a connection is closed or the maximum number of clients changes
+    pub(crate) slot_freed: Notify,
+    /// Connections accepted since the server started
+    pub(crate) total_connections: AtomicU64,
+    /// Connections turned away because `max_clients` was reached
+    pub(crate) rejected_connections: AtomicU64,
+    /// Commands rejected by the rate limits
+    pub(crate) rate_limited_commands: AtomicU64,
+    /// Commands cancelled for exceeding `command-timeout`
+    pub(crate) timed_out_commands: AtomicU64,
+}
+
+/// A value along with the metadata of the write which produced it
+#[derive(Debug)]
+pub(crate) struct EntryInfo {
+    pub(crate) data: Bytes,
+    /// Time left before the entry expires
+    pub(crate) ttl: Option<Duration>,
+    /// Identifier of the write, strictly increasing across writes
+    pub(crate) version: u64,
+    pub(crate) modified: SystemTime,
 }
 
 impl Db {
-    pub(crate) fn new() -> Db {
+    /// Create a key space split in `shards` partitions, the values are stored as transformed by
+    /// `transform` if any
+    pub(crate) fn new(shards: usize, transform: Option<Arc<dyn ValueTransform>>) -> Db {
+        let used_bytes = Arc::new(AtomicUsize::new(0));
         let shared = Arc::new(Shared {
+            shards: (0..shards.max(1))
+                .map(|_| {
+                    ShardLock::new(Shard {
+                        total_bytes: used_bytes.clone(),
+                        ..Shard::default()
+                    })
+                })
+                .collect(),
+            hasher: RandomState::new(),
             state: Mutex::new(State {
-                entries: HashMap::new(),
                 pub_sub: HashMap::new(),
-                expirations: BTreeMap::new(),
-                next_id: 0,
+                retired_channels: PubSubTotals::default(),
                 shutdown: false,
             }),
             background_task: Notify::new(),
+            config: Config::default(),
+            acl: Acl::default(),
+            rate_limits: RateLimits::default(),
+            defrag_runs: AtomicU64::new(0),
+            commits: Pipeline::new(),
+            clients: ClientStats::default(),
+            transform,
+            audited_reads: AtomicU64::new(0),
+            active_expire: AtomicBool::new(true),
+            latency: LatencyStats::default(),
+            command_stats: CommandStats::default(),
+            slowlog: SlowLog::default(),
+            used_bytes,
+            sentinel: Mutex::new(None),
+            command_handlers: Mutex::new(HashMap::new()),
         });
 
         tokio::spawn(purge_expired_tasks(shared.clone()));
         Db { shared }
     }
 
-    pub(crate) fn get(&self, key: &str) -> Option<Bytes> {
-        let state = self.shared.state.lock().unwrap();
-        state.entries.get(key).map(|entry| entry.data.clone())
+    /// Snapshot of the current settings
+    pub(crate) fn config(&self) -> Settings {
+        self.shared.config.get()
+    }
+
+    /// Change settings in place, the components depending on them are notified
+    pub(crate) fn configure(&self, f: impl FnOnce(&mut Settings)) {
+        self.shared.config.update(f);
+        self.config_changed();
+    }
+
+    /// Name and value of the settings matching `pattern`, see `CONFIG GET`
+    pub(crate) fn config_params(&self, pattern: &str) -> Vec<(&'static str, String)> {
+        self.shared.config.params(pattern)
+    }
+
+    /// Change settings from their textual values, see `CONFIG SET`
+    pub(crate) fn set_config_params(&self, changes: &[(String, String)]) -> Result<(), String> {
+        self.shared.config.set_params(changes)?;
+        self.config_changed();
+        Ok(())
+    }
+
+    fn config_changed(&self) {
+        let settings = self.config();
+        self.shared
+            .commits
+            .set_backlog_capacity(settings.write_backlog);
+        // the listener may be waiting for a free slot, and the background task for its next
+        // defragmentation run
+        self.shared.clients.slot_freed.notify_one();
+        self.shared.background_task.notify_one();
+    }
+
+    /// Whether the connections log the frames they receive and send
+    pub(crate) fn protocol_dump(&self) -> bool {
+        self.shared.config.read(|settings| settings.protocol_dump)
+    }
+
+    /// User new connections are authenticated as, `None` if they must authenticate with `AUTH`
+    pub(crate) fn default_login(&self) -> Option<String> {
+        let requirepass = self.requirepass();
+        self.shared
+            .acl
+            .default_login(requirepass.as_deref())
+            .then(|| acl::DEFAULT_USER.to_string())
+    }
+
+    /// Whether `username` can authenticate with `password`
+    pub(crate) fn authenticate(&self, username: &str, password: &str) -> bool {
+        let requirepass = self.requirepass();
+        self.shared
+            .acl
+            .authenticate(username, password, requirepass.as_deref())
+    }
+
+    /// Whether `username` may run `command`, of `category`, on `keys`. The error to reply otherwise.
+    /// `keys` is `None` when they aren't known.
+    pub(crate) fn check_permission(
+        &self,
+        username: &str,
+        command: &str,
+        category: Category,
+        keys: Option<&[&str]>,
+    ) -> Result<(), String> {
+        self.shared.acl.check(username, command, category, keys)
+    }
+
+    /// Create or change `username`, see `ACL SETUSER`
+    pub(crate) fn acl_set_user(&self, username: &str, rules: &[String]) -> Result<(), String> {
+        self.shared.acl.set_user(username, rules)
+    }
+
+    pub(crate) fn acl_user(&self, username: &str) -> Option<User> {
+        let requirepass = self.requirepass();
+        self.shared.acl.get_user(username, requirepass.as_deref())
+    }
+
+    /// Rules of every user, see `ACL LIST`
+    pub(crate) fn acl_list(&self) -> Vec<String> {
+        let requirepass = self.requirepass();
+        self.shared.acl.list(requirepass.as_deref())
+    }
+
+    fn requirepass(&self) -> Option<String> {
+        self.shared
+            .config
+            .read(|settings| settings.requirepass.clone())
+    }
+
+    /// Time a command may run, see `CommandTimeout`
+    pub(crate) fn command_timeout(&self) -> Option<Duration> {
+        self.shared.config.read(|settings| settings.command_timeout)
+    }
+
+    /// Failover state, `None` unless running as a sentinel
+    pub(crate) fn sentinel(&self) -> Option<Arc<Sentinel>> {
+        self.shared.sentinel.lock().unwrap().clone()
+    }
+
+    pub(crate) fn set_sentinel(&self, sentinel: Arc<Sentinel>) {
+        *self.shared.sentinel.lock().unwrap() = Some(sentinel);
+    }
+
+    /// Handler of the command registered by the application as `name`, lowercased
+    pub(crate) fn command_handler(&self, name: &str) -> Option<Arc<dyn CommandHandler>> {
+        self.shared
+            .command_handlers
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+    }
+
+    pub(crate) fn set_command_handlers(&self, handlers: HashMap<String, Arc<dyn CommandHandler>>) {
+        *self.shared.command_handlers.lock().unwrap() = handlers;
+    }
+
+    /// Path of the RDB file written by `SAVE`
+    pub(crate) fn rdb_path(&self) -> PathBuf {
+        self.shared
+            .config
+            .read(|settings| settings.dir.join(&settings.dbfilename))
+    }
+
+    /// Global and per client rates, and how clients are told apart, see `RateLimit`
+    pub(crate) fn rate_limit_settings(&self) -> (u64, u64, ClientKey) {
+        self.shared.config.read(|settings| {
+            (
+                settings.ratelimit_global,
+                settings.ratelimit_client,
+                settings.ratelimit_client_by,
+            )
+        })
+    }
+
+    /// Take a token for a command of `client`, returns `false` if the command must be rejected
+    pub(crate) fn acquire_rate_limit(
+        &self,
+        global_rate: u64,
+        client_rate: u64,
+        client: Option<&str>,
+    ) -> bool {
+        let allowed = self
+            .shared
+            .rate_limits
+            .acquire(global_rate, client_rate, client);
+        if !allowed {
+            self.shared
+                .clients
+                .rate_limited_commands
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        allowed
+    }
+
+    /// Acquire and release every lock of the key space in turn, blocks while a lock is held
+    pub(crate) fn check_locks(&self) {
+        for shard in self.shared.shards.iter() {
+            drop(shard.write());
+        }
+        drop(self.shared.state.lock().unwrap());
+    }
+
+    pub(crate) fn clients(&self) -> &ClientStats {
+        &self.shared.clients
+    }
+
+    pub(crate) fn latency(&self) -> &LatencyStats {
+        &self.shared.latency
+    }
+
+    pub(crate) fn command_stats(&self) -> &CommandStats {
+        &self.shared.command_stats
+    }
+
+    /// Reset the statistics reported by `INFO`, see `CONFIG RESETSTAT`. The connection counts
+    /// are kept, they identify the connections.
+    pub(crate) fn reset_stats(&self) {
+        self.shared.command_stats.reset();
+        self.shared.latency.reset(&[]);
+        let clients = &self.shared.clients;
+        clients.rejected_connections.store(0, Ordering::Relaxed);
+        clients.rate_limited_commands.store(0, Ordering::Relaxed);
+        clients.timed_out_commands.store(0, Ordering::Relaxed);
+    }
+
+    /// Record that the command `id` took `elapsed`, unless latency tracking is off. It is also
+    /// kept for `LATENCY HISTORY` when the latency monitor threshold is reached.
+    pub(crate) fn record_latency(&self, id: usize, elapsed: Duration) {
+        let (tracking, threshold) = self.shared.config.read(|settings| {
+            let tracking = settings
+                .latency_tracking
+                .then_some(settings.latency_tracking_precision);
+            (tracking, settings.latency_monitor_threshold)
+        });
+        if let Some(precision) = tracking {
+            self.shared.latency.record(id, elapsed, precision);
+        }
+        if threshold > 0 && elapsed >= Duration::from_millis(threshold) {
+            self.shared.latency.record_spike(id, elapsed);
+        }
+    }
+
+    /// Refuse the commands adding data once the key space uses more than `maxmemory`. Nothing is
+    /// evicted to make room, as with the `noeviction` policy of Redis.
+    pub(crate) fn check_maxmemory(&self) -> Result<(), String> {
+        let maxmemory = self.shared.config.read(|settings| settings.maxmemory);
+        if maxmemory > 0 && self.shared.used_bytes.load(Ordering::Relaxed) > maxmemory {
+            return Err("OOM command not allowed when used memory > 'maxmemory'.".to_string());
+        }
+        Ok(())
+    }
+
+    /// Time from which commands are kept in the slow log, `None` if it is off
+    pub(crate) fn slowlog_threshold(&self) -> Option<Duration> {
+        let usec = self
+            .shared
+            .config
+            .read(|settings| settings.slowlog_log_slower_than);
+        (usec >= 0).then(|| Duration::from_micros(usec as u64))
+    }
+
+    /// Keep a command which took `elapsed` in the slow log, see `SlowLog::push`
+    pub(crate) fn log_slow(
+        &self,
+        args: Vec<String>,
+        elapsed: Duration,
+        client: Option<SocketAddr>,
+        user: Option<String>,
+    ) {
+        let max_len = self.shared.config.read(|settings| settings.slowlog_max_len);
+        self.shared
+            .slowlog
+            .push(args, elapsed, client, user, max_len);
+    }
+
+    pub(crate) fn slowlog(&self) -> &SlowLog {
+        &self.shared.slowlog
+    }
+
+    /// Snapshot of the memory layout of the key space
+    pub(crate) fn memory_stats(&self) -> MemoryStats {
+        let (mut keys, mut keys_capacity, mut expires, mut dataset_bytes) = (0, 0, 0, 0);
+        for shard in self.shared.shards.iter() {
+            let shard = shard.read();
+            keys += shard.entries.len();
+            keys_capacity += shard.entries.capacity();
+            expires += shard.expirations.len();
+            dataset_bytes += shard.used_bytes;
+        }
+
+        let state = self.shared.state.lock().unwrap();
+        MemoryStats {
+            shards: self.shared.shards.len(),
+            keys,
+            keys_capacity,
+            expires,
+            dataset_bytes,
+            pubsub_channels: state.pub_sub.len(),
+            pubsub_channels_capacity: state.pub_sub.capacity(),
+            maxmemory: self.shared.config.read(|settings| settings.maxmemory),
+            active_defrag: self.config().active_defrag,
+            defrag_runs: self.shared.defrag_runs.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> crate::Result<Option<Bytes>> {
+        let shard = self.shared.shard(key).read();
+        let stored = match shard.live_entry(key, Instant::now()) {
+            Some(entry) => Some(entry.value.as_string()?.clone()),
+            None => None,
+        };
+        drop(shard);
+
+        stored.map(|data| self.decode(key, data)).transpose()
+    }
+
+    /// Get the value associated with a key along with its ttl, version and modification time.
+    /// Everything is read under one lock so the metadata always matches the value.
+    pub(crate) fn get_entry(&self, key: &str) -> crate::Result<Option<EntryInfo>> {
+        let shard = self.shared.shard(key).read();
+        let now = Instant::now();
+        let entry = match shard.live_entry(key, now) {
+            Some(entry) => Some(EntryInfo {
+                data: entry.value.as_string()?.clone(),
+                ttl: entry
+                    .expires_at
+                    .map(|when| when.saturating_duration_since(now)),
+                version: entry.id,
+                modified: entry.modified,
+            }),
+            None => None,
+        };
+        drop(shard);
+
+        match entry {
+            Some(mut entry) => {
+                entry.data = self.decode(key, entry.data)?;
+                Ok(Some(entry))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Estimated number of bytes held by `key` and its value, `None` if the key doesn't exist
+    pub(crate) fn memory_usage(&self, key: &str) -> Option<usize> {
+        let shard = self.shared.shard(key).read();
+        shard
+            .live_entry(key, Instant::now())
+            .map(|entry| entry.usage(key))
+    }
+
+    /// Describe the entry of `key` without reading its value
+    pub(crate) fn object_info(&self, key: &str) -> Option<ObjectInfo> {
+        let shard = self.shared.shard(key).read();
+        let now = Instant::now();
+        shard.live_entry(key, now).map(|entry| ObjectInfo {
+            kind: entry.value.kind(),
+            size: entry.value.len(),
+            ttl: entry
+                .expires_at
+                .map(|when| when.saturating_duration_since(now)),
+            version: entry.id,
+        })
+    }
+
+    /// Histogram of the time left before the keys expire, and the number of keys expiring in each
+    /// of the next `minutes`. Walks the whole expiration index.
+    pub(crate) fn ttl_stats(&self, minutes: usize) -> TtlStats {
+        let now = Instant::now();
+
+        let (mut keys_with_ttl, mut keys_without_ttl) = (0, 0);
+        let mut histogram = vec![0; TTL_BUCKETS.len() + 1];
+        let mut forecast = vec![0; minutes];
+        for shard in self.shared.shards.iter() {
+            let shard = shard.read();
+            keys_with_ttl += shard.expirations.len();
+            keys_without_ttl += shard.entries.len() - shard.expirations.len();
+
+            for &(when, _) in shard.expirations.keys() {
+                let ttl = when.saturating_duration_since(now);
+
+                let bucket = TTL_BUCKETS
+                    .iter()
+                    .position(|(_, bound)| ttl < *bound)
+                    .unwrap_or(TTL_BUCKETS.len());
+                histogram[bucket] += 1;
+
+                if let Some(count) = forecast.get_mut((ttl.as_secs() / 60) as usize) {
+                    *count += 1;
+                }
+            }
+        }
+
+        let labels = TTL_BUCKETS
+            .iter()
+            .map(|(label, _)| *label)
+            .chain(Some("inf"));
+        TtlStats {
+            keys_with_ttl,
+            keys_without_ttl,
+            histogram: labels.zip(histogram).collect(),
+            forecast,
+        }
+    }
+
+    /// Stop or resume the background removal of expired keys
+    pub(crate) fn set_active_expire(&self, enabled: bool) {
+        self.shared.active_expire.store(enabled, Ordering::Relaxed);
+        self.shared.background_task.notify_one();
+    }
+
+    /// Set the value associated with a key along with an optional expiration Duration
+    pub(crate) fn set(
+        &self,
+        key: String,
+        value: Bytes,
+        expire: Option<Duration>,
+    ) -> crate::Result<()> {
+        let value = self.encode(&key, value)?;
+        self.insert(key.clone(), value, expire, None);
+        self.shared
+            .notify_keyspace_event(Class::String, "set", &key);
+        Ok(())
+    }
+
+    /// Set `key` to `value`, dropping its expiration. Returns the value it replaced.
+    pub(crate) fn get_set(&self, key: String, value: Bytes) -> crate::Result<Option<Bytes>> {
+        self.swap(key, None, value)
+    }
+
+    /// Set `key` to `new` only if its current value is `expected`, all under the lock of the key.
+    /// Returns the current value, the swap happened if it is equal to `expected`. A missing key
+    /// never matches.
+    pub(crate) fn compare_and_swap(
+        &self,
+        key: String,
+        expected: &[u8],
+        new: Bytes,
+    ) -> crate::Result<Option<Bytes>> {
+        self.swap(key, Some(expected), new)
+    }
+
+    /// Replace the value of `key`, if `expected` is given only when it matches the current value
+    fn swap(
+        &self,
+        key: String,
+        expected: Option<&[u8]>,
+        value: Bytes,
+    ) -> crate::Result<Option<Bytes>> {
+        let value = self.encode(&key, value)?;
+        let mut shard = self.shared.shard(&key).write();
+
+        let current = match shard.live_entry(&key, Instant::now()) {
+            Some(entry) => Some(self.decode(&key, entry.value.as_string()?.clone())?),
+            None => None,
+        };
+        let matches = match expected {
+            Some(expected) => current.as_deref() == Some(expected),
+            None => true,
+        };
+        if !matches {
+            return Ok(current);
+        }
+
+        let notify = self.insert_locked(&mut shard, key.clone(), value, None, None);
+        drop(shard);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+        self.shared
+            .notify_keyspace_event(Class::String, "set", &key);
+        Ok(current)
+    }
+
+    /// Set `key` to `value` unless it exists and `replace` isn't set, as `RESTORE` does. Returns
+    /// whether the key was set.
+    pub(crate) fn restore(
+        &self,
+        key: String,
+        value: Bytes,
+        expire: Option<Duration>,
+        replace: bool,
+    ) -> crate::Result<bool> {
+        let value = self.encode(&key, value)?;
+        let mut shard = self.shared.shard(&key).write();
+        if !replace && shard.live_entry(&key, Instant::now()).is_some() {
+            return Ok(false);
+        }
+
+        let notify = self.insert_locked(&mut shard, key.clone(), value, expire, None);
+        drop(shard);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+        self.shared
+            .notify_keyspace_event(Class::Generic, "restore", &key);
+        Ok(true)
+    }
+
+    /// Set the bit at `offset` of the string value of `key`, growing the value with zeros as needed.
+    /// The expiration of the key is kept. Returns the previous value of the bit.
+    ///
+    /// The value is changed in place, unless it is stored transformed and must be decoded first.
+    pub(crate) fn set_bit(&self, key: String, offset: u64, bit: bool) -> crate::Result<bool> {
+        if self.shared.transform.is_some() {
+            return self.set_bit_transformed(key, offset, bit);
+        }
+
+        let mut guard = self.shared.shard(&key).write();
+        let shard = &mut *guard;
+        if shard.live_entry(&key, Instant::now()).is_none() {
+            let mut data = BytesMut::new();
+            let prev = bitmap::set(&mut data, offset, bit);
+            self.insert_locked(shard, key.clone(), data.freeze(), None, None);
+            drop(guard);
+            self.shared
+                .notify_keyspace_event(Class::String, "setbit", &key);
+            return Ok(prev);
+        }
+
+        let entry = shard.entries.get_mut(&key).unwrap();
+        let before = entry.usage(&key);
+        let stored = match &mut entry.value {
+            Value::String(stored) => stored,
+            _ => return Err(crate::Error::WrongType),
+        };
+        // only copied if still shared, with a reader or the backlog of the commit pipeline
+        let mut data = mem::take(stored)
+            .try_into_mut()
+            .unwrap_or_else(|shared| BytesMut::from(&shared[..]));
+        let prev = bitmap::set(&mut data, offset, bit);
+        *stored = data.freeze();
+        entry.modified = SystemTime::now();
+
+        let after = entry.usage(&key);
+        shard.account(after, before);
+        let seq = self.shared.commits.commit(WriteOp::SetBit {
+            key: key.clone(),
+            offset,
+            bit,
+        });
+        shard.set_id(&key, seq);
+        drop(guard);
+
+        self.shared
+            .notify_keyspace_event(Class::String, "setbit", &key);
+        Ok(prev)
+    }
+
+    /// `set_bit` on a value stored transformed, which is decoded, changed and encoded again
+    fn set_bit_transformed(&self, key: String, offset: u64, bit: bool) -> crate::Result<bool> {
+        let mut shard = self.shared.shard(&key).write();
+        let now = Instant::now();
+
+        let (mut data, expire, reservation) = match shard.live_entry(&key, now) {
+            Some(entry) => (
+                BytesMut::from(&self.decode(&key, entry.value.as_string()?.clone())?[..]),
+                entry
+                    .expires_at
+                    .map(|when| when.saturating_duration_since(now)),
+                entry.reservation.clone(),
+            ),
+            None => (BytesMut::new(), None, None),
+        };
+        let prev = bitmap::set(&mut data, offset, bit);
+
+        let value = self.encode(&key, data.freeze())?;
+        let notify = self.insert_locked(&mut shard, key.clone(), value, expire, reservation);
+        drop(shard);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+        self.shared
+            .notify_keyspace_event(Class::String, "setbit", &key);
+        Ok(prev)
+    }
+
+    /// Value of the bit at `offset` of the string value of `key`. Missing keys and bits past the
+    /// end of the value are `0`.
+    pub(crate) fn get_bit(&self, key: &str, offset: u64) -> crate::Result<bool> {
+        Ok(match self.get(key)? {
+            Some(data) => bitmap::get(&data, offset),
+            None => false,
+        })
+    }
+
+    /// Number of bits set in the string value of `key`, see `bitmap::count`
+    pub(crate) fn bit_count(
+        &self,
+        key: &str,
+        range: Option<(i64, i64, bitmap::Unit)>,
+    ) -> crate::Result<u64> {
+        Ok(match self.get(key)? {
+            Some(data) => bitmap::count(&data, range),
+            None => 0,
+        })
+    }
+
+    /// Remove `keys`, returns the number of keys which existed. A key given twice counts once.
+    pub(crate) fn delete(&self, keys: &[String]) -> usize {
+        let mut removed = 0;
+        for key in keys {
+            let mut shard = self.shared.shard(key).write();
+            if shard.live_entry(key, Instant::now()).is_none() {
+                continue;
+            }
+
+            if let Some(entry) = shard.remove_entry(key) {
+                if let Some(when) = entry.expires_at {
+                    shard.expirations.remove(&(when, entry.id));
+                }
+            }
+            self.shared
+                .commits
+                .commit(WriteOp::Delete { key: key.clone() });
+            drop(shard);
+
+            self.shared
+                .notify_keyspace_event(Class::Generic, "del", key);
+            removed += 1;
+        }
+        removed
+    }
+
+    /// Number of `keys` which exist, a key given twice counts twice
+    pub(crate) fn exists(&self, keys: &[String]) -> usize {
+        let now = Instant::now();
+        keys.iter()
+            .filter(|key| self.shared.shard(key).read().live_entry(key, now).is_some())
+            .count()
+    }
+
+    /// Expire `key` after `ttl`, replacing its current expiration. A zero `ttl` removes the key
+    /// right away. Returns `false` if the key doesn't exist.
+    pub(crate) fn expire(&self, key: &str, ttl: Duration) -> bool {
+        if ttl == Duration::from_secs(0) {
+            return self.delete(&[key.to_string()]) == 1;
+        }
+
+        let ttl = ttl.min(MAX_TTL);
+        let mut shard = self.shared.shard(key).write();
+        let now = Instant::now();
+        if shard.live_entry(key, now).is_none() {
+            return false;
+        }
+
+        // removed and inserted back so the memory accounting sees the expiration slot
+        let mut entry = shard.remove_entry(key).unwrap();
+        if let Some(when) = entry.expires_at {
+            shard.expirations.remove(&(when, entry.id));
+        }
+        let modified = SystemTime::now();
+        let seq = self.shared.commits.commit(WriteOp::SetExpiration {
+            key: key.to_string(),
+            expires_at: modified + ttl,
+        });
+
+        // a new expiration is a write like any other, it makes a new version
+        let when = now + ttl;
+        let notify = shard.next_expiration().map(|e| e > when).unwrap_or(true);
+        shard.expirations.insert((when, seq), key.to_string());
+        entry.id = seq;
+        entry.expires_at = Some(when);
+        entry.modified = modified;
+        shard.insert_entry(key.to_string(), entry);
+        drop(shard);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+        self.shared
+            .notify_keyspace_event(Class::Generic, "expire", key);
+        true
+    }
+
+    /// Add `delta` to the integer held by `key`, a missing key counts as `0`. The expiration of the
+    /// key is kept. Returns the new value.
+    ///
+    /// Counters are signed 64 bit integers, as in Redis.
+    pub(crate) fn incr_by(&self, key: String, delta: i64) -> crate::Result<i64> {
+        let mut shard = self.shared.shard(&key).write();
+        let now = Instant::now();
+
+        let (current, expire, reservation) = match shard.live_entry(&key, now) {
+            Some(entry) => {
+                let data = self.decode(&key, entry.value.as_string()?.clone())?;
+                let current = std::str::from_utf8(&data)
+                    .ok()
+                    .and_then(|text| text.parse::<i64>().ok())
+                    .ok_or("ERR value is not an integer or out of range")?;
+                (
+                    current,
+                    entry
+                        .expires_at
+                        .map(|when| when.saturating_duration_since(now)),
+                    entry.reservation.clone(),
+                )
+            }
+            None => (0, None, None),
+        };
+        let value = current
+            .checked_add(delta)
+            .ok_or("ERR increment or decrement would overflow")?;
+
+        let stored = self.encode(&key, Bytes::from(value.to_string()))?;
+        let notify = self.insert_locked(&mut shard, key.clone(), stored, expire, reservation);
+        drop(shard);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+        self.shared
+            .notify_keyspace_event(Class::String, "incrby", &key);
+        Ok(value)
+    }
+
+    /// Keys matching the glob `pattern`, see `glob::matches`. Walks the whole key space.
+    pub(crate) fn keys(&self, pattern: &str) -> Vec<String> {
+        let now = Instant::now();
+        let mut keys = vec![];
+        for shard in self.shared.shards.iter() {
+            let shard = shard.read();
+            keys.extend(
+                shard
+                    .entries
+                    .iter()
+                    .filter(|(key, entry)| {
+                        !entry.is_expired(now) && glob::matches(pattern.as_bytes(), key.as_bytes())
+                    })
+                    .map(|(key, _)| key.clone()),
+            );
+        }
+        keys
+    }
+
+    /// Reserve a key for `ttl`. If the reservation is not confirmed before the key expires, `value`
+    /// is pushed to the `dead_letter` list.
+    pub(crate) fn reserve(
+        &self,
+        key: String,
+        value: Bytes,
+        ttl: Duration,
+        dead_letter: String,
+    ) -> crate::Result<()> {
+        let value = self.encode(&key, value)?;
+        self.insert(key.clone(), value, Some(ttl), Some(dead_letter));
+        self.shared
+            .notify_keyspace_event(Class::String, "reserve", &key);
+        Ok(())
+    }
+
+    /// Record the read of `key` by `client` in the commit pipeline if the key matches one of the
+    /// audited patterns, `SYNCFROM` consumers receive it along with the writes. Only one read out
+    /// of `audit-read-sample` is recorded.
+    pub(crate) fn audit_read(
+        &self,
+        command: &'static str,
+        key: &str,
+        client: SocketAddr,
+        user: Option<&str>,
+    ) {
+        let sample = self.shared.config.read(|settings| {
+            let audited = settings
+                .audit_read_patterns
+                .iter()
+                .any(|pattern| glob::matches(pattern.as_bytes(), key.as_bytes()));
+            audited.then_some(settings.audit_read_sample)
+        });
+
+        let sample = match sample {
+            Some(sample) => sample,
+            None => return,
+        };
+        if !self
+            .shared
+            .audited_reads
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(sample)
+        {
+            return;
+        }
+
+        self.shared.commits.commit(WriteOp::AuditedRead {
+            key: key.to_string(),
+            command,
+            client,
+            user: user.map(str::to_string),
+            at: SystemTime::now(),
+        });
+    }
+
+    /// Append `chunk` to the byte log of `key`, created if missing. Returns the offset following
+    /// the appended bytes.
+    pub(crate) fn blog_append(&self, key: &str, chunk: Bytes) -> crate::Result<u64> {
+        // the chunks couldn't be read back by offset once transformed one by one
+        if self.shared.transform.is_some() {
+            return Err(
+                "ERR byte logs are not available when the values are stored transformed".into(),
+            );
+        }
+
+        let max_size = self.shared.config.read(|settings| settings.blog_max_bytes);
+        let mut shard = self.shared.shard(key).write();
+        let modified = SystemTime::now();
+
+        // an expired entry the background task didn't remove yet is replaced, not appended to
+        let now = Instant::now();
+        let expired = shard
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.is_expired(now));
+        if expired {
+            if let Some(entry) = shard.remove_entry(key) {
+                if let Some(when) = entry.expires_at {
+                    shard.expirations.remove(&(when, entry.id));
+                }
+            }
+            self.shared.commits.commit(WriteOp::Expire {
+                key: key.to_string(),
+            });
+        }
+        let before = shard.entries.get(key).map_or(0, |entry| entry.usage(key));
+
+        // identified by the append, like any write
+        let entry = shard
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry {
+                id: 0,
+                value: Value::Blog(Blog::default()),
+                expires_at: None,
+                reservation: None,
+                modified,
+            });
+
+        let end = match &mut entry.value {
+            Value::Blog(blog) => blog.append(&chunk, max_size),
+            _ => return Err(crate::Error::WrongType),
+        };
+        entry.modified = modified;
+
+        let after = entry.usage(key);
+        shard.account(after, before);
+        let seq = self.shared.commits.commit(WriteOp::BlogAppend {
+            key: key.to_string(),
+            chunk,
+        });
+        shard.set_id(key, seq);
+        drop(shard);
+
+        if expired {
+            self.shared
+                .notify_keyspace_event(Class::Expired, "expired", key);
+        }
+        self.shared
+            .notify_keyspace_event(Class::Blog, "blog.append", key);
+        Ok(end)
+    }
+
+    /// Read up to `len` bytes from the byte log of `key`, starting at `offset`
+    pub(crate) fn blog_read(
+        &self,
+        key: &str,
+        offset: u64,
+        len: usize,
+    ) -> crate::Result<Option<Bytes>> {
+        if self.shared.transform.is_some() {
+            return Err(
+                "ERR byte logs are not available when the values are stored transformed".into(),
+            );
+        }
+        let shard = self.shared.shard(key).read();
+        let blog = match shard
+            .live_entry(key, Instant::now())
+            .map(|entry| &entry.value)
+        {
+            Some(Value::Blog(blog)) => blog,
+            Some(_) => return Err(crate::Error::WrongType),
+            None => return Ok(None),
+        };
+
+        match blog.read(offset, len) {
+            Ok(data) => Ok(Some(data)),
+            Err(Rotated { oldest }) => Err(format!(
+                "ERR offset {} was rotated out of the log, oldest available is {}",
+                offset, oldest
+            )
+            .into()),
+        }
+    }
+
+    /// Push `values` one after the other at `end` of the list `key`, created if missing. Returns
+    /// the length of the list.
+    pub(crate) fn list_push(
+        &self,
+        key: &str,
+        values: Vec<Bytes>,
+        end: ListEnd,
+    ) -> crate::Result<usize> {
+        self.shared.list_push(key, values, end)
+    }
+
+    /// Remove up to `count` elements from `end` of the list `key`, removing the key once the list
+    /// is empty. Returns `None` if the key doesn't exist.
+    pub(crate) fn list_pop(
+        &self,
+        key: &str,
+        count: usize,
+        end: ListEnd,
+    ) -> crate::Result<Option<Vec<Bytes>>> {
+        let event = match end {
+            ListEnd::Head => "lpop",
+            ListEnd::Tail => "rpop",
+        };
+        let popped = self.shared.write_list(key, event, false, |elements| {
+            let count = count.min(elements.len());
+            let popped: Vec<Bytes> = match end {
+                ListEnd::Head => elements.drain(..count).collect(),
+                ListEnd::Tail => elements.drain(elements.len() - count..).rev().collect(),
+            };
+            let op = (count > 0).then(|| WriteOp::ListPop {
+                key: key.to_string(),
+                count: count as u64,
+                end,
+            });
+            Ok((popped, op))
+        })?;
+
+        match popped {
+            Some(popped) => popped
+                .into_iter()
+                .map(|data| self.decode(key, data))
+                .collect::<crate::Result<_>>()
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Pop an element from the first of `keys` holding a list, as `BLPOP` and `BRPOP` do. If
+    /// none does, waits until an element is pushed to one of them or `timeout` elapses, `None`
+    /// waiting forever. The clients blocked on a key are handed one element each, in the order they
+    /// blocked. Returns the key along with the element.
+    pub(crate) async fn blocking_list_pop(
+        &self,
+        keys: &[String],
+        end: ListEnd,
+        timeout: Option<Duration>,
+    ) -> crate::Result<Option<(String, Bytes)>> {
+        let (tx, rx) = oneshot::channel();
+        let mut blocked = Blocked {
+            shared: &self.shared,
+            waiter: Waiter {
+                end,
+                slot: Arc::new(Mutex::new(Some(tx))),
+            },
+            keys: vec![],
+            rx,
+        };
+
+        for key in keys {
+            // queued before looking at the list, so an element pushed in between isn't missed
+            self.shared.block_on(key, blocked.waiter.clone());
+            blocked.keys.push(key.clone());
+            if let Some(stored) = self.pop_for(key, &blocked.waiter)? {
+                return self
+                    .decode(key, stored)
+                    .map(|element| Some((key.clone(), element)));
+            }
+            if blocked.is_served() {
+                break;
+            }
+        }
+
+        let received = match timeout {
+            Some(timeout) => time::timeout(timeout, &mut blocked.rx).await.ok(),
+            None => Some((&mut blocked.rx).await),
+        };
+        let (key, stored) = match received {
+            Some(Ok(received)) => received,
+            // timed out, unless an element was handed over in the meantime
+            _ if blocked.cancel() => return Ok(None),
+            _ => match blocked.rx.try_recv() {
+                Ok(received) => received,
+                Err(_) => return Ok(None),
+            },
+        };
+        self.decode(&key, stored)
+            .map(|element| Some((key, element)))
+    }
+
+    /// Pop an element from `end` of the list `key` for the blocked client `waiter`, unless it was
+    /// handed one already
+    fn pop_for(&self, key: &str, waiter: &Waiter) -> crate::Result<Option<Bytes>> {
+        let event = match waiter.end {
+            ListEnd::Head => "lpop",
+            ListEnd::Tail => "rpop",
+        };
+        let popped = self.shared.write_list(key, event, false, |elements| {
+            let mut slot = waiter.slot.lock().unwrap();
+            if slot.is_none() {
+                return Ok((None, None));
+            }
+            let element = match waiter.end {
+                ListEnd::Head => elements.pop_front(),
+                ListEnd::Tail => elements.pop_back(),
+            };
+            if element.is_none() {
+                return Ok((None, None));
+            }
+
+            slot.take();
+            let op = WriteOp::ListPop {
+                key: key.to_string(),
+                count: 1,
+                end: waiter.end,
+            };
+            Ok((element, Some(op)))
+        })?;
+        Ok(popped.flatten())
+    }
+
+    /// Number of elements of the list `key`, `0` if it doesn't exist
+    pub(crate) fn list_len(&self, key: &str) -> crate::Result<usize> {
+        let shard = self.shared.shard(key).read();
+        match shard
+            .live_entry(key, Instant::now())
+            .map(|entry| &entry.value)
+        {
+            Some(Value::List(elements)) => Ok(elements.len()),
+            Some(_) => Err(crate::Error::WrongType),
+            None => Ok(0),
+        }
+    }
+
+    /// Elements of the list `key` from `start` to `stop` included. Negative indexes count from
+    /// the tail, `-1` being the last element, as in Redis.
+    pub(crate) fn list_range(&self, key: &str, start: i64, stop: i64) -> crate::Result<Vec<Bytes>> {
+        let shard = self.shared.shard(key).read();
+        let elements = match shard
+            .live_entry(key, Instant::now())
+            .map(|entry| &entry.value)
+        {
+            Some(Value::List(elements)) => elements,
+            Some(_) => return Err(crate::Error::WrongType),
+            None => return Ok(vec![]),
+        };
+
+        let len = elements.len() as i64;
+        let start = if start < 0 {
+            (len + start).max(0)
+        } else {
+            start
+        };
+        let stop = if stop < 0 {
+            len + stop
+        } else {
+            stop.min(len - 1)
+        };
+        let stored: Vec<Bytes> = if start > stop {
+            vec![]
+        } else {
+            elements
+                .range(start as usize..=stop as usize)
+                .cloned()
+                .collect()
+        };
+        drop(shard);
+
+        stored
+            .into_iter()
+            .map(|data| self.decode(key, data))
+            .collect()
+    }
+
+    /// Indexes of the elements of the list `key` equal to `element`, as `LPOS` finds them. Matches
+    /// are skipped until the `rank`-th one, counted from the tail if `rank` is negative, and the
+    /// search stops after `count` matches or `max_len` compared elements, `0` meaning no limit.
+    pub(crate) fn list_positions(
+        &self,
+        key: &str,
+        element: &[u8],
+        rank: i64,
+        count: usize,
+        max_len: usize,
+    ) -> crate::Result<Vec<usize>> {
+        let shard = self.shared.shard(key).read();
+        let elements = match shard
+            .live_entry(key, Instant::now())
+            .map(|entry| &entry.value)
+        {
+            Some(Value::List(elements)) => elements,
+            Some(_) => return Err(crate::Error::WrongType),
+            None => return Ok(vec![]),
+        };
+
+        let len = elements.len();
+        let scanned = if max_len == 0 { len } else { max_len.min(len) };
+        let mut skip = rank.unsigned_abs() - 1;
+        let mut positions = vec![];
+        for i in 0..scanned {
+            let index = if rank < 0 { len - 1 - i } else { i };
+            if self.decode(key, elements[index].clone())? != element {
+                continue;
+            }
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            positions.push(index);
+            if positions.len() == count {
+                break;
+            }
+        }
+        Ok(positions)
+    }
+
+    /// Replace the element at `index` of the list `key`, negative indexes counting from the tail
+    pub(crate) fn list_set(&self, key: &str, index: i64, value: Bytes) -> crate::Result<()> {
+        let stored = self.encode(key, value)?;
+        let set = self.shared.write_list(key, "lset", false, |elements| {
+            let index = list_index(index, elements.len()).ok_or("ERR index out of range")?;
+            elements[index] = stored.clone();
+            let op = WriteOp::ListSet {
+                key: key.to_string(),
+                index: index as u64,
+                value: stored,
+            };
+            Ok(((), Some(op)))
+        })?;
+        set.ok_or_else(|| "ERR no such key".into())
+    }
+
+    /// Insert `value` on `side` of the first element of the list `key` equal to `pivot`. Returns
+    /// the length of the list, `0` if the key doesn't exist, or `None` if `pivot` isn't found.
+    pub(crate) fn list_insert(
+        &self,
+        key: &str,
+        side: Side,
+        pivot: &[u8],
+        value: Bytes,
+    ) -> crate::Result<Option<usize>> {
+        let stored = self.encode(key, value)?;
+        let len = self.shared.write_list(key, "linsert", false, |elements| {
+            let mut found = None;
+            for (index, element) in elements.iter().enumerate() {
+                if self.decode(key, element.clone())? == pivot {
+                    found = Some(index);
+                    break;
+                }
+            }
+            let index = match (found, side) {
+                (Some(index), Side::Before) => index,
+                (Some(index), Side::After) => index + 1,
+                (None, _) => return Ok((None, None)),
+            };
+
+            elements.insert(index, stored.clone());
+            let op = WriteOp::ListInsert {
+                key: key.to_string(),
+                index: index as u64,
+                value: stored,
+            };
+            Ok((Some(elements.len()), Some(op)))
+        })?;
+        Ok(len.unwrap_or(Some(0)))
+    }
+
+    /// Remove the elements of the list `key` equal to `element`, as `LREM` does: the first
+    /// `count` ones if positive, the last `-count` ones if negative, all of them if `0`. Returns
+    /// the number of elements removed.
+    pub(crate) fn list_remove(
+        &self,
+        key: &str,
+        count: i64,
+        element: &[u8],
+    ) -> crate::Result<usize> {
+        let limit = match count {
+            0 => usize::MAX,
+            count => count.unsigned_abs().min(usize::MAX as u64) as usize,
+        };
+        let removed = self.shared.write_list(key, "lrem", false, |elements| {
+            let len = elements.len();
+            let mut indexes = vec![];
+            for i in 0..len {
+                if indexes.len() == limit {
+                    break;
+                }
+                let index = if count < 0 { len - 1 - i } else { i };
+                if self.decode(key, elements[index].clone())? == element {
+                    indexes.push(index);
+                }
+            }
+            indexes.sort_unstable();
+
+            let kept = elements
+                .drain(..)
+                .enumerate()
+                .filter(|(index, _)| indexes.binary_search(index).is_err())
+                .map(|(_, element)| element)
+                .collect();
+            *elements = kept;
+            let op = (!indexes.is_empty()).then(|| WriteOp::ListRemove {
+                key: key.to_string(),
+                indexes: indexes.iter().map(|&index| index as u64).collect(),
+            });
+            Ok((indexes.len(), op))
+        })?;
+        Ok(removed.unwrap_or(0))
+    }
+
+    /// Set the `fields` of the hash `key` to their values, the hash being created if missing.
+    /// Returns the number of fields added.
+    pub(crate) fn hash_set(&self, key: &str, fields: Vec<(Bytes, Bytes)>) -> crate::Result<usize> {
+        let stored = fields
+            .into_iter()
+            .map(|(field, value)| Ok((field, self.encode(key, value)?)))
+            .collect::<crate::Result<Vec<_>>>()?;
+        let added = self.shared.write_hash(key, "hset", true, |hash| {
+            let mut added = 0;
+            for (field, value) in stored.iter().cloned() {
+                if hash.insert(field, value) {
+                    added += 1;
+                }
+            }
+            let op = WriteOp::HashSet {
+                key: key.to_string(),
+                fields: stored,
+            };
+            Ok((added, Some(op)))
+        })?;
+        Ok(added.unwrap_or(0))
+    }
+
+    /// Value of `field` in the hash `key`, `None` if either doesn't exist
+    pub(crate) fn hash_get(&self, key: &str, field: &[u8]) -> crate::Result<Option<Bytes>> {
+        let shard = self.shared.shard(key).read();
+        let stored = match shard
+            .live_entry(key, Instant::now())
+            .map(|entry| &entry.value)
+        {
+            Some(Value::Hash(hash)) => hash.get(field).cloned(),
+            Some(_) => return Err(crate::Error::WrongType),
+            None => None,
+        };
+        drop(shard);
+
+        stored.map(|data| self.decode(key, data)).transpose()
+    }
+
+    /// Remove the `fields` of the hash `key`, removing the key once the hash is empty. Returns the
+    /// number of fields removed.
+    pub(crate) fn hash_delete(&self, key: &str, fields: Vec<Bytes>) -> crate::Result<usize> {
+        let removed = self.shared.write_hash(key, "hdel", false, |hash| {
+            let mut removed = vec![];
+            for field in fields {
+                if hash.remove(&field).is_some() {
+                    removed.push(field);
+                }
+            }
+            let count = removed.len();
+            let op = (count > 0).then(|| WriteOp::HashDelete {
+                key: key.to_string(),
+                fields: removed,
+            });
+            Ok((count, op))
+        })?;
+        Ok(removed.unwrap_or(0))
+    }
+
+    /// Number of fields of the hash `key`, `0` if it doesn't exist
+    pub(crate) fn hash_len(&self, key: &str) -> crate::Result<usize> {
+        let shard = self.shared.shard(key).read();
+        match shard
+            .live_entry(key, Instant::now())
+            .map(|entry| &entry.value)
+        {
+            Some(Value::Hash(hash)) => Ok(hash.len()),
+            Some(_) => Err(crate::Error::WrongType),
+            None => Ok(0),
+        }
+    }
+
+    /// Every field of the hash `key` along with its value
+    pub(crate) fn hash_get_all(&self, key: &str) -> crate::Result<Vec<(Bytes, Bytes)>> {
+        let shard = self.shared.shard(key).read();
+        let value = shard
+            .live_entry(key, Instant::now())
+            .map(|entry| &entry.value);
+        let stored: Vec<(Bytes, Bytes)> = match value {
+            Some(Value::Hash(hash)) => hash
+                .iter()
+                .map(|(field, value)| (field.clone(), value.clone()))
+                .collect(),
+            Some(_) => return Err(crate::Error::WrongType),
+            None => return Ok(vec![]),
+        };
+        drop(shard);
+
+        stored
+            .into_iter()
+            .map(|(field, data)| Ok((field, self.decode(key, data)?)))
+            .collect()
+    }
+
+    /// Add `delta` to the integer held by `field` of the hash `key`, the field and the hash being
+    /// created if missing. Returns the new value.
+    pub(crate) fn hash_incr_by(&self, key: &str, field: Bytes, delta: i64) -> crate::Result<i64> {
+        let value = self.shared.write_hash(key, "hincrby", true, |hash| {
+            let current = match hash.get(&field) {
+                Some(stored) => {
+                    let data = self.decode(key, stored.clone())?;
+                    std::str::from_utf8(&data)
+                        .ok()
+                        .and_then(|text| text.parse::<i64>().ok())
+                        .ok_or("ERR hash value is not an integer")?
+                }
+                None => 0,
+            };
+            let value = current
+                .checked_add(delta)
+                .ok_or("ERR increment or decrement would overflow")?;
+
+            let stored = self.encode(key, Bytes::from(value.to_string()))?;
+            hash.insert(field.clone(), stored.clone());
+            let op = WriteOp::HashSet {
+                key: key.to_string(),
+                fields: vec![(field, stored)],
+            };
+            Ok((value, Some(op)))
+        })?;
+        Ok(value.unwrap_or(delta))
+    }
+
+    /// Add `delta` to the number held by `field` of the hash `key`, the field and the hash being
+    /// created if missing. Returns the new value in its decimal form, as stored.
+    pub(crate) fn hash_incr_by_float(
+        &self,
+        key: &str,
+        field: Bytes,
+        delta: f64,
+    ) -> crate::Result<Bytes> {
+        let value = self.shared.write_hash(key, "hincrbyfloat", true, |hash| {
+            let current = match hash.get(&field) {
+                Some(stored) => {
+                    let data = self.decode(key, stored.clone())?;
+                    std::str::from_utf8(&data)
+                        .ok()
+                        .and_then(|text| text.parse::<f64>().ok())
+                        .filter(|current| current.is_finite())
+                        .ok_or("ERR hash value is not a float")?
+                }
+                None => 0.0,
+            };
+            let value = current + delta;
+            if !value.is_finite() {
+                return Err("ERR increment would produce NaN or Infinity".into());
+            }
+
+            let value = Bytes::from(value.to_string());
+            let stored = self.encode(key, value.clone())?;
+            hash.insert(field.clone(), stored.clone());
+            let op = WriteOp::HashSet {
+                key: key.to_string(),
+                fields: vec![(field, stored)],
+            };
+            Ok((value, Some(op)))
+        })?;
+        Ok(value.unwrap_or_default())
+    }
+
+    /// Fields of the hash `key` picked at random along with their values, as `HRANDFIELD` does.
+    /// With a positive `count` the fields are distinct, all of them at most. With a negative one
+    /// `-count` fields are picked, the same field possibly more than once.
+    pub(crate) fn hash_random_fields(
+        &self,
+        key: &str,
+        count: i64,
+    ) -> crate::Result<Vec<(Bytes, Bytes)>> {
+        let shard = self.shared.shard(key).read();
+        let hash = match shard
+            .live_entry(key, Instant::now())
+            .map(|entry| &entry.value)
+        {
+            Some(Value::Hash(hash)) => hash,
+            Some(_) => return Err(crate::Error::WrongType),
+            None => return Ok(vec![]),
+        };
+
+        let mut fields: Vec<_> = hash.iter().collect();
+        let len = fields.len();
+        let picked = if count >= 0 {
+            // partial Fisher-Yates shuffle of the first `count` fields
+            let count = count.min(len as i64) as usize;
+            for i in 0..count {
+                fields.swap(i, i + random_index(len - i));
+            }
+            fields.truncate(count);
+            fields
+        } else {
+            (0..count.unsigned_abs())
+                .map(|_| fields[random_index(len)])
+                .collect()
+        };
+        let stored: Vec<(Bytes, Bytes)> = picked
+            .into_iter()
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect();
+        drop(shard);
+
+        stored
+            .into_iter()
+            .map(|(field, data)| Ok((field, self.decode(key, data)?)))
+            .collect()
+    }
+
+    /// Fields of the hash `key` from `cursor` on, see `Hash::scan`, along with their values. Up to
+    /// `count` fields are walked, only those matching the glob `pattern` being returned. Returns
+    /// the cursor to resume from, `0` once the whole hash was walked.
+    pub(crate) fn hash_scan(
+        &self,
+        key: &str,
+        cursor: u64,
+        pattern: Option<&[u8]>,
+        count: usize,
+    ) -> crate::Result<(u64, Vec<(Bytes, Bytes)>)> {
+        let shard = self.shared.shard(key).read();
+        let (stored, next): (Vec<(Bytes, Bytes)>, u64) = match shard
+            .live_entry(key, Instant::now())
+            .map(|entry| &entry.value)
+        {
+            Some(Value::Hash(hash)) => {
+                let (fields, next) = hash.scan(cursor, count);
+                let matching = fields
+                    .into_iter()
+                    .filter(|(field, _)| {
+                        pattern.map_or(true, |pattern| glob::matches(pattern, field))
+                    })
+                    .map(|(field, value)| (field.clone(), value.clone()))
+                    .collect();
+                (matching, next)
+            }
+            Some(_) => return Err(crate::Error::WrongType),
+            None => return Ok((0, vec![])),
+        };
+        drop(shard);
+
+        let fields = stored
+            .into_iter()
+            .map(|(field, data)| Ok((field, self.decode(key, data)?)))
+            .collect::<crate::Result<_>>()?;
+        Ok((next, fields))
+    }
+
+    /// Set the scores of the `members` of the sorted set `key`, the set being created if missing.
+    /// `update` chooses the members written. Returns the number of members added, along with the
+    /// members whose score changed if `changed` is set.
+    pub(crate) fn sorted_set_add(
+        &self,
+        key: &str,
+        members: Vec<(f64, Bytes)>,
+        update: MemberUpdate,
+        changed: bool,
+    ) -> crate::Result<usize> {
+        let create = update != MemberUpdate::Change;
+        let count = self.shared.write_sorted_set(key, "zadd", create, |set| {
+            let mut count = 0;
+            let mut written = vec![];
+            for (score, member) in members {
+                let write = match (update, set.score(&member)) {
+                    (MemberUpdate::Add, Some(_)) | (MemberUpdate::Change, None) => false,
+                    (_, Some(current)) => current != score,
+                    (_, None) => true,
+                };
+                if !write {
+                    continue;
+                }
+                if set.insert(member.clone(), score) || changed {
+                    count += 1;
+                }
+                written.push((score, member));
+            }
+            let op = (!written.is_empty()).then(|| WriteOp::SortedSetAdd {
+                key: key.to_string(),
+                members: written,
+            });
+            Ok((count, op))
+        })?;
+        Ok(count.unwrap_or(0))
+    }
+
+    /// Score of `member` in the sorted set `key`, `None` if either doesn't exist
+    pub(crate) fn sorted_set_score(&self, key: &str, member: &[u8]) -> crate::Result<Option<f64>> {
+        let shard = self.shared.shard(key).read();
+        match shard
+            .live_entry(key, Instant::now())
+            .map(|entry| &entry.value)
+        {
+            Some(Value::SortedSet(set)) => Ok(set.score(member)),
+            Some(_) => Err(crate::Error::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    /// Remove the `members` of the sorted set `key`, removing the key once the set is empty.
+    /// Returns the number of members removed.
+    pub(crate) fn sorted_set_remove(&self, key: &str, members: Vec<Bytes>) -> crate::Result<usize> {
+        let removed = self.shared.write_sorted_set(key, "zrem", false, |set| {
+            let mut removed = vec![];
+            for member in members {
+                if set.remove(&member) {
+                    removed.push(member);
+                }
+            }
+            let count = removed.len();
+            let op = (count > 0).then(|| WriteOp::SortedSetRemove {
+                key: key.to_string(),
+                members: removed,
+            });
+            Ok((count, op))
+        })?;
+        Ok(removed.unwrap_or(0))
+    }
+
+    /// Number of members of the sorted set `key`, `0` if it doesn't exist
+    pub(crate) fn sorted_set_len(&self, key: &str) -> crate::Result<usize> {
+        let shard = self.shared.shard(key).read();
+        match shard
+            .live_entry(key, Instant::now())
+            .map(|entry| &entry.value)
+        {
+            Some(Value::SortedSet(set)) => Ok(set.len()),
+            Some(_) => Err(crate::Error::WrongType),
+            None => Ok(0),
+        }
+    }
+
+    /// Members of the sorted set `key` from rank `start` to `stop` included, along with their
+    /// scores. Ranks start at `0` for the lowest score, negative ones counting from the highest.
+    pub(crate) fn sorted_set_range(
+        &self,
+        key: &str,
+        start: i64,
+        stop: i64,
+    ) -> crate::Result<Vec<(Bytes, f64)>> {
+        let shard = self.shared.shard(key).read();
+        let set = match shard
+            .live_entry(key, Instant::now())
+            .map(|entry| &entry.value)
+        {
+            Some(Value::SortedSet(set)) => set,
+            Some(_) => return Err(crate::Error::WrongType),
+            None => return Ok(vec![]),
+        };
+
+        let len = set.len() as i64;
+        let start = if start < 0 {
+            (len + start).max(0)
+        } else {
+            start
+        };
+        let stop = if stop < 0 {
+            len + stop
+        } else {
+            stop.min(len - 1)
+        };
+        if start > stop {
+            return Ok(vec![]);
+        }
+        let members = set
+            .iter()
+            .skip(start as usize)
+            .take((stop - start + 1) as usize)
+            .map(|(member, score)| (member.clone(), score))
+            .collect();
+        Ok(members)
+    }
+
+    /// Distance in meters between the positions of the members `from` and `to` of the sorted set
+    /// `key`, see `geo`. `None` if either member or the key doesn't exist.
+    pub(crate) fn geo_distance(
+        &self,
+        key: &str,
+        from: &[u8],
+        to: &[u8],
+    ) -> crate::Result<Option<f64>> {
+        let shard = self.shared.shard(key).read();
+        let set = match shard
+            .live_entry(key, Instant::now())
+            .map(|entry| &entry.value)
+        {
+            Some(Value::SortedSet(set)) => set,
+            Some(_) => return Err(crate::Error::WrongType),
+            None => return Ok(None),
+        };
+
+        let position = |member: &[u8]| set.score(member).map(|score| geo::decode(score as u64));
+        Ok(position(from)
+            .zip(position(to))
+            .map(|(from, to)| geo::distance(from, to)))
+    }
+
+    /// Members of the sorted set `key` positioned within `shape` around `center`, see `geo`, along
+    /// with their distance to the center. Only the members in the score ranges of the area are
+    /// compared.
+    pub(crate) fn geo_search(
+        &self,
+        key: &str,
+        center: &geo::Center,
+        shape: geo::Shape,
+    ) -> crate::Result<Vec<geo::Found>> {
+        let shard = self.shared.shard(key).read();
+        let set = match shard
+            .live_entry(key, Instant::now())
+            .map(|entry| &entry.value)
+        {
+            Some(Value::SortedSet(set)) => set,
+            Some(_) => return Err(crate::Error::WrongType),
+            None => return Ok(vec![]),
+        };
+
+        let center = match center {
+            geo::Center::Member(member) => match set.score(member) {
+                Some(score) => geo::decode(score as u64),
+                None => return Err("ERR could not decode requested zset member".into()),
+            },
+            geo::Center::Position(longitude, latitude) => (*longitude, *latitude),
+        };
+        let mut found = vec![];
+        for (min, max) in geo::ranges(center, shape) {
+            for (member, score) in set.range_by_score(min, max) {
+                let hash = score as u64;
+                if let Some(distance) = shape.contains(center, geo::decode(hash)) {
+                    found.push(geo::Found {
+                        member: member.clone(),
+                        distance,
+                        hash,
+                    });
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    fn encode(&self, key: &str, value: Bytes) -> crate::Result<Bytes> {
+        self.shared.encode(key, value)
+    }
+
+    fn decode(&self, key: &str, stored: Bytes) -> crate::Result<Bytes> {
+        self.shared.decode(key, stored)
+    }
+
+    /// Take the lock `key` for `ttl` on behalf of `token`. The holder may take the lock again to
+    /// extend it, unless `nx` is set. Returns `false` if the lock is held by another token.
+    ///
+    /// A lock is a reservation of `key` holding `token`: if it expires before being released, the
+    /// token is published on the `LOCK_EXPIRED_PREFIX` channel of the key.
+    pub(crate) fn lock(
+        &self,
+        key: String,
+        token: Bytes,
+        ttl: Duration,
+        nx: bool,
+    ) -> crate::Result<bool> {
+        let stored = self.encode(&key, token.clone())?;
+        let mut shard = self.shared.shard(&key).write();
+
+        if let Some(entry) = shard.live_entry(&key, Instant::now()) {
+            if nx || self.decode(&key, entry.value.as_string()?.clone())? != token {
+                return Ok(false);
+            }
+        }
+
+        let dead_letter = format!("{}{}", LOCK_EXPIRED_PREFIX, key);
+        let notify = self.insert_locked(
+            &mut shard,
+            key.clone(),
+            stored,
+            Some(ttl),
+            Some(dead_letter),
+        );
+        drop(shard);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+        self.shared
+            .notify_keyspace_event(Class::String, "lock", &key);
+        Ok(true)
+    }
+
+    /// Release the lock `key` held by `token`. Returns `false` if `token` doesn't hold the lock.
+    pub(crate) fn unlock(&self, key: &str, token: &[u8]) -> crate::Result<bool> {
+        let mut shard = self.shared.shard(key).write();
+        let dead_letter = format!("{}{}", LOCK_EXPIRED_PREFIX, key);
+
+        let held = match shard.live_entry(key, Instant::now()) {
+            Some(entry) if entry.reservation.as_ref() == Some(&dead_letter) => {
+                self.decode(key, entry.value.as_string()?.clone())? == token
+            }
+            _ => false,
+        };
+        if !held {
+            return Ok(false);
+        }
+
+        if let Some(entry) = shard.remove_entry(key) {
+            if let Some(when) = entry.expires_at {
+                shard.expirations.remove(&(when, entry.id));
+            }
+        }
+        self.shared.commits.commit(WriteOp::Delete {
+            key: key.to_string(),
+        });
+        drop(shard);
+
+        self.shared
+            .notify_keyspace_event(Class::String, "unlock", key);
+        Ok(true)
+    }
+
+    /// Confirm the pending reservation on `key`, the key then expires silently. Returns `false` if
+    /// there was no pending reservation. Locks are not reservations for this purpose, they are
+    /// released with `unlock`.
+    pub(crate) fn confirm(&self, key: &str) -> bool {
+        let mut shard = self.shared.shard(key).write();
+        let now = Instant::now();
+        // too late once the key expired, even if the background task didn't get to it yet
+        let dead_letter = shard
+            .entries
+            .get_mut(key)
+            .filter(|entry| !entry.is_expired(now) && !entry.is_lock())
+            .and_then(|entry| entry.reservation.take());
+
+        match dead_letter {
+            Some(dead_letter) => {
+                shard.account(0, dead_letter.len());
+                let seq = self.shared.commits.commit(WriteOp::Confirm {
+                    key: key.to_string(),
+                });
+                shard.set_id(key, seq);
+                if let Some(entry) = shard.entries.get_mut(key) {
+                    entry.modified = SystemTime::now();
+                }
+                drop(shard);
+
+                self.shared
+                    .notify_keyspace_event(Class::String, "confirm", key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sequence number of the latest write, `0` if nothing was written yet
+    pub(crate) fn last_seq(&self) -> u64 {
+        self.shared.commits.last_seq()
+    }
+
+    /// Write every key to a snapshot at `path`, see `snapshot`. Returns the number of keys
+    /// written. The file is written synchronously, the server is expected to be done serving.
+    pub(crate) fn save_snapshot(&self, path: &Path) -> crate::Result<usize> {
+        let mut writer = snapshot::Writer::create(path, self.last_seq())?;
+        let now = Instant::now();
+        let wall_now = SystemTime::now();
+        let mut keys = 0;
+
+        for shard in self.shared.shards.iter() {
+            let shard = shard.read();
+            for (key, entry) in &shard.entries {
+                let expires_at = match entry.expires_at {
+                    // expired, the background task didn't get to it yet
+                    Some(when) if when <= now => continue,
+                    Some(when) => Some(wall_now + (when - now)),
+                    None => None,
+                };
+                let value = match &entry.value {
+                    Value::String(data) => Stored::String(data.clone()),
+                    Value::Blog(blog) => Stored::Blog {
+                        start: blog.start(),
+                        data: Bytes::copy_from_slice(blog.data()),
+                    },
+                    Value::List(elements) => Stored::List(elements.iter().cloned().collect()),
+                    Value::Hash(hash) => Stored::Hash(
+                        hash.iter()
+                            .map(|(field, value)| (field.clone(), value.clone()))
+                            .collect(),
+                    ),
+                    Value::SortedSet(set) => Stored::SortedSet(
+                        set.iter()
+                            .map(|(member, score)| (member.clone(), score))
+                            .collect(),
+                    ),
+                };
+
+                writer.write(&Record {
+                    key: key.clone(),
+                    id: entry.id,
+                    value,
+                    modified: entry.modified,
+                    expires_at,
+                    reservation: entry.reservation.clone(),
+                })?;
+                keys += 1;
+            }
+        }
+
+        writer.finish()?;
+        Ok(keys)
+    }
+
+    /// Load the keys of the snapshot at `path` then remove it, the sequence of writes resumes
+    /// where the snapshot left it. Returns the number of keys loaded, `None` if there is no
+    /// snapshot. The keys which expired since the snapshot was taken are skipped.
+    pub(crate) fn load_snapshot(&self, path: &Path) -> crate::Result<Option<usize>> {
+        let (last_seq, records) = match snapshot::read(path)? {
+            Some(snapshot) => snapshot,
+            None => return Ok(None),
+        };
+        self.shared.commits.resume_after(last_seq);
+
+        let now = Instant::now();
+        let wall_now = SystemTime::now();
+        let mut keys = 0;
+
+        for record in records {
+            let expires_at = match record.expires_at {
+                Some(when) => match when.duration_since(wall_now) {
+                    Ok(ttl) => Some(now + ttl),
+                    Err(_) => continue,
+                },
+                None => None,
+            };
+            let value = match record.value {
+                Stored::String(data) => Value::String(data),
+                Stored::Blog { start, data } => Value::Blog(Blog::from_parts(start, &data)),
+                Stored::List(elements) => Value::List(elements.into()),
+                Stored::Hash(fields) => {
+                    let mut hash = Hash::default();
+                    for (field, value) in fields {
+                        hash.insert(field, value);
+                    }
+                    Value::Hash(hash)
+                }
+                Stored::SortedSet(members) => {
+                    let mut set = SortedSet::default();
+                    for (member, score) in members {
+                        set.insert(member, score);
+                    }
+                    Value::SortedSet(set)
+                }
+            };
+
+            let mut shard = self.shared.shard(&record.key).write();
+            if let Some(when) = expires_at {
+                shard
+                    .expirations
+                    .insert((when, record.id), record.key.clone());
+            }
+            shard.insert_entry(
+                record.key,
+                Entry {
+                    id: record.id,
+                    value,
+                    expires_at,
+                    reservation: record.reservation,
+                    modified: record.modified,
+                },
+            );
+            keys += 1;
+        }
+
+        // the snapshot must not be loaded twice, a later restart would bring back stale keys
+        std::fs::remove_file(path)?;
+        self.shared.background_task.notify_one();
+        Ok(Some(keys))
+    }
+
+    /// Write the string keys to an RDB file at `path`, see `rdb`. Returns the number of keys
+    /// written and the number skipped, which have no Redis equivalent.
+    ///
+    /// Refused with a value transform: Redis couldn't read the transformed values, and writing
+    /// them decoded would put encrypted values on disk in the clear.
+    pub(crate) fn save_rdb(&self, path: &Path) -> crate::Result<(usize, usize)> {
+        if self.shared.transform.is_some() {
+            return Err("ERR SAVE is not available when the values are stored transformed".into());
+        }
+
+        let mut writer = rdb::Writer::create(path)?;
+        let now = Instant::now();
+        let wall_now = SystemTime::now();
+        let (mut keys, mut skipped) = (0, 0);
+
+        for shard in self.shared.shards.iter() {
+            let shard = shard.read();
+            for (key, entry) in &shard.entries {
+                let expires_at = match entry.expires_at {
+                    Some(when) if when <= now => continue,
+                    Some(when) => Some(wall_now + (when - now)),
+                    None => None,
+                };
+                match &entry.value {
+                    Value::String(data) => {
+                        writer.write(key, data, expires_at)?;
+                        keys += 1;
+                    }
+                    Value::Blog(_) | Value::List(_) | Value::Hash(_) | Value::SortedSet(_) => {
+                        skipped += 1
+                    }
+                }
+            }
+        }
+
+        writer.finish()?;
+        Ok((keys, skipped))
+    }
+
+    /// Load the string keys of the RDB file at `path`, replacing the existing ones. Returns the
+    /// number of keys loaded and the number skipped, see `rdb::read`. The keys already expired
+    /// are dropped.
+    pub(crate) fn load_rdb(&self, path: &Path) -> crate::Result<(usize, usize)> {
+        let (records, skipped) = rdb::read(path)?;
+        let wall_now = SystemTime::now();
+        let mut keys = 0;
+
+        for record in records {
+            let expire = match record.expires_at {
+                Some(when) => match when.duration_since(wall_now) {
+                    Ok(ttl) => Some(ttl),
+                    Err(_) => continue,
+                },
+                None => None,
+            };
+            self.restore(record.key, record.value, expire, true)?;
+            keys += 1;
+        }
+        Ok((keys, skipped))
+    }
+
+    /// Register a consumer of the commit pipeline starting at sequence number `from`, see
+    /// `Pipeline::subscribe_from`
+    pub(crate) fn subscribe_writes_from(
+        &self,
+        from: u64,
+    ) -> Result<(Vec<WriteRecord>, mpsc::Receiver<WriteRecord>), Evicted> {
+        self.shared.commits.subscribe_from(from)
+    }
+
+    fn insert(
+        &self,
+        key: String,
+        value: Bytes,
+        expire: Option<Duration>,
+        reservation: Option<String>,
+    ) {
+        let mut shard = self.shared.shard(&key).write();
+        let notify = self.insert_locked(&mut shard, key, value, expire, reservation);
+
+        // relase the mutex before notifying the background task. This helps reduce contention by
+        // aboud the background task waking up only to be unable to acquire the mutex due to this
+        // functions still holding it.
+        drop(shard);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
     }
 
-    /// Set the value associated with a key along with an optional expiration Duration
-    pub(crate) fn set(&self, key: String, value: Bytes, expire: Option<Duration>) {
-        let mut state = self.shared.state.lock().unwrap();
+    /// Insert the entry of `key` in `shard`, which is the locked shard of `key`. Returns whether the
+    /// background task must be notified once the lock is released.
+    fn insert_locked(
+        &self,
+        shard: &mut Shard,
+        key: String,
+        value: Bytes,
+        expire: Option<Duration>,
+        reservation: Option<String>,
+    ) -> bool {
+        let modified = SystemTime::now();
+        let expire = expire.map(|duration| duration.min(MAX_TTL));
 
-        let id = state.next_id;
-        state.next_id += 1;
+        // record the write in the commit pipeline, still under the lock to keep the order
+        let op = match &reservation {
+            Some(dead_letter) => WriteOp::Reserve {
+                key: key.clone(),
+                value: value.clone(),
+                expires_at: modified + expire.unwrap_or_default(),
+                dead_letter: dead_letter.clone(),
+            },
+            None => WriteOp::Set {
+                key: key.clone(),
+                value: value.clone(),
+                expires_at: expire.map(|duration| modified + duration),
+            },
+        };
+        let id = self.shared.commits.commit(op);
 
         // if this `set` becomes the key that expires **next**, thie background task needs to be
         // notified so it can update its sate
@@ -91,21 +2349,25 @@ impl Db {
 
         let expires_at = expire.map(|duration| {
             let when = Instant::now() + duration;
-            // Only notify the worker task if the newly inserted expiration is the **next** key to
-            // evict. In this case, the worker needs to be woken up to update its state
-            notify = state.next_expiration().map(|e| e > when).unwrap_or(true);
+            // Only notify the worker task if the newly inserted expiration is the **next** key of
+            // the shard to evict. The worker may then need to be woken up to update its state, an
+            // earlier expiration in another shard only makes the wake up spurious.
+            notify = shard.next_expiration().map(|e| e > when).unwrap_or(true);
 
             // track the expiration
-            state.expirations.insert((when, id), key.clone());
+            shard.expirations.insert((when, id), key.clone());
             when
         });
+
         // insert then entry nito the `HashMap`
-        let prev = state.entries.insert(
+        let prev = shard.insert_entry(
             key,
             Entry {
                 id,
-                data: value,
+                value: Value::String(value),
                 expires_at,
+                reservation,
+                modified,
             },
         );
 
@@ -115,49 +2377,209 @@ impl Db {
         if let Some(prev) = prev {
             if let Some(when) = prev.expires_at {
                 // clear the expiration
-                state.expirations.remove(&(when, prev.id));
+                shard.expirations.remove(&(when, prev.id));
             }
         }
-        // relase the mutex before notifying the background task. This helps reduce contention by
-        // aboud the background task waking up only to be unable to acquire the mutex due to this
-        // functions still holding it.
-        drop(state);
-
-        if notify {
-            self.shared.background_task.notify_one();
-        }
+        notify
     }
 
-    pub(crate) fn subscribe(&self, key: String) -> broadcast::Receiver<Bytes> {
+    /// Subscribe to `key`. The returned stats are updated by the caller as messages are received.
+    pub(crate) fn subscribe(
+        &self,
+        key: String,
+    ) -> (broadcast::Receiver<Published>, Arc<ChannelStats>) {
         use std::collections::hash_map::Entry;
         let mut state = self.shared.state.lock().unwrap();
 
-        match state.pub_sub.entry(key) {
-            Entry::Occupied(e) => e.get().subscribe(),
+        let channel = match state.pub_sub.entry(key) {
+            Entry::Occupied(e) => e.into_mut(),
             Entry::Vacant(e) => {
+                let mut frame_prefix = BytesMut::new();
+                frame_prefix.put_slice(b"*3\r\n");
+                Frame::Bulk(Bytes::from_static(b"message")).encode(&mut frame_prefix);
+                Frame::Bulk(Bytes::copy_from_slice(e.key().as_bytes())).encode(&mut frame_prefix);
+
                 // No broadcast channel exist yet, so create one.
                 //
-                // The channel is crated with a capacity of `1024` messages. A mesage is stored in
-                // the channel until *all* subscribers have seen it. This means that a slow
-                // subscriber could result in messages being held indefinitely.
+                // The channel is crated with a capacity of `pubsub-channel-capacity` messages. A
+                // mesage is stored in the channel until *all* subscribers have seen it. This means
+                // that a slow subscriber could result in messages being held indefinitely.
                 //
                 // When the channel's capacity fills up, publishing will result in old messages
                 // being dropped. This prevent slow consumers from blocking enrire system.
-                let (tx, rx) = broadcast::channel(1024);
-                e.insert(tx);
-                rx
+                let capacity = self
+                    .shared
+                    .config
+                    .read(|settings| settings.pubsub_channel_capacity);
+                let (tx, _) = broadcast::channel(capacity);
+                e.insert(Channel {
+                    tx,
+                    frame_prefix: frame_prefix.freeze(),
+                    stats: Arc::default(),
+                })
             }
-        }
+        };
+
+        channel.stats.subscribes.fetch_add(1, Ordering::Relaxed);
+        (channel.tx.subscribe(), channel.stats.clone())
     }
 
     /// Publish a mesage to the channel. Returns the number of subscribers listening on the channel
     pub(crate) fn publish(&self, key: &str, value: Bytes) -> usize {
+        self.shared.publish(key, value)
+    }
+
+    /// What happens to the subscribers whose channel is full
+    pub(crate) fn pubsub_overflow(&self) -> PubSubOverflow {
+        self.shared.config.read(|settings| settings.pubsub_overflow)
+    }
+
+    /// Delivery statistics of a channel along with its current number of subscribers
+    pub(crate) fn channel_stats(&self, key: &str) -> Option<(usize, Arc<ChannelStats>)> {
         let state = self.shared.state.lock().unwrap();
         state
             .pub_sub
             .get(key)
-            .map(|tx| tx.send(value).unwrap_or(0))
-            .unwrap_or(0)
+            .map(|channel| (channel.tx.receiver_count(), channel.stats.clone()))
+    }
+
+    /// Delivery statistics of every channel along with its current number of subscribers, by name
+    pub(crate) fn all_channel_stats(&self) -> Vec<(String, usize, Arc<ChannelStats>)> {
+        let state = self.shared.state.lock().unwrap();
+        let mut channels: Vec<_> = state
+            .pub_sub
+            .iter()
+            .map(|(name, channel)| {
+                (
+                    name.clone(),
+                    channel.tx.receiver_count(),
+                    channel.stats.clone(),
+                )
+            })
+            .collect();
+        drop(state);
+
+        channels.sort_by(|a, b| a.0.cmp(&b.0));
+        channels
+    }
+
+    /// Drop the channel if its last subscriber is gone. Subscribers call this once they are
+    /// done with a channel, the background task sweeps the channels which were missed.
+    pub(crate) fn release_channel(&self, key: &str) {
+        let mut state = self.shared.state.lock().unwrap();
+        let idle = state
+            .pub_sub
+            .get(key)
+            .map(|channel| channel.tx.receiver_count() == 0)
+            .unwrap_or(false);
+
+        if idle {
+            let channel = state.pub_sub.remove(key).unwrap();
+            state.retired_channels.add(&channel);
+        }
+    }
+
+    /// Delivery statistics summed over every channel
+    pub(crate) fn pubsub_totals(&self) -> PubSubTotals {
+        let state = self.shared.state.lock().unwrap();
+        let mut totals = state.retired_channels.clone();
+        for channel in state.pub_sub.values() {
+            totals.subscribers += channel.tx.receiver_count();
+            totals.add(channel);
+        }
+        totals
+    }
+}
+
+/// Pub/sub statistics of the whole server, reported by `INFO stats`
+#[derive(Debug, Default, Clone)]
+pub(crate) struct PubSubTotals {
+    pub(crate) subscribers: usize,
+    pub(crate) published: u64,
+    pub(crate) delivered: u64,
+    pub(crate) dropped: u64,
+    pub(crate) disconnected: u64,
+}
+
+impl PubSubTotals {
+    /// Add the delivery counters of `channel`
+    fn add(&mut self, channel: &Channel) {
+        self.published += channel.stats.published.load(Ordering::Relaxed);
+        self.delivered += channel.stats.delivered.load(Ordering::Relaxed);
+        self.dropped += channel.stats.dropped.load(Ordering::Relaxed);
+        self.disconnected += channel.stats.disconnected.load(Ordering::Relaxed);
+    }
+}
+
+impl Channel {
+    fn send(&self, value: Bytes) -> usize {
+        self.stats.published.fetch_add(1, Ordering::Relaxed);
+        // nobody to encode the frame for
+        if self.tx.receiver_count() == 0 {
+            return 0;
+        }
+
+        let mut frame = BytesMut::with_capacity(self.frame_prefix.len() + value.len() + 16);
+        frame.put_slice(&self.frame_prefix);
+        Frame::Bulk(value.clone()).encode(&mut frame);
+        let published = Published {
+            payload: value,
+            frame: frame.freeze(),
+        };
+        self.tx.send(published).unwrap_or(0)
+    }
+}
+
+impl ChannelStats {
+    pub(crate) fn record_delivered(&self) {
+        self.delivered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_dropped(&self, n: u64) {
+        self.dropped.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_disconnected(&self) {
+        self.disconnected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_unsubscribe(&self) {
+        self.unsubscribes.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Memory layout of the key space, reported by `INFO memory`
+#[derive(Debug)]
+pub(crate) struct MemoryStats {
+    /// Number of partitions of the key space
+    pub(crate) shards: usize,
+    pub(crate) keys: usize,
+    pub(crate) keys_capacity: usize,
+    pub(crate) expires: usize,
+    /// Estimated number of bytes held by the keys and values
+    pub(crate) dataset_bytes: usize,
+    /// Limit of `dataset_bytes` for the commands adding data, none if 0
+    pub(crate) maxmemory: usize,
+    pub(crate) pubsub_channels: usize,
+    pub(crate) pubsub_channels_capacity: usize,
+    pub(crate) active_defrag: bool,
+    pub(crate) defrag_runs: u64,
+}
+
+impl MemoryStats {
+    /// Estimated number of bytes held by allocated but unused map slots
+    pub(crate) fn overhead_bytes(&self) -> usize {
+        (self.keys_capacity - self.keys) * mem::size_of::<(String, Entry)>()
+            + (self.pubsub_channels_capacity - self.pubsub_channels)
+                * mem::size_of::<(String, Channel)>()
+    }
+
+    /// Ratio of allocated key slots to used ones. `1.0` means no fragmentation at all.
+    pub(crate) fn fragmentation_ratio(&self) -> f64 {
+        if self.keys == 0 {
+            return 1.0;
+        }
+        self.keys_capacity as f64 / self.keys as f64
     }
 }
 
@@ -183,24 +2605,479 @@ impl Drop for Db {
 }
 
 impl Shared {
-    fn purge_expired_keys(&self) -> Option<Instant> {
-        let mut state = self.state.lock().unwrap();
+    /// The shard holding `key`
+    fn shard(&self, key: &str) -> &ShardLock<Shard> {
+        let index = self.hasher.hash_one(key) % self.shards.len() as u64;
+        &self.shards[index as usize]
+    }
+
+    fn publish(&self, key: &str, value: Bytes) -> usize {
+        let state = self.state.lock().unwrap();
+        state
+            .pub_sub
+            .get(key)
+            .map(|channel| channel.send(value))
+            .unwrap_or(0)
+    }
+
+    /// Send the keyspace notification of `event` on `key`, if `notify-keyspace-events` enables
+    /// its class. Called once the lock of the key is released.
+    fn notify_keyspace_event(&self, class: Class, event: &str, key: &str) {
+        let (keyspace, keyevent) = self
+            .config
+            .read(|settings| settings.notify_keyspace_events.channels(class));
+        if keyspace {
+            self.publish(
+                &keyspace_events::keyspace_channel(key),
+                Bytes::copy_from_slice(event.as_bytes()),
+            );
+        }
+        if keyevent {
+            self.publish(
+                &keyspace_events::keyevent_channel(event),
+                Bytes::copy_from_slice(key.as_bytes()),
+            );
+        }
+    }
+
+    fn encode(&self, key: &str, value: Bytes) -> crate::Result<Bytes> {
+        match &self.transform {
+            Some(transform) => transform.encode(key, value),
+            None => Ok(value),
+        }
+    }
+
+    fn decode(&self, key: &str, stored: Bytes) -> crate::Result<Bytes> {
+        match &self.transform {
+            Some(transform) => transform.decode(key, stored),
+            None => Ok(stored),
+        }
+    }
+
+    /// Push `values` at `end` of the list `key`, see `Db::list_push`
+    fn list_push(&self, key: &str, values: Vec<Bytes>, end: ListEnd) -> crate::Result<usize> {
+        let stored = values
+            .into_iter()
+            .map(|value| self.encode(key, value))
+            .collect::<crate::Result<Vec<_>>>()?;
+        let event = match end {
+            ListEnd::Head => "lpush",
+            ListEnd::Tail => "rpush",
+        };
+
+        let len = self.write_list(key, event, true, |elements| {
+            for value in stored.iter().cloned() {
+                match end {
+                    ListEnd::Head => elements.push_front(value),
+                    ListEnd::Tail => elements.push_back(value),
+                }
+            }
+            let op = WriteOp::ListPush {
+                key: key.to_string(),
+                values: stored,
+                end,
+            };
+            Ok((elements.len(), Some(op)))
+        })?;
+        Ok(len.unwrap_or(0))
+    }
+
+    /// Queue the blocked client `waiter` on the list `key`
+    fn block_on(&self, key: &str, waiter: Waiter) {
+        let mut shard = self.shard(key).write();
+        shard
+            .waiters
+            .entry(key.to_string())
+            .or_default()
+            .push_back(waiter);
+    }
+
+    /// Apply `write` to the fields of the hash `key`, see `write_elements`
+    fn write_hash<T>(
+        &self,
+        key: &str,
+        event: &str,
+        create: bool,
+        write: impl FnOnce(&mut Hash) -> crate::Result<(T, Option<WriteOp>)>,
+    ) -> crate::Result<Option<T>> {
+        let empty = || Value::Hash(Hash::default());
+        self.write_elements(
+            key,
+            Class::Hash,
+            event,
+            create,
+            empty,
+            |value| match value {
+                Value::Hash(hash) => write(hash),
+                _ => unreachable!(),
+            },
+        )
+    }
+
+    /// Apply `write` to the members of the sorted set `key`, see `write_elements`
+    fn write_sorted_set<T>(
+        &self,
+        key: &str,
+        event: &str,
+        create: bool,
+        write: impl FnOnce(&mut SortedSet) -> crate::Result<(T, Option<WriteOp>)>,
+    ) -> crate::Result<Option<T>> {
+        let empty = || Value::SortedSet(SortedSet::default());
+        self.write_elements(
+            key,
+            Class::SortedSet,
+            event,
+            create,
+            empty,
+            |value| match value {
+                Value::SortedSet(set) => write(set),
+                _ => unreachable!(),
+            },
+        )
+    }
+
+    /// Apply `write` to the value of `key` under the lock of its shard, for the types holding
+    /// elements other than lists, which also serve their blocked clients in `write_list`. The
+    /// value must be of the type of `empty`. `write` returns its result along with the op
+    /// recording the change in the commit pipeline, `None` if nothing changed.
+    ///
+    /// With `create`, a missing key is written as `empty`, an expired one not removed yet being
+    /// replaced. Otherwise `None` is returned for a missing key. A value left without elements is
+    /// removed. The keyspace notifications, `event` of `class` for the change, are sent once the
+    /// lock is released.
+    fn write_elements<T>(
+        &self,
+        key: &str,
+        class: Class,
+        event: &str,
+        create: bool,
+        empty: impl Fn() -> Value,
+        write: impl FnOnce(&mut Value) -> crate::Result<(T, Option<WriteOp>)>,
+    ) -> crate::Result<Option<T>> {
+        let mut shard = self.shard(key).write();
+        let now = Instant::now();
+
+        let kind = empty().kind();
+        match shard.live_entry(key, now).map(|entry| entry.value.kind()) {
+            Some(found) if found == kind => {}
+            Some(_) => return Err(crate::Error::WrongType),
+            None if !create => return Ok(None),
+            None => {}
+        }
+
+        // an expired entry not removed yet is replaced, as by `BLOG.APPEND`
+        let expired = shard
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.is_expired(now));
+        if expired {
+            if let Some(entry) = shard.remove_entry(key) {
+                if let Some(when) = entry.expires_at {
+                    shard.expirations.remove(&(when, entry.id));
+                }
+            }
+            self.commits.commit(WriteOp::Expire {
+                key: key.to_string(),
+            });
+        }
+
+        // removed and inserted back so the memory accounting sees the elements change
+        let existed = shard.entries.contains_key(key);
+        let mut entry = shard.remove_entry(key).unwrap_or_else(|| Entry {
+            id: 0,
+            value: empty(),
+            expires_at: None,
+            reservation: None,
+            modified: SystemTime::now(),
+        });
+
+        let (result, op) = match write(&mut entry.value) {
+            Ok(written) => written,
+            Err(err) => {
+                if existed {
+                    shard.insert_entry(key.to_string(), entry);
+                }
+                return Err(err);
+            }
+        };
+
+        let changed = op.is_some();
+        if let Some(op) = op {
+            // each write makes a new version of the entry, which also keys its expiration
+            let seq = self.commits.commit(op);
+            if let Some(when) = entry.expires_at {
+                shard.expirations.remove(&(when, entry.id));
+                shard.expirations.insert((when, seq), key.to_string());
+            }
+            entry.id = seq;
+            entry.modified = SystemTime::now();
+        }
+        let emptied = entry.value.is_empty_collection();
+        if emptied {
+            if let Some(when) = entry.expires_at {
+                shard.expirations.remove(&(when, entry.id));
+            }
+        } else {
+            shard.insert_entry(key.to_string(), entry);
+        }
+        drop(shard);
+
+        if expired {
+            self.notify_keyspace_event(Class::Expired, "expired", key);
+        }
+        if changed {
+            self.notify_keyspace_event(class, event, key);
+        }
+        if emptied && existed {
+            self.notify_keyspace_event(Class::Generic, "del", key);
+        }
+        Ok(Some(result))
+    }
+
+    /// Remove the blocked client `waiter` from the queue of the list `key`
+    fn unblock(&self, key: &str, waiter: &Waiter) {
+        let mut shard = self.shard(key).write();
+        if let Some(waiters) = shard.waiters.get_mut(key) {
+            waiters.retain(|queued| !Arc::ptr_eq(&queued.slot, &waiter.slot));
+            if waiters.is_empty() {
+                shard.waiters.remove(key);
+            }
+        }
+    }
+
+    /// Apply `write` to the elements of the list `key` under the lock of its shard. `write` returns
+    /// its result along with the op recording the change in the commit pipeline, `None` if nothing
+    /// changed.
+    ///
+    /// With `create`, a missing key is written as an empty list, an expired one not removed yet
+    /// being replaced. Otherwise `None` is returned for a missing key. A list left empty is
+    /// removed. The keyspace notifications, `event` for the change, are sent once the lock is
+    /// released.
+    fn write_list<T>(
+        &self,
+        key: &str,
+        event: &str,
+        create: bool,
+        write: impl FnOnce(&mut VecDeque<Bytes>) -> crate::Result<(T, Option<WriteOp>)>,
+    ) -> crate::Result<Option<T>> {
+        let mut shard = self.shard(key).write();
+        let now = Instant::now();
+
+        match shard.live_entry(key, now).map(|entry| &entry.value) {
+            Some(Value::List(_)) => {}
+            Some(_) => return Err(crate::Error::WrongType),
+            None if !create => return Ok(None),
+            None => {}
+        }
+
+        // an expired entry not removed yet is replaced, as by `BLOG.APPEND`
+        let expired = shard
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.is_expired(now));
+        if expired {
+            if let Some(entry) = shard.remove_entry(key) {
+                if let Some(when) = entry.expires_at {
+                    shard.expirations.remove(&(when, entry.id));
+                }
+            }
+            self.commits.commit(WriteOp::Expire {
+                key: key.to_string(),
+            });
+        }
+
+        // removed and inserted back so the memory accounting sees the elements change
+        let existed = shard.entries.contains_key(key);
+        let mut entry = shard.remove_entry(key).unwrap_or_else(|| Entry {
+            id: 0,
+            value: Value::List(VecDeque::new()),
+            expires_at: None,
+            reservation: None,
+            modified: SystemTime::now(),
+        });
+        let elements = match &mut entry.value {
+            Value::List(elements) => elements,
+            _ => unreachable!(),
+        };
+
+        let (result, op) = match write(elements) {
+            Ok(written) => written,
+            Err(err) => {
+                if existed {
+                    shard.insert_entry(key.to_string(), entry);
+                }
+                return Err(err);
+            }
+        };
+
+        let changed = op.is_some();
+        // the clients blocked on the key are served as soon as the list has elements
+        let served = match changed {
+            true => shard.serve_waiters(key, elements),
+            false => vec![],
+        };
+        let emptied = elements.is_empty();
+
+        let pops = served.iter().map(|&end| WriteOp::ListPop {
+            key: key.to_string(),
+            count: 1,
+            end,
+        });
+        for op in op.into_iter().chain(pops) {
+            // each write makes a new version of the entry, which also keys its expiration
+            let seq = self.commits.commit(op);
+            if let Some(when) = entry.expires_at {
+                shard.expirations.remove(&(when, entry.id));
+                shard.expirations.insert((when, seq), key.to_string());
+            }
+            entry.id = seq;
+            entry.modified = SystemTime::now();
+        }
+        if emptied {
+            if let Some(when) = entry.expires_at {
+                shard.expirations.remove(&(when, entry.id));
+            }
+        } else {
+            shard.insert_entry(key.to_string(), entry);
+        }
+        drop(shard);
+
+        if expired {
+            self.notify_keyspace_event(Class::Expired, "expired", key);
+        }
+        if changed {
+            self.notify_keyspace_event(Class::List, event, key);
+        }
+        for end in served {
+            let event = match end {
+                ListEnd::Head => "lpop",
+                ListEnd::Tail => "rpop",
+            };
+            self.notify_keyspace_event(Class::List, event, key);
+        }
+        if emptied && existed {
+            self.notify_keyspace_event(Class::Generic, "del", key);
+        }
+        Ok(Some(result))
+    }
 
-        if state.shutdown {
+    /// Remove the expired keys of every shard. Returns when the next key expires, if any.
+    fn purge_expired_keys(&self) -> Option<Instant> {
+        if self.is_shutdown() {
             return None;
         }
 
-        let state = &mut *state;
+        let mut next = None;
+        let (keyspace, keyevent) = self
+            .config
+            .read(|settings| settings.notify_keyspace_events.channels(Class::Expired));
+        for shard in self.shards.iter() {
+            let (expires, expired, dead_letters) =
+                self.purge_shard(&mut shard.write(), keyspace || keyevent);
+            next = next.into_iter().chain(expires).min();
+
+            for key in expired {
+                self.notify_keyspace_event(Class::Expired, "expired", &key);
+            }
+
+            // the reservations were never confirmed, hand the data over to the dead-letter lists
+            for (key, dead_letter, data) in dead_letters {
+                let data = match self.decode(&key, data) {
+                    Ok(data) => data,
+                    Err(err) => {
+                        warn!(cause = %err, %key, "failed to decode expired reservation");
+                        continue;
+                    }
+                };
+                // the waiters of a lock only need to know it is free again, a notification
+                // nobody receives is fine
+                if dead_letter.starts_with(LOCK_EXPIRED_PREFIX) {
+                    self.publish(&dead_letter, data);
+                } else if let Err(err) = self.list_push(&dead_letter, vec![data], ListEnd::Tail) {
+                    warn!(cause = %err, %key, %dead_letter, "failed to push expired reservation");
+                }
+            }
+        }
+        next
+    }
+
+    /// Remove the expired keys of `shard`. Returns when its next key expires, the expired keys if
+    /// `notify` is set, and the expired reservations as `(key, dead-letter list, data)`.
+    fn purge_shard(
+        &self,
+        shard: &mut Shard,
+        notify: bool,
+    ) -> (Option<Instant>, Vec<String>, Vec<DeadLetter>) {
         let now = Instant::now();
+        let mut expired = vec![];
+        let mut dead_letters = vec![];
 
-        while let Some((&(when, id), key)) = state.expirations.iter().next() {
+        while let Some((&(when, id), key)) = shard.expirations.iter().next() {
             if when > now {
-                return Some(when);
+                return (Some(when), expired, dead_letters);
+            }
+            let key = key.clone();
+            if let Some(entry) = shard.remove_entry(&key) {
+                if let (Some(dead_letter), Value::String(data)) = (entry.reservation, entry.value) {
+                    dead_letters.push((key.clone(), dead_letter, data));
+                }
+            }
+            shard.expirations.remove(&(when, id));
+
+            if notify {
+                expired.push(key.clone());
+            }
+            self.commits.commit(WriteOp::Expire { key });
+        }
+        (None, expired, dead_letters)
+    }
+
+    /// Rebuild the maps whose allocation is more than twice what they hold.
+    ///
+    /// `HashMap` never gives memory back on its own, so after a mass deletion or expiry the table
+    /// stays at its peak size. Returns `true` if any map was rebuilt.
+    fn defrag(&self) -> bool {
+        fn is_fragmented(len: usize, capacity: usize) -> bool {
+            capacity - len >= DEFRAG_MIN_WASTED_SLOTS && capacity > len * 2
+        }
+
+        let mut rebuilt = false;
+
+        for shard in self.shards.iter() {
+            let mut shard = shard.write();
+            if is_fragmented(shard.entries.len(), shard.entries.capacity()) {
+                shard.entries.shrink_to_fit();
+                rebuilt = true;
             }
-            state.entries.remove(key);
-            state.expirations.remove(&(when, id));
         }
-        None
+
+        let mut state = self.state.lock().unwrap();
+        if is_fragmented(state.pub_sub.len(), state.pub_sub.capacity()) {
+            state.pub_sub.shrink_to_fit();
+            rebuilt = true;
+        }
+
+        if rebuilt {
+            self.defrag_runs.fetch_add(1, Ordering::Relaxed);
+        }
+        rebuilt
+    }
+
+    /// Drop the channels without subscribers. Returns the number of channels dropped.
+    fn sweep_channels(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let before = state.pub_sub.len();
+
+        let retired = &mut state.retired_channels;
+        state.pub_sub.retain(|_, channel| {
+            let idle = channel.tx.receiver_count() == 0;
+            if idle {
+                retired.add(channel);
+            }
+            !idle
+        });
+        before - state.pub_sub.len()
     }
 
     fn is_shutdown(&self) -> bool {
@@ -208,7 +3085,200 @@ impl Shared {
     }
 }
 
-impl State {
+impl Blocked<'_> {
+    /// Whether an element was handed to the client
+    fn is_served(&self) -> bool {
+        self.waiter.slot.lock().unwrap().is_none()
+    }
+
+    /// Stop serving the client. Returns `false` if it was handed an element already.
+    fn cancel(&self) -> bool {
+        self.waiter.slot.lock().unwrap().take().is_some()
+    }
+}
+
+impl Drop for Blocked<'_> {
+    fn drop(&mut self) {
+        let served = !self.cancel();
+        for key in &self.keys {
+            self.shared.unblock(key, &self.waiter);
+        }
+
+        // the client stopped waiting after being handed an element, which goes back to the list
+        if served {
+            if let Ok((key, stored)) = self.rx.try_recv() {
+                let res = self.shared.decode(&key, stored).and_then(|element| {
+                    self.shared.list_push(&key, vec![element], self.waiter.end)
+                });
+                if let Err(err) = res {
+                    warn!(%key, %err, "failed to put back the element of a client which stopped waiting");
+                }
+            }
+        }
+    }
+}
+
+/// Position of `index` in a list of `len` elements, negative indexes counting from the tail.
+/// `None` if it is out of range.
+fn list_index(index: i64, len: usize) -> Option<usize> {
+    let len = len as i64;
+    let index = if index < 0 { len + index } else { index };
+    (0..len).contains(&index).then_some(index as usize)
+}
+
+/// Random index in a collection of `len` elements, `len` being at least one
+fn random_index(len: usize) -> usize {
+    ((random_unit() * len as f64) as usize).min(len - 1)
+}
+
+impl Value {
+    /// Name of the type, as reported by `DEBUG OBJECT`
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Blog(_) => "blog",
+            Value::List(_) => "list",
+            Value::Hash(_) => "hash",
+            Value::SortedSet(_) => "zset",
+        }
+    }
+
+    /// Size of the value as stored
+    fn len(&self) -> usize {
+        match self {
+            Value::String(data) => data.len(),
+            Value::Blog(blog) => blog.len(),
+            Value::List(elements) => elements.iter().map(Bytes::len).sum(),
+            Value::Hash(hash) => hash.bytes(),
+            Value::SortedSet(set) => set.bytes(),
+        }
+    }
+
+    /// Whether the value holds elements and has none left, the key is then removed
+    fn is_empty_collection(&self) -> bool {
+        match self {
+            Value::String(_) | Value::Blog(_) => false,
+            Value::List(elements) => elements.is_empty(),
+            Value::Hash(hash) => hash.is_empty(),
+            Value::SortedSet(set) => set.is_empty(),
+        }
+    }
+
+    fn as_string(&self) -> crate::Result<&Bytes> {
+        match self {
+            Value::String(data) => Ok(data),
+            _ => Err(crate::Error::WrongType),
+        }
+    }
+}
+
+impl Entry {
+    /// Estimated number of bytes held for the entry of `key`: the map slot, the key and the value,
+    /// along with the slot in the expiration index and the dead-letter list name if any
+    fn usage(&self, key: &str) -> usize {
+        let mut bytes = mem::size_of::<(String, Entry)>() + key.len() + self.value.len();
+        if self.expires_at.is_some() {
+            bytes += mem::size_of::<((Instant, u64), String)>() + key.len();
+        }
+        if let Some(dead_letter) = &self.reservation {
+            bytes += dead_letter.len();
+        }
+        bytes
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.map(|when| when <= now).unwrap_or(false)
+    }
+
+    /// Whether the entry is a lock taken with `Db::lock`
+    fn is_lock(&self) -> bool {
+        self.reservation
+            .as_ref()
+            .map(|dead_letter| dead_letter.starts_with(LOCK_EXPIRED_PREFIX))
+            .unwrap_or(false)
+    }
+}
+
+impl Shard {
+    /// Make `seq`, the sequence number of a write changing the entry of `key` in place, the
+    /// identifier of the entry. Its expiration is keyed by the identifier and moves along.
+    fn set_id(&mut self, key: &str, seq: u64) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            if let Some(when) = entry.expires_at {
+                self.expirations.remove(&(when, entry.id));
+                self.expirations.insert((when, seq), key.to_string());
+            }
+            entry.id = seq;
+        }
+    }
+
+    /// Hand the elements of the list `key` to the clients blocked on it, one each, first blocked
+    /// first served. Returns the ends the elements were popped from.
+    fn serve_waiters(&mut self, key: &str, elements: &mut VecDeque<Bytes>) -> Vec<ListEnd> {
+        let waiters = match self.waiters.get_mut(key) {
+            Some(waiters) => waiters,
+            None => return vec![],
+        };
+
+        let mut served = vec![];
+        while !elements.is_empty() {
+            let waiter = match waiters.pop_front() {
+                Some(waiter) => waiter,
+                None => break,
+            };
+            // a client served by another key, or which stopped waiting, is skipped
+            let mut slot = waiter.slot.lock().unwrap();
+            let tx = match slot.take() {
+                Some(tx) => tx,
+                None => continue,
+            };
+            let element = match waiter.end {
+                ListEnd::Head => elements.pop_front(),
+                ListEnd::Tail => elements.pop_back(),
+            };
+            match tx.send((key.to_string(), element.unwrap())) {
+                Ok(()) => served.push(waiter.end),
+                Err((_, element)) => match waiter.end {
+                    ListEnd::Head => elements.push_front(element),
+                    ListEnd::Tail => elements.push_back(element),
+                },
+            }
+        }
+        if waiters.is_empty() {
+            self.waiters.remove(key);
+        }
+        served
+    }
+
+    /// Insert the entry of `key`, accounting for its memory usage. Returns the entry it replaced.
+    fn insert_entry(&mut self, key: String, entry: Entry) -> Option<Entry> {
+        let replaced = self.entries.get(&key).map_or(0, |prev| prev.usage(&key));
+        self.account(entry.usage(&key), replaced);
+        self.entries.insert(key, entry)
+    }
+
+    /// Remove the entry of `key`, releasing its memory usage
+    fn remove_entry(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.account(0, entry.usage(key));
+        Some(entry)
+    }
+
+    /// Account for entries growing by `added` bytes and shrinking by `removed` bytes, in the shard
+    /// and in the total of the key space
+    fn account(&mut self, added: usize, removed: usize) {
+        self.used_bytes = self.used_bytes + added - removed;
+        // added first so the total never drops below what the shards actually use
+        self.total_bytes.fetch_add(added, Ordering::Relaxed);
+        self.total_bytes.fetch_sub(removed, Ordering::Relaxed);
+    }
+
+    /// The entry of `key` unless it expired. Expired entries stay in place until the background
+    /// task removes them, reads must not see them in the meantime.
+    fn live_entry(&self, key: &str, now: Instant) -> Option<&Entry> {
+        self.entries.get(key).filter(|entry| !entry.is_expired(now))
+    }
+
     fn next_expiration(&self) -> Option<Instant> {
         self.expirations.keys().next().map(|e| e.0)
     }
@@ -216,17 +3286,34 @@ impl State {
 
 /// Routine excuted by the background task
 async fn purge_expired_tasks(shared: Arc<Shared>) {
+    let mut defrag = time::interval(DEFRAG_INTERVAL);
+    let mut channel_sweep = time::interval(CHANNEL_SWEEP_INTERVAL);
+
     while !shared.is_shutdown() {
-        if let Some(when) = shared.purge_expired_keys() {
-            // Wait until the next keys expires or until the background task is notified. If the
-            // task is notified, then it must reload its state as new keys has been set to expire
-            // early. This is done by looping
-            tokio::select! {
-                _ = time::sleep_until(when) => {}
-                _ = shared.background_task.notified() => {}
-            }
+        let next = if shared.active_expire.load(Ordering::Relaxed) {
+            shared.purge_expired_keys()
         } else {
-            shared.background_task.notified().await;
+            None
+        };
+        let active_defrag = shared.config.get().active_defrag;
+
+        // Wait until the next keys expires, the next defragmentation check or until the background
+        // task is notified. If the task is notified, then it must reload its state as new keys has
+        // been set to expire early. This is done by looping
+        tokio::select! {
+            _ = time::sleep_until(next.unwrap_or_else(Instant::now)), if next.is_some() => {}
+            _ = defrag.tick(), if active_defrag => {
+                if shared.defrag() {
+                    debug!("key space defragmented");
+                }
+            }
+            _ = channel_sweep.tick() => {
+                let dropped = shared.sweep_channels();
+                if dropped > 0 {
+                    debug!(dropped, "idle pub/sub channels dropped");
+                }
+            }
+            _ = shared.background_task.notified() => {}
         }
     }
 }
@@ -0,0 +1,74 @@
+//! Run a server within the application, for tests or programs embedding redust instead of
+//! managing a separate process.
+//!
+//! ```no_run
+//! # async fn example() -> redust::Result<()> {
+//! let (mut client, server) = redust::embedded::spawn().await?;
+//! client.set("foo", "bar").await?;
+//!
+//! // other clients can connect to the same server
+//! let mut other = server.connect().await?;
+//! let value: Option<String> = other.get("foo").await?;
+//! assert_eq!(value.as_deref(), Some("bar"));
+//!
+//! server.shutdown().await
+//! # }
+//! ```
+//!
+//! The server listens on an ephemeral port of the loopback interface and runs on the runtime of
+//! the caller, which must be a Tokio one.
+
+use crate::client::{self, Client};
+use crate::server::{self, Server};
+use crate::Store;
+
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+/// A server running in the background. Dropping it shuts the server down without waiting for it
+/// to complete, see `shutdown`.
+#[derive(Debug)]
+pub struct Handle {
+    server: Server,
+    store: Store,
+}
+
+/// Start a server with the default configuration, returns a client connected to it along with
+/// the handle of the server
+pub async fn spawn() -> crate::Result<(Client, Handle)> {
+    spawn_with(server::Builder::new()).await
+}
+
+/// Start a server configured by `builder`, see `spawn`
+pub async fn spawn_with(mut builder: server::Builder) -> crate::Result<(Client, Handle)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let store = builder.build_store();
+    let server = builder.start(listener)?;
+
+    let handle = Handle { server, store };
+    let client = handle.connect().await?;
+    Ok((client, handle))
+}
+
+impl Handle {
+    /// Address the server accepts connections on
+    pub fn addr(&self) -> SocketAddr {
+        self.server.local_addr()
+    }
+
+    /// Direct access to the key space of the server, see `Store`
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// Connect another client to the server
+    pub async fn connect(&self) -> crate::Result<Client> {
+        client::connect(self.addr()).await
+    }
+
+    /// Shut the server down and wait for its connections to be drained
+    pub async fn shutdown(mut self) -> crate::Result<()> {
+        self.server.shutdown();
+        self.server.join().await
+    }
+}
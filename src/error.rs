@@ -0,0 +1,109 @@
+//! Errors returned by the library.
+//!
+//! `Error` is an enum so callers can match on the kind of failure instead of inspecting messages.
+//! Conversions from strings and boxed errors are kept so any error can still be propagated with
+//! `?` or `.into()`.
+
+use crate::{frame, ParseError};
+
+use std::io;
+use std::string::FromUtf8Error;
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The peer sent data which is not valid RESP or not a well formed command
+    #[error("{0}")]
+    Protocol(String),
+
+    /// Reading from or writing to the underlying stream failed
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// The operation doesn't apply to the type of value held by the key
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+
+    /// The command requires the connection to be authenticated first
+    #[error("NOAUTH Authentication required.")]
+    NotAuthenticated,
+
+    /// The peer closed the connection in the middle of a frame or before replying
+    #[error("connection reset by peer")]
+    ConnectionReset,
+
+    /// The server replied with an error
+    #[error("{0}")]
+    Server(String),
+
+    /// The server didn't reply or accept the connection in time
+    #[error("operation timed out")]
+    Timeout,
+
+    /// A subscriber couldn't keep up with `channel` and `missed` messages were dropped. The
+    /// subscription stays active, the following messages are still received.
+    #[error("{missed} messages dropped on channel {channel}, the subscriber lagged behind")]
+    Lagged { channel: String, missed: u64 },
+
+    /// A frame isn't of the `expected` type, see the accessors of [`Frame`](crate::Frame)
+    #[error("unexpected frame, expected {expected}: {frame}")]
+    UnexpectedFrame {
+        expected: &'static str,
+        frame: crate::Frame,
+    },
+
+    /// A command lacks arguments, it was parsed past its last one
+    #[error("protocol error; unexpected end of stream")]
+    EndOfStream,
+
+    /// Any other error
+    #[error(transparent)]
+    Other(#[from] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Error {
+    /// Build the error matching an error reply received from the server
+    pub(crate) fn from_reply(msg: String) -> Error {
+        match msg.split(' ').next() {
+            Some("WRONGTYPE") => Error::WrongType,
+            Some("NOAUTH") => Error::NotAuthenticated,
+            _ => Error::Server(msg),
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(src: String) -> Error {
+        Error::Other(src.into())
+    }
+}
+
+impl From<&str> for Error {
+    fn from(src: &str) -> Error {
+        src.to_string().into()
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(_src: FromUtf8Error) -> Error {
+        Error::Protocol("protocol error; invalid utf-8 string".to_string())
+    }
+}
+
+impl From<frame::Error> for Error {
+    fn from(src: frame::Error) -> Error {
+        match src {
+            frame::Error::Incomplete => Error::Protocol("protocol error; stream ended early".to_string()),
+            frame::Error::Other(err) => err,
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(src: ParseError) -> Error {
+        match src {
+            ParseError::EndOfStream => Error::EndOfStream,
+            ParseError::Other(err) => err,
+        }
+    }
+}
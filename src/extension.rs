@@ -0,0 +1,61 @@
+//! Commands defined by the application embedding the server.
+//!
+//! A handler registered with `server::Builder::register_command` runs when a client sends its
+//! name. It parses the arguments itself and works on the key space through a `Store`:
+//!
+//! ```no_run
+//! use redust::{server, CommandHandler, Frame, Parse, Store};
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! /// `TAKE key limit seconds` replies `1` while `key` was taken less than `limit` times within
+//! /// the window, `0` afterwards
+//! #[derive(Debug)]
+//! struct Take;
+//!
+//! impl CommandHandler for Take {
+//!     fn call(&self, args: &mut Parse, store: &Store) -> redust::Result<Frame> {
+//!         let key = args.next_string()?;
+//!         let limit = args.next_int()? as i64;
+//!         let window = Duration::from_secs(args.next_int()?);
+//!         args.finish()?;
+//!
+//!         let taken = store.incr(&key)?;
+//!         if taken == 1 {
+//!             store.expire(&key, window);
+//!         }
+//!         Ok(Frame::Integer((taken <= limit) as i64))
+//!     }
+//!
+//!     fn keys(&self, args: &mut Parse) -> Option<Vec<String>> {
+//!         args.next_string().ok().map(|key| vec![key])
+//!     }
+//! }
+//!
+//! # fn main() -> redust::Result<()> {
+//! let builder = server::Builder::new().register_command("take", Arc::new(Take))?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Handlers run on the connection task and must not block. They belong to the `write` ACL
+//! category, and the keys declared by `CommandHandler::keys` are checked against the key patterns
+//! of the user. A command whose handler doesn't declare its keys can only be run by the users
+//! allowed every key.
+
+use crate::{Frame, Parse, Store};
+
+use std::fmt;
+
+pub trait CommandHandler: Send + Sync + fmt::Debug {
+    /// Run the command. `args` holds the arguments following the name, the reply is sent to the
+    /// client and an error is sent as an error reply.
+    fn call(&self, args: &mut Parse, store: &Store) -> crate::Result<Frame>;
+
+    /// Keys the command works on, parsed from a copy of the arguments given to `call`. `None`
+    /// when they can't be told.
+    fn keys(&self, args: &mut Parse) -> Option<Vec<String>> {
+        let _ = args;
+        None
+    }
+}
@@ -1,14 +1,20 @@
-use bytes::{Buf, Bytes};
+pub mod pretty;
+
+#[cfg(feature = "serde")]
+mod serde;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 
 use std::convert::TryInto;
 use std::io::Cursor;
+use tracing::debug;
 
 // A Frame in redis protocol
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Frame {
     Simple(String),
     Error(String),
-    Integer(u64),
+    Integer(i64),
     Bulk(Bytes),
     Null,
     Array(Vec<Frame>),
@@ -23,8 +29,57 @@ pub enum Error {
     Other(crate::Error),
 }
 
+/// Limits enforced while checking incoming frames, protecting the server from clients announcing
+/// huge payloads or deeply nested arrays.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Maximum length of a bulk string
+    pub max_bulk_len: usize,
+    /// Maximum number of elements in an array
+    pub max_array_len: usize,
+    /// Maximum nesting of arrays, a frame which is not an array has a depth of 0
+    pub max_depth: usize,
+    /// Maximum size of a whole encoded frame
+    pub max_frame_size: usize,
+}
+
+impl Default for Limits {
+    /// Same defaults as Redis `proto-max-bulk-len` and its multibulk length limit
+    fn default() -> Limits {
+        Limits {
+            max_bulk_len: 512 * 1024 * 1024,
+            max_array_len: 1024 * 1024,
+            max_depth: 32,
+            max_frame_size: 512 * 1024 * 1024,
+        }
+    }
+}
+
 impl Frame {
-    pub(crate) fn array() -> Frame {
+    /// The `+OK` reply
+    pub fn ok() -> Frame {
+        Frame::Simple("OK".to_string())
+    }
+
+    /// An error reply, `msg` starts with the error kind as in `ERR syntax error`
+    pub fn error(msg: impl Into<String>) -> Frame {
+        Frame::Error(msg.into())
+    }
+
+    /// Build an empty array frame, to be filled with the `try_push_*` methods
+    ///
+    /// ```
+    /// use redust::Frame;
+    ///
+    /// # fn main() -> redust::Result<()> {
+    /// let mut request = Frame::array();
+    /// request.try_push_bulk("incrby".into())?;
+    /// request.try_push_bulk("visits".into())?;
+    /// request.try_push_int(5)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn array() -> Frame {
         Frame::Array(vec![])
     }
 
@@ -32,7 +87,10 @@ impl Frame {
     /// # Panics
     ///
     /// panics if `self` is not an array
-    pub(crate) fn push_bulk(&mut self, bytes: Bytes) {
+    #[deprecated(
+        note = "panics if the frame isn't an array, use `try_push_bulk` or build a `Frame::Array`"
+    )]
+    pub fn push_bulk(&mut self, bytes: Bytes) {
         match self {
             Frame::Array(vec) => {
                 vec.push(Frame::Bulk(bytes));
@@ -41,7 +99,14 @@ impl Frame {
         }
     }
 
-    pub(crate) fn push_int(&mut self, value: u64) {
+    /// Push an integer frame into the array, `self` must be an Array frame
+    /// # Panics
+    ///
+    /// panics if `self` is not an array
+    #[deprecated(
+        note = "panics if the frame isn't an array, use `try_push_int` or build a `Frame::Array`"
+    )]
+    pub fn push_int(&mut self, value: i64) {
         match self {
             Frame::Array(vec) => {
                 vec.push(Frame::Integer(value));
@@ -50,37 +115,120 @@ impl Frame {
         }
     }
 
-    pub fn check(src: &mut Cursor<&[u8]>) -> Result<(), Error> {
-        match get_u8(src)? {
-            b'+' => {
-                get_line(src)?;
-                Ok(())
-            }
-            b'-' => {
-                get_line(src)?;
-                Ok(())
-            }
-            b':' => {
-                get_decimal(src)?;
-                Ok(())
-            }
-            b'$' => {
-                if b'-' == peek_u8(src)? {
-                    skip(src, 4)
-                } else {
-                    let len: usize = get_decimal(src)?.try_into()?;
-                    skip(src, len + 2)
-                }
+    /// Push a null frame into the array, `self` must be an Array frame
+    /// # Panics
+    ///
+    /// panics if `self` is not an array
+    #[deprecated(
+        note = "panics if the frame isn't an array, use `try_push_null` or build a `Frame::Array`"
+    )]
+    pub fn push_null(&mut self) {
+        match self {
+            Frame::Array(vec) => {
+                vec.push(Frame::Null);
             }
-            b'*' => {
-                let len = get_decimal(src)?;
-                for _ in 0..len {
-                    Frame::check(src)?;
-                }
+            _ => panic!("not an array frame"),
+        }
+    }
+
+    /// Push a bulk frame into the array, fails with [`UnexpectedFrame`] if `self` is not an array
+    ///
+    /// [`UnexpectedFrame`]: crate::Error::UnexpectedFrame
+    pub fn try_push_bulk(&mut self, bytes: Bytes) -> crate::Result<()> {
+        self.try_push(Frame::Bulk(bytes))
+    }
+
+    /// Push an integer frame into the array, fails if `self` is not an array
+    pub fn try_push_int(&mut self, value: i64) -> crate::Result<()> {
+        self.try_push(Frame::Integer(value))
+    }
+
+    /// Push a null frame into the array, fails if `self` is not an array
+    pub fn try_push_null(&mut self) -> crate::Result<()> {
+        self.try_push(Frame::Null)
+    }
+
+    fn try_push(&mut self, entry: Frame) -> crate::Result<()> {
+        match self {
+            Frame::Array(vec) => {
+                vec.push(entry);
                 Ok(())
             }
-            actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
+            frame => Err(frame.clone().unexpected("array")),
+        }
+    }
+
+    /// The payload of a bulk frame
+    pub fn as_bulk(&self) -> crate::Result<&Bytes> {
+        match self {
+            Frame::Bulk(data) => Ok(data),
+            frame => Err(frame.clone().unexpected("bulk")),
+        }
+    }
+
+    /// The value of an integer frame
+    pub fn as_int(&self) -> crate::Result<i64> {
+        match self {
+            Frame::Integer(value) => Ok(*value),
+            frame => Err(frame.clone().unexpected("integer")),
+        }
+    }
+
+    /// The entries of an array frame
+    pub fn into_array(self) -> crate::Result<Vec<Frame>> {
+        match self {
+            Frame::Array(entries) => Ok(entries),
+            frame => Err(frame.unexpected("array")),
+        }
+    }
+
+    fn unexpected(self, expected: &'static str) -> crate::Error {
+        crate::Error::UnexpectedFrame {
+            expected,
+            frame: self,
+        }
+    }
+
+    /// Check whether a full frame is buffered in `src`, using the default `Limits`
+    pub fn check(src: &mut Cursor<&[u8]>) -> Result<(), Error> {
+        Frame::check_with_limits(src, &Limits::default())
+    }
+
+    /// Check whether a full frame is buffered in `src`.
+    ///
+    /// Announced lengths are validated against `limits` before waiting for the data, so a
+    /// client can't make the caller buffer an arbitrarily large frame.
+    pub fn check_with_limits(src: &mut Cursor<&[u8]>, limits: &Limits) -> Result<(), Error> {
+        let start = src.position();
+        check_frame(src, limits, 0)?;
+
+        if (src.position() - start) as usize > limits.max_frame_size {
+            return Err("protocol error; frame too large".into());
         }
+        Ok(())
+    }
+
+    /// Parse the frame at the start of `src`, returning it along with the number of bytes it
+    /// spans. `Error::Incomplete` is returned when `src` doesn't hold a whole frame yet, the
+    /// caller should try again once more data is buffered.
+    ///
+    /// The frame is checked against the default `Limits` first, so any input can be passed.
+    ///
+    /// ```
+    /// use redust::frame::{self, Frame};
+    ///
+    /// let (frame, len) = Frame::parse_partial(b":42\r\n+OK").unwrap();
+    /// assert!(matches!(frame, Frame::Integer(42)));
+    /// assert_eq!(len, 5);
+    ///
+    /// assert!(matches!(Frame::parse_partial(b"$5\r\nhel"), Err(frame::Error::Incomplete)));
+    /// ```
+    pub fn parse_partial(src: &[u8]) -> Result<(Frame, usize), Error> {
+        let mut cursor = Cursor::new(src);
+        Frame::check(&mut cursor)?;
+        cursor.set_position(0);
+        let frame = Frame::parse(&mut cursor)?;
+        Ok((frame, cursor.position() as usize))
     }
 
     /// The message has alraedy been validated with `check`
@@ -97,10 +245,7 @@ impl Frame {
                 let string = String::from_utf8(line)?;
                 Ok(Frame::Error(string))
             }
-            b':' => {
-                let len = get_decimal(src)?;
-                Ok(Frame::Integer(len))
-            }
+            b':' => Ok(Frame::Integer(get_integer(src)?)),
             // bulk string
             b'$' => {
                 // check null string: `$-1\r\n`
@@ -121,6 +266,10 @@ impl Frame {
                     return Err(Error::Incomplete);
                 }
 
+                if &src.chunk()[len..next_cursor] != b"\r\n" {
+                    return Err("protocol error; invalid frame format".into());
+                }
+
                 let data = Bytes::copy_from_slice(&src.chunk()[..len]);
                 // move the cursor end of line
                 skip(src, next_cursor)?;
@@ -137,12 +286,80 @@ impl Frame {
                 }
                 Ok(Frame::Array(out))
             }
-            _ => unimplemented!(),
+            actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
+        }
+    }
+
+    /// Serialize the frame, in the redis protocol, into a new buffer
+    pub fn to_bytes(&self) -> Bytes {
+        let mut dst = BytesMut::new();
+        self.encode(&mut dst);
+        dst.freeze()
+    }
+
+    /// Append the frame, encoded in the redis protocol, to `dst`
+    pub fn encode(&self, dst: &mut BytesMut) {
+        match self {
+            Frame::Simple(val) => {
+                dst.put_u8(b'+');
+                dst.put_slice(val.as_bytes());
+                dst.put_slice(b"\r\n");
+            }
+            Frame::Error(val) => {
+                dst.put_u8(b'-');
+                dst.put_slice(val.as_bytes());
+                dst.put_slice(b"\r\n");
+            }
+            Frame::Integer(val) => {
+                dst.put_u8(b':');
+                put_decimal(dst, *val);
+            }
+            Frame::Null => {
+                dst.put_slice(b"$-1\r\n");
+            }
+            Frame::Bulk(val) => {
+                dst.put_u8(b'$');
+                put_decimal(dst, val.len() as u64);
+                dst.put_slice(val);
+                dst.put_slice(b"\r\n");
+            }
+            Frame::Array(val) => {
+                dst.put_u8(b'*');
+                put_decimal(dst, val.len() as u64);
+                for entry in val {
+                    entry.encode(dst);
+                }
+            }
         }
     }
 
+    /// Error reporting the frame wasn't expected. The message holds the frame on a single line, as
+    /// it may be sent back in a RESP error, the full rendering is logged.
     pub(crate) fn to_error(&self) -> crate::Error {
-        format!("unexpected frame: {}", self).into()
+        debug!("unexpected frame:\n{}", self);
+        format!("unexpected frame: {}", pretty::inline(self)).into()
+    }
+
+    /// Wrap `frame` with a correlation tag, as exchanged on tagged connections: `*2 :tag frame`
+    pub(crate) fn tagged(tag: u64, frame: Frame) -> Frame {
+        Frame::Array(vec![Frame::Integer(tag as i64), frame])
+    }
+
+    /// Split a tagged frame into its tag and the frame it wraps. The frame is given back as is
+    /// if it isn't tagged.
+    pub(crate) fn untag(self) -> Result<(u64, Frame), Frame> {
+        match self {
+            Frame::Array(mut parts)
+                if parts.len() == 2 && matches!(parts[0], Frame::Integer(tag) if tag >= 0) =>
+            {
+                let frame = parts.pop().unwrap();
+                match parts.pop() {
+                    Some(Frame::Integer(tag)) => Ok((tag as u64, frame)),
+                    _ => unreachable!(),
+                }
+            }
+            frame => Err(frame),
+        }
     }
 }
 
@@ -156,28 +373,81 @@ impl PartialEq<&str> for Frame {
     }
 }
 
+impl From<&str> for Frame {
+    /// A bulk string holding `src`
+    fn from(src: &str) -> Frame {
+        Frame::Bulk(Bytes::copy_from_slice(src.as_bytes()))
+    }
+}
+
+impl From<String> for Frame {
+    /// A bulk string holding `src`
+    fn from(src: String) -> Frame {
+        Frame::Bulk(Bytes::from(src))
+    }
+}
+
+impl From<Bytes> for Frame {
+    fn from(src: Bytes) -> Frame {
+        Frame::Bulk(src)
+    }
+}
+
+impl From<i64> for Frame {
+    fn from(src: i64) -> Frame {
+        Frame::Integer(src)
+    }
+}
+
 impl std::fmt::Display for Frame {
+    /// Renders the frame the way `redis-cli` does, see [`pretty`]
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        use std::str;
-        match self {
-            Frame::Simple(r) => r.fmt(fmt),
-            Frame::Error(msg) => write!(fmt, "error: {}", msg),
-            Frame::Integer(num) => num.fmt(fmt),
-            Frame::Bulk(msg) => match str::from_utf8(msg) {
-                Ok(s) => s.fmt(fmt),
-                Err(_) => write!(fmt, "{:?}", msg),
-            },
-            Frame::Null => "(nil)".fmt(fmt),
-            Frame::Array(parts) => {
-                for (i, part) in parts.iter().enumerate() {
-                    if i > 0 {
-                        write!(fmt, " ")?;
-                        part.fmt(fmt)?;
-                    }
+        pretty::pretty(self).fmt(fmt)
+    }
+}
+
+fn check_frame(src: &mut Cursor<&[u8]>, limits: &Limits, depth: usize) -> Result<(), Error> {
+    match get_u8(src)? {
+        b'+' => {
+            get_line(src)?;
+            Ok(())
+        }
+        b'-' => {
+            get_line(src)?;
+            Ok(())
+        }
+        b':' => {
+            get_integer(src)?;
+            Ok(())
+        }
+        b'$' => {
+            if b'-' == peek_u8(src)? {
+                if get_line(src)? != b"-1" {
+                    return Err("protocol error; invalid frame format".into());
                 }
                 Ok(())
+            } else {
+                let len: usize = get_decimal(src)?.try_into()?;
+                if len > limits.max_bulk_len {
+                    return Err("protocol error; invalid bulk length".into());
+                }
+                skip(src, len + 2)
             }
         }
+        b'*' => {
+            let len: usize = get_decimal(src)?.try_into()?;
+            if len > limits.max_array_len {
+                return Err("protocol error; invalid multibulk length".into());
+            }
+            if len > 0 && depth >= limits.max_depth {
+                return Err("protocol error; frame nested too deeply".into());
+            }
+            for _ in 0..len {
+                check_frame(src, limits, depth + 1)?;
+            }
+            Ok(())
+        }
+        actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
     }
 }
 
@@ -204,6 +474,14 @@ fn skip(src: &mut Cursor<&[u8]>, n: usize) -> Result<(), Error> {
     Ok(())
 }
 
+/// Write a new line terminated decimal
+fn put_decimal(dst: &mut BytesMut, val: impl itoa::Integer) {
+    // `itoa` formats into a stack buffer large enough for any integer
+    let mut buf = itoa::Buffer::new();
+    dst.put_slice(buf.format(val).as_bytes());
+    dst.put_slice(b"\r\n");
+}
+
 /// Read a new line terminated decimal
 fn get_decimal(src: &mut Cursor<&[u8]>) -> Result<u64, Error> {
     use atoi::atoi;
@@ -211,25 +489,33 @@ fn get_decimal(src: &mut Cursor<&[u8]>) -> Result<u64, Error> {
     atoi::<u64>(line).ok_or_else(|| "protocol error; invalid frame format".into())
 }
 
+/// Read a new line terminated decimal which may be negative, the value of an integer frame
+fn get_integer(src: &mut Cursor<&[u8]>) -> Result<i64, Error> {
+    use atoi::atoi;
+    let line = get_line(src)?;
+    atoi::<i64>(line).ok_or_else(|| "protocol error; invalid frame format".into())
+}
+
 // Find a line, return buffer and set the cursor to end after `\n`
 fn get_line<'a>(src: &mut Cursor<&'a [u8]>) -> Result<&'a [u8], Error> {
     // scan the line directly
     let start = src.position() as usize;
-    // scan the second last byte
-    let end = src.get_ref().len() - 1;
-    for i in start..end {
-        if src.get_ref()[i] == b'\r' && src.get_ref()[i + 1] == b'\n' {
-            // we found a line, update the potiion th be *after* the \n
-            src.set_position((i + 2) as u64);
-            return Ok(&src.get_ref()[start..i]);
+    let buf: &'a [u8] = src.get_ref();
+    // the cursor may already be at the end of the buffer
+    let rest = buf.get(start..).unwrap_or_default();
+    match rest.windows(2).position(|pair| pair == b"\r\n") {
+        Some(len) => {
+            // we found a line, update the position to be *after* the \n
+            src.set_position((start + len + 2) as u64);
+            Ok(&buf[start..start + len])
         }
+        None => Err(Error::Incomplete),
     }
-    Err(Error::Incomplete)
 }
 
 impl From<String> for Error {
     fn from(src: String) -> Self {
-        Error::Other(src.into())
+        Error::Other(crate::Error::Protocol(src))
     }
 }
 
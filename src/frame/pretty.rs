@@ -0,0 +1,105 @@
+//! Human readable rendering of frames, in the same layout `redis-cli` uses.
+//!
+//! Bulk strings are quoted and binary-safe: non printable bytes are escaped as `\xHH` so the
+//! output never loses information. Arrays are rendered as indexed items, nested arrays are
+//! indented under their parent index. `inline` renders the same items on a single line, for
+//! error messages.
+
+use crate::Frame;
+
+use std::fmt::{self, Write};
+
+/// Wraps a frame so it renders in the `redis-cli` style when displayed.
+pub struct Pretty<'a> {
+    frame: &'a Frame,
+}
+
+/// Wraps a frame so it renders on a single line when displayed, arrays as `[item, item]`
+pub struct Inline<'a> {
+    frame: &'a Frame,
+}
+
+/// Returns a displayable view of `frame`
+pub fn pretty(frame: &Frame) -> Pretty<'_> {
+    Pretty { frame }
+}
+
+/// Returns a single line displayable view of `frame`
+pub fn inline(frame: &Frame) -> Inline<'_> {
+    Inline { frame }
+}
+
+impl fmt::Display for Pretty<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write_frame(fmt, self.frame, 0)
+    }
+}
+
+impl fmt::Display for Inline<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write_inline(fmt, self.frame)
+    }
+}
+
+/// Write a bulk payload as a double quoted, escaped string
+pub fn write_quoted<W: Write>(dst: &mut W, data: &[u8]) -> fmt::Result {
+    dst.write_char('"')?;
+    for &b in data {
+        match b {
+            b'\\' => dst.write_str("\\\\")?,
+            b'"' => dst.write_str("\\\"")?,
+            b'\n' => dst.write_str("\\n")?,
+            b'\r' => dst.write_str("\\r")?,
+            b'\t' => dst.write_str("\\t")?,
+            0x07 => dst.write_str("\\a")?,
+            0x08 => dst.write_str("\\b")?,
+            b if b.is_ascii_graphic() || b == b' ' => dst.write_char(b as char)?,
+            b => write!(dst, "\\x{:02x}", b)?,
+        }
+    }
+    dst.write_char('"')
+}
+
+fn write_frame(fmt: &mut fmt::Formatter, frame: &Frame, indent: usize) -> fmt::Result {
+    match frame {
+        Frame::Simple(s) => fmt.write_str(s),
+        Frame::Error(msg) => write!(fmt, "(error) {}", msg),
+        Frame::Integer(num) => write!(fmt, "(integer) {}", num),
+        Frame::Bulk(data) => write_quoted(fmt, data),
+        Frame::Null => fmt.write_str("(nil)"),
+        Frame::Array(parts) if parts.is_empty() => fmt.write_str("(empty array)"),
+        Frame::Array(parts) => {
+            // indexes are right aligned, so every item starts at the same column
+            let width = parts.len().to_string().len();
+            for (i, part) in parts.iter().enumerate() {
+                if i > 0 {
+                    fmt.write_char('\n')?;
+                    write!(fmt, "{:indent$}", "", indent = indent)?;
+                }
+                write!(fmt, "{:>width$}) ", i + 1, width = width)?;
+                write_frame(fmt, part, indent + width + 2)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn write_inline(fmt: &mut fmt::Formatter, frame: &Frame) -> fmt::Result {
+    match frame {
+        Frame::Array(parts) if !parts.is_empty() => {
+            fmt.write_char('[')?;
+            for (i, part) in parts.iter().enumerate() {
+                if i > 0 {
+                    fmt.write_str(", ")?;
+                }
+                write_inline(fmt, part)?;
+            }
+            fmt.write_char(']')
+        }
+        // the other frames fit on a single line, error messages can't hold line breaks
+        Frame::Simple(s) | Frame::Error(s) if s.contains(['\r', '\n']) => {
+            write_quoted(fmt, s.as_bytes())
+        }
+        frame => write_frame(fmt, frame, 0),
+    }
+}
@@ -0,0 +1,126 @@
+//! Serde support for frames, enabled with the `serde` feature.
+//!
+//! Frames map onto the serde data model the natural way, so captured traffic reads well as JSON:
+//!
+//! - arrays are sequences,
+//! - bulk strings are strings when they hold valid UTF-8, and bytes otherwise,
+//! - integers are signed integers,
+//! - null is unit, `null` in JSON,
+//! - simple strings and errors are single entry maps, `{"simple": "OK"}` and
+//!   `{"error": "ERR unknown command"}`, so they don't read back as bulk strings.
+//!
+//! Formats without a bytes type, JSON among them, write binary bulk strings as a sequence of
+//! integers, which reads back as an array.
+
+use crate::Frame;
+
+use ::serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use ::serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use bytes::Bytes;
+use std::convert::TryFrom;
+use std::fmt;
+
+impl Serialize for Frame {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Frame::Simple(val) => single_entry(serializer, "simple", val),
+            Frame::Error(val) => single_entry(serializer, "error", val),
+            Frame::Integer(val) => serializer.serialize_i64(*val),
+            Frame::Bulk(val) => match std::str::from_utf8(val) {
+                Ok(val) => serializer.serialize_str(val),
+                Err(_) => serializer.serialize_bytes(val),
+            },
+            Frame::Null => serializer.serialize_unit(),
+            Frame::Array(val) => {
+                let mut seq = serializer.serialize_seq(Some(val.len()))?;
+                for entry in val {
+                    seq.serialize_element(entry)?;
+                }
+                seq.end()
+            }
+        }
+    }
+}
+
+fn single_entry<S: Serializer>(serializer: S, key: &str, val: &str) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(key, val)?;
+    map.end()
+}
+
+impl<'de> Deserialize<'de> for Frame {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Frame, D::Error> {
+        deserializer.deserialize_any(FrameVisitor)
+    }
+}
+
+struct FrameVisitor;
+
+impl<'de> Visitor<'de> for FrameVisitor {
+    type Value = Frame;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("a RESP frame")
+    }
+
+    fn visit_i64<E: de::Error>(self, val: i64) -> Result<Frame, E> {
+        Ok(Frame::Integer(val))
+    }
+
+    fn visit_u64<E: de::Error>(self, val: u64) -> Result<Frame, E> {
+        // self describing formats hand out the positive integers as u64
+        match i64::try_from(val) {
+            Ok(val) => Ok(Frame::Integer(val)),
+            Err(_) => Err(E::invalid_value(de::Unexpected::Unsigned(val), &self)),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, val: &str) -> Result<Frame, E> {
+        Ok(Frame::Bulk(Bytes::copy_from_slice(val.as_bytes())))
+    }
+
+    fn visit_string<E: de::Error>(self, val: String) -> Result<Frame, E> {
+        Ok(Frame::Bulk(Bytes::from(val)))
+    }
+
+    fn visit_bytes<E: de::Error>(self, val: &[u8]) -> Result<Frame, E> {
+        Ok(Frame::Bulk(Bytes::copy_from_slice(val)))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, val: Vec<u8>) -> Result<Frame, E> {
+        Ok(Frame::Bulk(Bytes::from(val)))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Frame, E> {
+        Ok(Frame::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Frame, E> {
+        Ok(Frame::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Frame, D::Error> {
+        Frame::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Frame, A::Error> {
+        let mut entries = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(entry) = seq.next_element()? {
+            entries.push(entry);
+        }
+        Ok(Frame::Array(entries))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Frame, A::Error> {
+        let frame = match map.next_key::<String>()?.as_deref() {
+            Some("simple") => Frame::Simple(map.next_value()?),
+            Some("error") => Frame::Error(map.next_value()?),
+            Some(key) => return Err(de::Error::unknown_field(key, &["simple", "error"])),
+            None => return Err(de::Error::invalid_length(0, &"a single entry map")),
+        };
+        if map.next_key::<de::IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(2, &"a single entry map"));
+        }
+        Ok(frame)
+    }
+}
@@ -0,0 +1,251 @@
+//! HTTP API for services which can't speak RESP. Enabled by the `http-gateway` feature, see
+//! `server::Builder::http_gateway`.
+//!
+//! * `GET /keys/{key}` replies with the value, `404` if the key doesn't exist.
+//! * `PUT /keys/{key}` sets the key to the body of the request, `?ex=seconds` or
+//!   `?px=milliseconds` set an expiration.
+//! * `DELETE /keys/{key}` removes the key, `404` if it didn't exist.
+//! * `POST /publish/{channel}` publishes the body, replies with the number of subscribers which
+//!   received it.
+//!
+//! Keys and channels are percent-decoded. Each request runs as the matching command through the
+//! same layers as the RESP connections, so ACLs, rate limits and timeouts apply alike. Requests are
+//! authenticated with HTTP Basic credentials, as `AUTH username password` would, or run as the
+//! default user when there are none.
+//!
+//! Errors are answered with the status closest to the RESP error, its message in the body:
+//! `401` for `NOAUTH`, `403` for `NOPERM`, `429` for `BUSY`, `504` for `TIMEOUT`.
+
+use crate::cmd::{self, Del, Get, Publish, Set};
+use crate::middleware::{Context, Next};
+use crate::{Command, Connection, Db, Frame, Layer, Shutdown};
+
+use bytes::{Bytes, BytesMut};
+use hyper::body::HttpBody;
+use hyper::header::{self, HeaderValue};
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tracing::{debug, warn};
+
+/// Runs the requests as commands
+pub(crate) struct Gateway {
+    pub(crate) db: Db,
+    pub(crate) layers: Arc<[Arc<dyn Layer>]>,
+    pub(crate) shutdown: Shutdown,
+    /// Largest body accepted, the maximum size of a RESP frame
+    pub(crate) max_body: usize,
+}
+
+/// What a request asks for
+enum Route {
+    Key(String),
+    Publish(String),
+}
+
+/// Answer the requests received on `listener`
+pub(crate) async fn serve(listener: TcpListener, gateway: Gateway) {
+    let gateway = Arc::new(gateway);
+    loop {
+        let (socket, peer_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                warn!(cause = %err, "failed to accept an HTTP connection");
+                continue;
+            }
+        };
+
+        let gateway = gateway.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                let gateway = gateway.clone();
+                async move { Ok::<_, Infallible>(gateway.handle(request, peer_addr).await) }
+            });
+            if let Err(err) = Http::new().http1_only(true).serve_connection(socket, service).await {
+                debug!(cause = %err, "HTTP connection failed");
+            }
+        });
+    }
+}
+
+impl Gateway {
+    async fn handle(&self, request: Request<Body>, peer_addr: SocketAddr) -> Response<Body> {
+        let route = match route(request.uri().path()) {
+            Some(route) => route,
+            None => return text(StatusCode::NOT_FOUND, "not found"),
+        };
+
+        let user = match credentials(&request) {
+            Some(Some((username, password))) if self.db.authenticate(&username, &password) => Some(username),
+            Some(Some(_)) => return unauthorized("WRONGPASS invalid username-password pair or user is disabled."),
+            Some(None) => return text(StatusCode::BAD_REQUEST, "invalid Authorization header"),
+            None => self.db.default_login(),
+        };
+
+        let method = request.method().clone();
+        let query = request.uri().query().unwrap_or_default().to_string();
+        let body = match self.read_body(request.into_body()).await {
+            Ok(body) => body,
+            Err(response) => return response,
+        };
+
+        let cmd = match (&method, route) {
+            (&Method::GET, Route::Key(key)) => Command::Get(Get::new(key)),
+            (&Method::PUT, Route::Key(key)) => match expiration(&query) {
+                Ok(expire) => Command::Set(Set::new(key, body, expire)),
+                Err(msg) => return text(StatusCode::BAD_REQUEST, msg),
+            },
+            (&Method::DELETE, Route::Key(key)) => Command::Del(Del::new(vec![key])),
+            (&Method::POST, Route::Publish(channel)) => Command::Publish(Publish::new(channel, body)),
+            (_, Route::Key(_)) => return not_allowed("GET, PUT, DELETE"),
+            (_, Route::Publish(_)) => return not_allowed("POST"),
+        };
+        debug!(?cmd, %peer_addr, "HTTP request");
+
+        match self.run(cmd, user, peer_addr).await.pop() {
+            Some(Frame::Bulk(value)) => Response::new(Body::from(value)),
+            Some(Frame::Null) | Some(Frame::Integer(0)) if method != Method::POST => {
+                text(StatusCode::NOT_FOUND, "not found")
+            }
+            Some(Frame::Simple(_)) | Some(Frame::Integer(_)) if method != Method::POST => {
+                status(StatusCode::NO_CONTENT)
+            }
+            Some(Frame::Integer(receivers)) => text(StatusCode::OK, &receivers.to_string()),
+            Some(Frame::Error(msg)) => error(&msg),
+            reply => {
+                warn!(?reply, "unexpected reply to an HTTP request");
+                status(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+
+    /// Run `cmd` for the client at `peer_addr` authenticated as `user`, returns the frames written
+    async fn run(&self, cmd: Command, user: Option<String>, peer_addr: SocketAddr) -> Vec<Frame> {
+        let mut connection = Connection::capture(Some(peer_addr));
+        connection.set_user(user);
+        let mut shutdown = self.shutdown.resubscribe();
+
+        let mut cx = Context {
+            db: &self.db,
+            connection: &mut connection,
+            shutdown: &mut shutdown,
+        };
+        if let Err(err) = Next::new(&self.layers).run(cmd, &mut cx).await {
+            let _ = connection.write_frame(&cmd::error_reply(&err)).await;
+        }
+        connection.into_captured()
+    }
+
+    async fn read_body(&self, mut body: Body) -> Result<Bytes, Response<Body>> {
+        let mut buf = BytesMut::new();
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk.map_err(|_| text(StatusCode::BAD_REQUEST, "failed to read the body"))?;
+            if buf.len() + chunk.len() > self.max_body {
+                return Err(text(StatusCode::PAYLOAD_TOO_LARGE, "body too large"));
+            }
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(buf.freeze())
+    }
+}
+
+fn route(path: &str) -> Option<Route> {
+    if let Some(key) = path.strip_prefix("/keys/") {
+        percent_decode(key).filter(|key| !key.is_empty()).map(Route::Key)
+    } else if let Some(channel) = path.strip_prefix("/publish/") {
+        percent_decode(channel).filter(|channel| !channel.is_empty()).map(Route::Publish)
+    } else {
+        None
+    }
+}
+
+/// The Basic credentials of the request, `Some(None)` if they can't be decoded
+fn credentials(request: &Request<Body>) -> Option<Option<(String, String)>> {
+    let value = request.headers().get(header::AUTHORIZATION)?;
+    let decoded = value
+        .to_str()
+        .ok()
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| base64::decode(encoded.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok());
+    Some(decoded.and_then(|decoded| {
+        let (username, password) = decoded.split_once(':')?;
+        Some((username.to_string(), password.to_string()))
+    }))
+}
+
+/// The expiration set by the `ex` or `px` query parameter
+fn expiration(query: &str) -> Result<Option<Duration>, &'static str> {
+    let mut expire = None;
+    for param in query.split('&').filter(|param| !param.is_empty()) {
+        let (name, value) = param.split_once('=').unwrap_or((param, ""));
+        let value: u64 = value.parse().map_err(|_| "expiration must be a positive integer")?;
+        expire = match name {
+            "ex" => Some(Duration::from_secs(value)),
+            "px" => Some(Duration::from_millis(value)),
+            _ => return Err("unknown query parameter, expected ex or px"),
+        };
+    }
+    Ok(expire)
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            decoded.push(b);
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// The response matching a RESP error reply
+fn error(msg: &str) -> Response<Body> {
+    match msg.split(' ').next().unwrap_or_default() {
+        "NOAUTH" | "WRONGPASS" => unauthorized(msg),
+        "NOPERM" => text(StatusCode::FORBIDDEN, msg),
+        "BUSY" => text(StatusCode::TOO_MANY_REQUESTS, msg),
+        "TIMEOUT" => text(StatusCode::GATEWAY_TIMEOUT, msg),
+        "WRONGTYPE" => text(StatusCode::CONFLICT, msg),
+        _ => text(StatusCode::BAD_REQUEST, msg),
+    }
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+fn text(status: StatusCode, msg: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(format!("{}\n", msg)));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+    response
+}
+
+fn unauthorized(msg: &str) -> Response<Body> {
+    let mut response = text(StatusCode::UNAUTHORIZED, msg);
+    response
+        .headers_mut()
+        .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Basic realm=\"redust\""));
+    response
+}
+
+fn not_allowed(allow: &'static str) -> Response<Body> {
+    let mut response = text(StatusCode::METHOD_NOT_ALLOWED, "method not allowed");
+    response.headers_mut().insert(header::ALLOW, HeaderValue::from_static(allow));
+    response
+}
@@ -0,0 +1,225 @@
+//! Geohash scores of the GEO commands, see `GEOADD` and `GEOSEARCH`.
+//!
+//! A position is stored in a sorted set with its geohash as the score. The longitude and the
+//! latitude are each split in 26 bits, interleaved into a 52 bit integer which a `f64` holds
+//! exactly, with the same layout as Redis. Positions in the same cell of a grid share a prefix of
+//! their geohash, so the members of an area are found by a few ranges of scores rather than by
+//! walking the whole set. The stored position is the center of the finest cell, within a meter of
+//! the one given.
+
+use bytes::Bytes;
+use std::f64::consts::FRAC_PI_2;
+
+pub(crate) const LONGITUDE_MIN: f64 = -180.0;
+pub(crate) const LONGITUDE_MAX: f64 = 180.0;
+/// Latitudes covered by the Web Mercator projection, as in Redis
+pub(crate) const LATITUDE_MIN: f64 = -85.05112878;
+pub(crate) const LATITUDE_MAX: f64 = 85.05112878;
+
+/// Bits of each coordinate in a geohash
+const STEPS: u32 = 26;
+
+/// Radius of the Earth used for distances, in meters, as in Redis
+const EARTH_RADIUS: f64 = 6372797.560856;
+
+/// Largest number of grid cells searched for an area
+const MAX_CELLS: i64 = 9;
+
+/// Center of a search, see `GEOSEARCH`
+#[derive(Debug, Clone)]
+pub(crate) enum Center {
+    /// The position of a member of the set
+    Member(Bytes),
+    /// A longitude and a latitude
+    Position(f64, f64),
+}
+
+/// Area of a search around its center, see `GEOSEARCH`
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Shape {
+    /// Up to a distance, in meters
+    Radius(f64),
+    /// Within a box, in meters
+    Box { width: f64, height: f64 },
+}
+
+/// A member found by a search
+#[derive(Debug)]
+pub(crate) struct Found {
+    pub(crate) member: Bytes,
+    /// From the center, in meters
+    pub(crate) distance: f64,
+    pub(crate) hash: u64,
+}
+
+/// Whether a longitude and a latitude can be stored
+pub(crate) fn is_valid(longitude: f64, latitude: f64) -> bool {
+    (LONGITUDE_MIN..=LONGITUDE_MAX).contains(&longitude)
+        && (LATITUDE_MIN..=LATITUDE_MAX).contains(&latitude)
+}
+
+/// Geohash of a position, which must be valid
+pub(crate) fn encode(longitude: f64, latitude: f64) -> u64 {
+    let last = (1i64 << STEPS) - 1;
+    let column = index(longitude, LONGITUDE_MIN, LONGITUDE_MAX, STEPS).min(last);
+    let row = index(latitude, LATITUDE_MIN, LATITUDE_MAX, STEPS).min(last);
+    interleave(column as u64, row as u64)
+}
+
+/// Longitude and latitude stored by `hash`, the center of its cell
+pub(crate) fn decode(hash: u64) -> (f64, f64) {
+    let cells = (1u64 << STEPS) as f64;
+    let column = squash(hash >> 1) as f64;
+    let row = squash(hash) as f64;
+    let longitude = LONGITUDE_MIN + (column + 0.5) / cells * (LONGITUDE_MAX - LONGITUDE_MIN);
+    let latitude = LATITUDE_MIN + (row + 0.5) / cells * (LATITUDE_MAX - LATITUDE_MIN);
+    (longitude, latitude)
+}
+
+/// Distance in meters between two positions along the surface of the Earth
+pub(crate) fn distance(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (latitude1, latitude2) = (from.1.to_radians(), to.1.to_radians());
+    let u = ((latitude2 - latitude1) / 2.0).sin();
+    let v = ((to.0 - from.0).to_radians() / 2.0).sin();
+    2.0 * EARTH_RADIUS
+        * (u * u + latitude1.cos() * latitude2.cos() * v * v)
+            .sqrt()
+            .asin()
+}
+
+impl Shape {
+    /// Distance from `center` to `position` if the position is within the shape
+    pub(crate) fn contains(self, center: (f64, f64), position: (f64, f64)) -> Option<f64> {
+        let to_position = distance(center, position);
+        let inside = match self {
+            Shape::Radius(radius) => to_position <= radius,
+            // as in Redis: north-south along the meridian of the center, then east-west along the
+            // parallel of the position
+            Shape::Box { width, height } => {
+                distance(center, (center.0, position.1)) <= height / 2.0
+                    && distance((center.0, position.1), position) <= width / 2.0
+            }
+        };
+        inside.then_some(to_position)
+    }
+
+    /// Farthest distances from the center within the shape, north-south then east-west
+    fn extent(self) -> (f64, f64) {
+        match self {
+            Shape::Radius(radius) => (radius, radius),
+            Shape::Box { width, height } => (height / 2.0, width / 2.0),
+        }
+    }
+}
+
+/// Ranges of scores, `min` included and `max` excluded, holding at least every position within
+/// `shape` around `center`. They are the cells of the finest grid covering the area with a few
+/// cells.
+pub(crate) fn ranges(center: (f64, f64), shape: Shape) -> Vec<(f64, f64)> {
+    let (longitude, latitude) = center;
+    let (north_south, east_west) = shape.extent();
+    let latitude_delta = (north_south / EARTH_RADIUS).to_degrees();
+    let south = (latitude - latitude_delta).max(LATITUDE_MIN);
+    let north = (latitude + latitude_delta).min(LATITUDE_MAX);
+    let longitude_delta = longitude_delta(east_west, south.abs().max(north.abs()));
+
+    let mut steps = STEPS;
+    let (cells, columns, rows) = loop {
+        let cells = 1i64 << steps;
+        let mut columns = (
+            index(
+                longitude - longitude_delta,
+                LONGITUDE_MIN,
+                LONGITUDE_MAX,
+                steps,
+            ),
+            index(
+                longitude + longitude_delta,
+                LONGITUDE_MIN,
+                LONGITUDE_MAX,
+                steps,
+            ),
+        );
+        if columns.1 - columns.0 + 1 >= cells {
+            columns = (0, cells - 1);
+        }
+        let rows = (
+            index(south, LATITUDE_MIN, LATITUDE_MAX, steps).min(cells - 1),
+            index(north, LATITUDE_MIN, LATITUDE_MAX, steps).min(cells - 1),
+        );
+        let count = (columns.1 - columns.0 + 1) * (rows.1 - rows.0 + 1);
+        if count <= MAX_CELLS || steps == 0 {
+            break (cells, columns, rows);
+        }
+        steps -= 1;
+    };
+
+    // a cell of the grid holds the geohashes starting with its own
+    let shift = 2 * (STEPS - steps);
+    let mut ranges = vec![];
+    for row in rows.0..=rows.1 {
+        // the columns past the 180th meridian wrap around
+        for column in columns.0..=columns.1 {
+            let hash = interleave(column.rem_euclid(cells) as u64, row as u64);
+            ranges.push((hash << shift, (hash + 1) << shift));
+        }
+    }
+    ranges.sort_unstable();
+
+    let mut merged: Vec<(u64, u64)> = vec![];
+    for (min, max) in ranges {
+        match merged.last_mut() {
+            Some(last) if last.1 == min => last.1 = max,
+            _ => merged.push((min, max)),
+        }
+    }
+    merged
+        .into_iter()
+        .map(|(min, max)| (min as f64, max as f64))
+        .collect()
+}
+
+/// Span of longitude east or west of a meridian, in degrees, holding the positions up to
+/// `distance` meters from it at latitudes up to `latitude` away from the equator. `180` once it
+/// goes around the Earth.
+fn longitude_delta(distance: f64, latitude: f64) -> f64 {
+    let angle = distance / EARTH_RADIUS;
+    let cos = latitude.to_radians().cos();
+    // the first bounds a circle around a position, the second a distance along a parallel
+    let ratios = [angle.sin() / cos, (angle / 2.0).sin() / cos];
+    if angle >= FRAC_PI_2 || ratios.iter().any(|ratio| *ratio >= 1.0) {
+        return 180.0;
+    }
+    ratios[0].asin().max(2.0 * ratios[1].asin()).to_degrees()
+}
+
+/// Index of the cell holding `value` among the `2^steps` cells between `min` and `max`. Values
+/// out of range give indexes out of range too.
+fn index(value: f64, min: f64, max: f64, steps: u32) -> i64 {
+    ((value - min) / (max - min) * (1u64 << steps) as f64).floor() as i64
+}
+
+/// Geohash of the cell at `column` and `row`: the bits of the row are the even ones
+fn interleave(column: u64, row: u64) -> u64 {
+    (spread(column) << 1) | spread(row)
+}
+
+/// The low 32 bits of `value` moved to the even bits
+fn spread(value: u64) -> u64 {
+    let mut value = value & 0x0000_0000_FFFF_FFFF;
+    value = (value | (value << 16)) & 0x0000_FFFF_0000_FFFF;
+    value = (value | (value << 8)) & 0x00FF_00FF_00FF_00FF;
+    value = (value | (value << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+    value = (value | (value << 2)) & 0x3333_3333_3333_3333;
+    (value | (value << 1)) & 0x5555_5555_5555_5555
+}
+
+/// The even bits of `value` moved to the low 32 bits, the reverse of `spread`
+fn squash(value: u64) -> u64 {
+    let mut value = value & 0x5555_5555_5555_5555;
+    value = (value | (value >> 1)) & 0x3333_3333_3333_3333;
+    value = (value | (value >> 2)) & 0x0F0F_0F0F_0F0F_0F0F;
+    value = (value | (value >> 4)) & 0x00FF_00FF_00FF_00FF;
+    value = (value | (value >> 8)) & 0x0000_FFFF_0000_FFFF;
+    (value | (value >> 16)) & 0x0000_0000_FFFF_FFFF
+}
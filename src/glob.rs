@@ -0,0 +1,103 @@
+//! Glob-style pattern matching, with the same syntax as Redis `KEYS` and `PSUBSCRIBE`.
+//!
+//! * `?` matches any single byte
+//! * `*` matches any sequence of bytes, including an empty one
+//! * `[abc]` matches one of the listed bytes, `[^abc]` any other byte and `[a-z]` a range
+//! * `\` escapes the following byte
+
+/// Returns `true` if `string` matches `pattern` as a whole
+pub(crate) fn matches(pattern: &[u8], string: &[u8]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // position after the last `*` seen and the byte of `string` it is currently matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while s < string.len() {
+        if p < pattern.len() {
+            match pattern[p] {
+                b'*' => {
+                    p += 1;
+                    backtrack = Some((p, s));
+                    continue;
+                }
+                b'?' => {
+                    p += 1;
+                    s += 1;
+                    continue;
+                }
+                b'[' => {
+                    if let Some((matched, len)) = match_class(&pattern[p..], string[s]) {
+                        if matched {
+                            p += len;
+                            s += 1;
+                            continue;
+                        }
+                    } else if string[s] == b'[' {
+                        // unterminated class, `[` is a literal
+                        p += 1;
+                        s += 1;
+                        continue;
+                    }
+                }
+                b'\\' if p + 1 < pattern.len() => {
+                    if pattern[p + 1] == string[s] {
+                        p += 2;
+                        s += 1;
+                        continue;
+                    }
+                }
+                c => {
+                    if c == string[s] {
+                        p += 1;
+                        s += 1;
+                        continue;
+                    }
+                }
+            }
+        }
+
+        // mismatch, let the last `*` absorb one more byte
+        match backtrack {
+            Some((star_p, star_s)) => {
+                p = star_p;
+                s = star_s + 1;
+                backtrack = Some((star_p, s));
+            }
+            None => return false,
+        }
+    }
+
+    // only trailing stars can match the empty rest of the string
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Match `c` against the class starting at `pattern[0] == b'['`. Returns whether it matched and
+/// the length of the class, `None` if the class isn't terminated.
+fn match_class(pattern: &[u8], c: u8) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negate = pattern.get(i) == Some(&b'^');
+    if negate {
+        i += 1;
+    }
+
+    let mut matched = false;
+    loop {
+        match *pattern.get(i)? {
+            b']' => return Some((matched != negate, i + 1)),
+            b'\\' => {
+                i += 1;
+                matched |= *pattern.get(i)? == c;
+                i += 1;
+            }
+            start if pattern.get(i + 1) == Some(&b'-') && !matches!(pattern.get(i + 2), None | Some(b']')) => {
+                let end = pattern[i + 2];
+                let (lo, hi) = if start <= end { (start, end) } else { (end, start) };
+                matched |= lo <= c && c <= hi;
+                i += 3;
+            }
+            other => {
+                matched |= other == c;
+                i += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Hash value, see `HSET` and `HSCAN`.
+//!
+//! The fields are kept ordered by a hash of their name which only depends on the name, so the
+//! position of a field doesn't move as others are added or removed. A scan resumes from the
+//! position of the next field to return: the fields present from the start to the end of the scan
+//! are all returned, whatever changed in between.
+
+use bytes::Bytes;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::Hasher;
+
+#[derive(Debug, Default)]
+pub(crate) struct Hash {
+    /// Values as stored, by position then name of their field
+    fields: BTreeMap<(u64, Bytes), Bytes>,
+}
+
+impl Hash {
+    /// Number of fields
+    pub(crate) fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Value of `field`, as stored
+    pub(crate) fn get(&self, field: &[u8]) -> Option<&Bytes> {
+        let position = position(field);
+        self.fields
+            .range((position, Bytes::new())..)
+            .take_while(|((at, _), _)| *at == position)
+            .find(|((_, name), _)| name == field)
+            .map(|(_, value)| value)
+    }
+
+    /// Set `field` to `value`. Returns whether the field is new.
+    pub(crate) fn insert(&mut self, field: Bytes, value: Bytes) -> bool {
+        self.fields
+            .insert((position(&field), field), value)
+            .is_none()
+    }
+
+    /// Remove `field`, returning its value
+    pub(crate) fn remove(&mut self, field: &[u8]) -> Option<Bytes> {
+        self.fields
+            .remove(&(position(field), Bytes::copy_from_slice(field)))
+    }
+
+    /// Fields and their values as stored, in scan order
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&Bytes, &Bytes)> {
+        self.fields.iter().map(|((_, field), value)| (field, value))
+    }
+
+    /// Up to `count` fields from `cursor` on, `0` starting from the first field. Returns the
+    /// cursor resuming after them, `0` once every field was returned.
+    pub(crate) fn scan(&self, cursor: u64, count: usize) -> (Vec<(&Bytes, &Bytes)>, u64) {
+        let mut fields = self.fields.range((cursor, Bytes::new())..);
+        let scanned = fields
+            .by_ref()
+            .take(count)
+            .map(|((_, field), value)| (field, value))
+            .collect();
+        let next = fields.next().map_or(0, |((position, _), _)| *position);
+        (scanned, next)
+    }
+
+    /// Number of bytes of the fields and the values
+    pub(crate) fn bytes(&self) -> usize {
+        self.iter()
+            .map(|(field, value)| field.len() + value.len())
+            .sum()
+    }
+}
+
+/// Position of `field` in scan order. `DefaultHasher::new` always uses the same keys, a field
+/// keeps its position for as long as the process runs.
+fn position(field: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(field);
+    hasher.finish()
+}
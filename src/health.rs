@@ -0,0 +1,248 @@
+//! HTTP readiness probe, for orchestrators such as Kubernetes, and metrics.
+//!
+//! `GET /healthz` answers `200 ok` once the server is found responsive, `503` with the failed
+//! check otherwise. Two checks are run, each within `CHECK_TIMEOUT`:
+//!
+//! * every lock of the key space is acquired and released, so a stuck command holding one makes
+//!   the probe fail;
+//! * a `PING` is sent over a fresh connection to the main listener, which goes through the accept
+//!   loop and a connection handler. A server at its client limit therefore reports not ready.
+//!
+//! The probe stops answering as soon as shutdown starts, so no new traffic is routed to a server
+//! draining its connections.
+//!
+//! The probes arriving while the locks are being checked wait on that check rather than starting
+//! another one: a stuck lock would otherwise hold a blocking thread per probe.
+//!
+//! `GET /metrics` answers the pub/sub statistics in the Prometheus text format: the counters of
+//! `PUBSUB STATS` for every channel, labelled by channel, and the totals of `INFO stats`. The
+//! counters of a channel restart from zero once it is dropped, after its last subscriber left.
+
+use crate::cmd::{CommandSpec, Ping};
+use crate::proxy_protocol;
+use crate::{Connection, Db, Frame};
+
+use std::fmt::Write;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::time::{self, Duration};
+use tracing::{debug, warn};
+
+/// Time each check is given before the server is reported not ready
+const CHECK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Requests are expected to fit, the headers past it are ignored
+const MAX_REQUEST: usize = 4 * 1024;
+
+/// Time given to a prober to send its request before the connection is closed
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The lock check in flight, if any, shared by the probes
+#[derive(Debug, Default)]
+struct LockCheck {
+    /// Turns `true` once every lock was acquired
+    in_flight: Mutex<Option<watch::Receiver<bool>>>,
+}
+
+/// Answer the probes received on `listener` about the server listening on `server_addr`, which
+/// expects a PROXY protocol header if `proxy_protocol` is set
+pub(crate) async fn serve(listener: TcpListener, server_addr: SocketAddr, proxy_protocol: bool, db: Db) {
+    let server_addr = reachable(server_addr);
+    let locks = Arc::new(LockCheck::default());
+    loop {
+        let socket = match listener.accept().await {
+            Ok((socket, _)) => socket,
+            Err(err) => {
+                warn!(cause = %err, "failed to accept a health check");
+                continue;
+            }
+        };
+        let (db, locks) = (db.clone(), locks.clone());
+        tokio::spawn(async move {
+            if let Err(err) = respond(socket, server_addr, proxy_protocol, &db, &locks).await {
+                debug!(cause = %err, "health check connection failed");
+            }
+        });
+    }
+}
+
+async fn respond(
+    mut socket: TcpStream,
+    server_addr: SocketAddr,
+    proxy_protocol: bool,
+    db: &Db,
+    locks: &Arc<LockCheck>,
+) -> crate::Result<()> {
+    let mut request = Vec::with_capacity(512);
+    let read = async {
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < MAX_REQUEST {
+            if socket.read_buf(&mut request).await? == 0 {
+                return Ok(false);
+            }
+        }
+        Ok::<_, std::io::Error>(true)
+    };
+    match time::timeout(REQUEST_TIMEOUT, read).await {
+        Ok(Ok(true)) => {}
+        Ok(Ok(false)) => return Ok(()),
+        Ok(Err(err)) => return Err(err.into()),
+        Err(_) => return Err("request not received in time".into()),
+    }
+
+    let request_line = request.split(|&b| b == b'\r').next().unwrap_or_default();
+    let mut parts = request_line.split(|&b| b == b' ');
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some(b"GET"), Some(b"/healthz")) => match check(server_addr, proxy_protocol, db, locks).await {
+            Ok(()) => ("200 OK", "ok\n".to_string()),
+            Err(reason) => {
+                warn!(%reason, "health check failed");
+                ("503 Service Unavailable", format!("{}\n", reason))
+            }
+        },
+        (Some(b"GET"), Some(b"/metrics")) => ("200 OK", metrics(db)),
+        (Some(b"GET"), Some(_)) => ("404 Not Found", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    socket.write_all(response.as_bytes()).await?;
+    socket.shutdown().await?;
+    Ok(())
+}
+
+/// Run the checks, returns the reason of the first one failing
+async fn check(server_addr: SocketAddr, proxy_protocol: bool, db: &Db, locks: &Arc<LockCheck>) -> Result<(), String> {
+    let mut acquired = locks.start(db);
+    match time::timeout(CHECK_TIMEOUT, acquired.wait_for(|acquired| *acquired)).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(_)) => return Err("key space check failed".to_string()),
+        Err(_) => return Err("key space locks not acquired in time".to_string()),
+    }
+
+    match time::timeout(CHECK_TIMEOUT, ping(server_addr, proxy_protocol)).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(err)) => Err(format!("PING failed: {}", err)),
+        Err(_) => Err("PING not answered in time".to_string()),
+    }
+}
+
+/// Metrics reported for every pub/sub channel: name, type and help
+const CHANNEL_METRICS: [(&str, &str, &str); 7] = [
+    ("redust_pubsub_channel_subscribers", "gauge", "Current subscribers of the channel"),
+    ("redust_pubsub_channel_published_total", "counter", "Messages published on the channel"),
+    ("redust_pubsub_channel_delivered_total", "counter", "Messages received by the subscribers"),
+    ("redust_pubsub_channel_dropped_total", "counter", "Messages missed by lagging subscribers"),
+    ("redust_pubsub_channel_disconnected_total", "counter", "Lagging subscribers disconnected"),
+    ("redust_pubsub_channel_subscribes_total", "counter", "Subscriptions to the channel"),
+    ("redust_pubsub_channel_unsubscribes_total", "counter", "Subscriptions left"),
+];
+
+/// The pub/sub statistics in the Prometheus text format
+fn metrics(db: &Db) -> String {
+    // values in the order of `CHANNEL_METRICS`
+    let channels: Vec<(String, [u64; 7])> = db
+        .all_channel_stats()
+        .into_iter()
+        .map(|(channel, subscribers, stats)| {
+            let values = [
+                subscribers as u64,
+                stats.published.load(Ordering::Relaxed),
+                stats.delivered.load(Ordering::Relaxed),
+                stats.dropped.load(Ordering::Relaxed),
+                stats.disconnected.load(Ordering::Relaxed),
+                stats.subscribes.load(Ordering::Relaxed),
+                stats.unsubscribes.load(Ordering::Relaxed),
+            ];
+            (channel, values)
+        })
+        .collect();
+
+    let mut out = String::new();
+    for (i, (name, kind, help)) in CHANNEL_METRICS.iter().enumerate() {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+        for (channel, values) in &channels {
+            let _ = writeln!(out, "{}{{channel=\"{}\"}} {}", name, escape_label(channel), values[i]);
+        }
+    }
+
+    let totals = db.pubsub_totals();
+    let server = [
+        ("redust_pubsub_subscribers", "gauge", "Current subscribers", totals.subscribers as u64),
+        ("redust_pubsub_published_total", "counter", "Messages published", totals.published),
+        ("redust_pubsub_delivered_total", "counter", "Messages received by the subscribers", totals.delivered),
+        ("redust_pubsub_dropped_total", "counter", "Messages missed by lagging subscribers", totals.dropped),
+        ("redust_pubsub_disconnected_total", "counter", "Lagging subscribers disconnected", totals.disconnected),
+    ];
+    for (name, kind, help, value) in server.iter() {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}\n{} {}", name, help, name, kind, name, value);
+    }
+    out
+}
+
+/// Escape a label value, channel names are arbitrary strings
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl LockCheck {
+    /// Join the check in flight, or start one
+    fn start(self: &Arc<LockCheck>, db: &Db) -> watch::Receiver<bool> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        match &*in_flight {
+            // the sender is gone if the check panicked
+            Some(acquired) if acquired.has_changed().is_ok() => acquired.clone(),
+            _ => {
+                let (tx, rx) = watch::channel(false);
+                let (db, locks) = (db.clone(), self.clone());
+                // the locks are blocking, keep the runtime threads free while waiting on them
+                tokio::task::spawn_blocking(move || {
+                    db.check_locks();
+                    *locks.in_flight.lock().unwrap() = None;
+                    let _ = tx.send(true);
+                });
+                *in_flight = Some(rx.clone());
+                rx
+            }
+        }
+    }
+}
+
+async fn ping(server_addr: SocketAddr, proxy_protocol: bool) -> crate::Result<()> {
+    let mut socket = TcpStream::connect(server_addr).await?;
+    if proxy_protocol {
+        socket.write_all(proxy_protocol::LOCAL_HEADER).await?;
+    }
+    let mut connection = Connection::new(socket);
+    connection.write_frame(&Ping::default().into_frame()).await?;
+
+    match connection.read_frame().await? {
+        Some(Frame::Simple(pong)) if pong == "PONG" => Ok(()),
+        // refused by `requirepass`, the connection was still served
+        Some(Frame::Error(err)) if err.starts_with("NOAUTH") => Ok(()),
+        // refused by the rate limits, same
+        Some(Frame::Error(err)) if err.starts_with("BUSY") => Ok(()),
+        Some(frame) => Err(format!("unexpected reply {}", frame).into()),
+        None => Err(crate::Error::ConnectionReset),
+    }
+}
+
+/// The address to connect to in order to reach a listener bound to `addr`
+fn reachable(addr: SocketAddr) -> SocketAddr {
+    match addr {
+        SocketAddr::V4(v4) if v4.ip().is_unspecified() => (Ipv4Addr::LOCALHOST, v4.port()).into(),
+        SocketAddr::V6(v6) if v6.ip().is_unspecified() => (Ipv6Addr::LOCALHOST, v6.port()).into(),
+        addr => addr,
+    }
+}
@@ -0,0 +1,122 @@
+//! Keyspace notifications, see the `notify-keyspace-events` setting.
+//!
+//! As in Redis, every change to a key can be published on two pub/sub channels:
+//!
+//! * `__keyspace@0__:<key>` receives the name of the event, e.g. `set` or `expired`.
+//! * `__keyevent@0__:<event>` receives the name of the key.
+//!
+//! Notifications are off by default. The setting is a string of flags choosing the channels and
+//! the classes of events sent:
+//!
+//! * `K` keyspace channels, `E` keyevent channels. At least one of them must be given.
+//! * `g` generic events: `del`, `expire`, `restore`.
+//! * `$` string events: `set`, `setbit`, `incrby`, and the reservations and locks: `reserve`,
+//!   `confirm`, `lock`, `unlock`.
+//! * `l` list events: `lpush`, `rpush`, `lpop`, `rpop`, `lset`, `linsert`, `lrem`.
+//! * `h` hash events: `hset`, `hdel`, `hincrby`, `hincrbyfloat`.
+//! * `z` sorted set events: `zadd`, `zrem`.
+//! * `b` byte log events: `blog.append`.
+//! * `x` expirations: `expired`, sent when an expired key is removed.
+//! * `A` alias for `g$lhzbx`.
+//!
+//! Events are sent once the write is applied, after the lock of the key is released.
+
+/// Classes of events and channels enabled by `notify-keyspace-events`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct KeyspaceEvents(u16);
+
+/// Class of an event, see the module documentation
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Class {
+    Generic = 1 << 2,
+    String = 1 << 3,
+    List = 1 << 4,
+    Hash = 1 << 5,
+    SortedSet = 1 << 6,
+    Blog = 1 << 7,
+    Expired = 1 << 8,
+}
+
+const KEYSPACE: u16 = 1;
+const KEYEVENT: u16 = 1 << 1;
+const ALL: u16 = Class::Generic as u16
+    | Class::String as u16
+    | Class::List as u16
+    | Class::Hash as u16
+    | Class::SortedSet as u16
+    | Class::Blog as u16
+    | Class::Expired as u16;
+
+impl KeyspaceEvents {
+    /// Parse the flags of `notify-keyspace-events`. An empty string turns notifications off.
+    pub(crate) fn parse(flags: &str) -> Result<KeyspaceEvents, String> {
+        let mut events = 0;
+        for flag in flags.chars() {
+            events |= match flag {
+                'K' => KEYSPACE,
+                'E' => KEYEVENT,
+                'g' => Class::Generic as u16,
+                '$' => Class::String as u16,
+                'l' => Class::List as u16,
+                'h' => Class::Hash as u16,
+                'z' => Class::SortedSet as u16,
+                'b' => Class::Blog as u16,
+                'x' => Class::Expired as u16,
+                'A' => ALL,
+                _ => return Err(format!("unknown flag '{}', flags are K, E, g, $, l, h, z, b, x and A", flag)),
+            };
+        }
+        // the classes are pointless without a channel to send them on, and the other way around
+        if events & (KEYSPACE | KEYEVENT) == 0 || events & ALL == 0 {
+            events = 0;
+        }
+        Ok(KeyspaceEvents(events))
+    }
+
+    /// Whether the events of `class` are sent on the keyspace and on the keyevent channels
+    pub(crate) fn channels(self, class: Class) -> (bool, bool) {
+        if self.0 & class as u16 == 0 {
+            return (false, false);
+        }
+        (self.0 & KEYSPACE != 0, self.0 & KEYEVENT != 0)
+    }
+
+    /// The flags, in the order listed by the module documentation
+    pub(crate) fn flags(self) -> String {
+        let mut flags = String::new();
+        if self.0 & ALL == ALL {
+            flags.push('A');
+        } else {
+            for (flag, class) in [
+                ('g', Class::Generic),
+                ('$', Class::String),
+                ('l', Class::List),
+                ('h', Class::Hash),
+                ('z', Class::SortedSet),
+                ('b', Class::Blog),
+                ('x', Class::Expired),
+            ] {
+                if self.0 & class as u16 != 0 {
+                    flags.push(flag);
+                }
+            }
+        }
+        if self.0 & KEYSPACE != 0 {
+            flags.push('K');
+        }
+        if self.0 & KEYEVENT != 0 {
+            flags.push('E');
+        }
+        flags
+    }
+}
+
+/// Channel of the keyspace notifications of `key`
+pub(crate) fn keyspace_channel(key: &str) -> String {
+    format!("__keyspace@0__:{}", key)
+}
+
+/// Channel of the keyevent notifications of `event`
+pub(crate) fn keyevent_channel(event: &str) -> String {
+    format!("__keyevent@0__:{}", event)
+}
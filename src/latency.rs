@@ -0,0 +1,226 @@
+//! Per command latency histograms, see `LATENCY PERCENTILE` and `INFO latencystats`, and the
+//! latency monitor, see `LATENCY LATEST` and `LATENCY HISTORY`.
+//!
+//! Every command executed is timed by the `RecordLatency` layer, innermost in the chain so only
+//! the command itself is measured. Latencies are kept in HDR histograms, which bound the relative
+//! error of every percentile to the configured number of significant digits whatever the
+//! distribution, in a fixed amount of memory per command. Each command has its own histogram,
+//! indexed by its id in the registry, so the commands running concurrently don't contend on a
+//! lock unless they are the same.
+//!
+//! The commands reaching `latency-monitor-threshold` are also kept as a time series, as Redis
+//! does: one sample per second, the slowest run of the command during that second, for the last
+//! `HISTORY_LEN` seconds with a spike.
+
+use crate::cmd::{registry, COMMANDS};
+use crate::middleware::{BoxFuture, Context, Layer, Next};
+use crate::Command;
+
+use hdrhistogram::Histogram;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Highest latency tracked, an hour in microseconds. Longer commands are counted as taking that
+/// long.
+const MAX_LATENCY_USEC: u64 = 3600 * 1_000_000;
+
+/// Samples kept by the latency monitor for each command, as Redis does
+const HISTORY_LEN: usize = 160;
+
+#[derive(Debug)]
+pub(crate) struct LatencyStats {
+    /// Latencies in microseconds, by command id, see `Command::id`
+    histograms: Box<[Mutex<Slot>]>,
+    /// Latency spikes by command name
+    history: Mutex<HashMap<String, History>>,
+}
+
+/// Histogram of a command, allocated the first time the command runs
+#[derive(Debug, Default)]
+struct Slot {
+    /// Significant digits of `histogram`, which is reset when the configured precision changes
+    precision: u8,
+    histogram: Option<Histogram<u64>>,
+}
+
+/// Latency spikes of a command
+#[derive(Debug, Default)]
+struct History {
+    /// Unix time in seconds and latency in milliseconds, oldest first
+    samples: VecDeque<(u64, u64)>,
+    /// Slowest sample ever recorded, including the ones dropped from `samples`
+    max: u64,
+}
+
+/// Latest latency spike of a command, see `LatencyStats::latest`
+#[derive(Debug)]
+pub(crate) struct Latest {
+    pub(crate) command: String,
+    /// Unix time in seconds
+    pub(crate) time: u64,
+    /// Latency in milliseconds
+    pub(crate) latency: u64,
+    /// Slowest spike in milliseconds
+    pub(crate) max: u64,
+}
+
+/// Times the execution of each command
+#[derive(Debug)]
+pub(crate) struct RecordLatency;
+
+impl Default for LatencyStats {
+    fn default() -> LatencyStats {
+        LatencyStats {
+            histograms: COMMANDS.iter().map(|_| Mutex::default()).collect(),
+            history: Mutex::default(),
+        }
+    }
+}
+
+impl LatencyStats {
+    /// Record that the command `id` took `elapsed`, with `precision` significant digits
+    pub(crate) fn record(&self, id: usize, elapsed: Duration, precision: u8) {
+        let mut slot = self.histograms[id].lock().unwrap();
+        if slot.histogram.is_none() || slot.precision != precision {
+            // the precision is validated by `CONFIG SET`
+            let histogram = Histogram::new_with_bounds(1, MAX_LATENCY_USEC, precision).unwrap();
+            slot.histogram = Some(histogram);
+            slot.precision = precision;
+        }
+        let histogram = slot.histogram.as_mut().unwrap();
+        histogram.saturating_record(elapsed.as_micros() as u64);
+    }
+
+    /// Latency of `command` at `percentile`, in microseconds. `None` if it never ran.
+    pub(crate) fn percentile(&self, command: &str, percentile: f64) -> Option<u64> {
+        let slot = self.histograms[registry::id(command)?].lock().unwrap();
+        slot.histogram
+            .as_ref()
+            .filter(|histogram| !histogram.is_empty())
+            .map(|histogram| histogram.value_at_percentile(percentile))
+    }
+
+    /// Latency of every command which ran at each of `percentiles`, in microseconds, sorted by
+    /// command name
+    pub(crate) fn percentiles(&self, percentiles: &[f64]) -> Vec<(String, Vec<u64>)> {
+        let mut latencies: Vec<_> = COMMANDS
+            .iter()
+            .zip(self.histograms.iter())
+            .filter_map(|(spec, slot)| {
+                let slot = slot.lock().unwrap();
+                let histogram = slot.histogram.as_ref().filter(|histogram| !histogram.is_empty())?;
+                let values = percentiles
+                    .iter()
+                    .map(|percentile| histogram.value_at_percentile(*percentile))
+                    .collect();
+                Some((spec.name.to_string(), values))
+            })
+            .collect();
+        latencies.sort();
+        latencies
+    }
+
+    /// Keep that the command `id` took `elapsed`, which reached the latency monitor threshold
+    pub(crate) fn record_spike(&self, id: usize, elapsed: Duration) {
+        let command = COMMANDS[id].name;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let latency = elapsed.as_millis() as u64;
+
+        let mut history = self.history.lock().unwrap();
+        if !history.contains_key(command) {
+            history.insert(command.to_string(), History::default());
+        }
+        let history = history.get_mut(command).unwrap();
+        history.max = history.max.max(latency);
+        match history.samples.back_mut() {
+            Some((time, slowest)) if *time == now => *slowest = (*slowest).max(latency),
+            _ => {
+                if history.samples.len() == HISTORY_LEN {
+                    history.samples.pop_front();
+                }
+                history.samples.push_back((now, latency));
+            }
+        }
+    }
+
+    /// The latest spike of every command which had one, sorted by command name
+    pub(crate) fn latest(&self) -> Vec<Latest> {
+        let history = self.history.lock().unwrap();
+        let mut latest: Vec<_> = history
+            .iter()
+            .filter_map(|(command, history)| {
+                let (time, latency) = *history.samples.back()?;
+                Some(Latest {
+                    command: command.clone(),
+                    time,
+                    latency,
+                    max: history.max,
+                })
+            })
+            .collect();
+        latest.sort_by(|a, b| a.command.cmp(&b.command));
+        latest
+    }
+
+    /// The spikes of `command` as unix time in seconds and latency in milliseconds, oldest first
+    pub(crate) fn history(&self, command: &str) -> Vec<(u64, u64)> {
+        let history = self.history.lock().unwrap();
+        history
+            .get(command)
+            .map(|history| history.samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Drop the histograms and the spikes of `commands`, or of all of them if empty. Returns the
+    /// number of commands which had either.
+    pub(crate) fn reset(&self, commands: &[String]) -> usize {
+        let mut history = self.history.lock().unwrap();
+        if commands.is_empty() {
+            let mut count = 0;
+            for (spec, slot) in COMMANDS.iter().zip(self.histograms.iter()) {
+                let dropped = slot.lock().unwrap().histogram.take().is_some();
+                if history.remove(spec.name).is_some() || dropped {
+                    count += 1;
+                }
+            }
+            return count + history.drain().count();
+        }
+        commands
+            .iter()
+            .map(|command| command.to_lowercase())
+            .filter(|command| {
+                let dropped = registry::id(command)
+                    .is_some_and(|id| self.histograms[id].lock().unwrap().histogram.take().is_some());
+                history.remove(command).is_some() || dropped
+            })
+            .count()
+    }
+}
+
+impl Layer for RecordLatency {
+    fn call<'a>(
+        &'a self,
+        cmd: Command,
+        cx: &'a mut Context<'_>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, crate::Result<()>> {
+        Box::pin(async move {
+            // unbounded commands last as long as their client wants, unknown ones aren't tracked
+            let id = match cmd.is_unbounded() {
+                true => None,
+                false => cmd.id(),
+            };
+
+            let start = Instant::now();
+            let res = next.run(cmd, cx).await;
+            if let Some(id) = id {
+                cx.db.record_latency(id, start.elapsed());
+            }
+            res
+        })
+    }
+}
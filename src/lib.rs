@@ -4,29 +4,85 @@ pub use cmd::Command;
 pub mod frame;
 pub use frame::Frame;
 
-mod parse;
-use parse::{Parse, ParseError};
+pub mod parse;
+pub use parse::{Parse, ParseError};
 
 mod connection;
-pub use connection::Connection;
+pub use connection::{Connection, OwnedReadHalf, OwnedWriteHalf};
+
+pub mod codec;
+pub use codec::RespCodec;
 
 mod db;
 use db::Db;
 
+mod acl;
+mod bitmap;
+mod blog;
+mod command_stats;
+mod commit;
+mod config;
+mod geo;
+mod glob;
+mod hash;
+mod keyspace_events;
+mod latency;
+mod proxy_protocol;
+mod rdb;
+mod shard_lock;
+mod slowlog;
+mod snapshot;
+mod socket;
+mod sorted_set;
+mod timeout;
+
+pub mod transform;
+pub use transform::ValueTransform;
+
+pub mod middleware;
+pub use middleware::Layer;
+
+pub mod extension;
+pub use extension::CommandHandler;
+
+pub mod rate_limit;
+
 mod rocks;
 
 mod buffer;
 pub use buffer::Buffer;
 
+pub mod backoff;
+pub use backoff::Backoff;
+
 mod shutdown;
 use shutdown::Shutdown;
 
 pub mod client;
 
+pub mod store;
+pub use store::Store;
+
 pub mod server;
 
+pub mod embedded;
+
+pub mod sentinel;
+
+mod health;
+
+#[cfg(feature = "websocket")]
+mod websocket;
+
+#[cfg(feature = "http-gateway")]
+mod gateway;
+
+#[cfg(feature = "simulation")]
+pub mod sim;
+
 pub const DEFAULT_PORT: &str = "6379";
 
-pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub mod error;
+pub use error::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
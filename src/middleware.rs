@@ -0,0 +1,85 @@
+//! Layers wrapping the execution of every command.
+//!
+//! The server runs each command through the layers registered with `server::Builder::layer`,
+//! outermost first, the command itself being at the end of the chain. A layer decides whether and
+//! when the rest of the chain runs: it can time it, trace it, or reply an error instead of running
+//! it. This is how concerns such as authentication, rate limiting or metrics are plugged in
+//! without touching the connection handler.
+//!
+//! Commands issued from within a subscription are not run through the layers.
+
+use crate::{Command, Connection, Db, Frame, Shutdown};
+
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+pub trait Layer: Send + Sync + fmt::Debug {
+    /// Handle `cmd`, usually by running the rest of the chain with `next.run(cmd, cx)`
+    fn call<'a>(
+        &'a self,
+        cmd: Command,
+        cx: &'a mut Context<'_>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, crate::Result<()>>;
+}
+
+/// The connection a command is executed for
+pub struct Context<'a> {
+    pub(crate) db: &'a Db,
+    pub(crate) connection: &'a mut Connection,
+    pub(crate) shutdown: &'a mut Shutdown,
+}
+
+/// The rest of the chain
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    layers: &'a [Arc<dyn Layer>],
+}
+
+impl Context<'_> {
+    /// Address of the client
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.connection.peer_addr().ok()
+    }
+
+    /// Write `frame` to the client, to answer a command without running it
+    pub async fn reply(&mut self, frame: &Frame) -> crate::Result<()> {
+        self.connection.write_frame(frame).await?;
+        Ok(())
+    }
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(layers: &'a [Arc<dyn Layer>]) -> Next<'a> {
+        Next { layers }
+    }
+
+    /// Run `cmd` through the remaining layers, then execute it
+    pub async fn run(self, cmd: Command, cx: &mut Context<'_>) -> crate::Result<()> {
+        match self.layers.split_first() {
+            Some((layer, layers)) => layer.call(cmd, cx, Next { layers }).await,
+            None => cmd.apply(cx.db, cx.connection, cx.shutdown).await,
+        }
+    }
+}
+
+impl fmt::Debug for Context<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Context")
+            .field("peer_addr", &self.peer_addr())
+            .finish()
+    }
+}
+
+impl fmt::Debug for Next<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Next")
+            .field("layers", &self.layers.len())
+            .finish()
+    }
+}
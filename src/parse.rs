@@ -1,26 +1,51 @@
+//! Reading the arguments of a command out of its request frame.
+//!
+//! The server parses its own commands with it, and so can the applications extending it:
+//!
+//! ```
+//! use redust::{Frame, Parse, ParseError};
+//!
+//! # fn main() -> Result<(), ParseError> {
+//! let request = Frame::Array(vec![
+//!     Frame::Bulk("incrby".into()),
+//!     Frame::Bulk("visits".into()),
+//!     Frame::Bulk("5".into()),
+//! ]);
+//!
+//! let mut parse = Parse::new(request)?;
+//! assert_eq!(parse.next_string()?, "incrby");
+//! assert_eq!(parse.next_string()?, "visits");
+//! assert_eq!(parse.next_int()?, 5);
+//! parse.finish()?;
+//! # Ok(())
+//! # }
+//! ```
+
 use crate::Frame;
 
 use bytes::Bytes;
+use std::convert::TryFrom;
 use std::{fmt, str, vec};
 
-/// Utitility to parsing a command
-#[derive(Debug)]
-pub(crate) struct Parse {
+/// Cursor over the entries of a request, an array frame
+#[derive(Debug, Clone)]
+pub struct Parse {
     // Array frame iterator
     parts: vec::IntoIter<Frame>,
 }
 
 /// Error encountered while parsing a frame
 #[derive(Debug)]
-pub(crate) enum ParseError {
-    /// Attempting to extract a value failed due to the frame being fully
+pub enum ParseError {
+    /// Attempting to extract a value failed due to the frame being fully consumed
     EndOfStream,
-    // All other errors
+    /// Any other error, an entry of the wrong type for instance
     Other(crate::Error),
 }
 
 impl Parse {
-    pub(crate) fn new(frame: Frame) -> Result<Parse, ParseError> {
+    /// Start parsing `frame`, which must be an array
+    pub fn new(frame: Frame) -> Result<Parse, ParseError> {
         let array = match frame {
             Frame::Array(array) => array,
             frame => return Err(format!("protocol error; expected array, got {:?}", frame).into()),
@@ -37,8 +62,8 @@ impl Parse {
         self.parts.next().ok_or(ParseError::EndOfStream)
     }
 
-    /// Return the next entry as a string
-    pub(crate) fn next_string(&mut self) -> Result<String, ParseError> {
+    /// Return the next entry as a string, it must be a simple string or valid UTF-8 bulk
+    pub fn next_string(&mut self) -> Result<String, ParseError> {
         match self.next()? {
             Frame::Simple(s) => Ok(s),
             Frame::Bulk(data) => str::from_utf8(&data[..])
@@ -52,7 +77,8 @@ impl Parse {
         }
     }
 
-    pub(crate) fn next_bytes(&mut self) -> Result<Bytes, ParseError> {
+    /// Return the next entry as raw bytes, it must be a simple string or a bulk
+    pub fn next_bytes(&mut self) -> Result<Bytes, ParseError> {
         match self.next()? {
             Frame::Simple(s) => Ok(Bytes::from(s.into_bytes())),
             Frame::Bulk(data) => Ok(data),
@@ -64,18 +90,26 @@ impl Parse {
         }
     }
 
-    pub(crate) fn next_int(&mut self) -> Result<u64, ParseError> {
+    /// Return the next entry as an unsigned integer, it may be an integer frame or a string of
+    /// decimal digits
+    pub fn next_int(&mut self) -> Result<u64, ParseError> {
         use atoi::atoi;
-        const MSG: &str = "protocol error; invalid number";
+        const MSG: &str = "ERR value is not an integer or out of range";
         match self.next()? {
-            Frame::Integer(v) => Ok(v),
+            Frame::Integer(v) => u64::try_from(v).map_err(|_| MSG.into()),
             Frame::Simple(data) => atoi::<u64>(data.as_bytes()).ok_or_else(|| MSG.into()),
             Frame::Bulk(data) => atoi::<u64>(&data).ok_or_else(|| MSG.into()),
             frame => Err(format!("protocol errpr; expected int frame but got {:?}", frame).into()),
         }
     }
 
-    pub(crate) fn finish(&mut self) -> Result<(), ParseError> {
+    /// Number of entries left
+    pub fn remaining(&self) -> usize {
+        self.parts.len()
+    }
+
+    /// Check that every entry was consumed, an error otherwise
+    pub fn finish(&mut self) -> Result<(), ParseError> {
         if self.parts.next().is_none() {
             Ok(())
         } else {
@@ -86,7 +120,7 @@ impl Parse {
 
 impl From<String> for ParseError {
     fn from(src: String) -> Self {
-        ParseError::Other(src.into())
+        ParseError::Other(crate::Error::Protocol(src))
     }
 }
 
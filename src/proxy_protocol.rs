@@ -0,0 +1,125 @@
+//! PROXY protocol header sent by load balancers such as HAProxy or AWS NLB, see
+//! <https://www.haproxy.org/download/2.8/doc/proxy-protocol.txt>.
+//!
+//! A load balancer relaying TCP connections opens its own connection to the server, which then
+//! only sees the address of the load balancer. With the PROXY protocol, the load balancer starts
+//! the connection with a header carrying the address of the client. Both the text format (v1) and
+//! the binary one (v2) are accepted.
+//!
+//! Once enabled with `server::Builder::proxy_protocol`, every connection must start with a header:
+//! a client connecting directly would otherwise be able to claim any address.
+
+use crate::Error;
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Signature starting a v2 header
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Longest v1 header, `\r\n` included
+const V1_MAX_LEN: usize = 107;
+
+/// Longest v2 address block accepted, TLVs included
+const V2_MAX_LEN: usize = 4096;
+
+/// Time given to a new connection to send its header
+pub(crate) const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Header sent by a connection made on behalf of no client, such as a health check
+pub(crate) const LOCAL_HEADER: &[u8] = b"PROXY UNKNOWN\r\n";
+
+/// Read the header starting `stream`, leaving the bytes following it unread.
+///
+/// Returns the address of the client, `None` if the load balancer didn't relay any, for its own
+/// health checks for instance.
+pub(crate) async fn read_header<R: AsyncRead + Unpin>(stream: &mut R) -> crate::Result<Option<SocketAddr>> {
+    // both the v2 signature and the shortest v1 header are at least that long
+    let mut start = [0; 12];
+    stream.read_exact(&mut start).await?;
+
+    if start == V2_SIGNATURE {
+        read_v2(stream).await
+    } else if start.starts_with(b"PROXY ") {
+        read_v1(stream, &start).await
+    } else {
+        Err(invalid("missing PROXY protocol header"))
+    }
+}
+
+/// `PROXY TCP4 192.168.0.1 192.168.0.11 56324 6379\r\n`
+async fn read_v1<R: AsyncRead + Unpin>(stream: &mut R, start: &[u8]) -> crate::Result<Option<SocketAddr>> {
+    // read byte per byte, the bytes past the header belong to the connection
+    let mut line = start.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LEN {
+            return Err(invalid("PROXY protocol v1 header too long"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+
+    let line = std::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| invalid("invalid PROXY protocol v1 header"))?;
+    let mut fields = line.split(' ').skip(1);
+    match fields.next() {
+        Some("UNKNOWN") => return Ok(None),
+        Some("TCP4") | Some("TCP6") => {}
+        _ => return Err(invalid("unsupported PROXY protocol v1 family")),
+    }
+
+    let source = fields.next().and_then(|ip| ip.parse::<IpAddr>().ok());
+    let _destination = fields.next();
+    let port = fields.next().and_then(|port| port.parse::<u16>().ok());
+    match (source, port) {
+        (Some(ip), Some(port)) => Ok(Some(SocketAddr::new(ip, port))),
+        _ => Err(invalid("invalid PROXY protocol v1 address")),
+    }
+}
+
+/// Signature, version and command, family and protocol, length of the addresses then addresses
+async fn read_v2<R: AsyncRead + Unpin>(stream: &mut R) -> crate::Result<Option<SocketAddr>> {
+    let version_command = stream.read_u8().await?;
+    let family = stream.read_u8().await?;
+    let len = stream.read_u16().await? as usize;
+
+    if version_command >> 4 != 2 {
+        return Err(invalid("unsupported PROXY protocol version"));
+    }
+    if len > V2_MAX_LEN {
+        return Err(invalid("PROXY protocol v2 header too long"));
+    }
+    let mut addresses = vec![0; len];
+    stream.read_exact(&mut addresses).await?;
+
+    match version_command & 0x0f {
+        // LOCAL, the connection was made by the load balancer itself
+        0x0 => return Ok(None),
+        // PROXY
+        0x1 => {}
+        _ => return Err(invalid("unsupported PROXY protocol v2 command")),
+    }
+
+    match family >> 4 {
+        // AF_INET
+        0x1 if len >= 12 => {
+            let ip = Ipv4Addr::new(addresses[0], addresses[1], addresses[2], addresses[3]);
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        // AF_INET6
+        0x2 if len >= 36 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(&addresses[..16]);
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+            Ok(Some(SocketAddr::new(Ipv6Addr::from(octets).into(), port)))
+        }
+        // AF_UNSPEC or AF_UNIX, there is no client address to report
+        0x0 | 0x3 => Ok(None),
+        _ => Err(invalid("invalid PROXY protocol v2 address")),
+    }
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::Protocol(msg.to_string())
+}
@@ -0,0 +1,160 @@
+//! Token bucket rate limiting of the commands, see the `ratelimit-*` settings.
+//!
+//! A bucket holds up to one second worth of commands, `rate` tokens, and is refilled continuously
+//! at `rate` tokens per second. Each command takes a token from the global bucket and from the
+//! bucket of its client, clients being told apart by address or by ACL user. A command finding a
+//! bucket empty is answered `-BUSY rate limit exceeded` without running. A rate of 0 disables the
+//! matching limit.
+//!
+//! The `RateLimit` layer is the outermost one, so rejected commands aren't timed nor seen by the
+//! layers added with `server::Builder::layer`.
+
+use crate::middleware::{BoxFuture, Context, Layer, Next};
+use crate::{Command, Frame};
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Number of client buckets past which the full ones are dropped
+const SWEEP_THRESHOLD: usize = 1024;
+
+/// How clients are told apart by the per client limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientKey {
+    /// Address of the client, port excluded
+    Addr,
+    /// User the client is authenticated as
+    User,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct RateLimits {
+    global: Mutex<Option<Bucket>>,
+    clients: Mutex<HashMap<String, Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Rejects the commands exceeding the rate limits
+#[derive(Debug)]
+pub(crate) struct RateLimit;
+
+impl Bucket {
+    fn full(rate: u64, now: Instant) -> Bucket {
+        Bucket {
+            tokens: rate as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, rate: u64, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64);
+        self.updated = now;
+    }
+
+    /// Take a token if one is left
+    fn take(&mut self, rate: u64, now: Instant) -> bool {
+        self.refill(rate, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether the bucket is full again, it can then be dropped and recreated on demand
+    fn is_idle(&self, rate: u64, now: Instant) -> bool {
+        now.saturating_duration_since(self.updated) >= Duration::from_secs(1)
+            || self.tokens >= rate as f64
+    }
+}
+
+impl RateLimits {
+    /// Take a token from the global bucket and from the one of `client`, `None` checks the global
+    /// limit only. Returns `false` if the command must be rejected.
+    pub(crate) fn acquire(&self, global_rate: u64, client_rate: u64, client: Option<&str>) -> bool {
+        let now = Instant::now();
+
+        if global_rate > 0 {
+            let mut global = self.global.lock().unwrap();
+            let bucket = global.get_or_insert_with(|| Bucket::full(global_rate, now));
+            if !bucket.take(global_rate, now) {
+                return false;
+            }
+        }
+
+        if let (true, Some(client)) = (client_rate > 0, client) {
+            let mut clients = self.clients.lock().unwrap();
+            if clients.len() >= SWEEP_THRESHOLD {
+                clients.retain(|_, bucket| !bucket.is_idle(client_rate, now));
+            }
+            if let Some(bucket) = clients.get_mut(client) {
+                return bucket.take(client_rate, now);
+            }
+            let mut bucket = Bucket::full(client_rate, now);
+            let allowed = bucket.take(client_rate, now);
+            clients.insert(client.to_string(), bucket);
+            return allowed;
+        }
+        true
+    }
+}
+
+impl Layer for RateLimit {
+    fn call<'a>(
+        &'a self,
+        cmd: Command,
+        cx: &'a mut Context<'_>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, crate::Result<()>> {
+        Box::pin(async move {
+            let (global_rate, client_rate, client_key) = cx.db.rate_limit_settings();
+            if global_rate == 0 && client_rate == 0 {
+                return next.run(cmd, cx).await;
+            }
+
+            let client = match client_key {
+                ClientKey::Addr => cx.peer_addr().map(|addr| addr.ip().to_string()),
+                ClientKey::User => cx.connection.user().map(str::to_string),
+            };
+            if cx.db.acquire_rate_limit(global_rate, client_rate, client.as_deref()) {
+                next.run(cmd, cx).await
+            } else {
+                cx.reply(&Frame::error("BUSY rate limit exceeded")).await
+            }
+        })
+    }
+}
+
+impl ClientKey {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ClientKey::Addr => "addr",
+            ClientKey::User => "user",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<ClientKey> {
+        match &name.to_lowercase()[..] {
+            "addr" => Some(ClientKey::Addr),
+            "user" => Some(ClientKey::User),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ClientKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ClientKey, String> {
+        ClientKey::from_name(s).ok_or_else(|| format!("unknown client key `{}`, expected `addr` or `user`", s))
+    }
+}
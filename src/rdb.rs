@@ -0,0 +1,449 @@
+//! The subset of the Redis RDB format needed to move string keys between redust and Redis, see
+//! <https://rdb.fnordig.de/file_format.html>.
+//!
+//! `DUMP` serializes a value as Redis does, so its payload can be given to `RESTORE` on either
+//! server. `SAVE` writes the whole key space to an RDB file Redis loads on startup, and a
+//! `dump.rdb` written by Redis is loaded with `server::Builder::import_rdb`.
+//!
+//! Only string values have a Redis equivalent. Binary logs are skipped when saving. When loading,
+//! the keys of the other Redis types, of databases other than `0` and with names which aren't
+//! valid UTF-8 are skipped; files holding streams or module values are rejected.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::commit::unix_millis;
+
+/// Version of the files and payloads written, loaded by Redis 5.0 and later
+const RDB_VERSION: u16 = 9;
+
+/// Newest version read, written by Redis 7.4
+const RDB_MAX_VERSION: u16 = 12;
+
+const OPCODE_SLOT_INFO: u8 = 0xf4;
+const OPCODE_FUNCTION2: u8 = 0xf5;
+const OPCODE_IDLE: u8 = 0xf8;
+const OPCODE_FREQ: u8 = 0xf9;
+const OPCODE_AUX: u8 = 0xfa;
+const OPCODE_RESIZEDB: u8 = 0xfb;
+const OPCODE_EXPIRETIME_MS: u8 = 0xfc;
+const OPCODE_EXPIRETIME: u8 = 0xfd;
+const OPCODE_SELECTDB: u8 = 0xfe;
+const OPCODE_EOF: u8 = 0xff;
+
+const TYPE_STRING: u8 = 0;
+
+const ENC_INT8: u8 = 0;
+const ENC_INT16: u8 = 1;
+const ENC_INT32: u8 = 2;
+const ENC_LZF: u8 = 3;
+
+/// Longest string read, the default `proto-max-bulk-len` of Redis
+const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+
+/// Most bytes a byte of LZF data expands to: a 3 bytes back reference copies up to 264 bytes
+const LZF_MAX_RATIO: usize = 88;
+
+/// A string key loaded from an RDB file
+#[derive(Debug)]
+pub(crate) struct Record {
+    pub(crate) key: String,
+    pub(crate) value: Bytes,
+    pub(crate) expires_at: Option<SystemTime>,
+}
+
+/// Serialize `value` as the payload of `DUMP`: the value, the RDB version then a CRC64 of both
+pub(crate) fn dump(value: &[u8]) -> Bytes {
+    let mut buf = BytesMut::new();
+    buf.put_u8(TYPE_STRING);
+    put_string(&mut buf, value);
+    buf.put_u16_le(RDB_VERSION);
+    let crc = crc64(0, &buf);
+    buf.put_u64_le(crc);
+    buf.freeze()
+}
+
+/// Read the value serialized by `DUMP`, by redust or by Redis
+pub(crate) fn restore(payload: &[u8]) -> crate::Result<Bytes> {
+    if payload.len() < 10 {
+        return Err(bad_payload());
+    }
+    let (body, footer) = payload.split_at(payload.len() - 10);
+    let version = u16::from_le_bytes([footer[0], footer[1]]);
+    let mut crc = [0; 8];
+    crc.copy_from_slice(&footer[2..]);
+    if version > RDB_MAX_VERSION || crc64(0, &payload[..payload.len() - 8]) != u64::from_le_bytes(crc) {
+        return Err(bad_payload());
+    }
+
+    let mut buf = Bytes::copy_from_slice(body);
+    match get_u8(&mut buf)? {
+        TYPE_STRING => {}
+        _ => return Err("ERR only string values can be restored".into()),
+    }
+    let value = get_string(&mut buf)?;
+    if buf.has_remaining() {
+        return Err("ERR Bad data format".into());
+    }
+    Ok(value)
+}
+
+/// Writes an RDB file to a temporary file, moved over the destination once complete
+pub(crate) struct Writer {
+    out: BufWriter<File>,
+    crc: u64,
+    tmp: PathBuf,
+    path: PathBuf,
+}
+
+impl Writer {
+    pub(crate) fn create(path: &Path) -> io::Result<Writer> {
+        let tmp = path.with_extension("tmp");
+        let mut writer = Writer {
+            out: BufWriter::new(File::create(&tmp)?),
+            crc: 0,
+            tmp,
+            path: path.to_path_buf(),
+        };
+
+        let mut buf = BytesMut::new();
+        buf.put_slice(format!("REDIS{:04}", RDB_VERSION).as_bytes());
+        buf.put_u8(OPCODE_SELECTDB);
+        put_length(&mut buf, 0);
+        writer.write_all(&buf)?;
+        Ok(writer)
+    }
+
+    pub(crate) fn write(&mut self, key: &str, value: &[u8], expires_at: Option<SystemTime>) -> io::Result<()> {
+        let mut buf = BytesMut::new();
+        if let Some(when) = expires_at {
+            buf.put_u8(OPCODE_EXPIRETIME_MS);
+            buf.put_u64_le(unix_millis(when));
+        }
+        buf.put_u8(TYPE_STRING);
+        put_string(&mut buf, key.as_bytes());
+        put_string(&mut buf, value);
+        self.write_all(&buf)
+    }
+
+    /// Write the end of the file and its checksum, then move it in place
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.write_all(&[OPCODE_EOF])?;
+        let crc = self.crc;
+        self.out.write_all(&crc.to_le_bytes())?;
+
+        let file = self.out.into_inner().map_err(|err| err.into_error())?;
+        file.sync_all()?;
+        fs::rename(&self.tmp, &self.path)
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        self.crc = crc64(self.crc, data);
+        self.out.write_all(data)
+    }
+}
+
+/// Read the string keys of the RDB file at `path`. Returns them along with the number of keys
+/// skipped.
+pub(crate) fn read(path: &Path) -> crate::Result<(Vec<Record>, usize)> {
+    let data = fs::read(path)?;
+    if data.len() < 9 || !data.starts_with(b"REDIS") {
+        return Err("invalid RDB file; unknown format".into());
+    }
+    let version = std::str::from_utf8(&data[5..9])
+        .ok()
+        .and_then(|version| version.parse::<u16>().ok())
+        .ok_or("invalid RDB file; unknown format")?;
+    if version > RDB_MAX_VERSION {
+        return Err(format!("unsupported RDB version {}", version).into());
+    }
+
+    let data = Bytes::from(data);
+    let mut buf = data.slice(9..);
+
+    let mut records = vec![];
+    let mut skipped = 0;
+    let mut db = 0;
+    let mut expires_at = None;
+    loop {
+        match get_u8(&mut buf)? {
+            OPCODE_EOF => break,
+            OPCODE_SELECTDB => db = get_length(&mut buf)?,
+            OPCODE_RESIZEDB => {
+                get_length(&mut buf)?;
+                get_length(&mut buf)?;
+            }
+            OPCODE_AUX => {
+                get_string(&mut buf)?;
+                get_string(&mut buf)?;
+            }
+            OPCODE_EXPIRETIME_MS => {
+                let ms = get_bytes(&mut buf, 8)?.get_u64_le();
+                expires_at = Some(UNIX_EPOCH + Duration::from_millis(ms));
+            }
+            OPCODE_EXPIRETIME => {
+                let secs = get_bytes(&mut buf, 4)?.get_u32_le();
+                expires_at = Some(UNIX_EPOCH + Duration::from_secs(secs as u64));
+            }
+            OPCODE_IDLE => {
+                get_length(&mut buf)?;
+            }
+            OPCODE_FREQ => {
+                get_u8(&mut buf)?;
+            }
+            OPCODE_FUNCTION2 => {
+                get_string(&mut buf)?;
+            }
+            OPCODE_SLOT_INFO => {
+                for _ in 0..3 {
+                    get_length(&mut buf)?;
+                }
+            }
+            kind => {
+                let key = get_string(&mut buf)?;
+                let value = match kind {
+                    TYPE_STRING => Some(get_string(&mut buf)?),
+                    _ => {
+                        skip_value(&mut buf, kind)?;
+                        None
+                    }
+                };
+                let expires_at = expires_at.take();
+
+                match (db, String::from_utf8(key.to_vec()), value) {
+                    (0, Ok(key), Some(value)) => records.push(Record {
+                        key,
+                        value,
+                        expires_at,
+                    }),
+                    _ => skipped += 1,
+                }
+            }
+        }
+    }
+
+    // the checksum covers everything up to the end marker included, files written with
+    // checksums disabled end with zeros
+    if version >= 5 {
+        let end = data.len() - buf.remaining();
+        let crc = get_bytes(&mut buf, 8)?.get_u64_le();
+        if crc != 0 && crc != crc64(0, &data[..end]) {
+            return Err("invalid RDB file; wrong checksum".into());
+        }
+    }
+
+    Ok((records, skipped))
+}
+
+/// Skip a value of a type other than string
+fn skip_value(buf: &mut Bytes, kind: u8) -> crate::Result<()> {
+    match kind {
+        // set, list, set of intset, ziplist or listpack encoded values are a single blob
+        9..=13 | 16 | 17 | 20 => {
+            get_string(buf)?;
+        }
+        // list, set, quicklist of ziplists
+        1 | 2 | 14 => {
+            for _ in 0..get_length(buf)? {
+                get_string(buf)?;
+            }
+        }
+        // hash
+        4 => {
+            for _ in 0..get_length(buf)? {
+                get_string(buf)?;
+                get_string(buf)?;
+            }
+        }
+        // sorted set, scores as strings
+        3 => {
+            for _ in 0..get_length(buf)? {
+                get_string(buf)?;
+                match get_u8(buf)? {
+                    // nan, +inf, -inf
+                    253..=255 => {}
+                    len => get_bytes(buf, len as usize).map(drop)?,
+                }
+            }
+        }
+        // sorted set, binary scores
+        5 => {
+            for _ in 0..get_length(buf)? {
+                get_string(buf)?;
+                get_bytes(buf, 8)?;
+            }
+        }
+        // quicklist of listpacks, each with its container kind
+        18 => {
+            for _ in 0..get_length(buf)? {
+                get_length(buf)?;
+                get_string(buf)?;
+            }
+        }
+        kind => return Err(format!("unsupported RDB value type {}", kind).into()),
+    }
+    Ok(())
+}
+
+/// Lengths are stored in 6, 14, 32 or 64 bits depending on their magnitude
+fn put_length(buf: &mut BytesMut, len: u64) {
+    if len < 1 << 6 {
+        buf.put_u8(len as u8);
+    } else if len < 1 << 14 {
+        buf.put_u8(0x40 | (len >> 8) as u8);
+        buf.put_u8(len as u8);
+    } else if len <= u32::MAX as u64 {
+        buf.put_u8(0x80);
+        buf.put_u32(len as u32);
+    } else {
+        buf.put_u8(0x81);
+        buf.put_u64(len);
+    }
+}
+
+fn put_string(buf: &mut BytesMut, data: &[u8]) {
+    put_length(buf, data.len() as u64);
+    buf.put_slice(data);
+}
+
+/// A length, or the encoding of a string if the two top bits are set
+enum Length {
+    Len(u64),
+    Encoded(u8),
+}
+
+fn get_length_or_encoding(buf: &mut Bytes) -> crate::Result<Length> {
+    let first = get_u8(buf)?;
+    Ok(match first >> 6 {
+        0 => Length::Len((first & 0x3f) as u64),
+        1 => Length::Len((((first & 0x3f) as u64) << 8) | get_u8(buf)? as u64),
+        3 => Length::Encoded(first & 0x3f),
+        _ => match first {
+            0x80 => Length::Len(get_bytes(buf, 4)?.get_u32() as u64),
+            0x81 => Length::Len(get_bytes(buf, 8)?.get_u64()),
+            _ => return Err(invalid()),
+        },
+    })
+}
+
+fn get_length(buf: &mut Bytes) -> crate::Result<u64> {
+    match get_length_or_encoding(buf)? {
+        Length::Len(len) => Ok(len),
+        Length::Encoded(_) => Err(invalid()),
+    }
+}
+
+/// A string, stored as is, as an integer or compressed with LZF
+fn get_string(buf: &mut Bytes) -> crate::Result<Bytes> {
+    match get_length_or_encoding(buf)? {
+        Length::Len(len) => get_bytes(buf, len as usize),
+        Length::Encoded(ENC_INT8) => Ok(Bytes::from((get_u8(buf)? as i8).to_string())),
+        Length::Encoded(ENC_INT16) => Ok(Bytes::from(get_bytes(buf, 2)?.get_i16_le().to_string())),
+        Length::Encoded(ENC_INT32) => Ok(Bytes::from(get_bytes(buf, 4)?.get_i32_le().to_string())),
+        Length::Encoded(ENC_LZF) => {
+            let compressed_len = get_length(buf)?;
+            let len = get_length(buf)?;
+            let compressed = get_bytes(buf, compressed_len as usize)?;
+            // the announced length is checked before decompressing, a small payload can't make
+            // the server allocate gigabytes
+            if len > MAX_STRING_LEN as u64 || len > (compressed.len() * LZF_MAX_RATIO) as u64 {
+                return Err(invalid());
+            }
+            lzf_decompress(&compressed, len as usize).map(Bytes::from)
+        }
+        Length::Encoded(_) => Err(invalid()),
+    }
+}
+
+/// Decompress LZF `data` into `len` bytes. Each chunk is either a run of literal bytes or a
+/// back reference into the output. The output grows as the data is decompressed, so a wrong
+/// `len` doesn't reserve memory.
+fn lzf_decompress(data: &[u8], len: usize) -> crate::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let ctrl = data[i] as usize;
+        i += 1;
+
+        if ctrl < 1 << 5 {
+            let run = data.get(i..i + ctrl + 1).ok_or_else(invalid)?;
+            out.extend_from_slice(run);
+            i += ctrl + 1;
+        } else {
+            let mut run = ctrl >> 5;
+            if run == 7 {
+                run += *data.get(i).ok_or_else(invalid)? as usize;
+                i += 1;
+            }
+            let offset = ((ctrl & 0x1f) << 8) + *data.get(i).ok_or_else(invalid)? as usize + 1;
+            i += 1;
+
+            let start = out.len().checked_sub(offset).ok_or_else(invalid)?;
+            // the reference may overlap the bytes being copied
+            for j in start..start + run + 2 {
+                out.push(out[j]);
+            }
+        }
+        if out.len() > len {
+            return Err(invalid());
+        }
+    }
+
+    if out.len() != len {
+        return Err(invalid());
+    }
+    Ok(out)
+}
+
+fn get_u8(buf: &mut Bytes) -> crate::Result<u8> {
+    if !buf.has_remaining() {
+        return Err(invalid());
+    }
+    Ok(buf.get_u8())
+}
+
+fn get_bytes(buf: &mut Bytes, len: usize) -> crate::Result<Bytes> {
+    if buf.remaining() < len {
+        return Err(invalid());
+    }
+    Ok(buf.split_to(len))
+}
+
+fn invalid() -> crate::Error {
+    "invalid RDB data; truncated or corrupted".into()
+}
+
+fn bad_payload() -> crate::Error {
+    "ERR DUMP payload version or checksum are wrong".into()
+}
+
+/// CRC64 of Redis, the Jones polynomial with reflected input and output
+fn crc64(crc: u64, data: &[u8]) -> u64 {
+    data.iter().fold(crc, |crc, &b| {
+        CRC64_TABLE[((crc ^ b as u64) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+const CRC64_TABLE: [u64; 256] = crc64_table();
+
+const fn crc64_table() -> [u64; 256] {
+    // the Jones polynomial 0xad93d23594c935a9, reflected
+    const POLY: u64 = 0x95ac_9329_ac4b_c9b5;
+
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ POLY } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
@@ -0,0 +1,495 @@
+//! Minimal sentinel: watch a primary and promote one of its replicas once enough sentinels agree
+//! it is down. Enabled with `server::Builder::sentinel`.
+//!
+//! The watched nodes must be replicated servers supporting `REPLICAOF`, such as Redis. redust has
+//! no replica role, `SYNCFROM` being its only outbound stream, so a redust sentinel watches Redis
+//! deployments and a redust primary can't be failed over.
+//!
+//! The primary is pinged every second. Once it stayed unreachable for `down_after`, the sentinel
+//! asks its peers with `SENTINEL is-master-down-by-addr`. When the sentinels seeing it down reach
+//! the quorum, one of them is elected to fail over, as in Redis Sentinel:
+//!
+//! * the candidate starts a new epoch, votes for itself and asks its peers for their vote with
+//!   `SENTINEL is-master-down-by-addr ip port <epoch> <run id>`;
+//! * a sentinel votes once per epoch, for the first candidate asking;
+//! * the candidate is the leader if it got the votes of a majority of the sentinels, and of at
+//!   least the quorum. Otherwise it tries again in a later epoch, after a random delay so the
+//!   candidates don't keep splitting the votes.
+//!
+//! The leader sends `REPLICAOF NO ONE` to the first reachable replica, in the configured order,
+//! then points the other replicas at it. The new primary is tagged with the epoch of the election,
+//! its configuration epoch. The other sentinels, still seeing the former primary down, ask their
+//! peers with `SENTINEL master` and adopt the primary with the highest configuration epoch.
+//!
+//! Clients discover the current primary with `SENTINEL get-master-addr-by-name <name>`.
+//!
+//! Unlike Redis Sentinel, the replicas and peers aren't discovered, they are configured. The
+//! former primary isn't reconfigured when it comes back, it has to be made a replica by hand.
+
+use crate::backoff::{random_bits, random_unit};
+use crate::client::{self, Client};
+
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::time::{self, Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// How often the primary is pinged
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time given to a node to accept a connection and reply
+const REPLY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Default time the primary must stay unreachable to be considered down
+const DEFAULT_DOWN_AFTER: Duration = Duration::from_secs(30);
+
+/// The primary watched along with its replicas and the other sentinels watching it
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    name: String,
+    primary: SocketAddr,
+    quorum: usize,
+    replicas: Vec<SocketAddr>,
+    peers: Vec<SocketAddr>,
+    down_after: Duration,
+    auth: Option<Credentials>,
+    peer_auth: Option<Credentials>,
+}
+
+/// Sent with `AUTH` on the connections opened by the sentinel
+#[derive(Clone)]
+struct Credentials {
+    username: Option<String>,
+    password: String,
+}
+
+/// State of the failover, shared with the `SENTINEL` command
+#[derive(Debug)]
+pub(crate) struct Sentinel {
+    name: String,
+    /// Identifies the sentinel in the elections
+    run_id: String,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    primary: SocketAddr,
+    replicas: Vec<SocketAddr>,
+    /// Whether this sentinel sees the current primary down
+    down: bool,
+    /// Former primaries, still reported down to the peers which didn't fail over yet
+    demoted: Vec<SocketAddr>,
+    /// Latest epoch seen, from this sentinel or its peers
+    current_epoch: u64,
+    /// Epoch of the election which made `primary` the primary, `0` for the configured one
+    config_epoch: u64,
+    /// Latest vote: its epoch, the run id voted for and when
+    vote: Option<(u64, String, Instant)>,
+}
+
+impl Monitor {
+    /// Watch `primary` under `name`, failing over once `quorum` sentinels, this one included, see
+    /// it down
+    pub fn new(name: impl ToString, primary: SocketAddr, quorum: usize) -> Monitor {
+        Monitor {
+            name: name.to_string(),
+            primary,
+            quorum,
+            replicas: vec![],
+            peers: vec![],
+            down_after: DEFAULT_DOWN_AFTER,
+            auth: None,
+            peer_auth: None,
+        }
+    }
+
+    /// Replicas of the primary, in the order they are tried for promotion
+    pub fn replicas(mut self, replicas: Vec<SocketAddr>) -> Monitor {
+        self.replicas = replicas;
+        self
+    }
+
+    /// Addresses of the other sentinels watching the primary
+    pub fn peers(mut self, peers: Vec<SocketAddr>) -> Monitor {
+        self.peers = peers;
+        self
+    }
+
+    /// Time the primary must stay unreachable to be considered down, 30 seconds by default
+    pub fn down_after(mut self, down_after: Duration) -> Monitor {
+        self.down_after = down_after;
+        self
+    }
+
+    /// Authenticate to the primary and the replicas as `username`, or as the default user, with
+    /// `password`
+    pub fn auth(mut self, username: Option<&str>, password: impl ToString) -> Monitor {
+        self.auth = Some(Credentials::new(username, password));
+        self
+    }
+
+    /// Authenticate to the other sentinels as `username`, or as the default user, with
+    /// `password`
+    pub fn peer_auth(mut self, username: Option<&str>, password: impl ToString) -> Monitor {
+        self.peer_auth = Some(Credentials::new(username, password));
+        self
+    }
+
+    /// Votes a candidate needs to lead a failover: a majority of the sentinels, and at least the
+    /// quorum
+    fn votes_needed(&self) -> usize {
+        let sentinels = self.peers.len() + 1;
+        let majority = sentinels / 2 + 1;
+        majority.max(self.quorum)
+    }
+}
+
+/// Parse `<name> <ip:port> <quorum>`, as `sentinel monitor` is configured in Redis
+impl FromStr for Monitor {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<Monitor> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        match parts[..] {
+            [name, primary, quorum] => {
+                let primary = primary
+                    .parse()
+                    .map_err(|_| format!("invalid primary address '{}'", primary))?;
+                let quorum = quorum
+                    .parse()
+                    .map_err(|_| format!("invalid quorum '{}'", quorum))?;
+                Ok(Monitor::new(name, primary, quorum))
+            }
+            _ => Err("expected '<name> <ip:port> <quorum>'".into()),
+        }
+    }
+}
+
+impl Credentials {
+    fn new(username: Option<&str>, password: impl ToString) -> Credentials {
+        Credentials {
+            username: username.map(str::to_string),
+            password: password.to_string(),
+        }
+    }
+}
+
+/// The password is kept out of the logs
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .finish()
+    }
+}
+
+impl Sentinel {
+    pub(crate) fn new(monitor: &Monitor) -> Sentinel {
+        let run_id = (0..2).map(|_| format!("{:016x}", random_bits())).collect();
+
+        Sentinel {
+            name: monitor.name.clone(),
+            run_id,
+            state: Mutex::new(State {
+                primary: monitor.primary,
+                replicas: monitor.replicas.clone(),
+                down: false,
+                demoted: vec![],
+                current_epoch: 0,
+                config_epoch: 0,
+                vote: None,
+            }),
+        }
+    }
+
+    /// Address of the current primary of `name`, `None` if it isn't the one watched
+    pub(crate) fn primary_addr(&self, name: &str) -> Option<SocketAddr> {
+        Some(self.primary()).filter(|_| name == self.name)
+    }
+
+    /// Address of the current primary of `name` along with its configuration epoch and whether
+    /// this sentinel sees it down, `None` if it isn't the one watched
+    pub(crate) fn primary_config(&self, name: &str) -> Option<(SocketAddr, u64, bool)> {
+        let state = self.state.lock().unwrap();
+        Some((state.primary, state.config_epoch, state.down)).filter(|_| name == self.name)
+    }
+
+    /// Whether this sentinel sees `addr` down, as asked by its peers
+    pub(crate) fn is_down(&self, addr: SocketAddr) -> bool {
+        let state = self.state.lock().unwrap();
+        (state.primary == addr && state.down) || state.demoted.contains(&addr)
+    }
+
+    /// Vote for `candidate` in `epoch` unless this sentinel already voted in that epoch. Returns
+    /// the run id voted for in the latest epoch along with that epoch.
+    pub(crate) fn vote(&self, epoch: u64, candidate: &str) -> (String, u64) {
+        let mut state = self.state.lock().unwrap();
+        state.current_epoch = state.current_epoch.max(epoch);
+        let voted = state.vote.as_ref().map_or(0, |(voted, _, _)| *voted);
+        if voted < epoch {
+            debug!(epoch, candidate, "voted");
+            state.vote = Some((epoch, candidate.to_string(), Instant::now()));
+        }
+        match &state.vote {
+            Some((epoch, leader, _)) => (leader.clone(), *epoch),
+            None => (String::new(), 0),
+        }
+    }
+
+    fn primary(&self) -> SocketAddr {
+        self.state.lock().unwrap().primary
+    }
+
+    /// Mark the primary down or up, returns the previous state
+    fn set_down(&self, down: bool) -> bool {
+        std::mem::replace(&mut self.state.lock().unwrap().down, down)
+    }
+
+    fn replicas(&self) -> Vec<SocketAddr> {
+        self.state.lock().unwrap().replicas.clone()
+    }
+
+    fn config_epoch(&self) -> u64 {
+        self.state.lock().unwrap().config_epoch
+    }
+
+    /// Start an election in a new epoch, voting for this sentinel. Returns the epoch.
+    fn start_election(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.current_epoch += 1;
+        let epoch = state.current_epoch;
+        state.vote = Some((epoch, self.run_id.clone(), Instant::now()));
+        epoch
+    }
+
+    /// Whether this sentinel voted for another one within `within`, which may be failing over
+    fn backing_peer(&self, within: Duration) -> bool {
+        let state = self.state.lock().unwrap();
+        matches!(&state.vote, Some((_, leader, at)) if *leader != self.run_id && at.elapsed() < within)
+    }
+
+    /// Make `addr` the primary as of `epoch`, the former primary joins the replicas. Ignored if
+    /// the current primary is as recent.
+    fn promote(&self, addr: SocketAddr, epoch: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if epoch <= state.config_epoch {
+            return false;
+        }
+        let former = std::mem::replace(&mut state.primary, addr);
+        if former != addr {
+            state.replicas.retain(|replica| *replica != addr);
+            state.replicas.push(former);
+            state.demoted.push(former);
+            state.demoted.retain(|demoted| *demoted != addr);
+        }
+        state.config_epoch = epoch;
+        state.current_epoch = state.current_epoch.max(epoch);
+        state.down = false;
+        true
+    }
+}
+
+/// Watch the primary and fail over when the quorum is reached, until the task is aborted
+pub(crate) async fn watch(sentinel: Arc<Sentinel>, monitor: Monitor) {
+    let mut last_reply = Instant::now();
+    let mut interval = time::interval(PING_INTERVAL);
+    let mut next_election = Instant::now();
+
+    loop {
+        interval.tick().await;
+        let primary = sentinel.primary();
+        if ping(primary, monitor.auth.as_ref()).await {
+            last_reply = Instant::now();
+            if sentinel.set_down(false) {
+                info!(%primary, "primary is reachable again");
+            }
+            continue;
+        }
+        if last_reply.elapsed() < monitor.down_after {
+            continue;
+        }
+        if !sentinel.set_down(true) {
+            warn!(%primary, "primary is down");
+        }
+
+        // a peer may have failed over already
+        if let Some((promoted, epoch)) = newer_primary(&sentinel, &monitor).await {
+            if sentinel.promote(promoted, epoch) {
+                info!(former = %primary, primary = %promoted, epoch, "primary changed by a peer");
+                last_reply = Instant::now();
+                continue;
+            }
+        }
+
+        let mut votes = 1;
+        for peer in &monitor.peers {
+            if peer_sees_down(*peer, primary, monitor.peer_auth.as_ref()).await {
+                votes += 1;
+            }
+        }
+        if votes < monitor.quorum {
+            debug!(%primary, votes, quorum = monitor.quorum, "quorum not reached");
+            continue;
+        }
+
+        // give the election in progress, or the one which failed, time to settle
+        let retry = PING_INTERVAL.mul_f64(1.0 + 3.0 * random_unit());
+        if Instant::now() < next_election || sentinel.backing_peer(monitor.down_after.max(retry)) {
+            continue;
+        }
+        next_election = Instant::now() + retry;
+
+        let epoch = sentinel.start_election();
+        let mut votes = 1;
+        for peer in &monitor.peers {
+            if request_vote(*peer, primary, epoch, &sentinel.run_id, monitor.peer_auth.as_ref()).await {
+                votes += 1;
+            }
+        }
+        let needed = monitor.votes_needed();
+        if votes < needed {
+            debug!(%primary, epoch, votes, needed, "election lost");
+            continue;
+        }
+
+        info!(%primary, epoch, votes, "elected to fail over");
+        match failover(&sentinel, &monitor, epoch).await {
+            Some(promoted) => {
+                info!(former = %primary, primary = %promoted, epoch, "failed over");
+                last_reply = Instant::now();
+            }
+            None => warn!(%primary, "no replica could be promoted"),
+        }
+    }
+}
+
+/// Promote the first replica which accepts `REPLICAOF NO ONE`, then point the others at it
+async fn failover(sentinel: &Sentinel, monitor: &Monitor, epoch: u64) -> Option<SocketAddr> {
+    let auth = monitor.auth.as_ref();
+    for replica in sentinel.replicas() {
+        let promoted = async {
+            let mut client = connect(replica, auth).await?;
+            client.command::<String>(("replicaof", "no", "one")).await
+        };
+        if let Err(err) = promoted.await {
+            warn!(%replica, cause = %err, "failed to promote the replica");
+            continue;
+        }
+        sentinel.promote(replica, epoch);
+
+        for other in sentinel.replicas() {
+            let repointed = async {
+                let mut client = connect(other, auth).await?;
+                let port = replica.port();
+                client.command::<String>(("replicaof", replica.ip().to_string(), port)).await
+            };
+            if let Err(err) = repointed.await {
+                debug!(replica = %other, cause = %err, "failed to point the replica at the new primary");
+            }
+        }
+        return Some(replica);
+    }
+    None
+}
+
+/// Connect to `addr`, authenticating with `credentials` if given
+async fn connect(addr: SocketAddr, credentials: Option<&Credentials>) -> crate::Result<Client> {
+    let mut client = client::connect_timeout(addr, REPLY_TIMEOUT).await?;
+    client.set_timeout(Some(REPLY_TIMEOUT));
+    if let Some(credentials) = credentials {
+        client
+            .auth(credentials.username.as_deref(), &credentials.password)
+            .await?;
+    }
+    Ok(client)
+}
+
+async fn ping(addr: SocketAddr, credentials: Option<&Credentials>) -> bool {
+    let reply = async {
+        let mut client = connect(addr, credentials).await?;
+        client.ping(None).await
+    };
+    // a node replying an error, refusing the credentials for instance, is up
+    matches!(
+        reply.await,
+        Ok(_) | Err(crate::Error::Server(_)) | Err(crate::Error::NotAuthenticated)
+    )
+}
+
+/// Ask `peer` whether it sees `primary` down, an unreachable peer doesn't
+async fn peer_sees_down(peer: SocketAddr, primary: SocketAddr, credentials: Option<&Credentials>) -> bool {
+    let reply = async {
+        let mut client = connect(peer, credentials).await?;
+        let args = (
+            ("sentinel", "is-master-down-by-addr"),
+            (primary.ip().to_string(), primary.port()),
+            (0, "*"),
+        );
+        client.command::<(u64, String, u64)>(args).await
+    };
+    matches!(reply.await, Ok((1, _, _)))
+}
+
+/// Ask `peer` to vote for `run_id` in `epoch`, returns whether it did
+async fn request_vote(
+    peer: SocketAddr,
+    primary: SocketAddr,
+    epoch: u64,
+    run_id: &str,
+    credentials: Option<&Credentials>,
+) -> bool {
+    let reply = async {
+        let mut client = connect(peer, credentials).await?;
+        let args = (
+            ("sentinel", "is-master-down-by-addr"),
+            (primary.ip().to_string(), primary.port()),
+            (epoch, run_id),
+        );
+        client.command::<(u64, String, u64)>(args).await
+    };
+    match reply.await {
+        Ok((_, leader, leader_epoch)) => leader == run_id && leader_epoch == epoch,
+        Err(err) => {
+            debug!(%peer, cause = %err, "failed to request a vote");
+            false
+        }
+    }
+}
+
+/// The primary of the peers with the highest configuration epoch, if more recent than the one of
+/// this sentinel
+async fn newer_primary(sentinel: &Sentinel, monitor: &Monitor) -> Option<(SocketAddr, u64)> {
+    let mut newest: Option<(SocketAddr, u64)> = None;
+    for peer in &monitor.peers {
+        let reply = async {
+            let mut client = connect(*peer, monitor.peer_auth.as_ref()).await?;
+            client.command::<Vec<String>>(("sentinel", "master", &monitor.name[..])).await
+        };
+        let fields = match reply.await {
+            Ok(fields) => fields,
+            Err(err) => {
+                debug!(%peer, cause = %err, "failed to ask for the primary");
+                continue;
+            }
+        };
+        let field = |name: &str| {
+            fields
+                .chunks(2)
+                .find(|pair| pair.len() == 2 && pair[0] == name)
+                .map(|pair| pair[1].clone())
+        };
+        let addr = match (field("ip"), field("port")) {
+            (Some(ip), Some(port)) => format!("{}:{}", ip, port).parse::<SocketAddr>().ok(),
+            _ => None,
+        };
+        let epoch = field("config-epoch").and_then(|epoch| epoch.parse::<u64>().ok());
+        if let (Some(addr), Some(epoch)) = (addr, epoch) {
+            if newest.is_none_or(|(_, newest)| epoch > newest) {
+                newest = Some((addr, epoch));
+            }
+        }
+    }
+    newest.filter(|(_, epoch)| *epoch > sentinel.config_epoch())
+}
@@ -1,22 +1,55 @@
-use crate::{Command, Connection, Db, Shutdown};
-
+use crate::config::Settings;
+use crate::frame::Limits;
+use crate::command_stats::RecordCalls;
+use crate::latency::RecordLatency;
+use crate::middleware::{Context, Next};
+use crate::rate_limit::{ClientKey, RateLimit};
+use crate::slowlog::RecordSlow;
+use crate::sentinel::{self, Monitor, Sentinel};
+use crate::timeout::CommandTimeout;
+use crate::{cmd, db, health, proxy_protocol, socket, Backoff, Command, CommandHandler, Connection, Db, Frame, Layer, Shutdown, Store, ValueTransform};
+
+use std::collections::HashMap;
 use std::future::Future;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{broadcast, mpsc, Semaphore};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
 use tokio::time::{self, Duration};
-use tracing::{debug, error, info, instrument};
+use tracing::{debug, error, field, info, info_span, warn, Instrument, Span};
 
 #[derive(Debug)]
 struct Listener {
     db: Db,
 
+    frame_limits: Limits,
+
     listener: TcpListener,
 
-    limit_connections: Arc<Semaphore>,
+    /// Retries of a failed `accept`
+    accept_backoff: Backoff,
+
+    /// Whether connections over the limit are turned away instead of waiting for a slot
+    reject_excess_clients: bool,
+
+    /// Whether connections start with a PROXY protocol header
+    proxy_protocol: bool,
+
+    /// Connections accepted on other transports, served along with the ones of `listener`
+    bridged: Option<mpsc::Receiver<Connection>>,
+
+    /// Run around every command, outermost first
+    layers: Arc<[Arc<dyn Layer>]>,
 
     notify_shutdown: broadcast::Sender<()>,
 
+    /// Dropped when the drain timeout elapses, aborting the handlers still running
+    notify_abort: broadcast::Sender<()>,
+
     shutdown_complete_rx: mpsc::Receiver<()>,
     shutdown_complete_tx: mpsc::Sender<()>,
 }
@@ -27,114 +60,796 @@ struct Handler {
 
     connection: Connection,
 
-    limit_connections: Arc<Semaphore>,
+    layers: Arc<[Arc<dyn Layer>]>,
 
     shutdown: Shutdown,
 
     _shutdown_complete: mpsc::Sender<()>,
 }
 
-const MAX_CONNECTION: usize = 250;
+/// Shards of the key space per runtime worker thread, see `Builder::worker_threads`
+pub const SHARDS_PER_WORKER: usize = 4;
+
+/// Server configuration, `run` is a shorthand for running with the defaults.
+#[derive(Debug, Default)]
+pub struct Builder {
+    /// Initial value of the settings which can be changed at runtime with `CONFIG SET`
+    settings: Settings,
+    frame_limits: Limits,
+    shards: Option<usize>,
+    worker_threads: Option<usize>,
+    drain_timeout: Option<Duration>,
+    reject_excess_clients: bool,
+    proxy_protocol: bool,
+    accept_backoff: Backoff,
+    value_transform: Option<Arc<dyn ValueTransform>>,
+    store: Option<Store>,
+    warm_restart: Option<PathBuf>,
+    import_rdb: Option<PathBuf>,
+    layers: Vec<Arc<dyn Layer>>,
+    commands: HashMap<String, Arc<dyn CommandHandler>>,
+    health_listener: Option<TcpListener>,
+    sentinel: Option<Monitor>,
+    #[cfg(feature = "websocket")]
+    websocket_listener: Option<TcpListener>,
+    #[cfg(feature = "http-gateway")]
+    http_listener: Option<TcpListener>,
+    /// Connections over other transports, served along with the ones of the listener
+    bridge: Option<(mpsc::Sender<Connection>, mpsc::Receiver<Connection>)>,
+}
+
+/// What happens to a subscriber falling so far behind that its channel is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PubSubOverflow {
+    /// The oldest messages are dropped and the subscriber gets a `lagged` message saying how many
+    Drop,
+    /// The subscriber is answered an error and disconnected
+    Disconnect,
+}
+
+impl PubSubOverflow {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            PubSubOverflow::Drop => "drop",
+            PubSubOverflow::Disconnect => "disconnect",
+        }
+    }
 
+    pub(crate) fn from_name(name: &str) -> Option<PubSubOverflow> {
+        match &name.to_lowercase()[..] {
+            "drop" => Some(PubSubOverflow::Drop),
+            "disconnect" => Some(PubSubOverflow::Disconnect),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for PubSubOverflow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PubSubOverflow, String> {
+        PubSubOverflow::from_name(s)
+            .ok_or_else(|| format!("unknown overflow policy `{}`, expected `drop` or `disconnect`", s))
+    }
+}
+
+/// Run the server with the default configuration.
+///
+/// Accepts connections from `listener` until `shutdown` completes.
 pub async fn run(listener: TcpListener, shutdown: impl Future) -> crate::Result<()> {
-    let (notify_shutdown, _) = broadcast::channel(1);
-    let (shutdown_complete_tx, shutdown_complete_rx) = mpsc::channel(1);
+    Builder::new().run(listener, shutdown).await
+}
 
-    let mut server = Listener{
-        listener,
-        db: Db::new(),
-        limit_connections: Arc::new(Semaphore::new(MAX_CONNECTION)),
-        notify_shutdown,
-        shutdown_complete_tx,
-        shutdown_complete_rx,
+/// Start the server with the default configuration in the background, see `Builder::start`.
+pub fn start(listener: TcpListener) -> crate::Result<Server> {
+    Builder::new().start(listener)
+}
 
-    };
+/// A server running in the background, returned by `Builder::start`.
+///
+/// Dropping it shuts the server down without waiting for it to complete, see `join`.
+#[derive(Debug)]
+pub struct Server {
+    local_addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    task: JoinHandle<crate::Result<()>>,
+}
 
-    tokio::select! {
-        res = server.run() => {
-            if let Err(err) = res {
-                error!(cause = %err, "failed to accept");
-            }
+impl Server {
+    /// Address the server accepts connections on, the actual port when bound to port 0
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting connections and start draining the open ones. Returns right away, `join`
+    /// waits for the server to complete.
+    pub fn shutdown(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+
+    /// Wait for the server to complete, after `shutdown` or on a fatal error
+    pub async fn join(mut self) -> crate::Result<()> {
+        // keep the shutdown trigger alive, dropping it would stop the server
+        let _shutdown = self.shutdown.take();
+        (&mut self.task).await.map_err(|err| err.to_string())?
+    }
+}
+
+impl Builder {
+    pub fn new() -> Builder {
+        Builder::default()
+    }
+
+    /// Periodically shrink the key space maps once they hold far more capacity than entries.
+    pub fn active_defrag(mut self, enabled: bool) -> Builder {
+        self.settings.active_defrag = enabled;
+        self
+    }
+
+    /// Limits applied to frames received from clients. A client exceeding them gets disconnected.
+    pub fn frame_limits(mut self, limits: Limits) -> Builder {
+        self.frame_limits = limits;
+        self
+    }
+
+    /// Number of the latest writes kept for `SYNCFROM` consumers resuming from a past sequence
+    /// number.
+    pub fn write_backlog(mut self, records: usize) -> Builder {
+        self.settings.write_backlog = records;
+        self
+    }
+
+    /// Number of partitions of the key space, each with its own lock. Commands on keys of
+    /// different shards run in parallel. Defaults to 16, or to `SHARDS_PER_WORKER` shards per
+    /// worker thread when `worker_threads` is set.
+    pub fn shards(mut self, shards: usize) -> Builder {
+        self.shards = Some(shards);
+        self
+    }
+
+    /// Number of worker threads of the runtime the server runs on, which sizes the key space so
+    /// the workers seldom contend on a shard. The runtime itself is left to the caller.
+    pub fn worker_threads(mut self, workers: usize) -> Builder {
+        self.worker_threads = Some(workers);
+        self
+    }
+
+    /// How long in-flight connections are given to finish once shutdown starts, the handlers still
+    /// running afterward are aborted. Without a timeout the server waits for every connection.
+    pub fn drain_timeout(mut self, timeout: Duration) -> Builder {
+        self.drain_timeout = Some(timeout);
+        self
+    }
+
+    /// Maximum number of connections served at once.
+    pub fn max_clients(mut self, max: usize) -> Builder {
+        self.settings.max_clients = max;
+        self
+    }
+
+    /// Memory usage of the key space past which the commands adding data are answered `-OOM`,
+    /// nothing is evicted. No limit if 0, the default.
+    pub fn maxmemory(mut self, bytes: usize) -> Builder {
+        self.settings.maxmemory = bytes;
+        self
+    }
+
+    /// Whether `TCP_NODELAY` is set on the accepted connections, so replies are sent without
+    /// waiting to be coalesced. On by default.
+    pub fn tcp_nodelay(mut self, enabled: bool) -> Builder {
+        self.settings.tcp_nodelay = enabled;
+        self
+    }
+
+    /// Send keepalive probes on the connections idle for `idle`, so the slot of a vanished client
+    /// is freed instead of held until the operating system gives up. `None` disables them, the
+    /// default is 300 seconds.
+    pub fn tcp_keepalive(mut self, idle: Option<Duration>) -> Builder {
+        self.settings.tcp_keepalive = idle;
+        self
+    }
+
+    /// Once `max_clients` is reached, reply `-ERR max clients reached` to new connections and
+    /// close them, as Redis does. By default they wait in the accept backlog for a free slot.
+    pub fn reject_excess_clients(mut self, enabled: bool) -> Builder {
+        self.reject_excess_clients = enabled;
+        self
+    }
+
+    /// Expect every connection to start with a PROXY protocol header, v1 or v2, as sent by
+    /// HAProxy or AWS NLB. The address of the client it carries is then the one logged, reported
+    /// and rate limited, instead of the address of the load balancer. Connections without a
+    /// valid header are closed.
+    pub fn proxy_protocol(mut self, enabled: bool) -> Builder {
+        self.proxy_protocol = enabled;
+        self
+    }
+
+    /// How failed accepts are retried, the server stops once the retries are exhausted.
+    pub fn accept_backoff(mut self, backoff: Backoff) -> Builder {
+        self.accept_backoff = backoff;
+        self
+    }
+
+    /// Record one read out of `sample` of the keys matching `patterns` in the commit pipeline, so
+    /// `SYNCFROM` consumers receive who read which key and when, see `WriteRecord::to_frame`. The
+    /// recorded reads take room in the write backlog like the writes.
+    pub fn audit_reads(mut self, patterns: Vec<String>, sample: u64) -> Builder {
+        self.settings.audit_read_patterns = patterns;
+        self.settings.audit_read_sample = sample.max(1);
+        self
+    }
+
+    /// Store the values as transformed by `transform`, to encrypt or compress them at rest.
+    pub fn value_transform(mut self, transform: Arc<dyn ValueTransform>) -> Builder {
+        self.value_transform = Some(transform);
+        self
+    }
+
+    /// Run every command through `layer`. Layers added first are the outermost ones.
+    pub fn layer(mut self, layer: Arc<dyn Layer>) -> Builder {
+        self.layers.push(layer);
+        self
+    }
+
+    /// Serve the command `name` with `handler`, see the `extension` module. Names are case
+    /// insensitive, registering a name twice keeps the last handler. Built-in commands can't be
+    /// replaced, their names are refused with an error.
+    pub fn register_command(
+        mut self,
+        name: impl ToString,
+        handler: Arc<dyn CommandHandler>,
+    ) -> crate::Result<Builder> {
+        let name = name.to_string().trim().to_lowercase();
+        if cmd::registry::lookup(&name).is_some() {
+            return Err(format!("`{}` is a built-in command", name).into());
         }
-        _ = shutdown => {
-            info!("shutdown");
+        self.commands.insert(name, handler);
+        Ok(self)
+    }
+
+    /// Allow the `DEBUG` subcommands altering the server behavior, such as `DEBUG SLEEP`. Fixed
+    /// at startup, `CONFIG SET enable-debug-command` is refused.
+    pub fn enable_debug_command(mut self, enabled: bool) -> Builder {
+        self.settings.enable_debug_command = enabled;
+        self
+    }
+
+    /// Log every frame received and sent by the connections, see `Connection::set_protocol_dump`.
+    /// Can be changed at runtime with `CONFIG SET protocol-dump`.
+    pub fn protocol_dump(mut self, enabled: bool) -> Builder {
+        self.settings.protocol_dump = enabled;
+        self
+    }
+
+    /// Answer HTTP readiness probes on `GET /healthz` and serve the pub/sub metrics on
+    /// `GET /metrics` from `listener`, see the `health` module.
+    pub fn health_check(mut self, listener: TcpListener) -> Builder {
+        self.health_listener = Some(listener);
+        self
+    }
+
+    /// Watch the primary of `monitor` and fail over to one of its replicas once it is down, see
+    /// the `sentinel` module. The primary and its replicas must support `REPLICAOF`, as Redis
+    /// servers do. The current primary is then served by `SENTINEL
+    /// get-master-addr-by-name`.
+    pub fn sentinel(mut self, monitor: Monitor) -> Builder {
+        self.sentinel = Some(monitor);
+        self
+    }
+
+    /// Also accept connections over WebSocket from `listener`, see the `websocket` module.
+    #[cfg(feature = "websocket")]
+    pub fn websocket(mut self, listener: TcpListener) -> Builder {
+        self.websocket_listener = Some(listener);
+        self
+    }
+
+    /// Also serve the HTTP API from `listener`, see the `gateway` module.
+    #[cfg(feature = "http-gateway")]
+    pub fn http_gateway(mut self, listener: TcpListener) -> Builder {
+        self.http_listener = Some(listener);
+        self
+    }
+
+    /// Password clients must authenticate with, using `AUTH`, before running commands. Can be
+    /// changed at runtime with `CONFIG SET requirepass`, connections already open stay
+    /// authenticated.
+    pub fn requirepass(mut self, password: impl ToString) -> Builder {
+        self.settings.requirepass = Some(password.to_string());
+        self
+    }
+
+    /// Accept at most `ops_per_sec` commands per second from all the clients together, the
+    /// others are answered `-BUSY rate limit exceeded`. Unlimited if 0, the default. Can be
+    /// changed at runtime with `CONFIG SET ratelimit-global`.
+    pub fn rate_limit(mut self, ops_per_sec: u64) -> Builder {
+        self.settings.ratelimit_global = ops_per_sec;
+        self
+    }
+
+    /// Accept at most `ops_per_sec` commands per second from each client, clients being told
+    /// apart by `key`. Can be changed at runtime with `CONFIG SET ratelimit-client` and
+    /// `ratelimit-client-by`.
+    pub fn client_rate_limit(mut self, ops_per_sec: u64, key: ClientKey) -> Builder {
+        self.settings.ratelimit_client = ops_per_sec;
+        self.settings.ratelimit_client_by = key;
+        self
+    }
+
+    /// Number of messages a pub/sub channel holds for its slowest subscriber, 1024 by default.
+    /// Applies to the channels created afterward. Can be changed at runtime with `CONFIG SET
+    /// pubsub-channel-capacity`.
+    pub fn pubsub_channel_capacity(mut self, capacity: usize) -> Builder {
+        self.settings.pubsub_channel_capacity = capacity.max(1);
+        self
+    }
+
+    /// What happens to the subscribers whose channel is full, `PubSubOverflow::Drop` by default.
+    /// Can be changed at runtime with `CONFIG SET pubsub-overflow`.
+    pub fn pubsub_overflow(mut self, policy: PubSubOverflow) -> Builder {
+        self.settings.pubsub_overflow = policy;
+        self
+    }
+
+    /// Answer `-TIMEOUT` to the commands still running after `timeout`, see the `timeout`
+    /// module. Can be changed at runtime with `CONFIG SET command-timeout`, in milliseconds.
+    pub fn command_timeout(mut self, timeout: Duration) -> Builder {
+        self.settings.command_timeout = Some(timeout);
+        self
+    }
+
+    /// Keep the key space across restarts through the snapshot file at `path`.
+    ///
+    /// On `SIGUSR2` the server stops accepting connections, drains them then writes the snapshot
+    /// and returns. A snapshot found at `path` on startup is loaded before accepting connections,
+    /// then removed.
+    pub fn warm_restart(mut self, path: impl Into<PathBuf>) -> Builder {
+        self.warm_restart = Some(path.into());
+        self
+    }
+
+    /// Path of the RDB file written by `SAVE`, `dump.rdb` in the working directory by default.
+    /// The file name can be changed at runtime with `CONFIG SET dbfilename`, not its directory.
+    pub fn rdb_file(mut self, path: impl Into<PathBuf>) -> Builder {
+        let path = path.into();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            self.settings.dir = dir.to_path_buf();
+        }
+        if let Some(name) = path.file_name() {
+            self.settings.dbfilename = name.to_string_lossy().into_owned();
         }
+        self
+    }
+
+    /// Load the string keys of the RDB file at `path` before accepting connections, to migrate
+    /// from Redis. See the `rdb` module for what is left out.
+    pub fn import_rdb(mut self, path: impl Into<PathBuf>) -> Builder {
+        self.import_rdb = Some(path.into());
+        self
+    }
+
+    /// Sender of connections over other transports, such as the in-memory streams of the
+    /// simulation, served along with the ones accepted from the listener
+    #[cfg(feature = "simulation")]
+    pub(crate) fn bridge(&mut self) -> mpsc::Sender<Connection> {
+        self.bridge.get_or_insert_with(|| mpsc::channel(1)).0.clone()
     }
 
-    let Listener {
-        mut shutdown_complete_rx,
-        shutdown_complete_tx,
-        notify_shutdown,
-        ..
-    } = server;
+    /// Serve `store` instead of a new key space, so the application keeps direct access to the
+    /// data the clients see. `shards` and `value_transform` don't apply, the key space is already
+    /// created.
+    pub fn store(mut self, store: Store) -> Builder {
+        self.store = Some(store);
+        self
+    }
 
-    drop(notify_shutdown);
-    drop(shutdown_complete_tx);
+    /// The key space served, created on the first call unless one was given to `store`
+    pub(crate) fn build_store(&mut self) -> Store {
+        let shards = self.shards.unwrap_or_else(|| match self.worker_threads {
+            Some(workers) => (workers * SHARDS_PER_WORKER).max(db::DEFAULT_SHARDS),
+            None => db::DEFAULT_SHARDS,
+        });
+        let value_transform = &mut self.value_transform;
+        self.store
+            .get_or_insert_with(|| Store::from_db(Db::new(shards, value_transform.take())))
+            .clone()
+    }
+
+    /// Run the server in the background, on the current Tokio runtime. The returned `Server`
+    /// gives the bound address and shuts the server down.
+    ///
+    /// ```no_run
+    /// # async fn example() -> redust::Result<()> {
+    /// let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    /// let mut server = redust::server::Builder::new().start(listener)?;
+    /// let mut client = redust::client::connect(server.local_addr()).await?;
+    /// client.set("foo", "bar").await?;
+    ///
+    /// server.shutdown();
+    /// server.join().await
+    /// # }
+    /// ```
+    pub fn start(self, listener: TcpListener) -> crate::Result<Server> {
+        let local_addr = listener.local_addr()?;
+        let (shutdown, rx) = oneshot::channel::<()>();
+        // the server also stops if `Server` is dropped, the receiver then completes with an error
+        let task = tokio::spawn(self.run(listener, rx));
+        Ok(Server {
+            local_addr,
+            shutdown: Some(shutdown),
+            task,
+        })
+    }
 
-    let _ = shutdown_complete_rx.recv().await;
-    Ok(())
+    /// Accept connections from `listener` until `shutdown` completes.
+    pub async fn run(mut self, listener: TcpListener, shutdown: impl Future) -> crate::Result<()> {
+        let db = self.build_store().db().clone();
+        let mut settings = self.settings;
+        settings.listen_addr = listener.local_addr().ok();
+        db.configure(|current| *current = settings);
+        db.set_command_handlers(self.commands);
+
+        if let Some(path) = &self.warm_restart {
+            match db.load_snapshot(path) {
+                Ok(Some(keys)) => info!(keys, ?path, "key space restored from the warm restart snapshot"),
+                Ok(None) => {}
+                Err(err) => warn!(cause = %err, ?path, "failed to load the warm restart snapshot"),
+            }
+        }
+
+        if let Some(path) = &self.import_rdb {
+            let (keys, skipped) = db.load_rdb(path)?;
+            info!(keys, skipped, ?path, "RDB file imported");
+        }
+
+        let health = match self.health_listener {
+            Some(health_listener) => {
+                let server_addr = listener.local_addr()?;
+                let probe = health::serve(health_listener, server_addr, self.proxy_protocol, db.clone());
+                Some(tokio::spawn(probe))
+            }
+            None => None,
+        };
+
+        let watch = self.sentinel.map(|monitor| {
+            let state = Arc::new(Sentinel::new(&monitor));
+            db.set_sentinel(state.clone());
+            tokio::spawn(sentinel::watch(state, monitor))
+        });
+
+        #[cfg(feature = "websocket")]
+        let websocket = match self.websocket_listener {
+            Some(websocket_listener) => {
+                let connections = self.bridge.get_or_insert_with(|| mpsc::channel(1)).0.clone();
+                let accept = crate::websocket::accept(websocket_listener, db.clone(), connections);
+                Some(tokio::spawn(accept))
+            }
+            None => None,
+        };
+        // the channel closes once the transports drop their senders
+        let bridged = self.bridge.take().map(|(_, connections)| connections);
+
+        let (notify_shutdown, _) = broadcast::channel(1);
+        let (notify_abort, _) = broadcast::channel(1);
+        let (shutdown_complete_tx, shutdown_complete_rx) = mpsc::channel(1);
+
+        let mut server = Listener{
+            listener,
+            accept_backoff: self.accept_backoff,
+            db,
+            frame_limits: self.frame_limits,
+            reject_excess_clients: self.reject_excess_clients,
+            proxy_protocol: self.proxy_protocol,
+            bridged,
+            // rate limiting is outermost so rejected commands skip the other layers, the statistics
+            // innermost so only the command itself is counted and timed
+            layers: vec![Arc::new(RateLimit) as Arc<dyn Layer>, Arc::new(CommandTimeout)]
+                .into_iter()
+                .chain(self.layers)
+                .chain(vec![
+                    Arc::new(RecordCalls) as Arc<dyn Layer>,
+                    Arc::new(RecordSlow),
+                    Arc::new(RecordLatency),
+                ])
+                .collect(),
+            notify_shutdown,
+            notify_abort,
+            shutdown_complete_tx,
+            shutdown_complete_rx,
+        };
+
+        #[cfg(feature = "http-gateway")]
+        let gateway = self.http_listener.map(|http_listener| {
+            let gateway = crate::gateway::Gateway {
+                db: server.db.clone(),
+                layers: server.layers.clone(),
+                shutdown: Shutdown::new(server.notify_shutdown.subscribe()),
+                max_body: server.frame_limits.max_frame_size,
+            };
+            tokio::spawn(crate::gateway::serve(http_listener, gateway))
+        });
+
+        let mut restarting = false;
+        tokio::select! {
+            res = server.run() => {
+                if let Err(err) = res {
+                    error!(cause = %err, "failed to accept");
+                }
+            }
+            _ = shutdown => {
+                info!("shutdown");
+            }
+            _ = warm_restart_signal(self.warm_restart.is_some()) => {
+                info!("warm restart");
+                restarting = true;
+            }
+        }
+
+        // report not ready while draining
+        if let Some(health) = health {
+            health.abort();
+        }
+        if let Some(watch) = watch {
+            watch.abort();
+        }
+        #[cfg(feature = "websocket")]
+        if let Some(websocket) = websocket {
+            websocket.abort();
+        }
+        #[cfg(feature = "http-gateway")]
+        if let Some(gateway) = gateway {
+            gateway.abort();
+        }
 
+        let Listener {
+            mut shutdown_complete_rx,
+            shutdown_complete_tx,
+            notify_shutdown,
+            notify_abort,
+            db,
+            ..
+        } = server;
+
+        let open = db.clients().connected.load(Ordering::SeqCst);
+        drop(notify_shutdown);
+        drop(shutdown_complete_tx);
+
+        let drained = match self.drain_timeout {
+            Some(timeout) => time::timeout(timeout, shutdown_complete_rx.recv()).await.is_ok(),
+            None => {
+                let _ = shutdown_complete_rx.recv().await;
+                true
+            }
+        };
+
+        let forced = if drained {
+            0
+        } else {
+            let forced = db.clients().connected.load(Ordering::SeqCst);
+            drop(notify_abort);
+            let _ = shutdown_complete_rx.recv().await;
+            forced
+        };
+
+        info!(graceful = open.saturating_sub(forced), forced, "connections closed");
+
+        if let (true, Some(path)) = (restarting, &self.warm_restart) {
+            let keys = db.save_snapshot(path)?;
+            info!(keys, ?path, "key space saved for the warm restart");
+        }
+        Ok(())
+    }
+}
+
+/// Completes on `SIGUSR2` if `enabled`, never otherwise
+async fn warm_restart_signal(enabled: bool) {
+    #[cfg(unix)]
+    if enabled {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::user_defined2()) {
+            Ok(mut usr2) => {
+                usr2.recv().await;
+                return;
+            }
+            Err(err) => warn!(cause = %err, "failed to listen for SIGUSR2, warm restart disabled"),
+        }
+    }
+    std::future::pending().await
 }
 
 impl Listener {
     async fn run (&mut self) -> crate::Result<()> {
         info!("accept inbound connections");
 
+        let mut bridged = self.bridged.take();
         loop {
-            // wait for permit available
-            self.limit_connections.acquire().await.unwrap().forget();
+            let mut connection = if self.reject_excess_clients {
+                let connection = self.next_connection(&mut bridged).await?;
+                if self.is_full() {
+                    self.reject(connection);
+                    continue;
+                }
+                connection
+            } else {
+                if self.is_full() {
+                    warn!("max clients reached, waiting for a connection to close");
+                }
+                // wait for a connection slot, the maximum can change while waiting
+                while self.is_full() {
+                    self.db.clients().slot_freed.notified().await;
+                }
+                self.next_connection(&mut bridged).await?
+            };
+
+            let clients = self.db.clients();
+            clients.connected.fetch_add(1, Ordering::SeqCst);
+            // identifies the connection in the logs
+            let id = clients.total_connections.fetch_add(1, Ordering::Relaxed) + 1;
 
-            let socket = self.accept().await?;
+            connection.set_limits(self.frame_limits);
+            connection.set_user(self.db.default_login());
 
             let mut handler = Handler{
                 db: self.db.clone(),
 
-                connection: Connection::new(socket),
+                connection,
 
-                limit_connections: self.limit_connections.clone(),
+                layers: self.layers.clone(),
 
                 shutdown: Shutdown::new(self.notify_shutdown.subscribe()),
                 _shutdown_complete: self.shutdown_complete_tx.clone(),
             };
 
-            tokio::spawn(async move {
-                if let Err(err) = handler.run().await {
-                    error!(cause =?err, "connection error");
+            // everything logged while serving the connection carries its id and peer address, the
+            // latter is only known once the PROXY protocol header is read if one is expected
+            let span = info_span!("connection", id, peer = field::Empty);
+            if !self.proxy_protocol {
+                handler.record_peer(&span);
+            }
+
+            let mut abort = Shutdown::new(self.notify_abort.subscribe());
+            let proxy_protocol = self.proxy_protocol;
+            let task = async move {
+                if proxy_protocol && !handler.read_proxy_header().await {
+                    return;
+                }
+                tokio::select! {
+                    res = handler.run() => {
+                        if let Err(err) = res {
+                            error!(cause =?err, "connection error");
+                        }
+                    }
+                    _ = abort.recv() => {
+                        debug!("connection aborted after the drain timeout");
+                    }
                 }
-            });
+            };
+            tokio::spawn(task.instrument(span));
         }
     }
 
-    async fn accept(&mut self) -> crate::Result<TcpStream> {
-        let mut backoff = 1;
+    /// Whether the maximum number of clients is reached
+    fn is_full(&self) -> bool {
+        self.db.clients().connected.load(Ordering::SeqCst) >= self.db.config().max_clients
+    }
 
-        // try to accept a few times.
-        loop {
-            match self.listener.accept().await {
-                Ok((socket, _)) => return Ok(socket),
-                Err(err) => {
-                    if backoff > 64 {
-                        return Err(err.into());
-                    }
+    /// The next connection accepted from the listener, or bridged from another transport
+    async fn next_connection(
+        &mut self,
+        bridged: &mut Option<mpsc::Receiver<Connection>>,
+    ) -> crate::Result<Connection> {
+        let bridged = async {
+            match bridged {
+                Some(bridged) => bridged.recv().await,
+                None => None,
+            }
+        };
+        tokio::select! {
+            res = self.accept() => {
+                let socket = res?;
+                // picks up `CONFIG SET tcp-nodelay` and `tcp-keepalive` from the next connection
+                let settings = self.db.config();
+                if let Err(err) = socket::configure(&socket, settings.tcp_nodelay, settings.tcp_keepalive) {
+                    warn!(cause = %err, "failed to set the socket options");
                 }
+                Ok(Connection::new(socket))
             }
-
-            time::sleep(Duration::from_secs(backoff)).await;
-            backoff *= 2;
+            Some(connection) = bridged => Ok(connection),
         }
+    }
+
+    /// Tell the client the server is full and close the connection
+    fn reject(&self, mut connection: Connection) {
+        self.db.clients().rejected_connections.fetch_add(1, Ordering::Relaxed);
+        debug!("max clients reached, connection rejected");
 
+        tokio::spawn(async move {
+            let err = Frame::error("ERR max clients reached");
+            let _ = connection.write_frame(&err).await;
+        });
+    }
+
+    /// Accept a connection, errors are retried according to the backoff policy
+    async fn accept(&mut self) -> crate::Result<TcpStream> {
+        let listener = &self.listener;
+        let (socket, _) = self
+            .accept_backoff
+            .retry(|| async {
+                listener.accept().await.map_err(|err| {
+                    warn!(cause = %err, "failed to accept, retrying");
+                    err
+                })
+            })
+            .await?;
+        Ok(socket)
     }
 }
 
+/// Replies of a tagged request: its tag and the frames written by the command
+type TaggedReply = (u64, Vec<Frame>);
+
+/// Tagged requests of a connection running at once. Past it, the next requests aren't read until
+/// one completes, so a client pipelining without reading the replies is slowed down instead of
+/// piling up tasks and replies.
+const MAX_TAGGED_IN_FLIGHT: usize = 128;
+
 impl Handler {
-    #[instrument(skip(self))]
+    /// Read the PROXY protocol header, returns `false` if the connection must be closed
+    async fn read_proxy_header(&mut self) -> bool {
+        match time::timeout(proxy_protocol::HEADER_TIMEOUT, self.connection.read_proxy_header()).await {
+            Ok(Ok(())) => {
+                self.record_peer(&Span::current());
+                true
+            }
+            Ok(Err(err)) => {
+                debug!(cause = %err, "invalid PROXY protocol header, connection closed");
+                false
+            }
+            Err(_) => {
+                debug!("no PROXY protocol header received in time, connection closed");
+                false
+            }
+        }
+    }
+
+    /// Set the `peer` field of the connection span
+    fn record_peer(&self, span: &Span) {
+        if let Ok(addr) = self.connection.peer_addr() {
+            span.record("peer", field::display(addr));
+        }
+    }
+
     async fn run(&mut self) -> crate::Result<()> {
+        // replies of the tagged requests running in the background, there is room for the reply
+        // of every request in flight so they never wait on the channel
+        let (replies_tx, mut replies) = mpsc::channel(MAX_TAGGED_IN_FLIGHT);
+        let mut in_flight = 0;
+
         while !self.shutdown.is_shutdown() {
+            // picks up `CONFIG SET protocol-dump` from the next request
+            self.connection.set_protocol_dump(self.db.protocol_dump());
 
             let maybe_frame = tokio::select! {
-                res = self.connection.read_frame() => res?,
+                res = self.connection.read_frame(), if in_flight < MAX_TAGGED_IN_FLIGHT => match res {
+                    Ok(maybe_frame) => maybe_frame,
+                    Err(err) => {
+                        // The stream can't be resynchronized after invalid data, let the client
+                        // know why before closing the connection.
+                        let _ = self.connection.write_frame(&cmd::error_reply(&err)).await;
+                        return Err(err);
+                    }
+                },
+                Some((tag, frames)) = replies.recv(), if in_flight > 0 => {
+                    in_flight -= 1;
+                    self.write_tagged(tag, frames).await?;
+                    continue;
+                }
                 _ = self.shutdown.recv()=> {
-                    return Ok(());
+                    break;
                 }
             };
 
@@ -143,19 +858,125 @@ impl Handler {
                 None => return Ok(()),
             };
 
-            let cmd = Command::from_frame(frame)?;
+            if self.connection.is_tagged() {
+                if self.dispatch_tagged(frame, &replies_tx).await? {
+                    in_flight += 1;
+                }
+                continue;
+            }
+
+            // A malformed command doesn't desynchronize the stream, report the problem and keep
+            // serving the connection.
+            let cmd = match Command::from_frame(frame) {
+                Ok(cmd) => cmd,
+                Err(err) => {
+                    debug!(cause = %err, "invalid command");
+                    self.connection.write_frame(&cmd::error_reply(&err)).await?;
+                    continue;
+                }
+            };
 
             debug!(?cmd);
 
-            cmd.apply(&self.db, &mut self.connection, &mut self.shutdown).await?;
+            let span = request_span(&cmd);
+            let mut cx = Context {
+                db: &self.db,
+                connection: &mut self.connection,
+                shutdown: &mut self.shutdown,
+            };
+            Next::new(&self.layers).run(cmd, &mut cx).instrument(span).await?;
+
+            if self.connection.is_closing() {
+                break;
+            }
+        }
+
+        // let the tagged requests already running complete
+        while in_flight > 0 {
+            match replies.recv().await {
+                Some((tag, frames)) => self.write_tagged(tag, frames).await?,
+                None => break,
+            }
+            in_flight -= 1;
+        }
+        Ok(())
+    }
+
+    /// Run a request of a tagged connection in the background, its reply is sent on `replies`.
+    /// Returns `false` if the request was answered right away instead.
+    async fn dispatch_tagged(
+        &mut self,
+        frame: Frame,
+        replies: &mpsc::Sender<TaggedReply>,
+    ) -> crate::Result<bool> {
+        let (tag, frame) = match frame.untag() {
+            Ok(tagged) => tagged,
+            Err(_) => {
+                let err = Frame::error("ERR requests must be tagged as *2 :tag request");
+                self.connection.write_frame(&err).await?;
+                return Ok(false);
+            }
+        };
+
+        let cmd = match Command::from_frame(frame) {
+            Ok(cmd) if cmd.is_connection_bound() => {
+                Err("ERR command not allowed on a tagged connection".into())
+            }
+            res => res,
+        };
+        let cmd = match cmd {
+            Ok(cmd) => cmd,
+            Err(err) => {
+                debug!(cause = %err, tag, "invalid command");
+                self.write_tagged(tag, vec![cmd::error_reply(&err)]).await?;
+                return Ok(false);
+            }
+        };
+
+        debug!(?cmd, tag);
+
+        // the command runs apart from the connection, its output is captured then tagged
+        let db = self.db.clone();
+        let layers = self.layers.clone();
+        let mut shutdown = self.shutdown.resubscribe();
+        let mut connection = Connection::capture(self.connection.peer_addr().ok());
+        connection.set_user(self.connection.user().map(str::to_string));
+        let replies = replies.clone();
+        let span = request_span(&cmd);
+        let task = async move {
+            let mut cx = Context {
+                db: &db,
+                connection: &mut connection,
+                shutdown: &mut shutdown,
+            };
+            if let Err(err) = Next::new(&layers).run(cmd, &mut cx).await {
+                let _ = connection.write_frame(&cmd::error_reply(&err)).await;
+            }
+            let _ = replies.send((tag, connection.into_captured())).await;
+        };
+        tokio::spawn(task.instrument(span));
+        Ok(true)
+    }
+
+    async fn write_tagged(&mut self, tag: u64, frames: Vec<Frame>) -> crate::Result<()> {
+        for frame in frames {
+            self.connection.write_frame_unflushed(&Frame::tagged(tag, frame)).await?;
         }
+        self.connection.flush().await?;
         Ok(())
     }
 }
 
+/// Span of a request, nested in the span of its connection
+fn request_span(cmd: &Command) -> Span {
+    info_span!("request", command = cmd.get_name(), key = cmd.key())
+}
+
 impl Drop for Handler {
     fn drop(&mut self) {
-        // release 1 the semaphore
-        self.limit_connections.add_permits(1);
+        // release the connection slot
+        let clients = self.db.clients();
+        clients.connected.fetch_sub(1, Ordering::SeqCst);
+        clients.slot_freed.notify_one();
     }
 }
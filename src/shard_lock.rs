@@ -0,0 +1,58 @@
+//! Lock guarding a shard of the key space.
+//!
+//! A mutex by default. With the `rwlock-shards` feature it is a read-write lock instead, so the
+//! commands only reading a shard, such as `GET`, run in parallel and only wait on writes. This
+//! favors read-heavy workloads, at the cost of a more expensive lock for writes.
+
+#[cfg(not(feature = "rwlock-shards"))]
+use std::sync::{Mutex, MutexGuard};
+#[cfg(feature = "rwlock-shards")]
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[derive(Debug, Default)]
+pub(crate) struct ShardLock<T> {
+    #[cfg(not(feature = "rwlock-shards"))]
+    inner: Mutex<T>,
+    #[cfg(feature = "rwlock-shards")]
+    inner: RwLock<T>,
+}
+
+#[cfg(not(feature = "rwlock-shards"))]
+pub(crate) type ReadGuard<'a, T> = MutexGuard<'a, T>;
+#[cfg(not(feature = "rwlock-shards"))]
+pub(crate) type WriteGuard<'a, T> = MutexGuard<'a, T>;
+
+#[cfg(feature = "rwlock-shards")]
+pub(crate) type ReadGuard<'a, T> = RwLockReadGuard<'a, T>;
+#[cfg(feature = "rwlock-shards")]
+pub(crate) type WriteGuard<'a, T> = RwLockWriteGuard<'a, T>;
+
+impl<T> ShardLock<T> {
+    pub(crate) fn new(value: T) -> ShardLock<T> {
+        ShardLock { inner: value.into() }
+    }
+}
+
+#[cfg(not(feature = "rwlock-shards"))]
+impl<T> ShardLock<T> {
+    /// Lock for reading, exclusive like any other access
+    pub(crate) fn read(&self) -> ReadGuard<'_, T> {
+        self.inner.lock().unwrap()
+    }
+
+    pub(crate) fn write(&self) -> WriteGuard<'_, T> {
+        self.inner.lock().unwrap()
+    }
+}
+
+#[cfg(feature = "rwlock-shards")]
+impl<T> ShardLock<T> {
+    /// Lock for reading, shared with the other readers
+    pub(crate) fn read(&self) -> ReadGuard<'_, T> {
+        self.inner.read().unwrap()
+    }
+
+    pub(crate) fn write(&self) -> WriteGuard<'_, T> {
+        self.inner.write().unwrap()
+    }
+}
@@ -14,6 +14,14 @@ impl Shutdown {
         }
     }
 
+    /// Another listener of the same shutdown signal
+    pub(crate) fn resubscribe(&self) -> Shutdown {
+        Shutdown {
+            shutdown: self.shutdown,
+            notify: self.notify.resubscribe(),
+        }
+    }
+
     pub(crate) fn is_shutdown(&self) -> bool {
         self.shutdown
     }
@@ -0,0 +1,272 @@
+//! Deterministic simulation of a server and its clients, for end-to-end tests.
+//!
+//! A `Simulation` runs the server and every client on a single threaded runtime whose clock is
+//! paused: time only moves forward once every task is idle, straight to the next timer. Expirations
+//! and timeouts lasting minutes complete instantly, and tasks always interleave the same way.
+//! Clients reach the server over in-memory streams rather than sockets, so the data in flight is
+//! always delivered before the clock moves and a run never depends on the network stack.
+//!
+//! Clients reach the server through a `Link`, a relay whose faults are scripted by the test: added
+//! latency, partitions holding the traffic until they heal and connection resets. Randomized fault
+//! schedules draw from `Simulation::rng`, a failing schedule is replayed from its seed.
+//!
+//! Only available with the `simulation` feature.
+
+use crate::client::Client;
+use crate::{server, Connection};
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio::net::TcpListener;
+use tokio::runtime::{self, Runtime};
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration};
+
+/// Bytes buffered in each direction of an in-memory stream
+const BUFFER: usize = 64 * 1024;
+
+/// Single threaded runtime with a paused clock, along with the seed of its random generator
+pub struct Simulation {
+    runtime: Runtime,
+    seed: u64,
+}
+
+/// Pseudo random generator (SplitMix64), the same seed always yields the same sequence
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+/// A server running in the simulation
+pub struct Host {
+    /// Hands the server side of the in-memory streams over to the server
+    connections: mpsc::Sender<Connection>,
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<crate::Result<()>>,
+}
+
+/// Relays the connections of clients to a host, applying the faults scripted on it
+pub struct Link {
+    connections: mpsc::Sender<Connection>,
+    faults: Arc<Faults>,
+}
+
+#[derive(Debug)]
+struct Faults {
+    /// Delay added to every chunk of data relayed
+    latency: Mutex<Duration>,
+    /// While `true`, the relayed data is held
+    partitioned: watch::Sender<bool>,
+    /// Drops every connection relayed at the time of the reset
+    reset: broadcast::Sender<()>,
+}
+
+impl Simulation {
+    pub fn new(seed: u64) -> Simulation {
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .unwrap();
+
+        Simulation { runtime, seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// A random generator seeded with the seed of the simulation
+    pub fn rng(&self) -> Rng {
+        Rng::new(self.seed)
+    }
+
+    /// Run `future` to completion, along with the tasks it spawns
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Random number in `[0, n)`
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n.max(1)
+    }
+
+    /// `true` with probability `p`
+    pub fn chance(&mut self, p: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+
+    /// Random duration in `[0, max)`, with a millisecond resolution
+    pub fn duration(&mut self, max: Duration) -> Duration {
+        Duration::from_millis(self.below(max.as_millis() as u64))
+    }
+}
+
+/// Start a server configured by `builder`. Must be called from within `Simulation::block_on`.
+pub async fn start(mut builder: server::Builder) -> crate::Result<Host> {
+    // the server needs a listener, nothing connects to it
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let connections = builder.bridge();
+    let (shutdown, rx) = oneshot::channel();
+    let task = tokio::spawn(builder.run(listener, rx));
+
+    Ok(Host {
+        connections,
+        shutdown,
+        task,
+    })
+}
+
+/// Open an in-memory stream to the server behind `connections`, returns the client side
+async fn open(connections: &mpsc::Sender<Connection>) -> crate::Result<DuplexStream> {
+    let (client, served) = io::duplex(BUFFER);
+    connections
+        .send(Connection::from_stream(served, None))
+        .await
+        .map_err(|_| "server shut down")?;
+    Ok(client)
+}
+
+impl Host {
+    /// Open a connection straight to the server, without any fault
+    pub async fn open(&self) -> crate::Result<Connection> {
+        Ok(Connection::from_stream(open(&self.connections).await?, None))
+    }
+
+    /// Connect a client straight to the server, without any fault
+    pub async fn connect(&self) -> crate::Result<Client> {
+        Ok(Client::from_connection(self.open().await?))
+    }
+
+    /// Open a link to the server, initially without any fault
+    pub fn link(&self) -> Link {
+        let (partitioned, _) = watch::channel(false);
+        let (reset, _) = broadcast::channel(1);
+        let faults = Arc::new(Faults {
+            latency: Mutex::new(Duration::from_secs(0)),
+            partitioned,
+            reset,
+        });
+
+        Link {
+            connections: self.connections.clone(),
+            faults,
+        }
+    }
+
+    /// Shut the server down and wait for it to complete
+    pub async fn shutdown(self) -> crate::Result<()> {
+        let _ = self.shutdown.send(());
+        self.task.await.map_err(|err| err.to_string())?
+    }
+}
+
+impl Link {
+    /// Open a connection to the server through the link
+    pub async fn open(&self) -> crate::Result<Connection> {
+        let server = open(&self.connections).await?;
+        let (client, relayed) = io::duplex(BUFFER);
+        // subscribed right away so a reset following the opening drops the connection
+        let reset = self.faults.reset.subscribe();
+        tokio::spawn(relay(relayed, server, self.faults.clone(), reset));
+        Ok(Connection::from_stream(client, None))
+    }
+
+    /// Connect a client to the server through the link
+    pub async fn connect(&self) -> crate::Result<Client> {
+        Ok(Client::from_connection(self.open().await?))
+    }
+
+    /// Delay every chunk of data relayed from now on by `latency`, in both directions
+    pub fn set_latency(&self, latency: Duration) {
+        *self.faults.latency.lock().unwrap() = latency;
+    }
+
+    /// Hold the data relayed in both directions until `heal` is called. Connections stay open, as
+    /// they would while the network is down without either side noticing.
+    pub fn partition(&self) {
+        self.faults.partitioned.send_replace(true);
+    }
+
+    /// Deliver the data held by `partition`
+    pub fn heal(&self) {
+        self.faults.partitioned.send_replace(false);
+    }
+
+    /// Close every connection currently going through the link, both sides see the connection
+    /// drop. New connections are still relayed.
+    pub fn reset(&self) {
+        let _ = self.faults.reset.send(());
+    }
+}
+
+/// Relay the data of a connection between the `client` and the `server` until either side
+/// closes or the link is reset
+async fn relay(
+    client: DuplexStream,
+    server: DuplexStream,
+    faults: Arc<Faults>,
+    mut reset: broadcast::Receiver<()>,
+) {
+    let (client_rd, client_wr) = io::split(client);
+    let (server_rd, server_wr) = io::split(server);
+
+    // both halves are dropped together, closing the connection on both ends
+    tokio::select! {
+        _ = async {
+            tokio::join!(
+                pump(client_rd, server_wr, &faults),
+                pump(server_rd, client_wr, &faults),
+            )
+        } => {}
+        _ = reset.recv() => {}
+    }
+}
+
+/// Copy the data read from `src` to `dst`, applying the faults, until `src` is closed
+async fn pump(
+    mut src: impl AsyncReadExt + Unpin,
+    mut dst: impl AsyncWriteExt + Unpin,
+    faults: &Faults,
+) {
+    let mut partitioned = faults.partitioned.subscribe();
+    let mut buf = vec![0; 16 * 1024];
+
+    loop {
+        let n = match src.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+
+        let latency = *faults.latency.lock().unwrap();
+        if latency > Duration::from_secs(0) {
+            time::sleep(latency).await;
+        }
+        while *partitioned.borrow_and_update() {
+            if partitioned.changed().await.is_err() {
+                return;
+            }
+        }
+
+        if dst.write_all(&buf[..n]).await.is_err() {
+            break;
+        }
+    }
+    let _ = dst.shutdown().await;
+}
@@ -0,0 +1,124 @@
+//! The slow log, see `SLOWLOG` and the `slowlog-*` settings.
+//!
+//! Every command taking at least `slowlog-log-slower-than` microseconds is kept, the latest
+//! `slowlog-max-len` of them. A negative threshold turns the log off, 0 keeps every command.
+//!
+//! Unlike Redis, only the name of the command and the keys it works on are kept, not the rest of
+//! its arguments: the log is readable by every admin and values may be secrets. The client is
+//! identified by its address and its user, redust has no client names.
+
+use crate::middleware::{BoxFuture, Context, Layer, Next};
+use crate::Command;
+
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Default threshold of the slow log, in microseconds
+pub(crate) const DEFAULT_SLOWER_THAN: i64 = 10_000;
+
+/// Default number of entries of the slow log
+pub(crate) const DEFAULT_MAX_LEN: usize = 128;
+
+#[derive(Debug, Default)]
+pub(crate) struct SlowLog {
+    /// Latest entry first
+    entries: Mutex<VecDeque<SlowEntry>>,
+    /// Identifier of the next entry, kept across resets as in Redis. Taken under the lock of
+    /// `entries`, so the entries are ordered by id.
+    next_id: AtomicU64,
+}
+
+/// A command which reached the threshold
+#[derive(Debug, Clone)]
+pub(crate) struct SlowEntry {
+    pub(crate) id: u64,
+    /// Unix time in seconds the command was logged at
+    pub(crate) time: u64,
+    /// Execution time in microseconds
+    pub(crate) duration: u64,
+    /// Name of the command followed by its keys
+    pub(crate) args: Vec<String>,
+    pub(crate) client: Option<SocketAddr>,
+    pub(crate) user: Option<String>,
+}
+
+/// Logs the commands reaching the threshold of the slow log
+#[derive(Debug)]
+pub(crate) struct RecordSlow;
+
+impl SlowLog {
+    /// Log a command which took `elapsed`, keeping the latest `max_len` entries
+    pub(crate) fn push(
+        &self,
+        args: Vec<String>,
+        elapsed: Duration,
+        client: Option<SocketAddr>,
+        user: Option<String>,
+        max_len: usize,
+    ) {
+        let mut entries = self.entries.lock().unwrap();
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        entries.push_front(SlowEntry {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            time,
+            duration: elapsed.as_micros() as u64,
+            args,
+            client,
+            user,
+        });
+        entries.truncate(max_len);
+    }
+
+    /// The latest `count` entries, latest first
+    pub(crate) fn get(&self, count: usize) -> Vec<SlowEntry> {
+        let entries = self.entries.lock().unwrap();
+        entries.iter().take(count).cloned().collect()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Drop every entry
+    pub(crate) fn reset(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+impl Layer for RecordSlow {
+    fn call<'a>(
+        &'a self,
+        cmd: Command,
+        cx: &'a mut Context<'_>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, crate::Result<()>> {
+        Box::pin(async move {
+            let threshold = match cmd.is_unbounded() {
+                true => None,
+                false => cx.db.slowlog_threshold(),
+            };
+            let args = threshold.map(|_| {
+                let mut args = vec![cmd.get_name().to_string()];
+                args.extend(cmd.keys().into_iter().map(str::to_string));
+                args
+            });
+
+            let start = Instant::now();
+            let res = next.run(cmd, cx).await;
+            let elapsed = start.elapsed();
+            if let (Some(threshold), Some(args)) = (threshold, args) {
+                if elapsed >= threshold {
+                    let user = cx.connection.user().map(str::to_string);
+                    cx.db.log_slow(args, elapsed, cx.peer_addr(), user);
+                }
+            }
+            res
+        })
+    }
+}
@@ -0,0 +1,255 @@
+//! Snapshot of the key space, used for warm restarts.
+//!
+//! A server started with a warm restart file writes every key to it when it receives `SIGUSR2`,
+//! once its connections are drained. The next process started with the same file loads it before
+//! accepting connections, so a binary upgrade doesn't lose the cached data.
+//!
+//! The file starts with a magic string and the sequence number of the latest write, followed by
+//! one record per key. Integers are big endian `u64`, strings and byte arrays are prefixed by
+//! their length, times are unix timestamps in milliseconds:
+//!
+//! ```text
+//! kind id modified expires_at|0 key dead_letter|"" value
+//! ```
+//!
+//! where the value is, by kind:
+//!
+//! ```text
+//! string: data
+//! blog: start data
+//! list: len element...
+//! hash: len (field value)...
+//! sorted set: len (score member)...
+//! ```
+//!
+//! Scores are the bits of the `f64`.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::commit::unix_millis;
+
+const MAGIC: &[u8] = b"REDUST-SNAPSHOT-1";
+
+const KIND_STRING: u8 = 0;
+const KIND_BLOG: u8 = 1;
+const KIND_LIST: u8 = 2;
+const KIND_HASH: u8 = 3;
+const KIND_SORTED_SET: u8 = 4;
+
+/// A key along with its entry, as written in the snapshot
+#[derive(Debug)]
+pub(crate) struct Record {
+    pub(crate) key: String,
+    /// Sequence number of the write which created the entry
+    pub(crate) id: u64,
+    pub(crate) value: Stored,
+    pub(crate) modified: SystemTime,
+    pub(crate) expires_at: Option<SystemTime>,
+    /// Dead-letter list of a pending reservation
+    pub(crate) reservation: Option<String>,
+}
+
+/// A value as stored, after the value transform if any
+#[derive(Debug)]
+pub(crate) enum Stored {
+    String(Bytes),
+    Blog { start: u64, data: Bytes },
+    /// Elements of a list, head first
+    List(Vec<Bytes>),
+    /// Fields of a hash along with their values
+    Hash(Vec<(Bytes, Bytes)>),
+    /// Members of a sorted set along with their scores, lowest score first
+    SortedSet(Vec<(Bytes, f64)>),
+}
+
+/// Writes a snapshot to a temporary file, moved over the destination once complete so a crash
+/// never leaves a truncated snapshot behind
+pub(crate) struct Writer {
+    out: BufWriter<File>,
+    tmp: PathBuf,
+    path: PathBuf,
+}
+
+impl Writer {
+    /// Start a snapshot of a key space whose latest write is `last_seq`
+    pub(crate) fn create(path: &Path, last_seq: u64) -> io::Result<Writer> {
+        let tmp = path.with_extension("tmp");
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&last_seq.to_be_bytes())?;
+
+        Ok(Writer {
+            out,
+            tmp,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub(crate) fn write(&mut self, record: &Record) -> io::Result<()> {
+        let mut buf = BytesMut::new();
+        let kind = match record.value {
+            Stored::String(_) => KIND_STRING,
+            Stored::Blog { .. } => KIND_BLOG,
+            Stored::List(_) => KIND_LIST,
+            Stored::Hash(_) => KIND_HASH,
+            Stored::SortedSet(_) => KIND_SORTED_SET,
+        };
+        buf.put_u8(kind);
+        buf.put_u64(record.id);
+        buf.put_u64(unix_millis(record.modified));
+        buf.put_u64(record.expires_at.map(unix_millis).unwrap_or(0));
+        put_bytes(&mut buf, record.key.as_bytes());
+        put_bytes(&mut buf, record.reservation.as_deref().unwrap_or("").as_bytes());
+
+        match &record.value {
+            Stored::String(data) => put_bytes(&mut buf, data),
+            Stored::Blog { start, data } => {
+                buf.put_u64(*start);
+                put_bytes(&mut buf, data);
+            }
+            Stored::List(elements) => {
+                buf.put_u64(elements.len() as u64);
+                for element in elements {
+                    put_bytes(&mut buf, element);
+                }
+            }
+            Stored::Hash(fields) => {
+                buf.put_u64(fields.len() as u64);
+                for (field, value) in fields {
+                    put_bytes(&mut buf, field);
+                    put_bytes(&mut buf, value);
+                }
+            }
+            Stored::SortedSet(members) => {
+                buf.put_u64(members.len() as u64);
+                for (member, score) in members {
+                    buf.put_u64(score.to_bits());
+                    put_bytes(&mut buf, member);
+                }
+            }
+        }
+        self.out.write_all(&buf)
+    }
+
+    /// Flush the snapshot to disk and move it in place
+    pub(crate) fn finish(self) -> io::Result<()> {
+        let file = self.out.into_inner().map_err(|err| err.into_error())?;
+        file.sync_all()?;
+        fs::rename(&self.tmp, &self.path)
+    }
+}
+
+/// Read the snapshot at `path`, `None` if there is none. Returns the sequence number of the
+/// latest write along with the records.
+pub(crate) fn read(path: &Path) -> crate::Result<Option<(u64, Vec<Record>)>> {
+    let mut buf = match fs::read(path) {
+        Ok(data) => Bytes::from(data),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    if !buf.starts_with(MAGIC) {
+        return Err("invalid snapshot; unknown format".into());
+    }
+    buf.advance(MAGIC.len());
+    let last_seq = get_u64(&mut buf)?;
+
+    let mut records = vec![];
+    while buf.has_remaining() {
+        records.push(read_record(&mut buf)?);
+    }
+    Ok(Some((last_seq, records)))
+}
+
+fn read_record(buf: &mut Bytes) -> crate::Result<Record> {
+    if !buf.has_remaining() {
+        return Err(truncated());
+    }
+    let kind = buf.get_u8();
+    let id = get_u64(buf)?;
+    let modified = from_unix_millis(get_u64(buf)?);
+    let expires_at = match get_u64(buf)? {
+        0 => None,
+        ms => Some(from_unix_millis(ms)),
+    };
+    let key = String::from_utf8(get_bytes(buf)?.to_vec())?;
+    let reservation = match get_bytes(buf)? {
+        channel if channel.is_empty() => None,
+        channel => Some(String::from_utf8(channel.to_vec())?),
+    };
+
+    let value = match kind {
+        KIND_STRING => Stored::String(get_bytes(buf)?),
+        KIND_BLOG => Stored::Blog {
+            start: get_u64(buf)?,
+            data: get_bytes(buf)?,
+        },
+        KIND_LIST => {
+            let len = get_u64(buf)?;
+            let mut elements = vec![];
+            for _ in 0..len {
+                elements.push(get_bytes(buf)?);
+            }
+            Stored::List(elements)
+        }
+        KIND_HASH => {
+            let len = get_u64(buf)?;
+            let mut fields = vec![];
+            for _ in 0..len {
+                fields.push((get_bytes(buf)?, get_bytes(buf)?));
+            }
+            Stored::Hash(fields)
+        }
+        KIND_SORTED_SET => {
+            let len = get_u64(buf)?;
+            let mut members = vec![];
+            for _ in 0..len {
+                let score = f64::from_bits(get_u64(buf)?);
+                members.push((get_bytes(buf)?, score));
+            }
+            Stored::SortedSet(members)
+        }
+        kind => return Err(format!("invalid snapshot; unknown value kind {}", kind).into()),
+    };
+
+    Ok(Record {
+        key,
+        id,
+        value,
+        modified,
+        expires_at,
+        reservation,
+    })
+}
+
+fn put_bytes(buf: &mut BytesMut, data: &[u8]) {
+    buf.put_u64(data.len() as u64);
+    buf.put_slice(data);
+}
+
+fn get_u64(buf: &mut Bytes) -> crate::Result<u64> {
+    if buf.remaining() < 8 {
+        return Err(truncated());
+    }
+    Ok(buf.get_u64())
+}
+
+fn get_bytes(buf: &mut Bytes) -> crate::Result<Bytes> {
+    let len = get_u64(buf)?;
+    if (buf.remaining() as u64) < len {
+        return Err(truncated());
+    }
+    Ok(buf.split_to(len as usize))
+}
+
+fn truncated() -> crate::Error {
+    "invalid snapshot; truncated record".into()
+}
+
+fn from_unix_millis(ms: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(ms)
+}
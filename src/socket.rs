@@ -0,0 +1,23 @@
+//! TCP options shared by the server and the client connections.
+
+use socket2::{SockRef, TcpKeepalive};
+use std::io;
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+/// Idle time before the first keepalive probe, as Redis defaults to
+pub(crate) const DEFAULT_KEEPALIVE: Duration = Duration::from_secs(300);
+
+/// Set `TCP_NODELAY`, and enable keepalive probes after `keepalive` of inactivity if set.
+///
+/// Without keepalive, a peer gone without closing the connection, after a crash or a network
+/// partition, is only noticed when writing to it, which an idle connection never does.
+pub(crate) fn configure(stream: &TcpStream, nodelay: bool, keepalive: Option<Duration>) -> io::Result<()> {
+    stream.set_nodelay(nodelay)?;
+
+    let socket = SockRef::from(stream);
+    match keepalive {
+        Some(idle) => socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(idle)),
+        None => socket.set_keepalive(false),
+    }
+}
@@ -0,0 +1,96 @@
+//! Sorted set value, see `ZADD` and the GEO commands.
+//!
+//! Members are ordered by score, then by their bytes for equal scores. Scores are never NaN, so
+//! ordering them is total; `-0` is stored as `0` so both sort the same.
+
+use bytes::Bytes;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Bound;
+
+#[derive(Debug, Default)]
+pub(crate) struct SortedSet {
+    /// Score of each member
+    scores: HashMap<Bytes, f64>,
+    /// Members in order
+    order: BTreeSet<(Score, Bytes)>,
+}
+
+/// A score other than NaN, ordered as a number
+#[derive(Debug, Clone, Copy)]
+struct Score(f64);
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Score) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Score) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Score) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl SortedSet {
+    /// Number of members
+    pub(crate) fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    /// Score of `member`
+    pub(crate) fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// Set the score of `member`, which can't be NaN. Returns whether the member is new.
+    pub(crate) fn insert(&mut self, member: Bytes, score: f64) -> bool {
+        debug_assert!(!score.is_nan());
+        let score = if score == 0.0 { 0.0 } else { score };
+        let previous = self.scores.insert(member.clone(), score);
+        if let Some(previous) = previous {
+            self.order.remove(&(Score(previous), member.clone()));
+        }
+        self.order.insert((Score(score), member));
+        previous.is_none()
+    }
+
+    /// Remove `member`. Returns whether it was present.
+    pub(crate) fn remove(&mut self, member: &[u8]) -> bool {
+        match self.scores.remove_entry(member) {
+            Some((member, score)) => self.order.remove(&(Score(score), member)),
+            None => false,
+        }
+    }
+
+    /// Members along with their scores, lowest score first
+    pub(crate) fn iter(&self) -> impl DoubleEndedIterator<Item = (&Bytes, f64)> {
+        self.order.iter().map(|(score, member)| (member, score.0))
+    }
+
+    /// Members whose score is at least `min` and below `max`, lowest score first
+    pub(crate) fn range_by_score(&self, min: f64, max: f64) -> impl Iterator<Item = (&Bytes, f64)> {
+        let start = Bound::Included((Score(min), Bytes::new()));
+        self.order
+            .range((start, Bound::Unbounded))
+            .take_while(move |(score, _)| score.0 < max)
+            .map(|(score, member)| (member, score.0))
+    }
+
+    /// Number of bytes of the members, along with their scores
+    pub(crate) fn bytes(&self) -> usize {
+        self.scores.keys().map(|member| member.len() + 8).sum()
+    }
+}
@@ -0,0 +1,181 @@
+//! Direct access to the key space from the application embedding the server, without going
+//! through a socket.
+//!
+//! ```no_run
+//! # async fn example() -> redust::Result<()> {
+//! use redust::{server, Store};
+//! use tokio::net::TcpListener;
+//!
+//! let store = Store::new();
+//! let listener = TcpListener::bind("127.0.0.1:6379").await?;
+//! tokio::spawn(server::Builder::new().store(store.clone()).run(listener, tokio::signal::ctrl_c()));
+//!
+//! // network clients see this value, and the application sees theirs
+//! store.set("foo", "bar".into())?;
+//! assert_eq!(store.get("foo")?.as_deref(), Some(&b"bar"[..]));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The operations behave as the matching commands do, writes are replicated and notified alike.
+//! They aren't subject to the ACLs, rate limits or timeouts of the connections however: the
+//! application is trusted.
+
+use crate::db::{ChannelStats, Db, Published, DEFAULT_SHARDS};
+use crate::Error;
+
+use bytes::Bytes;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// A handle to a key space. Cloning it is cheap, the clones share the same data.
+#[derive(Debug, Clone)]
+pub struct Store {
+    db: Db,
+}
+
+/// Messages published on a channel, see `Store::subscribe`
+#[derive(Debug)]
+pub struct Subscription {
+    channel: String,
+    /// Taken on drop, so the channel can be released once nobody else receives it
+    rx: Option<broadcast::Receiver<Published>>,
+    stats: Arc<ChannelStats>,
+    db: Db,
+}
+
+impl Store {
+    /// Create an empty key space. Must be called from a Tokio runtime, which runs the expiration
+    /// of the keys.
+    pub fn new() -> Store {
+        Store::with_shards(DEFAULT_SHARDS)
+    }
+
+    /// Create an empty key space split in `shards` partitions, see `server::Builder::shards`
+    pub fn with_shards(shards: usize) -> Store {
+        Store {
+            db: Db::new(shards, None),
+        }
+    }
+
+    pub(crate) fn from_db(db: Db) -> Store {
+        Store { db }
+    }
+
+    pub(crate) fn db(&self) -> &Db {
+        &self.db
+    }
+
+    /// Get the value of `key`, `None` if it doesn't exist
+    pub fn get(&self, key: &str) -> crate::Result<Option<Bytes>> {
+        self.db.get(key)
+    }
+
+    /// Set `key` to `value`, dropping its expiration
+    pub fn set(&self, key: &str, value: Bytes) -> crate::Result<()> {
+        self.db.set(key.to_string(), value, None)
+    }
+
+    /// Set `key` to `value`, which expires after `expiration`
+    pub fn set_expires(&self, key: &str, value: Bytes, expiration: Duration) -> crate::Result<()> {
+        self.db.set(key.to_string(), value, Some(expiration))
+    }
+
+    /// Remove `keys`, returns the number of keys which existed
+    pub fn del(&self, keys: &[String]) -> usize {
+        self.db.delete(keys)
+    }
+
+    /// Number of `keys` which exist, a key given twice counts twice
+    pub fn exists(&self, keys: &[String]) -> usize {
+        self.db.exists(keys)
+    }
+
+    /// Expire `key` after `ttl`, returns whether the key exists
+    pub fn expire(&self, key: &str, ttl: Duration) -> bool {
+        self.db.expire(key, ttl)
+    }
+
+    /// Increment the integer held by `key`, a missing key counts as `0`. Returns the new value.
+    pub fn incr(&self, key: &str) -> crate::Result<i64> {
+        self.db.incr_by(key.to_string(), 1)
+    }
+
+    /// Write the string keys to an RDB file Redis can load, see `SAVE`. Returns the number of keys
+    /// written. Fails if the server stores its values through a `ValueTransform`.
+    pub fn save_rdb(&self, path: impl AsRef<Path>) -> crate::Result<usize> {
+        self.db.save_rdb(path.as_ref()).map(|(keys, _)| keys)
+    }
+
+    /// Load the string keys of an RDB file written by Redis or by `save_rdb`, replacing the keys
+    /// with the same names. Returns the number of keys loaded.
+    pub fn load_rdb(&self, path: impl AsRef<Path>) -> crate::Result<usize> {
+        self.db.load_rdb(path.as_ref()).map(|(keys, _)| keys)
+    }
+
+    /// Publish `message` on `channel`, returns the number of subscribers which received it,
+    /// network clients included
+    pub fn publish(&self, channel: &str, message: Bytes) -> usize {
+        self.db.publish(channel, message)
+    }
+
+    /// Receive the messages published on `channel` from now on, by the application or by network
+    /// clients
+    pub fn subscribe(&self, channel: &str) -> Subscription {
+        let (rx, stats) = self.db.subscribe(channel.to_string());
+        Subscription {
+            channel: channel.to_string(),
+            rx: Some(rx),
+            stats,
+            db: self.db.clone(),
+        }
+    }
+}
+
+impl Default for Store {
+    fn default() -> Store {
+        Store::new()
+    }
+}
+
+impl Subscription {
+    pub fn channel(&self) -> &str {
+        &self.channel
+    }
+
+    /// Wait for the next message published on the channel. Returns `None` once the channel is
+    /// closed, which doesn't happen while the subscription is alive.
+    ///
+    /// Messages the subscription was too slow to receive are dropped, which is reported as
+    /// `Error::Lagged`. The subscription is still usable after that error.
+    pub async fn next_message(&mut self) -> crate::Result<Option<Bytes>> {
+        let rx = match &mut self.rx {
+            Some(rx) => rx,
+            None => return Ok(None),
+        };
+        match rx.recv().await {
+            Ok(message) => {
+                self.stats.record_delivered();
+                Ok(Some(message.payload))
+            }
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                self.stats.record_dropped(missed);
+                Err(Error::Lagged {
+                    channel: self.channel.clone(),
+                    missed,
+                })
+            }
+            Err(broadcast::error::RecvError::Closed) => Ok(None),
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        drop(self.rx.take());
+        self.stats.record_unsubscribe();
+        self.db.release_channel(&self.channel);
+    }
+}
@@ -0,0 +1,55 @@
+//! Execution budget of the commands, see the `command-timeout` setting.
+//!
+//! A command still running once its budget is spent is cancelled at its next await point and
+//! answered `-TIMEOUT`, the connection is then served as usual. A command cancelled after it
+//! started writing its reply can't be answered anymore without desynchronizing the client, the
+//! connection is closed instead.
+//!
+//! Work done while holding the key space locks is never interrupted, a command only yields to the
+//! runtime around its I/O. `SUBSCRIBE` and `SYNCFROM` run as long as their client wants and have no
+//! budget, nor have `BLPOP` and `BRPOP`, which block for the timeout they are given.
+
+use crate::middleware::{BoxFuture, Context, Layer, Next};
+use crate::{Command, Frame};
+
+use std::sync::atomic::Ordering;
+use tokio::time;
+use tracing::debug;
+
+/// Cancels the commands exceeding `command-timeout`
+#[derive(Debug)]
+pub(crate) struct CommandTimeout;
+
+impl Layer for CommandTimeout {
+    fn call<'a>(
+        &'a self,
+        cmd: Command,
+        cx: &'a mut Context<'_>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, crate::Result<()>> {
+        Box::pin(async move {
+            let budget = match cx.db.command_timeout() {
+                Some(budget) if !cmd.is_unbounded() => budget,
+                _ => return next.run(cmd, cx).await,
+            };
+
+            let name = cmd.get_name().to_string();
+            let frames_written = cx.connection.frames_written();
+            if let Ok(res) = time::timeout(budget, next.run(cmd, cx)).await {
+                return res;
+            }
+
+            cx.db.clients().timed_out_commands.fetch_add(1, Ordering::Relaxed);
+            if cx.connection.frames_written() != frames_written {
+                return Err(format!("'{}' timed out while writing its reply", name).into());
+            }
+            debug!(command = %name, ?budget, "command timed out");
+            let err = format!(
+                "TIMEOUT '{}' exceeded the execution budget of {} ms",
+                name,
+                budget.as_millis()
+            );
+            cx.reply(&Frame::Error(err)).await
+        })
+    }
+}
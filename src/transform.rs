@@ -0,0 +1,17 @@
+//! Hooks transforming values on their way in and out of the key space.
+//!
+//! A transform is applied to every value stored with `SET` or `RESERVE`, and reversed when the
+//! value is read back: clients only ever see the original value. The commit pipeline carries the
+//! stored form, so write streams such as `SYNCFROM` only hold transformed values too. This is how
+//! values get encrypted or compressed at rest, the key or the KMS client lives in the transform.
+
+use bytes::Bytes;
+use std::fmt;
+
+pub trait ValueTransform: Send + Sync + fmt::Debug {
+    /// Transform `value` before it is stored under `key`
+    fn encode(&self, key: &str, value: Bytes) -> crate::Result<Bytes>;
+
+    /// Recover the original value from the form stored under `key`
+    fn decode(&self, key: &str, stored: Bytes) -> crate::Result<Bytes>;
+}
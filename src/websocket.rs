@@ -0,0 +1,105 @@
+//! WebSocket transport, for browser dashboards and WASM clients which can't open raw TCP
+//! connections. Enabled by the `websocket` feature, see `server::Builder::websocket`.
+//!
+//! The binary messages received carry RESP, as a TCP client would send it, and the replies are
+//! sent back as binary messages. Messages aren't aligned on frames: a message may hold several
+//! frames or a part of one, the client reassembles the stream. Text messages are accepted too and
+//! handled as binary ones.
+//!
+//! Each WebSocket is bridged to an in-memory stream, which is served as any other connection:
+//! it counts toward `maxclients`, goes through authentication and is drained on shutdown.
+
+use crate::{socket, Connection, Db};
+
+use futures_util::{SinkExt, StreamExt};
+use std::error::Error;
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::{self, Duration};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, warn};
+
+/// Time given to a client to complete the WebSocket handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Bytes buffered in each direction between a WebSocket and its connection
+const BRIDGE_BUFFER: usize = 64 * 1024;
+
+/// Accept WebSockets from `listener`, the connections bridged to them are sent on `connections`
+/// to be served
+pub(crate) async fn accept(listener: TcpListener, db: Db, connections: mpsc::Sender<Connection>) {
+    loop {
+        let socket = match listener.accept().await {
+            Ok((socket, _)) => socket,
+            Err(err) => {
+                warn!(cause = %err, "failed to accept a WebSocket");
+                continue;
+            }
+        };
+
+        let settings = db.config();
+        if let Err(err) = socket::configure(&socket, settings.tcp_nodelay, settings.tcp_keepalive) {
+            warn!(cause = %err, "failed to set the socket options");
+        }
+
+        let connections = connections.clone();
+        tokio::spawn(async move {
+            if let Err(err) = bridge(socket, connections).await {
+                debug!(cause = %err, "WebSocket closed");
+            }
+        });
+    }
+}
+
+/// Complete the handshake, hand a connection over to the server then relay the bytes until
+/// either side closes
+async fn bridge(socket: TcpStream, connections: mpsc::Sender<Connection>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let peer_addr = socket.peer_addr().ok();
+    let websocket = match time::timeout(HANDSHAKE_TIMEOUT, tokio_tungstenite::accept_async(socket)).await {
+        Ok(websocket) => websocket?,
+        Err(_) => return Err("WebSocket handshake not completed in time".into()),
+    };
+
+    let (served, bridged) = io::duplex(BRIDGE_BUFFER);
+    if connections.send(Connection::from_stream(served, peer_addr)).await.is_err() {
+        // the server is shutting down
+        return Ok(());
+    }
+
+    let (mut sink, mut messages) = websocket.split();
+    let (mut replies, mut requests) = io::split(bridged);
+
+    // each direction is relayed on its own, so a client pipelining requests while replies pile up
+    // can't block both
+    let inbound = async {
+        while let Some(message) = messages.next().await {
+            match message? {
+                Message::Binary(data) => requests.write_all(&data).await?,
+                Message::Text(text) => requests.write_all(text.as_bytes()).await?,
+                Message::Close(_) => break,
+                // pings are answered by tungstenite
+                _ => {}
+            }
+        }
+        Ok::<_, Box<dyn Error + Send + Sync>>(())
+    };
+    let outbound = async {
+        let mut buf = vec![0; BRIDGE_BUFFER];
+        loop {
+            let n = replies.read(&mut buf).await?;
+            if n == 0 {
+                // the server closed the connection
+                sink.send(Message::Close(None)).await?;
+                return Ok::<_, Box<dyn Error + Send + Sync>>(());
+            }
+            sink.send(Message::Binary(buf[..n].to_vec())).await?;
+        }
+    };
+
+    tokio::select! {
+        res = inbound => res,
+        res = outbound => res,
+    }
+}
+
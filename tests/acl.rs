@@ -0,0 +1,150 @@
+use redust::{client, server, CommandHandler, Frame, Parse, Store};
+
+use bytes::Bytes;
+use std::sync::Arc;
+
+mod common;
+use common::start;
+
+/// `TOUCH key` sets `key`, declaring it
+#[derive(Debug)]
+struct Touch;
+
+impl CommandHandler for Touch {
+    fn call(&self, args: &mut Parse, store: &Store) -> redust::Result<Frame> {
+        store.set(&args.next_string()?, Bytes::from("touched"))?;
+        Ok(Frame::ok())
+    }
+
+    fn keys(&self, args: &mut Parse) -> Option<Vec<String>> {
+        args.next_string().ok().map(|key| vec![key])
+    }
+}
+
+/// `FLUSH` deletes a fixed key, without declaring it
+#[derive(Debug)]
+struct Flush;
+
+impl CommandHandler for Flush {
+    fn call(&self, _args: &mut Parse, store: &Store) -> redust::Result<Frame> {
+        Ok(Frame::Integer(store.del(&["secret".to_string()]) as i64))
+    }
+}
+
+async fn connect_as(server: &server::Server, username: &str, password: &str) -> client::Client {
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.auth(Some(username), password).await.unwrap();
+    client
+}
+
+#[tokio::test]
+async fn passwords() {
+    let server = start(server::Builder::new()).await;
+    let mut admin = client::connect(server.local_addr()).await.unwrap();
+    admin.acl_set_user("alice", &["on", ">first", ">second", "+@read", "allkeys"]).await.unwrap();
+
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    assert!(client.auth(Some("alice"), "first").await.is_ok());
+    assert!(client.auth(Some("alice"), "second").await.is_ok());
+    assert!(client.auth(Some("alice"), "First").await.is_err());
+    assert!(client.auth(Some("bob"), "first").await.is_err());
+
+    admin.acl_set_user("alice", &["<first"]).await.unwrap();
+    assert!(client.auth(Some("alice"), "first").await.is_err());
+    assert!(admin.acl_set_user("alice", &["<first"]).await.is_err());
+
+    admin.acl_set_user("alice", &["off"]).await.unwrap();
+    assert!(client.auth(Some("alice"), "second").await.is_err());
+
+    // never shown back
+    let list = admin.acl_list().await.unwrap();
+    assert!(list.iter().all(|rules| !rules.contains("second")), "{:?}", list);
+}
+
+#[tokio::test]
+async fn categories() {
+    let server = start(server::Builder::new()).await;
+    let mut admin = client::connect(server.local_addr()).await.unwrap();
+    admin.acl_set_user("reader", &["on", ">pass", "+@read", "allkeys"]).await.unwrap();
+    admin.set("key", "value").await.unwrap();
+
+    let mut reader = connect_as(&server, "reader", "pass").await;
+    let value: Option<Bytes> = reader.get("key").await.unwrap();
+    assert_eq!(value, Some(Bytes::from("value")));
+    let err = reader.set("key", "other").await.unwrap_err();
+    assert_eq!(err.to_string(), "NOPERM User reader has no permissions to run the 'set' command");
+
+    // connection commands and ACL WHOAMI need no category
+    reader.ping(None).await.unwrap();
+    assert_eq!(reader.acl_whoami().await.unwrap(), "reader");
+    assert!(reader.acl_list().await.is_err());
+}
+
+#[tokio::test]
+async fn key_patterns() {
+    let server = start(server::Builder::new()).await;
+    let mut admin = client::connect(server.local_addr()).await.unwrap();
+    admin
+        .acl_set_user("app", &["on", ">pass", "+@read", "+@write", "~app:*"])
+        .await
+        .unwrap();
+    admin.set("secret", "value").await.unwrap();
+
+    let mut app = connect_as(&server, "app", "pass").await;
+    app.set("app:1", "value").await.unwrap();
+    let err = app.get::<Option<Bytes>>("secret").await.unwrap_err();
+    assert_eq!(err.to_string(), "NOPERM No permissions to access a key");
+    assert!(app.memory_usage("app:1").await.unwrap().is_some());
+    let err = app.memory_usage("secret").await.unwrap_err();
+    assert_eq!(err.to_string(), "NOPERM No permissions to access a key");
+}
+
+#[tokio::test]
+async fn keys_of_registered_commands() {
+    let builder = server::Builder::new()
+        .register_command("touch", Arc::new(Touch))
+        .unwrap()
+        .register_command("flush", Arc::new(Flush))
+        .unwrap();
+    let server = start(builder).await;
+    let mut admin = client::connect(server.local_addr()).await.unwrap();
+    admin
+        .acl_set_user("app", &["on", ">pass", "+@write", "~app:*"])
+        .await
+        .unwrap();
+
+    let mut app = connect_as(&server, "app", "pass").await;
+    let reply: String = app.command(("touch", "app:1")).await.unwrap();
+    assert_eq!(reply, "OK");
+    let err = app.command::<String>(("touch", "secret")).await.unwrap_err();
+    assert_eq!(err.to_string(), "NOPERM No permissions to access a key");
+    // the keys aren't known, only a user allowed every key may run it
+    let err = app.command::<u64>(("flush",)).await.unwrap_err();
+    assert_eq!(err.to_string(), "NOPERM No permissions to access a key");
+
+    admin.acl_set_user("app", &["allkeys"]).await.unwrap();
+    let removed: u64 = app.command(("flush",)).await.unwrap();
+    assert_eq!(removed, 0);
+}
+
+#[test]
+fn built_in_commands_not_replaced() {
+    for name in ["get", " SET "].iter() {
+        let err = server::Builder::new()
+            .register_command(name, Arc::new(Touch))
+            .unwrap_err();
+        let expected = format!("`{}` is a built-in command", name.trim().to_lowercase());
+        assert_eq!(err.to_string(), expected);
+    }
+}
+
+#[tokio::test]
+async fn requirepass() {
+    let server = start(server::Builder::new().requirepass("secret")).await;
+
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    assert!(client.ping(None).await.is_err());
+    assert!(client.auth(None, "wrong").await.is_err());
+    client.auth(None, "secret").await.unwrap();
+    assert_eq!(client.acl_whoami().await.unwrap(), "default");
+}
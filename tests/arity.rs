@@ -0,0 +1,46 @@
+use redust::{client, server};
+
+use bytes::Bytes;
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn missing_or_extra_arguments() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let cases: [&[&str]; 4] = [
+        &["get"],
+        &["get", "key", "extra"],
+        // the optional expiration lacks its value
+        &["set", "key", "value", "ex"],
+        &["set", "key", "value", "ex", "10", "extra"],
+    ];
+    for args in cases.iter() {
+        let err = client.command::<Option<Bytes>>(args.to_vec()).await.unwrap_err();
+        let expected = format!("ERR wrong number of arguments for '{}' command", args[0]);
+        assert_eq!(err.to_string(), expected, "{:?}", args);
+    }
+}
+
+#[tokio::test]
+async fn invalid_arguments_keep_their_error() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let err = client
+        .command::<Option<Bytes>>(vec!["set", "key", "value", "ex", "notanumber"])
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "ERR value is not an integer or out of range");
+
+    let err = client
+        .command::<Option<Bytes>>(vec!["set", "key", "value", "keepttl"])
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "ERR currently `SET` only uspport the expiration option");
+
+    // the connection is still usable
+    client.set("key", "value").await.unwrap();
+}
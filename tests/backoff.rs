@@ -0,0 +1,72 @@
+use redust::Backoff;
+
+use std::time::Duration;
+use tokio::time::Instant;
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+#[test]
+fn delay_doubles_up_to_the_cap() {
+    let backoff = Backoff::new(ms(100), ms(1000));
+    let delays: Vec<_> = (0..6).map(|attempt| backoff.delay(attempt)).collect();
+    assert_eq!(delays, [ms(100), ms(200), ms(400), ms(800), ms(1000), ms(1000)]);
+
+    // the factor overflowing doesn't go past the cap either
+    assert_eq!(backoff.delay(40), ms(1000));
+    assert_eq!(backoff.delay(u32::MAX), ms(1000));
+}
+
+#[test]
+fn jitter_shortens_the_delay() {
+    let backoff = Backoff::new(ms(100), ms(1000)).jitter(0.5);
+    for attempt in 0..6 {
+        let full = Backoff::new(ms(100), ms(1000)).delay(attempt);
+        let delay = backoff.delay(attempt);
+        assert!(delay <= full && delay >= full / 2, "{:?} for {:?}", delay, full);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn retry_sleeps_between_attempts() {
+    let backoff = Backoff::new(ms(100), ms(300));
+    let start = Instant::now();
+    let mut attempts = vec![];
+
+    let res: Result<u32, ()> = backoff
+        .retry(|| {
+            attempts.push(start.elapsed());
+            let attempt = attempts.len() as u32;
+            async move {
+                match attempt {
+                    5 => Ok(attempt),
+                    _ => Err(()),
+                }
+            }
+        })
+        .await;
+
+    assert_eq!(res, Ok(5));
+    // waited 100ms, 200ms, then the cap of 300ms twice
+    assert_eq!(attempts, [ms(0), ms(100), ms(300), ms(600), ms(900)]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn retry_gives_up() {
+    let backoff = Backoff::new(ms(100), ms(1000)).max_retries(2);
+    let start = Instant::now();
+    let mut attempts = 0;
+
+    let res: Result<(), u32> = backoff
+        .retry(|| {
+            attempts += 1;
+            let attempt = attempts;
+            async move { Err(attempt) }
+        })
+        .await;
+
+    // the first attempt and two retries, the last error is returned
+    assert_eq!(res, Err(3));
+    assert_eq!(start.elapsed(), ms(300));
+}
@@ -0,0 +1,167 @@
+use redust::{client, server, ValueTransform};
+
+use bytes::Bytes;
+use std::sync::Arc;
+use std::time::Duration;
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn set_and_get_bits() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    assert!(!client.set_bit("bits", 7, true).await.unwrap());
+    assert!(client.set_bit("bits", 7, true).await.unwrap());
+    assert!(client.get_bit("bits", 7).await.unwrap());
+    assert!(!client.get_bit("bits", 6).await.unwrap());
+    assert!(!client.get_bit("missing", 6).await.unwrap());
+
+    assert!(client.set_bit("bits", 7, false).await.unwrap());
+    assert!(!client.get_bit("bits", 7).await.unwrap());
+}
+
+#[tokio::test]
+async fn value_grows_with_the_offset() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("bits", "\u{1}").await.unwrap();
+    client.set_bit("bits", 100, true).await.unwrap();
+
+    let value: Option<Bytes> = client.get("bits").await.unwrap();
+    let mut expected = vec![0; 13];
+    expected[0] = 1;
+    expected[12] = 0x08;
+    assert_eq!(value, Some(Bytes::from(expected)));
+
+    // bits past the end read as 0 and don't grow the value
+    assert!(!client.get_bit("bits", 1000).await.unwrap());
+    assert_eq!(client.bit_count("bits").await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn expiration_kept() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set_bit("bits", 3, true).await.unwrap();
+    assert!(client.expire("bits", Duration::from_secs(1)).await.unwrap());
+    client.set_bit("bits", 4, true).await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert_eq!(client.bit_count("bits").await.unwrap(), 0);
+    assert!(!client.set_bit("bits", 4, true).await.unwrap());
+}
+
+#[tokio::test]
+async fn out_of_range_offsets() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let max = ((1u64 << 32) - 1).to_string();
+    for offset in ["4294967296", "-1", "1.5", "bit"] {
+        let err = client.command::<u64>(("setbit", "bits", offset, "1")).await.unwrap_err();
+        assert_eq!(err.to_string(), "ERR bit offset is not an integer or out of range", "{}", offset);
+        let err = client.command::<u64>(("getbit", "bits", offset)).await.unwrap_err();
+        assert_eq!(err.to_string(), "ERR bit offset is not an integer or out of range", "{}", offset);
+    }
+    let err = client.command::<u64>(("setbit", "bits", "1", "2")).await.unwrap_err();
+    assert_eq!(err.to_string(), "ERR bit is not an integer or out of range");
+
+    let bit: u64 = client.command(("getbit", "bits", max.as_str())).await.unwrap();
+    assert_eq!(bit, 0);
+}
+
+#[tokio::test]
+async fn bitcount_ranges() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    // 0xff 0xf0 0x0f
+    client.set("bits", Bytes::from_static(&[0xff, 0xf0, 0x0f])).await.unwrap();
+    let cases: &[(&[&str], u64)] = &[
+        (&[], 16),
+        (&["0", "0"], 8),
+        (&["1", "2"], 8),
+        (&["-1", "-1"], 4),
+        (&["-100", "100"], 16),
+        (&["2", "1"], 0),
+        (&["3", "10"], 0),
+        (&["0", "0", "BIT"], 1),
+        (&["4", "11", "bit"], 8),
+        (&["-4", "-1", "BIT"], 4),
+        (&["12", "19", "byte"], 0),
+    ];
+    for (range, expected) in cases {
+        let mut args = vec!["bitcount", "bits"];
+        args.extend_from_slice(range);
+        let count: u64 = client.command(args).await.unwrap();
+        assert_eq!(count, *expected, "{:?}", range);
+    }
+
+    let count: u64 = client.command(("bitcount", "missing", "0", "-1")).await.unwrap();
+    assert_eq!(count, 0);
+    let err = client.command::<u64>(vec!["bitcount", "bits", "0", "1", "word"]).await.unwrap_err();
+    assert_eq!(err.to_string(), "ERR syntax error");
+    let err = client.command::<u64>(("bitcount", "bits", "zero", "1")).await.unwrap_err();
+    assert_eq!(err.to_string(), "ERR value is not an integer or out of range");
+}
+
+#[tokio::test]
+async fn wrong_type() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.blog_append("log", Bytes::from("chunk")).await.unwrap();
+    assert!(client.set_bit("log", 1, true).await.is_err());
+    assert!(client.get_bit("log", 1).await.is_err());
+    assert!(client.bit_count("log").await.is_err());
+}
+
+#[derive(Debug)]
+struct Reverse;
+
+impl ValueTransform for Reverse {
+    fn encode(&self, _key: &str, value: Bytes) -> redust::Result<Bytes> {
+        Ok(value.iter().rev().copied().collect::<Vec<u8>>().into())
+    }
+
+    fn decode(&self, key: &str, stored: Bytes) -> redust::Result<Bytes> {
+        self.encode(key, stored)
+    }
+}
+
+#[tokio::test]
+async fn bits_of_transformed_values() {
+    let server = start(server::Builder::new().value_transform(Arc::new(Reverse))).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set_bit("bits", 0, true).await.unwrap();
+    client.set_bit("bits", 15, true).await.unwrap();
+    assert!(client.get_bit("bits", 0).await.unwrap());
+    assert!(!client.get_bit("bits", 8).await.unwrap());
+
+    let value: Option<Bytes> = client.get("bits").await.unwrap();
+    assert_eq!(value, Some(Bytes::from_static(&[0x80, 0x01])));
+}
+
+#[tokio::test]
+async fn set_bit_makes_a_new_version() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set_expires("bits", Bytes::from("\u{0}"), Duration::from_secs(60)).await.unwrap();
+    let before = client.get_entry("bits").await.unwrap().unwrap();
+    client.set_bit("bits", 7, true).await.unwrap();
+    let after = client.get_entry("bits").await.unwrap().unwrap();
+    assert!(after.version > before.version, "{} {}", after.version, before.version);
+    assert_eq!(after.value, Bytes::from("\u{1}"));
+
+    // the expiration follows the new version and still fires
+    assert!(client.expire("bits", Duration::from_secs(1)).await.unwrap());
+    client.set_bit("bits", 6, true).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(client.get::<Option<Bytes>>("bits").await.unwrap(), None);
+}
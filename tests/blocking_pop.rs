@@ -0,0 +1,236 @@
+use redust::{client, server};
+
+use bytes::Bytes;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
+
+mod common;
+use common::start;
+
+type Popped = Option<(String, Bytes)>;
+
+fn keys(keys: &[&str]) -> Vec<String> {
+    keys.iter().map(|key| key.to_string()).collect()
+}
+
+/// Block a new client on `keys`, leaving it time to be queued before returning
+async fn block(addr: SocketAddr, keys: Vec<String>, wait: Duration) -> JoinHandle<Popped> {
+    let mut client = client::connect(addr).await.unwrap();
+    let blocked = tokio::spawn(async move { client.blpop(&keys, wait).await.unwrap() });
+    sleep(Duration::from_millis(50)).await;
+    blocked
+}
+
+fn popped(key: &str, element: &'static str) -> Popped {
+    Some((key.to_string(), Bytes::from(element)))
+}
+
+#[tokio::test]
+async fn pop_without_blocking() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client
+        .rpush("second", vec![Bytes::from("a"), Bytes::from("b")])
+        .await
+        .unwrap();
+
+    // the first non-empty list is popped
+    let wait = Duration::from_secs(1);
+    let first = client
+        .blpop(&keys(&["first", "second"]), wait)
+        .await
+        .unwrap();
+    assert_eq!(first, popped("second", "a"));
+    let last = client
+        .brpop(&keys(&["first", "second"]), wait)
+        .await
+        .unwrap();
+    assert_eq!(last, popped("second", "b"));
+    assert_eq!(client.exists(&keys(&["second"])).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn blocked_clients_served_in_order() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let mut blocked = vec![];
+    for _ in 0..3 {
+        blocked.push(block(server.local_addr(), keys(&["queue"]), Duration::ZERO).await);
+    }
+
+    // each client is handed one element, the first to block gets the first element
+    let elements = vec![
+        Bytes::from("a"),
+        Bytes::from("b"),
+        Bytes::from("c"),
+        Bytes::from("d"),
+    ];
+    assert_eq!(client.rpush("queue", elements).await.unwrap(), 4);
+    for (blocked, element) in blocked.into_iter().zip(["a", "b", "c"].iter()) {
+        let popped_by = timeout(Duration::from_secs(1), blocked)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(popped_by, popped("queue", element));
+    }
+    assert_eq!(
+        client.lrange("queue", 0, -1).await.unwrap(),
+        [Bytes::from("d")]
+    );
+}
+
+#[tokio::test]
+async fn one_client_per_element() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let first = block(
+        server.local_addr(),
+        keys(&["queue"]),
+        Duration::from_secs(5),
+    )
+    .await;
+    let second = block(
+        server.local_addr(),
+        keys(&["queue"]),
+        Duration::from_millis(500),
+    )
+    .await;
+
+    client
+        .lpush("queue", vec![Bytes::from("only")])
+        .await
+        .unwrap();
+    let popped_by = timeout(Duration::from_secs(1), first)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(popped_by, popped("queue", "only"));
+
+    // the other client times out empty handed, and the element isn't left in the list
+    assert_eq!(second.await.unwrap(), None);
+    assert_eq!(client.llen("queue").await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn blocked_on_several_keys() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let blocked = block(
+        server.local_addr(),
+        keys(&["first", "second"]),
+        Duration::ZERO,
+    )
+    .await;
+    client
+        .rpush("second", vec![Bytes::from("a")])
+        .await
+        .unwrap();
+    let popped_by = timeout(Duration::from_secs(1), blocked)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(popped_by, popped("second", "a"));
+
+    // the client was served once, it doesn't take from the other key anymore
+    client.rpush("first", vec![Bytes::from("b")]).await.unwrap();
+    sleep(Duration::from_millis(50)).await;
+    assert_eq!(client.llen("first").await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn disconnected_client_not_served() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let gone = block(server.local_addr(), keys(&["queue"]), Duration::ZERO).await;
+    let waiting = block(server.local_addr(), keys(&["queue"]), Duration::ZERO).await;
+    // dropping the client closes its connection
+    gone.abort();
+    sleep(Duration::from_millis(50)).await;
+
+    client
+        .rpush("queue", vec![Bytes::from("a"), Bytes::from("b")])
+        .await
+        .unwrap();
+    let popped_by = timeout(Duration::from_secs(1), waiting)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(popped_by, popped("queue", "a"));
+    assert_eq!(
+        client.lrange("queue", 0, -1).await.unwrap(),
+        [Bytes::from("b")]
+    );
+}
+
+#[tokio::test]
+async fn timeouts() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    // the request timeout of the client is extended by the time the server blocks
+    client.set_timeout(Some(Duration::from_millis(100)));
+    let popped_by = client
+        .blpop(&keys(&["queue"]), Duration::from_millis(300))
+        .await
+        .unwrap();
+    assert_eq!(popped_by, None);
+    client.set_timeout(None);
+
+    let popped_by: Popped = client.command(vec!["brpop", "queue", "0.1"]).await.unwrap();
+    assert_eq!(popped_by, None);
+
+    let errors = [
+        ("-1", "ERR timeout is negative"),
+        ("soon", "ERR timeout is not a float or out of range"),
+        ("inf", "ERR timeout is not a float or out of range"),
+    ];
+    for (wait, message) in errors.iter() {
+        let err = client
+            .command::<Popped>(vec!["blpop", "queue", wait])
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), *message, "{}", wait);
+    }
+
+    client.set("string", "value").await.unwrap();
+    let err = client
+        .blpop(&keys(&["string"]), Duration::ZERO)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+}
+
+#[tokio::test]
+async fn blocked_time_not_recorded() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client
+        .config_set("latency-monitor-threshold", "50")
+        .await
+        .unwrap();
+
+    let wait = Duration::from_millis(200);
+    assert_eq!(client.blpop(&keys(&["queue"]), wait).await.unwrap(), None);
+    assert_eq!(client.brpop(&keys(&["queue"]), wait).await.unwrap(), None);
+
+    // waiting for the timeout is neither a latency spike nor time spent running
+    assert!(client.latency_latest().await.unwrap().is_empty());
+    let info = client.info(Some("commandstats")).await.unwrap();
+    for command in ["blpop", "brpop"] {
+        let usec: u64 = info
+            .lines()
+            .find_map(|line| line.strip_prefix(&format!("cmdstat_{}:", command)))
+            .and_then(|line| {
+                line.split(',')
+                    .find_map(|field| field.strip_prefix("usec="))
+            })
+            .map_or(0, |usec| usec.parse().unwrap());
+        assert!(usec < 50_000, "{}", info);
+    }
+}
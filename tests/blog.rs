@@ -0,0 +1,123 @@
+use redust::{client, server, ValueTransform};
+
+use bytes::Bytes;
+use std::sync::Arc;
+use std::time::Duration;
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn append_and_read() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    assert_eq!(client.blog_append("log", Bytes::from("hello ")).await.unwrap(), 6);
+    assert_eq!(client.blog_append("log", Bytes::from("world")).await.unwrap(), 11);
+
+    let read = client.blog_read("log", 0, 100).await.unwrap();
+    assert_eq!(read, Some(Bytes::from("hello world")));
+    let read = client.blog_read("log", 6, 3).await.unwrap();
+    assert_eq!(read, Some(Bytes::from("wor")));
+    let read = client.blog_read("log", 20, 3).await.unwrap();
+    assert_eq!(read, Some(Bytes::new()));
+
+    assert_eq!(client.blog_read("missing", 0, 10).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn oldest_bytes_rotated_out_past_max_size() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.config_set("blog-max-bytes", "8").await.unwrap();
+
+    client.blog_append("log", Bytes::from("0123456789")).await.unwrap();
+    assert_eq!(client.blog_append("log", Bytes::from("ab")).await.unwrap(), 12);
+
+    let read = client.blog_read("log", 4, 100).await.unwrap();
+    assert_eq!(read, Some(Bytes::from("456789ab")));
+    let err = client.blog_read("log", 3, 100).await.unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "ERR offset 3 was rotated out of the log, oldest available is 4"
+    );
+}
+
+#[tokio::test]
+async fn wrong_type() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("string", Bytes::from("value")).await.unwrap();
+    assert!(client.blog_append("string", Bytes::from("chunk")).await.is_err());
+    assert!(client.blog_read("string", 0, 10).await.is_err());
+
+    client.blog_append("log", Bytes::from("chunk")).await.unwrap();
+    assert!(client.get::<Option<Bytes>>("log").await.is_err());
+}
+
+#[tokio::test]
+async fn expired_log_replaced_on_append() {
+    let server = start(server::Builder::new().enable_debug_command(true)).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    // keeps the expired log around so the append finds it
+    let _: String = client.command(("debug", "set-active-expire", "0")).await.unwrap();
+
+    client.blog_append("log", Bytes::from("old")).await.unwrap();
+    assert!(client.expire("log", Duration::from_secs(1)).await.unwrap());
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+
+    assert_eq!(client.blog_append("log", Bytes::from("new")).await.unwrap(), 3);
+    let read = client.blog_read("log", 0, 100).await.unwrap();
+    assert_eq!(read, Some(Bytes::from("new")));
+}
+
+/// Version of the entry of `key` reported by `DEBUG OBJECT`
+async fn version(client: &mut client::Client, key: &str) -> u64 {
+    let info: String = client.command(("debug", "object", key)).await.unwrap();
+    info.rsplit("version:").next().unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn each_append_makes_a_new_version() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.blog_append("log", Bytes::from("first")).await.unwrap();
+    let created = version(&mut client, "log").await;
+    client.blog_append("log", Bytes::from("second")).await.unwrap();
+    let appended = version(&mut client, "log").await;
+    assert!(appended > created, "{} {}", appended, created);
+
+    // the expiration follows the new version and still removes the log
+    assert!(client.expire("log", Duration::from_secs(1)).await.unwrap());
+    client.blog_append("log", Bytes::from("third")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert_eq!(client.blog_read("log", 0, 100).await.unwrap(), None);
+}
+
+#[derive(Debug)]
+struct Reverse;
+
+impl ValueTransform for Reverse {
+    fn encode(&self, _key: &str, value: Bytes) -> redust::Result<Bytes> {
+        Ok(value.iter().rev().copied().collect::<Vec<u8>>().into())
+    }
+
+    fn decode(&self, key: &str, stored: Bytes) -> redust::Result<Bytes> {
+        self.encode(key, stored)
+    }
+}
+
+#[tokio::test]
+async fn refused_with_a_value_transform() {
+    let server = start(server::Builder::new().value_transform(Arc::new(Reverse))).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let err = client.blog_append("log", Bytes::from("chunk")).await.unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "ERR byte logs are not available when the values are stored transformed"
+    );
+    assert!(client.blog_read("log", 0, 10).await.is_err());
+}
@@ -0,0 +1,75 @@
+use redust::{client, server};
+
+use bytes::Bytes;
+use std::time::Duration;
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn get_set_returns_the_replaced_value() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    assert_eq!(client.get_set("key", Bytes::from("first")).await.unwrap(), None);
+    let replaced = client.get_set("key", Bytes::from("second")).await.unwrap();
+    assert_eq!(replaced, Some(Bytes::from("first")));
+    assert_eq!(client.get::<Option<Bytes>>("key").await.unwrap(), Some(Bytes::from("second")));
+
+    // the expiration is dropped along with the old value
+    client.set_expires("key", "third", Duration::from_millis(100)).await.unwrap();
+    client.get_set("key", Bytes::from("fourth")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(client.get::<Option<Bytes>>("key").await.unwrap(), Some(Bytes::from("fourth")));
+}
+
+#[tokio::test]
+async fn compare_and_swap_only_on_match() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    // a missing key never matches
+    let current = client
+        .compare_and_swap("key", Bytes::new(), Bytes::from("v1"))
+        .await
+        .unwrap();
+    assert_eq!(current, None);
+    assert_eq!(client.get::<Option<Bytes>>("key").await.unwrap(), None);
+
+    client.set("key", "v1").await.unwrap();
+    let before = client.get_entry("key").await.unwrap().unwrap();
+
+    // a stale expected value leaves the key alone and returns the current one
+    let current = client
+        .compare_and_swap("key", Bytes::from("v0"), Bytes::from("v2"))
+        .await
+        .unwrap();
+    assert_eq!(current, Some(Bytes::from("v1")));
+    let unchanged = client.get_entry("key").await.unwrap().unwrap();
+    assert_eq!(unchanged.version, before.version);
+
+    let current = client
+        .compare_and_swap("key", Bytes::from("v1"), Bytes::from("v2"))
+        .await
+        .unwrap();
+    assert_eq!(current, Some(Bytes::from("v1")));
+    let swapped = client.get_entry("key").await.unwrap().unwrap();
+    assert_eq!(swapped.value, Bytes::from("v2"));
+    assert!(swapped.version > before.version, "{} {}", swapped.version, before.version);
+}
+
+#[tokio::test]
+async fn swap_of_a_list_fails() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.command::<i64>(vec!["rpush", "list", "a"]).await.unwrap();
+    let err = client.get_set("list", Bytes::from("b")).await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+    let err = client
+        .compare_and_swap("list", Bytes::from("a"), Bytes::from("b"))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+    assert_eq!(client.llen("list").await.unwrap(), 1);
+}
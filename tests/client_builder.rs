@@ -0,0 +1,73 @@
+use redust::client::ClientBuilder;
+use redust::{client, server, Connection, Frame};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn authenticates_as_the_user() {
+    let server = start(server::Builder::new()).await;
+    let mut admin = client::connect(server.local_addr()).await.unwrap();
+    admin.acl_set_user("alice", &["on", ">secret", "+@all", "allkeys"]).await.unwrap();
+
+    let mut client = ClientBuilder::new()
+        .username("alice")
+        .password("secret")
+        .timeout(Duration::from_secs(1))
+        .connect(server.local_addr())
+        .await
+        .unwrap();
+    assert_eq!(client.acl_whoami().await.unwrap(), "alice");
+    assert_eq!(client.timeout(), Some(Duration::from_secs(1)));
+
+    let client = ClientBuilder::new()
+        .username("alice")
+        .password("wrong")
+        .connect(server.local_addr())
+        .await;
+    assert!(client.is_err());
+}
+
+#[tokio::test]
+async fn setup_commands_sent_in_order() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // answers OK to the setup commands and records them
+    let fake = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut connection = Connection::new(socket);
+        let mut received = vec![];
+        for _ in 0..3 {
+            received.push(connection.read_frame().await.unwrap().unwrap());
+            connection.write_frame(&Frame::Simple("OK".to_string())).await.unwrap();
+        }
+        received
+    });
+
+    let client = ClientBuilder::new()
+        .password("secret")
+        .name("worker")
+        .database(2)
+        .nodelay(false)
+        .keepalive(true)
+        .connect(addr)
+        .await;
+    assert!(client.is_ok());
+
+    let args = |args: &[&'static str]| {
+        Frame::Array(args.iter().map(|arg| Frame::Bulk(Bytes::from_static(arg.as_bytes()))).collect())
+    };
+    assert_eq!(
+        fake.await.unwrap(),
+        [
+            args(&["auth", "secret"]),
+            args(&["client", "setname", "worker"]),
+            args(&["select", "2"]),
+        ]
+    );
+}
@@ -0,0 +1,58 @@
+use redust::{client, server, Frame};
+
+use bytes::Bytes;
+use std::io;
+use std::sync::{Arc, Mutex};
+use tracing::Level;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+
+mod common;
+use common::start;
+
+/// Log output kept in memory
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Captured {
+    type Writer = Captured;
+
+    fn make_writer(&'a self) -> Captured {
+        self.clone()
+    }
+}
+
+#[tokio::test]
+async fn raw_command_logs_only_the_command_name() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let captured = Captured::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(captured.clone()).with_ansi(false))
+        .with(Targets::new().with_target("redust::client", Level::TRACE));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let frame = Frame::Array(vec![
+        Frame::Bulk(Bytes::from("AUTH")),
+        Frame::Bulk(Bytes::from("s3cr3t-password")),
+    ]);
+    // no password is configured, the reply is an error
+    assert!(client.raw_command(frame).await.is_err());
+
+    let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    assert!(logs.contains("command=auth"), "{}", logs);
+    assert!(!logs.contains("s3cr3t-password"), "{}", logs);
+}
@@ -0,0 +1,48 @@
+use redust::client::{self, ClientBuilder};
+use redust::{server, Error};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::time::sleep;
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn request_timeout() {
+    let server = start(server::Builder::new().enable_debug_command(true)).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.set_timeout(Some(Duration::from_millis(100)));
+
+    let err = client.command::<String>(("debug", "sleep", "0.3")).await.unwrap_err();
+    assert!(matches!(err, Error::Timeout), "{}", err);
+
+    // still waiting for the late reply, then for its own
+    let err = client.get::<Option<Bytes>>("missing").await.unwrap_err();
+    assert!(matches!(err, Error::Timeout), "{}", err);
+
+    // once they arrive the late replies are skipped, the next request gets its own reply
+    sleep(Duration::from_millis(300)).await;
+    let value: Option<Bytes> = client.get("missing").await.unwrap();
+    assert_eq!(value, None);
+    client.set("key", "value").await.unwrap();
+    assert_eq!(client.ping(Some(Bytes::from("hello"))).await.unwrap(), Bytes::from("hello"));
+}
+
+#[tokio::test]
+async fn connect_timeout_covers_the_setup() {
+    // accepted by the kernel, but nothing ever replies
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = client::connect_timeout(addr, Duration::from_millis(100)).await;
+    assert!(client.is_ok());
+
+    let client = ClientBuilder::new()
+        .password("secret")
+        .connect_timeout(Duration::from_millis(100))
+        .connect(addr)
+        .await;
+    assert!(matches!(client, Err(Error::Timeout)));
+}
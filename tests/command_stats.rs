@@ -0,0 +1,68 @@
+use redust::{client, server, CommandHandler, Frame, Parse, Store};
+
+use std::sync::Arc;
+
+mod common;
+use common::start;
+
+#[derive(Debug)]
+struct Echo;
+
+impl CommandHandler for Echo {
+    fn call(&self, args: &mut Parse, _store: &Store) -> redust::Result<Frame> {
+        Ok(Frame::Bulk(args.next_bytes()?))
+    }
+}
+
+/// `calls` and `failed_calls` of `command` in `INFO commandstats`
+fn stats(info: &str, command: &str) -> Option<(u64, u64)> {
+    let line = info
+        .lines()
+        .find_map(|line| line.strip_prefix(&format!("cmdstat_{}:", command)))?;
+    let field = |name: &str| {
+        line.split(',')
+            .find_map(|field| field.strip_prefix(name)?.strip_prefix('='))
+            .unwrap()
+            .parse()
+            .unwrap()
+    };
+    Some((field("calls"), field("failed_calls")))
+}
+
+#[tokio::test]
+async fn calls_counted_by_command() {
+    let builder = server::Builder::new().register_command("echo", Arc::new(Echo)).unwrap();
+    let server = start(builder).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("key", "value").await.unwrap();
+    client.set("key", "value").await.unwrap();
+    client.blog_append("key", "chunk".into()).await.unwrap_err();
+    let _: String = client.command(("echo", "hello")).await.unwrap();
+    client.command::<String>(("echo",)).await.unwrap_err();
+    client.command::<String>(("nope",)).await.unwrap_err();
+
+    let info = client.info(Some("commandstats")).await.unwrap();
+    assert_eq!(stats(&info, "set"), Some((2, 0)));
+    assert_eq!(stats(&info, "blog.append"), Some((1, 1)));
+    assert_eq!(stats(&info, "echo"), Some((2, 1)));
+    assert_eq!(stats(&info, "nope"), None);
+    assert_eq!(stats(&info, "get"), None);
+}
+
+#[tokio::test]
+async fn resetstat() {
+    let builder = server::Builder::new().register_command("echo", Arc::new(Echo)).unwrap();
+    let server = start(builder).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("key", "value").await.unwrap();
+    let _: String = client.command(("echo", "hello")).await.unwrap();
+    client.config_resetstat().await.unwrap();
+
+    let info = client.info(Some("commandstats")).await.unwrap();
+    assert_eq!(stats(&info, "set"), None);
+    assert_eq!(stats(&info, "echo"), None);
+    // the reset itself
+    assert_eq!(stats(&info, "config"), Some((1, 0)));
+}
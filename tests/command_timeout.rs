@@ -0,0 +1,48 @@
+use redust::{client, server};
+
+use std::time::Duration;
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn slow_command_times_out() {
+    let builder = server::Builder::new()
+        .enable_debug_command(true)
+        .command_timeout(Duration::from_millis(100));
+    let server = start(builder).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let err = client
+        .command::<String>(vec!["debug", "sleep", "1"])
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "TIMEOUT 'debug' exceeded the execution budget of 100 ms");
+
+    // the connection is still served
+    client.set("key", "value").await.unwrap();
+    let info = client.info(Some("stats")).await.unwrap();
+    assert!(info.contains("timed_out_commands:1\r\n"), "{}", info);
+
+    // blocking pops wait for their own timeout
+    let keys = vec!["queue".to_string()];
+    assert_eq!(client.blpop(&keys, Duration::from_millis(300)).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn budget_set_at_runtime() {
+    let server = start(server::Builder::new().enable_debug_command(true)).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let _: String = client.command(vec!["debug", "sleep", "0.2"]).await.unwrap();
+    client.config_set("command-timeout", "50").await.unwrap();
+    let err = client
+        .command::<String>(vec!["debug", "sleep", "0.2"])
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("TIMEOUT"), "{}", err);
+
+    // 0 disables it again
+    client.config_set("command-timeout", "0").await.unwrap();
+    let _: String = client.command(vec!["debug", "sleep", "0.2"]).await.unwrap();
+}
@@ -0,0 +1,21 @@
+//! Helpers shared by the integration tests
+
+use redust::{client, server};
+use tokio::net::TcpListener;
+
+/// Start the server built by `builder` on a free local port
+pub async fn start(builder: server::Builder) -> server::Server {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    builder.start(listener).unwrap()
+}
+
+/// Reads a field of the `INFO memory` report
+#[allow(dead_code)]
+pub async fn memory_field(client: &mut client::Client, field: &str) -> f64 {
+    let info = client.info(Some("memory")).await.unwrap();
+    info.lines()
+        .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))
+        .unwrap()
+        .parse()
+        .unwrap()
+}
@@ -0,0 +1,197 @@
+use redust::{client, server};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::time::timeout;
+
+mod common;
+use common::start;
+
+fn names(params: &[(String, String)]) -> Vec<&str> {
+    params.iter().map(|(name, _)| name.as_str()).collect()
+}
+
+#[tokio::test]
+async fn get_by_pattern() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let params = client.config_get("maxmemory").await.unwrap();
+    assert_eq!(params, vec![("maxmemory".to_string(), "0".to_string())]);
+    let params = client.config_get("MAXCLIENTS").await.unwrap();
+    assert_eq!(names(&params), ["maxclients"]);
+
+    let params = client.config_get("slowlog-*").await.unwrap();
+    assert_eq!(names(&params), ["slowlog-log-slower-than", "slowlog-max-len"]);
+    let params = client.config_get("ratelimit-client?by").await.unwrap();
+    assert_eq!(names(&params), ["ratelimit-client-by"]);
+    let params = client.config_get("*").await.unwrap();
+    assert!(names(&params).contains(&"notify-keyspace-events"), "{:?}", params);
+
+    assert!(client.config_get("nope*").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn set_validates_values() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let cases = [
+        ("maxclients", "many", "argument couldn't be parsed into an integer"),
+        ("activedefrag", "maybe", "argument must be 'yes' or 'no'"),
+        ("maxmemory", "1tb", "argument must be a memory value"),
+        ("notify-keyspace-events", "KZ", "unknown flag 'Z', flags are K, E, g, $, l, h, z, b, x and A"),
+        ("latency-tracking-precision", "6", "argument must be between 1 and 5"),
+        ("port", "6380", "can't be changed at runtime"),
+    ];
+    for (name, value, reason) in cases {
+        let err = client.config_set(name, value).await.unwrap_err();
+        let expected = format!("ERR Invalid argument '{}' for CONFIG SET '{}' - {}", value, name, reason);
+        assert_eq!(err.to_string(), expected);
+    }
+
+    let err = client.config_set("nope", "1").await.unwrap_err();
+    assert_eq!(err.to_string(), "ERR Unknown option or number of arguments for CONFIG SET - 'nope'");
+}
+
+#[tokio::test]
+async fn set_applies_all_or_nothing() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let args = vec!["config", "set", "maxclients", "10", "activedefrag", "maybe"];
+    assert!(client.command::<String>(args).await.is_err());
+    let params = client.config_get("maxclients").await.unwrap();
+    assert_eq!(params, vec![("maxclients".to_string(), "250".to_string())]);
+
+    let args = vec!["config", "set", "maxclients", "10", "MAXMEMORY", "2kb"];
+    let reply: String = client.command(args).await.unwrap();
+    assert_eq!(reply, "OK");
+    let params = client.config_get("max*").await.unwrap();
+    assert_eq!(
+        params,
+        vec![
+            ("maxclients".to_string(), "10".to_string()),
+            ("maxmemory".to_string(), "2048".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn maxmemory_refuses_writes() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("key", "value").await.unwrap();
+    client.config_set("maxmemory", "1").await.unwrap();
+
+    let err = client.set("other", "value").await.unwrap_err();
+    assert_eq!(err.to_string(), "OOM command not allowed when used memory > 'maxmemory'.");
+    assert!(client.incr("counter").await.is_err());
+    assert!(client.blog_append("log", Bytes::from("chunk")).await.is_err());
+
+    // reading and freeing memory are still allowed
+    let value: Option<Bytes> = client.get("key").await.unwrap();
+    assert_eq!(value, Some(Bytes::from("value")));
+    assert_eq!(client.del(&["key".to_string()]).await.unwrap(), 1);
+    client.set("other", "value").await.unwrap();
+
+    let info = client.info(Some("memory")).await.unwrap();
+    assert!(info.contains("maxmemory:1\r\n"), "{}", info);
+}
+
+#[tokio::test]
+async fn keyspace_notifications() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let subscriber = client::connect(server.local_addr()).await.unwrap();
+    let channels = vec!["__keyspace@0__:key".to_string(), "__keyevent@0__:del".to_string()];
+    let mut subscriber = subscriber.subscribe(channels).await.unwrap();
+
+    // off by default
+    client.set("key", "value").await.unwrap();
+
+    client.config_set("notify-keyspace-events", "gKE").await.unwrap();
+    let params = client.config_get("notify-keyspace-events").await.unwrap();
+    assert_eq!(params, vec![("notify-keyspace-events".to_string(), "gKE".to_string())]);
+    // string events aren't enabled
+    client.set("key", "value").await.unwrap();
+    assert!(client.expire("key", Duration::from_secs(60)).await.unwrap());
+    assert_eq!(client.del(&["key".to_string()]).await.unwrap(), 1);
+
+    // the channels are read in no particular order
+    let mut received = vec![];
+    for _ in 0..3 {
+        let message = timeout(Duration::from_secs(1), subscriber.next_message())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        received.push((message.channel, message.content));
+    }
+    received.sort();
+    assert_eq!(
+        received,
+        [
+            ("__keyevent@0__:del".to_string(), Bytes::from("key")),
+            ("__keyspace@0__:key".to_string(), Bytes::from("del")),
+            ("__keyspace@0__:key".to_string(), Bytes::from("expire")),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn expired_notifications() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.config_set("notify-keyspace-events", "KEA").await.unwrap();
+    let params = client.config_get("notify-keyspace-events").await.unwrap();
+    assert_eq!(params, vec![("notify-keyspace-events".to_string(), "AKE".to_string())]);
+
+    let subscriber = client::connect(server.local_addr()).await.unwrap();
+    let mut subscriber = subscriber
+        .subscribe(vec!["__keyevent@0__:expired".to_string()])
+        .await
+        .unwrap();
+
+    client.set_expires("key", "value", Duration::from_millis(50)).await.unwrap();
+    let message = timeout(Duration::from_secs(1), subscriber.next_message())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(message.content, Bytes::from("key"));
+}
+
+#[tokio::test]
+async fn slowlog() {
+    let server = start(server::Builder::new().enable_debug_command(true)).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.config_set("slowlog-log-slower-than", "20000").await.unwrap();
+    client.ping(None).await.unwrap();
+    client.debug_sleep(Duration::from_millis(50)).await.unwrap();
+
+    let entries = client.slowlog_get(None).await.unwrap();
+    assert_eq!(entries.len(), 1, "{:?}", entries);
+    assert_eq!(entries[0].args, ["debug"]);
+    assert!(entries[0].duration >= Duration::from_millis(50));
+    assert_eq!(entries[0].user, "default");
+
+    // every command is kept with a threshold of 0, only the latest ones past the maximum length
+    client.config_set("slowlog-log-slower-than", "0").await.unwrap();
+    client.config_set("slowlog-max-len", "2").await.unwrap();
+    client.set("key", "secret").await.unwrap();
+    assert_eq!(client.slowlog_len().await.unwrap(), 2);
+    // the command is logged once it ran, after its reply
+    let entries = client.slowlog_get(Some(-1)).await.unwrap();
+    assert_eq!(entries[0].args, ["slowlog"]);
+    assert_eq!(entries[1].args, ["set", "key"]);
+    assert!(entries[0].id > entries[1].id);
+
+    client.config_set("slowlog-log-slower-than", "-1").await.unwrap();
+    client.slowlog_reset().await.unwrap();
+    client.set("key", "value").await.unwrap();
+    assert_eq!(client.slowlog_len().await.unwrap(), 0);
+}
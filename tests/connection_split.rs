@@ -0,0 +1,74 @@
+use redust::frame::Limits;
+use redust::{server, Connection, Frame};
+
+use bytes::Bytes;
+use tokio::net::{TcpListener, TcpStream};
+
+mod common;
+use common::start;
+
+fn bulk(s: &'static str) -> Frame {
+    Frame::Bulk(Bytes::from_static(s.as_bytes()))
+}
+
+/// Both ends of a local TCP connection
+async fn pair() -> (Connection, Connection) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let local = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+    let (remote, _) = listener.accept().await.unwrap();
+    (Connection::new(local), Connection::new(remote))
+}
+
+#[tokio::test]
+async fn halves_used_from_different_tasks() {
+    let (connection, mut remote) = pair().await;
+    let peer_addr = connection.peer_addr().unwrap();
+    let (mut reader, mut writer) = connection.into_split();
+    assert_eq!(reader.peer_addr().unwrap(), peer_addr);
+    assert_eq!(writer.peer_addr().unwrap(), peer_addr);
+
+    // the writer doesn't wait on the reader, which is blocked until the remote end writes
+    let reading = tokio::spawn(async move {
+        let frame = reader.read_frame().await.unwrap();
+        (reader, frame)
+    });
+    writer.write_frame(&bulk("ping")).await.unwrap();
+    assert_eq!(remote.read_frame().await.unwrap(), Some(bulk("ping")));
+
+    // two frames in one write, the reader only returns the first one
+    remote.write_frame_unflushed(&bulk("first")).await.unwrap();
+    remote.write_frame_unflushed(&bulk("second")).await.unwrap();
+    remote.flush().await.unwrap();
+    let (reader, frame) = reading.await.unwrap();
+    assert_eq!(frame, Some(bulk("first")));
+
+    // the frame read ahead by the reader is kept by the reunited connection
+    let mut connection = reader.reunite(writer);
+    assert_eq!(connection.peer_addr().unwrap(), peer_addr);
+    assert_eq!(connection.read_frame().await.unwrap(), Some(bulk("second")));
+    connection.write_frame(&bulk("pong")).await.unwrap();
+    assert_eq!(remote.read_frame().await.unwrap(), Some(bulk("pong")));
+}
+
+#[tokio::test]
+async fn connection_state_kept_across_subscribe_mode() {
+    let limits = Limits {
+        max_bulk_len: 16,
+        ..Limits::default()
+    };
+    let server = start(server::Builder::new().frame_limits(limits)).await;
+    let mut connection = Connection::new(TcpStream::connect(server.local_addr()).await.unwrap());
+
+    // the server splits the connection while subscribed, and puts it back together on RESET
+    connection.write_frame(&Frame::Array(vec![bulk("subscribe"), bulk("news")])).await.unwrap();
+    assert!(matches!(connection.read_frame().await.unwrap(), Some(Frame::Array(_))));
+    connection.write_frame(&Frame::Array(vec![bulk("reset")])).await.unwrap();
+    assert_eq!(connection.read_frame().await.unwrap(), Some(Frame::Simple("RESET".to_string())));
+
+    // the frame limits still apply
+    let set = Frame::Array(vec![bulk("set"), bulk("key"), bulk("a value past the limit")]);
+    connection.write_frame(&set).await.unwrap();
+    let err = Frame::Error("ERR protocol error; invalid bulk length".to_string());
+    assert_eq!(connection.read_frame().await.unwrap(), Some(err));
+    assert_eq!(connection.read_frame().await.unwrap(), None);
+}
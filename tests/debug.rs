@@ -0,0 +1,66 @@
+use redust::{client, server};
+
+use std::time::Duration;
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn debug_sleep_rejects_invalid_durations() {
+    let server = start(server::Builder::new().enable_debug_command(true)).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    for secs in ["1e30", "nan", "-1", "inf", "soon"] {
+        let err = client.command::<String>(("debug", "sleep", secs)).await.unwrap_err();
+        assert_eq!(err.to_string(), "ERR value is not a valid float", "{}", secs);
+    }
+    let reply: String = client.command(("debug", "sleep", "0.01")).await.unwrap();
+    assert_eq!(reply, "OK");
+}
+
+#[tokio::test]
+async fn enable_debug_command_is_fixed_at_startup() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    assert!(client.config_set("enable-debug-command", "yes").await.is_err());
+    let config = client.config_get("enable-debug-command").await.unwrap();
+    assert_eq!(config, vec![("enable-debug-command".to_string(), "no".to_string())]);
+    assert!(client.command::<String>(("debug", "sleep", "0")).await.is_err());
+}
+
+/// The `field:value` pairs of a `DEBUG OBJECT` reply
+fn object_fields(reply: &str) -> Vec<(&str, &str)> {
+    reply.split(' ').map(|field| field.split_once(':').unwrap()).collect()
+}
+
+#[tokio::test]
+async fn debug_object_describes_entries() {
+    // allowed without enable-debug-command, it changes nothing
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("key", "value").await.unwrap();
+    let reply: String = client.command(("debug", "object", "key")).await.unwrap();
+    let fields = object_fields(&reply);
+    assert_eq!(&fields[..3], [("type", "string"), ("serializedlength", "5"), ("ttl", "-1")]);
+    assert_eq!(fields[3].0, "version");
+
+    // a write gives the entry a new version
+    client.set_expires("key", "other value", Duration::from_secs(10)).await.unwrap();
+    let reply: String = client.command(("debug", "object", "key")).await.unwrap();
+    let updated = object_fields(&reply);
+    assert_eq!(updated[1], ("serializedlength", "11"));
+    let ttl: u64 = updated[2].1.parse().unwrap();
+    assert!(ttl > 9_000 && ttl <= 10_000, "{}", ttl);
+    let version = |fields: &[(&str, &str)]| fields[3].1.parse::<u64>().unwrap();
+    assert!(version(&updated) > version(&fields));
+
+    // the size of a list is the sum of its elements
+    client.command::<i64>(("rpush", "list", "a", "bcd")).await.unwrap();
+    let reply: String = client.command(("debug", "object", "list")).await.unwrap();
+    assert_eq!(&object_fields(&reply)[..2], [("type", "list"), ("serializedlength", "4")]);
+
+    let err = client.command::<String>(("debug", "object", "missing")).await.unwrap_err();
+    assert_eq!(err.to_string(), "ERR no such key");
+}
@@ -0,0 +1,48 @@
+use redust::{client, server};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+mod common;
+use common::{memory_field, start};
+
+#[tokio::test]
+async fn mass_deletion_reported_and_defragmented() {
+    let server = start(server::Builder::new().shards(1)).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let keys: Vec<String> = (0..3000).map(|i| format!("key:{}", i)).collect();
+    for key in &keys {
+        client.set(key, "value").await.unwrap();
+    }
+    assert_eq!(client.del(&keys[10..]).await.unwrap(), 2990);
+
+    // the map didn't shrink
+    assert_eq!(memory_field(&mut client, "keys").await, 10.0);
+    assert!(memory_field(&mut client, "keys_capacity").await > 1024.0);
+    assert!(memory_field(&mut client, "mem_fragmentation_ratio").await > 100.0);
+    assert!(memory_field(&mut client, "keyspace_overhead_bytes").await > 0.0);
+    assert_eq!(
+        memory_field(&mut client, "active_defrag_enabled").await,
+        0.0
+    );
+    assert_eq!(memory_field(&mut client, "active_defrag_runs").await, 0.0);
+
+    client.config_set("activedefrag", "yes").await.unwrap();
+    timeout(Duration::from_secs(5), async {
+        while memory_field(&mut client, "active_defrag_runs").await < 1.0 {
+            sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    assert!(memory_field(&mut client, "keys_capacity").await < 100.0);
+    assert!(memory_field(&mut client, "mem_fragmentation_ratio").await < 10.0);
+    // the keys left are untouched
+    for key in &keys[..10] {
+        let value: Option<Bytes> = client.get(key).await.unwrap();
+        assert_eq!(value, Some(Bytes::from("value")));
+    }
+}
@@ -0,0 +1,32 @@
+use redust::{embedded, server};
+
+use bytes::Bytes;
+
+#[tokio::test]
+async fn spawn_connect_and_shut_down() {
+    let (mut client, server) = embedded::spawn().await.unwrap();
+    client.set("foo", "bar").await.unwrap();
+
+    // the store and the other clients see the same key space
+    assert_eq!(server.store().get("foo").unwrap(), Some(Bytes::from("bar")));
+    server.store().set("other", Bytes::from("value")).unwrap();
+    let mut other = server.connect().await.unwrap();
+    let value: Option<Bytes> = other.get("other").await.unwrap();
+    assert_eq!(value, Some(Bytes::from("value")));
+
+    let addr = server.addr();
+    drop((client, other));
+    server.shutdown().await.unwrap();
+    assert!(redust::client::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn spawn_configured() {
+    let builder = server::Builder::new().requirepass("secret");
+    let (mut client, server) = embedded::spawn_with(builder).await.unwrap();
+    assert!(client.set("foo", "bar").await.is_err());
+    client.auth(None, "secret").await.unwrap();
+    client.set("foo", "bar").await.unwrap();
+    drop(client);
+    server.shutdown().await.unwrap();
+}
@@ -0,0 +1,53 @@
+use redust::{client, server};
+
+use bytes::Bytes;
+use std::time::Duration;
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn out_of_range_ttls() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.set("key", "value").await.unwrap();
+
+    let err = client
+        .expire("key", Duration::from_secs(u64::MAX / 2))
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "ERR invalid expire time in 'expire' command");
+
+    let huge = (u64::MAX / 2).to_string();
+    for unit in ["ex", "px"].iter() {
+        let err = client
+            .command::<Option<Bytes>>(vec!["set", "key", "other", unit, &huge])
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "ERR invalid expire time in 'set' command", "{}", unit);
+    }
+
+    // the key is untouched and the shard still serves it
+    assert_eq!(client.get::<Option<Bytes>>("key").await.unwrap(), Some(Bytes::from("value")));
+    assert!(client.expire("key", Duration::from_secs(60)).await.unwrap());
+    client.set_expires("key", Bytes::from("other"), Duration::from_secs(60)).await.unwrap();
+    assert_eq!(client.get::<Option<Bytes>>("key").await.unwrap(), Some(Bytes::from("other")));
+}
+
+#[tokio::test]
+async fn expire_makes_a_new_version() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("key", "value").await.unwrap();
+    let before = client.get_entry("key").await.unwrap().unwrap();
+    assert!(client.expire("key", Duration::from_secs(60)).await.unwrap());
+    let after = client.get_entry("key").await.unwrap().unwrap();
+    assert!(after.version > before.version, "{} {}", after.version, before.version);
+    assert!(after.ttl.is_some());
+
+    // expiring it again moves the expiration along
+    assert!(client.expire("key", Duration::from_secs(1)).await.unwrap());
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(client.get::<Option<Bytes>>("key").await.unwrap(), None);
+}
@@ -0,0 +1,181 @@
+use redust::{frame, Connection, Frame, RespCodec};
+
+use bytes::{Bytes, BytesMut};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::{Decoder, Encoder};
+
+fn encode(frame: Frame) -> BytesMut {
+    let mut dst = BytesMut::new();
+    RespCodec::new().encode(frame, &mut dst).unwrap();
+    dst
+}
+
+#[test]
+fn encode_integer_bounds() {
+    let cases: [(i64, &[u8]); 3] = [
+        (i64::MAX, b":9223372036854775807\r\n"),
+        (i64::MIN, b":-9223372036854775808\r\n"),
+        (-1, b":-1\r\n"),
+    ];
+    for (value, encoded) in cases.iter() {
+        let dst = encode(Frame::Integer(*value));
+        assert_eq!(&dst[..], *encoded);
+
+        match RespCodec::new().decode(&mut dst.clone()).unwrap() {
+            Some(Frame::Integer(n)) => assert_eq!(n, *value),
+            frame => panic!("unexpected frame: {:?}", frame),
+        }
+    }
+}
+
+#[test]
+fn from_conversions() {
+    assert_eq!(Frame::from(-1), Frame::Integer(-1));
+    assert_eq!(Frame::from(i64::MIN), Frame::Integer(i64::MIN));
+    assert_eq!(Frame::from("value"), Frame::Bulk(Bytes::from_static(b"value")));
+    assert_eq!(&encode(Frame::from(-42))[..], b":-42\r\n");
+}
+
+#[test]
+fn encode_nested_array() {
+    let frame = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"message")),
+        Frame::Array(vec![Frame::Integer(1), Frame::Null]),
+        Frame::Array(vec![]),
+    ]);
+    let mut dst = encode(frame);
+    assert_eq!(
+        &dst[..],
+        &b"*3\r\n$7\r\nmessage\r\n*2\r\n:1\r\n$-1\r\n*0\r\n"[..]
+    );
+
+    match RespCodec::new().decode(&mut dst).unwrap() {
+        Some(Frame::Array(parts)) => {
+            assert_eq!(parts.len(), 3);
+            assert!(matches!(&parts[1], Frame::Array(inner) if inner.len() == 2));
+            assert!(matches!(&parts[2], Frame::Array(inner) if inner.is_empty()));
+        }
+        frame => panic!("unexpected frame: {:?}", frame),
+    }
+    assert!(dst.is_empty());
+}
+
+#[tokio::test]
+async fn connection_writes_large_integers() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let writer = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut conn = Connection::new(socket);
+        for n in &[0, 9, 10, -1, i64::MIN, i64::MAX] {
+            conn.write_frame(&Frame::Integer(*n)).await.unwrap();
+        }
+    });
+
+    let mut conn = Connection::new(TcpStream::connect(addr).await.unwrap());
+    for expected in &[0, 9, 10, -1, i64::MIN, i64::MAX] {
+        match conn.read_frame().await.unwrap() {
+            Some(Frame::Integer(n)) => assert_eq!(n, *expected),
+            frame => panic!("unexpected frame: {:?}", frame),
+        }
+    }
+    writer.await.unwrap();
+}
+
+#[tokio::test]
+async fn connection_writes_nested_arrays() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let writer = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut conn = Connection::new(socket);
+        let frame = Frame::Array(vec![
+            Frame::Array(vec![Frame::Array(vec![Frame::Integer(1)]), Frame::Null]),
+            Frame::Bulk(Bytes::from_static(b"tail")),
+        ]);
+        conn.write_frame(&frame).await.unwrap();
+    });
+
+    let mut conn = Connection::new(TcpStream::connect(addr).await.unwrap());
+    match conn.read_frame().await.unwrap() {
+        Some(Frame::Array(parts)) => {
+            assert_eq!(parts.len(), 2);
+            match &parts[0] {
+                Frame::Array(inner) => {
+                    assert!(matches!(&inner[0], Frame::Array(v) if matches!(v[..], [Frame::Integer(1)])));
+                    assert!(matches!(inner[1], Frame::Null));
+                }
+                frame => panic!("unexpected frame: {:?}", frame),
+            }
+            assert_eq!(parts[1], "tail");
+        }
+        frame => panic!("unexpected frame: {:?}", frame),
+    }
+    writer.await.unwrap();
+}
+
+#[test]
+fn to_bytes_matches_codec() {
+    let frame = Frame::Array(vec![
+        Frame::Simple("OK".to_string()),
+        Frame::Error("ERR boom".to_string()),
+        Frame::Bulk(Bytes::new()),
+    ]);
+    assert_eq!(
+        &frame.to_bytes()[..],
+        &b"*3\r\n+OK\r\n-ERR boom\r\n$0\r\n\r\n"[..]
+    );
+    assert_eq!(frame.to_bytes(), encode(frame).freeze());
+}
+
+#[test]
+fn parse_partial_reports_consumed_length() {
+    let (frame, len) = Frame::parse_partial(b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n+OK\r\n").unwrap();
+    assert!(matches!(frame, Frame::Array(ref parts) if parts.len() == 2));
+    assert_eq!(len, 20);
+
+    // the line terminator is the very last bytes of the buffer
+    let (frame, len) = Frame::parse_partial(b"+OK\r\n").unwrap();
+    assert_eq!(frame, "OK");
+    assert_eq!(len, 5);
+}
+
+#[test]
+fn parse_partial_incomplete() {
+    for src in &[&b""[..], b"+OK", b"+OK\r", b"$3\r\nge", b"$3\r\nget\r", b"*2\r\n:1\r\n"] {
+        assert!(
+            matches!(Frame::parse_partial(src), Err(frame::Error::Incomplete)),
+            "{:?}",
+            src
+        );
+    }
+}
+
+#[test]
+fn parse_partial_rejects_malformed() {
+    for src in &[&b"?\r\n"[..], b"$3\r\ngetxx", b"$-2\r\n", b":abc\r\n"] {
+        assert!(
+            matches!(Frame::parse_partial(src), Err(frame::Error::Other(_))),
+            "{:?}",
+            src
+        );
+    }
+}
+
+#[test]
+fn pretty_and_inline_rendering() {
+    let frame = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"a\r\nb")),
+        Frame::Array(vec![Frame::Integer(1), Frame::Null]),
+    ]);
+    assert_eq!(
+        frame::pretty::pretty(&frame).to_string(),
+        "1) \"a\\r\\nb\"\n2) 1) (integer) 1\n   2) (nil)"
+    );
+    assert_eq!(
+        frame::pretty::inline(&frame).to_string(),
+        "[\"a\\r\\nb\", [(integer) 1, (nil)]]"
+    );
+}
@@ -0,0 +1,66 @@
+use redust::frame::Limits;
+use redust::{client, server};
+
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+mod common;
+use common::start;
+
+/// Send `request` on a new connection, returns everything received until the server closes it
+async fn send(addr: SocketAddr, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request).await.unwrap();
+    let mut reply = vec![];
+    timeout(Duration::from_secs(1), stream.read_to_end(&mut reply)).await.unwrap().unwrap();
+    String::from_utf8(reply).unwrap()
+}
+
+#[tokio::test]
+async fn huge_lengths_rejected_before_reading() {
+    let server = start(server::Builder::new()).await;
+    let addr = server.local_addr();
+
+    // announced, not sent, the server doesn't wait for them
+    let reply = send(addr, b"*4294967295\r\n").await;
+    assert_eq!(reply, "-ERR protocol error; invalid multibulk length\r\n");
+    let reply = send(addr, b"*2\r\n$3\r\nGET\r\n$4294967295\r\n").await;
+    assert_eq!(reply, "-ERR protocol error; invalid bulk length\r\n");
+}
+
+#[tokio::test]
+async fn configured_limits() {
+    let limits = Limits {
+        max_bulk_len: 8,
+        max_array_len: 4,
+        max_depth: 2,
+        max_frame_size: 64,
+    };
+    let server = start(server::Builder::new().frame_limits(limits)).await;
+    let addr = server.local_addr();
+
+    let reply = send(addr, b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$9\r\n123456789\r\n").await;
+    assert_eq!(reply, "-ERR protocol error; invalid bulk length\r\n");
+    let reply = send(addr, b"*5\r\n").await;
+    assert_eq!(reply, "-ERR protocol error; invalid multibulk length\r\n");
+    let reply = send(addr, b"*1\r\n*1\r\n*1\r\n:1\r\n").await;
+    assert_eq!(reply, "-ERR protocol error; frame nested too deeply\r\n");
+
+    // an incomplete frame can't grow past the size limit either
+    let mut request = b"*4\r\n".to_vec();
+    for _ in 0..2 {
+        request.extend_from_slice(b"*4\r\n");
+        for _ in 0..4 {
+            request.extend_from_slice(b"$8\r\n12345678\r\n");
+        }
+    }
+    let reply = send(addr, &request).await;
+    assert_eq!(reply, "-ERR protocol error; frame too large\r\n");
+
+    // the frames within the limits are served
+    let mut client = client::connect(addr).await.unwrap();
+    client.set("key", "12345678").await.unwrap();
+}
@@ -0,0 +1,36 @@
+use redust::{Error, Frame};
+
+use bytes::Bytes;
+
+#[test]
+fn try_push_onto_array() {
+    let mut frame = Frame::array();
+    frame.try_push_bulk(Bytes::from("incrby")).unwrap();
+    frame.try_push_int(-5).unwrap();
+    frame.try_push_null().unwrap();
+    assert_eq!(
+        frame,
+        Frame::Array(vec![Frame::Bulk(Bytes::from("incrby")), Frame::Integer(-5), Frame::Null])
+    );
+}
+
+#[test]
+fn try_push_onto_other_frame() {
+    // the push fails instead of panicking, and the frame is left untouched
+    let mut frame = Frame::Simple("OK".to_string());
+    let results = [
+        frame.try_push_bulk(Bytes::from("value")),
+        frame.try_push_int(1),
+        frame.try_push_null(),
+    ];
+    for result in results {
+        match result {
+            Err(Error::UnexpectedFrame { expected, frame }) => {
+                assert_eq!(expected, "array");
+                assert_eq!(frame, Frame::Simple("OK".to_string()));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+    assert_eq!(frame, Frame::Simple("OK".to_string()));
+}
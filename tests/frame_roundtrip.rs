@@ -0,0 +1,55 @@
+use redust::frame::Limits;
+use redust::Frame;
+
+use bytes::Bytes;
+use proptest::prelude::*;
+
+/// Frames which can be encoded, simple strings and errors can't hold a line break
+fn frame() -> impl Strategy<Value = Frame> {
+    let leaf = prop_oneof![
+        "[^\r\n]*".prop_map(Frame::Simple),
+        "[^\r\n]*".prop_map(Frame::Error),
+        any::<i64>().prop_map(Frame::Integer),
+        prop_oneof![Just(i64::MAX), Just(i64::MIN), Just(0)].prop_map(Frame::Integer),
+        any::<Vec<u8>>().prop_map(|data| Frame::Bulk(Bytes::from(data))),
+        Just(Frame::Bulk(Bytes::new())),
+        Just(Frame::Null),
+    ];
+    leaf.prop_recursive(8, 256, 10, |inner| {
+        prop::collection::vec(inner, 0..10).prop_map(Frame::Array)
+    })
+}
+
+fn roundtrip(frame: &Frame) -> Frame {
+    let encoded = frame.to_bytes();
+    let (parsed, len) = Frame::parse_partial(&encoded).unwrap();
+    assert_eq!(len, encoded.len());
+    parsed
+}
+
+proptest! {
+    #[test]
+    fn encode_then_parse(frame in frame()) {
+        prop_assert_eq!(roundtrip(&frame), frame);
+    }
+
+    #[test]
+    fn parse_ignores_trailing_data(frame in frame(), tail in any::<Vec<u8>>()) {
+        let mut encoded = frame.to_bytes().to_vec();
+        let len = encoded.len();
+        encoded.extend_from_slice(&tail);
+
+        let (parsed, parsed_len) = Frame::parse_partial(&encoded).unwrap();
+        prop_assert_eq!(parsed_len, len);
+        prop_assert_eq!(parsed, frame);
+    }
+}
+
+#[test]
+fn roundtrip_nested_to_max_depth() {
+    let mut frame = Frame::Array(vec![]);
+    for _ in 0..Limits::default().max_depth {
+        frame = Frame::Array(vec![Frame::Integer(i64::MIN), frame, Frame::Bulk(Bytes::new())]);
+    }
+    assert_eq!(roundtrip(&frame), frame);
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "serde")]
+
+use redust::Frame;
+
+use bytes::Bytes;
+
+fn roundtrip(frame: Frame) -> String {
+    let json = serde_json::to_string(&frame).unwrap();
+    let parsed: Frame = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, frame, "{}", json);
+    json
+}
+
+#[test]
+fn simple() {
+    assert_eq!(roundtrip(Frame::Simple("OK".to_string())), r#"{"simple":"OK"}"#);
+}
+
+#[test]
+fn error() {
+    let json = roundtrip(Frame::Error("ERR unknown command".to_string()));
+    assert_eq!(json, r#"{"error":"ERR unknown command"}"#);
+}
+
+#[test]
+fn integer() {
+    assert_eq!(roundtrip(Frame::Integer(42)), "42");
+    assert_eq!(roundtrip(Frame::Integer(-7)), "-7");
+    roundtrip(Frame::Integer(i64::MIN));
+    roundtrip(Frame::Integer(i64::MAX));
+}
+
+#[test]
+fn bulk() {
+    assert_eq!(roundtrip(Frame::Bulk(Bytes::from("hello"))), r#""hello""#);
+    assert_eq!(roundtrip(Frame::Bulk(Bytes::new())), r#""""#);
+}
+
+#[test]
+fn binary_bulk_reads_back_as_array() {
+    // JSON has no bytes type, the bytes are written as a sequence of integers
+    let json = serde_json::to_string(&Frame::Bulk(Bytes::from_static(&[0xff, 0x00]))).unwrap();
+    assert_eq!(json, "[255,0]");
+    let parsed: Frame = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, Frame::Array(vec![Frame::Integer(255), Frame::Integer(0)]));
+}
+
+#[test]
+fn null() {
+    assert_eq!(roundtrip(Frame::Null), "null");
+}
+
+#[test]
+fn nested_array() {
+    let frame = Frame::Array(vec![
+        Frame::Bulk(Bytes::from("message")),
+        Frame::Array(vec![Frame::Integer(-1), Frame::Null, Frame::Array(vec![])]),
+        Frame::Simple("OK".to_string()),
+        Frame::Error("ERR nested".to_string()),
+    ]);
+    let json = roundtrip(frame);
+    assert_eq!(json, r#"["message",[-1,null,[]],{"simple":"OK"},{"error":"ERR nested"}]"#);
+}
+
+#[test]
+fn unknown_map_entry_rejected() {
+    assert!(serde_json::from_str::<Frame>(r#"{"other":"OK"}"#).is_err());
+}
@@ -0,0 +1,398 @@
+use redust::{client, server, Frame};
+
+use bytes::Bytes;
+
+mod common;
+use common::start;
+
+const PALERMO: (f64, f64) = (13.361389, 38.115556);
+const CATANIA: (f64, f64) = (15.087269, 37.502669);
+
+const UNIT_ERROR: &str = "ERR unsupported unit provided. please use M, KM, FT, MI";
+const FROM_ERROR: &str =
+    "ERR exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH";
+const BY_ERROR: &str = "ERR exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH";
+
+fn positions(values: &[((f64, f64), &'static str)]) -> Vec<(f64, f64, Bytes)> {
+    values
+        .iter()
+        .map(|((longitude, latitude), member)| (*longitude, *latitude, Bytes::from(*member)))
+        .collect()
+}
+
+fn names(values: &[&'static str]) -> Vec<Bytes> {
+    values.iter().map(|value| Bytes::from(*value)).collect()
+}
+
+async fn sicily(client: &mut client::Client) {
+    let added = client
+        .geoadd(
+            "sicily",
+            positions(&[(PALERMO, "Palermo"), (CATANIA, "Catania")]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(added, 2);
+}
+
+#[tokio::test]
+async fn add_positions() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    sicily(&mut client).await;
+
+    // positions are members of a sorted set, scored by their geohash
+    assert_eq!(client.zcard("sicily").await.unwrap(), 2);
+    let score = client
+        .zscore("sicily", Bytes::from("Palermo"))
+        .await
+        .unwrap();
+    assert_eq!(score, Some(3479099956230698.0));
+    assert_eq!(
+        client.zrange("sicily", 0, -1).await.unwrap(),
+        names(&["Palermo", "Catania"])
+    );
+
+    // an existing member is moved but not counted
+    let added = client
+        .geoadd(
+            "sicily",
+            positions(&[((13.5, 38.0), "Palermo"), ((15.0, 37.0), "Edge")]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(added, 1);
+    let added: i64 = client
+        .command(vec![
+            "geoadd", "sicily", "nx", "ch", "1", "1", "Palermo", "2", "2", "Other",
+        ])
+        .await
+        .unwrap();
+    assert_eq!(added, 1);
+    let added: i64 = client
+        .command(vec![
+            "geoadd", "sicily", "xx", "ch", "1", "1", "Palermo", "2", "2", "New",
+        ])
+        .await
+        .unwrap();
+    assert_eq!(added, 1);
+    assert_eq!(client.zcard("sicily").await.unwrap(), 4);
+}
+
+#[tokio::test]
+async fn invalid_positions() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let errors = [
+        (
+            &["181", "0", "a"][..],
+            "ERR invalid longitude,latitude pair 181.000000,0.000000",
+        ),
+        (
+            &["0", "86", "a"][..],
+            "ERR invalid longitude,latitude pair 0.000000,86.000000",
+        ),
+        (&["east", "0", "a"][..], "ERR value is not a valid float"),
+        (
+            &["0", "0", "a", "1"][..],
+            "ERR wrong number of arguments for 'geoadd' command",
+        ),
+        (
+            &["nx", "xx", "0", "0", "a"][..],
+            "ERR XX and NX options at the same time are not compatible",
+        ),
+    ];
+    for (args, message) in errors.iter() {
+        let mut command = vec!["geoadd", "geo"];
+        command.extend_from_slice(args);
+        let err = client.command::<i64>(command).await.unwrap_err();
+        assert_eq!(err.to_string(), *message, "{:?}", args);
+    }
+    // nothing is added when a position is invalid
+    assert_eq!(client.exists(&["geo".to_string()]).await.unwrap(), 0);
+
+    client.set("string", "value").await.unwrap();
+    let err = client
+        .geoadd("string", positions(&[(PALERMO, "a")]))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+}
+
+#[tokio::test]
+async fn distance() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    sicily(&mut client).await;
+
+    let meters = client
+        .geodist("sicily", Bytes::from("Palermo"), Bytes::from("Catania"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!((meters - 166274.1516).abs() < 0.01, "{}", meters);
+
+    let kilometers: Option<f64> = client
+        .command(vec!["geodist", "sicily", "Palermo", "Catania", "KM"])
+        .await
+        .unwrap();
+    assert_eq!(kilometers, Some(166.2742));
+
+    let missing = client
+        .geodist("sicily", Bytes::from("Palermo"), Bytes::from("Rome"))
+        .await
+        .unwrap();
+    assert_eq!(missing, None);
+    let missing = client
+        .geodist("missing", Bytes::from("Palermo"), Bytes::from("Catania"))
+        .await
+        .unwrap();
+    assert_eq!(missing, None);
+
+    let errors = [
+        (
+            &["geodist", "sicily", "Palermo", "Catania", "yd"][..],
+            UNIT_ERROR,
+        ),
+        (
+            &["geodist", "sicily", "Palermo", "Catania", "km", "m"][..],
+            "ERR syntax error",
+        ),
+    ];
+    for (args, message) in errors.iter() {
+        let err = client
+            .command::<Option<f64>>(args.to_vec())
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), *message, "{:?}", args);
+    }
+}
+
+#[tokio::test]
+async fn search_area() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    sicily(&mut client).await;
+    client
+        .geoadd(
+            "sicily",
+            positions(&[
+                ((12.758489, 38.788135), "edge1"),
+                ((17.241510, 38.788135), "edge2"),
+            ]),
+        )
+        .await
+        .unwrap();
+
+    let found = client
+        .geosearch_radius("sicily", 15.0, 37.0, 200_000.0)
+        .await
+        .unwrap();
+    assert_eq!(found, names(&["Catania", "Palermo"]));
+    let found = client
+        .geosearch_radius("sicily", 15.0, 37.0, 100_000.0)
+        .await
+        .unwrap();
+    assert_eq!(found, names(&["Catania"]));
+    let found = client
+        .geosearch_radius("missing", 15.0, 37.0, 100_000.0)
+        .await
+        .unwrap();
+    assert_eq!(found, Vec::<Bytes>::new());
+
+    let cases: [(&[&str], &[&str]); 6] = [
+        (
+            &["fromlonlat", "15", "37", "bybox", "400", "400", "km", "asc"],
+            &["Catania", "Palermo", "edge2", "edge1"],
+        ),
+        (
+            &[
+                "fromlonlat",
+                "15",
+                "37",
+                "bybox",
+                "400",
+                "400",
+                "km",
+                "desc",
+            ],
+            &["edge1", "edge2", "Palermo", "Catania"],
+        ),
+        (
+            &["fromlonlat", "15", "37", "byradius", "200", "km", "desc"],
+            &["Palermo", "Catania"],
+        ),
+        // the nearest ones without an order
+        (
+            &[
+                "fromlonlat",
+                "15",
+                "37",
+                "byradius",
+                "400",
+                "km",
+                "count",
+                "2",
+            ],
+            &["Catania", "Palermo"],
+        ),
+        (
+            &["frommember", "Palermo", "byradius", "100", "km"],
+            &["Palermo"],
+        ),
+        (
+            &["frommember", "Catania", "byradius", "100", "mi", "asc"],
+            &["Catania"],
+        ),
+    ];
+    for (options, expected) in cases.iter() {
+        let mut args = vec!["geosearch", "sicily"];
+        args.extend_from_slice(options);
+        let found: Vec<Bytes> = client.command(args).await.unwrap();
+        assert_eq!(found, names(expected), "{:?}", options);
+    }
+
+    let found: Vec<Bytes> = client
+        .command(vec![
+            "geosearch",
+            "sicily",
+            "fromlonlat",
+            "15",
+            "37",
+            "byradius",
+            "400",
+            "km",
+            "count",
+            "1",
+            "any",
+        ])
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 1);
+}
+
+#[tokio::test]
+async fn search_with_details() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    sicily(&mut client).await;
+
+    let found: Vec<Frame> = client
+        .command(vec![
+            "geosearch",
+            "sicily",
+            "fromlonlat",
+            "15",
+            "37",
+            "byradius",
+            "200",
+            "km",
+            "asc",
+            "withcoord",
+            "withdist",
+            "withhash",
+        ])
+        .await
+        .unwrap();
+    let expected = [
+        ("Catania", "56.4413", 3479447370796909),
+        ("Palermo", "190.4424", 3479099956230698),
+    ];
+    assert_eq!(found.len(), expected.len());
+    for (item, (member, distance, hash)) in found.into_iter().zip(expected.iter()) {
+        let item = match item {
+            Frame::Array(item) => item,
+            frame => panic!("{:?}", frame),
+        };
+        assert_eq!(item[0], Frame::Bulk(Bytes::from(*member)));
+        assert_eq!(item[1], Frame::Bulk(Bytes::from(*distance)));
+        assert_eq!(item[2], Frame::Integer(*hash));
+        let coordinates = match &item[3] {
+            Frame::Array(coordinates) => coordinates,
+            frame => panic!("{:?}", frame),
+        };
+        let position = if *member == "Palermo" {
+            PALERMO
+        } else {
+            CATANIA
+        };
+        for (coordinate, given) in coordinates.iter().zip([position.0, position.1]) {
+            let coordinate: f64 = match coordinate {
+                Frame::Bulk(text) => std::str::from_utf8(text).unwrap().parse().unwrap(),
+                frame => panic!("{:?}", frame),
+            };
+            assert!(
+                (coordinate - given).abs() < 0.00001,
+                "{} {}",
+                coordinate,
+                given
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn search_errors() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    sicily(&mut client).await;
+
+    let err = client
+        .command::<Vec<Frame>>(vec!["geosearch", "sicily", "byradius", "10", "km", "asc"])
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), FROM_ERROR);
+    let err = client
+        .command::<Vec<Frame>>(vec![
+            "geosearch",
+            "sicily",
+            "frommember",
+            "Rome",
+            "byradius",
+            "1",
+            "m",
+        ])
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "ERR could not decode requested zset member"
+    );
+
+    let errors = [
+        (
+            &["fromlonlat", "15", "37", "byradius", "10", "km"][..],
+            FROM_ERROR,
+        ),
+        (&["asc", "count", "3"][..], BY_ERROR),
+        (
+            &["byradius", "10", "km", "bybox", "1", "1", "km"][..],
+            BY_ERROR,
+        ),
+        (
+            &["byradius", "-1", "km"][..],
+            "ERR radius cannot be negative",
+        ),
+        (
+            &["bybox", "1", "-1", "km"][..],
+            "ERR height or width cannot be negative",
+        ),
+        (&["byradius", "10", "yd"][..], UNIT_ERROR),
+        (
+            &["byradius", "10", "km", "count", "0"][..],
+            "ERR COUNT must be > 0",
+        ),
+        (
+            &["byradius", "10", "km", "any"][..],
+            "ERR the ANY argument requires COUNT argument",
+        ),
+        (&["byradius", "10", "km", "nearest"][..], "ERR syntax error"),
+    ];
+    for (options, message) in errors.iter() {
+        let mut args = vec!["geosearch", "sicily", "frommember", "Palermo"];
+        args.extend_from_slice(options);
+        let err = client.command::<Vec<Frame>>(args).await.unwrap_err();
+        assert_eq!(err.to_string(), *message, "{:?}", options);
+    }
+}
@@ -0,0 +1,346 @@
+use redust::{client, server, Frame};
+
+use bytes::Bytes;
+
+mod common;
+use common::start;
+
+fn fields(pairs: &[(&'static str, &'static str)]) -> Vec<(Bytes, Bytes)> {
+    pairs
+        .iter()
+        .map(|(field, value)| (Bytes::from(*field), Bytes::from(*value)))
+        .collect()
+}
+
+#[tokio::test]
+async fn set_get_and_delete_fields() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    assert_eq!(
+        client
+            .hset("hash", fields(&[("a", "1"), ("b", "2")]))
+            .await
+            .unwrap(),
+        2
+    );
+    // an existing field is updated but not counted
+    assert_eq!(
+        client
+            .hset("hash", fields(&[("b", "3"), ("c", "4")]))
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(client.hlen("hash").await.unwrap(), 3);
+
+    assert_eq!(
+        client.hget("hash", Bytes::from("b")).await.unwrap(),
+        Some(Bytes::from("3"))
+    );
+    assert_eq!(client.hget("hash", Bytes::from("z")).await.unwrap(), None);
+    assert_eq!(
+        client.hget("missing", Bytes::from("a")).await.unwrap(),
+        None
+    );
+
+    let mut all = client.hgetall("hash").await.unwrap();
+    all.sort();
+    assert_eq!(all, fields(&[("a", "1"), ("b", "3"), ("c", "4")]));
+    assert_eq!(client.hgetall("missing").await.unwrap(), vec![]);
+    assert_eq!(client.hlen("missing").await.unwrap(), 0);
+
+    let removed = client
+        .hdel("hash", vec![Bytes::from("a"), Bytes::from("z")])
+        .await
+        .unwrap();
+    assert_eq!(removed, 1);
+    assert_eq!(client.hlen("hash").await.unwrap(), 2);
+
+    // the hash is removed once empty
+    let removed = client
+        .hdel("hash", vec![Bytes::from("b"), Bytes::from("c")])
+        .await
+        .unwrap();
+    assert_eq!(removed, 2);
+    assert_eq!(client.exists(&["hash".to_string()]).await.unwrap(), 0);
+    assert_eq!(
+        client
+            .hdel("missing", vec![Bytes::from("a")])
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn field_without_value() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let err = client
+        .command::<i64>(vec!["hset", "hash", "a", "1", "b"])
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "ERR wrong number of arguments for 'hset' command"
+    );
+    assert_eq!(client.exists(&["hash".to_string()]).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn wrong_type() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.set("string", "value").await.unwrap();
+
+    let err = client
+        .hset("string", fields(&[("a", "1")]))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+    let err = client.hget("string", Bytes::from("a")).await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+    let err = client.hgetall("string").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+
+    client.hset("hash", fields(&[("a", "1")])).await.unwrap();
+    let err = client.get::<Option<Bytes>>("hash").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+    let err = client.lpop("hash").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+}
+
+#[tokio::test]
+async fn increment_fields() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    assert_eq!(
+        client.hincrby("hash", Bytes::from("n"), 5).await.unwrap(),
+        5
+    );
+    assert_eq!(
+        client.hincrby("hash", Bytes::from("n"), -8).await.unwrap(),
+        -3
+    );
+    assert_eq!(
+        client.hget("hash", Bytes::from("n")).await.unwrap(),
+        Some(Bytes::from("-3"))
+    );
+
+    client
+        .hset(
+            "hash",
+            fields(&[("max", "9223372036854775807"), ("text", "a")]),
+        )
+        .await
+        .unwrap();
+    let err = client
+        .hincrby("hash", Bytes::from("max"), 1)
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "ERR increment or decrement would overflow");
+    let err = client
+        .hincrby("hash", Bytes::from("text"), 1)
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "ERR hash value is not an integer");
+
+    assert_eq!(
+        client
+            .hincrbyfloat("hash", Bytes::from("f"), 10.5)
+            .await
+            .unwrap(),
+        10.5
+    );
+    assert_eq!(
+        client
+            .hincrbyfloat("hash", Bytes::from("f"), 0.25)
+            .await
+            .unwrap(),
+        10.75
+    );
+    // an integer field is a number as well
+    assert_eq!(
+        client
+            .hincrbyfloat("hash", Bytes::from("n"), 0.5)
+            .await
+            .unwrap(),
+        -2.5
+    );
+    let err = client
+        .hincrbyfloat("hash", Bytes::from("text"), 1.0)
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "ERR hash value is not a float");
+    let err = client
+        .hincrbyfloat("hash", Bytes::from("f"), f64::INFINITY)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "ERR increment would produce NaN or Infinity"
+    );
+    assert_eq!(
+        client.hget("hash", Bytes::from("f")).await.unwrap(),
+        Some(Bytes::from("10.75"))
+    );
+
+    // a failed increment doesn't create the hash
+    client.set("string", "value").await.unwrap();
+    let err = client
+        .hincrby("string", Bytes::from("n"), 1)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+    let err = client
+        .command::<i64>(vec!["hincrby", "other", "n", "one"])
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "ERR value is not an integer or out of range"
+    );
+    assert_eq!(client.exists(&["other".to_string()]).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn random_fields() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client
+        .hset("hash", fields(&[("a", "1"), ("b", "2"), ("c", "3")]))
+        .await
+        .unwrap();
+    let names = [Bytes::from("a"), Bytes::from("b"), Bytes::from("c")];
+
+    let field: Option<Bytes> = client.command(vec!["hrandfield", "hash"]).await.unwrap();
+    assert!(names.contains(&field.unwrap()));
+    let field: Option<Bytes> = client.command(vec!["hrandfield", "missing"]).await.unwrap();
+    assert_eq!(field, None);
+
+    // distinct fields, all of them at most
+    let mut picked = client.hrandfield("hash", 2).await.unwrap();
+    picked.sort();
+    picked.dedup();
+    assert_eq!(picked.len(), 2);
+    let mut picked = client.hrandfield("hash", 10).await.unwrap();
+    picked.sort();
+    assert_eq!(picked, names);
+
+    // possibly repeated fields
+    let picked = client.hrandfield("hash", -10).await.unwrap();
+    assert_eq!(picked.len(), 10);
+    assert!(picked.iter().all(|field| names.contains(field)));
+
+    let picked: Vec<Bytes> = client
+        .command(vec!["hrandfield", "hash", "-2", "withvalues"])
+        .await
+        .unwrap();
+    assert_eq!(picked.len(), 4);
+    for pair in picked.chunks(2) {
+        assert_eq!(
+            client.hget("hash", pair[0].clone()).await.unwrap(),
+            Some(pair[1].clone())
+        );
+    }
+
+    assert_eq!(
+        client.hrandfield("missing", 3).await.unwrap(),
+        Vec::<Bytes>::new()
+    );
+    let err = client
+        .command::<Vec<Bytes>>(vec!["hrandfield", "hash", "2", "values"])
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "ERR syntax error");
+}
+
+#[tokio::test]
+async fn scan_fields() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    let all: Vec<(Bytes, Bytes)> = (0..100)
+        .map(|i| {
+            (
+                Bytes::from(format!("field:{}", i)),
+                Bytes::from(i.to_string()),
+            )
+        })
+        .collect();
+    client.hset("hash", all.clone()).await.unwrap();
+
+    let mut scanned = vec![];
+    let mut cursor = 0;
+    let mut calls = 0;
+    loop {
+        let (next, batch) = client.hscan("hash", cursor, None, 7).await.unwrap();
+        assert!(batch.len() <= 7);
+        scanned.extend(batch);
+        calls += 1;
+        // changes in between don't hide the fields present for the whole iteration
+        client
+            .hset("hash", fields(&[("added", "x")]))
+            .await
+            .unwrap();
+        client
+            .hdel("hash", vec![Bytes::from("added")])
+            .await
+            .unwrap();
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+    assert!(calls >= 100 / 7);
+    scanned.retain(|(field, _)| field != "added");
+    scanned.sort();
+    scanned.dedup();
+    let mut expected = all;
+    expected.sort();
+    assert_eq!(scanned, expected);
+
+    let mut matching = vec![];
+    let mut cursor = 0;
+    loop {
+        let (next, batch) = client
+            .hscan("hash", cursor, Some("field:1?"), 20)
+            .await
+            .unwrap();
+        matching.extend(batch.into_iter().map(|(field, _)| field));
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+    matching.sort();
+    let expected: Vec<Bytes> = (10..20)
+        .map(|i| Bytes::from(format!("field:{}", i)))
+        .collect();
+    assert_eq!(matching, expected);
+
+    assert_eq!(
+        client.hscan("missing", 0, None, 10).await.unwrap(),
+        (0, vec![])
+    );
+    let errors = [
+        (&["hscan", "hash", "-1"][..], "ERR invalid cursor"),
+        (
+            &["hscan", "hash", "0", "count", "0"][..],
+            "ERR syntax error",
+        ),
+        (
+            &["hscan", "hash", "0", "limit", "5"][..],
+            "ERR syntax error",
+        ),
+    ];
+    for (args, message) in errors.iter() {
+        let err = client
+            .command::<Vec<Frame>>(args.to_vec())
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), *message, "{:?}", args);
+    }
+}
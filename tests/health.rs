@@ -0,0 +1,93 @@
+use redust::{client, server};
+
+use bytes::Bytes;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Start a server answering probes, returns it along with the address of the probe
+async fn start() -> (server::Server, SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let health = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let health_addr = health.local_addr().unwrap();
+    let server = server::Builder::new().health_check(health).start(listener).unwrap();
+    (server, health_addr)
+}
+
+async fn probe(addr: SocketAddr, request: &str) -> String {
+    let mut socket = TcpStream::connect(addr).await.unwrap();
+    socket.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    socket.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test]
+async fn ready() {
+    let (_server, addr) = start().await;
+
+    let response = probe(addr, "GET /healthz HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.ends_with("\r\n\r\nok\n"), "{}", response);
+
+    let response = probe(addr, "GET /other HTTP/1.1\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", response);
+    let response = probe(addr, "POST /healthz HTTP/1.1\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"), "{}", response);
+}
+
+#[tokio::test]
+async fn concurrent_probes() {
+    let (_server, addr) = start().await;
+
+    let probes: Vec<_> = (0..32)
+        .map(|_| tokio::spawn(probe(addr, "GET /healthz HTTP/1.1\r\n\r\n")))
+        .collect();
+    for probe in probes {
+        let response = probe.await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    }
+}
+
+#[tokio::test]
+async fn incomplete_request_dropped() {
+    let (_server, addr) = start().await;
+
+    let mut idle = TcpStream::connect(addr).await.unwrap();
+    idle.write_all(b"GET /healthz HTTP/1.1\r\n").await.unwrap();
+
+    // other probes are still answered meanwhile
+    let response = probe(addr, "GET /healthz HTTP/1.1\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+
+    let mut buf = vec![];
+    let read = tokio::time::timeout(Duration::from_secs(5), idle.read_to_end(&mut buf)).await;
+    assert!(matches!(read, Ok(Ok(0))), "{:?}", read);
+}
+
+#[tokio::test]
+async fn pubsub_metrics() {
+    let (server, addr) = start().await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let subscriber = client::connect(server.local_addr()).await.unwrap();
+    let mut subscriber = subscriber.subscribe(vec!["news \"daily\"".to_string()]).await.unwrap();
+    for message in ["first", "second"] {
+        assert_eq!(client.publish("news \"daily\"", Bytes::from(message)).await.unwrap(), 1);
+        subscriber.next_message().await.unwrap().unwrap();
+    }
+
+    let response = probe(addr, "GET /metrics HTTP/1.1\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    for line in [
+        "# TYPE redust_pubsub_channel_published_total counter\n",
+        "redust_pubsub_channel_subscribers{channel=\"news \\\"daily\\\"\"} 1\n",
+        "redust_pubsub_channel_published_total{channel=\"news \\\"daily\\\"\"} 2\n",
+        "redust_pubsub_channel_delivered_total{channel=\"news \\\"daily\\\"\"} 2\n",
+        "redust_pubsub_channel_subscribes_total{channel=\"news \\\"daily\\\"\"} 1\n",
+        "redust_pubsub_published_total 2\n",
+    ] {
+        assert!(response.contains(line), "{} not in {}", line, response);
+    }
+}
@@ -0,0 +1,66 @@
+#![cfg(feature = "http-gateway")]
+
+use redust::{client, server};
+
+use bytes::Bytes;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+mod common;
+use common::start;
+
+/// Send a request on a new connection, returns the status code and body of the response
+async fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let status = response[9..12].parse().unwrap();
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    (status, body.to_string())
+}
+
+#[tokio::test]
+async fn keys_over_http() {
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = http.local_addr().unwrap();
+    let server = start(server::Builder::new().http_gateway(http)).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    assert_eq!(request(addr, "GET", "/keys/greeting", "").await.0, 404);
+    assert_eq!(request(addr, "PUT", "/keys/greeting", "hello").await.0, 204);
+    assert_eq!(request(addr, "GET", "/keys/greeting", "").await, (200, "hello".to_string()));
+
+    // the same key space as the RESP clients, keys are percent-decoded
+    let value: Option<Bytes> = client.get("greeting").await.unwrap();
+    assert_eq!(value, Some(Bytes::from("hello")));
+    client.set("a key", "spaced").await.unwrap();
+    assert_eq!(request(addr, "GET", "/keys/a%20key", "").await, (200, "spaced".to_string()));
+
+    assert_eq!(request(addr, "DELETE", "/keys/greeting", "").await.0, 204);
+    assert_eq!(request(addr, "DELETE", "/keys/greeting", "").await.0, 404);
+    assert_eq!(client.get::<Option<Bytes>>("greeting").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn publish_over_http() {
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = http.local_addr().unwrap();
+    let server = start(server::Builder::new().http_gateway(http)).await;
+
+    let subscriber = client::connect(server.local_addr()).await.unwrap();
+    let mut subscriber = subscriber.subscribe(vec!["news".to_string()]).await.unwrap();
+
+    assert_eq!(request(addr, "POST", "/publish/news", "extra").await, (200, "1\n".to_string()));
+    let message = subscriber.next_message().await.unwrap().unwrap();
+    assert_eq!(message.content, Bytes::from("extra"));
+}
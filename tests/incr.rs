@@ -0,0 +1,28 @@
+use redust::{client, server};
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn incr_negative_value() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("counter", "-5").await.unwrap();
+    assert_eq!(client.incr("counter").await.unwrap(), -4);
+    assert_eq!(client.incr("missing").await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn incr_overflow() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("counter", i64::MAX.to_string()).await.unwrap();
+    let err = client.incr("counter").await.unwrap_err();
+    assert_eq!(err.to_string(), "ERR increment or decrement would overflow");
+
+    // the value is left as it was
+    let value: Option<String> = client.get("counter").await.unwrap();
+    assert_eq!(value, Some(i64::MAX.to_string()));
+}
@@ -0,0 +1,101 @@
+use redust::{client, server};
+
+use std::time::{Duration, SystemTime};
+use tokio::time::sleep;
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn percentiles_by_command() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let latency: Option<u64> = client.command(("latency", "percentile", "ping", "50")).await.unwrap();
+    assert_eq!(latency, None);
+
+    for _ in 0..10 {
+        client.ping(None).await.unwrap();
+    }
+    let latency: Option<u64> = client.command(("latency", "percentile", "ping", "50")).await.unwrap();
+    assert!(latency.is_some());
+    let latency: Option<u64> = client.command(("latency", "percentile", "PING", "99")).await.unwrap();
+    assert!(latency.is_some());
+    let latency: Option<u64> = client.command(("latency", "percentile", "get", "50")).await.unwrap();
+    assert_eq!(latency, None);
+    let latency: Option<u64> = client.command(("latency", "percentile", "nope", "50")).await.unwrap();
+    assert_eq!(latency, None);
+}
+
+#[tokio::test]
+async fn reset() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.ping(None).await.unwrap();
+    client.set("key", "value").await.unwrap();
+
+    let reset: u64 = client.command(("latency", "reset", "ping", "nope")).await.unwrap();
+    assert_eq!(reset, 1);
+    let latency: Option<u64> = client.command(("latency", "percentile", "ping", "50")).await.unwrap();
+    assert_eq!(latency, None);
+
+    // the set and the latency commands which ran since
+    let reset: u64 = client.command(("latency", "reset")).await.unwrap();
+    assert_eq!(reset, 2);
+    let latency: Option<u64> = client.command(("latency", "percentile", "set", "50")).await.unwrap();
+    assert_eq!(latency, None);
+}
+
+#[tokio::test]
+async fn untracked() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.config_set("latency-tracking", "no").await.unwrap();
+    client.ping(None).await.unwrap();
+    let latency: Option<u64> = client.command(("latency", "percentile", "ping", "50")).await.unwrap();
+    assert_eq!(latency, None);
+}
+
+#[tokio::test]
+async fn spikes_history() {
+    let server = start(server::Builder::new().enable_debug_command(true)).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    // no spike is recorded without a threshold
+    let _: String = client.command(("debug", "sleep", "0.1")).await.unwrap();
+    assert!(client.latency_latest().await.unwrap().is_empty());
+
+    client.config_set("latency-monitor-threshold", "50").await.unwrap();
+    let started = SystemTime::now() - Duration::from_secs(1);
+    let _: String = client.command(("debug", "sleep", "0.1")).await.unwrap();
+    let _: String = client.command(("debug", "sleep", "0.2")).await.unwrap();
+    client.ping(None).await.unwrap();
+
+    // one sample per second, the spikes of the same second are merged
+    let history = client.latency_history("debug").await.unwrap();
+    assert!(!history.is_empty() && history.len() <= 2, "{:?}", history);
+    for (time, latency) in &history {
+        assert!(*time >= started);
+        assert!(*latency >= Duration::from_millis(100), "{:?}", latency);
+    }
+    assert!(client.latency_history("ping").await.unwrap().is_empty());
+
+    sleep(Duration::from_secs(1)).await;
+    let _: String = client.command(("debug", "sleep", "0.1")).await.unwrap();
+    let later = client.latency_history("debug").await.unwrap();
+    assert_eq!(later.len(), history.len() + 1);
+    assert!(later[later.len() - 1].0 > later[later.len() - 2].0);
+
+    let latest = client.latency_latest().await.unwrap();
+    assert_eq!(latest.len(), 1);
+    assert_eq!(latest[0].command, "debug");
+    assert!(latest[0].max >= Duration::from_millis(200), "{:?}", latest[0]);
+
+    let info = client.info(Some("latencystats")).await.unwrap();
+    assert!(info.contains("latency_percentiles_usec_ping:"), "{}", info);
+
+    let _: u64 = client.command(("latency", "reset", "debug")).await.unwrap();
+    assert!(client.latency_history("debug").await.unwrap().is_empty());
+}
@@ -0,0 +1,269 @@
+use redust::{client, server};
+
+use bytes::Bytes;
+
+mod common;
+use common::start;
+
+fn elements(values: &[&'static str]) -> Vec<Bytes> {
+    values.iter().map(|value| Bytes::from(*value)).collect()
+}
+
+#[tokio::test]
+async fn push_and_pop_at_both_ends() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    assert_eq!(
+        client.rpush("list", elements(&["c", "d"])).await.unwrap(),
+        2
+    );
+    // pushed one after the other, the last one ends up first
+    assert_eq!(
+        client.lpush("list", elements(&["b", "a"])).await.unwrap(),
+        4
+    );
+    assert_eq!(
+        client.lrange("list", 0, -1).await.unwrap(),
+        elements(&["a", "b", "c", "d"])
+    );
+    assert_eq!(client.llen("list").await.unwrap(), 4);
+
+    assert_eq!(client.lpop("list").await.unwrap(), Some(Bytes::from("a")));
+    assert_eq!(client.rpop("list").await.unwrap(), Some(Bytes::from("d")));
+
+    let popped: Vec<Bytes> = client.command(vec!["rpop", "list", "5"]).await.unwrap();
+    assert_eq!(popped, elements(&["c", "b"]));
+
+    // the list is removed once empty
+    assert_eq!(client.exists(&["list".to_string()]).await.unwrap(), 0);
+    assert_eq!(client.lpop("list").await.unwrap(), None);
+    let popped: Option<Vec<Bytes>> = client.command(vec!["lpop", "list", "2"]).await.unwrap();
+    assert_eq!(popped, None);
+}
+
+#[tokio::test]
+async fn range_indexes() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client
+        .rpush("list", elements(&["a", "b", "c", "d", "e"]))
+        .await
+        .unwrap();
+
+    let cases: [(i64, i64, &[&str]); 6] = [
+        (0, 1, &["a", "b"]),
+        (-2, -1, &["d", "e"]),
+        (1, -2, &["b", "c", "d"]),
+        (-100, 100, &["a", "b", "c", "d", "e"]),
+        (3, 1, &[]),
+        (5, 10, &[]),
+    ];
+    for (start, stop, expected) in cases.iter() {
+        let range = client.lrange("list", *start, *stop).await.unwrap();
+        assert_eq!(range, elements(expected), "{} {}", start, stop);
+    }
+    assert_eq!(
+        client.lrange("missing", 0, -1).await.unwrap(),
+        Vec::<Bytes>::new()
+    );
+}
+
+#[tokio::test]
+async fn wrong_type() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.set("string", "value").await.unwrap();
+
+    let err = client.rpush("string", elements(&["a"])).await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+    let err = client.lpop("string").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+    let err = client.llen("string").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+
+    client.rpush("list", elements(&["a"])).await.unwrap();
+    let err = client.get::<Option<Bytes>>("list").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+
+    let err = client
+        .command::<Vec<Bytes>>(vec!["lpop", "list", "-1"])
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "ERR value is out of range, must be positive"
+    );
+}
+
+#[tokio::test]
+async fn positions() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client
+        .rpush("list", elements(&["a", "b", "c", "b", "a", "b"]))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        client.lpos("list", Bytes::from("b")).await.unwrap(),
+        Some(1)
+    );
+    assert_eq!(client.lpos("list", Bytes::from("z")).await.unwrap(), None);
+    assert_eq!(
+        client.lpos("missing", Bytes::from("b")).await.unwrap(),
+        None
+    );
+
+    let cases: [(&[&str], &[i64]); 7] = [
+        (&["rank", "2"], &[3]),
+        (&["rank", "-1"], &[5]),
+        (&["count", "0"], &[1, 3, 5]),
+        (&["count", "2", "rank", "-1"], &[5, 3]),
+        (&["count", "0", "maxlen", "4"], &[1, 3]),
+        (&["count", "0", "rank", "-2", "maxlen", "3"], &[3]),
+        (&["rank", "4"], &[]),
+    ];
+    for (options, expected) in cases.iter() {
+        let mut args = vec!["lpos", "list", "b"];
+        args.extend(options.iter());
+        let reply: Option<Vec<i64>> = if options.contains(&"count") {
+            client.command(args).await.unwrap()
+        } else {
+            client
+                .command::<Option<i64>>(args)
+                .await
+                .unwrap()
+                .map(|index| vec![index])
+        };
+        assert_eq!(reply.unwrap_or_default(), *expected, "{:?}", options);
+    }
+
+    let errors = [
+        (&["rank", "0"][..], "ERR RANK can't be zero"),
+        (&["count", "-1"][..], "ERR COUNT can't be negative"),
+        (&["maxlen", "-1"][..], "ERR MAXLEN can't be negative"),
+        (&["first", "1"][..], "ERR syntax error"),
+    ];
+    for (options, message) in errors.iter() {
+        let mut args = vec!["lpos", "list", "b"];
+        args.extend(options.iter());
+        let err = client.command::<Option<i64>>(args).await.unwrap_err();
+        assert!(err.to_string().starts_with(message), "{}", err);
+    }
+}
+
+#[tokio::test]
+async fn insert_around_a_pivot() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client
+        .rpush("list", elements(&["a", "c", "c"]))
+        .await
+        .unwrap();
+
+    let len = client
+        .linsert_before("list", Bytes::from("c"), Bytes::from("b"))
+        .await
+        .unwrap();
+    assert_eq!(len, Some(4));
+    let len = client
+        .linsert_after("list", Bytes::from("c"), Bytes::from("d"))
+        .await
+        .unwrap();
+    assert_eq!(len, Some(5));
+    assert_eq!(
+        client.lrange("list", 0, -1).await.unwrap(),
+        elements(&["a", "b", "c", "d", "c"])
+    );
+
+    let len = client
+        .linsert_after("list", Bytes::from("z"), Bytes::from("y"))
+        .await
+        .unwrap();
+    assert_eq!(len, None);
+    let len = client
+        .linsert_after("missing", Bytes::from("a"), Bytes::from("b"))
+        .await
+        .unwrap();
+    assert_eq!(len, Some(0));
+    assert_eq!(client.exists(&["missing".to_string()]).await.unwrap(), 0);
+
+    let err = client
+        .command::<i64>(vec!["linsert", "list", "between", "a", "b"])
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "ERR syntax error");
+}
+
+#[tokio::test]
+async fn remove_occurrences() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client
+        .rpush("list", elements(&["a", "x", "b", "x", "c", "x", "d", "x"]))
+        .await
+        .unwrap();
+
+    assert_eq!(client.lrem("list", 1, Bytes::from("x")).await.unwrap(), 1);
+    assert_eq!(client.lrem("list", -2, Bytes::from("x")).await.unwrap(), 2);
+    assert_eq!(
+        client.lrange("list", 0, -1).await.unwrap(),
+        elements(&["a", "b", "x", "c", "d"])
+    );
+    assert_eq!(client.lrem("list", 0, Bytes::from("z")).await.unwrap(), 0);
+    assert_eq!(client.lrem("list", 0, Bytes::from("x")).await.unwrap(), 1);
+    assert_eq!(
+        client.lrange("list", 0, -1).await.unwrap(),
+        elements(&["a", "b", "c", "d"])
+    );
+
+    // the list is removed once empty
+    client.rpush("single", elements(&["x", "x"])).await.unwrap();
+    assert_eq!(client.lrem("single", 0, Bytes::from("x")).await.unwrap(), 2);
+    assert_eq!(client.exists(&["single".to_string()]).await.unwrap(), 0);
+    assert_eq!(
+        client.lrem("missing", 0, Bytes::from("x")).await.unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn set_by_index() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client
+        .rpush("list", elements(&["a", "b", "c"]))
+        .await
+        .unwrap();
+
+    client.lset("list", 0, Bytes::from("A")).await.unwrap();
+    client.lset("list", -1, Bytes::from("C")).await.unwrap();
+    assert_eq!(
+        client.lrange("list", 0, -1).await.unwrap(),
+        elements(&["A", "b", "C"])
+    );
+
+    for index in [3, -4].iter() {
+        let err = client
+            .lset("list", *index, Bytes::from("z"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "ERR index out of range", "{}", index);
+    }
+    let err = client
+        .lset("missing", 0, Bytes::from("z"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "ERR no such key");
+    assert_eq!(client.exists(&["missing".to_string()]).await.unwrap(), 0);
+
+    client.set("string", "value").await.unwrap();
+    let err = client
+        .lset("string", 0, Bytes::from("z"))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+    let err = client.lpos("string", Bytes::from("v")).await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+}
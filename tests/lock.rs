@@ -0,0 +1,99 @@
+use redust::{client, server};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn lock_held_by_one_token() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    let ttl = Duration::from_secs(60);
+
+    assert!(client.lock("job", Bytes::from("alice"), ttl).await.unwrap());
+    // someone else can't take it, nor release it
+    assert!(!client.lock("job", Bytes::from("bob"), ttl).await.unwrap());
+    assert!(!client.unlock("job", Bytes::from("bob")).await.unwrap());
+
+    // the holder extends it, unless NX is given
+    assert!(client.lock("job", Bytes::from("alice"), ttl).await.unwrap());
+    assert!(!client.lock_nx("job", Bytes::from("alice"), ttl).await.unwrap());
+
+    assert!(client.unlock("job", Bytes::from("alice")).await.unwrap());
+    assert!(!client.unlock("job", Bytes::from("alice")).await.unwrap());
+    assert!(client.lock_nx("job", Bytes::from("bob"), ttl).await.unwrap());
+}
+
+#[tokio::test]
+async fn plain_value_is_not_a_lock() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("job", "alice").await.unwrap();
+    assert!(!client.unlock("job", Bytes::from("alice")).await.unwrap());
+    assert_eq!(client.get::<Option<Bytes>>("job").await.unwrap(), Some(Bytes::from("alice")));
+}
+
+#[tokio::test]
+async fn lock_is_not_confirmed() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    let ttl = Duration::from_secs(60);
+
+    assert!(client.lock("job", Bytes::from("alice"), ttl).await.unwrap());
+    // CONFIRM leaves the lock alone, the holder still releases it
+    assert!(!client.confirm("job").await.unwrap());
+    assert!(!client.lock("job", Bytes::from("bob"), ttl).await.unwrap());
+    assert!(client.unlock("job", Bytes::from("alice")).await.unwrap());
+}
+
+#[tokio::test]
+async fn expired_lock_released_and_published() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let subscriber = client::connect(server.local_addr()).await.unwrap();
+    let mut subscriber = subscriber
+        .subscribe(vec!["__lock__:expired:job".to_string()])
+        .await
+        .unwrap();
+
+    assert!(client.lock("job", Bytes::from("alice"), Duration::from_millis(100)).await.unwrap());
+    let message = timeout(Duration::from_secs(2), subscriber.next_message())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(message.content, Bytes::from("alice"));
+
+    // the holder lost it, and someone else can take it
+    assert!(!client.unlock("job", Bytes::from("alice")).await.unwrap());
+    assert!(client.lock_nx("job", Bytes::from("bob"), Duration::from_secs(60)).await.unwrap());
+
+    // extending keeps the lock past its first expiration
+    assert!(client.lock("other", Bytes::from("carol"), Duration::from_millis(200)).await.unwrap());
+    assert!(client.lock("other", Bytes::from("carol"), Duration::from_secs(60)).await.unwrap());
+    sleep(Duration::from_millis(400)).await;
+    assert!(client.unlock("other", Bytes::from("carol")).await.unwrap());
+}
+
+#[tokio::test]
+async fn invalid_ttls() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let huge = (u64::MAX / 2).to_string();
+    for ttl in ["0", &huge[..]].iter() {
+        let err = client
+            .command::<i64>(vec!["lock", "job", "alice", ttl])
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "ERR invalid expire time in 'lock' command", "{}", ttl);
+    }
+
+    // nothing was locked
+    assert!(client.lock_nx("job", Bytes::from("bob"), Duration::from_secs(60)).await.unwrap());
+}
@@ -0,0 +1,47 @@
+use redust::{client, server};
+
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout};
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn excess_clients_rejected() {
+    let builder = server::Builder::new().max_clients(1).reject_excess_clients(true);
+    let server = start(builder).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.ping(None).await.unwrap();
+
+    // the excess connection is told why before being closed
+    let mut excess = TcpStream::connect(server.local_addr()).await.unwrap();
+    let mut reply = vec![];
+    timeout(Duration::from_secs(1), excess.read_to_end(&mut reply)).await.unwrap().unwrap();
+    assert_eq!(reply, b"-ERR max clients reached\r\n");
+
+    let info = client.info(Some("stats")).await.unwrap();
+    assert!(info.contains("rejected_connections:1\r\n"), "{}", info);
+
+    // the slot is free once the client leaves
+    drop(client);
+    sleep(Duration::from_millis(50)).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.ping(None).await.unwrap();
+}
+
+#[tokio::test]
+async fn excess_clients_wait_for_a_slot() {
+    let server = start(server::Builder::new().max_clients(1)).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.ping(None).await.unwrap();
+
+    let mut waiting = client::connect(server.local_addr()).await.unwrap();
+    let waiting = tokio::spawn(async move { waiting.ping(None).await });
+    sleep(Duration::from_millis(100)).await;
+    assert!(!waiting.is_finished());
+
+    drop(client);
+    timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap().unwrap();
+}
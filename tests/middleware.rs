@@ -0,0 +1,84 @@
+use redust::middleware::{BoxFuture, Context, Next};
+use redust::{client, server, Command, Frame, Layer};
+
+use bytes::Bytes;
+use std::sync::{Arc, Mutex};
+
+mod common;
+use common::start;
+
+/// Records the commands going through it, before and after the rest of the chain
+#[derive(Debug)]
+struct Record {
+    name: &'static str,
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl Layer for Record {
+    fn call<'a>(
+        &'a self,
+        cmd: Command,
+        cx: &'a mut Context<'_>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, redust::Result<()>> {
+        Box::pin(async move {
+            let command = cmd.get_name().to_string();
+            self.calls.lock().unwrap().push(format!("{} > {}", self.name, command));
+            let res = next.run(cmd, cx).await;
+            self.calls.lock().unwrap().push(format!("{} < {}", self.name, command));
+            res
+        })
+    }
+}
+
+/// Answers `DEL` with an error instead of running it
+#[derive(Debug)]
+struct DenyDel;
+
+impl Layer for DenyDel {
+    fn call<'a>(
+        &'a self,
+        cmd: Command,
+        cx: &'a mut Context<'_>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, redust::Result<()>> {
+        Box::pin(async move {
+            if cmd.get_name() == "del" {
+                return cx.reply(&Frame::error("ERR DEL is denied")).await;
+            }
+            next.run(cmd, cx).await
+        })
+    }
+}
+
+#[tokio::test]
+async fn layers_run_outermost_first() {
+    let calls = Arc::new(Mutex::new(vec![]));
+    let layer = |name| Arc::new(Record { name, calls: calls.clone() }) as Arc<dyn Layer>;
+    let builder = server::Builder::new().layer(layer("outer")).layer(layer("inner"));
+    let server = start(builder).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("key", "value").await.unwrap();
+    assert_eq!(
+        *calls.lock().unwrap(),
+        ["outer > set", "inner > set", "inner < set", "outer < set"]
+    );
+}
+
+#[tokio::test]
+async fn layer_answers_without_running_the_command() {
+    let calls = Arc::new(Mutex::new(vec![]));
+    let record = Arc::new(Record { name: "inner", calls: calls.clone() });
+    let builder = server::Builder::new().layer(Arc::new(DenyDel)).layer(record);
+    let server = start(builder).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("key", "value").await.unwrap();
+    let err = client.del(&["key".to_string()]).await.unwrap_err();
+    assert_eq!(err.to_string(), "ERR DEL is denied");
+    assert_eq!(client.get::<Option<Bytes>>("key").await.unwrap(), Some(Bytes::from("value")));
+
+    // the inner layers don't see the denied command
+    assert!(!calls.lock().unwrap().iter().any(|call| call.ends_with("del")));
+}
@@ -0,0 +1,74 @@
+use redust::{client, server};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::time::sleep;
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn repeated_reads_hit_the_cache() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    let mut other = client::connect(server.local_addr()).await.unwrap();
+    client.enable_near_cache(16, Duration::from_secs(60));
+
+    client.set("key", "first").await.unwrap();
+    assert_eq!(client.get::<Option<Bytes>>("key").await.unwrap(), Some(Bytes::from("first")));
+    let stats = client.near_cache_stats().unwrap();
+    assert_eq!((stats.hits, stats.misses, stats.len), (0, 1, 1));
+
+    // a write by another client isn't seen while the value is cached
+    other.set("key", "second").await.unwrap();
+    assert_eq!(client.get::<Option<Bytes>>("key").await.unwrap(), Some(Bytes::from("first")));
+    let stats = client.near_cache_stats().unwrap();
+    assert_eq!((stats.hits, stats.misses), (1, 1));
+
+    // missing keys aren't cached
+    assert_eq!(client.get::<Option<Bytes>>("missing").await.unwrap(), None);
+    assert_eq!(client.near_cache_stats().unwrap().len, 1);
+}
+
+#[tokio::test]
+async fn cached_values_expire() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    let mut other = client::connect(server.local_addr()).await.unwrap();
+    client.enable_near_cache(16, Duration::from_millis(200));
+
+    // kept no longer than the key lives on the server
+    client.set_expires("short", "value", Duration::from_millis(100)).await.unwrap();
+    client.get::<Option<Bytes>>("short").await.unwrap();
+    sleep(Duration::from_millis(150)).await;
+    assert_eq!(client.get::<Option<Bytes>>("short").await.unwrap(), None);
+
+    // nor longer than the maximum age
+    client.set("key", "first").await.unwrap();
+    client.get::<Option<Bytes>>("key").await.unwrap();
+    other.set("key", "second").await.unwrap();
+    sleep(Duration::from_millis(250)).await;
+    assert_eq!(client.get::<Option<Bytes>>("key").await.unwrap(), Some(Bytes::from("second")));
+}
+
+#[tokio::test]
+async fn own_writes_invalidate() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.enable_near_cache(16, Duration::from_secs(60));
+
+    client.set("key", "first").await.unwrap();
+    client.get::<Option<Bytes>>("key").await.unwrap();
+    client.set("key", "second").await.unwrap();
+    assert_eq!(client.get::<Option<Bytes>>("key").await.unwrap(), Some(Bytes::from("second")));
+
+    client.del(&["key".to_string()]).await.unwrap();
+    assert_eq!(client.get::<Option<Bytes>>("key").await.unwrap(), None);
+
+    // raw commands may write any key, the whole cache is dropped
+    client.set("key", "third").await.unwrap();
+    client.get::<Option<Bytes>>("key").await.unwrap();
+    let _: String = client.command(vec!["set", "key", "fourth"]).await.unwrap();
+    assert_eq!(client.get::<Option<Bytes>>("key").await.unwrap(), Some(Bytes::from("fourth")));
+    assert_eq!(client.near_cache_stats().unwrap().hits, 0);
+}
@@ -0,0 +1,69 @@
+use redust::{client, server};
+
+use std::io;
+use std::sync::{Arc, Mutex};
+use tracing::Level;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+
+mod common;
+use common::start;
+
+/// Log output kept in memory
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Captured {
+    fn logs(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl io::Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Captured {
+    type Writer = Captured;
+
+    fn make_writer(&'a self) -> Captured {
+        self.clone()
+    }
+}
+
+#[tokio::test]
+async fn frames_dumped_while_enabled() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    // the test runtime is single threaded, the server logs to this subscriber too
+    let captured = Captured::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(captured.clone()).with_ansi(false))
+        .with(Targets::new().with_target("redust::protocol", Level::INFO));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    client.set("before", "value").await.unwrap();
+    assert!(!captured.logs().contains("before"), "{}", captured.logs());
+
+    // applies from the request following CONFIG SET
+    client.config_set("protocol-dump", "yes").await.unwrap();
+    client.set("dumped", "value").await.unwrap();
+    let logs = captured.logs();
+    let received = logs.lines().find(|line| line.contains("<<")).unwrap();
+    assert!(logs.contains("\"dumped\""), "{}", logs);
+    assert!(received.contains("redust::protocol"), "{}", received);
+    assert!(logs.lines().any(|line| line.contains(">>") && line.contains("OK")), "{}", logs);
+
+    client.config_set("protocol-dump", "no").await.unwrap();
+    client.set("after", "value").await.unwrap();
+    assert!(!captured.logs().contains("after"), "{}", captured.logs());
+}
@@ -0,0 +1,199 @@
+use redust::{Connection, Frame};
+
+use std::net::SocketAddr;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+
+/// Commands received by the fake upstream server, with the connection each arrived on
+type Received = Arc<Mutex<Vec<(usize, String)>>>;
+
+/// Start an upstream server replying `OK` to every command and recording them
+async fn fake_upstream() -> (SocketAddr, Received) {
+    fake_server(Frame::Simple("OK".to_string())).await
+}
+
+/// Start a server replying `reply` to every command and recording them
+async fn fake_server(reply: Frame) -> (SocketAddr, Received) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let received = Received::default();
+
+    let log = received.clone();
+    tokio::spawn(async move {
+        for id in 0.. {
+            let (socket, _) = listener.accept().await.unwrap();
+            let log = log.clone();
+            let reply = reply.clone();
+            tokio::spawn(async move {
+                let mut conn = Connection::new(socket);
+                while let Ok(Some(frame)) = conn.read_frame().await {
+                    log.lock().unwrap().push((id, command(&frame)));
+                    conn.write_frame(&reply).await.unwrap();
+                }
+            });
+        }
+    });
+    (addr, received)
+}
+
+/// The proxy process, killed on drop
+struct Proxy(Child);
+
+impl Drop for Proxy {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+async fn start_proxy(upstream: SocketAddr) -> (Proxy, Connection) {
+    start_proxy_with(upstream, &[]).await
+}
+
+/// Start the proxy with `args` on top of the ones of `start_proxy`
+async fn start_proxy_with(upstream: SocketAddr, args: &[&str]) -> (Proxy, Connection) {
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let child = Command::new(env!("CARGO_BIN_EXE_redust-proxy"))
+        .args(["--port", &port.to_string(), "--upstream", &upstream.to_string(), "--pool-size", "1"])
+        .args(args)
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let proxy = Proxy(child);
+
+    for _ in 0..100 {
+        if let Ok(socket) = TcpStream::connect(("127.0.0.1", port)).await {
+            return (proxy, Connection::new(socket));
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("the proxy didn't start");
+}
+
+fn command(frame: &Frame) -> String {
+    match frame {
+        Frame::Array(parts) => parts
+            .iter()
+            .map(|part| match part {
+                Frame::Bulk(arg) => String::from_utf8_lossy(arg).to_lowercase(),
+                other => other.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+        other => other.to_string(),
+    }
+}
+
+async fn call(conn: &mut Connection, args: &[&str]) -> Frame {
+    let frame = Frame::Array(args.iter().map(|arg| Frame::Bulk(arg.to_string().into())).collect());
+    conn.write_frame(&frame).await.unwrap();
+    conn.read_frame().await.unwrap().unwrap()
+}
+
+/// Connection each of `commands` arrived on upstream
+fn connections(received: &Received, commands: &[&str]) -> Vec<usize> {
+    let received = received.lock().unwrap();
+    commands
+        .iter()
+        .map(|command| received.iter().find(|(_, c)| c == command).unwrap().0)
+        .collect()
+}
+
+#[tokio::test]
+async fn watch_pins_the_client_until_exec() {
+    let (upstream, received) = fake_upstream().await;
+    let (_proxy, mut client) = start_proxy(upstream).await;
+
+    call(&mut client, &["WATCH", "k"]).await;
+    call(&mut client, &["GET", "k"]).await;
+    call(&mut client, &["MULTI"]).await;
+    call(&mut client, &["UNWATCH"]).await;
+    call(&mut client, &["SET", "k", "1"]).await;
+    call(&mut client, &["EXEC"]).await;
+    call(&mut client, &["GET", "after"]).await;
+
+    let pinned = connections(&received, &["watch k", "get k", "multi", "unwatch", "set k 1", "exec"]);
+    assert!(pinned.iter().all(|&conn| conn == pinned[0]), "{:?}", pinned);
+    assert_ne!(connections(&received, &["get after"])[0], pinned[0]);
+}
+
+#[tokio::test]
+async fn unwatch_releases_the_client() {
+    let (upstream, received) = fake_upstream().await;
+    let (_proxy, mut client) = start_proxy(upstream).await;
+
+    call(&mut client, &["WATCH", "k"]).await;
+    call(&mut client, &["UNWATCH"]).await;
+    call(&mut client, &["GET", "k"]).await;
+
+    let conns = connections(&received, &["watch k", "unwatch", "get k"]);
+    assert_eq!(conns[0], conns[1]);
+    assert_ne!(conns[1], conns[2]);
+}
+
+#[tokio::test]
+async fn connection_state_refused() {
+    let (upstream, received) = fake_upstream().await;
+    let (_proxy, mut client) = start_proxy(upstream).await;
+
+    for args in [&["SELECT", "1"][..], &["AUTH", "secret"], &["HELLO", "3"], &["CLIENT", "SETNAME", "me"]] {
+        let reply = call(&mut client, args).await;
+        assert!(matches!(reply, Frame::Error(_)), "{:?}: {:?}", args, reply);
+    }
+    // refused while pinned too
+    call(&mut client, &["MULTI"]).await;
+    let reply = call(&mut client, &["SELECT", "1"]).await;
+    assert!(matches!(reply, Frame::Error(_)), "{:?}", reply);
+    call(&mut client, &["EXEC"]).await;
+
+    let reply = call(&mut client, &["CLIENT", "GETNAME"]).await;
+    assert_eq!(reply, Frame::Simple("OK".to_string()));
+
+    let received = received.lock().unwrap();
+    let commands: Vec<_> = received.iter().map(|(_, command)| command.as_str()).collect();
+    assert_eq!(commands, ["multi", "exec", "client getname"]);
+}
+
+/// Commands received by a fake server, in order
+fn commands(received: &Received) -> Vec<String> {
+    received.lock().unwrap().iter().map(|(_, command)| command.clone()).collect()
+}
+
+#[tokio::test]
+async fn shadow_mirrors_writes() {
+    let (upstream, received) = fake_upstream().await;
+    let (shadow, shadowed) = fake_server(Frame::error("ERR shadow failure")).await;
+    let shadow = shadow.to_string();
+    let args = ["--shadow", &shadow, "--shadow-compare-every", "0"];
+    let (_proxy, mut client) = start_proxy_with(upstream, &args).await;
+
+    // the client only ever sees the replies of the upstream server
+    assert_eq!(call(&mut client, &["SET", "k", "1"]).await, Frame::Simple("OK".to_string()));
+    assert_eq!(call(&mut client, &["GET", "k"]).await, Frame::Simple("OK".to_string()));
+    assert_eq!(call(&mut client, &["DEL", "k"]).await, Frame::Simple("OK".to_string()));
+    assert_eq!(commands(&received), ["set k 1", "get k", "del k"]);
+
+    // the writes reach the shadow server in the background, the reads aren't mirrored
+    for _ in 0..100 {
+        if commands(&shadowed).len() == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(commands(&shadowed), ["set k 1", "del k"]);
+}
+
+#[tokio::test]
+async fn shadow_unreachable() {
+    let (upstream, received) = fake_upstream().await;
+    // nothing listens on the port once the listener is dropped
+    let shadow = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let shadow = shadow.to_string();
+    let (_proxy, mut client) = start_proxy_with(upstream, &["--shadow", &shadow]).await;
+
+    assert_eq!(call(&mut client, &["SET", "k", "1"]).await, Frame::Simple("OK".to_string()));
+    assert_eq!(call(&mut client, &["GET", "k"]).await, Frame::Simple("OK".to_string()));
+    assert_eq!(commands(&received), ["set k 1", "get k"]);
+}
@@ -0,0 +1,170 @@
+use redust::{server, Connection, Frame};
+
+use bytes::Bytes;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+
+async fn start() -> server::Server {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    server::Builder::new().proxy_protocol(true).start(listener).unwrap()
+}
+
+fn command(args: &[&str]) -> Frame {
+    Frame::Array(
+        args.iter()
+            .map(|arg| Frame::Bulk(Bytes::copy_from_slice(arg.as_bytes())))
+            .collect(),
+    )
+}
+
+/// Connect to `server` and send `header`
+async fn connect(server: &server::Server, header: &[u8]) -> TcpStream {
+    let mut socket = TcpStream::connect(server.local_addr()).await.unwrap();
+    socket.write_all(header).await.unwrap();
+    socket
+}
+
+/// Address of the client as seen by the server, read back from the slow log
+async fn client_addr(socket: TcpStream) -> String {
+    let mut connection = Connection::new(socket);
+    for args in [&["config", "set", "slowlog-log-slower-than", "0"][..], &["ping"]] {
+        connection.write_frame(&command(args)).await.unwrap();
+        connection.read_frame().await.unwrap().unwrap();
+    }
+
+    connection.write_frame(&command(&["slowlog", "get", "1"])).await.unwrap();
+    let entry = match connection.read_frame().await.unwrap().unwrap() {
+        Frame::Array(mut entries) => entries.remove(0),
+        frame => panic!("unexpected reply {}", frame),
+    };
+    match entry {
+        Frame::Array(fields) => match &fields[4] {
+            Frame::Bulk(addr) => String::from_utf8(addr.to_vec()).unwrap(),
+            frame => panic!("unexpected client {}", frame),
+        },
+        frame => panic!("unexpected entry {}", frame),
+    }
+}
+
+/// Whether the server closed `socket` without replying to a `PING`
+async fn refused(mut socket: TcpStream) -> bool {
+    // the write fails if the server already closed the connection
+    let _ = socket.write_all(b"*1\r\n$4\r\nPING\r\n").await;
+    let mut buf = vec![];
+    let read = timeout(Duration::from_secs(2), socket.read_to_end(&mut buf)).await;
+    match read {
+        Ok(Ok(_)) => buf.is_empty(),
+        Ok(Err(_)) => true,
+        Err(_) => false,
+    }
+}
+
+fn v2_header(command: u8, family: u8, addresses: &[u8]) -> Vec<u8> {
+    let mut header = V2_SIGNATURE.to_vec();
+    header.push(0x20 | command);
+    header.push(family);
+    header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+    header.extend_from_slice(addresses);
+    header
+}
+
+#[tokio::test]
+async fn v1_header() {
+    let server = start().await;
+
+    let socket = connect(&server, b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 6379\r\n").await;
+    assert_eq!(client_addr(socket).await, "203.0.113.7:51234");
+
+    let socket = connect(&server, b"PROXY TCP6 2001:db8::7 2001:db8::1 51234 6379\r\n").await;
+    assert_eq!(client_addr(socket).await, "[2001:db8::7]:51234");
+
+    // health checks of the load balancer, the connection keeps its own address
+    let socket = connect(&server, b"PROXY UNKNOWN\r\n").await;
+    let local = socket.local_addr().unwrap();
+    assert_eq!(client_addr(socket).await, local.to_string());
+}
+
+#[tokio::test]
+async fn v2_header_with_tlvs() {
+    let server = start().await;
+
+    // AF_INET over TCP, followed by a TLV which is skipped
+    let mut addresses = vec![198, 51, 100, 9, 10, 0, 0, 1];
+    addresses.extend_from_slice(&40000u16.to_be_bytes());
+    addresses.extend_from_slice(&6379u16.to_be_bytes());
+    addresses.extend_from_slice(&[0x04, 0x00, 0x01, 0x00]);
+    let socket = connect(&server, &v2_header(0x1, 0x11, &addresses)).await;
+    assert_eq!(client_addr(socket).await, "198.51.100.9:40000");
+
+    // AF_INET6 over TCP
+    let ip: std::net::Ipv6Addr = "2001:db8::9".parse().unwrap();
+    let mut addresses = ip.octets().to_vec();
+    addresses.extend_from_slice(&[0; 16]);
+    addresses.extend_from_slice(&40000u16.to_be_bytes());
+    addresses.extend_from_slice(&6379u16.to_be_bytes());
+    let socket = connect(&server, &v2_header(0x1, 0x21, &addresses)).await;
+    assert_eq!(client_addr(socket).await, "[2001:db8::9]:40000");
+
+    // LOCAL, the addresses are ignored
+    let socket = connect(&server, &v2_header(0x0, 0x11, &[0; 12])).await;
+    let local = socket.local_addr().unwrap();
+    assert_eq!(client_addr(socket).await, local.to_string());
+}
+
+#[tokio::test]
+async fn truncated_headers() {
+    let server = start().await;
+
+    let mut truncated_v2 = v2_header(0x1, 0x11, &[198, 51, 100, 9, 10, 0, 0, 1, 0, 1, 0, 2]);
+    truncated_v2.truncate(truncated_v2.len() - 6);
+    let headers: [&[u8]; 4] = [
+        b"PROXY TCP4 203.0.113.7",
+        b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 6379",
+        &V2_SIGNATURE[..8],
+        &truncated_v2,
+    ];
+    for header in headers.iter() {
+        let mut socket = connect(&server, header).await;
+        // the server sees the end of the stream within the header
+        socket.shutdown().await.unwrap();
+        let mut buf = vec![];
+        let read = timeout(Duration::from_secs(2), socket.read_to_end(&mut buf)).await;
+        assert!(matches!(read, Ok(Ok(0))), "{:?} for {:?}", read, header);
+    }
+}
+
+#[tokio::test]
+async fn invalid_headers_refused() {
+    let server = start().await;
+
+    let too_long = format!("PROXY TCP4 {} 10.0.0.1 51234 6379\r\n", "1".repeat(120));
+    let headers: [&[u8]; 4] = [
+        b"PROXY TCP4 not-an-ip 10.0.0.1 51234 6379\r\n",
+        b"PROXY UDP4 203.0.113.7 10.0.0.1 51234 6379\r\n",
+        too_long.as_bytes(),
+        &v2_header(0x1, 0x11, &[198, 51, 100]),
+    ];
+    for header in headers.iter() {
+        let socket = connect(&server, header).await;
+        assert!(refused(socket).await, "{:?}", String::from_utf8_lossy(header));
+    }
+}
+
+#[tokio::test]
+async fn connections_without_header_refused() {
+    let server = start().await;
+
+    // a client reaching the server directly can't claim an address, nor be served
+    let socket = TcpStream::connect(server.local_addr()).await.unwrap();
+    assert!(refused(socket).await);
+
+    // the server still serves the connections of the load balancer
+    let socket = connect(&server, b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 6379\r\n").await;
+    let addr: SocketAddr = client_addr(socket).await.parse().unwrap();
+    assert_eq!(addr.port(), 51234);
+}
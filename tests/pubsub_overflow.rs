@@ -0,0 +1,98 @@
+use redust::server::PubSubOverflow;
+use redust::{client, server, Error};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::time::timeout;
+
+mod common;
+use common::start;
+
+/// Publish far more than a subscriber not reading can be buffered, in its channel, its queue on
+/// the server and the socket buffers
+async fn flood(client: &mut client::Client, channel: &str) {
+    let message = Bytes::from(vec![b'm'; 16 * 1024]);
+    for _ in 0..3_000 {
+        client.publish(channel, message.clone()).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn lagging_subscriber_told_how_many_messages_it_missed() {
+    let server = start(server::Builder::new().pubsub_channel_capacity(16)).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    let subscriber = client::connect(server.local_addr()).await.unwrap();
+    let mut subscriber = subscriber.subscribe(vec!["news".to_string()]).await.unwrap();
+
+    flood(&mut client, "news").await;
+    client.publish("news", Bytes::from("last")).await.unwrap();
+
+    // the subscription goes on after the lag
+    let mut missed = 0;
+    let mut received = 0;
+    loop {
+        let next = timeout(Duration::from_secs(5), subscriber.next_message()).await.unwrap();
+        match next {
+            Ok(Some(message)) if message.content == "last" => break,
+            Ok(Some(_)) => received += 1,
+            Err(Error::Lagged { channel, missed: n }) => {
+                assert_eq!(channel, "news");
+                missed += n;
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+    assert!(missed > 0);
+    assert_eq!(received + missed, 3_000);
+}
+
+#[tokio::test]
+async fn lagging_subscriber_disconnected() {
+    let builder = server::Builder::new()
+        .pubsub_channel_capacity(16)
+        .pubsub_overflow(PubSubOverflow::Disconnect);
+    let server = start(builder).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    let subscriber = client::connect(server.local_addr()).await.unwrap();
+    let mut subscriber = subscriber.subscribe(vec!["news".to_string()]).await.unwrap();
+
+    flood(&mut client, "news").await;
+
+    // the messages queued before the overflow are delivered, then the error closes the connection
+    let mut received = 0;
+    let err = loop {
+        match timeout(Duration::from_secs(5), subscriber.next_message()).await.unwrap() {
+            Ok(Some(_)) => received += 1,
+            Err(Error::Server(msg)) => break msg,
+            other => panic!("{:?}", other),
+        }
+    };
+    assert!(err.starts_with("ERR "), "{}", err);
+    assert!(err.ends_with("dropped on channel news, the subscriber lagged behind"), "{}", err);
+    assert!(subscriber.next_message().await.unwrap().is_none());
+
+    // the channel is gone along with its only subscriber, its counters are in the server totals
+    let info = client.info(Some("stats")).await.unwrap();
+    let stat = |name: &str| -> u64 {
+        let line = info.lines().find(|line| line.starts_with(name)).unwrap();
+        line[name.len() + 1..].parse().unwrap()
+    };
+    assert_eq!(stat("pubsub_disconnected"), 1);
+    // the messages still in the channel at the disconnection are neither delivered nor dropped
+    assert!(stat("pubsub_dropped") > 0);
+    assert!(stat("pubsub_dropped") + received <= 3_000);
+}
+
+#[tokio::test]
+async fn overflow_policy_set_at_runtime() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let policy = client.config_get("pubsub-overflow").await.unwrap();
+    assert_eq!(policy, [("pubsub-overflow".to_string(), "drop".to_string())]);
+    client.config_set("pubsub-overflow", "disconnect").await.unwrap();
+    let policy = client.config_get("pubsub-overflow").await.unwrap();
+    assert_eq!(policy, [("pubsub-overflow".to_string(), "disconnect".to_string())]);
+
+    assert!(client.config_set("pubsub-overflow", "block").await.is_err());
+}
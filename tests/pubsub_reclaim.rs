@@ -0,0 +1,50 @@
+use redust::{client, server};
+
+use std::time::Duration;
+use tokio::time::sleep;
+
+mod common;
+use common::{memory_field, start};
+
+#[tokio::test]
+async fn unsubscribed_channels_are_released() {
+    let server = start(server::Builder::new()).await;
+    let client = client::connect(server.local_addr()).await.unwrap();
+    let mut subscriber = client.subscribe(vec!["channel-0".into()]).await.unwrap();
+    subscriber.unsubscribe(&[]).await.unwrap();
+
+    for i in 1..500 {
+        let channel = vec![format!("channel-{}", i)];
+        subscriber.subscribe(&channel).await.unwrap();
+        subscriber.unsubscribe(&channel).await.unwrap();
+    }
+
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    assert_eq!(memory_field(&mut client, "pubsub_channels").await, 0.0);
+}
+
+#[tokio::test]
+async fn channels_are_released_when_the_subscriber_leaves() {
+    let server = start(server::Builder::new()).await;
+
+    for round in 0..20 {
+        let client = client::connect(server.local_addr()).await.unwrap();
+        let channels: Vec<String> = (0..10)
+            .map(|i| format!("channel-{}-{}", round, i))
+            .collect();
+        let subscriber = client.subscribe(channels).await.unwrap();
+        drop(subscriber);
+    }
+
+    // the server notices the closed connections asynchronously
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    let mut channels = f64::MAX;
+    for _ in 0..100 {
+        channels = memory_field(&mut client, "pubsub_channels").await;
+        if channels == 0.0 {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(channels, 0.0);
+}
@@ -0,0 +1,76 @@
+use redust::{client, server};
+
+use bytes::Bytes;
+
+mod common;
+use common::start;
+
+fn stat(stats: &[(String, u64)], name: &str) -> u64 {
+    stats.iter().find(|(field, _)| field == name).unwrap().1
+}
+
+/// Reads a field of an `INFO` report
+fn info_field(info: &str, field: &str) -> u64 {
+    info.lines()
+        .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn delivery_counters() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    assert_eq!(client.channel_stats("news").await.unwrap(), None);
+
+    let mut subscribers = vec![];
+    for _ in 0..2 {
+        let subscriber = client::connect(server.local_addr()).await.unwrap();
+        subscribers.push(subscriber.subscribe(vec!["news".to_string()]).await.unwrap());
+    }
+    for message in ["first", "second", "third"] {
+        assert_eq!(client.publish("news", Bytes::from(message)).await.unwrap(), 2);
+    }
+    for subscriber in subscribers.iter_mut() {
+        for _ in 0..3 {
+            subscriber.next_message().await.unwrap().unwrap();
+        }
+    }
+
+    let stats = client.channel_stats("news").await.unwrap().unwrap();
+    assert_eq!(stat(&stats, "subscribers"), 2);
+    assert_eq!(stat(&stats, "published"), 3);
+    // once per receiving subscriber
+    assert_eq!(stat(&stats, "delivered"), 6);
+    assert_eq!(stat(&stats, "dropped"), 0);
+
+    let info = client.info(Some("stats")).await.unwrap();
+    assert_eq!(info_field(&info, "pubsub_subscribers"), 2);
+    assert_eq!(info_field(&info, "pubsub_published"), 3);
+    assert_eq!(info_field(&info, "pubsub_delivered"), 6);
+}
+
+#[tokio::test]
+async fn subscriber_churn() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let subscriber = client::connect(server.local_addr()).await.unwrap();
+    let mut subscriber = subscriber
+        .subscribe(vec!["news".to_string(), "other".to_string()])
+        .await
+        .unwrap();
+    // keeps the channel alive once the first subscriber leaves it
+    let other = client::connect(server.local_addr()).await.unwrap();
+    let _other = other.subscribe(vec!["news".to_string()]).await.unwrap();
+
+    subscriber.unsubscribe(&["news".to_string()]).await.unwrap();
+    subscriber.subscribe(&["news".to_string()]).await.unwrap();
+    subscriber.unsubscribe(&["news".to_string()]).await.unwrap();
+
+    let stats = client.channel_stats("news").await.unwrap().unwrap();
+    assert_eq!(stat(&stats, "subscribers"), 1);
+    assert_eq!(stat(&stats, "subscribes"), 3);
+    assert_eq!(stat(&stats, "unsubscribes"), 2);
+}
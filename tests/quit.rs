@@ -0,0 +1,26 @@
+use redust::{client, server};
+
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn quit_closes_the_connection() {
+    let server = start(server::Builder::new()).await;
+    let client = client::connect(server.local_addr()).await.unwrap();
+    client.quit().await.unwrap();
+
+    // the commands pipelined after QUIT aren't run
+    let mut stream = TcpStream::connect(server.local_addr()).await.unwrap();
+    stream
+        .write_all(b"*2\r\n$4\r\nQUIT\r\n$3\r\nnow\r\n*1\r\n$4\r\nPING\r\n")
+        .await
+        .unwrap();
+    let mut reply = vec![];
+    timeout(Duration::from_secs(1), stream.read_to_end(&mut reply)).await.unwrap().unwrap();
+    assert_eq!(reply, b"+OK\r\n");
+}
@@ -0,0 +1,85 @@
+use redust::rate_limit::ClientKey;
+use redust::{client, server};
+
+use std::time::Duration;
+use tokio::time::sleep;
+
+mod common;
+use common::start;
+
+/// Run `PING` `n` times, returns how many were throttled
+async fn pings(client: &mut client::Client, n: usize) -> usize {
+    let mut throttled = 0;
+    for _ in 0..n {
+        if let Err(err) = client.ping(None).await {
+            assert_eq!(err.to_string(), "BUSY rate limit exceeded");
+            throttled += 1;
+        }
+    }
+    throttled
+}
+
+#[tokio::test]
+async fn global_limit_throttles_and_replenishes() {
+    let server = start(server::Builder::new().rate_limit(10)).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    let mut other = client::connect(server.local_addr()).await.unwrap();
+
+    // the bucket starts full, holding one second worth of commands shared by every client
+    assert_eq!(pings(&mut client, 6).await, 0);
+    assert_eq!(pings(&mut other, 4).await, 0);
+    assert_eq!(pings(&mut client, 2).await, 2);
+    assert_eq!(pings(&mut other, 1).await, 1);
+
+    // refilled at 10 tokens per second, up to 10 tokens
+    sleep(Duration::from_millis(1100)).await;
+    assert_eq!(pings(&mut client, 10).await, 0);
+    assert_eq!(pings(&mut client, 1).await, 1);
+
+    sleep(Duration::from_millis(250)).await;
+    let info = client.info(Some("stats")).await.unwrap();
+    assert!(info.contains("rate_limited_commands:4\r\n"), "{}", info);
+
+    // turned off at runtime
+    client.config_set("ratelimit-global", "0").await.unwrap();
+    assert_eq!(pings(&mut client, 50).await, 0);
+}
+
+#[tokio::test]
+async fn client_limit_by_address() {
+    let server = start(server::Builder::new().client_rate_limit(4, ClientKey::Addr)).await;
+    let mut first = client::connect(server.local_addr()).await.unwrap();
+    let mut second = client::connect(server.local_addr()).await.unwrap();
+
+    // both connections come from 127.0.0.1
+    assert_eq!(pings(&mut first, 2).await, 0);
+    assert_eq!(pings(&mut second, 2).await, 0);
+    assert_eq!(pings(&mut first, 1).await, 1);
+
+    sleep(Duration::from_millis(600)).await;
+    assert_eq!(pings(&mut second, 2).await, 0);
+}
+
+#[tokio::test]
+async fn client_limit_by_user() {
+    let server = start(server::Builder::new()).await;
+    let mut admin = client::connect(server.local_addr()).await.unwrap();
+    admin.acl_set_user("alice", &["on", ">secret", "+@all", "allkeys"]).await.unwrap();
+
+    let mut alice = client::connect(server.local_addr()).await.unwrap();
+    alice.auth(Some("alice"), "secret").await.unwrap();
+    let mut alice_again = client::connect(server.local_addr()).await.unwrap();
+    alice_again.auth(Some("alice"), "secret").await.unwrap();
+
+    admin.config_set("ratelimit-client-by", "user").await.unwrap();
+    admin.config_set("ratelimit-client", "5").await.unwrap();
+
+    // the connections of a user share a bucket, other users have their own
+    assert_eq!(pings(&mut alice, 3).await, 0);
+    assert_eq!(pings(&mut alice_again, 3).await, 1);
+    assert_eq!(pings(&mut admin, 5).await, 0);
+    assert_eq!(pings(&mut admin, 1).await, 1);
+
+    sleep(Duration::from_millis(500)).await;
+    assert_eq!(pings(&mut alice, 2).await, 0);
+}
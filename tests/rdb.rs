@@ -0,0 +1,150 @@
+use redust::{client, server, Store, ValueTransform};
+
+use bytes::Bytes;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+mod common;
+use common::start;
+
+/// CRC64 of Redis, computed bit by bit
+fn crc64(data: &[u8]) -> u64 {
+    let mut crc = 0u64;
+    for &b in data {
+        crc ^= b as u64;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0x95ac_9329_ac4b_c9b5 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+/// A `DUMP` payload of a string value holding `body`, version 9
+fn payload(body: &[u8]) -> Bytes {
+    let mut payload = body.to_vec();
+    payload.extend_from_slice(&9u16.to_le_bytes());
+    let crc = crc64(&payload);
+    payload.extend_from_slice(&crc.to_le_bytes());
+    payload.into()
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("redust-{}-{}.rdb", name, std::process::id()))
+}
+
+#[test]
+fn crc64_matches_redis() {
+    assert_eq!(crc64(b"123456789"), 0xe9c6_d914_c4b8_d9ca);
+}
+
+#[tokio::test]
+async fn dump_restore_round_trip() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("foo", Bytes::from_static(b"bar\r\n\x00")).await.unwrap();
+    let dumped = client.dump("foo").await.unwrap().unwrap();
+    assert_eq!(dumped, payload(b"\x00\x06bar\r\n\x00"));
+
+    client.restore("copy", dumped.clone(), Some(Duration::from_secs(60)), false).await.unwrap();
+    assert_eq!(client.get::<Option<Bytes>>("copy").await.unwrap().as_deref(), Some(&b"bar\r\n\x00"[..]));
+    assert!(client.restore("copy", dumped, None, false).await.is_err());
+    assert_eq!(client.dump("missing").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn restore_decompresses_lzf() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    // "aaaaaaaaaa": a literal `a` then a back reference copying 9 bytes from 1 byte back
+    let body = b"\x00\xc3\x05\x0a\x00a\xe0\x00\x00";
+    client.restore("lzf", payload(body), None, false).await.unwrap();
+    assert_eq!(client.get::<String>("lzf").await.unwrap(), "aaaaaaaaaa");
+}
+
+#[tokio::test]
+async fn restore_rejects_bad_payloads() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let good = payload(b"\x00\x03bar");
+    let mut corrupted = good.to_vec();
+    corrupted[2] = b'c';
+    let err = client.restore("k", corrupted.into(), None, false).await.unwrap_err();
+    assert_eq!(err.to_string(), "ERR DUMP payload version or checksum are wrong");
+
+    for len in [0, 5, good.len() - 1] {
+        assert!(client.restore("k", good.slice(..len), None, false).await.is_err(), "{}", len);
+    }
+    // a string announcing more bytes than the payload holds
+    assert!(client.restore("k", payload(b"\x00\x05bar"), None, false).await.is_err());
+    // a 5 bytes LZF string announcing 1GB once decompressed
+    let bomb = payload(b"\x00\xc3\x05\x80\x40\x00\x00\x00\x00a\xe0\x00\x00");
+    assert!(client.restore("k", bomb, None, false).await.is_err());
+    // a back reference before the start of the output
+    assert!(client.restore("k", payload(b"\x00\xc3\x02\x05\x20\x05"), None, false).await.is_err());
+
+    assert_eq!(client.get::<Option<Bytes>>("k").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn rdb_file_round_trip() {
+    let path = temp_path("round-trip");
+    let store = Store::new();
+    store.set("foo", "bar".into()).unwrap();
+    store.set_expires("ttl", "value".into(), Duration::from_secs(60)).unwrap();
+    store.set("baz", "qux".into()).unwrap();
+    assert_eq!(store.save_rdb(&path).unwrap(), 3);
+
+    let loaded = Store::new();
+    assert_eq!(loaded.load_rdb(&path).unwrap(), 3);
+    assert_eq!(loaded.get("foo").unwrap().as_deref(), Some(&b"bar"[..]));
+    assert_eq!(loaded.get("ttl").unwrap().as_deref(), Some(&b"value"[..]));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn rdb_file_rejects_corruption() {
+    let path = temp_path("corrupted");
+    let store = Store::new();
+    store.set("foo", "bar".into()).unwrap();
+    store.save_rdb(&path).unwrap();
+
+    let data = std::fs::read(&path).unwrap();
+    let mut corrupted = data.clone();
+    let at = corrupted.len() - 12;
+    corrupted[at] ^= 0xff;
+    std::fs::write(&path, &corrupted).unwrap();
+    assert!(Store::new().load_rdb(&path).is_err());
+
+    std::fs::write(&path, &data[..data.len() - 10]).unwrap();
+    assert!(Store::new().load_rdb(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[derive(Debug)]
+struct Reverse;
+
+impl ValueTransform for Reverse {
+    fn encode(&self, _key: &str, value: Bytes) -> redust::Result<Bytes> {
+        Ok(value.iter().rev().copied().collect::<Vec<u8>>().into())
+    }
+
+    fn decode(&self, key: &str, stored: Bytes) -> redust::Result<Bytes> {
+        self.encode(key, stored)
+    }
+}
+
+#[tokio::test]
+async fn save_refused_with_a_value_transform() {
+    let path = temp_path("transformed");
+    let builder = server::Builder::new().value_transform(Arc::new(Reverse)).rdb_file(&path);
+    let server = start(builder).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("secret", "value").await.unwrap();
+    assert!(client.save().await.is_err());
+    assert!(!path.exists());
+}
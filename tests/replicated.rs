@@ -0,0 +1,129 @@
+use redust::client::{self, ReadPolicy};
+use redust::{server, Connection, Frame};
+
+use bytes::Bytes;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+mod common;
+use common::start;
+
+/// A Redis-like replica, replying its own name to every `GET`
+struct Replica {
+    addr: SocketAddr,
+    /// Commands received, lowercased
+    commands: Arc<Mutex<Vec<String>>>,
+    /// Once set, the connections are closed on their next request
+    stopped: Arc<AtomicBool>,
+}
+
+impl Replica {
+    async fn start(name: &'static str) -> Replica {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let commands = Arc::new(Mutex::new(vec![]));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let (received, stop) = (commands.clone(), stopped.clone());
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let (received, stop) = (received.clone(), stop.clone());
+                tokio::spawn(async move {
+                    let mut connection = Connection::new(socket);
+                    while let Ok(Some(Frame::Array(args))) = connection.read_frame().await {
+                        if stop.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        let command = match &args[0] {
+                            Frame::Bulk(name) => String::from_utf8(name.to_vec()).unwrap().to_lowercase(),
+                            frame => frame.to_string(),
+                        };
+                        let reply = match &command[..] {
+                            "info" => Frame::Bulk(Bytes::from("# Replication\r\nrole:slave\r\n")),
+                            "get" => Frame::Bulk(Bytes::from(name)),
+                            _ => Frame::error("READONLY You can't write against a read only replica."),
+                        };
+                        received.lock().unwrap().push(command);
+                        connection.write_frame(&reply).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        Replica { addr, commands, stopped }
+    }
+
+    fn received(&self, command: &str) -> usize {
+        let commands = self.commands.lock().unwrap();
+        commands.iter().filter(|received| *received == command).count()
+    }
+}
+
+#[tokio::test]
+async fn reads_spread_over_replicas() {
+    let primary = start(server::Builder::new()).await;
+    let (first, second) = (Replica::start("first").await, Replica::start("second").await);
+    let mut client = client::connect_replicated(primary.local_addr(), vec![first.addr, second.addr])
+        .await
+        .unwrap();
+    assert_eq!(client.available_replicas(), [first.addr, second.addr]);
+
+    let mut reads = vec![];
+    for _ in 0..4 {
+        let value: Option<Bytes> = client.get("key").await.unwrap();
+        reads.push(value.unwrap());
+    }
+    assert_eq!(reads, ["first", "second", "first", "second"]);
+
+    // writes go to the primary
+    client.set("key", "primary").await.unwrap();
+    let value: Option<Bytes> = client.primary().get("key").await.unwrap();
+    assert_eq!(value, Some(Bytes::from("primary")));
+    assert_eq!(first.received("set") + second.received("set"), 0);
+
+    client.set_read_policy(ReadPolicy::LowestLatency);
+    let value: Option<Bytes> = client.get("key").await.unwrap();
+    assert_ne!(value, Some(Bytes::from("primary")));
+}
+
+#[tokio::test]
+async fn redust_servers_are_not_replicas() {
+    let primary = start(server::Builder::new()).await;
+    // redust has no replica role, this server doesn't follow the primary
+    let other = start(server::Builder::new()).await;
+    let mut client = client::connect_replicated(primary.local_addr(), vec![other.local_addr()])
+        .await
+        .unwrap();
+    assert!(client.available_replicas().is_empty());
+
+    client.set("key", "written").await.unwrap();
+    let value: Option<Bytes> = client.get("key").await.unwrap();
+    assert_eq!(value, Some(Bytes::from("written")));
+}
+
+#[tokio::test]
+async fn failed_replicas_left_out() {
+    let primary = start(server::Builder::new()).await;
+    let (first, second) = (Replica::start("first").await, Replica::start("second").await);
+    let mut client = client::connect_replicated(primary.local_addr(), vec![first.addr, second.addr])
+        .await
+        .unwrap();
+    client.set_retry_after(Duration::from_secs(60));
+    client.set("key", "primary").await.unwrap();
+
+    // the read is retried on the other replica
+    first.stopped.store(true, Ordering::SeqCst);
+    let value: Option<Bytes> = client.get("key").await.unwrap();
+    assert_eq!(value, Some(Bytes::from("second")));
+    assert_eq!(client.available_replicas(), [second.addr]);
+
+    // then falls back to the primary
+    second.stopped.store(true, Ordering::SeqCst);
+    let value: Option<Bytes> = client.get("key").await.unwrap();
+    assert_eq!(value, Some(Bytes::from("primary")));
+    assert!(client.available_replicas().is_empty());
+}
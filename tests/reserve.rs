@@ -0,0 +1,125 @@
+use redust::{client, server};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+mod common;
+use common::start;
+
+const DEAD_LETTER: &str = "__reservations__:expired";
+
+/// Wait until the list `key` holds `len` elements
+async fn wait_len(client: &mut client::Client, key: &str, len: u64) {
+    timeout(Duration::from_secs(5), async {
+        while client.llen(key).await.unwrap() < len {
+            sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn expired_reservations_pushed_to_dead_letter_list() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.reserve("hold:1", Bytes::from("first"), Duration::from_secs(1)).await.unwrap();
+    client.reserve("hold:2", Bytes::from("second"), Duration::from_secs(1)).await.unwrap();
+    // nobody listens, the payloads are kept until consumed
+    wait_len(&mut client, DEAD_LETTER, 2).await;
+
+    let mut elements = client.lrange(DEAD_LETTER, 0, -1).await.unwrap();
+    elements.sort();
+    assert_eq!(elements, [Bytes::from("first"), Bytes::from("second")]);
+    assert_eq!(client.lrange(DEAD_LETTER, 5, 10).await.unwrap(), Vec::<Bytes>::new());
+
+    assert!(client.lpop(DEAD_LETTER).await.unwrap().is_some());
+    assert!(client.lpop(DEAD_LETTER).await.unwrap().is_some());
+    // the list is removed once empty
+    assert_eq!(client.lpop(DEAD_LETTER).await.unwrap(), None);
+    assert_eq!(client.llen(DEAD_LETTER).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn confirmed_reservations_not_delivered() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.reserve("confirmed", Bytes::from("kept"), Duration::from_secs(1)).await.unwrap();
+    client.reserve("expired", Bytes::from("dropped"), Duration::from_secs(1)).await.unwrap();
+    assert!(client.confirm("confirmed").await.unwrap());
+
+    wait_len(&mut client, DEAD_LETTER, 1).await;
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(client.lrange(DEAD_LETTER, 0, -1).await.unwrap(), [Bytes::from("dropped")]);
+}
+
+#[tokio::test]
+async fn confirm_makes_a_new_version() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.reserve("hold", Bytes::from("payload"), Duration::from_secs(1)).await.unwrap();
+    let reserved = client.get_entry("hold").await.unwrap().unwrap();
+    assert!(client.confirm("hold").await.unwrap());
+    let confirmed = client.get_entry("hold").await.unwrap().unwrap();
+    assert!(confirmed.version > reserved.version, "{} {}", confirmed.version, reserved.version);
+
+    // the key still expires, without reaching the dead-letter list
+    sleep(Duration::from_millis(1500)).await;
+    assert_eq!(client.get::<Option<Bytes>>("hold").await.unwrap(), None);
+    assert_eq!(client.llen(DEAD_LETTER).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn custom_dead_letter_list() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let args = vec!["reserve", "order:1", "1", "payload", "DEADLETTER", "orders:expired"];
+    let reply: String = client.command(args).await.unwrap();
+    assert_eq!(reply, "OK");
+
+    wait_len(&mut client, "orders:expired", 1).await;
+    assert_eq!(client.llen(DEAD_LETTER).await.unwrap(), 0);
+
+    let args = vec!["lpop", "orders:expired", "5"];
+    let popped: Vec<Bytes> = client.command(args).await.unwrap();
+    assert_eq!(popped, [Bytes::from("payload")]);
+}
+
+#[tokio::test]
+async fn dead_letter_key_holding_a_string() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("taken", "value").await.unwrap();
+    let args = vec!["reserve", "hold", "1", "payload", "DEADLETTER", "taken"];
+    let _: String = client.command(args).await.unwrap();
+    sleep(Duration::from_millis(1500)).await;
+
+    // the string is left alone
+    let value: Option<Bytes> = client.get("taken").await.unwrap();
+    assert_eq!(value, Some(Bytes::from("value")));
+    let err = client.llen("taken").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+}
+
+#[tokio::test]
+async fn out_of_range_ttl() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let err = client
+        .reserve("hold", Bytes::from("payload"), Duration::from_secs(u64::MAX / 2))
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "ERR invalid expire time in 'reserve' command");
+
+    // nothing was reserved and the shard still serves the key
+    assert_eq!(client.exists(&["hold".to_string()]).await.unwrap(), 0);
+    client.reserve("hold", Bytes::from("payload"), Duration::from_secs(60)).await.unwrap();
+    assert!(client.confirm("hold").await.unwrap());
+}
@@ -0,0 +1,42 @@
+use redust::{client, server, Error};
+
+use bytes::Bytes;
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn reset_drops_authentication() {
+    let server = start(server::Builder::new().requirepass("secret")).await;
+
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.auth(None, "secret").await.unwrap();
+    client.ping(None).await.unwrap();
+
+    client.reset().await.unwrap();
+    assert!(matches!(client.ping(None).await, Err(Error::NotAuthenticated)));
+}
+
+#[tokio::test]
+async fn reset_drops_subscriptions() {
+    let server = start(server::Builder::new()).await;
+    let mut publisher = client::connect(server.local_addr()).await.unwrap();
+
+    let subscriber = client::connect(server.local_addr()).await.unwrap();
+    let subscriber = subscriber.subscribe(vec!["news".to_string()]).await.unwrap();
+    assert_eq!(publisher.publish("news", Bytes::from("first")).await.unwrap(), 1);
+
+    // back to a regular client, which no longer gets the messages of the channel
+    let mut client = subscriber.reset().await.unwrap();
+    assert_eq!(publisher.publish("news", Bytes::from("second")).await.unwrap(), 0);
+    assert_eq!(client.ping(None).await.unwrap(), Bytes::from("PONG"));
+}
+
+#[tokio::test]
+async fn lolwut_replies_the_version() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let art = client.lolwut().await.unwrap();
+    assert!(art.contains(&format!("redust ver. {}", env!("CARGO_PKG_VERSION"))));
+}
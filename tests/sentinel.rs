@@ -0,0 +1,191 @@
+use redust::sentinel::Monitor;
+use redust::{client, server, Connection, Frame};
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
+
+const NODE_PASSWORD: &str = "node-secret";
+const SENTINEL_PASSWORD: &str = "sentinel-secret";
+
+/// A Redis-like node answering `AUTH`, `PING` and `REPLICAOF`, which redust doesn't implement
+struct Node {
+    addr: SocketAddr,
+    /// Commands received, lowercased
+    commands: Arc<Mutex<Vec<Vec<String>>>>,
+    task: JoinHandle<()>,
+}
+
+impl Node {
+    async fn start() -> Node {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let commands = Arc::new(Mutex::new(vec![]));
+
+        let received = commands.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let received = received.clone();
+                tokio::spawn(async move {
+                    let mut connection = Connection::new(socket);
+                    let mut authenticated = false;
+                    while let Ok(Some(Frame::Array(args))) = connection.read_frame().await {
+                        let args: Vec<String> = args
+                            .into_iter()
+                            .map(|arg| match arg {
+                                Frame::Bulk(data) => String::from_utf8(data.to_vec()).unwrap().to_lowercase(),
+                                frame => frame.to_string(),
+                            })
+                            .collect();
+                        let reply = match (&args[0][..], authenticated) {
+                            ("auth", _) if args.last().unwrap() == NODE_PASSWORD => {
+                                authenticated = true;
+                                Frame::Simple("OK".to_string())
+                            }
+                            ("auth", _) => Frame::error("WRONGPASS invalid username-password pair"),
+                            (_, false) => Frame::error("NOAUTH Authentication required."),
+                            ("ping", true) => Frame::Simple("PONG".to_string()),
+                            ("replicaof", true) => Frame::Simple("OK".to_string()),
+                            (_, true) => Frame::error("ERR unknown command"),
+                        };
+                        received.lock().unwrap().push(args);
+                        if connection.write_frame(&reply).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        Node { addr, commands, task }
+    }
+
+    fn received(&self, command: &[&str]) -> usize {
+        let commands = self.commands.lock().unwrap();
+        commands.iter().filter(|args| *args == command).count()
+    }
+}
+
+async fn sentinel_client(addr: SocketAddr) -> client::Client {
+    let mut client = client::connect(addr).await.unwrap();
+    client.auth(None, SENTINEL_PASSWORD).await.unwrap();
+    client
+}
+
+/// Start sentinels on `listeners`, each knowing the others as peers
+fn start_sentinels(listeners: Vec<TcpListener>, monitor: Monitor) -> Vec<server::Server> {
+    let addrs: Vec<SocketAddr> = listeners.iter().map(|listener| listener.local_addr().unwrap()).collect();
+    listeners
+        .into_iter()
+        .map(|listener| {
+            let own = listener.local_addr().unwrap();
+            let peers = addrs.iter().copied().filter(|addr| *addr != own).collect();
+            let monitor = monitor.clone().peers(peers);
+            server::Builder::new()
+                .requirepass(SENTINEL_PASSWORD)
+                .sentinel(monitor)
+                .start(listener)
+                .unwrap()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn elected_sentinel_fails_over() {
+    let primary = Node::start().await;
+    let (first, second) = (Node::start().await, Node::start().await);
+
+    let mut listeners = vec![];
+    for _ in 0..3 {
+        listeners.push(TcpListener::bind("127.0.0.1:0").await.unwrap());
+    }
+    let monitor = Monitor::new("main", primary.addr, 2)
+        .replicas(vec![first.addr, second.addr])
+        .down_after(Duration::from_millis(200))
+        .auth(None, NODE_PASSWORD)
+        .peer_auth(None, SENTINEL_PASSWORD);
+    let sentinels = start_sentinels(listeners, monitor);
+
+    let mut client = sentinel_client(sentinels[0].local_addr()).await;
+    assert_eq!(client.sentinel_primary("main").await.unwrap(), Some(primary.addr));
+
+    primary.task.abort();
+    // every sentinel ends up with the promoted replica, the peers of the leader adopting it
+    timeout(Duration::from_secs(20), async {
+        for sentinel in &sentinels {
+            let mut client = sentinel_client(sentinel.local_addr()).await;
+            while client.sentinel_primary("main").await.unwrap() != Some(first.addr) {
+                sleep(Duration::from_millis(50)).await;
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    // only the elected sentinel failed over
+    assert_eq!(first.received(&["replicaof", "no", "one"]), 1);
+    let port = first.addr.port().to_string();
+    assert_eq!(second.received(&["replicaof", "127.0.0.1", &port]), 1);
+    assert!(first.received(&["auth", NODE_PASSWORD]) > 0);
+
+    let fields: Vec<String> = client.command(vec!["sentinel", "master", "main"]).await.unwrap();
+    let epoch = fields.iter().position(|field| field == "config-epoch").unwrap() + 1;
+    assert!(fields[epoch].parse::<u64>().unwrap() > 0, "{:?}", fields);
+}
+
+#[tokio::test]
+async fn no_failover_without_a_majority() {
+    let primary = Node::start().await;
+    let replica = Node::start().await;
+
+    // the two other sentinels are gone
+    let mut peers = vec![];
+    for _ in 0..2 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        peers.push(listener.local_addr().unwrap());
+    }
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    // the quorum is reached on its own, the election needs 2 votes out of 3
+    let monitor = Monitor::new("main", primary.addr, 1)
+        .replicas(vec![replica.addr])
+        .peers(peers)
+        .down_after(Duration::from_millis(200))
+        .auth(None, NODE_PASSWORD);
+    let sentinel = server::Builder::new().sentinel(monitor).start(listener).unwrap();
+
+    primary.task.abort();
+    sleep(Duration::from_secs(3)).await;
+
+    assert_eq!(replica.received(&["replicaof", "no", "one"]), 0);
+    let mut client = client::connect(sentinel.local_addr()).await.unwrap();
+    assert_eq!(client.sentinel_primary("main").await.unwrap(), Some(primary.addr));
+}
+
+#[tokio::test]
+async fn one_vote_per_epoch() {
+    let primary = Node::start().await;
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let monitor = Monitor::new("main", primary.addr, 2).auth(None, NODE_PASSWORD);
+    let sentinel = server::Builder::new().sentinel(monitor).start(listener).unwrap();
+    let mut client = client::connect(sentinel.local_addr()).await.unwrap();
+
+    let port = primary.addr.port().to_string();
+    let cases = [
+        ("5", "first", (0, "first", 5)),
+        // already voted in that epoch, and past it
+        ("5", "second", (0, "first", 5)),
+        ("4", "third", (0, "first", 5)),
+        ("6", "second", (0, "second", 6)),
+        // no vote asked
+        ("7", "*", (0, "*", 0)),
+    ];
+    for (epoch, run_id, expected) in cases.iter() {
+        let args = vec!["sentinel", "is-master-down-by-addr", "127.0.0.1", &port, epoch, run_id];
+        let reply: (u64, String, u64) = client.command(args).await.unwrap();
+        assert_eq!(reply, (expected.0, expected.1.to_string(), expected.2), "{} {}", epoch, run_id);
+    }
+}
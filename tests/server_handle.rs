@@ -0,0 +1,19 @@
+use redust::{client, server};
+
+use tokio::net::TcpListener;
+
+#[tokio::test]
+async fn start_reports_address_and_shuts_down() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let mut server = server::start(listener).unwrap();
+    let addr = server.local_addr();
+    assert_ne!(addr.port(), 0);
+
+    let mut client = client::connect(addr).await.unwrap();
+    client.set("foo", "bar").await.unwrap();
+    drop(client);
+
+    server.shutdown();
+    server.join().await.unwrap();
+    assert!(client::connect(addr).await.is_err());
+}
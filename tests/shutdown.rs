@@ -0,0 +1,46 @@
+use redust::{client, server};
+
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn in_flight_commands_complete() {
+    let builder = server::Builder::new().enable_debug_command(true);
+    let mut server = start(builder).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    let in_flight = tokio::spawn(async move {
+        client.command::<String>(vec!["debug", "sleep", "0.3"]).await
+    });
+    sleep(Duration::from_millis(50)).await;
+
+    // without a drain timeout the server waits for the reply to be sent
+    server.shutdown();
+    timeout(Duration::from_secs(2), server.join()).await.unwrap().unwrap();
+    assert_eq!(in_flight.await.unwrap().unwrap(), "OK");
+}
+
+#[tokio::test]
+async fn stalled_connections_aborted_after_drain_timeout() {
+    let builder = server::Builder::new()
+        .enable_debug_command(true)
+        .drain_timeout(Duration::from_millis(200));
+    let mut server = start(builder).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    let mut idle = client::connect(server.local_addr()).await.unwrap();
+    idle.ping(None).await.unwrap();
+
+    let stalled = tokio::spawn(async move {
+        client.command::<String>(vec!["debug", "sleep", "30"]).await
+    });
+    sleep(Duration::from_millis(50)).await;
+
+    server.shutdown();
+    timeout(Duration::from_secs(2), server.join()).await.unwrap().unwrap();
+    // the connection is closed without a reply
+    assert!(stalled.await.unwrap().is_err());
+    assert!(idle.ping(None).await.is_err());
+}
@@ -0,0 +1,194 @@
+#![cfg(feature = "simulation")]
+
+use redust::server::Builder;
+use redust::sim::{self, Simulation};
+use redust::Frame;
+
+use bytes::Bytes;
+use tokio::time::{self, Duration, Instant};
+
+fn command(args: &[&str]) -> Frame {
+    Frame::Array(
+        args.iter()
+            .map(|arg| Frame::Bulk(Bytes::copy_from_slice(arg.as_bytes())))
+            .collect(),
+    )
+}
+
+/// Sequence number of a `SYNCFROM` record
+fn record_seq(frame: &Frame) -> u64 {
+    match frame {
+        Frame::Array(parts) => match parts.first() {
+            Some(Frame::Integer(seq)) => *seq as u64,
+            other => panic!("unexpected record start {:?}", other),
+        },
+        other => panic!("unexpected record {:?}", other),
+    }
+}
+
+#[test]
+fn keys_expire_in_virtual_time() {
+    Simulation::new(1).block_on(async {
+        let host = sim::start(Builder::new()).await.unwrap();
+        let mut client = host.connect().await.unwrap();
+
+        client.set_expires("session", "token", Duration::from_secs(3600)).await.unwrap();
+
+        time::sleep(Duration::from_secs(59 * 60)).await;
+        assert_eq!(client.get::<Option<String>>("session").await.unwrap(), Some("token".into()));
+
+        time::sleep(Duration::from_secs(2 * 60)).await;
+        assert_eq!(client.get::<Option<String>>("session").await.unwrap(), None);
+
+        // the background task removed the key, not only hid it
+        let stats = client.ttl_stats(1).await.unwrap();
+        assert_eq!(stats.keys_with_ttl, 0);
+
+        host.shutdown().await.unwrap();
+    });
+}
+
+#[test]
+fn messages_are_held_until_a_partition_heals() {
+    Simulation::new(2).block_on(async {
+        let host = sim::start(Builder::new()).await.unwrap();
+        let link = host.link();
+
+        let mut subscriber = link.open().await.unwrap();
+        subscriber.write_frame(&command(&["subscribe", "news"])).await.unwrap();
+        subscriber.read_frame().await.unwrap().unwrap();
+
+        link.partition();
+        let mut publisher = host.open().await.unwrap();
+        for msg in &["one", "two", "three"] {
+            publisher.write_frame(&command(&["publish", "news", msg])).await.unwrap();
+            publisher.read_frame().await.unwrap().unwrap();
+        }
+
+        let held = time::timeout(Duration::from_secs(60), subscriber.read_frame()).await;
+        assert!(held.is_err(), "message delivered through a partition");
+
+        link.heal();
+        for expected in &["one", "two", "three"] {
+            match subscriber.read_frame().await.unwrap().unwrap() {
+                Frame::Array(parts) => assert_eq!(parts[2], *expected),
+                frame => panic!("unexpected frame {:?}", frame),
+            }
+        }
+
+        host.shutdown().await.unwrap();
+    });
+}
+
+#[test]
+fn shutdown_waits_for_in_flight_commands() {
+    Simulation::new(3).block_on(async {
+        let host = sim::start(Builder::new().enable_debug_command(true)).await.unwrap();
+        let mut client = host.connect().await.unwrap();
+
+        let start = Instant::now();
+        let slow = tokio::spawn(async move { client.debug_sleep(Duration::from_secs(30)).await });
+
+        time::sleep(Duration::from_secs(1)).await;
+        host.shutdown().await.unwrap();
+
+        assert!(start.elapsed() >= Duration::from_secs(30));
+        slow.await.unwrap().unwrap();
+    });
+}
+
+#[test]
+fn sync_resumes_after_a_connection_reset() {
+    Simulation::new(4).block_on(async {
+        let host = sim::start(Builder::new()).await.unwrap();
+        let link = host.link();
+        link.set_latency(Duration::from_millis(200));
+        let mut writer = host.connect().await.unwrap();
+
+        let mut consumer = link.open().await.unwrap();
+        consumer.write_frame(&command(&["syncfrom", "1"])).await.unwrap();
+
+        let mut last = 0;
+        for i in 0..5 {
+            writer.set(&format!("key-{}", i), "value").await.unwrap();
+            let seq = record_seq(&consumer.read_frame().await.unwrap().unwrap());
+            assert!(seq > last);
+            last = seq;
+        }
+
+        link.reset();
+        assert!(!matches!(consumer.read_frame().await, Ok(Some(_))));
+
+        // writes keep going while the consumer is away
+        for i in 5..10 {
+            writer.set(&format!("key-{}", i), "value").await.unwrap();
+        }
+
+        let mut consumer = link.open().await.unwrap();
+        let from = (last + 1).to_string();
+        consumer.write_frame(&command(&["syncfrom", &from])).await.unwrap();
+        for _ in 5..10 {
+            let seq = record_seq(&consumer.read_frame().await.unwrap().unwrap());
+            assert_eq!(seq, last + 1);
+            last = seq;
+        }
+
+        host.shutdown().await.unwrap();
+    });
+}
+
+/// Clients writing and reading through faulty links, returns what each client observed and when
+fn random_faults(seed: u64) -> Vec<String> {
+    let sim = Simulation::new(seed);
+    let mut rng = sim.rng();
+
+    sim.block_on(async move {
+        let host = sim::start(Builder::new()).await.unwrap();
+        let start = Instant::now();
+        let mut clients = vec![];
+
+        for id in 0..3 {
+            let link = host.link();
+            let mut client = link.connect().await.unwrap();
+            let ops: Vec<(u64, Duration, Duration, bool)> = (0..20)
+                .map(|_| {
+                    let latency = rng.duration(Duration::from_millis(50));
+                    (rng.below(4), latency, rng.duration(Duration::from_millis(100)), rng.chance(0.1))
+                })
+                .collect();
+
+            clients.push(tokio::spawn(async move {
+                let mut trace = vec![];
+                for (key, latency, pause, partition) in ops {
+                    let key = format!("key-{}", key);
+                    link.set_latency(latency);
+                    if partition {
+                        link.partition();
+                    }
+                    let heal = async {
+                        if partition {
+                            time::sleep(pause).await;
+                            link.heal();
+                        }
+                    };
+                    let (res, _) = tokio::join!(client.set(&key, id), heal);
+                    res.unwrap();
+                    let value: Option<u32> = client.get(&key).await.unwrap();
+                    trace.push(format!("{} {:?} {} {:?}", id, start.elapsed(), key, value));
+                }
+                trace
+            }));
+        }
+
+        let mut trace = vec![];
+        for client in clients {
+            trace.extend(client.await.unwrap());
+        }
+        trace
+    })
+}
+
+#[test]
+fn the_same_seed_replays_the_same_run() {
+    assert_eq!(random_faults(42), random_faults(42));
+}
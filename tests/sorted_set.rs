@@ -0,0 +1,190 @@
+use redust::{client, server};
+
+use bytes::Bytes;
+
+mod common;
+use common::start;
+
+fn members(pairs: &[(f64, &'static str)]) -> Vec<(f64, Bytes)> {
+    pairs
+        .iter()
+        .map(|(score, member)| (*score, Bytes::from(*member)))
+        .collect()
+}
+
+fn names(values: &[&'static str]) -> Vec<Bytes> {
+    values.iter().map(|value| Bytes::from(*value)).collect()
+}
+
+#[tokio::test]
+async fn add_score_and_remove_members() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    assert_eq!(
+        client
+            .zadd("set", members(&[(2.0, "b"), (1.0, "a")]))
+            .await
+            .unwrap(),
+        2
+    );
+    // an existing member is updated but not counted
+    assert_eq!(
+        client
+            .zadd("set", members(&[(0.5, "b"), (3.0, "c")]))
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(client.zcard("set").await.unwrap(), 3);
+
+    assert_eq!(
+        client.zscore("set", Bytes::from("b")).await.unwrap(),
+        Some(0.5)
+    );
+    assert_eq!(client.zscore("set", Bytes::from("z")).await.unwrap(), None);
+    assert_eq!(
+        client.zscore("missing", Bytes::from("a")).await.unwrap(),
+        None
+    );
+    assert_eq!(
+        client.zrange("set", 0, -1).await.unwrap(),
+        names(&["b", "a", "c"])
+    );
+
+    let removed = client.zrem("set", names(&["a", "z"])).await.unwrap();
+    assert_eq!(removed, 1);
+    assert_eq!(client.zcard("set").await.unwrap(), 2);
+
+    // the set is removed once empty
+    assert_eq!(client.zrem("set", names(&["b", "c"])).await.unwrap(), 2);
+    assert_eq!(client.exists(&["set".to_string()]).await.unwrap(), 0);
+    assert_eq!(client.zrem("missing", names(&["a"])).await.unwrap(), 0);
+    assert_eq!(client.zcard("missing").await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn ordered_by_score_then_member() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    let added = client
+        .zadd(
+            "set",
+            members(&[(1.0, "b"), (1.0, "a"), (-2.5, "c"), (10.0, "d")]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(added, 4);
+    client
+        .command::<i64>(vec!["zadd", "set", "-inf", "low", "+inf", "high"])
+        .await
+        .unwrap();
+
+    let cases: [(i64, i64, &[&str]); 5] = [
+        (0, -1, &["low", "c", "a", "b", "d", "high"]),
+        (1, 2, &["c", "a"]),
+        (-2, -1, &["d", "high"]),
+        (-100, 100, &["low", "c", "a", "b", "d", "high"]),
+        (3, 1, &[]),
+    ];
+    for (start, stop, expected) in cases.iter() {
+        let range = client.zrange("set", *start, *stop).await.unwrap();
+        assert_eq!(range, names(expected), "{} {}", start, stop);
+    }
+
+    let range: Vec<Bytes> = client
+        .command(vec!["zrange", "set", "0", "2", "withscores"])
+        .await
+        .unwrap();
+    assert_eq!(range, names(&["low", "-inf", "c", "-2.5", "a", "1"]));
+    assert_eq!(
+        client.zrange("missing", 0, -1).await.unwrap(),
+        Vec::<Bytes>::new()
+    );
+}
+
+#[tokio::test]
+async fn add_options() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.zadd("set", members(&[(1.0, "a")])).await.unwrap();
+
+    // NX only adds new members
+    let added: i64 = client
+        .command(vec!["zadd", "set", "nx", "5", "a", "2", "b"])
+        .await
+        .unwrap();
+    assert_eq!(added, 1);
+    assert_eq!(
+        client.zscore("set", Bytes::from("a")).await.unwrap(),
+        Some(1.0)
+    );
+
+    // XX only updates existing ones, CH counts them
+    let changed: i64 = client
+        .command(vec![
+            "zadd", "set", "xx", "ch", "3", "a", "1", "c", "2", "b",
+        ])
+        .await
+        .unwrap();
+    assert_eq!(changed, 1);
+    assert_eq!(
+        client.zscore("set", Bytes::from("a")).await.unwrap(),
+        Some(3.0)
+    );
+    assert_eq!(client.zscore("set", Bytes::from("c")).await.unwrap(), None);
+
+    // XX doesn't create the set
+    let added: i64 = client
+        .command(vec!["zadd", "other", "xx", "1", "a"])
+        .await
+        .unwrap();
+    assert_eq!(added, 0);
+    assert_eq!(client.exists(&["other".to_string()]).await.unwrap(), 0);
+
+    let errors = [
+        (
+            &["zadd", "set", "nx", "xx", "1", "a"][..],
+            "ERR XX and NX options at the same time are not compatible",
+        ),
+        (
+            &["zadd", "set", "one", "a"][..],
+            "ERR value is not a valid float",
+        ),
+        (
+            &["zadd", "set", "nan", "a"][..],
+            "ERR value is not a valid float",
+        ),
+        (
+            &["zadd", "set", "1", "a", "2"][..],
+            "ERR wrong number of arguments for 'zadd' command",
+        ),
+    ];
+    for (args, message) in errors.iter() {
+        let err = client.command::<i64>(args.to_vec()).await.unwrap_err();
+        assert_eq!(err.to_string(), *message, "{:?}", args);
+    }
+}
+
+#[tokio::test]
+async fn wrong_type() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.set("string", "value").await.unwrap();
+
+    let err = client
+        .zadd("string", members(&[(1.0, "a")]))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+    let err = client.zscore("string", Bytes::from("a")).await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+    let err = client.zrange("string", 0, -1).await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+
+    client.zadd("set", members(&[(1.0, "a")])).await.unwrap();
+    let err = client.get::<Option<Bytes>>("set").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+    let err = client.hlen("set").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+}
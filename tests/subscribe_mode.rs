@@ -0,0 +1,64 @@
+use redust::{client, server, Connection, Frame};
+
+use bytes::Bytes;
+use tokio::net::TcpStream;
+
+mod common;
+use common::start;
+
+fn bulk(s: &'static str) -> Frame {
+    Frame::Bulk(Bytes::from_static(s.as_bytes()))
+}
+
+async fn call(conn: &mut Connection, args: &[&'static str]) -> Option<Frame> {
+    let frame = Frame::Array(args.iter().map(|arg| bulk(arg)).collect());
+    conn.write_frame(&frame).await.unwrap();
+    conn.read_frame().await.unwrap()
+}
+
+/// Open a connection subscribed to `news`
+async fn subscribed(server: &server::Server) -> Connection {
+    let mut conn = Connection::new(TcpStream::connect(server.local_addr()).await.unwrap());
+    let reply = call(&mut conn, &["subscribe", "news"]).await;
+    assert_eq!(reply, Some(Frame::Array(vec![bulk("subscribe"), bulk("news"), Frame::Integer(1)])));
+    conn
+}
+
+#[tokio::test]
+async fn ping_while_subscribed() {
+    let server = start(server::Builder::new()).await;
+    let mut conn = subscribed(&server).await;
+
+    let reply = call(&mut conn, &["ping"]).await;
+    assert_eq!(reply, Some(Frame::Array(vec![bulk("pong"), bulk("")])));
+    let reply = call(&mut conn, &["ping", "hello"]).await;
+    assert_eq!(reply, Some(Frame::Array(vec![bulk("pong"), bulk("hello")])));
+
+    // other commands are refused, the connection stays subscribed
+    assert!(matches!(call(&mut conn, &["get", "key"]).await, Some(Frame::Error(_))));
+    let mut publisher = client::connect(server.local_addr()).await.unwrap();
+    assert_eq!(publisher.publish("news", Bytes::from("still")).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn reset_leaves_subscribe_mode() {
+    let server = start(server::Builder::new()).await;
+    let mut conn = subscribed(&server).await;
+
+    assert_eq!(call(&mut conn, &["reset"]).await, Some(Frame::Simple("RESET".to_string())));
+
+    // back to a regular connection, without subscriptions
+    assert_eq!(call(&mut conn, &["ping"]).await, Some(Frame::Simple("PONG".to_string())));
+    assert_eq!(call(&mut conn, &["get", "key"]).await, Some(Frame::Null));
+    let mut publisher = client::connect(server.local_addr()).await.unwrap();
+    assert_eq!(publisher.publish("news", Bytes::from("gone")).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn quit_while_subscribed() {
+    let server = start(server::Builder::new()).await;
+    let mut conn = subscribed(&server).await;
+
+    assert_eq!(call(&mut conn, &["quit"]).await, Some(Frame::Simple("OK".to_string())));
+    assert_eq!(conn.read_frame().await.unwrap(), None);
+}
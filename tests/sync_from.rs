@@ -0,0 +1,128 @@
+use redust::{client, server, Connection, Frame, Store};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpSocket};
+use tokio::time;
+
+async fn start(store: Store) -> server::Server {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    server::Builder::new().store(store).start(listener).unwrap()
+}
+
+async fn sync_from(server: &server::Server, seq: &'static str) -> Connection {
+    // a small receive buffer, so a consumer which doesn't read quickly holds up the server
+    let socket = TcpSocket::new_v4().unwrap();
+    socket.set_recv_buffer_size(4096).unwrap();
+    let mut consumer = Connection::new(socket.connect(server.local_addr()).await.unwrap());
+    let cmd = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"syncfrom")),
+        Frame::Bulk(seq.into()),
+    ]);
+    consumer.write_frame(&cmd).await.unwrap();
+    consumer
+}
+
+fn record_seq(frame: Frame) -> u64 {
+    match frame {
+        Frame::Array(parts) => match parts[0] {
+            Frame::Integer(seq) => seq as u64,
+            _ => panic!("unexpected record {:?}", parts),
+        },
+        frame => panic!("unexpected frame {:?}", frame),
+    }
+}
+
+#[tokio::test]
+async fn sync_from_zero_starts_at_the_oldest_record() {
+    let server = start(Store::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    for key in ["a", "b", "c"] {
+        client.set(key, "value").await.unwrap();
+    }
+
+    let mut consumer = sync_from(&server, "0").await;
+    for seq in 1..=3 {
+        assert_eq!(record_seq(consumer.read_frame().await.unwrap().unwrap()), seq);
+    }
+}
+
+#[tokio::test]
+async fn lagging_consumer_is_disconnected() {
+    let store = Store::new();
+    let server = start(store.clone()).await;
+    let mut consumer = sync_from(&server, "0").await;
+    // let the subscription start before writing
+    client::connect(server.local_addr()).await.unwrap().ping(None).await.unwrap();
+
+    // far more records than the consumer queue and the socket buffers hold, none is read yet
+    for i in 0..300_000 {
+        store.set(&format!("key-{}", i), Bytes::from(vec![b'v'; 64])).unwrap();
+        if i % 10_000 == 0 {
+            // let the server forward the records
+            time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    let mut last = 0;
+    loop {
+        match consumer.read_frame().await.unwrap() {
+            Some(Frame::Error(msg)) => {
+                let expected = format!("ERR consumer fell behind the writes, resume with SYNCFROM {}", last + 1);
+                assert_eq!(msg, expected);
+                break;
+            }
+            Some(frame) => last = record_seq(frame),
+            None => panic!("closed without an error"),
+        }
+    }
+    assert!(last < 300_000);
+    assert_eq!(consumer.read_frame().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn audited_reads_are_recorded_with_the_writes() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server = server::Builder::new()
+        .audit_reads(vec!["secret:*".to_string()], 1)
+        .start(listener)
+        .unwrap();
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("secret:a b", "value").await.unwrap();
+    client.get::<Option<Bytes>>("secret:a b").await.unwrap();
+    client.get::<Option<Bytes>>("public").await.unwrap();
+    client.get::<Option<Bytes>>("secret:missing").await.unwrap();
+    client.get_set("secret:a b", "other".into()).await.unwrap();
+    client.get_bit("secret:a b", 3).await.unwrap();
+    client.bit_count("secret:a b").await.unwrap();
+    client.blog_read("secret:log", 0, 10).await.unwrap();
+
+    let mut consumer = sync_from(&server, "0").await;
+    let mut reads = vec![];
+    for _ in 0..8 {
+        let parts = match consumer.read_frame().await.unwrap().unwrap() {
+            Frame::Array(parts) => parts,
+            frame => panic!("unexpected frame {:?}", frame),
+        };
+        if parts[1] == "read" {
+            assert_eq!(parts.len(), 7);
+            assert_eq!(parts[5], "default");
+            reads.push((parts[2].clone(), parts[3].clone()));
+        }
+    }
+
+    let expected = [
+        ("secret:a b", "get"),
+        ("secret:missing", "get"),
+        ("secret:a b", "getset"),
+        ("secret:a b", "getbit"),
+        ("secret:a b", "bitcount"),
+        ("secret:log", "blog.read"),
+    ];
+    assert_eq!(reads.len(), expected.len());
+    for ((key, command), (expected_key, expected_command)) in reads.iter().zip(expected) {
+        assert_eq!(*key, expected_key);
+        assert_eq!(*command, expected_command);
+    }
+}
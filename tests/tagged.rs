@@ -0,0 +1,89 @@
+use redust::{client, server, Connection, Frame};
+
+use bytes::Bytes;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use tokio::net::{TcpListener, TcpStream};
+
+fn bulk(s: &'static str) -> Frame {
+    Frame::Bulk(Bytes::from_static(s.as_bytes()))
+}
+
+/// Open a connection to `addr` switched to tagged framing
+async fn tagged(addr: std::net::SocketAddr) -> Connection {
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let hello = Frame::Array(vec![bulk("hello"), bulk("2"), bulk("tagged")]);
+    connection.write_frame(&hello).await.unwrap();
+    assert!(matches!(connection.read_frame().await.unwrap(), Some(Frame::Array(_))));
+    connection
+}
+
+#[tokio::test]
+async fn tagged_requests_in_flight_are_bounded() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server = server::Builder::new().enable_debug_command(true).start(listener).unwrap();
+    let mut connection = tagged(server.local_addr()).await;
+
+    // one more than the server runs at once, the last one waits for a slot
+    let start = Instant::now();
+    let requests = 129;
+    for tag in 0..requests {
+        let sleep = Frame::Array(vec![bulk("debug"), bulk("sleep"), bulk("0.3")]);
+        connection.write_frame(&Frame::Array(vec![Frame::Integer(tag), sleep])).await.unwrap();
+    }
+
+    let mut tags = HashSet::new();
+    for _ in 0..requests {
+        match connection.read_frame().await.unwrap() {
+            Some(Frame::Array(parts)) => match &parts[..] {
+                [Frame::Integer(tag), Frame::Simple(ok)] if ok == "OK" => assert!(tags.insert(*tag)),
+                parts => panic!("unexpected reply {:?}", parts),
+            },
+            frame => panic!("unexpected frame {:?}", frame),
+        }
+    }
+    assert_eq!(tags.len(), requests as usize);
+    assert!(start.elapsed() >= Duration::from_millis(600));
+}
+
+#[tokio::test]
+async fn blocking_pop_refused() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server = server::Builder::new().start(listener).unwrap();
+    let mut connection = tagged(server.local_addr()).await;
+
+    let blpop = Frame::Array(vec![bulk("blpop"), bulk("queue"), bulk("0")]);
+    connection.write_frame(&Frame::Array(vec![Frame::Integer(1), blpop])).await.unwrap();
+    match connection.read_frame().await.unwrap() {
+        Some(Frame::Array(parts)) => match &parts[..] {
+            [Frame::Integer(1), Frame::Error(err)] => assert!(err.contains("tagged connection")),
+            parts => panic!("unexpected reply {:?}", parts),
+        },
+        frame => panic!("unexpected frame {:?}", frame),
+    }
+
+    // the client going away leaves no waiter behind to take the element pushed next
+    drop(connection);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.lpush("queue", vec![Bytes::from("kept")]).await.unwrap();
+    assert_eq!(client.lrange("queue", 0, -1).await.unwrap(), [Bytes::from("kept")]);
+}
+
+#[tokio::test]
+async fn reset_refused() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server = server::Builder::new().start(listener).unwrap();
+    let mut connection = tagged(server.local_addr()).await;
+
+    // it would only reset the state of the connection the request runs on
+    let reset = Frame::Array(vec![bulk("reset")]);
+    connection.write_frame(&Frame::Array(vec![Frame::Integer(1), reset])).await.unwrap();
+    match connection.read_frame().await.unwrap() {
+        Some(Frame::Array(parts)) => match &parts[..] {
+            [Frame::Integer(1), Frame::Error(err)] => assert!(err.contains("tagged connection")),
+            parts => panic!("unexpected reply {:?}", parts),
+        },
+        frame => panic!("unexpected frame {:?}", frame),
+    }
+}
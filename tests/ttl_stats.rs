@@ -0,0 +1,43 @@
+use redust::{client, server, Frame};
+
+use std::time::Duration;
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn histogram_and_forecast() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    client.set("persistent", "value").await.unwrap();
+    for (key, secs) in [("a", 5), ("b", 30), ("c", 90), ("d", 150), ("e", 2 * 60 * 60)] {
+        client.set_expires(key, "value", Duration::from_secs(secs)).await.unwrap();
+    }
+
+    let stats = client.ttl_stats(3).await.unwrap();
+    assert_eq!((stats.keys_with_ttl, stats.keys_without_ttl), (5, 1));
+    let histogram: Vec<_> = stats.histogram.iter().map(|(label, n)| (&label[..], *n)).collect();
+    assert_eq!(
+        histogram,
+        [("1s", 0), ("10s", 1), ("1m", 1), ("10m", 2), ("1h", 0), ("1d", 1), ("inf", 0)]
+    );
+    // the key expiring in two hours is past the forecast
+    assert_eq!(stats.forecast, [2, 1, 1]);
+}
+
+#[tokio::test]
+async fn forecast_length() {
+    let server = start(server::Builder::new()).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+
+    // 10 minutes unless told otherwise
+    let reply: Vec<Frame> = client.command(("ttlstats",)).await.unwrap();
+    assert_eq!(reply[6], "forecast");
+    assert_eq!(reply[7], Frame::Array(vec![Frame::Integer(0); 10]));
+    assert_eq!(client.ttl_stats(0).await.unwrap().forecast, Vec::<u64>::new());
+    assert_eq!(client.ttl_stats(24 * 60).await.unwrap().forecast.len(), 24 * 60);
+
+    let err = client.ttl_stats(24 * 60 + 1).await.unwrap_err();
+    assert_eq!(err.to_string(), "ERR forecast is limited to 1440 minutes");
+}
@@ -0,0 +1,55 @@
+#![cfg(unix)]
+
+use redust::{client, server};
+
+use bytes::Bytes;
+use std::process::Command;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+mod common;
+use common::start;
+
+// The only test of this binary: SIGUSR2 is delivered to the whole process
+#[tokio::test]
+async fn key_space_kept_across_restarts() {
+    let path = std::env::temp_dir().join(format!("redust-warm-{}.snapshot", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let server = start(server::Builder::new().warm_restart(&path)).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    client.set("string", "value").await.unwrap();
+    client.set_expires("expiring", "value", Duration::from_secs(60)).await.unwrap();
+    client.set_expires("short", "value", Duration::from_millis(200)).await.unwrap();
+    client.rpush("list", vec![Bytes::from("a"), Bytes::from("b")]).await.unwrap();
+    client.hset("hash", vec![(Bytes::from("f"), Bytes::from("v"))]).await.unwrap();
+    client.zadd("zset", vec![(2.5, Bytes::from("m"))]).await.unwrap();
+    let before = client.get_entry("string").await.unwrap().unwrap();
+    drop(client);
+
+    // the server drains its connections, writes the snapshot then completes
+    let pid = std::process::id().to_string();
+    assert!(Command::new("kill").args(["-USR2", &pid]).status().unwrap().success());
+    timeout(Duration::from_secs(2), server.join()).await.unwrap().unwrap();
+    assert!(path.exists());
+
+    sleep(Duration::from_millis(300)).await;
+    let server = start(server::Builder::new().warm_restart(&path)).await;
+    let mut client = client::connect(server.local_addr()).await.unwrap();
+    let after = client.get_entry("string").await.unwrap().unwrap();
+    // loaded before serving the connections, once only
+    assert!(!path.exists());
+    assert_eq!((after.value, after.version), (Bytes::from("value"), before.version));
+    let ttl = client.get_entry("expiring").await.unwrap().unwrap().ttl.unwrap();
+    assert!(ttl > Duration::from_secs(55) && ttl <= Duration::from_secs(60), "{:?}", ttl);
+    assert_eq!(client.lrange("list", 0, -1).await.unwrap(), [Bytes::from("a"), Bytes::from("b")]);
+    assert_eq!(client.hget("hash", Bytes::from("f")).await.unwrap(), Some(Bytes::from("v")));
+    assert_eq!(client.zscore("zset", Bytes::from("m")).await.unwrap(), Some(2.5));
+    // expired while the server was down
+    assert_eq!(client.get::<Option<Bytes>>("short").await.unwrap(), None);
+
+    // the sequence of writes goes on where it stopped
+    client.set("string", "new").await.unwrap();
+    let updated = client.get_entry("string").await.unwrap().unwrap();
+    assert!(updated.version > before.version);
+}
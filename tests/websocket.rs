@@ -0,0 +1,38 @@
+#![cfg(feature = "websocket")]
+
+use redust::server;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::Message;
+
+mod common;
+use common::start;
+
+#[tokio::test]
+async fn commands_over_a_websocket() {
+    let websocket = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = websocket.local_addr().unwrap();
+    let _server = start(server::Builder::new().websocket(websocket)).await;
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let url = format!("ws://{}/", addr);
+    let (mut socket, _) = tokio_tungstenite::client_async(url, stream).await.unwrap();
+
+    // a frame split across messages, then two frames in one message
+    socket.send(Message::Binary(b"*3\r\n$3\r\nSET\r\n$3\r\nkey".to_vec())).await.unwrap();
+    socket.send(Message::Binary(b"\r\n$5\r\nvalue\r\n".to_vec())).await.unwrap();
+    let get = b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n*1\r\n$4\r\nPING\r\n".to_vec();
+    socket.send(Message::Binary(get)).await.unwrap();
+
+    // the replies may be split across messages too
+    let expected = b"+OK\r\n$5\r\nvalue\r\n+PONG\r\n";
+    let mut replies = vec![];
+    while replies.len() < expected.len() {
+        match socket.next().await.unwrap().unwrap() {
+            Message::Binary(data) => replies.extend(data),
+            message => panic!("unexpected message {:?}", message),
+        }
+    }
+    assert_eq!(replies, expected);
+}
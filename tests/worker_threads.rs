@@ -0,0 +1,78 @@
+use redust::{client, server};
+
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+mod common;
+use common::start;
+
+/// Reads the number of shards from the `INFO memory` report of `addr`
+async fn shards(addr: impl tokio::net::ToSocketAddrs) -> usize {
+    let mut client = client::connect(addr).await.unwrap();
+    let info = client.info(Some("memory")).await.unwrap();
+    info.lines()
+        .find_map(|line| line.strip_prefix("keyspace_shards:"))
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn shards_follow_the_worker_threads() {
+    let server = start(server::Builder::new().worker_threads(8)).await;
+    assert_eq!(shards(server.local_addr()).await, 8 * server::SHARDS_PER_WORKER);
+
+    // never fewer than the default
+    let server = start(server::Builder::new().worker_threads(1)).await;
+    assert_eq!(shards(server.local_addr()).await, 16);
+
+    // the shard count given wins
+    let server = start(server::Builder::new().worker_threads(8).shards(3)).await;
+    assert_eq!(shards(server.local_addr()).await, 3);
+}
+
+/// The server process, killed on drop
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+fn server_command(threads: &str, port: u16) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_redust-server"));
+    command
+        .args(["--port", &port.to_string(), "--threads", threads])
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    command
+}
+
+#[tokio::test]
+async fn threads_option_sizes_the_server() {
+    let port = free_port();
+    let _server = Server(server_command("10", port).spawn().unwrap());
+
+    for _ in 0..100 {
+        if tokio::net::TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            assert_eq!(shards(("127.0.0.1", port)).await, 10 * server::SHARDS_PER_WORKER);
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("the server didn't start");
+}
+
+#[test]
+fn zero_threads_rejected() {
+    let output = server_command("0", free_port()).output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--threads must be at least 1"), "{}", stderr);
+}